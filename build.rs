@@ -10,8 +10,38 @@ fn main() {
     if target_os == "windows" {
         #[cfg(feature = "bindgen")]
         build_wrapper_wintun();
+        #[cfg(feature = "embed-wintun")]
+        embed_wintun();
     }
 }
+/// Copies the `wintun.dll` of the target architecture from `WINTUN_DIR` (the extracted
+/// zip from wintun.net) to `OUT_DIR`, where `include_bytes!` picks it up.
+#[cfg(feature = "embed-wintun")]
+fn embed_wintun() {
+    use std::env;
+    use std::path::PathBuf;
+    println!("cargo:rerun-if-env-changed=WINTUN_DIR");
+    let arch = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "x86_64" => "amd64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        arch => panic!("no wintun.dll for {arch}"),
+    };
+    let dir = PathBuf::from(
+        env::var("WINTUN_DIR").expect("embed-wintun requires WINTUN_DIR to point at wintun"),
+    );
+    let dll = [
+        dir.join("bin").join(arch).join("wintun.dll"),
+        dir.join("wintun").join("bin").join(arch).join("wintun.dll"),
+    ]
+    .into_iter()
+    .find(|path| path.exists())
+    .unwrap_or_else(|| panic!("bin/{arch}/wintun.dll not found in {}", dir.display()));
+    println!("cargo:rerun-if-changed={}", dll.display());
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    std::fs::copy(&dll, out_path.join("wintun.dll")).expect("Couldn't copy wintun.dll");
+}
 #[cfg(feature = "bindgen")]
 fn build_wrapper_wintun() {
     use std::env;
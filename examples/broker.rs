@@ -0,0 +1,54 @@
+//! A privileged device broker.
+//!
+//! Run it as root, then build devices from unprivileged processes with
+//! `DeviceBuilder::via_broker`:
+//!
+//! ```text
+//! sudo cargo run --example broker --features broker -- /run/tun-broker.sock 1000
+//! ```
+//!
+//! Only the user whose id is given as the second argument may request devices.
+
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+fn main() -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .unwrap_or_else(|| "/run/tun-broker.sock".to_string());
+    let uid: u32 = args
+        .next()
+        .and_then(|uid| uid.parse().ok())
+        .expect("usage: broker <socket path> <allowed uid>");
+
+    _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    // The uid check below does the access control.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666))?;
+    log::info!("serving devices for uid {uid} on {path}");
+    tun_rs::broker::serve(&listener, |peer, request| {
+        let allowed = peer.uid == uid;
+        log::info!("request from {peer:?}: {request:?}, allowed: {allowed}");
+        allowed
+    })
+}
+
+#[cfg(not(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+)))]
+fn main() {
+    println!("the broker is only available on Unix")
+}
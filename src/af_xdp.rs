@@ -0,0 +1,784 @@
+/*!
+# AF_XDP Sockets
+
+[`XdpSocket`] receives and sends packets on a network interface through an AF_XDP socket.
+Packets are exchanged with the kernel through a shared memory region (the UMEM) and four
+lock-free rings, without a system call per packet; with `zero_copy(true)` and a driver that
+supports it, the NIC DMAs straight into the UMEM.
+
+The socket loads a minimal XDP program that redirects every packet of its queue to the
+socket and attaches it to the interface through a BPF link, which is detached again when the
+socket is dropped. This needs `CAP_NET_ADMIN` and `CAP_BPF` (or root) and Linux 5.9 or later.
+Packets that arrive on other queues are passed to the network stack as usual.
+
+# Topology
+
+XDP runs on the receive path of an interface. For a TUN/TAP interface, that is the packets
+*written* to its file descriptor, and its transmit path delivers to the reader of the
+descriptor, i.e. the opposite direction of what an application serving the device sees. The
+intended use is therefore a veth pair (optionally bridged with TAP devices): the application
+binds an `XdpSocket` to one end and the other end is used like the TAP device would be.
+
+```text
+  kernel / bridge  <-->  veth0  <-->  veth1  <-->  XdpSocket (application)
+```
+
+# Example
+
+```no_run
+use tun_rs::af_xdp::{XdpMode, XdpSocketBuilder};
+
+// ip link add veth0 type veth peer name veth1 && ip link set veth0 up && ip link set veth1 up
+let mut sock = XdpSocketBuilder::new("veth1")
+    .queue_id(0)
+    .mode(XdpMode::Generic)
+    .build()?;
+let mut bufs = vec![vec![0u8; 2048]; 64];
+let mut sizes = vec![0; 64];
+loop {
+    let n = sock.recv_batch(&mut bufs, &mut sizes)?;
+    let frames: Vec<&[u8]> = (0..n).map(|i| &bufs[i][..sizes[i]]).collect();
+    // ... handle the frames, here they are reflected
+    sock.send_batch(&frames)?;
+}
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use std::ffi::CString;
+use std::io;
+use std::mem::size_of;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const BPF_MAP_CREATE: libc::c_int = 0;
+const BPF_MAP_UPDATE_ELEM: libc::c_int = 2;
+const BPF_PROG_LOAD: libc::c_int = 5;
+const BPF_LINK_CREATE: libc::c_int = 28;
+const BPF_MAP_TYPE_XSKMAP: u32 = 17;
+const BPF_PROG_TYPE_XDP: u32 = 6;
+const BPF_XDP: u32 = 37;
+const XDP_FLAGS_SKB_MODE: u32 = 1 << 1;
+const XDP_FLAGS_DRV_MODE: u32 = 1 << 2;
+const XDP_PASS: i32 = 2;
+const BPF_FUNC_REDIRECT_MAP: i32 = 51;
+const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+/// How the XDP program is attached to the interface.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum XdpMode {
+    /// Native if the driver supports it, generic otherwise.
+    #[default]
+    Auto,
+    /// Generic (SKB) mode, available on every interface but without zero-copy.
+    Generic,
+    /// Native (driver) mode; fails if the driver does not support XDP.
+    Native,
+}
+
+impl XdpMode {
+    fn link_flags(self) -> u32 {
+        match self {
+            XdpMode::Auto => 0,
+            XdpMode::Generic => XDP_FLAGS_SKB_MODE,
+            XdpMode::Native => XDP_FLAGS_DRV_MODE,
+        }
+    }
+}
+
+/// Builder of an [`XdpSocket`].
+#[derive(Clone, Debug)]
+pub struct XdpSocketBuilder {
+    interface: String,
+    queue_id: u32,
+    frame_count: u32,
+    frame_size: u32,
+    ring_size: u32,
+    mode: XdpMode,
+    zero_copy: bool,
+}
+
+impl XdpSocketBuilder {
+    /// Creates a builder for a socket bound to `interface`.
+    pub fn new(interface: &str) -> Self {
+        Self {
+            interface: interface.to_string(),
+            queue_id: 0,
+            frame_count: 4096,
+            frame_size: 2048,
+            ring_size: 2048,
+            mode: XdpMode::Auto,
+            zero_copy: false,
+        }
+    }
+    /// Sets the receive queue of the interface to bind to; defaults to 0.
+    pub fn queue_id(mut self, queue_id: u32) -> Self {
+        self.queue_id = queue_id;
+        self
+    }
+    /// Sets the number of frames in the UMEM; defaults to 4096.
+    ///
+    /// Half of the frames are used for receiving and half for sending.
+    pub fn frame_count(mut self, frame_count: u32) -> Self {
+        self.frame_count = frame_count;
+        self
+    }
+    /// Sets the size of a UMEM frame, a power of two between 2048 and the page size; defaults
+    /// to 2048. Packets larger than a frame can be neither received nor sent.
+    pub fn frame_size(mut self, frame_size: u32) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+    /// Sets the number of entries of each ring, a power of two; defaults to 2048.
+    pub fn ring_size(mut self, ring_size: u32) -> Self {
+        self.ring_size = ring_size;
+        self
+    }
+    /// Sets how the XDP program is attached; defaults to [`XdpMode::Auto`].
+    pub fn mode(mut self, mode: XdpMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Requires zero-copy mode, which fails unless the driver supports it in native mode.
+    /// Otherwise the kernel copies packets into the UMEM.
+    pub fn zero_copy(mut self, zero_copy: bool) -> Self {
+        self.zero_copy = zero_copy;
+        self
+    }
+    /// Creates the socket and attaches the XDP program.
+    pub fn build(self) -> io::Result<XdpSocket> {
+        XdpSocket::new(self)
+    }
+}
+
+struct Mmap {
+    addr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(len: usize, fd: Option<RawFd>, offset: libc::off_t) -> io::Result<Self> {
+        let (flags, fd) = match fd {
+            Some(fd) => (libc::MAP_SHARED | libc::MAP_POPULATE, fd),
+            None => (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1),
+        };
+        // SAFETY: a new mapping is created; no existing memory is affected.
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                fd,
+                offset,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { addr, len })
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `Mmap::new` and is not referenced any more.
+        unsafe { libc::munmap(self.addr, self.len) };
+    }
+}
+
+/// A single-producer single-consumer ring shared with the kernel.
+struct Ring<T> {
+    producer: *const AtomicU32,
+    consumer: *const AtomicU32,
+    flags: *const AtomicU32,
+    descs: *mut T,
+    mask: u32,
+    _mmap: Mmap,
+}
+
+impl<T> Ring<T> {
+    fn new(
+        fd: RawFd,
+        offsets: &libc::xdp_ring_offset,
+        size: u32,
+        pgoff: libc::off_t,
+    ) -> io::Result<Self> {
+        let len = offsets.desc as usize + size as usize * size_of::<T>();
+        let mmap = Mmap::new(len, Some(fd), pgoff)?;
+        let base = mmap.addr.cast::<u8>();
+        // SAFETY: the offsets reported by the kernel lie within the mapping.
+        unsafe {
+            Ok(Self {
+                producer: base.add(offsets.producer as usize).cast(),
+                consumer: base.add(offsets.consumer as usize).cast(),
+                flags: base.add(offsets.flags as usize).cast(),
+                descs: base.add(offsets.desc as usize).cast(),
+                mask: size - 1,
+                _mmap: mmap,
+            })
+        }
+    }
+    fn producer(&self) -> &AtomicU32 {
+        // SAFETY: points into the live mapping and is only accessed atomically.
+        unsafe { &*self.producer }
+    }
+    fn consumer(&self) -> &AtomicU32 {
+        // SAFETY: as above.
+        unsafe { &*self.consumer }
+    }
+    fn needs_wakeup(&self) -> bool {
+        // SAFETY: as above.
+        unsafe { &*self.flags }.load(Ordering::Relaxed) & libc::XDP_RING_NEED_WAKEUP != 0
+    }
+    /// Number of entries the kernel has produced and we have not consumed yet.
+    fn available(&self) -> u32 {
+        let producer = self.producer().load(Ordering::Acquire);
+        producer.wrapping_sub(self.consumer().load(Ordering::Relaxed))
+    }
+    /// Number of entries we can produce without overwriting unconsumed ones.
+    fn free(&self) -> u32 {
+        let consumer = self.consumer().load(Ordering::Acquire);
+        (self.mask + 1)
+            - self
+                .producer()
+                .load(Ordering::Relaxed)
+                .wrapping_sub(consumer)
+    }
+    fn read(&self, index: u32) -> T
+    where
+        T: Copy,
+    {
+        // SAFETY: the index is masked into the ring.
+        unsafe { self.descs.add((index & self.mask) as usize).read() }
+    }
+    fn write(&self, index: u32, value: T) {
+        // SAFETY: the index is masked into the ring.
+        unsafe { self.descs.add((index & self.mask) as usize).write(value) }
+    }
+    /// Consumes the next `n` entries.
+    fn consume(&self, n: u32) -> impl Iterator<Item = T> + '_
+    where
+        T: Copy,
+    {
+        let start = self.consumer().load(Ordering::Relaxed);
+        (0..n).map(move |i| self.read(start.wrapping_add(i)))
+    }
+    fn release(&self, n: u32) {
+        let consumer = self.consumer().load(Ordering::Relaxed);
+        self.consumer()
+            .store(consumer.wrapping_add(n), Ordering::Release);
+    }
+    /// Produces `values`, which must not exceed [`free`](Self::free).
+    fn produce(&self, values: impl IntoIterator<Item = T>) {
+        let mut producer = self.producer().load(Ordering::Relaxed);
+        for value in values {
+            self.write(producer, value);
+            producer = producer.wrapping_add(1);
+        }
+        self.producer().store(producer, Ordering::Release);
+    }
+}
+
+/// An AF_XDP socket bound to one queue of a network interface.
+///
+/// Created with [`XdpSocketBuilder`]. Dropping it detaches the XDP program.
+pub struct XdpSocket {
+    fill: Ring<u64>,
+    completion: Ring<u64>,
+    rx: Ring<libc::xdp_desc>,
+    tx: Ring<libc::xdp_desc>,
+    umem: Mmap,
+    frame_size: u32,
+    free_frames: Vec<u64>,
+    nonblocking: bool,
+    _link: OwnedFd,
+    _prog: OwnedFd,
+    _map: OwnedFd,
+    fd: OwnedFd,
+}
+
+// SAFETY: the raw pointers refer to mappings owned by the socket, which is only accessed
+// through `&mut self`.
+unsafe impl Send for XdpSocket {}
+
+impl XdpSocket {
+    fn new(builder: XdpSocketBuilder) -> io::Result<Self> {
+        let XdpSocketBuilder {
+            interface,
+            queue_id,
+            frame_count,
+            frame_size,
+            ring_size,
+            mode,
+            zero_copy,
+        } = builder;
+        if !ring_size.is_power_of_two() || frame_count < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the ring size must be a power of two and there must be at least two frames",
+            ));
+        }
+        let name = CString::new(interface)?;
+        // SAFETY: `name` is a valid C string.
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: plain socket creation.
+        let fd = unsafe { libc::socket(libc::AF_XDP, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just created and is owned by nothing else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw = fd.as_raw_fd();
+
+        let umem = Mmap::new(frame_count as usize * frame_size as usize, None, 0)?;
+        let reg = libc::xdp_umem_reg {
+            addr: umem.addr as u64,
+            len: umem.len as u64,
+            chunk_size: frame_size,
+            headroom: 0,
+            flags: 0,
+            tx_metadata_len: 0,
+        };
+        set_option(raw, libc::XDP_UMEM_REG, &reg)?;
+        for option in [
+            libc::XDP_UMEM_FILL_RING,
+            libc::XDP_UMEM_COMPLETION_RING,
+            libc::XDP_RX_RING,
+            libc::XDP_TX_RING,
+        ] {
+            set_option(raw, option, &ring_size)?;
+        }
+        let offsets = mmap_offsets(raw)?;
+        let fill = Ring::new(
+            raw,
+            &offsets.fr,
+            ring_size,
+            libc::XDP_UMEM_PGOFF_FILL_RING as _,
+        )?;
+        let completion = Ring::new(
+            raw,
+            &offsets.cr,
+            ring_size,
+            libc::XDP_UMEM_PGOFF_COMPLETION_RING as _,
+        )?;
+        let rx = Ring::new(raw, &offsets.rx, ring_size, libc::XDP_PGOFF_RX_RING)?;
+        let tx = Ring::new(raw, &offsets.tx, ring_size, libc::XDP_PGOFF_TX_RING)?;
+
+        let mut bind_flags = libc::XDP_USE_NEED_WAKEUP;
+        if zero_copy {
+            bind_flags |= libc::XDP_ZEROCOPY;
+        } else if mode == XdpMode::Generic {
+            bind_flags |= libc::XDP_COPY;
+        }
+        let addr = libc::sockaddr_xdp {
+            sxdp_family: libc::AF_XDP as u16,
+            sxdp_flags: bind_flags,
+            sxdp_ifindex: ifindex,
+            sxdp_queue_id: queue_id,
+            sxdp_shared_umem_fd: 0,
+        };
+        // SAFETY: `addr` is a valid `sockaddr_xdp` of the given length.
+        let res = unsafe {
+            libc::bind(
+                raw,
+                (&addr as *const libc::sockaddr_xdp).cast(),
+                size_of::<libc::sockaddr_xdp>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let map = create_xskmap(queue_id + 1)?;
+        let key = queue_id;
+        let value = raw as u32;
+        map_update(&map, &key, &value)?;
+        let prog = load_redirect_program(&map)?;
+        let link = attach_xdp(&prog, ifindex, mode.link_flags())?;
+
+        let frames = (0..frame_count as u64).map(|i| i * frame_size as u64);
+        let rx_frames = (frame_count / 2).min(ring_size);
+        let mut free_frames: Vec<u64> = frames.collect();
+        fill.produce(free_frames.drain(..rx_frames as usize));
+        Ok(Self {
+            fill,
+            completion,
+            rx,
+            tx,
+            umem,
+            frame_size,
+            free_frames,
+            nonblocking: false,
+            _link: link,
+            _prog: prog,
+            _map: map,
+            fd,
+        })
+    }
+    /// Sets whether `recv_batch`, `recv_with` and `send_batch` fail with
+    /// [`io::ErrorKind::WouldBlock`] instead of waiting. Defaults to `false`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+    /// Receives up to `max` packets, passing each as a slice of the UMEM to `f`
+    /// without copying it, and returns their number.
+    ///
+    /// Waits until at least one packet is available unless the socket is nonblocking.
+    pub fn recv_with<F: FnMut(&[u8])>(&mut self, max: usize, mut f: F) -> io::Result<usize> {
+        let n = self.wait_rx(max)?;
+        let umem = self.umem.addr.cast::<u8>();
+        for desc in self.rx.consume(n) {
+            // SAFETY: the kernel hands out descriptors within the UMEM, and the frame is not
+            // reused before it is returned to the fill ring below.
+            let packet =
+                unsafe { std::slice::from_raw_parts(umem.add(desc.addr as usize), desc.len as _) };
+            f(packet);
+        }
+        self.recycle_rx(n);
+        Ok(n as usize)
+    }
+    /// Receives up to `bufs.len()` packets, copying packet `i` into `bufs[i]` and its length
+    /// into `sizes[i]`, and returns their number.
+    ///
+    /// Packets larger than their buffer are truncated. Waits until at least one packet is
+    /// available unless the socket is nonblocking.
+    pub fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        let max = bufs.len().min(sizes.len());
+        let mut i = 0;
+        self.recv_with(max, |packet| {
+            let buf = bufs[i].as_mut();
+            let len = packet.len().min(buf.len());
+            buf[..len].copy_from_slice(&packet[..len]);
+            sizes[i] = len;
+            i += 1;
+        })
+    }
+    /// Sends as many of `packets` as there are free frames and returns their number.
+    ///
+    /// Waits until at least one frame is free unless the socket is nonblocking. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if a packet does not fit into a frame.
+    pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        if packets
+            .iter()
+            .any(|packet| packet.as_ref().len() > self.frame_size as usize)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet larger than the UMEM frame size",
+            ));
+        }
+        if packets.is_empty() {
+            return Ok(0);
+        }
+        let n = loop {
+            self.reclaim_tx();
+            let n = packets
+                .len()
+                .min(self.free_frames.len())
+                .min(self.tx.free() as usize);
+            if n > 0 {
+                break n;
+            }
+            self.kick_tx()?;
+            self.wait(libc::POLLOUT)?;
+        };
+        let umem = self.umem.addr.cast::<u8>();
+        let start = self.free_frames.len() - n;
+        let frames: Vec<u64> = self.free_frames.drain(start..).collect();
+        self.tx
+            .produce(packets.iter().zip(frames).map(|(packet, addr)| {
+                let packet = packet.as_ref();
+                // SAFETY: `addr` is a free frame of the UMEM and the packet fits into it.
+                unsafe {
+                    ptr::copy_nonoverlapping(packet.as_ptr(), umem.add(addr as usize), packet.len())
+                };
+                libc::xdp_desc {
+                    addr,
+                    len: packet.len() as u32,
+                    options: 0,
+                }
+            }));
+        self.kick_tx()?;
+        Ok(n)
+    }
+    fn wait_rx(&mut self, max: usize) -> io::Result<u32> {
+        loop {
+            let n = self.rx.available().min(max as u32);
+            if n > 0 || max == 0 {
+                return Ok(n);
+            }
+            if self.fill.needs_wakeup() {
+                // SAFETY: a zero-length receive only wakes up the kernel.
+                unsafe {
+                    libc::recvfrom(
+                        self.fd.as_raw_fd(),
+                        ptr::null_mut(),
+                        0,
+                        libc::MSG_DONTWAIT,
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                    )
+                };
+            }
+            self.wait(libc::POLLIN)?;
+        }
+    }
+    fn recycle_rx(&mut self, n: u32) {
+        let addrs: Vec<u64> = self.rx.consume(n).map(|desc| desc.addr).collect();
+        self.rx.release(n);
+        // The fill ring has room for every frame that was handed out through the RX ring.
+        self.fill.produce(addrs);
+    }
+    fn reclaim_tx(&mut self) {
+        let n = self.completion.available();
+        self.free_frames.extend(self.completion.consume(n));
+        self.completion.release(n);
+    }
+    fn kick_tx(&self) -> io::Result<()> {
+        if !self.tx.needs_wakeup() {
+            return Ok(());
+        }
+        // SAFETY: a zero-length send only wakes up the kernel.
+        let res = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                ptr::null(),
+                0,
+                libc::MSG_DONTWAIT,
+                ptr::null(),
+                0,
+            )
+        };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EAGAIN | libc::EBUSY | libc::ENOBUFS | libc::ENETDOWN) => {}
+                _ => return Err(err),
+            }
+        }
+        Ok(())
+    }
+    fn wait(&self, events: libc::c_short) -> io::Result<()> {
+        if self.nonblocking {
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        }
+        let mut fds = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events,
+            revents: 0,
+        };
+        // SAFETY: `fds` is a valid pollfd.
+        if unsafe { libc::poll(&mut fds, 1, -1) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsRawFd for XdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for XdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+fn set_option<T>(fd: RawFd, option: libc::c_int, value: &T) -> io::Result<()> {
+    // SAFETY: `value` points to a `T` of the given size.
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_XDP,
+            option,
+            (value as *const T).cast(),
+            size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn mmap_offsets(fd: RawFd) -> io::Result<libc::xdp_mmap_offsets> {
+    // SAFETY: all-zero is a valid `xdp_mmap_offsets`.
+    let mut offsets: libc::xdp_mmap_offsets = unsafe { std::mem::zeroed() };
+    let mut len = size_of::<libc::xdp_mmap_offsets>() as libc::socklen_t;
+    // SAFETY: `offsets` has room for `len` bytes.
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_XDP,
+            libc::XDP_MMAP_OFFSETS,
+            (&mut offsets as *mut libc::xdp_mmap_offsets).cast(),
+            &mut len,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(offsets)
+}
+
+/// Calls `bpf(2)` with an attribute struct whose unused trailing fields are zero.
+fn bpf<T>(cmd: libc::c_int, attr: &T) -> io::Result<libc::c_long> {
+    // SAFETY: `attr` is a valid prefix of `union bpf_attr` for `cmd`.
+    let res = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr as *const T, size_of::<T>()) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res)
+}
+
+fn bpf_fd<T>(cmd: libc::c_int, attr: &T) -> io::Result<OwnedFd> {
+    let fd = bpf(cmd, attr)?;
+    // SAFETY: the command returned a new file descriptor.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+fn create_xskmap(max_entries: u32) -> io::Result<OwnedFd> {
+    #[repr(C)]
+    struct MapCreate {
+        map_type: u32,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+    }
+    bpf_fd(
+        BPF_MAP_CREATE,
+        &MapCreate {
+            map_type: BPF_MAP_TYPE_XSKMAP,
+            key_size: 4,
+            value_size: 4,
+            max_entries,
+        },
+    )
+}
+
+fn map_update(map: &OwnedFd, key: &u32, value: &u32) -> io::Result<()> {
+    #[repr(C)]
+    struct MapUpdate {
+        map_fd: u32,
+        _pad: u32,
+        key: u64,
+        value: u64,
+        flags: u64,
+    }
+    bpf(
+        BPF_MAP_UPDATE_ELEM,
+        &MapUpdate {
+            map_fd: map.as_raw_fd() as u32,
+            _pad: 0,
+            key: key as *const u32 as u64,
+            value: value as *const u32 as u64,
+            flags: 0,
+        },
+    )?;
+    Ok(())
+}
+
+/// An eBPF instruction.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Insn {
+    code: u8,
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+const fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+    Insn {
+        code,
+        regs: dst | (src << 4),
+        off,
+        imm,
+    }
+}
+
+/// Loads the program `return bpf_redirect_map(&xsks, ctx->rx_queue_index, XDP_PASS);`.
+fn load_redirect_program(map: &OwnedFd) -> io::Result<OwnedFd> {
+    let program = [
+        // r2 = *(u32 *)(r1 + offsetof(struct xdp_md, rx_queue_index))
+        insn(0x61, 2, 1, 16, 0),
+        // r1 = map (64-bit immediate spanning two instructions)
+        insn(0x18, 1, BPF_PSEUDO_MAP_FD, 0, map.as_raw_fd()),
+        insn(0, 0, 0, 0, 0),
+        // r3 = XDP_PASS, the action if no socket is bound to the queue
+        insn(0xb7, 3, 0, 0, XDP_PASS),
+        // call bpf_redirect_map
+        insn(0x85, 0, 0, 0, BPF_FUNC_REDIRECT_MAP),
+        // exit
+        insn(0x95, 0, 0, 0, 0),
+    ];
+    let license = c"GPL";
+    #[repr(C)]
+    struct ProgLoad {
+        prog_type: u32,
+        insn_cnt: u32,
+        insns: u64,
+        license: u64,
+        log_level: u32,
+        log_size: u32,
+        log_buf: u64,
+        kern_version: u32,
+        prog_flags: u32,
+        prog_name: [u8; 16],
+        prog_ifindex: u32,
+        expected_attach_type: u32,
+    }
+    let mut prog_name = [0; 16];
+    prog_name[..8].copy_from_slice(b"tun_xsks");
+    bpf_fd(
+        BPF_PROG_LOAD,
+        &ProgLoad {
+            prog_type: BPF_PROG_TYPE_XDP,
+            insn_cnt: program.len() as u32,
+            insns: program.as_ptr() as u64,
+            license: license.as_ptr() as u64,
+            log_level: 0,
+            log_size: 0,
+            log_buf: 0,
+            kern_version: 0,
+            prog_flags: 0,
+            prog_name,
+            prog_ifindex: 0,
+            expected_attach_type: BPF_XDP,
+        },
+    )
+}
+
+fn attach_xdp(prog: &OwnedFd, ifindex: u32, flags: u32) -> io::Result<OwnedFd> {
+    #[repr(C)]
+    struct LinkCreate {
+        prog_fd: u32,
+        target_ifindex: u32,
+        attach_type: u32,
+        flags: u32,
+    }
+    bpf_fd(
+        BPF_LINK_CREATE,
+        &LinkCreate {
+            prog_fd: prog.as_raw_fd() as u32,
+            target_ifindex: ifindex,
+            attach_type: BPF_XDP,
+            flags,
+        },
+    )
+}
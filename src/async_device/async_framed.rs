@@ -1,4 +1,5 @@
 use std::borrow::Borrow;
+use std::collections::VecDeque;
 use std::io;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
@@ -83,6 +84,59 @@ impl<T: Encoder<Item>, Item> Encoder<Item> for &mut T {
     }
 }
 
+/// Read buffer strategy of [`DeviceFramed`] and [`DeviceFramedRead`].
+///
+/// The defaults match [`DeviceFramed::new`]: the buffer is sized from the device MTU,
+/// allocated up front and reused for every read.
+///
+/// # Example
+///
+/// ```no_run
+/// use tun_rs::async_framed::{BytesCodec, DeviceFramed, FramedConfig};
+/// use tun_rs::DeviceBuilder;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .mtu(9000)
+///     .build_async()?;
+/// // Jumbo frames, but do not keep 9 KiB per device around while idle.
+/// let config = FramedConfig {
+///     max_frame_len: Some(9000),
+///     initial_capacity: Some(0),
+///     reuse_buffer: false,
+/// };
+/// let framed = DeviceFramed::with_config(dev, BytesCodec::new(), config);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FramedConfig {
+    /// Largest packet that can be read from the device, in bytes.
+    ///
+    /// `None` uses the MTU of the device. Longer packets are truncated by the OS.
+    pub max_frame_len: Option<usize>,
+    /// Capacity allocated for the read buffer on construction.
+    ///
+    /// `None` pre-allocates `max_frame_len`, `Some(0)` defers the allocation to the first read.
+    pub initial_capacity: Option<usize>,
+    /// Keeps the read buffer between reads.
+    ///
+    /// When `false` the buffer is released after each frame is decoded, trading an
+    /// allocation per packet for no memory being held while idle.
+    pub reuse_buffer: bool,
+}
+
+impl Default for FramedConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_len: None,
+            initial_capacity: None,
+            reuse_buffer: true,
+        }
+    }
+}
+
 /// A unified `Stream` and `Sink` interface over an `AsyncDevice`,
 /// using `Encoder` and `Decoder` traits to frame packets as higher-level messages.
 ///
@@ -147,6 +201,7 @@ impl<T: Encoder<Item>, Item> Encoder<Item> for &mut T {
 pub struct DeviceFramed<C, T = AsyncDevice> {
     dev: T,
     codec: C,
+    config: FramedConfig,
     r_state: ReadState,
     w_state: WriteState,
 }
@@ -195,10 +250,18 @@ where
 {
     /// Construct from a [`AsyncDevice`] with a specific codec
     pub fn new(dev: T, codec: C) -> DeviceFramed<C, T> {
+        Self::with_config(dev, codec, FramedConfig::default())
+    }
+
+    /// Construct from a [`AsyncDevice`] with a specific codec and read buffer strategy.
+    ///
+    /// The write buffer is sized from the MTU as in [`DeviceFramed::new`].
+    pub fn with_config(dev: T, codec: C, config: FramedConfig) -> DeviceFramed<C, T> {
         let buffer_size = compute_buffer_size(&dev);
         DeviceFramed {
-            r_state: ReadState::new(buffer_size, dev.borrow()),
+            r_state: ReadState::new(&config, buffer_size, dev.borrow()),
             w_state: WriteState::new(buffer_size, dev.borrow()),
+            config,
             dev,
             codec,
         }
@@ -222,6 +285,7 @@ where
     ///
     /// Must be at least as large as the MTU to ensure complete packet reception.
     pub fn set_read_buffer_size(&mut self, read_buffer_size: usize) {
+        self.config.max_frame_len = Some(read_buffer_size);
         self.r_state.set_read_buffer_size(read_buffer_size);
     }
     /// Sets the size of the write buffer in bytes.
@@ -281,7 +345,7 @@ where
         let dev = self.dev;
         let codec = self.codec;
         (
-            DeviceFramedRead::new(dev.clone(), codec.clone()),
+            DeviceFramedRead::with_config(dev.clone(), codec.clone(), self.config),
             DeviceFramedWrite::new(dev, codec),
         )
     }
@@ -359,9 +423,14 @@ where
     /// # Note
     /// An efficient way is to directly use [`DeviceFramed::split`] if the device is cloneable
     pub fn new(dev: T, codec: C) -> DeviceFramedRead<C, T> {
+        Self::with_config(dev, codec, FramedConfig::default())
+    }
+
+    /// Construct from a [`AsyncDevice`] with a specific codec and read buffer strategy.
+    pub fn with_config(dev: T, codec: C, config: FramedConfig) -> DeviceFramedRead<C, T> {
         let buffer_size = compute_buffer_size(&dev);
         DeviceFramedRead {
-            state: ReadState::new(buffer_size, dev.borrow()),
+            state: ReadState::new(&config, buffer_size, dev.borrow()),
             dev,
             codec,
         }
@@ -548,6 +617,14 @@ fn compute_buffer_size<T: Borrow<AsyncDevice>>(_dev: &T) -> usize {
     )))]
     let mtu = 4096usize;
 
+    // feth frames carry an Ethernet header on top of the MTU.
+    #[cfg(target_os = "macos")]
+    let mtu = if _dev.borrow().is_tap() {
+        mtu + 14
+    } else {
+        mtu
+    };
+
     #[cfg(windows)]
     {
         let mtu_v6 = _dev.borrow().mtu_v6().map(|m| m as usize).unwrap_or(4096);
@@ -558,12 +635,15 @@ fn compute_buffer_size<T: Borrow<AsyncDevice>>(_dev: &T) -> usize {
 }
 struct ReadState {
     recv_buffer_size: usize,
+    reuse_buffer: bool,
     rd: BytesMut,
     #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
     packet_splitter: Option<PacketSplitter>,
 }
 impl ReadState {
-    pub(crate) fn new(recv_buffer_size: usize, _device: &AsyncDevice) -> ReadState {
+    pub(crate) fn new(config: &FramedConfig, mtu: usize, _device: &AsyncDevice) -> ReadState {
+        let recv_buffer_size = config.max_frame_len.unwrap_or(mtu);
+        let initial_capacity = config.initial_capacity.unwrap_or(recv_buffer_size);
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         let packet_splitter = if _device.tcp_gso() {
             Some(PacketSplitter::new(recv_buffer_size))
@@ -573,7 +653,8 @@ impl ReadState {
 
         ReadState {
             recv_buffer_size,
-            rd: BytesMut::with_capacity(recv_buffer_size),
+            reuse_buffer: config.reuse_buffer,
+            rd: BytesMut::with_capacity(initial_capacity),
             #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
             packet_splitter,
         }
@@ -594,6 +675,8 @@ impl ReadState {
 struct WriteState {
     send_buffer_size: usize,
     wr: BytesMut,
+    /// Packets of a [`BurstCodec`] batch not sent yet, when GSO is not in use.
+    burst: VecDeque<Bytes>,
     #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
     packet_arena: Option<PacketArena>,
 }
@@ -609,6 +692,7 @@ impl WriteState {
         WriteState {
             send_buffer_size,
             wr: BytesMut::new(),
+            burst: VecDeque::new(),
             #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
             packet_arena,
         }
@@ -672,6 +756,219 @@ impl Encoder<BytesMut> for BytesCodec {
     }
 }
 
+/// The buffer goes back to its [`PacketPool`](crate::PacketPool) once the packet is queued.
+impl Encoder<crate::PoolBuf> for BytesCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, data: crate::PoolBuf, buf: &mut BytesMut) -> Result<(), io::Error> {
+        buf.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+/// Codec that receives packets into the buffers of a [`PacketPool`](crate::PacketPool).
+///
+/// Decoded packets are [`Bytes`] backed by a pool buffer (see
+/// [`PoolBuf::freeze`](crate::PoolBuf::freeze)), so they can be cloned and sliced without
+/// copying and the buffer goes back to the pool once the last handle is dropped. A packet
+/// that does not fit into a pool buffer, or arrives while all of them are in use, is copied
+/// to the heap instead. Encoding accepts the same items as [`BytesCodec`].
+///
+/// [`BurstCodec`] batches are not drawn from a pool.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::{SinkExt, StreamExt};
+/// use tun_rs::async_framed::{DeviceFramed, PoolCodec};
+/// use tun_rs::{DeviceBuilder, PacketPool};
+///
+/// #[tokio::main]
+/// async fn main() -> std::io::Result<()> {
+///     let dev = DeviceBuilder::new()
+///         .ipv4("10.0.0.1", 24, None)
+///         .build_async()?;
+///     let pool = PacketPool::new(1024, 1500);
+///     let mut framed = DeviceFramed::new(dev, PoolCodec::new(pool));
+///     while let Some(packet) = framed.next().await {
+///         framed.send(packet?).await?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PoolCodec {
+    pool: crate::PacketPool,
+}
+impl PoolCodec {
+    /// Creates a `PoolCodec` that receives into the buffers of `pool`.
+    pub fn new(pool: crate::PacketPool) -> PoolCodec {
+        PoolCodec { pool }
+    }
+    /// Returns the pool packets are received into.
+    pub fn pool(&self) -> &crate::PacketPool {
+        &self.pool
+    }
+}
+impl Decoder for PoolCodec {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Bytes>, io::Error> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        let packet = match self.pool.get() {
+            Some(mut pooled) if buf.len() <= pooled.capacity() => {
+                pooled.extend_from_slice(buf);
+                pooled.freeze()
+            }
+            _ => Bytes::copy_from_slice(buf),
+        };
+        // The read buffer keeps its allocation for the next packet.
+        buf.clear();
+        Ok(Some(packet))
+    }
+}
+impl<I> Encoder<I> for PoolCodec
+where
+    BytesCodec: Encoder<I, Error = io::Error>,
+{
+    type Error = io::Error;
+
+    fn encode(&mut self, item: I, buf: &mut BytesMut) -> Result<(), io::Error> {
+        BytesCodec::new().encode(item, buf)
+    }
+}
+
+/// Codec that moves packets in bursts instead of one at a time.
+///
+/// With this codec [`DeviceFramed`], [`DeviceFramedRead`] and [`DeviceFramedWrite`] yield
+/// and accept `Vec<Bytes>` batches. A received batch holds the packets available in one
+/// readiness cycle or, with offload enabled on Linux, the segments of one GRO super-packet.
+/// A sent batch is queued as a whole before flushing, so that GSO can coalesce it.
+///
+/// Batches hold at most [`max_burst`](Self::max_burst) packets.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::{SinkExt, StreamExt};
+/// use tun_rs::async_framed::{BurstCodec, DeviceFramed};
+/// use tun_rs::DeviceBuilder;
+///
+/// #[tokio::main]
+/// async fn main() -> std::io::Result<()> {
+///     let dev = DeviceBuilder::new()
+///         .ipv4("10.0.0.1", 24, None)
+///         .build_async()?;
+///     let mut framed = DeviceFramed::new(dev, BurstCodec::new());
+///     while let Some(burst) = framed.next().await {
+///         // Echo every batch back as one batch.
+///         framed.send(burst?).await?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct BurstCodec {
+    max_burst: usize,
+}
+impl BurstCodec {
+    /// Creates a `BurstCodec` with batches of up to 128 packets.
+    pub fn new() -> BurstCodec {
+        BurstCodec::with_max_burst(128)
+    }
+    /// Creates a `BurstCodec` with batches of up to `max_burst` packets, at least one.
+    pub fn with_max_burst(max_burst: usize) -> BurstCodec {
+        BurstCodec {
+            max_burst: max_burst.max(1),
+        }
+    }
+    /// Returns the largest number of packets in a received batch.
+    pub fn max_burst(&self) -> usize {
+        self.max_burst
+    }
+}
+impl Default for BurstCodec {
+    fn default() -> Self {
+        BurstCodec::new()
+    }
+}
+impl<T> Stream for DeviceFramed<BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    type Item = io::Result<Vec<Bytes>>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pin = self.get_mut();
+        DeviceFramedReadInner::new(&pin.dev, &mut pin.codec, &mut pin.r_state).poll_next_burst(cx)
+    }
+}
+impl<T> Stream for DeviceFramedRead<BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    type Item = io::Result<Vec<Bytes>>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pin = self.get_mut();
+        DeviceFramedReadInner::new(&pin.dev, &mut pin.codec, &mut pin.state).poll_next_burst(cx)
+    }
+}
+impl<T> Sink<Vec<Bytes>> for DeviceFramed<BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.w_state).poll_ready_burst(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, burst: Vec<Bytes>) -> io::Result<()> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.w_state)
+            .start_send_burst(burst)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.w_state)
+            .poll_flush_packets(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+impl<T> Sink<Vec<Bytes>> for DeviceFramedWrite<BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.state).poll_ready_burst(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, burst: Vec<Bytes>) -> io::Result<()> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.state)
+            .start_send_burst(burst)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pin = self.get_mut();
+        DeviceFramedWriteInner::new(&pin.dev, &mut pin.codec, &mut pin.state).poll_flush_packets(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 struct PacketSplitter {
     bufs: Vec<BytesMut>,
@@ -728,6 +1025,14 @@ impl PacketSplitter {
             Some(buf)
         }
     }
+    fn take_burst(&mut self, burst: &mut Vec<Bytes>, max_burst: usize) {
+        while burst.len() < max_burst {
+            let Some(buf) = self.next() else {
+                break;
+            };
+            burst.push(buf.split().freeze());
+        }
+    }
     fn set_recv_buffer_size(&mut self, recv_buffer_size: usize) {
         self.recv_buffer_size = recv_buffer_size;
     }
@@ -771,13 +1076,11 @@ impl PacketArena {
         if !self.gro_table.to_write.is_empty() {
             return Ok(());
         }
-        crate::platform::offload::handle_gro(
+        self.gro_table.apply_gro_link(
             &mut self.bufs[..self.offset],
             VIRTIO_NET_HDR_LEN,
-            &mut self.gro_table.tcp_gro_table,
-            &mut self.gro_table.udp_gro_table,
+            dev.link_len(),
             dev.udp_gso,
-            &mut self.gro_table.to_write,
         )
     }
     fn poll_send_bufs(&mut self, cx: &mut Context<'_>, dev: &AsyncDevice) -> Poll<io::Result<()>> {
@@ -827,7 +1130,6 @@ struct DeviceFramedReadInner<'a, C, T = AsyncDevice> {
 impl<'a, C, T> DeviceFramedReadInner<'a, C, T>
 where
     T: Borrow<AsyncDevice>,
-    C: Decoder,
 {
     fn new(
         dev: &'a T,
@@ -837,6 +1139,29 @@ where
         DeviceFramedReadInner { dev, codec, state }
     }
 
+    /// Reads the next packet from the device into the read buffer, replacing its contents.
+    fn poll_read_packet(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        self.state.rd.clear();
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if self.state.packet_splitter.is_some() {
+            self.state.rd.reserve(VIRTIO_NET_HDR_LEN + 65536);
+        }
+        self.state.rd.reserve(self.state.recv_buffer_size);
+        let len = ready!(self.dev.borrow().poll_recv_buf(cx, &mut self.state.rd))?;
+        if len > self.state.rd.len() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "device initialized more bytes than available buffer space",
+            )));
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+impl<'a, C, T> DeviceFramedReadInner<'a, C, T>
+where
+    T: Borrow<AsyncDevice>,
+    C: Decoder,
+{
     fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<C::Item, C::Error>>> {
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         if let Some(packet_splitter) = &mut self.state.packet_splitter {
@@ -847,26 +1172,7 @@ where
             }
         }
 
-        self.state.rd.clear();
-        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
-        if self.state.packet_splitter.is_some() {
-            self.state.rd.reserve(VIRTIO_NET_HDR_LEN + 65536);
-        }
-        self.state.rd.reserve(self.state.recv_buffer_size);
-        let len = {
-            let buf = self.state.rd.chunk_mut();
-            let spare_len = buf.len();
-            let len = ready!(self.dev.borrow().poll_recv_uninit(cx, buf))?;
-            if len > spare_len {
-                let err = io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "device initialized more bytes than available buffer space",
-                );
-                return Poll::Ready(Some(Err(err.into())));
-            }
-            len
-        };
-        unsafe { self.state.rd.advance_mut(len) };
+        ready!(self.poll_read_packet(cx))?;
 
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
         if let Some(packet_splitter) = &mut self.state.packet_splitter {
@@ -878,12 +1184,60 @@ where
             }
             return Poll::Ready(None);
         }
-        if let Some(frame) = self.codec.decode_eof(&mut self.state.rd)? {
+        let frame = self.codec.decode_eof(&mut self.state.rd);
+        if !self.state.reuse_buffer {
+            self.state.rd = BytesMut::new();
+        }
+        if let Some(frame) = frame? {
             return Poll::Ready(Some(Ok(frame)));
         }
         Poll::Ready(None)
     }
 }
+impl<T> DeviceFramedReadInner<'_, BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    fn poll_next_burst(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Vec<Bytes>>>> {
+        let max_burst = self.codec.max_burst;
+        let mut burst = Vec::new();
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if let Some(packet_splitter) = &mut self.state.packet_splitter {
+            packet_splitter.take_burst(&mut burst, max_burst);
+            if !burst.is_empty() {
+                return Poll::Ready(Some(Ok(burst)));
+            }
+        }
+        while burst.len() < max_burst {
+            let len = match self.poll_read_packet(cx) {
+                Poll::Ready(Ok(len)) => len,
+                // Hand over what this readiness cycle produced; a lasting error is
+                // reported by the next poll.
+                Poll::Ready(Err(_)) | Poll::Pending if !burst.is_empty() => break,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            };
+            if len == 0 {
+                break;
+            }
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            if let Some(packet_splitter) = &mut self.state.packet_splitter {
+                // One GRO super-packet is one burst.
+                packet_splitter.handle(self.dev.borrow(), &mut self.state.rd)?;
+                packet_splitter.take_burst(&mut burst, max_burst);
+                break;
+            }
+            burst.push(self.state.rd.split().freeze());
+        }
+        if !self.state.reuse_buffer {
+            self.state.rd = BytesMut::new();
+        }
+        if burst.is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(Ok(burst)))
+    }
+}
 struct DeviceFramedWriteInner<'a, C, T = AsyncDevice> {
     dev: &'a T,
     codec: &'a mut C,
@@ -937,6 +1291,11 @@ where
     where
         C: Encoder<I>,
     {
+        ready!(self.poll_flush_packets(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_packets(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         let dev = self.dev.borrow();
 
         #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
@@ -952,6 +1311,11 @@ where
             self.state.wr.clear();
             rs?;
         }
+        while let Some(packet) = self.state.burst.front() {
+            let rs = ready!(dev.poll_send(cx, packet));
+            self.state.burst.pop_front();
+            rs?;
+        }
         Poll::Ready(Ok(()))
     }
 
@@ -963,3 +1327,32 @@ where
         Poll::Ready(Ok(()))
     }
 }
+impl<T> DeviceFramedWriteInner<'_, BurstCodec, T>
+where
+    T: Borrow<AsyncDevice>,
+{
+    fn poll_ready_burst(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if let Some(packet_arena) = &self.state.packet_arena {
+            if packet_arena.is_idle() {
+                return Poll::Ready(Ok(()));
+            }
+        }
+        self.poll_flush_packets(cx)
+    }
+
+    fn start_send_burst(&mut self, burst: Vec<Bytes>) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if let Some(packet_arena) = &mut self.state.packet_arena {
+            // Queued together so that GSO can coalesce the whole burst on flush.
+            for packet in burst {
+                let buf = packet_arena.get();
+                buf.resize(VIRTIO_NET_HDR_LEN, 0);
+                buf.extend_from_slice(&packet);
+            }
+            return Ok(());
+        }
+        self.state.burst.extend(burst);
+        Ok(())
+    }
+}
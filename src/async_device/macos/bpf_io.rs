@@ -0,0 +1,359 @@
+use crate::platform::macos::{is_no_buffer_space, ENOBUFS_BACKOFF};
+use crate::DeviceImpl;
+use bytes::buf::UninitSlice;
+use std::io;
+use std::io::{IoSlice, IoSliceMut};
+use std::ops::Deref;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ready, Context, Poll};
+
+#[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+use ::async_io::Async;
+#[cfg(feature = "async_tokio")]
+use ::tokio::io::{unix::AsyncFd, Interest};
+
+/// The NDRV socket of a `feth` pair, borrowed from the device that owns it.
+struct NdrvFd(RawFd);
+impl AsRawFd for NdrvFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+#[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+impl std::os::fd::AsFd for NdrvFd {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        unsafe { std::os::fd::BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+/// An async `feth` device, driven by the kqueue reactor of the runtime like a `utun` device.
+///
+/// Packets are read from a BPF fd and written to an NDRV socket, so each direction has its own
+/// registration. BPF fds only support `EVFILT_READ`, so the BPF fd is registered for
+/// readability only. Packets left over from a BPF read are returned before the fd is read
+/// again, and readiness is only cleared once both are exhausted.
+///
+/// A full interface queue makes NDRV sends fail with `ENOBUFS` while the socket stays
+/// writable, so no write event marks the end of it. Such a send leaves write readiness set,
+/// and the next wait for writability backs off for [`ENOBUFS_BACKOFF`] instead.
+pub struct AsyncDevice {
+    no_buffer_space: AtomicBool,
+    // Declared first, so that it is deregistered before `reader` closes the socket.
+    #[cfg(feature = "async_tokio")]
+    writer: AsyncFd<NdrvFd>,
+    #[cfg(feature = "async_tokio")]
+    reader: AsyncFd<DeviceImpl>,
+    #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+    writer: Async<NdrvFd>,
+    #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+    reader: Async<DeviceImpl>,
+}
+
+impl Deref for AsyncDevice {
+    type Target = DeviceImpl;
+    fn deref(&self) -> &Self::Target {
+        self.reader.get_ref()
+    }
+}
+
+#[cfg(feature = "async_tokio")]
+impl AsyncDevice {
+    pub(crate) fn new_dev(device: DeviceImpl) -> io::Result<AsyncDevice> {
+        device.set_nonblocking(true)?;
+        let writer = AsyncFd::with_interest(NdrvFd(device.tun.write_fd()), Interest::WRITABLE)?;
+        let reader = AsyncFd::with_interest(device, Interest::READABLE)?;
+        Ok(AsyncDevice {
+            no_buffer_space: AtomicBool::new(false),
+            writer,
+            reader,
+        })
+    }
+    pub async fn readable(&self) -> io::Result<()> {
+        if !self.pushback.is_empty() {
+            return Ok(());
+        }
+        self.reader.readable().await.map(|_| ())
+    }
+    pub async fn writable(&self) -> io::Result<()> {
+        self.back_off().await;
+        self.writer.writable().await.map(|_| ())
+    }
+    pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.pushback.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        self.reader.poll_read_ready(cx).map_ok(|_| ())
+    }
+    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.poll_back_off(cx) {
+            return Poll::Pending;
+        }
+        self.writer.poll_write_ready(cx).map_ok(|_| ())
+    }
+    fn poll_read_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let mut guard = ready!(self.reader.poll_read_ready(cx))?;
+            if let Ok(rs) = guard.try_io(|reader| f(reader.get_ref())) {
+                return Poll::Ready(rs);
+            }
+        }
+    }
+    fn poll_write_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let mut guard = ready!(self.writer.poll_write_ready(cx))?;
+            match guard.try_io(|_| self.keep_ready(f(self.reader.get_ref()))) {
+                Ok(Ok(Err(e))) if is_no_buffer_space(&e) => {
+                    if self.poll_back_off(cx) {
+                        return Poll::Pending;
+                    }
+                }
+                Ok(rs) => return Poll::Ready(rs.and_then(|rs| rs)),
+                Err(_would_block) => {}
+            }
+        }
+    }
+    fn try_read_io<R>(&self, f: impl FnOnce(&DeviceImpl) -> io::Result<R>) -> io::Result<R> {
+        self.reader.try_io(Interest::READABLE, |device| f(device))
+    }
+    fn try_write_io<R>(&self, f: impl FnOnce(&DeviceImpl) -> io::Result<R>) -> io::Result<R> {
+        self.writer
+            .try_io(Interest::WRITABLE, |_| {
+                self.keep_ready(f(self.reader.get_ref()))
+            })
+            .and_then(|rs| rs)
+    }
+    pub(crate) async fn read_with<R>(
+        &self,
+        mut op: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> io::Result<R> {
+        self.reader
+            .async_io(Interest::READABLE, |device| op(device))
+            .await
+    }
+    async fn write_with<R>(
+        &self,
+        mut op: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> io::Result<R> {
+        loop {
+            let rs = self
+                .writer
+                .async_io(Interest::WRITABLE, |_| {
+                    self.keep_ready(op(self.reader.get_ref()))
+                })
+                .await?;
+            match rs {
+                Err(e) if is_no_buffer_space(&e) => self.back_off().await,
+                rs => return rs,
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+impl AsyncDevice {
+    pub(crate) fn new_dev(device: DeviceImpl) -> io::Result<AsyncDevice> {
+        device.set_nonblocking(true)?;
+        let writer = Async::new(NdrvFd(device.tun.write_fd()))?;
+        let reader = Async::new(device)?;
+        Ok(AsyncDevice {
+            no_buffer_space: AtomicBool::new(false),
+            writer,
+            reader,
+        })
+    }
+    pub async fn readable(&self) -> io::Result<()> {
+        if !self.pushback.is_empty() {
+            return Ok(());
+        }
+        self.reader.readable().await
+    }
+    pub async fn writable(&self) -> io::Result<()> {
+        self.back_off().await;
+        self.writer.writable().await
+    }
+    pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.reader.poll_readable(cx)
+    }
+    pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.poll_back_off(cx) {
+            return Poll::Pending;
+        }
+        self.writer.poll_writable(cx)
+    }
+    fn poll_read_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            match f(self.reader.get_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => return Poll::Ready(rs),
+            }
+            ready!(self.reader.poll_readable(cx))?;
+        }
+    }
+    fn poll_write_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut f: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            match f(self.reader.get_ref()) {
+                Err(e) if is_no_buffer_space(&e) => {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => return Poll::Ready(rs),
+            }
+            ready!(self.writer.poll_writable(cx))?;
+        }
+    }
+    fn try_read_io<R>(&self, f: impl FnOnce(&DeviceImpl) -> io::Result<R>) -> io::Result<R> {
+        f(self.reader.get_ref())
+    }
+    fn try_write_io<R>(&self, f: impl FnOnce(&DeviceImpl) -> io::Result<R>) -> io::Result<R> {
+        self.keep_ready(f(self.reader.get_ref())).and_then(|rs| rs)
+    }
+    pub(crate) async fn read_with<R>(
+        &self,
+        mut op: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> io::Result<R> {
+        self.reader.read_with(|device| op(device)).await
+    }
+    async fn write_with<R>(
+        &self,
+        mut op: impl FnMut(&DeviceImpl) -> io::Result<R>,
+    ) -> io::Result<R> {
+        loop {
+            let rs = self
+                .writer
+                .write_with(|_| self.keep_ready(op(self.reader.get_ref())))
+                .await?;
+            match rs {
+                Err(e) if is_no_buffer_space(&e) => self.back_off().await,
+                rs => return rs,
+            }
+        }
+    }
+}
+
+impl AsyncDevice {
+    /// Passes the result of a send on, with an `ENOBUFS` failure moved into the inner result:
+    /// the reactor must not take it for a `WouldBlock` and wait for a write event.
+    fn keep_ready<R>(&self, rs: io::Result<R>) -> io::Result<io::Result<R>> {
+        match rs {
+            Err(e) if is_no_buffer_space(&e) => {
+                self.no_buffer_space.store(true, Ordering::Relaxed);
+                Ok(Err(e))
+            }
+            rs => rs.map(Ok),
+        }
+    }
+    /// Waits for [`ENOBUFS_BACKOFF`] if the last send found the interface queue full.
+    async fn back_off(&self) {
+        if !self.no_buffer_space.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        #[cfg(feature = "async_tokio")]
+        ::tokio::time::sleep(ENOBUFS_BACKOFF).await;
+        #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+        ::async_io::Timer::after(ENOBUFS_BACKOFF).await;
+    }
+    /// The polling counterpart of [`back_off`](Self::back_off), which has no timer to wait
+    /// on: yields to the executor once instead, returning whether it did.
+    fn poll_back_off(&self, cx: &mut Context<'_>) -> bool {
+        if !self.no_buffer_space.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        cx.waker().wake_by_ref();
+        true
+    }
+    pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.pushback.take(buf) {
+            return Poll::Ready(Ok(len));
+        }
+        self.poll_read_io(cx, |device| device.recv_hooked(buf, |buf| device.recv(buf)))
+    }
+    #[allow(dead_code)]
+    pub(crate) fn poll_recv_uninit(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut UninitSlice,
+    ) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.pushback.take_uninit(buf) {
+            return Poll::Ready(Ok(len));
+        }
+        self.poll_read_io(cx, |device| device.recv_uninit(buf))
+    }
+    pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll_write_io(cx, |device| device.send_hooked(buf, |buf| device.send(buf)))
+    }
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        // A peeked packet went through the hooks when `peek` received it.
+        if let Some(len) = self.pushback.take(buf) {
+            return Ok(len);
+        }
+        self.read_with(|device| device.recv_hooked(buf, |buf| device.recv(buf)))
+            .await
+    }
+    pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(len) = self.pushback.take(buf) {
+            return Ok(len);
+        }
+        self.try_read_io(|device| device.recv_hooked(buf, |buf| device.recv(buf)))
+    }
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        let Some(packet) = self.middleware_outbound(buf) else {
+            return Ok(buf.len());
+        };
+        let len = packet.len();
+        let frame = self.validate_outbound(&packet)?;
+        let buf = &frame[..];
+        crate::async_device::egress_wait(self, buf.len()).await;
+        self.tap_outbound(buf);
+        let result = self.write_with(|device| device.send(buf)).await;
+        self.count_sent(result).map(|n| n.min(len))
+    }
+    pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.try_write_io(|device| device.send_hooked(buf, |buf| device.send(buf)))
+    }
+    pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        if let Some(len) = self.pushback.take_vectored(bufs) {
+            return Ok(len);
+        }
+        self.read_with(|device| {
+            device.recv_vectored_hooked(bufs, |bufs| device.recv_vectored(bufs))
+        })
+        .await
+    }
+    pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        if let Some(len) = self.pushback.take_vectored(bufs) {
+            return Ok(len);
+        }
+        self.try_read_io(|device| {
+            device.recv_vectored_hooked(bufs, |bufs| device.recv_vectored(bufs))
+        })
+    }
+    pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.write_with(|device| {
+            device.send_vectored_hooked(bufs, |bufs| device.send_vectored(bufs))
+        })
+        .await
+    }
+    pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.try_write_io(|device| {
+            device.send_vectored_hooked(bufs, |bufs| device.send_vectored(bufs))
+        })
+    }
+}
@@ -1,5 +1,5 @@
 use crate::async_device::unix;
-use crate::{DeviceImpl, SyncDevice};
+use crate::{DeviceImpl, FdOptions, SyncDevice};
 use bytes::buf::UninitSlice;
 use std::io;
 use std::io::{IoSlice, IoSliceMut};
@@ -7,7 +7,7 @@ use std::ops::Deref;
 use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::task::{Context, Poll};
 
-mod select_io;
+mod bpf_io;
 /// An async Tun/Tap device wrapper around a Tun/Tap device.
 ///
 /// This type does not provide a split method, because this functionality can be achieved by instead wrapping the socket in an Arc.
@@ -57,14 +57,14 @@ impl Deref for AsyncDevice {
 }
 enum AsyncModel {
     Async(unix::AsyncDevice),
-    Select(select_io::AsyncDevice),
+    Bpf(bpf_io::AsyncDevice),
 }
 
 impl AsyncModel {
     fn as_device(&self) -> &DeviceImpl {
         match &self {
             AsyncModel::Async(dev) => dev,
-            AsyncModel::Select(dev) => dev,
+            AsyncModel::Bpf(dev) => dev,
         }
     }
 }
@@ -95,6 +95,39 @@ impl AsyncDevice {
         AsyncDevice::new_dev(DeviceImpl::from_fd(fd)?)
     }
 
+    /// Construct an `AsyncDevice` from an adopted file descriptor, validating it first.
+    ///
+    /// Intended for fds handed over by a system VPN framework (e.g. the `packetFlow`
+    /// socket of an iOS/macOS Network Extension). The fd is checked to be a TUN device
+    /// (a `utun` control socket on Apple platforms, an attached `/dev/net/tun` fd on
+    /// Linux/Android) and is put into non-blocking mode. If the check fails, the fd is
+    /// left open and an error carrying [`Error::InvalidFd`](crate::Error::InvalidFd)
+    /// describing the problem is returned.
+    ///
+    /// # Safety
+    /// The fd must not be owned by anything else; on success, ownership moves to the device.
+    pub unsafe fn from_fd_checked(fd: RawFd) -> io::Result<AsyncDevice> {
+        let device = DeviceImpl::from_fd_checked(fd)?;
+        device.set_nonblocking(true)?;
+        AsyncDevice::new_dev(device)
+    }
+
+    /// Construct an `AsyncDevice` from an existing `utun` file descriptor, as configured by
+    /// `options`.
+    ///
+    /// With [`FdOptions::set_nonblocking`] unset the fd's flags are not touched; it must
+    /// then already be non-blocking, or an error of kind [`io::ErrorKind::InvalidInput`] is
+    /// returned.
+    ///
+    /// # Safety
+    /// The fd must be a valid, open `utun` file descriptor. If [`FdOptions::owned`] is set,
+    /// ownership moves to the device; otherwise the fd must outlive it.
+    pub unsafe fn from_fd_with_options(fd: RawFd, options: FdOptions) -> io::Result<AsyncDevice> {
+        Ok(Self {
+            async_model: AsyncModel::Async(unix::AsyncDevice::from_fd_with_options(fd, options)?),
+        })
+    }
+
     /// # Safety
     /// The fd passed in must be a valid, open file descriptor.
     /// Unlike [`from_fd`], this function does **not** take ownership of `fd`,
@@ -106,7 +139,7 @@ impl AsyncDevice {
     pub fn into_fd(self) -> io::Result<RawFd> {
         match self.async_model {
             AsyncModel::Async(dev) => Ok(dev.into_device()?.into_raw_fd()),
-            AsyncModel::Select(_) => Err(io::Error::new(
+            AsyncModel::Bpf(_) => Err(io::Error::new(
                 io::ErrorKind::Unsupported,
                 "into_raw_fd operation is not supported for feth/bpf devices",
             )),
@@ -116,7 +149,7 @@ impl AsyncDevice {
         let async_model = if device.tun.is_tun() {
             AsyncModel::Async(unix::AsyncDevice::new_dev(device)?)
         } else {
-            AsyncModel::Select(select_io::AsyncDevice::new_dev(device)?)
+            AsyncModel::Bpf(bpf_io::AsyncDevice::new_dev(device)?)
         };
         Ok(Self { async_model })
     }
@@ -139,7 +172,7 @@ impl AsyncDevice {
     pub async fn readable(&self) -> io::Result<()> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.readable().await,
-            AsyncModel::Select(dev) => dev.readable().await,
+            AsyncModel::Bpf(dev) => dev.readable().await,
         }
     }
     /// Waits for the device to become writable.
@@ -150,6 +183,10 @@ impl AsyncDevice {
     /// false-positive and attempting a `try_send()` will return with
     /// `io::ErrorKind::WouldBlock`.
     ///
+    /// On a `feth` device, `try_send()` also fails with `WouldBlock` when the interface
+    /// queue is full (`ENOBUFS`). The kernel signals no event when the queue drains, so the
+    /// next call waits about a millisecond before checking writability.
+    ///
     /// # Cancel safety
     ///
     /// This method is cancel safe. Once a readiness event occurs, the method
@@ -159,7 +196,7 @@ impl AsyncDevice {
     pub async fn writable(&self) -> io::Result<()> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.writable().await,
-            AsyncModel::Select(dev) => dev.writable().await,
+            AsyncModel::Bpf(dev) => dev.writable().await,
         }
     }
     /// Polls the I/O handle for readability.
@@ -184,7 +221,7 @@ impl AsyncDevice {
     pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.poll_readable(cx),
-            AsyncModel::Select(dev) => dev.poll_readable(cx),
+            AsyncModel::Bpf(dev) => dev.poll_readable(cx),
         }
     }
     /// Attempts to receive a single packet from the device
@@ -209,7 +246,7 @@ impl AsyncDevice {
     pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.poll_recv(cx, buf),
-            AsyncModel::Select(dev) => dev.poll_recv(cx, buf),
+            AsyncModel::Bpf(dev) => dev.poll_recv(cx, buf),
         }
     }
     #[allow(dead_code)]
@@ -220,7 +257,7 @@ impl AsyncDevice {
     ) -> Poll<io::Result<usize>> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.poll_recv_uninit(cx, buf),
-            AsyncModel::Select(dev) => dev.poll_recv_uninit(cx, buf),
+            AsyncModel::Bpf(dev) => dev.poll_recv_uninit(cx, buf),
         }
     }
     /// Polls the I/O handle for writability.
@@ -245,7 +282,7 @@ impl AsyncDevice {
     pub fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.poll_writable(cx),
-            AsyncModel::Select(dev) => dev.poll_writable(cx),
+            AsyncModel::Bpf(dev) => dev.poll_writable(cx),
         }
     }
     /// Attempts to send packet to the device
@@ -270,7 +307,7 @@ impl AsyncDevice {
     pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.poll_send(cx, buf),
-            AsyncModel::Select(dev) => dev.poll_send(cx, buf),
+            AsyncModel::Bpf(dev) => dev.poll_send(cx, buf),
         }
     }
     /// Receives a single packet from the device.
@@ -282,7 +319,7 @@ impl AsyncDevice {
     pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.recv(buf).await,
-            AsyncModel::Select(dev) => dev.recv(buf).await,
+            AsyncModel::Bpf(dev) => dev.recv(buf).await,
         }
     }
     /// Tries to receive a single packet from the device.
@@ -297,7 +334,7 @@ impl AsyncDevice {
     pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.try_recv(buf),
-            AsyncModel::Select(dev) => dev.try_recv(buf),
+            AsyncModel::Bpf(dev) => dev.try_recv(buf),
         }
     }
     /// Send a packet to the device
@@ -307,7 +344,7 @@ impl AsyncDevice {
     pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.send(buf).await,
-            AsyncModel::Select(dev) => dev.send(buf).await,
+            AsyncModel::Bpf(dev) => dev.send(buf).await,
         }
     }
     /// Tries to send packet to the device.
@@ -323,7 +360,7 @@ impl AsyncDevice {
     pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.try_send(buf),
-            AsyncModel::Select(dev) => dev.try_send(buf),
+            AsyncModel::Bpf(dev) => dev.try_send(buf),
         }
     }
 
@@ -332,28 +369,89 @@ impl AsyncDevice {
     pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.recv_vectored(bufs).await,
-            AsyncModel::Select(dev) => dev.recv_vectored(bufs).await,
+            AsyncModel::Bpf(dev) => dev.recv_vectored(bufs).await,
+        }
+    }
+    /// Receives a batch of packets, see [`DeviceImpl::recv_multiple`].
+    ///
+    /// A TAP (`feth`) device returns every packet of one BPF read in one call; a TUN (`utun`)
+    /// device returns one packet per call.
+    pub async fn recv_multiple<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        original_buffer: &mut [u8],
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        offset: usize,
+    ) -> io::Result<usize> {
+        if !self.pushback.is_empty() {
+            if bufs.is_empty() || bufs.len() != sizes.len() {
+                return Err(io::Error::other("bufs error"));
+            }
+            let Some(buf) = bufs[0].as_mut().get_mut(offset..) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid offset",
+                ));
+            };
+            if let Some(len) = self.pushback.take(buf) {
+                sizes[0] = len;
+                return Ok(1);
+            }
+        }
+        match &self.async_model {
+            AsyncModel::Async(dev) => {
+                dev.read_with(|device| device.recv_multiple(original_buffer, bufs, sizes, offset))
+                    .await
+            }
+            AsyncModel::Bpf(dev) => {
+                dev.read_with(|device| device.recv_multiple(original_buffer, bufs, sizes, offset))
+                    .await
+            }
+        }
+    }
+    /// Sends a batch of packets, see [`DeviceImpl::send_multiple`].
+    pub async fn send_multiple<B: AsRef<[u8]>>(
+        &self,
+        bufs: &[B],
+        offset: usize,
+    ) -> io::Result<usize> {
+        let mut total = 0;
+        let mut err = Ok(());
+        for buf in bufs {
+            let Some(buf) = buf.as_ref().get(offset..) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid offset",
+                ));
+            };
+            match self.send(buf).await {
+                Ok(n) => total += n,
+                Err(e) if e.raw_os_error() == Some(libc::EBADF) => return Err(e),
+                Err(e) => err = Err(e),
+            }
         }
+        err?;
+        Ok(total)
     }
     /// Non-blocking version of `recv_vectored`.
     pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.try_recv_vectored(bufs),
-            AsyncModel::Select(dev) => dev.try_recv_vectored(bufs),
+            AsyncModel::Bpf(dev) => dev.try_recv_vectored(bufs),
         }
     }
     /// Sends multiple buffers as a single packet (gather write).
     pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.send_vectored(bufs).await,
-            AsyncModel::Select(dev) => dev.send_vectored(bufs).await,
+            AsyncModel::Bpf(dev) => dev.send_vectored(bufs).await,
         }
     }
     /// Non-blocking version of `send_vectored`.
     pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
         match &self.async_model {
             AsyncModel::Async(dev) => dev.try_send_vectored(bufs),
-            AsyncModel::Select(dev) => dev.try_send_vectored(bufs),
+            AsyncModel::Bpf(dev) => dev.try_send_vectored(bufs),
         }
     }
 }
@@ -91,8 +91,11 @@ Takes ownership of the underlying file descriptor and closes it when dropped.
 
 ### `BorrowedAsyncDevice`
 
-A borrowed variant that does not take ownership of the file descriptor.
+A borrowed variant that does not take ownership of the file descriptor (or, on Windows,
+of the tap-windows6 handle).
 Useful when the file descriptor is managed externally (e.g., on mobile platforms).
+`AsyncDevice::from_fd_with_options` covers the same case and also leaves the fd's
+blocking mode alone.
 
 ```no_run
 # #[cfg(unix)]
@@ -123,6 +126,9 @@ async fn use_borrowed_fd(fd: std::os::fd::RawFd) -> std::io::Result<()> {
 - `send(&self, buf: &[u8]) -> impl Future<Output = io::Result<usize>>`
   - Asynchronously send a packet to the device
 
+- `peek(&self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>>`
+  - Read the next packet without consuming it; the following receive returns it again
+
 ### Readiness Operations
 
 - `readable(&self) -> impl Future<Output = io::Result<()>>`
@@ -167,6 +173,8 @@ The `from_fd` and `borrow_raw` methods are `unsafe` because they:
 Always ensure proper lifetime management when using these methods.
 */
 
+mod pushback;
+pub(crate) use pushback::Pushback;
 #[cfg(unix)]
 pub(crate) mod unix;
 #[cfg(all(unix, not(target_os = "macos")))]
@@ -177,6 +185,10 @@ mod macos;
 pub use macos::AsyncDevice;
 #[cfg(windows)]
 mod windows;
+#[cfg(all(unix, feature = "interruptible"))]
+use unix::wait_interrupt;
+#[cfg(all(windows, feature = "interruptible"))]
+use windows::wait_interrupt;
 #[cfg(windows)]
 pub use windows::AsyncDevice;
 
@@ -196,6 +208,378 @@ pub mod async_framed;
 #[cfg(all(feature = "async_tokio", feature = "async_io", not(doc)))]
 compile_error! {"More than one asynchronous runtime is simultaneously specified in features"}
 
+/// Waits until the egress rate limit of `device` admits `len` bytes.
+pub(crate) async fn egress_wait(device: &crate::platform::DeviceImpl, len: usize) {
+    let Some(delay) = device.egress_limit.reserve(len) else {
+        return;
+    };
+    #[cfg(feature = "async_tokio")]
+    ::tokio::time::sleep(delay).await;
+    #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+    ::async_io::Timer::after(delay).await;
+}
+
+/// Runs `op` until it completes, or fails with [`TimedOut`](std::io::ErrorKind::TimedOut)
+/// once `timeout` has passed, using the timer of the enabled runtime.
+///
+/// `op` is polled before the timer, so a result ready at the deadline is not lost.
+async fn with_timeout<R>(
+    timeout: std::time::Duration,
+    op: impl std::future::Future<Output = std::io::Result<R>>,
+) -> std::io::Result<R> {
+    use std::future::Future;
+    use std::task::Poll;
+
+    #[cfg(feature = "async_tokio")]
+    let timer = ::tokio::time::sleep(timeout);
+    #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
+    let timer = ::async_io::Timer::after(timeout);
+    let mut op = std::pin::pin!(op);
+    let mut timer = std::pin::pin!(timer);
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(rs) = op.as_mut().poll(cx) {
+            return Poll::Ready(rs);
+        }
+        timer.as_mut().poll(cx).map(|_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "operation timed out",
+            ))
+        })
+    })
+    .await
+}
+
+impl AsyncDevice {
+    /// Receives a packet into `buf`, placing it at offset `headroom`.
+    ///
+    /// The first `headroom` bytes of `buf` are left untouched, so that encapsulation headers
+    /// can later be written in front of the packet without a copy.
+    /// Returns the packet length; the packet is `buf[headroom..headroom + len]`.
+    ///
+    /// See [`SyncDevice::recv_with_headroom`](crate::SyncDevice::recv_with_headroom).
+    pub async fn recv_with_headroom(
+        &self,
+        buf: &mut [u8],
+        headroom: usize,
+    ) -> std::io::Result<usize> {
+        self.recv_with_reserve(buf, headroom, 0).await
+    }
+    /// Like [`recv_with_headroom`](Self::recv_with_headroom), but additionally keeps the last
+    /// `tailroom` bytes of `buf` free.
+    pub async fn recv_with_reserve(
+        &self,
+        buf: &mut [u8],
+        headroom: usize,
+        tailroom: usize,
+    ) -> std::io::Result<usize> {
+        let buf = crate::platform::reserved_slice(buf, headroom, tailroom)?;
+        self.recv(buf).await
+    }
+    /// Receives a packet into `buf` without requiring it to be initialized.
+    ///
+    /// Returns the packet length; the first `len` bytes of `buf` (at most `buf.len()`) are
+    /// initialized afterwards. See [`SyncDevice::recv_uninit`](crate::SyncDevice::recv_uninit).
+    pub async fn recv_uninit(
+        &self,
+        buf: &mut [std::mem::MaybeUninit<u8>],
+    ) -> std::io::Result<usize> {
+        let buf = bytes::buf::UninitSlice::uninit(buf);
+        std::future::poll_fn(|cx| self.poll_recv_uninit_hooked(cx, buf)).await
+    }
+    /// Receives a packet into the spare capacity of `buf` and advances it past the packet.
+    ///
+    /// See [`SyncDevice::recv_buf`](crate::SyncDevice::recv_buf).
+    pub async fn recv_buf<B: bytes::BufMut>(&self, buf: &mut B) -> std::io::Result<usize> {
+        let chunk = buf.chunk_mut();
+        let len = std::future::poll_fn(|cx| self.poll_recv_uninit_hooked(cx, chunk)).await?;
+        let filled = len.min(chunk.len());
+        // SAFETY: the first `filled` bytes of the chunk are initialized.
+        unsafe { buf.advance_mut(filled) };
+        Ok(len)
+    }
+    /// Polls for a packet read into `buf`, which may be uninitialized, running the receive
+    /// hooks like `poll_recv`.
+    ///
+    /// If the hooks look at the packet, `buf` is zeroed first, as they take initialized bytes.
+    fn poll_recv_uninit_hooked(
+        &self,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut bytes::buf::UninitSlice,
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        if self.inspects_inbound() {
+            // SAFETY: only initialized bytes are written into `buf`.
+            let buf = crate::platform::zeroed(unsafe { buf.as_uninit_slice_mut() });
+            return self.poll_recv(cx, buf);
+        }
+        if let Some(len) = self.pushback.take_uninit(buf) {
+            return std::task::Poll::Ready(Ok(len));
+        }
+        let len = std::task::ready!(self.poll_recv_uninit(cx, buf))?;
+        if self.hooks_plain_io() {
+            self.count_received(len);
+        }
+        std::task::Poll::Ready(Ok(len))
+    }
+    /// Polls for a packet read into the spare capacity of `buf`, advancing it past the
+    /// packet.
+    #[cfg(feature = "async_framed")]
+    pub(crate) fn poll_recv_buf<B: bytes::BufMut>(
+        &self,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut B,
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        let chunk = buf.chunk_mut();
+        let len = std::task::ready!(self.poll_recv_uninit_hooked(cx, chunk))?;
+        let filled = len.min(chunk.len());
+        // SAFETY: the first `filled` bytes of the chunk are initialized.
+        unsafe { buf.advance_mut(filled) };
+        std::task::Poll::Ready(Ok(len))
+    }
+    /// Receives a packet like [`recv`](Self::recv), but fails with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut) if none arrives within `timeout`.
+    ///
+    /// Works on either runtime, without depending on its timeout utilities. A receive that
+    /// timed out has consumed no packet.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(dev: tun_rs::AsyncDevice) -> std::io::Result<()> {
+    /// use std::time::Duration;
+    ///
+    /// let mut buf = [0u8; 1500];
+    /// match dev.recv_or(&mut buf, Duration::from_secs(1)).await {
+    ///     Ok(len) => println!("received {len} bytes"),
+    ///     Err(e) if e.kind() == std::io::ErrorKind::TimedOut => println!("idle"),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn recv_or(
+        &self,
+        buf: &mut [u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        with_timeout(timeout, self.recv(buf)).await
+    }
+    /// Sends a packet like [`send`](Self::send), but fails with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut) if the device does not take it within
+    /// `timeout`, e.g. because its queue stays full.
+    ///
+    /// A send that timed out has not written the packet.
+    pub async fn send_or(
+        &self,
+        buf: &[u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        with_timeout(timeout, self.send(buf)).await
+    }
+    /// Sends a frame to the host as if it had arrived on the wire.
+    ///
+    /// This is what `send` does; the name spells out the direction for user-space switches
+    /// that also inject frames the other way with `send_as_outgoing`, available on TAP
+    /// devices on Linux and macOS.
+    pub async fn send_as_incoming(&self, frame: &[u8]) -> std::io::Result<usize> {
+        self.send(frame).await
+    }
+    /// Receives the next packet into `buf` without consuming it.
+    ///
+    /// The packet is kept in a pushback buffer and returned again by the next receive call
+    /// (`recv`, `try_recv`, `recv_vectored`, `recv_multiple`, or a `DeviceFramed` read),
+    /// so that a protocol demultiplexer can look at its headers before deciding who consumes
+    /// it. Peeking again returns the same packet.
+    ///
+    /// Only the first `buf.len()` bytes are copied, the whole packet is kept. Returns the
+    /// number of bytes copied. Fails with [`Unsupported`](std::io::ErrorKind::Unsupported)
+    /// on Linux devices built with `offload(true)`, whose packets are read with
+    /// `recv_multiple`, which would have to split a packet pushed back behind its virtio
+    /// network header.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(dev: tun_rs::AsyncDevice) -> std::io::Result<()> {
+    /// let mut header = [0u8; 20];
+    /// dev.peek(&mut header).await?;
+    /// if header[0] >> 4 == 6 {
+    ///     // Leave IPv6 to another task, which receives this very packet.
+    ///     return Ok(());
+    /// }
+    /// let mut buf = [0u8; 1500];
+    /// let len = dev.recv(&mut buf).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn peek(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.check_peek()?;
+        if let Some(len) = self.pushback.peek(buf) {
+            return Ok(len);
+        }
+        let mut packet = vec![0u8; pushback::MAX_PACKET_LEN];
+        let len = self.recv(&mut packet).await?;
+        Ok(self.push_peeked(packet, len, buf))
+    }
+    /// Like [`peek`](Self::peek), but fails with `WouldBlock` instead of waiting for a packet.
+    pub fn try_peek(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.check_peek()?;
+        if let Some(len) = self.pushback.peek(buf) {
+            return Ok(len);
+        }
+        let mut packet = vec![0u8; pushback::MAX_PACKET_LEN];
+        let len = self.try_recv(&mut packet)?;
+        Ok(self.push_peeked(packet, len, buf))
+    }
+    /// Fails on devices with offload, see [`peek`](Self::peek).
+    fn check_peek(&self) -> std::io::Result<()> {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if self.vnet_hdr {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "peek is not available on devices with offload",
+            ));
+        }
+        Ok(())
+    }
+    /// Copies the head of a packet just read into `buf` and pushes the packet back.
+    fn push_peeked(&self, mut packet: Vec<u8>, len: usize, buf: &mut [u8]) -> usize {
+        packet.truncate(len);
+        let copied = packet.len().min(buf.len());
+        buf[..copied].copy_from_slice(&packet[..copied]);
+        self.pushback.push(packet);
+        copied
+    }
+}
+
+/// Runs `op` until it completes, or fails with [`io::ErrorKind::Interrupted`] once `event` is
+/// triggered.
+///
+/// The event is checked before `op` is polled, and a completed `op` is returned even if the
+/// event is triggered afterwards, so that no received packet is lost.
+#[cfg(feature = "interruptible")]
+async fn interruptible<R>(
+    event: &crate::InterruptEvent,
+    op: impl std::future::Future<Output = std::io::Result<R>>,
+) -> std::io::Result<R> {
+    use std::future::Future;
+    use std::task::Poll;
+
+    let interrupted = || std::io::Error::new(std::io::ErrorKind::Interrupted, "trigger interrupt");
+    if event.is_trigger() {
+        return Err(interrupted());
+    }
+    let mut op = std::pin::pin!(op);
+    let mut wait = std::pin::pin!(wait_interrupt(event));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(rs) = wait.as_mut().poll(cx) {
+            return Poll::Ready(Err(rs.err().unwrap_or_else(interrupted)));
+        }
+        op.as_mut().poll(cx)
+    })
+    .await
+}
+
+#[cfg(feature = "interruptible")]
+impl AsyncDevice {
+    /// Like [`readable`](Self::readable), but fails with
+    /// [`Interrupted`](std::io::ErrorKind::Interrupted) once `event` is triggered.
+    ///
+    /// This allows cancelling a wait from outside the task, without dropping the future.
+    pub async fn readable_intr(&self, event: &crate::InterruptEvent) -> std::io::Result<()> {
+        interruptible(event, self.readable()).await
+    }
+    /// Like [`writable`](Self::writable), but fails with
+    /// [`Interrupted`](std::io::ErrorKind::Interrupted) once `event` is triggered.
+    #[cfg(unix)]
+    pub async fn writable_intr(&self, event: &crate::InterruptEvent) -> std::io::Result<()> {
+        interruptible(event, self.writable()).await
+    }
+    /// Receives a packet like [`recv`](Self::recv), but fails with
+    /// [`Interrupted`](std::io::ErrorKind::Interrupted) once `event` is triggered.
+    ///
+    /// The asynchronous counterpart of [`SyncDevice::recv_intr`](crate::SyncDevice::recv_intr):
+    /// one event can stop any number of tasks, e.g. on shutdown.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> std::io::Result<()> {
+    /// use std::sync::Arc;
+    /// use tun_rs::{DeviceBuilder, InterruptEvent};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_async()?;
+    /// let event = Arc::new(InterruptEvent::new()?);
+    /// // Another task or thread calls `event.trigger()` to stop the loop.
+    /// let mut buf = [0u8; 1500];
+    /// loop {
+    ///     match dev.recv_intr(&mut buf, &event).await {
+    ///         Ok(len) => println!("received {len} bytes"),
+    ///         Err(e) if e.kind() == std::io::ErrorKind::Interrupted => break,
+    ///         Err(e) => return Err(e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn recv_intr(
+        &self,
+        buf: &mut [u8],
+        event: &crate::InterruptEvent,
+    ) -> std::io::Result<usize> {
+        interruptible(event, self.recv(buf)).await
+    }
+    /// Sends a packet like [`send`](Self::send), but fails with
+    /// [`Interrupted`](std::io::ErrorKind::Interrupted) once `event` is triggered.
+    pub async fn send_intr(
+        &self,
+        buf: &[u8],
+        event: &crate::InterruptEvent,
+    ) -> std::io::Result<usize> {
+        interruptible(event, self.send(buf)).await
+    }
+}
+
+/// How [`AsyncDevice::from_fd_with_options`] takes over a file descriptor.
+///
+/// The default matches [`AsyncDevice::from_fd`]: the fd is put into non-blocking mode and
+/// closed when the device is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(unix)]
+/// # fn example(fd: std::os::fd::RawFd) -> std::io::Result<()> {
+/// use tun_rs::{AsyncDevice, FdOptions};
+///
+/// // An fd detached from Android's VpnService, already non-blocking and closed by Java.
+/// let options = FdOptions {
+///     set_nonblocking: false,
+///     owned: false,
+/// };
+/// let dev = unsafe { AsyncDevice::from_fd_with_options(fd, options)? };
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct FdOptions {
+    /// Whether to put the fd into non-blocking mode. When `false` its flags are left alone,
+    /// and it must already be non-blocking.
+    pub set_nonblocking: bool,
+    /// Whether the device closes the fd when dropped. When `false` the caller keeps
+    /// ownership, as with [`BorrowedAsyncDevice`].
+    pub owned: bool,
+}
+#[cfg(unix)]
+impl Default for FdOptions {
+    fn default() -> Self {
+        Self {
+            set_nonblocking: true,
+            owned: true,
+        }
+    }
+}
+
 /// A borrowed asynchronous TUN/TAP device.
 ///
 /// This type wraps an [`AsyncDevice`] but does not take ownership of the underlying file descriptor.
@@ -238,12 +622,13 @@ compile_error! {"More than one asynchronous runtime is simultaneously specified
 /// 2. The file descriptor is a TUN/TAP device
 /// 3. The file descriptor outlives the `BorrowedAsyncDevice`
 /// 4. No other code closes the file descriptor while in use
-#[cfg(unix)]
+///
+/// On Windows, [`borrow_raw_handle`](Self::borrow_raw_handle) borrows a tap-windows6 handle
+/// in the same way.
 pub struct BorrowedAsyncDevice<'dev> {
     dev: AsyncDevice,
     _phantom: std::marker::PhantomData<&'dev AsyncDevice>,
 }
-#[cfg(unix)]
 impl std::ops::Deref for BorrowedAsyncDevice<'_> {
     type Target = AsyncDevice;
     fn deref(&self) -> &Self::Target {
@@ -316,3 +701,38 @@ impl BorrowedAsyncDevice<'_> {
         }
     }
 }
+#[cfg(windows)]
+impl BorrowedAsyncDevice<'_> {
+    /// Borrows an open tap-windows6 device handle without taking ownership.
+    ///
+    /// The handle is typically one a privileged service opened and duplicated into this
+    /// process with `DuplicateHandle`, as the driver allows only one open handle per adapter.
+    /// It is **not** closed when the device is dropped. The adapter is identified by the MAC
+    /// address the driver reports for the handle, and nothing about it is changed or undone.
+    /// Wintun adapters cannot be borrowed, their sessions do not outlive the process that
+    /// opened them.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that:
+    ///
+    /// - `handle` is a tap-windows6 device handle opened with `FILE_FLAG_OVERLAPPED`
+    /// - `handle` remains open for the lifetime of the returned `BorrowedAsyncDevice`
+    /// - No other code reads from or writes to `handle` while the device is in use
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`std::io::ErrorKind::NotFound`] if no tap-windows6 adapter
+    /// has the MAC address of the handle.
+    pub unsafe fn borrow_raw_handle(
+        handle: std::os::windows::io::RawHandle,
+    ) -> std::io::Result<Self> {
+        #[allow(unused_unsafe)]
+        unsafe {
+            Ok(Self {
+                dev: AsyncDevice::borrow_raw_handle(handle)?,
+                _phantom: std::marker::PhantomData,
+            })
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Packets read by [`AsyncDevice::peek`](crate::AsyncDevice::peek) and not yet received.
+
+use bytes::buf::UninitSlice;
+use std::collections::VecDeque;
+use std::io::IoSliceMut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Size of the buffer `peek` reads into: the largest packet any backend returns, a 64 KiB
+/// packet behind an Ethernet and a virtio-net header.
+pub(crate) const MAX_PACKET_LEN: usize = 14 + 10 + 65536;
+
+/// Pushback buffer of a device, consulted by every receive call before the device itself.
+///
+/// It holds at most one packet unless several tasks peek concurrently, in which case each
+/// leaves its packet behind, in the order they were read.
+#[derive(Default)]
+pub(crate) struct Pushback {
+    /// Whether `packets` is non-empty, so that receiving does not take the lock.
+    pending: AtomicBool,
+    packets: Mutex<VecDeque<Box<[u8]>>>,
+}
+
+impl Pushback {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.pending.load(Ordering::Acquire)
+    }
+    /// Appends a packet that the next receive call returns.
+    pub(crate) fn push(&self, packet: impl Into<Box<[u8]>>) {
+        let packet = packet.into();
+        let mut packets = self.packets.lock().unwrap();
+        packets.push_back(packet);
+        self.pending.store(true, Ordering::Release);
+    }
+    /// Discards all packets, returning how many there were.
+    pub(crate) fn clear(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let mut packets = self.packets.lock().unwrap();
+        let count = packets.len();
+        packets.clear();
+        self.pending.store(false, Ordering::Release);
+        count
+    }
+    /// Copies the next packet into `buf` without removing it.
+    pub(crate) fn peek(&self, buf: &mut [u8]) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let packets = self.packets.lock().unwrap();
+        let packet = packets.front()?;
+        let len = packet.len().min(buf.len());
+        buf[..len].copy_from_slice(&packet[..len]);
+        Some(len)
+    }
+    fn pop(&self) -> Option<Box<[u8]>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut packets = self.packets.lock().unwrap();
+        let packet = packets.pop_front();
+        self.pending.store(!packets.is_empty(), Ordering::Release);
+        packet
+    }
+    /// Moves the next packet into `buf`, truncating it like a read from the device would.
+    #[inline]
+    pub(crate) fn take(&self, buf: &mut [u8]) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let packet = self.pop()?;
+        let len = packet.len().min(buf.len());
+        buf[..len].copy_from_slice(&packet[..len]);
+        Some(len)
+    }
+    #[inline]
+    #[allow(dead_code)]
+    pub(crate) fn take_uninit(&self, buf: &mut UninitSlice) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let packet = self.pop()?;
+        let len = packet.len().min(buf.len());
+        buf[..len].copy_from_slice(&packet[..len]);
+        Some(len)
+    }
+    /// Scatters the next packet across `bufs` in order.
+    #[inline]
+    pub(crate) fn take_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let packet = self.pop()?;
+        let mut rest = &packet[..];
+        let mut len = 0;
+        for buf in bufs {
+            let n = buf.len().min(rest.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            rest = &rest[n..];
+            len += n;
+        }
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peek_then_take() {
+        let pushback = Pushback::default();
+        let mut buf = [0u8; 8];
+        assert_eq!(pushback.take(&mut buf), None);
+        pushback.push(&[1, 2, 3, 4][..]);
+        assert_eq!(pushback.peek(&mut buf[..2]), Some(2));
+        assert_eq!(&buf[..2], &[1, 2]);
+        assert!(!pushback.is_empty());
+
+        let (mut a, mut b) = ([0u8; 3], [0u8; 3]);
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        assert_eq!(pushback.take_vectored(&mut bufs), Some(4));
+        assert_eq!((a, b), ([1, 2, 3], [4, 0, 0]));
+        assert!(pushback.is_empty());
+        assert_eq!(pushback.peek(&mut buf), None);
+    }
+}
@@ -67,6 +67,9 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.get_ref().pushback.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
         self.0.poll_readable(cx)
     }
 
@@ -89,8 +92,12 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.get_ref().pushback.take(buf) {
+            return Poll::Ready(Ok(len));
+        }
+        let device = self.0.get_ref();
         loop {
-            match self.0.get_ref().recv(buf) {
+            match device.recv_hooked(buf, |buf| device.recv(buf)) {
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
                 rs => return Poll::Ready(rs),
             }
@@ -108,6 +115,9 @@ impl AsyncDevice {
         cx: &mut Context<'_>,
         buf: &mut UninitSlice,
     ) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.get_ref().pushback.take_uninit(buf) {
+            return Poll::Ready(Ok(len));
+        }
         loop {
             match self.0.get_ref().recv_uninit(buf) {
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
@@ -163,8 +173,9 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let device = self.0.get_ref();
         loop {
-            match self.0.get_ref().send(buf) {
+            match device.send_hooked(buf, |buf| device.send(buf)) {
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
                 rs => return Poll::Ready(rs),
             }
@@ -180,6 +191,10 @@ impl AsyncDevice {
     pub(crate) fn new_dev(device: DeviceImpl) -> io::Result<Self> {
         Ok(Self(Async::new(device)?))
     }
+    /// Registers a device that is already non-blocking, leaving its flags alone.
+    pub(crate) fn register(device: DeviceImpl) -> io::Result<Self> {
+        Ok(Self(Async::new_nonblocking(device)?))
+    }
     pub(crate) fn into_device(self) -> io::Result<DeviceImpl> {
         self.0.into_inner()
     }
@@ -213,3 +228,16 @@ impl AsyncDevice {
         self.0.get_ref()
     }
 }
+
+/// Waits until `event` is triggered.
+#[cfg(feature = "interruptible")]
+pub(crate) async fn wait_interrupt(event: &crate::InterruptEvent) -> io::Result<()> {
+    // A duplicate is registered, so that several tasks can wait on the same event.
+    let fd = Async::new(event.try_clone_event_fd()?)?;
+    loop {
+        fd.readable().await?;
+        if event.is_trigger() {
+            return Ok(());
+        }
+    }
+}
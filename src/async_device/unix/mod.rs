@@ -1,9 +1,8 @@
-#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
-use crate::platform::offload::{handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_LEN};
+use crate::platform::unix::Fd;
 use crate::platform::DeviceImpl;
 #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
 use crate::platform::GROTable;
-use crate::SyncDevice;
+use crate::{FdOptions, SyncDevice};
 use std::io;
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::Deref;
@@ -11,11 +10,19 @@ use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 
 #[cfg(feature = "async_tokio")]
 mod tokio;
+#[cfg(all(feature = "async_tokio", feature = "interruptible"))]
+pub(crate) use self::tokio::wait_interrupt;
 #[cfg(feature = "async_tokio")]
 pub use self::tokio::AsyncDevice;
 
 #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
 mod async_io;
+#[cfg(all(
+    feature = "async_io",
+    not(feature = "async_tokio"),
+    feature = "interruptible"
+))]
+pub(crate) use self::async_io::wait_interrupt;
 #[cfg(all(feature = "async_io", not(feature = "async_tokio")))]
 pub use self::async_io::AsyncDevice;
 
@@ -56,6 +63,54 @@ impl AsyncDevice {
         AsyncDevice::new_dev(DeviceImpl::from_fd(fd)?)
     }
 
+    /// Construct an `AsyncDevice` from an adopted file descriptor, validating it first.
+    ///
+    /// Intended for fds handed over by a system VPN framework (e.g. the `packetFlow`
+    /// socket of an iOS/macOS Network Extension). The fd is checked to be a TUN device
+    /// (a `utun` control socket on Apple platforms, an attached `/dev/net/tun` fd on
+    /// Linux/Android) and is put into non-blocking mode. If the check fails, the fd is
+    /// left open and an error carrying [`Error::InvalidFd`](crate::Error::InvalidFd)
+    /// describing the problem is returned.
+    ///
+    /// # Safety
+    /// The fd must not be owned by anything else; on success, ownership moves to the device.
+    pub unsafe fn from_fd_checked(fd: RawFd) -> io::Result<AsyncDevice> {
+        let device = DeviceImpl::from_fd_checked(fd)?;
+        device.set_nonblocking(true)?;
+        AsyncDevice::new_dev(device)
+    }
+
+    /// Construct an `AsyncDevice` from an existing file descriptor, as configured by
+    /// `options`.
+    ///
+    /// With [`FdOptions::set_nonblocking`] unset the fd's flags are not touched, for fds
+    /// whose mode is managed elsewhere (e.g. one detached from Android's `VpnService`); it
+    /// must then already be non-blocking, or an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned. The fd is still registered with the
+    /// runtime's reactor.
+    ///
+    /// # Safety
+    /// The fd must be a valid, open TUN/TAP file descriptor. If [`FdOptions::owned`] is set,
+    /// ownership moves to the device; otherwise the fd must outlive it.
+    pub unsafe fn from_fd_with_options(fd: RawFd, options: FdOptions) -> io::Result<AsyncDevice> {
+        if !options.set_nonblocking && !Fd::new_unchecked_with_borrow(fd, true).is_nonblocking()? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the fd is in blocking mode",
+            ));
+        }
+        let device = if options.owned {
+            DeviceImpl::from_fd(fd)?
+        } else {
+            DeviceImpl::borrow_raw(fd)?
+        };
+        if options.set_nonblocking {
+            AsyncDevice::new_dev(device)
+        } else {
+            AsyncDevice::register(device)
+        }
+    }
+
     /// # Safety
     /// The fd passed in must be a valid, open file descriptor.
     /// Unlike [`from_fd`], this function does **not** take ownership of `fd`,
@@ -111,7 +166,12 @@ impl AsyncDevice {
     /// # }
     /// ```
     pub async fn readable(&self) -> io::Result<()> {
-        self.0.readable().await.map(|_| ())
+        if !self.pushback.is_empty() {
+            return Ok(());
+        }
+        self.0.readable().await.map(|_| ())?;
+        crate::trace::dev_event!(trace, self, "readable");
+        Ok(())
     }
     /// Waits for the device to become writable.
     ///
@@ -157,7 +217,9 @@ impl AsyncDevice {
     /// # }
     /// ```
     pub async fn writable(&self) -> io::Result<()> {
-        self.0.writable().await.map(|_| ())
+        self.0.writable().await.map(|_| ())?;
+        crate::trace::dev_event!(trace, self, "writable");
+        Ok(())
     }
     /// Receives a single packet from the device.
     /// On success, returns the number of bytes read.
@@ -166,7 +228,12 @@ impl AsyncDevice {
     /// size to hold the message bytes. If a message is too long to fit in the
     /// supplied buffer, excess bytes may be discarded.
     pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.read_with(|device| device.recv(buf)).await
+        // A peeked packet went through the hooks when `peek` received it.
+        if let Some(len) = self.pushback.take(buf) {
+            return Ok(len);
+        }
+        self.read_with(|device| device.recv_hooked(buf, |buf| device.recv(buf)))
+            .await
     }
     /// Tries to receive a single packet from the device.
     /// On success, returns the number of bytes read.
@@ -178,7 +245,10 @@ impl AsyncDevice {
     /// When there is no pending data, `Err(io::ErrorKind::WouldBlock)` is
     /// returned. This function is usually paired with `readable()`.
     pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.try_read_io(|device| device.recv(buf))
+        if let Some(len) = self.pushback.take(buf) {
+            return Ok(len);
+        }
+        self.try_read_io(|device| device.recv_hooked(buf, |buf| device.recv(buf)))
     }
 
     /// Send a packet to the device
@@ -186,7 +256,20 @@ impl AsyncDevice {
     /// # Return
     /// On success, the number of bytes sent is returned, otherwise, the encountered error is returned.
     pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.write_with(|device| device.send(buf)).await
+        if !self.hooks_plain_io() {
+            crate::async_device::egress_wait(self, buf.len()).await;
+            return self.write_with(|device| device.send(buf)).await;
+        }
+        let Some(packet) = self.middleware_outbound(buf) else {
+            return Ok(buf.len());
+        };
+        let len = packet.len();
+        let frame = self.validate_outbound(&packet)?;
+        let buf = &frame[..];
+        crate::async_device::egress_wait(self, buf.len()).await;
+        self.tap_outbound(buf);
+        let result = self.write_with(|device| device.send(buf)).await;
+        self.count_sent(result).map(|n| n.min(len))
     }
     /// Tries to send packet to the device.
     ///
@@ -199,24 +282,40 @@ impl AsyncDevice {
     /// sent. If the device is not ready to send data,
     /// `Err(ErrorKind::WouldBlock)` is returned.
     pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.try_write_io(|device| device.send(buf))
+        self.try_write_io(|device| device.send_hooked(buf, |buf| device.send(buf)))
     }
     /// Receives a packet into multiple buffers (scatter read).
     /// **Processes single packet per call**.
     pub async fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        self.read_with(|device| device.recv_vectored(bufs)).await
+        if let Some(len) = self.pushback.take_vectored(bufs) {
+            return Ok(len);
+        }
+        self.read_with(|device| {
+            device.recv_vectored_hooked(bufs, |bufs| device.recv_vectored(bufs))
+        })
+        .await
     }
     /// Non-blocking version of `recv_vectored`.
     pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        self.try_read_io(|device| device.recv_vectored(bufs))
+        if let Some(len) = self.pushback.take_vectored(bufs) {
+            return Ok(len);
+        }
+        self.try_read_io(|device| {
+            device.recv_vectored_hooked(bufs, |bufs| device.recv_vectored(bufs))
+        })
     }
     /// Sends multiple buffers as a single packet (gather write).
     pub async fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.write_with(|device| device.send_vectored(bufs)).await
+        self.write_with(|device| {
+            device.send_vectored_hooked(bufs, |bufs| device.send_vectored(bufs))
+        })
+        .await
     }
     /// Non-blocking version of `send_vectored`.
     pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.try_write_io(|device| device.send_vectored(bufs))
+        self.try_write_io(|device| {
+            device.send_vectored_hooked(bufs, |bufs| device.send_vectored(bufs))
+        })
     }
 }
 
@@ -254,22 +353,13 @@ impl AsyncDevice {
                 "too many packet buffers",
             ));
         }
-        let tun = self.get_ref();
-        if tun.vnet_hdr {
-            let len = self.recv(original_buffer).await?;
-            if len <= VIRTIO_NET_HDR_LEN {
-                Err(io::Error::other(format!(
-                    "length of packet ({len}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
-                )))?
-            }
-            let hdr = VirtioNetHdr::decode(&original_buffer[..VIRTIO_NET_HDR_LEN])?;
-            tun.handle_virtio_read(
-                hdr,
-                &mut original_buffer[VIRTIO_NET_HDR_LEN..len],
-                bufs,
-                sizes,
-                offset,
-            )
+        if self.get_ref().vnet_hdr {
+            self.read_with(|device| {
+                device.recv_multiple0(original_buffer, bufs, sizes, offset, |tun, buf| {
+                    tun.recv(buf)
+                })
+            })
+            .await
         } else {
             let Some(buf) = bufs[0].as_mut().get_mut(offset..) else {
                 return Err(io::Error::new(
@@ -290,12 +380,8 @@ impl AsyncDevice {
         &self,
         gro_table: &mut GROTable,
         bufs: &mut [B],
-        mut offset: usize,
+        offset: usize,
     ) -> io::Result<usize> {
-        gro_table.reset();
-        if bufs.is_empty() {
-            return Ok(0);
-        }
         if bufs.len() > u16::MAX as usize {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
@@ -303,24 +389,28 @@ impl AsyncDevice {
             ));
         }
         let tun = self.get_ref();
-        if tun.vnet_hdr {
-            handle_gro(
-                bufs,
-                offset,
-                &mut gro_table.tcp_gro_table,
-                &mut gro_table.udp_gro_table,
-                tun.udp_gso,
-                &mut gro_table.to_write,
-            )?;
-            offset -= VIRTIO_NET_HDR_LEN;
-        } else {
-            for i in 0..bufs.len() {
-                gro_table.to_write.push(i);
+        if !tun.vnet_hdr {
+            let mut total = 0;
+            let mut err = Ok(());
+            for buf in bufs.iter() {
+                let Some(buf) = buf.as_ref().get(offset..) else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid offset",
+                    ));
+                };
+                match self.send(buf).await {
+                    Ok(n) => total += n,
+                    Err(e) if e.raw_os_error() == Some(libc::EBADFD) => return Err(e),
+                    Err(e) => err = Err(e),
+                }
             }
+            gro_table.reset();
+            err?;
+            return Ok(total);
         }
-
+        let (offset, mut err) = tun.prepare_send_multiple(gro_table, bufs, offset)?;
         let mut total = 0;
-        let mut err = Ok(());
         for buf_idx in &gro_table.to_write {
             let Some(buf) = bufs[*buf_idx].as_ref().get(offset..) else {
                 return Err(io::Error::new(
@@ -328,7 +418,9 @@ impl AsyncDevice {
                     "invalid offset",
                 ));
             };
-            match self.send(buf).await {
+            crate::async_device::egress_wait(tun, buf.len()).await;
+            let result = self.write_with(|device| device.tun.send(buf)).await;
+            match tun.count_sent_multiple(buf, result) {
                 Ok(n) => {
                     total += n;
                 }
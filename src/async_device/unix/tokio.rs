@@ -66,6 +66,9 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.get_ref().pushback.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
         self.0.poll_read_ready(cx).map_ok(|_| ())
     }
     /// Attempts to receive a single packet from the device
@@ -88,10 +91,16 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.get_ref().pushback.take(buf) {
+            return Poll::Ready(Ok(len));
+        }
         loop {
             return match self.0.poll_read_ready(cx) {
                 Poll::Ready(Ok(mut rs)) => {
-                    let n = match rs.try_io(|dev| dev.get_ref().recv(buf)) {
+                    let n = match rs.try_io(|dev| {
+                        let dev = dev.get_ref();
+                        dev.recv_hooked(buf, |buf| dev.recv(buf))
+                    }) {
                         Ok(rs) => rs?,
                         Err(_) => continue,
                     };
@@ -109,6 +118,9 @@ impl AsyncDevice {
         cx: &mut Context<'_>,
         buf: &mut UninitSlice,
     ) -> Poll<io::Result<usize>> {
+        if let Some(len) = self.get_ref().pushback.take_uninit(buf) {
+            return Poll::Ready(Ok(len));
+        }
         loop {
             return match self.0.poll_read_ready(cx) {
                 Poll::Ready(Ok(mut rs)) => {
@@ -169,7 +181,10 @@ impl AsyncDevice {
         loop {
             return match self.0.poll_write_ready(cx) {
                 Poll::Ready(Ok(mut rs)) => {
-                    let n = match rs.try_io(|dev| dev.get_ref().send(buf)) {
+                    let n = match rs.try_io(|dev| {
+                        let dev = dev.get_ref();
+                        dev.send_hooked(buf, |buf| dev.send(buf))
+                    }) {
                         Ok(rs) => rs?,
                         Err(_) => continue,
                     };
@@ -185,6 +200,10 @@ impl AsyncDevice {
 impl AsyncDevice {
     pub(crate) fn new_dev(device: DeviceImpl) -> io::Result<Self> {
         device.set_nonblocking(true)?;
+        Self::register(device)
+    }
+    /// Registers a device that is already non-blocking, leaving its flags alone.
+    pub(crate) fn register(device: DeviceImpl) -> io::Result<Self> {
         Ok(Self(TokioAsyncFd::new(device)?))
     }
     pub(crate) fn into_device(self) -> io::Result<DeviceImpl> {
@@ -226,3 +245,18 @@ impl AsyncDevice {
         self.0.get_ref()
     }
 }
+
+/// Waits until `event` is triggered.
+#[cfg(feature = "interruptible")]
+pub(crate) async fn wait_interrupt(event: &crate::InterruptEvent) -> io::Result<()> {
+    // A duplicate is registered, so that several tasks can wait on the same event.
+    let fd = TokioAsyncFd::with_interest(event.try_clone_event_fd()?, Interest::READABLE)?;
+    loop {
+        let mut guard = fd.readable().await?;
+        if event.is_trigger() {
+            return Ok(());
+        }
+        // Reset in the meantime.
+        guard.clear_ready();
+    }
+}
@@ -0,0 +1,107 @@
+use std::ffi::c_void;
+use std::future::Future;
+use std::io;
+use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use windows_sys::Win32::Foundation::{HANDLE, INVALID_HANDLE_VALUE, WAIT_OBJECT_0};
+use windows_sys::Win32::System::Threading::{
+    RegisterWaitForSingleObject, UnregisterWaitEx, WaitForSingleObject, INFINITE,
+    WT_EXECUTEINWAITTHREAD, WT_EXECUTEONLYONCE,
+};
+
+#[derive(Default)]
+struct Shared {
+    signaled: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// Resolves once an event is signaled, e.g. the event of an OVERLAPPED operation on
+/// completion, or the first of several events with [`any`](Self::any).
+///
+/// The wait is registered with `RegisterWaitForSingleObject`: a thread-pool thread waits on
+/// many such handles at once and wakes the task from its callback, so no thread is blocked
+/// per pending operation.
+pub(crate) struct EventWait {
+    events: Vec<Arc<OwnedHandle>>,
+    shared: Arc<Shared>,
+    waits: Vec<HANDLE>,
+}
+
+// SAFETY: `waits` are only used to unregister the waits, which may be done from any thread.
+unsafe impl Send for EventWait {}
+
+impl EventWait {
+    pub(crate) fn new(event: Arc<OwnedHandle>) -> Self {
+        Self::any(vec![event])
+    }
+    /// Waits until any of `events` is signaled.
+    pub(crate) fn any(events: Vec<Arc<OwnedHandle>>) -> Self {
+        Self {
+            events,
+            shared: Arc::new(Shared::default()),
+            waits: Vec::new(),
+        }
+    }
+}
+
+unsafe extern "system" fn signaled(context: *mut c_void, _timed_out: bool) {
+    // SAFETY: `context` is the `Shared` of an `EventWait`, which unregisters the wait and
+    // waits for this callback before releasing it.
+    let shared = unsafe { &*(context as *const Shared) };
+    shared.signaled.store(true, Ordering::Release);
+    if let Some(waker) = shared.waker.lock().unwrap().take() {
+        waker.wake();
+    }
+}
+
+impl Future for EventWait {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.shared.signaled.load(Ordering::Acquire)
+            || this.events.iter().any(
+                |event| unsafe { WaitForSingleObject(event.as_raw_handle(), 0) } == WAIT_OBJECT_0,
+            )
+        {
+            return Poll::Ready(Ok(()));
+        }
+        *this.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+        while this.waits.len() < this.events.len() {
+            let mut wait = ptr::null_mut();
+            let registered = unsafe {
+                RegisterWaitForSingleObject(
+                    &mut wait,
+                    this.events[this.waits.len()].as_raw_handle(),
+                    Some(signaled),
+                    Arc::as_ptr(&this.shared) as *const c_void,
+                    INFINITE,
+                    WT_EXECUTEONLYONCE | WT_EXECUTEINWAITTHREAD,
+                )
+            };
+            if registered == 0 {
+                return Poll::Ready(Err(io::Error::last_os_error()));
+            }
+            this.waits.push(wait);
+        }
+        // The callback may have run before the waker was stored.
+        if this.shared.signaled.load(Ordering::Acquire) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for EventWait {
+    fn drop(&mut self) {
+        for wait in self.waits.drain(..) {
+            // Blocks until a running callback has returned, so that `shared` outlives it.
+            unsafe { UnregisterWaitEx(wait, INVALID_HANDLE_VALUE) };
+        }
+    }
+}
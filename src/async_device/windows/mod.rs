@@ -2,14 +2,18 @@ use crate::platform::windows::{ffi, InterruptEvent};
 use crate::platform::DeviceImpl;
 use crate::SyncDevice;
 use bytes::buf::UninitSlice;
+use event::EventWait;
 use std::future::Future;
 use std::io;
+use std::io::{IoSlice, IoSliceMut};
 use std::ops::Deref;
-use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::os::windows::io::{AsRawHandle, OwnedHandle, RawHandle};
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
+mod event;
+
 /// An async Tun/Tap device wrapper around a Tun/Tap device.
 ///
 /// This type does not provide a split method, because this functionality can be achieved by instead wrapping the device in an Arc.
@@ -50,10 +54,13 @@ use std::task::{Context, Poll};
 /// ```
 pub struct AsyncDevice {
     inner: Arc<DeviceImpl>,
-    recv_task_lock: Arc<Mutex<Option<RecvTask>>>,
     send_task_lock: Arc<Mutex<Option<SendTask>>>,
+    /// The read and write OVERLAPPED events of a tap-windows6 device, whose I/O completes
+    /// through these events instead of on a blocking thread.
+    tap_events: Option<(Arc<OwnedHandle>, Arc<OwnedHandle>)>,
+    read_wait: Mutex<Option<EventWait>>,
+    tap_write_wait: Mutex<Option<EventWait>>,
 }
-type RecvTask = blocking::Task<io::Result<(Vec<u8>, usize)>>;
 type SendTask = blocking::Task<io::Result<usize>>;
 impl Deref for AsyncDevice {
     type Target = DeviceImpl;
@@ -71,16 +78,70 @@ impl AsyncDevice {
     pub fn new(device: SyncDevice) -> io::Result<AsyncDevice> {
         AsyncDevice::new_dev(device.0)
     }
+    /// # Safety
+    /// See [`BorrowedAsyncDevice::borrow_raw_handle`](crate::BorrowedAsyncDevice::borrow_raw_handle).
+    pub(crate) unsafe fn borrow_raw_handle(handle: RawHandle) -> io::Result<AsyncDevice> {
+        AsyncDevice::new_dev(DeviceImpl::borrow_raw_handle(handle)?)
+    }
     /// Create a new `AsyncDevice` wrapping around a `Device`.
     pub(crate) fn new_dev(device: DeviceImpl) -> io::Result<AsyncDevice> {
+        let tap_events = device.tap_overlapped_events();
         let inner = Arc::new(device);
 
         Ok(AsyncDevice {
             inner,
-            recv_task_lock: Arc::new(Mutex::new(None)),
             send_task_lock: Arc::new(Mutex::new(None)),
+            tap_events,
+            read_wait: Mutex::new(None),
+            tap_write_wait: Mutex::new(None),
         })
     }
+    /// A wait for the device to become readable: the completion of the pending tap-windows6
+    /// read, or the Wintun read event, which is only set once the ring has run empty, or the
+    /// shutdown event.
+    fn read_event_wait(&self) -> io::Result<EventWait> {
+        if let Some((read_event, _)) = &self.tap_events {
+            return Ok(EventWait::new(read_event.clone()));
+        }
+        let (read_event, shutdown_event) = self.inner.wintun_read_wait_events()?;
+        Ok(EventWait::any(vec![
+            Arc::new(read_event),
+            Arc::new(shutdown_event),
+        ]))
+    }
+    /// Polls `op`, a nonblocking operation, retrying it whenever the wait created by
+    /// `new_wait` for the event it is waiting for is signaled.
+    fn poll_overlapped<R>(
+        cx: &mut Context<'_>,
+        wait: &Mutex<Option<EventWait>>,
+        new_wait: impl Fn() -> io::Result<EventWait>,
+        mut op: impl FnMut() -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        let mut wait = wait.lock().unwrap();
+        loop {
+            match op() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => {
+                    *wait = None;
+                    return Poll::Ready(rs);
+                }
+            }
+            if wait.is_none() {
+                match new_wait() {
+                    Ok(new) => *wait = Some(new),
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+            match Pin::new(wait.as_mut().unwrap()).poll(cx) {
+                Poll::Ready(Ok(())) => *wait = None,
+                Poll::Ready(Err(e)) => {
+                    *wait = None;
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
     /// Attempts to receive a single packet from the device
     ///
     /// # Caveats
@@ -100,44 +161,15 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
-        let mut guard = self.recv_task_lock.lock().unwrap();
-        let mut task = if let Some(task) = guard.take() {
-            task
-        } else {
-            match self.try_recv(buf) {
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-                rs => return Poll::Ready(rs),
-            }
-            let device = self.inner.clone();
-            let size = buf.len();
-            blocking::unblock(move || {
-                let mut in_buf = vec![0; size];
-                let n = device.recv(&mut in_buf)?;
-                Ok((in_buf, n))
-            })
-        };
-        match Pin::new(&mut task).poll(cx) {
-            Poll::Ready(rs) => {
-                drop(guard);
-                match rs {
-                    Ok((packet, n)) => {
-                        if n > buf.len() {
-                            return Poll::Ready(Err(io::Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "receive buffer too small",
-                            )));
-                        }
-                        buf[..n].copy_from_slice(&packet[..n]);
-                        Poll::Ready(Ok(n))
-                    }
-                    Err(e) => Poll::Ready(Err(e)),
-                }
-            }
-            Poll::Pending => {
-                guard.replace(task);
-                Poll::Pending
-            }
+        if let Some(len) = self.pushback.take(buf) {
+            return Poll::Ready(Ok(len));
         }
+        Self::poll_overlapped(
+            cx,
+            &self.read_wait,
+            || self.read_event_wait(),
+            || self.inner.recv_hooked(buf, |buf| self.inner.try_recv(buf)),
+        )
     }
     #[allow(dead_code)]
     pub(crate) fn poll_recv_uninit(
@@ -145,46 +177,15 @@ impl AsyncDevice {
         cx: &mut Context<'_>,
         buf: &mut UninitSlice,
     ) -> Poll<io::Result<usize>> {
-        let mut guard = self.recv_task_lock.lock().unwrap();
-        let mut task = if let Some(task) = guard.take() {
-            task
-        } else {
-            match self.inner.try_recv_uninit(buf) {
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-                rs => return Poll::Ready(rs),
-            }
-            let device = self.inner.clone();
-            let size = buf.len();
-            blocking::unblock(move || {
-                let mut in_buf = vec![0; size];
-                let n = device.recv(&mut in_buf)?;
-                Ok((in_buf, n))
-            })
-        };
-        match Pin::new(&mut task).poll(cx) {
-            Poll::Ready(rs) => {
-                drop(guard);
-                match rs {
-                    Ok((packet, n)) => {
-                        if n > buf.len() {
-                            return Poll::Ready(Err(io::Error::new(
-                                io::ErrorKind::InvalidInput,
-                                "receive buffer too small",
-                            )));
-                        }
-                        unsafe {
-                            std::ptr::copy_nonoverlapping(packet.as_ptr(), buf.as_mut_ptr(), n);
-                        }
-                        Poll::Ready(Ok(n))
-                    }
-                    Err(e) => Poll::Ready(Err(e)),
-                }
-            }
-            Poll::Pending => {
-                guard.replace(task);
-                Poll::Pending
-            }
+        if let Some(len) = self.pushback.take_uninit(buf) {
+            return Poll::Ready(Ok(len));
         }
+        Self::poll_overlapped(
+            cx,
+            &self.read_wait,
+            || self.read_event_wait(),
+            || self.inner.try_recv_uninit(buf),
+        )
     }
     /// Attempts to send packet to the device
     ///
@@ -205,6 +206,14 @@ impl AsyncDevice {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     pub fn poll_send(&self, cx: &mut Context<'_>, src: &[u8]) -> Poll<io::Result<usize>> {
+        if let Some((_, write_event)) = &self.tap_events {
+            return Self::poll_overlapped(
+                cx,
+                &self.tap_write_wait,
+                || Ok(EventWait::new(write_event.clone())),
+                || self.inner.send_hooked(src, |buf| self.inner.try_send(buf)),
+            );
+        }
         let mut guard = self.send_task_lock.lock().unwrap();
         let mut task = if let Some(task) = guard.take() {
             task
@@ -215,7 +224,7 @@ impl AsyncDevice {
             }
             let device = self.inner.clone();
             let buf = src.to_vec();
-            blocking::unblock(move || device.send(&buf))
+            blocking::unblock(move || device.send_hooked(&buf, |buf| device.send(buf)))
         };
         match Pin::new(&mut task).poll(cx) {
             Poll::Ready(rs) => {
@@ -243,17 +252,10 @@ impl AsyncDevice {
     /// consumed by an attempt to read that fails with `WouldBlock` or
     /// `Poll::Pending`.
     pub async fn readable(&self) -> io::Result<()> {
-        let mut canceller = Canceller::new_cancelable()?;
-        let device = self.inner.clone();
-        let (cancel_guard, exit_guard) = canceller.guard(device);
-        blocking::unblock(move || {
-            exit_guard.call(|device, cancel_event_handle| {
-                device.wait_readable_interruptible(cancel_event_handle, None)
-            })
-        })
-        .await?;
-        std::mem::forget(cancel_guard);
-        Ok(())
+        if !self.pushback.is_empty() {
+            return Ok(());
+        }
+        self.read_event_wait()?.await
     }
 
     /// Recv a packet from the device
@@ -269,7 +271,10 @@ impl AsyncDevice {
     /// Attempts to read a packet without blocking.
     #[inline]
     pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.try_recv(buf)
+        if let Some(len) = self.pushback.take(buf) {
+            return Ok(len);
+        }
+        self.inner.recv_hooked(buf, |buf| self.inner.try_recv(buf))
     }
 
     /// Send a packet to the device
@@ -278,9 +283,30 @@ impl AsyncDevice {
     /// This method is not cancellation safe.
     /// After cancellation, it is uncertain whether the data has been written or not.
     pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        let Some(packet) = self.middleware_outbound(buf) else {
+            return Ok(buf.len());
+        };
+        let len = packet.len();
+        let frame = self.validate_outbound(&packet)?;
+        let buf = &frame[..];
+        crate::async_device::egress_wait(self, buf.len()).await;
+        self.tap_outbound(buf);
+        if let Some((_, write_event)) = &self.tap_events {
+            let wait = Mutex::new(None);
+            let result = std::future::poll_fn(|cx| {
+                Self::poll_overlapped(
+                    cx,
+                    &wait,
+                    || Ok(EventWait::new(write_event.clone())),
+                    || self.inner.try_send(buf),
+                )
+            })
+            .await;
+            return self.count_sent(result).map(|n| n.min(len));
+        }
         match self.inner.try_send(buf) {
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
-            rs => return rs,
+            rs => return self.count_sent(rs).map(|n| n.min(len)),
         }
         let buf = buf.to_vec();
         let device = self.inner.clone();
@@ -293,12 +319,27 @@ impl AsyncDevice {
         })
         .await;
         std::mem::forget(cancel_guard);
-        result
+        self.count_sent(result).map(|n| n.min(len))
     }
     /// Attempts to write a packet without blocking.
     #[inline]
     pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.try_send(buf)
+        self.inner.send_hooked(buf, |buf| self.inner.try_send(buf))
+    }
+    /// Attempts to read a packet into multiple buffers without blocking.
+    #[inline]
+    pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        if let Some(len) = self.pushback.take_vectored(bufs) {
+            return Ok(len);
+        }
+        self.inner
+            .recv_vectored_hooked(bufs, |bufs| self.inner.try_recv_vectored(bufs))
+    }
+    /// Attempts to write the contents of multiple buffers as one packet without blocking.
+    #[inline]
+    pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.inner
+            .send_vectored_hooked(bufs, |bufs| self.inner.try_send_vectored(bufs))
     }
 }
 
@@ -354,6 +395,35 @@ impl Canceller {
     }
 }
 
+/// Waits until `event` is triggered.
+#[cfg(feature = "interruptible")]
+pub(crate) async fn wait_interrupt(event: &InterruptEvent) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::{WAIT_FAILED, WAIT_OBJECT_0};
+    use windows_sys::Win32::System::Threading::{WaitForMultipleObjects, INFINITE};
+
+    struct SetOnDrop(Arc<OwnedHandle>);
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            _ = ffi::set_event(self.0.as_raw_handle());
+        }
+    }
+    let event_handle = event.handle.try_clone()?;
+    let exit_event = Arc::new(ffi::create_event()?);
+    // Releases the blocking thread when the future is dropped first.
+    let _exit_guard = SetOnDrop(exit_event.clone());
+    blocking::unblock(move || {
+        let handles = [event_handle.as_raw_handle(), exit_event.as_raw_handle()];
+        //SAFETY: handles is a pointer to valid, aligned, stack memory
+        let result = unsafe { WaitForMultipleObjects(2, &handles as _, 0, INFINITE) };
+        match result {
+            WAIT_OBJECT_0 => Ok(()),
+            WAIT_FAILED => Err(io::Error::last_os_error()),
+            _ => Err(io::Error::other("interrupt wait abandoned")),
+        }
+    })
+    .await
+}
+
 struct CancelWaitGuard<'a> {
     exit_event_handle: &'a Arc<OwnedHandle>,
     cancel_event_handle: &'a Arc<InterruptEvent>,
@@ -0,0 +1,523 @@
+/*!
+# Privileged Device Broker
+
+Creating and configuring a device needs privileges (`CAP_NET_ADMIN` on Linux, root
+elsewhere) that the rest of an application should not hold. With a broker, a small
+privileged process builds devices on behalf of unprivileged clients and passes them the
+device file descriptor over a unix socket:
+
+- The client calls [`DeviceBuilder::via_broker`](crate::DeviceBuilder::via_broker) with
+  the path of the broker socket, then builds the device as usual.
+- The broker runs [`serve`], which builds each requested device with this crate and sends
+  back its descriptor, after asking an authorization callback.
+
+Only the portable settings are forwarded: the name, MTU, addresses, layer, MAC address,
+enabled state and packet information. The device is deleted once the client closes it, as usual.
+
+# Protocol
+
+The client connects and writes the request as lines of `key value`, ended by an empty
+line. The broker answers with one message: `ok` with the descriptor attached as
+`SCM_RIGHTS`, or `err <errno> <message>`, where `errno` is `0` if the error has no OS code.
+
+```text
+name tun7
+mtu 1400
+ipv4 10.0.0.2/24 10.0.0.1
+ipv6 fd00::2/64
+layer l3
+enabled true
+
+```
+
+# Example
+
+The broker:
+
+```no_run
+use std::os::unix::net::UnixListener;
+
+let listener = UnixListener::bind("/run/tun-broker.sock")?;
+tun_rs::broker::serve(&listener, |peer, request| {
+    // Only allow one user, and only devices with the expected prefix.
+    peer.uid == 1000 && request.name.as_deref().is_some_and(|n| n.starts_with("tun"))
+})?;
+# Ok::<(), std::io::Error>(())
+```
+
+The unprivileged client:
+
+```no_run
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new()
+    .name("tun7")
+    .ipv4("10.0.0.2", 24, None)
+    .via_broker("/run/tun-broker.sock")
+    .build_sync()?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::{DeviceBuilder, Layer, SyncDevice};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem::size_of;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::Duration;
+
+// Requests are a handful of short lines.
+const MAX_REQUEST_LEN: u64 = 64 * 1024;
+const MAX_REPLY_LEN: usize = 4096;
+// A client that does not finish its request in time is dropped, so it cannot stall `serve`.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Pause after a failed accept, so running out of descriptors does not spin the broker.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A device requested from a broker.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct BrokerRequest {
+    /// The device name.
+    pub name: Option<String>,
+    /// The MTU.
+    pub mtu: Option<u16>,
+    /// The IPv4 address, prefix length and destination.
+    pub ipv4: Option<(Ipv4Addr, u8, Option<Ipv4Addr>)>,
+    /// Further IPv4 addresses and prefix lengths.
+    pub ipv4_extra: Vec<(Ipv4Addr, u8)>,
+    /// The IPv6 addresses and prefix lengths.
+    pub ipv6: Vec<(Ipv6Addr, u8)>,
+    /// The layer.
+    pub layer: Option<Layer>,
+    /// Whether the device is brought up.
+    pub enabled: Option<bool>,
+    /// Whether packets carry the packet information header.
+    pub packet_information: Option<bool>,
+    /// The MAC address of an L2 device.
+    pub mac_addr: Option<[u8; 6]>,
+}
+
+/// The identity of a broker client, from its socket.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerCredentials {
+    /// Effective user id.
+    pub uid: u32,
+    /// Effective group id.
+    pub gid: u32,
+}
+
+impl PeerCredentials {
+    /// The credentials of the process on the other end of `stream`.
+    pub fn of(stream: &UnixStream) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+            let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+            let res = unsafe {
+                libc::getsockopt(
+                    stream.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_PEERCRED,
+                    (&mut cred as *mut libc::ucred).cast(),
+                    &mut len,
+                )
+            };
+            if res < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                uid: cred.uid,
+                gid: cred.gid,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let mut uid = 0;
+            let mut gid = 0;
+            if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { uid, gid })
+        }
+    }
+}
+
+impl BrokerRequest {
+    fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            out.push_str(&format!("name {name}\n"));
+        }
+        if let Some(mtu) = self.mtu {
+            out.push_str(&format!("mtu {mtu}\n"));
+        }
+        if let Some((address, prefix, destination)) = self.ipv4 {
+            out.push_str(&format!("ipv4 {address}/{prefix}"));
+            if let Some(destination) = destination {
+                out.push_str(&format!(" {destination}"));
+            }
+            out.push('\n');
+        }
+        for (address, prefix) in &self.ipv4_extra {
+            out.push_str(&format!("ipv4_extra {address}/{prefix}\n"));
+        }
+        for (address, prefix) in &self.ipv6 {
+            out.push_str(&format!("ipv6 {address}/{prefix}\n"));
+        }
+        match self.layer {
+            Some(Layer::L2) => out.push_str("layer l2\n"),
+            Some(Layer::L3) => out.push_str("layer l3\n"),
+            None => {}
+        }
+        if let Some(enabled) = self.enabled {
+            out.push_str(&format!("enabled {enabled}\n"));
+        }
+        if let Some(packet_information) = self.packet_information {
+            out.push_str(&format!("packet_information {packet_information}\n"));
+        }
+        if let Some(mac_addr) = self.mac_addr {
+            let mac_addr = crate::ethernet::MacAddr(mac_addr);
+            out.push_str(&format!("mac_addr {mac_addr}\n"));
+        }
+        out
+    }
+
+    fn decode(text: &str) -> io::Result<Self> {
+        fn invalid(line: &str) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid broker request line: {line:?}"),
+            )
+        }
+        fn address<T: std::str::FromStr>(value: &str) -> Option<(T, u8)> {
+            let (address, prefix) = value.split_once('/')?;
+            Some((address.parse().ok()?, prefix.parse().ok()?))
+        }
+        let mut request = Self::default();
+        let mut seen = Vec::new();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            // Only the address lists may repeat; a second value for anything else is
+            // ambiguous.
+            if !matches!(key, "ipv4_extra" | "ipv6") {
+                if seen.contains(&key) {
+                    return Err(invalid(line));
+                }
+                seen.push(key);
+            }
+            match key {
+                "name" if valid_name(value) => request.name = Some(value.to_string()),
+                "mtu" => request.mtu = Some(value.parse().map_err(|_| invalid(line))?),
+                "ipv4" => {
+                    let mut parts = value.split(' ');
+                    let (ip, prefix) = parts
+                        .next()
+                        .and_then(address)
+                        .ok_or_else(|| invalid(line))?;
+                    let destination = match parts.next() {
+                        Some(destination) => Some(destination.parse().map_err(|_| invalid(line))?),
+                        None => None,
+                    };
+                    request.ipv4 = Some((ip, prefix, destination));
+                }
+                "ipv4_extra" => request
+                    .ipv4_extra
+                    .push(address(value).ok_or_else(|| invalid(line))?),
+                "ipv6" => request
+                    .ipv6
+                    .push(address(value).ok_or_else(|| invalid(line))?),
+                "layer" => {
+                    request.layer = Some(match value {
+                        "l2" => Layer::L2,
+                        "l3" => Layer::L3,
+                        _ => return Err(invalid(line)),
+                    })
+                }
+                "enabled" => request.enabled = Some(value.parse().map_err(|_| invalid(line))?),
+                "packet_information" => {
+                    request.packet_information = Some(value.parse().map_err(|_| invalid(line))?)
+                }
+                "mac_addr" => {
+                    request.mac_addr =
+                        Some(crate::config::parse_mac_addr(value).map_err(|_| invalid(line))?)
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(request)
+    }
+
+    fn into_builder(self) -> DeviceBuilder {
+        let mut builder = DeviceBuilder::new();
+        if let Some(name) = self.name {
+            builder = builder.name(name);
+        }
+        if let Some(mtu) = self.mtu {
+            builder = builder.mtu(mtu);
+        }
+        if let Some((address, prefix, destination)) = self.ipv4 {
+            builder = builder.ipv4(address, prefix, destination);
+        }
+        if !self.ipv4_extra.is_empty() {
+            builder = builder.ipv4_tuple(&self.ipv4_extra);
+        }
+        if !self.ipv6.is_empty() {
+            builder = builder.ipv6_tuple(&self.ipv6);
+        }
+        if let Some(layer) = self.layer {
+            builder = builder.layer(layer);
+        }
+        builder = match self.enabled {
+            Some(enabled) => builder.enable(enabled),
+            None => builder.inherit_enable_state(),
+        };
+        if let Some(packet_information) = self.packet_information {
+            builder = builder.packet_information(packet_information);
+        }
+        if let Some(mac_addr) = self.mac_addr {
+            builder = builder.mac_addr(mac_addr);
+        }
+        builder
+    }
+}
+
+/// Whether `name` can be sent to a broker: it must not be empty, and must not contain
+/// whitespace or control characters, which would break the line-based protocol.
+pub(crate) fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Asks the broker listening at `path` for the device described by `request`.
+pub(crate) fn request_device(path: &Path, request: &BrokerRequest) -> io::Result<SyncDevice> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.write_all(request.encode().as_bytes())?;
+    stream.write_all(b"\n")?;
+    let mut reply = [0u8; MAX_REPLY_LEN];
+    let (len, fd) = recv_with_fd(&stream, &mut reply)?;
+    let reply = String::from_utf8_lossy(&reply[..len]);
+    let reply = reply.trim_end();
+    if reply == "ok" {
+        let fd = fd.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "broker sent no descriptor")
+        })?;
+        // SAFETY: the descriptor was just received and is owned by nobody else.
+        return unsafe { SyncDevice::from_fd(fd.into_raw_fd()) };
+    }
+    let Some((errno, message)) = reply
+        .strip_prefix("err ")
+        .and_then(|rest| rest.split_once(' '))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid broker reply: {reply:?}"),
+        ));
+    };
+    let kind = match errno.parse() {
+        Ok(0) | Err(_) => io::ErrorKind::Other,
+        Ok(errno) => io::Error::from_raw_os_error(errno).kind(),
+    };
+    Err(io::Error::new(kind, format!("broker: {message}")))
+}
+
+/// Serves device requests on `listener`, returning only if the listener itself is unusable.
+///
+/// Each request is passed to `authorize` together with the credentials of the client; if it
+/// returns `false` the client gets a permission error. Failures of single requests and of
+/// single accepts are logged, and do not stop the broker.
+pub fn serve<F>(listener: &UnixListener, authorize: F) -> io::Result<()>
+where
+    F: Fn(&PeerCredentials, &BrokerRequest) -> bool,
+{
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(libc::EBADF | libc::EINVAL | libc::ENOTSOCK)
+                ) =>
+            {
+                return Err(e)
+            }
+            Err(e) => {
+                log::warn!("broker accept failed: {e}");
+                std::thread::sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        if let Err(e) = handle(&stream, &authorize) {
+            log::warn!("broker request failed: {e}");
+        }
+    }
+}
+
+/// Serves the device request of one client connection.
+///
+/// The request must arrive within a few seconds, or the client gets a timeout error. Returns the error that was sent to the client, if any.
+pub fn handle<F>(stream: &UnixStream, authorize: F) -> io::Result<()>
+where
+    F: Fn(&PeerCredentials, &BrokerRequest) -> bool,
+{
+    let result = build_requested(stream, authorize);
+    let sent = match &result {
+        Ok(fd) => send_with_fd(stream, b"ok\n", Some(fd.as_raw_fd())),
+        Err(e) => {
+            let message = format!("err {} {e}\n", e.raw_os_error().unwrap_or(0));
+            send_with_fd(stream, message.as_bytes(), None)
+        }
+    };
+    // The client now owns the device: closing our descriptor does not delete it.
+    result?;
+    sent
+}
+
+fn build_requested<F>(stream: &UnixStream, authorize: F) -> io::Result<OwnedFd>
+where
+    F: Fn(&PeerCredentials, &BrokerRequest) -> bool,
+{
+    let peer = PeerCredentials::of(stream)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = BrokerRequest::decode(&read_request(stream)?)?;
+    if !authorize(&peer, &request) {
+        return Err(io::Error::from_raw_os_error(libc::EPERM));
+    }
+    let device = request.into_builder().build_sync()?;
+    // SAFETY: `into_raw_fd` transfers ownership of the descriptor.
+    Ok(unsafe { OwnedFd::from_raw_fd(device.into_raw_fd()) })
+}
+
+/// Reads one request, up to the empty line that ends it.
+fn read_request(stream: &UnixStream) -> io::Result<String> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_LEN));
+    let mut text = String::new();
+    loop {
+        let start = text.len();
+        if reader.read_line(&mut text)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "unterminated broker request",
+            ));
+        }
+        if &text[start..] == "\n" {
+            return Ok(text);
+        }
+    }
+}
+
+fn send_with_fd(stream: &UnixStream, data: &[u8], fd: Option<RawFd>) -> io::Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let mut control = [0u8; 64];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if let Some(fd) = fd {
+        let space = unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as _) } as usize;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = space as _;
+        // SAFETY: `control` is large enough for one descriptor.
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as _) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+        }
+    }
+    if unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn recv_with_fd(stream: &UnixStream, buf: &mut [u8]) -> io::Result<(usize, Option<OwnedFd>)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let mut control = [0u8; 64];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = control.len() as _;
+    #[cfg(target_os = "linux")]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(target_os = "linux"))]
+    let flags = 0;
+    let len = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, flags) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut fd = None;
+    // SAFETY: the kernel filled in `msg.msg_control` up to `msg.msg_controllen`.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let raw = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>());
+                fd = Some(OwnedFd::from_raw_fd(raw));
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    if let Some(fd) = &fd {
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    Ok((len as usize, fd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_request, BrokerRequest};
+    use crate::Layer;
+    use std::io::Write;
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn request_roundtrip() {
+        let request = BrokerRequest {
+            name: Some("tun7".into()),
+            mtu: Some(1400),
+            ipv4: Some((
+                "10.0.0.2".parse().unwrap(),
+                24,
+                Some("10.0.0.1".parse().unwrap()),
+            )),
+            ipv4_extra: vec![("10.0.1.2".parse().unwrap(), 24)],
+            ipv6: vec![("fd00::2".parse().unwrap(), 64)],
+            layer: Some(Layer::L3),
+            enabled: Some(true),
+            packet_information: Some(false),
+            mac_addr: Some([0x02, 0, 0, 0xab, 0xcd, 0xef]),
+        };
+        assert_eq!(BrokerRequest::decode(&request.encode()).unwrap(), request);
+        assert!(BrokerRequest::decode("mtu many\n").is_err());
+        assert!(BrokerRequest::decode("route 0.0.0.0/0\n").is_err());
+        assert!(BrokerRequest::decode("name tun7 x\n").is_err());
+        assert!(BrokerRequest::decode("mtu 1400\nmtu 9000\n").is_err());
+        assert!(BrokerRequest::decode("name tun7\nname tun8\n").is_err());
+        let request = BrokerRequest::decode("ipv6 fd00::2/64\nipv6 fd00::3/64\n").unwrap();
+        assert_eq!(request.ipv6.len(), 2);
+    }
+
+    #[test]
+    fn request_ends_at_empty_line() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        // The client keeps its write side open: the empty line alone ends the request.
+        client.write_all(b"mtu 1400\n\n").unwrap();
+        assert_eq!(read_request(&server).unwrap(), "mtu 1400\n\n");
+
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client.write_all(b"mtu 1400\n").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        assert!(read_request(&server).is_err());
+    }
+}
@@ -117,13 +117,14 @@ let tun = DeviceBuilder::new()
 
 ## Multiple IP Addresses
 
-You can configure multiple IPv6 addresses during device creation:
+You can configure multiple IPv4 and IPv6 addresses during device creation:
 
 ```no_run
 use tun_rs::DeviceBuilder;
 
 let dev = DeviceBuilder::new()
     .ipv4("10.0.0.1", 24, None)
+    .ipv4_tuple(&[("10.0.1.1", 24)])
     .ipv6("fd00::1", 64)
     .ipv6("fd00::2", 64)
     .build_sync()?;
@@ -227,6 +228,7 @@ use crate::platform::{DeviceImpl, SyncDevice};
 /// - On Linux, both modes use the kernel TUN/TAP driver
 /// - Android and iOS only support TUN (L3) mode
 #[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Layer {
     /// Data Link Layer (Ethernet frames with MAC addresses).
@@ -255,6 +257,385 @@ pub enum Layer {
     L3,
 }
 
+/// What a receive does when the packet is larger than the destination buffer.
+///
+/// Only used where the driver hands over whole packets: Wintun and tap-windows on Windows
+/// and `feth` TAP devices on macOS. Elsewhere the kernel truncates the packet itself.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum TruncatePolicy {
+    /// Drop the packet and fail with an error (default).
+    #[default]
+    Error,
+    /// Copy the part of the packet that fits into the buffer.
+    ///
+    /// The receive returns the number of bytes copied, like a datagram socket. The length of
+    /// the packet before it was cut is reported by `SyncDevice::recv_with_len`, like `recvmsg`
+    /// with `MSG_TRUNC`.
+    Truncate,
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+impl TruncatePolicy {
+    /// Returns the number of bytes to copy of a packet of `packet_len` bytes into a buffer
+    /// of `buf_len` bytes, or `None` if the packet is dropped.
+    pub(crate) fn apply(self, packet_len: usize, buf_len: usize) -> Option<usize> {
+        if packet_len <= buf_len {
+            return Some(packet_len);
+        }
+        match self {
+            TruncatePolicy::Error => None,
+            TruncatePolicy::Truncate => Some(buf_len),
+        }
+    }
+}
+
+/// Kernel flags of an IPv6 address added with [`DeviceBuilder::ipv6_with`].
+///
+/// The scope of an IPv6 address follows from the address itself and cannot be set. Temporary
+/// (privacy) addresses are created by the kernel, which `manage_temporary` asks for.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Ipv6AddressFlags {
+    /// Skip duplicate address detection, so the address is usable at once (`nodad`).
+    pub nodad: bool,
+    /// Use the address while duplicate address detection runs (`optimistic`).
+    pub optimistic: bool,
+    /// Do not add the route to the prefix of the address (`noprefixroute`).
+    pub no_prefix_route: bool,
+    /// Let the kernel derive temporary addresses from this one (`mngtmpaddr`), which needs a
+    /// /64 prefix.
+    pub manage_temporary: bool,
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+impl Ipv6AddressFlags {
+    /// The `IFA_F_*` bits.
+    pub(crate) fn bits(self) -> u32 {
+        use crate::platform::linux::netlink;
+        [
+            (self.nodad, netlink::IFA_F_NODAD),
+            (self.optimistic, netlink::IFA_F_OPTIMISTIC),
+            (self.no_prefix_route, netlink::IFA_F_NOPREFIXROUTE),
+            (self.manage_temporary, netlink::IFA_F_MANAGETEMPADDR),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |bits, (_, bit)| bits | bit)
+    }
+}
+
+/// One setting applied by the builder to a newly created device.
+///
+/// Reported by [`Error::Config`](crate::Error::Config) when the configuration fails part way.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigStep {
+    /// `mtu`, on Windows the IPv4 MTU.
+    Mtu(u16),
+    /// `mtu_v6`.
+    #[cfg(windows)]
+    MtuV6(u16),
+    /// `metric`.
+    #[cfg(windows)]
+    Metric(u16),
+    /// `metric_v6`.
+    #[cfg(windows)]
+    MetricV6(u16),
+    /// `tx_queue_len`.
+    #[cfg(target_os = "linux")]
+    TxQueueLen(u32),
+    /// `mac_addr`.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "macos",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    MacAddress([u8; 6]),
+    /// `ipv6_dad`.
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    Ipv6Dad(bool),
+    /// `ipv6_privacy`.
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    Ipv6Privacy(bool),
+    /// The primary IPv4 address set by `ipv4`.
+    Ipv4 {
+        /// The address.
+        address: Ipv4Addr,
+        /// The prefix length.
+        prefix: u8,
+        /// The destination of a point-to-point link.
+        destination: Option<Ipv4Addr>,
+    },
+    /// An additional IPv4 address.
+    AddIpv4 {
+        /// The address.
+        address: Ipv4Addr,
+        /// The prefix length.
+        prefix: u8,
+    },
+    /// An IPv6 address.
+    AddIpv6 {
+        /// The address.
+        address: Ipv6Addr,
+        /// The prefix length.
+        prefix: u8,
+    },
+    /// The primary IPv4 address with the broadcast address set by `ipv4_broadcast`.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    Ipv4Broadcast {
+        /// The address.
+        address: Ipv4Addr,
+        /// The prefix length.
+        prefix: u8,
+        /// The broadcast address.
+        broadcast: Ipv4Addr,
+    },
+    /// An IPv6 address added by `ipv6_with`.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    AddIpv6Flags {
+        /// The address.
+        address: Ipv6Addr,
+        /// The prefix length.
+        prefix: u8,
+        /// The flags of the address.
+        flags: Ipv6AddressFlags,
+    },
+    /// `enable`.
+    Enabled(bool),
+}
+
+impl ConfigStep {
+    fn apply(&self, device: &DeviceImpl) -> io::Result<()> {
+        match *self {
+            ConfigStep::Mtu(mtu) => device.set_mtu(mtu),
+            #[cfg(windows)]
+            ConfigStep::MtuV6(mtu) => device.set_mtu_v6(mtu),
+            #[cfg(windows)]
+            ConfigStep::Metric(metric) => device.set_metric(metric),
+            #[cfg(windows)]
+            ConfigStep::MetricV6(metric) => device.set_metric_v6(metric),
+            #[cfg(target_os = "linux")]
+            ConfigStep::TxQueueLen(tx_queue_len) => device.set_tx_queue_len(tx_queue_len),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "macos",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            ))]
+            ConfigStep::MacAddress(mac_addr) => device.set_mac_address(mac_addr),
+            #[cfg(any(
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "windows",
+                target_os = "macos"
+            ))]
+            ConfigStep::Ipv6Dad(dad) => device.set_ipv6_dad(dad),
+            #[cfg(any(
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "windows",
+                target_os = "macos"
+            ))]
+            ConfigStep::Ipv6Privacy(privacy) => device.set_ipv6_privacy(privacy),
+            ConfigStep::Ipv4 {
+                address,
+                prefix,
+                destination,
+            } => device.set_network_address(address, prefix, destination),
+            ConfigStep::AddIpv4 { address, prefix } => device.add_address_v4(address, prefix),
+            ConfigStep::AddIpv6 { address, prefix } => device.add_address_v6(address, prefix),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::Ipv4Broadcast {
+                address,
+                prefix,
+                broadcast,
+            } => device.set_network_address_with_broadcast(address, prefix, broadcast),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::AddIpv6Flags {
+                address,
+                prefix,
+                flags,
+            } => device.add_address_v6_with_flags(address, prefix, flags),
+            ConfigStep::Enabled(enabled) => device.enabled(enabled),
+        }
+    }
+    /// Removes the address added by this step, if any.
+    fn undo(&self, device: &DeviceImpl) -> io::Result<()> {
+        match *self {
+            ConfigStep::Ipv4 { address, .. } | ConfigStep::AddIpv4 { address, .. } => {
+                device.remove_address(address.into())
+            }
+            ConfigStep::AddIpv6 { address, .. } => device.remove_address(address.into()),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::Ipv4Broadcast { address, .. } => device.remove_address(address.into()),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::AddIpv6Flags { address, .. } => device.remove_address(address.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigStep::Mtu(mtu) => write!(f, "mtu {mtu}"),
+            #[cfg(windows)]
+            ConfigStep::MtuV6(mtu) => write!(f, "ipv6 mtu {mtu}"),
+            #[cfg(windows)]
+            ConfigStep::Metric(metric) => write!(f, "metric {metric}"),
+            #[cfg(windows)]
+            ConfigStep::MetricV6(metric) => write!(f, "ipv6 metric {metric}"),
+            #[cfg(target_os = "linux")]
+            ConfigStep::TxQueueLen(tx_queue_len) => write!(f, "tx queue length {tx_queue_len}"),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "macos",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            ))]
+            ConfigStep::MacAddress(m) => write!(
+                f,
+                "mac address {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                m[0], m[1], m[2], m[3], m[4], m[5]
+            ),
+            #[cfg(any(
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "windows",
+                target_os = "macos"
+            ))]
+            ConfigStep::Ipv6Dad(dad) => write!(f, "ipv6 dad {}", if *dad { "on" } else { "off" }),
+            #[cfg(any(
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "windows",
+                target_os = "macos"
+            ))]
+            ConfigStep::Ipv6Privacy(privacy) => {
+                write!(f, "ipv6 privacy {}", if *privacy { "on" } else { "off" })
+            }
+            ConfigStep::Ipv4 {
+                address,
+                prefix,
+                destination: Some(destination),
+            } => write!(f, "ipv4 address {address}/{prefix} -> {destination}"),
+            ConfigStep::Ipv4 {
+                address, prefix, ..
+            }
+            | ConfigStep::AddIpv4 { address, prefix } => {
+                write!(f, "ipv4 address {address}/{prefix}")
+            }
+            ConfigStep::AddIpv6 { address, prefix } => {
+                write!(f, "ipv6 address {address}/{prefix}")
+            }
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::Ipv4Broadcast {
+                address,
+                prefix,
+                broadcast,
+            } => write!(f, "ipv4 address {address}/{prefix} brd {broadcast}"),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            ConfigStep::AddIpv6Flags {
+                address,
+                prefix,
+                flags,
+            } => write!(f, "ipv6 address {address}/{prefix} {flags:?}"),
+            ConfigStep::Enabled(true) => f.write_str("enable"),
+            ConfigStep::Enabled(false) => f.write_str("disable"),
+        }
+    }
+}
+
+/// A problem with the settings of a [`DeviceBuilder`], reported by
+/// [`DeviceBuilder::validate`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BuilderIssue {
+    /// The device name exceeds the platform limit.
+    NameTooLong {
+        /// The requested name.
+        name: String,
+        /// The longest accepted name, in bytes (UTF-16 units on Windows).
+        max: usize,
+    },
+    /// The device name does not have the form the platform requires for the layer, e.g.
+    /// `utunN` for an L3 device on macOS.
+    InvalidName {
+        /// The requested name.
+        name: String,
+        /// The required form.
+        expected: &'static str,
+    },
+    /// The MTU is outside the range supported by the backend, see
+    /// [`BackendKind::mtu_limits`](crate::BackendKind::mtu_limits).
+    MtuOutOfRange {
+        /// The requested MTU.
+        mtu: u16,
+        /// Minimum supported MTU.
+        min: u16,
+        /// Maximum supported MTU.
+        max: u16,
+    },
+    /// The Wintun ring capacity is outside the range accepted by the driver.
+    #[cfg(windows)]
+    RingCapacity {
+        /// The requested capacity.
+        capacity: u32,
+        /// Minimum accepted capacity.
+        min: u32,
+        /// Maximum accepted capacity.
+        max: u32,
+    },
+    /// An address, netmask or prefix length cannot be used.
+    InvalidAddress {
+        /// The setting the address was passed to, e.g. `"ipv6"`.
+        setting: &'static str,
+        /// Why it was rejected.
+        reason: String,
+    },
+    /// `mac_addr` is set for an L3 device, which has no link-layer address.
+    MacAddrOnL3,
+    /// A setting cannot be used with the other settings or on this platform.
+    Conflict {
+        /// The offending setting.
+        setting: &'static str,
+        /// Why it cannot be used.
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for BuilderIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuilderIssue::NameTooLong { name, max } => {
+                write!(f, "name {name:?} is longer than {max}")
+            }
+            BuilderIssue::InvalidName { name, expected } => {
+                write!(f, "name {name:?} is not of the form {expected}")
+            }
+            BuilderIssue::MtuOutOfRange { mtu, min, max } => {
+                write!(f, "mtu {mtu} not in [{min},{max}]")
+            }
+            #[cfg(windows)]
+            BuilderIssue::RingCapacity { capacity, min, max } => {
+                write!(f, "ring capacity {capacity} not in [{min},{max}]")
+            }
+            BuilderIssue::InvalidAddress { setting, reason } => write!(f, "{setting}: {reason}"),
+            BuilderIssue::MacAddrOnL3 => f.write_str("mac_addr requires an L2 device"),
+            BuilderIssue::Conflict { setting, reason } => write!(f, "{setting}: {reason}"),
+        }
+    }
+}
+
 /// Configuration for a TUN/TAP interface.
 ///
 /// This structure stores settings such as the device name, operating layer,
@@ -266,9 +647,24 @@ pub(crate) struct DeviceConfig {
     /// The description of the device/interface.
     #[cfg(windows)]
     pub(crate) description: Option<String>,
+    /// The Wintun tunnel type the adapter is created with.
+    #[cfg(windows)]
+    pub(crate) tunnel_type: Option<String>,
     /// Available with Layer::L2; creates a pair of feth devices, with peer_feth as the IO interface name.
     #[cfg(target_os = "macos")]
     pub(crate) peer_feth: Option<String>,
+    /// Size of the BPF buffer of a feth device, in bytes.
+    #[cfg(target_os = "macos")]
+    pub(crate) bpf_buffer_size: Option<usize>,
+    /// Whether BPF reads of a feth device return as soon as a packet arrives.
+    #[cfg(target_os = "macos")]
+    pub(crate) bpf_immediate: Option<bool>,
+    /// How long a BPF read of a feth device waits for its buffer to fill.
+    #[cfg(target_os = "macos")]
+    pub(crate) bpf_read_timeout: Option<std::time::Duration>,
+    /// `SO_SNDBUF` of the NDRV socket frames of a feth device are written to, in bytes.
+    #[cfg(target_os = "macos")]
+    pub(crate) ndrv_send_buffer_size: Option<usize>,
     /// If true (default), the program will automatically add or remove routes on macOS or FreeBSD to provide consistent routing behavior across all platforms.
     /// If false, the program will not modify or manage routes in any way, allowing the system to handle all routing natively.
     /// Set this to be false to obtain the platform's default routing behavior.
@@ -285,8 +681,29 @@ pub(crate) struct DeviceConfig {
     pub(crate) reuse_dev: Option<bool>,
     /// If true, the feth device will be kept after the program exits;
     /// if false (default), the device will be destroyed automatically.
-    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    /// On Linux this is the persistent flag of the device, set or cleared at creation.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos"))
+    ))]
     pub(crate) persist: Option<bool>,
+    /// If true, the persistent flag of a device created by this process is cleared when it is
+    /// dropped.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub(crate) unpersist_on_drop: Option<bool>,
+    /// If true, attach to an existing interface with the given name instead of creating one.
+    pub(crate) attach_existing: Option<bool>,
+    /// What is undone when the device is dropped.
+    #[cfg(any(
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    ))]
+    pub(crate) cleanup_policy: Option<crate::CleanupPolicy>,
     /// Specifies whether the interface operates at L2 or L3.
     #[allow(dead_code)]
     pub(crate) layer: Option<Layer>,
@@ -298,6 +715,12 @@ pub(crate) struct DeviceConfig {
     /// Path to the wintun file on Windows.
     #[cfg(windows)]
     pub(crate) wintun_file: Option<String>,
+    /// Where to look for the wintun file instead of next to the executable, on Windows.
+    #[cfg(windows)]
+    pub(crate) wintun_search_paths: Option<Vec<std::path::PathBuf>>,
+    /// How the wintun file is checked before it is loaded, on Windows.
+    #[cfg(windows)]
+    pub(crate) wintun_trust: Option<crate::WintunTrust>,
     /// Capacity of the ring buffer on Windows.
     #[cfg(windows)]
     pub(crate) ring_capacity: Option<u32>,
@@ -305,15 +728,21 @@ pub(crate) struct DeviceConfig {
     /// Default: false.
     #[cfg(windows)]
     pub(crate) delete_driver: Option<bool>,
+    /// Windows 7 compatibility mode of Wintun; `None` detects it from the OS version.
+    #[cfg(windows)]
+    pub(crate) compat_win7: Option<bool>,
     #[cfg(windows)]
     pub(crate) mac_address: Option<String>,
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub(crate) truncate_policy: Option<TruncatePolicy>,
     /// switch of Enable/Disable packet information for network driver
     #[cfg(any(
         target_os = "macos",
         target_os = "linux",
         target_os = "freebsd",
         target_os = "openbsd",
-        target_os = "netbsd"
+        target_os = "netbsd",
+        target_os = "windows"
     ))]
     pub(crate) packet_information: Option<bool>,
     /// Enable/Disable TUN offloads.
@@ -323,7 +752,156 @@ pub(crate) struct DeviceConfig {
     /// Enable multi queue support
     #[cfg(target_os = "linux")]
     pub(crate) multi_queue: Option<bool>,
+    /// Request NAPI receive processing (IFF_NAPI)
+    #[cfg(target_os = "linux")]
+    pub(crate) napi: Option<bool>,
+    /// Request NAPI fragment receive processing (IFF_NAPI_FRAGS)
+    #[cfg(target_os = "linux")]
+    pub(crate) napi_frags: Option<bool>,
+    /// Create or open a macvtap/ipvtap device instead of a TUN/TAP device.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub(crate) vtap: Option<crate::platform::linux::vtap::Vtap>,
+    /// eBPF program that picks the queue of each packet (TUNSETSTEERINGEBPF).
+    #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    pub(crate) steering_ebpf: Option<std::sync::Arc<std::os::fd::OwnedFd>>,
+}
+/// Checks `name` against the interface naming rules of the platform for `layer`.
+fn name_issue(name: &str, layer: Layer) -> Option<BuilderIssue> {
+    let _ = layer;
+    #[cfg(windows)]
+    let (len, max) = (name.encode_utf16().count(), 256);
+    // IFNAMSIZ including the terminating NUL.
+    #[cfg(unix)]
+    let (len, max) = (name.len(), 15);
+    if len > max {
+        return Some(BuilderIssue::NameTooLong {
+            name: name.to_string(),
+            max,
+        });
+    }
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    {
+        let (prefix, expected) = name_form(layer);
+        let valid = match name.strip_prefix(prefix) {
+            // The bare prefix asks for the next free unit.
+            #[cfg(target_os = "macos")]
+            Some("") => true,
+            Some(unit) => unit.parse::<u32>().is_ok(),
+            None => false,
+        };
+        if !valid {
+            return Some(BuilderIssue::InvalidName {
+                name: name.to_string(),
+                expected,
+            });
+        }
+    }
+    None
+}
+/// The prefix every interface name has on platforms with fixed names, followed by a unit,
+/// and the form of the names for error messages.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+fn name_form(layer: Layer) -> (&'static str, &'static str) {
+    #[cfg(target_os = "macos")]
+    return match layer {
+        Layer::L2 => ("feth", "fethN"),
+        Layer::L3 => ("utun", "utunN"),
+    };
+    #[cfg(not(target_os = "macos"))]
+    return match layer {
+        Layer::L2 => ("tap", "tapN"),
+        Layer::L3 => ("tun", "tunN"),
+    };
+}
+/// The valid name for `layer` closest to `hint`, see [`DeviceBuilder::name_hint`], or `None`
+/// to let the platform pick one.
+fn closest_name(hint: &str, layer: Layer) -> Option<String> {
+    if name_issue(hint, layer).is_none() {
+        #[cfg(target_os = "linux")]
+        if interface_exists(hint) {
+            return next_free_name(hint);
+        }
+        #[cfg(target_os = "macos")]
+        if layer == Layer::L3 {
+            let taken = hint[4..].parse::<u32>().is_ok_and(|unit| {
+                crate::platform::utun_units().is_ok_and(|units| units.contains(&unit))
+            });
+            if taken {
+                return Some("utun".to_string());
+            }
+        }
+        return Some(hint.to_string());
+    }
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    {
+        // Swap in the required prefix, keeping the unit of the hint.
+        let unit = &hint[hint.trim_end_matches(|c: char| c.is_ascii_digit()).len()..];
+        let name = format!("{}{unit}", name_form(layer).0);
+        if name_issue(&name, layer).is_some() {
+            return None;
+        }
+        closest_name(&name, layer)
+    }
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )))]
+    {
+        // Free-form names: drop what the kernel rejects and cut to the length limit.
+        #[cfg(unix)]
+        let mut name: String = hint
+            .chars()
+            .filter(|c| !c.is_whitespace() && !matches!(c, '/' | ':'))
+            .collect();
+        #[cfg(windows)]
+        let mut name = hint.to_string();
+        while name_issue(&name, layer).is_some() {
+            name.pop();
+        }
+        if name.is_empty() || name == "." || name == ".." {
+            return None;
+        }
+        closest_name(&name, layer)
+    }
+}
+#[cfg(target_os = "linux")]
+fn interface_exists(name: &str) -> bool {
+    std::ffi::CString::new(name)
+        .is_ok_and(|name| unsafe { libc::if_nametoindex(name.as_ptr()) } != 0)
 }
+/// `name` with its unit replaced by the next free one, e.g. `tun1` for a taken `tun0`.
+#[cfg(target_os = "linux")]
+fn next_free_name(name: &str) -> Option<String> {
+    let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    (0..1000)
+        .map(|unit| {
+            let mut base = base.to_string();
+            // Shorten the base rather than the unit to stay within the length limit.
+            while name_issue(&format!("{base}{unit}"), Layer::L3).is_some() {
+                base.pop();
+            }
+            format!("{base}{unit}")
+        })
+        .find(|name| !interface_exists(name))
+}
+
 type IPV4 = (
     io::Result<Ipv4Addr>,
     io::Result<u8>,
@@ -410,6 +988,13 @@ impl DeviceBuilderGuard<'_> {
         self.0.description = Some(description.into());
         self
     }
+    /// Sets the Wintun tunnel type (effective only on Windows L3 mode).
+    /// See [`DeviceBuilder::tunnel_type`].
+    #[cfg(windows)]
+    pub fn tunnel_type<S: Into<String>>(&mut self, tunnel_type: S) -> &mut Self {
+        self.0.tunnel_type = Some(tunnel_type.into());
+        self
+    }
 
     /// Sets the IPv4 MTU specifically for Windows.
     #[cfg(windows)]
@@ -424,6 +1009,9 @@ impl DeviceBuilderGuard<'_> {
         self
     }
     /// Sets the MAC address for the device (effective only in L2 mode).
+    ///
+    /// [`MacAddr::random_locally_administered`](crate::ethernet::MacAddr::random_locally_administered)
+    /// generates one that cannot collide with a vendor-assigned address.
     #[cfg(any(
         target_os = "windows",
         target_os = "linux",
@@ -453,11 +1041,43 @@ impl DeviceBuilderGuard<'_> {
         self
     }
     /// Sets the `wintun.dll` file path on Windows.
+    ///
+    /// A bare file name is looked up next to the executable, also in the `<arch>` and
+    /// `wintun/bin/<arch>` directories of the official distribution, where `<arch>` is
+    /// `amd64`, `x86`, `arm64` or `arm`. With the `embed-wintun` feature, the embedded DLL is
+    /// used unless a file is set here.
     #[cfg(windows)]
     pub fn wintun_file(&mut self, wintun_file: String) -> &mut Self {
         self.0.wintun_file = Some(wintun_file);
         self
     }
+    /// Looks for `wintun.dll` only in `paths`, in order, instead of next to the executable.
+    ///
+    /// Each path is a directory containing the file set with [`wintun_file`](Self::wintun_file),
+    /// or the DLL itself. The first DLL that passes the architecture and
+    /// [`wintun_trust`](Self::wintun_trust) checks is loaded, so a list can fall back from a
+    /// system-wide installation to a copy shipped with the application. The DLL search order,
+    /// including the working directory, is never consulted.
+    #[cfg(windows)]
+    pub fn wintun_search_paths(&mut self, paths: Vec<std::path::PathBuf>) -> &mut Self {
+        self.0.wintun_search_paths = Some(paths);
+        self
+    }
+    /// Checks `wintun.dll` before it is loaded on Windows, e.g. with
+    /// [`WintunTrust::official`](crate::WintunTrust::official) for the signature of the
+    /// official builds.
+    ///
+    /// Unless the trust is [`WintunTrust::Any`](crate::WintunTrust::Any), the default, a DLL
+    /// is only loaded from an explicit path: a file name not found next to the executable
+    /// (or in [`wintun_search_paths`](Self::wintun_search_paths)) is not looked up in the
+    /// working directory or `PATH`. The DLL is held open while it is checked and loaded, so it
+    /// cannot be swapped in between. A DLL that fails the check makes the build fail with
+    /// [`Error::UntrustedDriver`](crate::Error::UntrustedDriver).
+    #[cfg(windows)]
+    pub fn wintun_trust(&mut self, trust: crate::WintunTrust) -> &mut Self {
+        self.0.wintun_trust = Some(trust);
+        self
+    }
     /// Sets the ring capacity on Windows.
     /// This specifies the capacity of the packet ring buffer in bytes.
     /// By default, the ring capacity is set to `0x20_0000` (2 MB).
@@ -500,6 +1120,12 @@ impl DeviceBuilderGuard<'_> {
         self.0.metric = Some(metric);
         self
     }
+    /// Sets the IPv6 routing metric on Windows, overriding [`metric`](Self::metric) for IPv6.
+    #[cfg(windows)]
+    pub fn metric_v6(&mut self, metric: u16) -> &mut Self {
+        self.0.metric_v6 = Some(metric);
+        self
+    }
     /// Whether to call `WintunDeleteDriver` to remove the driver.
     /// Default: false.
     /// # Note
@@ -509,6 +1135,18 @@ impl DeviceBuilderGuard<'_> {
         self.0.delete_driver = Some(delete_driver);
         self
     }
+    /// Forces the Windows 7 compatibility mode of Wintun on or off.
+    ///
+    /// By default the mode follows the OS version, so one binary runs on both legacy and
+    /// current systems. In this mode orphaned adapters are recognised by their owning process
+    /// instead of their device status, and the adapter description cannot be set: a
+    /// [`description`](Self::description) alongside a [`tunnel_type`](Self::tunnel_type) is
+    /// skipped with a warning. `capabilities()` reports the mode that is in effect.
+    #[cfg(windows)]
+    pub fn compat_win7(&mut self, compat_win7: bool) -> &mut Self {
+        self.0.compat_win7 = Some(compat_win7);
+        self
+    }
     /// Sets the transmit queue length for the network interface on Linux.
     ///
     /// The transmit queue length controls how many packets can be queued for
@@ -553,6 +1191,10 @@ impl DeviceBuilderGuard<'_> {
     /// After enabling offload, you should use [`recv_multiple`](crate::SyncDevice::recv_multiple)
     /// and [`send_multiple`](crate::SyncDevice::send_multiple) for optimal performance.
     ///
+    /// Offload also works with [`Layer::L2`]: the packets are then Ethernet frames, and each
+    /// segment of a split frame gets a copy of its Ethernet header. Only IPv4 and IPv6 frames
+    /// are coalesced, and only with frames that have the same Ethernet header.
+    ///
     /// # Arguments
     ///
     /// * `offload` - `true` to enable offload, `false` to disable (default: false)
@@ -655,8 +1297,65 @@ impl DeviceBuilderGuard<'_> {
         self.0.multi_queue = Some(multi_queue);
         self
     }
+    /// Enables NAPI receive processing (`IFF_NAPI`) on Linux.
+    ///
+    /// With NAPI, packets written to the device are injected into the stack through the
+    /// NAPI poll loop, which batches receive processing on newer kernels (4.15+).
+    ///
+    /// The kernel's support is probed with `TUNGETFEATURES` (see [`tun_features`](crate::tun_features));
+    /// if it is missing, the flag is dropped with a warning and the device is created without it.
+    /// Requires `CAP_NET_ADMIN`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .with(|builder| {
+    ///         builder.napi(true);
+    ///     })
+    ///     .build_sync()?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// # Platform
+    ///
+    /// Linux only.
+    #[cfg(target_os = "linux")]
+    pub fn napi(&mut self, napi: bool) -> &mut Self {
+        self.0.napi = Some(napi);
+        self
+    }
+    /// Enables NAPI fragment processing (`IFF_NAPI_FRAGS`) on Linux. Implies [`napi`](Self::napi).
+    ///
+    /// The kernel only accepts this flag for TAP devices; in TUN mode, or if the kernel lacks
+    /// support, it is dropped with a warning.
+    ///
+    /// # Platform
+    ///
+    /// Linux only. TAP mode (Layer 2) only.
+    #[cfg(target_os = "linux")]
+    pub fn napi_frags(&mut self, napi_frags: bool) -> &mut Self {
+        self.0.napi_frags = Some(napi_frags);
+        self
+    }
+    /// Loads `prog` as the queue steering program of a multi-queue device.
+    ///
+    /// # Platform
+    ///
+    /// Linux only, with the `ebpf` feature. See [`DeviceBuilder::steering_ebpf`].
+    #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    pub fn steering_ebpf(&mut self, prog: std::os::fd::OwnedFd) -> &mut Self {
+        self.0.steering_ebpf = Some(prog);
+        self
+    }
     /// Enables or disables packet information for the network driver(TUN)
-    /// on macOS, Linux, freebsd, openbsd, netbsd.
+    /// on macOS, Linux, freebsd, openbsd, netbsd and Windows.
     ///
     /// This option is disabled by default (`false`).
     /// # Note
@@ -664,12 +1363,17 @@ impl DeviceBuilderGuard<'_> {
     /// The elimination of the packet information on macOS according to this setting
     /// is processed by this library.
     /// The set value `v` can be retrieved by `ignore_packet_info`, the returned value is `!v`.
+    ///
+    /// Wintun has no packet information either; on Windows the library prepends and strips
+    /// a `tun_pi` header laid out as on Linux, see [`framing::TunPi`](crate::framing::TunPi).
+    /// It is ignored for TAP devices.
     #[cfg(any(
         target_os = "macos",
         target_os = "linux",
         target_os = "freebsd",
         target_os = "openbsd",
-        target_os = "netbsd"
+        target_os = "netbsd",
+        target_os = "windows"
     ))]
     pub fn packet_information(&mut self, packet_information: bool) -> &mut Self {
         self.0.packet_information = Some(packet_information);
@@ -712,29 +1416,42 @@ impl DeviceBuilderGuard<'_> {
         self.0.peer_feth = Some(peer_feth.into());
         self
     }
-    /// Controls automatic route management on BSD and macOS platforms.
+    /// Sets the size of the BPF buffer packets of a `feth` device are read from, 128 KiB by
+    /// default.
     ///
-    /// When enabled (the default), the library automatically adds or removes routes
-    /// to provide consistent routing behavior across all platforms. When disabled,
-    /// the system's native routing behavior is used.
+    /// A larger buffer holds more packets per read. The kernel clamps the size to its limits
+    /// (the `debug.bpf_maxbufsize` sysctl).
     ///
-    /// # Arguments
+    /// # Platform
     ///
-    /// * `associate_route` - `true` to enable automatic route management (default),
-    ///   `false` to use native system routing
+    /// macOS only, Layer 2 (TAP) mode only.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.0.bpf_buffer_size = Some(size);
+        self
+    }
+    /// Sets whether BPF reads of a `feth` device return as soon as a packet arrives, `true` by
+    /// default.
+    ///
+    /// With `false` a read waits until the buffer is full or the
+    /// [`bpf_read_timeout`](Self::bpf_read_timeout) expires, trading latency for fewer reads.
     ///
     /// # Example
     ///
     /// ```no_run
-    /// # #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    /// # #[cfg(target_os = "macos")]
     /// # {
-    /// use tun_rs::DeviceBuilder;
+    /// use std::time::Duration;
+    /// use tun_rs::{DeviceBuilder, Layer};
     ///
-    /// // Use native system routing without automatic route management
+    /// // Batch up to 1 MiB of frames, delivered at least every millisecond
     /// let dev = DeviceBuilder::new()
-    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .layer(Layer::L2)
     ///     .with(|builder| {
-    ///         builder.associate_route(false) // Disable automatic route management
+    ///         builder
+    ///             .bpf_buffer_size(1 << 20)
+    ///             .bpf_immediate(false)
+    ///             .bpf_read_timeout(Duration::from_millis(1))
     ///     })
     ///     .build_sync()?;
     /// # }
@@ -743,15 +1460,79 @@ impl DeviceBuilderGuard<'_> {
     ///
     /// # Platform
     ///
-    /// macOS, FreeBSD, OpenBSD, NetBSD only.
-    #[cfg(any(
-        target_os = "macos",
-        target_os = "freebsd",
-        target_os = "openbsd",
-        target_os = "netbsd"
-    ))]
-    pub fn associate_route(&mut self, associate_route: bool) -> &mut Self {
-        self.0.associate_route = Some(associate_route);
+    /// macOS only, Layer 2 (TAP) mode only.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_immediate(&mut self, immediate: bool) -> &mut Self {
+        self.0.bpf_immediate = Some(immediate);
+        self
+    }
+    /// Sets how long a BPF read of a `feth` device waits for the buffer to fill when
+    /// [`bpf_immediate`](Self::bpf_immediate) is off (`BIOCSRTIMEOUT`). By default a read
+    /// waits until the buffer is full.
+    ///
+    /// # Platform
+    ///
+    /// macOS only, Layer 2 (TAP) mode only.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_read_timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.0.bpf_read_timeout = Some(timeout);
+        self
+    }
+    /// Sets the send buffer (`SO_SNDBUF`) of the NDRV socket frames of a `feth` device are
+    /// written to. By default the system default is kept.
+    ///
+    /// A send that finds no buffer space fails with `ENOBUFS`, which a non-blocking device
+    /// reports as [`io::ErrorKind::WouldBlock`] and a blocking one retries; a larger buffer
+    /// leaves more room for bursts.
+    ///
+    /// # Platform
+    ///
+    /// macOS only, Layer 2 (TAP) mode only.
+    #[cfg(target_os = "macos")]
+    pub fn ndrv_send_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.0.ndrv_send_buffer_size = Some(size);
+        self
+    }
+    /// Controls automatic route management on BSD and macOS platforms.
+    ///
+    /// When enabled (the default), the library automatically adds or removes routes
+    /// to provide consistent routing behavior across all platforms. When disabled,
+    /// the system's native routing behavior is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `associate_route` - `true` to enable automatic route management (default),
+    ///   `false` to use native system routing
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// // Use native system routing without automatic route management
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .with(|builder| {
+    ///         builder.associate_route(false) // Disable automatic route management
+    ///     })
+    ///     .build_sync()?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// # Platform
+    ///
+    /// macOS, FreeBSD, OpenBSD, NetBSD only.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    pub fn associate_route(&mut self, associate_route: bool) -> &mut Self {
+        self.0.associate_route = Some(associate_route);
         self
     }
     /// Controls whether to reuse an existing device with the same name.
@@ -822,22 +1603,64 @@ impl DeviceBuilderGuard<'_> {
     ///
     /// # Platform
     ///
-    /// macOS, Windows only. TAP mode (Layer 2) only.
-    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    /// macOS, Windows: TAP mode (Layer 2) only. Linux: both modes, where `persist` sets or
+    /// clears the persistent flag of the device (`TUNSETPERSIST`), also when attaching to a
+    /// device a previous process left persistent.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos"))
+    ))]
     pub fn persist(&mut self, persist: bool) -> &mut Self {
         self.0.persist = Some(persist);
         self
     }
+    /// Clears the persistent flag of the device when it is dropped, if this process created it
+    /// and the cleanup policy still destroys it.
+    ///
+    /// # Platform
+    ///
+    /// Linux only.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn unpersist_on_drop(&mut self, unpersist_on_drop: bool) -> &mut Self {
+        self.0.unpersist_on_drop = Some(unpersist_on_drop);
+        self
+    }
+    /// Sets what a receive does with a packet larger than the buffer.
+    ///
+    /// By default ([`TruncatePolicy::Error`]) the packet is dropped and the receive fails.
+    /// With [`TruncatePolicy::Truncate`] the part that fits is copied instead, which is what
+    /// relays usually prefer.
+    ///
+    /// # Platform
+    ///
+    /// macOS (TAP mode only), Windows.
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    pub fn truncate_policy(&mut self, truncate_policy: TruncatePolicy) -> &mut Self {
+        self.0.truncate_policy = Some(truncate_policy);
+        self
+    }
 }
 /// This is a unified constructor of a device for various platforms. The specification of every API can be found by looking at
 /// the documentation of the concrete platform.
 #[derive(Default)]
 pub struct DeviceBuilder {
     dev_name: Option<String>,
+    name_hint: Option<String>,
     #[cfg(windows)]
     description: Option<String>,
+    #[cfg(windows)]
+    tunnel_type: Option<String>,
     #[cfg(target_os = "macos")]
     peer_feth: Option<String>,
+    #[cfg(target_os = "macos")]
+    bpf_buffer_size: Option<usize>,
+    #[cfg(target_os = "macos")]
+    bpf_immediate: Option<bool>,
+    #[cfg(target_os = "macos")]
+    bpf_read_timeout: Option<std::time::Duration>,
+    #[cfg(target_os = "macos")]
+    ndrv_send_buffer_size: Option<usize>,
     #[cfg(any(
         target_os = "macos",
         target_os = "freebsd",
@@ -847,14 +1670,47 @@ pub struct DeviceBuilder {
     associate_route: Option<bool>,
     #[cfg(any(target_os = "macos", target_os = "windows", target_os = "netbsd"))]
     reuse_dev: Option<bool>,
-    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos"))
+    ))]
     persist: Option<bool>,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    unpersist_on_drop: Option<bool>,
+    attach_existing: Option<bool>,
+    #[cfg(any(
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    ))]
+    cleanup_policy: Option<crate::CleanupPolicy>,
     enabled: Option<bool>,
     mtu: Option<u16>,
     #[cfg(windows)]
     mtu_v6: Option<u16>,
     ipv4: Option<IPV4>,
+    ipv4_extra: Option<Vec<(io::Result<Ipv4Addr>, io::Result<u8>)>>,
     ipv6: Option<Vec<(io::Result<Ipv6Addr>, io::Result<u8>)>>,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    ipv4_broadcast: Option<io::Result<Ipv4Addr>>,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    ipv6_flags: Vec<(io::Result<Ipv6Addr>, io::Result<u8>, Ipv6AddressFlags)>,
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    ipv6_dad: Option<bool>,
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    ipv6_privacy: Option<bool>,
     layer: Option<Layer>,
     #[cfg(any(
         target_os = "windows",
@@ -872,18 +1728,29 @@ pub struct DeviceBuilder {
     #[cfg(windows)]
     wintun_file: Option<String>,
     #[cfg(windows)]
+    wintun_search_paths: Option<Vec<std::path::PathBuf>>,
+    #[cfg(windows)]
+    wintun_trust: Option<crate::WintunTrust>,
+    #[cfg(windows)]
     ring_capacity: Option<u32>,
     #[cfg(windows)]
     metric: Option<u16>,
     #[cfg(windows)]
+    metric_v6: Option<u16>,
+    #[cfg(windows)]
     delete_driver: Option<bool>,
+    #[cfg(windows)]
+    compat_win7: Option<bool>,
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    truncate_policy: Option<TruncatePolicy>,
     /// switch of Enable/Disable packet information for network driver
     #[cfg(any(
         target_os = "macos",
         target_os = "linux",
         target_os = "freebsd",
         target_os = "openbsd",
-        target_os = "netbsd"
+        target_os = "netbsd",
+        target_os = "windows"
     ))]
     packet_information: Option<bool>,
     #[cfg(target_os = "linux")]
@@ -895,6 +1762,25 @@ pub struct DeviceBuilder {
     /// Enable multi queue support
     #[cfg(target_os = "linux")]
     multi_queue: Option<bool>,
+    #[cfg(target_os = "linux")]
+    napi: Option<bool>,
+    #[cfg(target_os = "linux")]
+    napi_frags: Option<bool>,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    vtap: Option<crate::platform::linux::vtap::Vtap>,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    steering_ebpf: Option<std::os::fd::OwnedFd>,
+    #[cfg(all(
+        feature = "broker",
+        any(
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        )
+    ))]
+    broker: Option<std::path::PathBuf>,
 }
 
 impl DeviceBuilder {
@@ -903,17 +1789,66 @@ impl DeviceBuilder {
         Self::default().enable(true)
     }
     /// Sets the device name.
+    ///
+    /// On macOS an L3 device must be named `utunN`. Building fails with
+    /// [`Error::AlreadyInUse`](crate::Error::AlreadyInUse) if that unit is taken; the name
+    /// `"utun"` requests the next free unit.
     pub fn name<S: Into<String>>(mut self, dev_name: S) -> Self {
         self.dev_name = Some(dev_name.into());
         self
     }
+    /// Sets a preferred device name, which is adjusted to the platform's naming rules
+    /// instead of failing the build.
+    ///
+    /// A name set with [`name`](Self::name) takes precedence. A hint that is a valid and
+    /// unused name is used as is, otherwise the closest one is picked:
+    ///
+    /// - **Linux**: characters the kernel rejects (`/`, `:`, whitespace) are dropped and the
+    ///   name is cut to 15 bytes. If an interface of that name exists, the next free unit is
+    ///   appended instead, e.g. `tun1` for `tun0`.
+    /// - **Windows**: the name is cut to the length limit.
+    /// - **macOS, FreeBSD, OpenBSD, NetBSD**: the required prefix replaces the hint's,
+    ///   keeping its unit number, e.g. `vpn3` becomes `utun3`. A `utun` unit that is taken,
+    ///   or a hint without a unit, leaves the choice to the system.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// // `wg0` on Linux and Windows, `utun0` on macOS.
+    /// let dev = DeviceBuilder::new().name_hint("wg0").build_sync()?;
+    /// println!("created {}", dev.name()?);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn name_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.name_hint = Some(hint.into());
+        self
+    }
     /// Sets the device description (effective only on Windows L3 mode).
     #[cfg(windows)]
     pub fn description<S: Into<String>>(mut self, description: S) -> Self {
         self.description = Some(description.into());
         self
     }
+    /// Sets the Wintun tunnel type (effective only on Windows L3 mode).
+    ///
+    /// Wintun groups adapters by tunnel type, e.g. in the device manager, and uses it as the
+    /// device description of new adapters. Give it the name of the application, so that its
+    /// adapters are kept apart from those of other Wintun users.
+    ///
+    /// Without a tunnel type the [`description`](Self::description) is used, or else the
+    /// device name. With both set, the description is applied to the adapter separately
+    /// after creating it.
+    #[cfg(windows)]
+    pub fn tunnel_type<S: Into<String>>(mut self, tunnel_type: S) -> Self {
+        self.tunnel_type = Some(tunnel_type.into());
+        self
+    }
     /// Sets the device MTU (Maximum Transmission Unit).
+    ///
+    /// Checked against the limits of the backend when building, see
+    /// [`BackendKind::mtu_limits`](crate::BackendKind::mtu_limits).
     pub fn mtu(mut self, mtu: u16) -> Self {
         self.mtu = Some(mtu);
         #[cfg(windows)]
@@ -936,6 +1871,9 @@ impl DeviceBuilder {
         self
     }
     /// Sets the MAC address for the device (effective only in L2 mode).
+    ///
+    /// [`MacAddr::random_locally_administered`](crate::ethernet::MacAddr::random_locally_administered)
+    /// generates one that cannot collide with a vendor-assigned address.
     #[cfg(any(
         target_os = "windows",
         target_os = "linux",
@@ -968,6 +1906,36 @@ impl DeviceBuilder {
         self.ipv4 = Some((address.ipv4(), mask.prefix(), destination.map(|v| v.ipv4())));
         self
     }
+    /// Configures multiple IPv4 addresses in batch.
+    ///
+    /// Accepts a slice of (IPv4 address, netmask) tuples. These addresses are added
+    /// to the interface in addition to the one set by [`ipv4`](Self::ipv4), which is
+    /// always applied first.
+    /// # Example
+    /// ```rust
+    /// use tun_rs::DeviceBuilder;
+    /// DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .ipv4_tuple(&[("10.0.1.1", 24), ("10.0.2.1", 24)]);
+    /// ```
+    pub fn ipv4_tuple<IPv4: ToIpv4Address, Netmask: ToIpv4Netmask>(
+        mut self,
+        addrs: &[(IPv4, Netmask)],
+    ) -> Self {
+        if let Some(v) = &mut self.ipv4_extra {
+            for (address, mask) in addrs {
+                v.push((address.ipv4(), mask.prefix()));
+            }
+        } else {
+            self.ipv4_extra = Some(
+                addrs
+                    .iter()
+                    .map(|(ip, mask)| (ip.ipv4(), mask.prefix()))
+                    .collect(),
+            );
+        }
+        self
+    }
     /// Configures a single IPv6 address for the device.
     ///
     /// - `address`: The IPv6 address.
@@ -1019,6 +1987,106 @@ impl DeviceBuilder {
         }
         self
     }
+    /// Sets the broadcast address of the [`ipv4`](Self::ipv4) address, instead of the last
+    /// address of its network.
+    ///
+    /// The address is added with its broadcast address in one request, so there is no
+    /// moment at which the device has the derived one.
+    /// A point-to-point address, one with a destination, has no broadcast address and this
+    /// setting is then ignored ([`validate`](Self::validate) reports it).
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    /// DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .ipv4_broadcast("10.0.0.127");
+    /// # }
+    /// ```
+    ///
+    /// # Platform
+    ///
+    /// Linux only.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn ipv4_broadcast<IPv4: ToIpv4Address>(mut self, broadcast: IPv4) -> Self {
+        self.ipv4_broadcast = Some(broadcast.ipv4());
+        self
+    }
+    /// Configures an IPv6 address with the given kernel flags, e.g. to skip duplicate address
+    /// detection.
+    ///
+    /// The address is added after the ones set by [`ipv6`](Self::ipv6), with its flags in
+    /// the same request.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    /// # {
+    /// use tun_rs::{DeviceBuilder, Ipv6AddressFlags};
+    /// DeviceBuilder::new().ipv6_with(
+    ///     "fd00::1",
+    ///     64,
+    ///     Ipv6AddressFlags {
+    ///         nodad: true,
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// # }
+    /// ```
+    ///
+    /// # Platform
+    ///
+    /// Linux only.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn ipv6_with<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        mut self,
+        address: IPv6,
+        mask: Netmask,
+        flags: Ipv6AddressFlags,
+    ) -> Self {
+        self.ipv6_flags.push((address.ipv6(), mask.prefix(), flags));
+        self
+    }
+    /// Enables or disables IPv6 duplicate address detection on the interface, before any
+    /// address is added.
+    ///
+    /// DAD holds a new address back for about a second, which delays the first connections
+    /// over a freshly created tunnel; on a point-to-point link there is nobody to collide
+    /// with, so it can safely be turned off. See `set_ipv6_dad` of the device for how each
+    /// platform applies it: on macOS the setting is system-wide.
+    ///
+    /// # Platform
+    ///
+    /// Linux, Windows and macOS.
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    pub fn ipv6_dad(mut self, enable: bool) -> Self {
+        self.ipv6_dad = Some(enable);
+        self
+    }
+    /// Enables or disables IPv6 privacy extensions (temporary addresses) on the interface,
+    /// before any address is added.
+    ///
+    /// See `set_ipv6_privacy` of the device for how each platform applies it: on Windows and
+    /// macOS the setting is system-wide.
+    ///
+    /// # Platform
+    ///
+    /// Linux, Windows and macOS.
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "windows",
+        target_os = "macos"
+    ))]
+    pub fn ipv6_privacy(mut self, enable: bool) -> Self {
+        self.ipv6_privacy = Some(enable);
+        self
+    }
     /// Sets the operating layer (L2 or L3) for the device.
     ///
     /// * L2 corresponds to TAP
@@ -1043,11 +2111,58 @@ impl DeviceBuilder {
         self
     }
     /// Sets the `wintun.dll` file path on Windows.
+    ///
+    /// A bare file name is looked up next to the executable, also in the `<arch>` and
+    /// `wintun/bin/<arch>` directories of the official distribution, where `<arch>` is
+    /// `amd64`, `x86`, `arm64` or `arm`. With the `embed-wintun` feature, the embedded DLL is
+    /// used unless a file is set here.
     #[cfg(windows)]
     pub fn wintun_file(mut self, wintun_file: String) -> Self {
         self.wintun_file = Some(wintun_file);
         self
     }
+    /// Looks for `wintun.dll` only in `paths`, in order, instead of next to the executable.
+    ///
+    /// Each path is a directory containing the file set with [`wintun_file`](Self::wintun_file),
+    /// or the DLL itself. The first DLL that passes the architecture and
+    /// [`wintun_trust`](Self::wintun_trust) checks is loaded, so a list can fall back from a
+    /// system-wide installation to a copy shipped with the application. The DLL search order,
+    /// including the working directory, is never consulted.
+    #[cfg(windows)]
+    pub fn wintun_search_paths(mut self, paths: Vec<std::path::PathBuf>) -> Self {
+        self.wintun_search_paths = Some(paths);
+        self
+    }
+    /// Checks `wintun.dll` before it is loaded on Windows, e.g. with
+    /// [`WintunTrust::official`](crate::WintunTrust::official) for the signature of the
+    /// official builds.
+    ///
+    /// Unless the trust is [`WintunTrust::Any`](crate::WintunTrust::Any), the default, a DLL
+    /// is only loaded from an explicit path: a file name not found next to the executable
+    /// (or in [`wintun_search_paths`](Self::wintun_search_paths)) is not looked up in the
+    /// working directory or `PATH`. The DLL is held open while it is checked and loaded, so it
+    /// cannot be swapped in between. A DLL that fails the check makes the build fail with
+    /// [`Error::UntrustedDriver`](crate::Error::UntrustedDriver).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(windows)]
+    /// # {
+    /// use tun_rs::{DeviceBuilder, WintunTrust};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .wintun_search_paths(vec![r"C:\Program Files\MyVpn\bin".into()])
+    ///     .wintun_trust(WintunTrust::official())
+    ///     .build_sync()?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(windows)]
+    pub fn wintun_trust(mut self, trust: crate::WintunTrust) -> Self {
+        self.wintun_trust = Some(trust);
+        self
+    }
     /// Sets the ring capacity on Windows.
     /// This specifies the capacity of the packet ring buffer in bytes.
     /// By default, the ring capacity is set to `0x20_0000` (2 MB).
@@ -1088,6 +2203,12 @@ impl DeviceBuilder {
         self.metric = Some(metric);
         self
     }
+    /// Sets the IPv6 routing metric on Windows, overriding [`metric`](Self::metric) for IPv6.
+    #[cfg(windows)]
+    pub fn metric_v6(mut self, metric: u16) -> Self {
+        self.metric_v6 = Some(metric);
+        self
+    }
     /// Whether to call `WintunDeleteDriver` to remove the driver.
     /// Default: false.
     /// # Note
@@ -1097,13 +2218,25 @@ impl DeviceBuilder {
         self.delete_driver = Some(delete_driver);
         self
     }
+    /// Forces the Windows 7 compatibility mode of Wintun on or off.
+    ///
+    /// By default the mode follows the OS version, so one binary runs on both legacy and
+    /// current systems. In this mode orphaned adapters are recognised by their owning process
+    /// instead of their device status, and the adapter description cannot be set: a
+    /// [`description`](Self::description) alongside a [`tunnel_type`](Self::tunnel_type) is
+    /// skipped with a warning. `capabilities()` reports the mode that is in effect.
+    #[cfg(windows)]
+    pub fn compat_win7(mut self, compat_win7: bool) -> Self {
+        self.compat_win7 = Some(compat_win7);
+        self
+    }
     /// Sets the transmit queue length on Linux.
     #[cfg(target_os = "linux")]
     pub fn tx_queue_len(mut self, tx_queue_len: u32) -> Self {
         self.tx_queue_len = Some(tx_queue_len);
         self
     }
-    /// Enables TUN offloads on Linux.
+    /// Enables TUN/TAP offloads on Linux.
     /// After enabling, use `recv_multiple`/`send_multiple` for data transmission.
     #[cfg(target_os = "linux")]
     pub fn offload(mut self, offload: bool) -> Self {
@@ -1116,8 +2249,83 @@ impl DeviceBuilder {
         self.multi_queue = Some(multi_queue);
         self
     }
+    /// Enables NAPI receive processing (`IFF_NAPI`) on Linux.
+    /// Ignored with a warning if the kernel does not support it.
+    #[cfg(target_os = "linux")]
+    pub fn napi(mut self, napi: bool) -> Self {
+        self.napi = Some(napi);
+        self
+    }
+    /// Enables NAPI fragment processing (`IFF_NAPI_FRAGS`) on Linux, implies `napi`.
+    /// Only available in TAP mode; ignored with a warning otherwise.
+    #[cfg(target_os = "linux")]
+    pub fn napi_frags(mut self, napi_frags: bool) -> Self {
+        self.napi_frags = Some(napi_frags);
+        self
+    }
+    /// Loads `prog` as the queue steering program of a multi-queue device on Linux, see
+    /// [`set_steering_ebpf`](crate::DeviceImpl::set_steering_ebpf).
+    ///
+    /// The program is set right after the device is created, before any queue is attached
+    /// or any packet is read. Not available on macvtap and ipvtap devices, and not forwarded
+    /// to a broker.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    pub fn steering_ebpf(mut self, prog: std::os::fd::OwnedFd) -> Self {
+        self.steering_ebpf = Some(prog);
+        self
+    }
+    /// Creates a macvtap device on the interface `lower` instead of a TAP device, on Linux.
+    ///
+    /// Frames written to a macvtap device are sent out of `lower` and frames `lower` receives
+    /// for the MAC address of the device are read from it, with the same API as a TAP
+    /// device. The link is created over netlink, named after [`name`](Self::name) or the next
+    /// free `macvtapN`, and deleted when the device is dropped. With
+    /// [`attach_existing`](Self::attach_existing) the existing link of that name is opened
+    /// instead. Implies [`Layer::L2`]; multi-queue, NAPI and packet information are not
+    /// available. Requires `CAP_NET_ADMIN`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    /// # {
+    /// use tun_rs::{DeviceBuilder, MacvtapMode};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .macvtap("eth0", MacvtapMode::Bridge)
+    ///     .build_sync()?;
+    /// let mut buf = [0u8; 1514];
+    /// let len = dev.recv(&mut buf)?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn macvtap(mut self, lower: impl Into<String>, mode: crate::MacvtapMode) -> Self {
+        use crate::platform::linux::vtap::{Vtap, VtapKind};
+        self.layer = Some(Layer::L2);
+        self.vtap = Some(Vtap {
+            lower: lower.into(),
+            kind: VtapKind::Macvtap(mode),
+        });
+        self
+    }
+    /// Creates an ipvtap device on the interface `lower` instead of a TAP device, on Linux.
+    ///
+    /// Like [`macvtap`](Self::macvtap), except that the device shares the MAC address of
+    /// `lower` and frames are told apart by their IP addresses. The link is named after
+    /// [`name`](Self::name) or the next free `ipvtapN`.
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn ipvtap(mut self, lower: impl Into<String>, mode: crate::IpvtapMode) -> Self {
+        use crate::platform::linux::vtap::{Vtap, VtapKind};
+        self.layer = Some(Layer::L2);
+        self.vtap = Some(Vtap {
+            lower: lower.into(),
+            kind: VtapKind::Ipvtap(mode),
+        });
+        self
+    }
     /// Enables or disables packet information for the network driver(TUN)
-    /// on macOS, Linux, freebsd, openbsd, netbsd.
+    /// on macOS, Linux, freebsd, openbsd, netbsd and Windows.
     ///
     /// This option is disabled by default (`false`).
     /// # Note
@@ -1125,12 +2333,17 @@ impl DeviceBuilder {
     /// The elimination of the packet information on macOS according to this setting
     /// is processed by this library.
     /// The set value `v` can be retrieved by `ignore_packet_info`, the returned value is `!v`.
+    ///
+    /// Wintun has no packet information either; on Windows the library prepends and strips
+    /// a `tun_pi` header laid out as on Linux, see [`framing::TunPi`](crate::framing::TunPi).
+    /// It is ignored for TAP devices.
     #[cfg(any(
         target_os = "macos",
         target_os = "linux",
         target_os = "freebsd",
         target_os = "openbsd",
-        target_os = "netbsd"
+        target_os = "netbsd",
+        target_os = "windows"
     ))]
     pub fn packet_information(mut self, packet_information: bool) -> Self {
         self.packet_information = Some(packet_information);
@@ -1143,6 +2356,94 @@ impl DeviceBuilder {
         self.peer_feth = Some(peer_feth.into());
         self
     }
+    /// Creates a TAP device from the `feth` pair with the given unit numbers: `feth<dev>`
+    /// carries the addresses and `feth<peer>` is used for I/O.
+    ///
+    /// Sets the name, [`peer_feth`](Self::peer_feth) and [`Layer::L2`](crate::Layer::L2).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "macos")]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new().feth_units(10, 11).build_sync()?;
+    /// assert_eq!(dev.name()?, "feth10");
+    /// assert_eq!(dev.peer_name()?, "feth11");
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(target_os = "macos")]
+    pub fn feth_units(mut self, dev: u32, peer: u32) -> Self {
+        self.dev_name = Some(format!("feth{dev}"));
+        self.peer_feth = Some(format!("feth{peer}"));
+        self.layer = Some(Layer::L2);
+        self
+    }
+    /// Attaches to the existing `feth` pair `dev`/`peer`, e.g. after a supervised restart,
+    /// instead of creating one.
+    ///
+    /// Both interfaces must exist; they are peered again only if they are not peered with
+    /// each other already. The pair is left in place when the device is dropped, as with
+    /// [`attach_existing`](Self::attach_existing), which this implies together with
+    /// [`Layer::L2`](crate::Layer::L2). The configuration of the pair is kept as it is.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "macos")]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .adopt_existing_pair("feth10", "feth11")
+    ///     .build_sync()?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(target_os = "macos")]
+    pub fn adopt_existing_pair<D: Into<String>, P: Into<String>>(
+        mut self,
+        dev: D,
+        peer: P,
+    ) -> Self {
+        self.dev_name = Some(dev.into());
+        self.peer_feth = Some(peer.into());
+        self.layer = Some(Layer::L2);
+        self.attach_existing = Some(true);
+        self
+    }
+    /// Available on Layer::L2;
+    /// sets the size of the BPF buffer `feth` frames are read from, 128 KiB by default.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_buffer_size(mut self, size: usize) -> Self {
+        self.bpf_buffer_size = Some(size);
+        self
+    }
+    /// Available on Layer::L2;
+    /// sets whether BPF reads return as soon as a frame arrives, `true` by default.
+    /// With `false` frames are batched until the buffer fills or the `bpf_read_timeout` expires.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_immediate(mut self, immediate: bool) -> Self {
+        self.bpf_immediate = Some(immediate);
+        self
+    }
+    /// Available on Layer::L2;
+    /// sets how long a non-immediate BPF read waits for the buffer to fill.
+    #[cfg(target_os = "macos")]
+    pub fn bpf_read_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.bpf_read_timeout = Some(timeout);
+        self
+    }
+    /// Available on Layer::L2;
+    /// sets `SO_SNDBUF` of the NDRV socket `feth` frames are written to, so that bursts
+    /// are queued instead of failing with `ENOBUFS`.
+    #[cfg(target_os = "macos")]
+    pub fn ndrv_send_buffer_size(mut self, size: usize) -> Self {
+        self.ndrv_send_buffer_size = Some(size);
+        self
+    }
     /// If true (default), the program will automatically add or remove routes on macOS or FreeBSD to provide consistent routing behavior across all platforms.
     /// If false, the program will not modify or manage routes in any way, allowing the system to handle all routing natively.
     /// Set this to be false to obtain the platform's default routing behavior.
@@ -1164,14 +2465,139 @@ impl DeviceBuilder {
         self.reuse_dev = Some(reuse);
         self
     }
-    /// Only works in TAP mode.
+    /// Only works in TAP mode on macOS and Windows.
     /// If true, the `feth` device will be kept after the program exits;
     /// if false (default), the device will be destroyed automatically.
-    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    ///
+    /// On Linux this sets or clears the persistent flag of the device in both modes, so
+    /// `persist(false)` also clears a flag left behind by a previous process.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos"))
+    ))]
     pub fn persist(mut self, persist: bool) -> Self {
         self.persist = Some(persist);
         self
     }
+    /// Clears the persistent flag of the device when the last of its queues is dropped, so
+    /// the device is destroyed with the fd even if it was made persistent while running.
+    ///
+    /// This applies only to devices this process created, not to ones joined with
+    /// [`attach_existing`](Self::attach_existing). The default
+    /// [`CleanupPolicy`](crate::CleanupPolicy) of such a device has `destroy_device` set
+    /// despite `persist(true)`, and the flag is only cleared while the policy still has it:
+    /// after [`leak`](crate::SyncDevice::leak), or with a policy that keeps the device, the
+    /// device outlives the process. A device handed over with `into_raw_fd` is left alone.
+    /// It costs one ioctl on drop.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    /// # {
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// // Persistent while the process runs, so a restarted worker can attach to it,
+    /// // but gone once the process shuts down cleanly.
+    /// let dev = DeviceBuilder::new()
+    ///     .name("tun-worker")
+    ///     .persist(true)
+    ///     .unpersist_on_drop(true)
+    ///     .build_sync()?;
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn unpersist_on_drop(mut self, unpersist_on_drop: bool) -> Self {
+        self.unpersist_on_drop = Some(unpersist_on_drop);
+        self
+    }
+    /// Sets what a receive does with a packet larger than the buffer.
+    /// By default the packet is dropped and the receive fails, see [`TruncatePolicy`].
+    /// On macOS only TAP mode is affected.
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    pub fn truncate_policy(mut self, truncate_policy: TruncatePolicy) -> Self {
+        self.truncate_policy = Some(truncate_policy);
+        self
+    }
+    /// Attaches to an existing interface with the configured name instead of creating a new one.
+    ///
+    /// This is intended for hot-standby takeover: a new process attaches to the interface
+    /// owned by (or left behind by) the previous process, and the interface together with
+    /// its addresses and routes is left untouched. When attaching, MTU, addresses, MAC address
+    /// and enable state set on the builder are **not** applied.
+    ///
+    /// A name must be set with [`name`](Self::name), and the interface must already exist,
+    /// otherwise an error of kind [`io::ErrorKind::NotFound`] is returned.
+    ///
+    /// # Platform
+    ///
+    /// - **Linux**: attaches to a TUN/TAP interface (e.g. one made with
+    ///   [`persist`](crate::SyncDevice::persist)) and marks it persistent, so it also survives
+    ///   this process.
+    /// - **Windows**: reopens the existing Wintun adapter, or the tap-windows adapter with
+    ///   `persist` implied. Wintun removes an adapter when the process that created it exits,
+    ///   so the new process must attach while the old one is still running.
+    /// - **macOS**: supported for `feth` (L2) pairs, with `reuse_dev` and `persist` implied;
+    ///   without [`peer_feth`](Self::peer_feth) the current peer of the device is used, see
+    ///   also `adopt_existing_pair`.
+    ///   A `utun` is destroyed together with its fd, so for L3 hand the fd over instead
+    ///   (see [`send_fd`](crate::send_fd)/[`recv_fd`](crate::recv_fd)).
+    /// - **FreeBSD/OpenBSD/NetBSD**: not supported (the device node is exclusive);
+    ///   hand the fd over instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// // In the new process, take over `tun0` from the old one.
+    /// let dev = DeviceBuilder::new()
+    ///     .name("tun0")
+    ///     .attach_existing(true)
+    ///     .build_sync()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn attach_existing(mut self, attach_existing: bool) -> Self {
+        self.attach_existing = Some(attach_existing);
+        self
+    }
+    /// Sets what is undone when the device is dropped, see
+    /// [`CleanupPolicy`](crate::CleanupPolicy).
+    ///
+    /// By default the device is destroyed, except that persistent and attached devices are
+    /// left alone ([`CleanupPolicy::NONE`](crate::CleanupPolicy::NONE)). The policy can be
+    /// changed later with [`set_cleanup_policy`](crate::SyncDevice::set_cleanup_policy).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tun_rs::{CleanupPolicy, DeviceBuilder};
+    ///
+    /// // Keep the device for the next run, but take its addresses and routes down.
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .cleanup_policy(CleanupPolicy {
+    ///         remove_addresses: true,
+    ///         remove_routes: true,
+    ///         destroy_device: false,
+    ///     })
+    ///     .build_sync()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(any(
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    ))]
+    pub fn cleanup_policy(mut self, policy: crate::CleanupPolicy) -> Self {
+        self.cleanup_policy = Some(policy);
+        self
+    }
     /// Enables or disables the network interface upon creation.
     ///
     /// By default, newly created TUN/TAP devices are enabled (brought up).
@@ -1242,13 +2668,93 @@ impl DeviceBuilder {
         self.enabled = None;
         self
     }
+    /// Builds the device in the privileged broker listening at `path` instead of in this
+    /// process, which then needs no privileges of its own. See [`broker`](crate::broker).
+    ///
+    /// Only the name, MTU, addresses, layer, enabled state and packet information are
+    /// forwarded to the broker.
+    /// Building fails with [`io::ErrorKind::InvalidInput`] if the name is empty or contains
+    /// whitespace or control characters.
+    #[cfg(all(
+        feature = "broker",
+        any(
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        )
+    ))]
+    pub fn via_broker<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.broker = Some(path.into());
+        self
+    }
+    #[cfg(all(
+        feature = "broker",
+        any(
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        )
+    ))]
+    fn broker_request(&mut self) -> io::Result<crate::broker::BrokerRequest> {
+        let mut request = crate::broker::BrokerRequest {
+            name: self.take_name(),
+            mtu: self.mtu,
+            layer: self.layer,
+            enabled: self.enabled,
+            packet_information: self.packet_information,
+            mac_addr: self.mac_addr,
+            ..Default::default()
+        };
+        if request
+            .name
+            .as_deref()
+            .is_some_and(|name| !crate::broker::valid_name(name))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid device name",
+            ));
+        }
+        if let Some((address, prefix, destination)) = self.ipv4.take() {
+            request.ipv4 = Some((address?, prefix?, destination.transpose()?));
+        }
+        for (address, prefix) in self.ipv4_extra.take().unwrap_or_default() {
+            request.ipv4_extra.push((address?, prefix?));
+        }
+        for (address, prefix) in self.ipv6.take().unwrap_or_default() {
+            request.ipv6.push((address?, prefix?));
+        }
+        Ok(request)
+    }
+    /// The name set with `name`, or the one picked for the `name_hint`.
+    fn take_name(&mut self) -> Option<String> {
+        if let Some(name) = self.dev_name.take() {
+            return Some(name);
+        }
+        let hint = self.name_hint.take()?;
+        closest_name(&hint, self.layer.unwrap_or_default())
+    }
     pub(crate) fn build_config(&mut self) -> DeviceConfig {
         DeviceConfig {
-            dev_name: self.dev_name.take(),
+            dev_name: self.take_name(),
             #[cfg(windows)]
             description: self.description.take(),
+            #[cfg(windows)]
+            tunnel_type: self.tunnel_type.take(),
             #[cfg(target_os = "macos")]
             peer_feth: self.peer_feth.take(),
+            #[cfg(target_os = "macos")]
+            bpf_buffer_size: self.bpf_buffer_size.take(),
+            #[cfg(target_os = "macos")]
+            bpf_immediate: self.bpf_immediate.take(),
+            #[cfg(target_os = "macos")]
+            bpf_read_timeout: self.bpf_read_timeout.take(),
+            #[cfg(target_os = "macos")]
+            ndrv_send_buffer_size: self.ndrv_send_buffer_size.take(),
             #[cfg(any(
                 target_os = "macos",
                 target_os = "freebsd",
@@ -1258,8 +2764,24 @@ impl DeviceBuilder {
             associate_route: self.associate_route,
             #[cfg(any(target_os = "macos", target_os = "windows", target_os = "netbsd"))]
             reuse_dev: self.reuse_dev,
-            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "windows",
+                all(target_os = "linux", not(target_env = "ohos"))
+            ))]
             persist: self.persist,
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            unpersist_on_drop: self.unpersist_on_drop,
+            attach_existing: self.attach_existing,
+            #[cfg(any(
+                target_os = "windows",
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "netbsd",
+            ))]
+            cleanup_policy: self.cleanup_policy,
             layer: self.layer.take(),
             #[cfg(windows)]
             device_guid: self.device_guid.take(),
@@ -1268,10 +2790,16 @@ impl DeviceBuilder {
             #[cfg(windows)]
             wintun_file: self.wintun_file.take(),
             #[cfg(windows)]
+            wintun_search_paths: self.wintun_search_paths.take(),
+            #[cfg(windows)]
+            wintun_trust: self.wintun_trust.take(),
+            #[cfg(windows)]
             ring_capacity: self.ring_capacity.take(),
             #[cfg(windows)]
             delete_driver: self.delete_driver.take(),
             #[cfg(windows)]
+            compat_win7: self.compat_win7.take(),
+            #[cfg(windows)]
             mac_address: self.mac_addr.map(|v| {
                 use std::fmt::Write;
                 v.iter()
@@ -1280,35 +2808,313 @@ impl DeviceBuilder {
                         s
                     })
             }),
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            truncate_policy: self.truncate_policy.take(),
             #[cfg(any(
                 target_os = "macos",
                 target_os = "linux",
                 target_os = "freebsd",
                 target_os = "openbsd",
-                target_os = "netbsd"
+                target_os = "netbsd",
+                target_os = "windows"
             ))]
             packet_information: self.packet_information.take(),
             #[cfg(target_os = "linux")]
             offload: self.offload.take(),
             #[cfg(target_os = "linux")]
             multi_queue: self.multi_queue.take(),
+            #[cfg(target_os = "linux")]
+            napi: self.napi.take(),
+            #[cfg(target_os = "linux")]
+            napi_frags: self.napi_frags.take(),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+            steering_ebpf: self.steering_ebpf.take().map(std::sync::Arc::new),
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            vtap: self.vtap.take(),
         }
     }
-    pub(crate) fn config(self, device: &DeviceImpl) -> io::Result<()> {
+    /// The backend `build_sync` will create for the configured layer.
+    fn expected_backend(&self) -> crate::BackendKind {
+        let _l2 = matches!(self.layer, Some(Layer::L2));
+        #[cfg(target_os = "linux")]
+        return crate::BackendKind::LinuxTun;
+        #[cfg(windows)]
+        return if _l2 {
+            crate::BackendKind::TapWindows
+        } else {
+            crate::BackendKind::Wintun
+        };
+        #[cfg(target_os = "macos")]
+        return if _l2 {
+            crate::BackendKind::Feth
+        } else {
+            crate::BackendKind::Utun
+        };
+        #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+        return if _l2 {
+            crate::BackendKind::BsdTap
+        } else {
+            crate::BackendKind::BsdTun
+        };
+    }
+    fn check_mtu(&self) -> io::Result<()> {
+        match self.mtu_issues().into_iter().next() {
+            Some(BuilderIssue::MtuOutOfRange { mtu, min, max }) => {
+                Err(crate::Error::MtuOutOfRange { mtu, min, max }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+    fn mtu_issues(&self) -> Vec<BuilderIssue> {
+        let (min, max) = self.expected_backend().mtu_limits();
+        let mut issues = Vec::new();
+        if let Some(mtu) = self.mtu.filter(|mtu| !(min..=max).contains(mtu)) {
+            issues.push(BuilderIssue::MtuOutOfRange { mtu, min, max });
+        }
+        // The IPv6 minimum link MTU (RFC 8200).
+        #[cfg(windows)]
+        if let Some(mtu) = self
+            .mtu_v6
+            .filter(|mtu| !(min.max(1280)..=max).contains(mtu))
+        {
+            issues.push(BuilderIssue::MtuOutOfRange {
+                mtu,
+                min: min.max(1280),
+                max,
+            });
+        }
+        issues
+    }
+    /// Checks the settings for the current platform without creating a device, and returns
+    /// every problem found.
+    ///
+    /// This covers the device name rules of the platform, the MTU range of the backend, the
+    /// Wintun ring capacity, the addresses, `mac_addr` on L3 devices and settings that cannot
+    /// be combined. An empty list does not guarantee that building succeeds: the name may be
+    /// taken, privileges may be missing, or the driver may be absent.
+    ///
+    /// # Example
+    /// ```
+    /// use tun_rs::{BuilderIssue, DeviceBuilder};
+    ///
+    /// let builder = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).mtu(20);
+    /// for issue in builder.validate() {
+    ///     eprintln!("invalid configuration: {issue}");
+    /// }
+    /// assert!(matches!(
+    ///     builder.validate()[..],
+    ///     [BuilderIssue::MtuOutOfRange { mtu: 20, .. }]
+    /// ));
+    /// ```
+    pub fn validate(&self) -> Vec<BuilderIssue> {
+        let layer = self.layer.unwrap_or_default();
+        let l2 = layer == Layer::L2;
+        let mut issues = Vec::new();
+        let conflict = |setting, reason| BuilderIssue::Conflict { setting, reason };
+
+        if let Some(name) = &self.dev_name {
+            issues.extend(name_issue(name, layer));
+        }
+        #[cfg(target_os = "macos")]
+        if let Some(peer) = &self.peer_feth {
+            if !l2 {
+                issues.push(conflict("peer_feth", "only applies to L2 devices"));
+            }
+            issues.extend(name_issue(peer, Layer::L2));
+        }
+        #[cfg(target_os = "macos")]
+        if !l2 {
+            if self.bpf_buffer_size.is_some() {
+                issues.push(conflict("bpf_buffer_size", "only applies to L2 devices"));
+            }
+            if self.bpf_immediate.is_some() {
+                issues.push(conflict("bpf_immediate", "only applies to L2 devices"));
+            }
+            if self.bpf_read_timeout.is_some() {
+                issues.push(conflict("bpf_read_timeout", "only applies to L2 devices"));
+            }
+            if self.ndrv_send_buffer_size.is_some() {
+                issues.push(conflict(
+                    "ndrv_send_buffer_size",
+                    "only applies to L2 devices",
+                ));
+            }
+        }
+        if self.attach_existing.unwrap_or(false) {
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+            issues.push(conflict(
+                "attach_existing",
+                "not supported on this platform, pass the device fd instead",
+            ));
+            if self.dev_name.is_none() {
+                issues.push(conflict("attach_existing", "requires a device name"));
+            }
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            if self.unpersist_on_drop.unwrap_or(false) {
+                issues.push(conflict(
+                    "unpersist_on_drop",
+                    "only applies to devices this process creates",
+                ));
+            }
+        } else {
+            issues.extend(self.mtu_issues());
+        }
+        #[cfg(windows)]
+        {
+            use crate::platform::windows::tun::{MAX_RING_CAPACITY, MIN_RING_CAPACITY};
+            if let Some(capacity) = self.ring_capacity {
+                if l2 {
+                    issues.push(conflict("ring_capacity", "only applies to L3 devices"));
+                } else if !(MIN_RING_CAPACITY..=MAX_RING_CAPACITY).contains(&capacity) {
+                    issues.push(BuilderIssue::RingCapacity {
+                        capacity,
+                        min: MIN_RING_CAPACITY,
+                        max: MAX_RING_CAPACITY,
+                    });
+                }
+            }
+            if l2 && self.description.is_some() {
+                issues.push(conflict("description", "only applies to L3 devices"));
+            }
+            if l2 && self.tunnel_type.is_some() {
+                issues.push(conflict("tunnel_type", "only applies to L3 devices"));
+            }
+            if l2 && self.compat_win7.is_some() {
+                issues.push(conflict("compat_win7", "only applies to L3 devices"));
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if !l2 && self.napi_frags.unwrap_or(false) {
+            issues.push(conflict("napi_frags", "only applies to L2 devices"));
+        }
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if self.vtap.is_some() {
+            if !l2 {
+                issues.push(conflict(
+                    "layer",
+                    "macvtap and ipvtap devices are L2 devices",
+                ));
+            }
+            if self.multi_queue.unwrap_or(false) {
+                issues.push(conflict(
+                    "multi_queue",
+                    "not available on macvtap and ipvtap devices",
+                ));
+            }
+            if self.napi.unwrap_or(false) || self.napi_frags.unwrap_or(false) {
+                issues.push(conflict(
+                    "napi",
+                    "not available on macvtap and ipvtap devices",
+                ));
+            }
+            if self.packet_information.unwrap_or(false) {
+                issues.push(conflict(
+                    "packet_information",
+                    "not available on macvtap and ipvtap devices",
+                ));
+            }
+            #[cfg(feature = "ebpf")]
+            if self.steering_ebpf.is_some() {
+                issues.push(conflict(
+                    "steering_ebpf",
+                    "not available on macvtap and ipvtap devices",
+                ));
+            }
+        }
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "macos",
+            target_os = "netbsd"
+        ))]
+        if !l2 && self.mac_addr.is_some() {
+            issues.push(BuilderIssue::MacAddrOnL3);
+        }
+
+        let invalid = |setting, e: &io::Error| BuilderIssue::InvalidAddress {
+            setting,
+            reason: e.to_string(),
+        };
+        if let Some((address, prefix, destination)) = &self.ipv4 {
+            let destination = destination.as_ref().and_then(|d| d.as_ref().err());
+            for e in [address.as_ref().err(), prefix.as_ref().err(), destination]
+                .into_iter()
+                .flatten()
+            {
+                issues.push(invalid("ipv4", e));
+            }
+        }
+        for (address, prefix) in self.ipv4_extra.iter().flatten() {
+            for e in [address.as_ref().err(), prefix.as_ref().err()]
+                .into_iter()
+                .flatten()
+            {
+                issues.push(invalid("ipv4", e));
+            }
+        }
+        let ipv6_issues = |setting, address: &io::Result<Ipv6Addr>, prefix: &io::Result<u8>| {
+            let mut issues = Vec::new();
+            if let Err(e) = address {
+                issues.push(invalid(setting, e));
+            }
+            match prefix {
+                Err(e) => issues.push(invalid(setting, e)),
+                Ok(prefix) if *prefix > 128 => issues.push(BuilderIssue::InvalidAddress {
+                    setting,
+                    reason: format!("prefix length {prefix} exceeds 128"),
+                }),
+                Ok(_) => {}
+            }
+            issues
+        };
+        for (address, prefix) in self.ipv6.iter().flatten() {
+            issues.extend(ipv6_issues("ipv6", address, prefix));
+        }
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        {
+            for (address, prefix, _) in &self.ipv6_flags {
+                issues.extend(ipv6_issues("ipv6_with", address, prefix));
+            }
+            if let Some(broadcast) = &self.ipv4_broadcast {
+                if let Err(e) = broadcast {
+                    issues.push(invalid("ipv4_broadcast", e));
+                }
+                match &self.ipv4 {
+                    None => issues.push(conflict("ipv4_broadcast", "requires an ipv4 address")),
+                    Some((_, _, Some(_))) => issues.push(conflict(
+                        "ipv4_broadcast",
+                        "cannot be used with a point-to-point destination",
+                    )),
+                    Some(_) => {}
+                }
+            }
+        }
+        issues
+    }
+    /// Resolves the settings to apply to a new device, failing before anything is applied if
+    /// an address cannot be parsed.
+    fn config_steps(self) -> io::Result<Vec<ConfigStep>> {
+        let mut steps = Vec::new();
         if let Some(mtu) = self.mtu {
-            device.set_mtu(mtu)?;
+            steps.push(ConfigStep::Mtu(mtu));
         }
         #[cfg(windows)]
         if let Some(mtu) = self.mtu_v6 {
-            device.set_mtu_v6(mtu)?;
+            steps.push(ConfigStep::MtuV6(mtu));
         }
         #[cfg(windows)]
         if let Some(metric) = self.metric {
-            device.set_metric(metric)?;
+            steps.push(ConfigStep::Metric(metric));
+        }
+        #[cfg(windows)]
+        if let Some(metric) = self.metric_v6 {
+            steps.push(ConfigStep::MetricV6(metric));
         }
         #[cfg(target_os = "linux")]
         if let Some(tx_queue_len) = self.tx_queue_len {
-            device.set_tx_queue_len(tx_queue_len)?;
+            steps.push(ConfigStep::TxQueueLen(tx_queue_len));
         }
         #[cfg(any(
             target_os = "linux",
@@ -1318,31 +3124,148 @@ impl DeviceBuilder {
             target_os = "netbsd"
         ))]
         if let Some(mac_addr) = self.mac_addr {
-            device.set_mac_address(mac_addr)?;
+            steps.push(ConfigStep::MacAddress(mac_addr));
+        }
+        // Before the addresses, which would otherwise go through DAD first.
+        #[cfg(any(
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "windows",
+            target_os = "macos"
+        ))]
+        if let Some(dad) = self.ipv6_dad {
+            steps.push(ConfigStep::Ipv6Dad(dad));
+        }
+        #[cfg(any(
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "windows",
+            target_os = "macos"
+        ))]
+        if let Some(privacy) = self.ipv6_privacy {
+            steps.push(ConfigStep::Ipv6Privacy(privacy));
         }
 
-        if let Some((address, prefix, destination)) = self.ipv4 {
-            let prefix = prefix?;
-            let address = address?;
-            let destination = destination.transpose()?;
-            device.set_network_address(address, prefix, destination)?;
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        let ipv4 = match (self.ipv4, self.ipv4_broadcast) {
+            (Some((address, prefix, None)), Some(broadcast)) => {
+                steps.push(ConfigStep::Ipv4Broadcast {
+                    address: address?,
+                    prefix: prefix?,
+                    broadcast: broadcast?,
+                });
+                None
+            }
+            (ipv4, _) => ipv4,
+        };
+        #[cfg(not(all(target_os = "linux", not(target_env = "ohos"))))]
+        let ipv4 = self.ipv4;
+        if let Some((address, prefix, destination)) = ipv4 {
+            steps.push(ConfigStep::Ipv4 {
+                address: address?,
+                prefix: prefix?,
+                destination: destination.transpose()?,
+            });
+        }
+        if let Some(ipv4_extra) = self.ipv4_extra {
+            for (address, prefix) in ipv4_extra {
+                steps.push(ConfigStep::AddIpv4 {
+                    address: address?,
+                    prefix: prefix?,
+                });
+            }
         }
         if let Some(ipv6) = self.ipv6 {
             for (address, prefix) in ipv6 {
-                let prefix = prefix?;
-                let address = address?;
-                device.add_address_v6(address, prefix)?;
+                steps.push(ConfigStep::AddIpv6 {
+                    address: address?,
+                    prefix: prefix?,
+                });
             }
         }
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        for (address, prefix, flags) in self.ipv6_flags {
+            steps.push(ConfigStep::AddIpv6Flags {
+                address: address?,
+                prefix: prefix?,
+                flags,
+            });
+        }
         if let Some(enabled) = self.enabled {
-            device.enabled(enabled)?;
+            steps.push(ConfigStep::Enabled(enabled));
+        }
+        Ok(steps)
+    }
+    /// Applies the configuration to a new device.
+    ///
+    /// If a step fails, the addresses added by the previous steps are removed again and
+    /// [`Error::Config`](crate::Error::Config) reports what had been applied.
+    pub(crate) fn config(self, device: &DeviceImpl) -> io::Result<()> {
+        let steps = self.config_steps()?;
+        let mut applied: Vec<ConfigStep> = Vec::with_capacity(steps.len());
+        for step in steps {
+            if let Err(source) = step.apply(device) {
+                crate::trace::dev_event!(debug, device, %step, error = %source, "config step failed");
+                for done in applied.iter().rev() {
+                    if let Err(e) = done.undo(device) {
+                        log::warn!("failed to roll back {done}: {e}");
+                    }
+                }
+                return Err(crate::Error::Config {
+                    failed: step,
+                    applied,
+                    source,
+                }
+                .into());
+            }
+            crate::trace::dev_event!(debug, device, %step, "config step applied");
+            applied.push(step);
         }
         Ok(())
     }
     /// Builds a synchronous device instance and applies all configuration parameters.
+    ///
+    /// If applying a parameter fails, the addresses already added are removed and the device
+    /// is closed, which deletes it unless it is persistent. The error carries an
+    /// [`Error::Config`](crate::Error::Config) listing the applied [`ConfigStep`]s.
     pub fn build_sync(mut self) -> io::Result<SyncDevice> {
+        #[cfg(all(
+            feature = "broker",
+            any(
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            )
+        ))]
+        if let Some(path) = self.broker.take() {
+            let request = self.broker_request()?;
+            return crate::broker::request_device(&path, &request);
+        }
+        let attach_existing = self.attach_existing.unwrap_or(false);
+        if attach_existing {
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "attach_existing is not supported on this platform, pass the device fd instead",
+            ));
+            #[allow(unreachable_code)]
+            if self.dev_name.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "attach_existing requires a device name",
+                ));
+            }
+        }
+        if !attach_existing {
+            self.check_mtu()?;
+        }
         let device = DeviceImpl::new(self.build_config())?;
-        self.config(&device)?;
+        #[cfg(feature = "tracing")]
+        device.init_span();
+        crate::trace::dev_event!(debug, device, attach_existing, "device opened");
+        if !attach_existing {
+            self.config(&device)?;
+        }
         Ok(SyncDevice(device))
     }
     /// Builds an asynchronous device instance.
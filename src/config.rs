@@ -0,0 +1,347 @@
+//! Declarative device definitions, see [`InterfaceConfig`].
+
+use std::io;
+use std::net::Ipv4Addr;
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::{DeviceBuilder, Layer};
+
+/// A device definition that can be loaded from a configuration file and turned into a
+/// [`DeviceBuilder`] with [`DeviceBuilder::from_config`].
+///
+/// Every field is optional and maps to the builder method of the same name. Platform specific
+/// options are grouped by platform and ignored on the other platforms, so that one file can
+/// describe the device everywhere.
+///
+/// With the `serde` feature the definition implements `Serialize` and `Deserialize`. Missing
+/// fields take their default, unknown fields are rejected, addresses are written in CIDR
+/// notation and the MAC address as `"aa:bb:cc:dd:ee:ff"`. In TOML:
+///
+/// ```toml
+/// name = "tun0"
+/// mtu = 1400
+/// ipv4 = "10.0.0.1/24"
+/// ipv6 = ["fd00::1/64"]
+///
+/// [linux]
+/// offload = true
+///
+/// [windows]
+/// metric = 5
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct InterfaceConfig {
+    /// See [`DeviceBuilder::name`].
+    pub name: Option<String>,
+    /// See [`DeviceBuilder::layer`].
+    pub layer: Option<Layer>,
+    /// See [`DeviceBuilder::mtu`].
+    pub mtu: Option<u16>,
+    /// The address and prefix of [`DeviceBuilder::ipv4`].
+    pub ipv4: Option<Ipv4Net>,
+    /// The destination of [`DeviceBuilder::ipv4`], only valid together with `ipv4`.
+    pub destination: Option<Ipv4Addr>,
+    /// See [`DeviceBuilder::ipv4_tuple`].
+    pub ipv4_extra: Vec<Ipv4Net>,
+    /// See [`DeviceBuilder::ipv6_tuple`].
+    pub ipv6: Vec<Ipv6Net>,
+    /// See `DeviceBuilder::mac_addr`, written as six hexadecimal octets separated by `:` or `-`.
+    pub mac_addr: Option<String>,
+    /// See [`DeviceBuilder::enable`].
+    pub enabled: Option<bool>,
+    /// See `DeviceBuilder::packet_information` (Linux, macOS and the BSDs).
+    pub packet_information: Option<bool>,
+    /// See `DeviceBuilder::associate_route` (macOS and the BSDs).
+    pub associate_route: Option<bool>,
+    /// Options only applied on Linux.
+    pub linux: LinuxConfig,
+    /// Options only applied on Windows.
+    pub windows: WindowsConfig,
+    /// Options only applied on macOS.
+    pub macos: MacosConfig,
+}
+
+/// Linux options of an [`InterfaceConfig`], each mapping to the builder method of the same name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct LinuxConfig {
+    /// See `DeviceBuilder::offload`.
+    pub offload: Option<bool>,
+    /// See `DeviceBuilder::multi_queue`.
+    pub multi_queue: Option<bool>,
+    /// See `DeviceBuilder::tx_queue_len`.
+    pub tx_queue_len: Option<u32>,
+    /// See `DeviceBuilder::napi`.
+    pub napi: Option<bool>,
+    /// See `DeviceBuilder::napi_frags`.
+    pub napi_frags: Option<bool>,
+}
+
+/// Windows options of an [`InterfaceConfig`], each mapping to the builder method of the same
+/// name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct WindowsConfig {
+    /// See `DeviceBuilder::description`.
+    pub description: Option<String>,
+    /// See `DeviceBuilder::tunnel_type`.
+    pub tunnel_type: Option<String>,
+    /// See `DeviceBuilder::mtu_v6`.
+    pub mtu_v6: Option<u16>,
+    /// See `DeviceBuilder::metric`.
+    pub metric: Option<u16>,
+    /// See `DeviceBuilder::metric_v6`.
+    pub metric_v6: Option<u16>,
+    /// See `DeviceBuilder::ring_capacity`.
+    pub ring_capacity: Option<u32>,
+    /// See `DeviceBuilder::wintun_file`.
+    pub wintun_file: Option<String>,
+    /// See `DeviceBuilder::wintun_search_paths`.
+    pub wintun_search_paths: Option<Vec<std::path::PathBuf>>,
+}
+
+/// macOS options of an [`InterfaceConfig`], each mapping to the builder method of the same name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct MacosConfig {
+    /// See `DeviceBuilder::peer_feth`.
+    pub peer_feth: Option<String>,
+}
+
+impl DeviceBuilder {
+    /// Creates a builder from a device definition, e.g. one loaded from a configuration file.
+    ///
+    /// Starts from [`DeviceBuilder::new`], so further settings can be chained before building.
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the MAC address cannot be parsed or a
+    /// destination is given without an IPv4 address.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, InterfaceConfig};
+    ///
+    /// let config = InterfaceConfig {
+    ///     name: Some("tun0".into()),
+    ///     ipv4: Some("10.0.0.1/24".parse().unwrap()),
+    ///     ..Default::default()
+    /// };
+    /// let dev = DeviceBuilder::from_config(config)?.build_sync()?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_config(config: InterfaceConfig) -> io::Result<Self> {
+        let mut builder = DeviceBuilder::new();
+        if let Some(name) = config.name {
+            builder = builder.name(name);
+        }
+        if let Some(layer) = config.layer {
+            builder = builder.layer(layer);
+        }
+        if let Some(mtu) = config.mtu {
+            builder = builder.mtu(mtu);
+        }
+        match (config.ipv4, config.destination) {
+            (Some(net), destination) => {
+                builder = builder.ipv4(net.addr(), net.prefix_len(), destination);
+            }
+            (None, Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "destination requires an ipv4 address",
+                ))
+            }
+            (None, None) => {}
+        }
+        if !config.ipv4_extra.is_empty() {
+            let addrs: Vec<_> = config
+                .ipv4_extra
+                .iter()
+                .map(|net| (net.addr(), net.prefix_len()))
+                .collect();
+            builder = builder.ipv4_tuple(&addrs);
+        }
+        if !config.ipv6.is_empty() {
+            let addrs: Vec<_> = config
+                .ipv6
+                .iter()
+                .map(|net| (net.addr(), net.prefix_len()))
+                .collect();
+            builder = builder.ipv6_tuple(&addrs);
+        }
+        if let Some(mac_addr) = config.mac_addr {
+            let _mac_addr = parse_mac_addr(&mac_addr)?;
+            #[cfg(any(
+                target_os = "windows",
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "macos",
+                target_os = "netbsd"
+            ))]
+            {
+                builder = builder.mac_addr(_mac_addr);
+            }
+        }
+        if let Some(enabled) = config.enabled {
+            builder = builder.enable(enabled);
+        }
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        if let Some(packet_information) = config.packet_information {
+            builder = builder.packet_information(packet_information);
+        }
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        if let Some(associate_route) = config.associate_route {
+            builder = builder.associate_route(associate_route);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let linux = config.linux;
+            if let Some(offload) = linux.offload {
+                builder = builder.offload(offload);
+            }
+            if let Some(multi_queue) = linux.multi_queue {
+                builder = builder.multi_queue(multi_queue);
+            }
+            if let Some(tx_queue_len) = linux.tx_queue_len {
+                builder = builder.tx_queue_len(tx_queue_len);
+            }
+            if let Some(napi) = linux.napi {
+                builder = builder.napi(napi);
+            }
+            if let Some(napi_frags) = linux.napi_frags {
+                builder = builder.napi_frags(napi_frags);
+            }
+        }
+        #[cfg(windows)]
+        {
+            let windows = config.windows;
+            if let Some(description) = windows.description {
+                builder = builder.description(description);
+            }
+            if let Some(tunnel_type) = windows.tunnel_type {
+                builder = builder.tunnel_type(tunnel_type);
+            }
+            if let Some(mtu) = windows.mtu_v6 {
+                builder = builder.mtu_v6(mtu);
+            }
+            if let Some(metric) = windows.metric {
+                builder = builder.metric(metric);
+            }
+            if let Some(metric) = windows.metric_v6 {
+                builder = builder.metric_v6(metric);
+            }
+            if let Some(ring_capacity) = windows.ring_capacity {
+                builder = builder.ring_capacity(ring_capacity);
+            }
+            if let Some(wintun_file) = windows.wintun_file {
+                builder = builder.wintun_file(wintun_file);
+            }
+            if let Some(paths) = windows.wintun_search_paths {
+                builder = builder.wintun_search_paths(paths);
+            }
+        }
+        #[cfg(target_os = "macos")]
+        if let Some(peer_feth) = config.macos.peer_feth {
+            builder = builder.peer_feth(peer_feth);
+        }
+        Ok(builder)
+    }
+}
+
+pub(crate) fn parse_mac_addr(s: &str) -> io::Result<[u8; 6]> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid MAC address {s:?}"),
+        )
+    };
+    let mut mac = [0u8; 6];
+    let mut octets = s.split([':', '-']);
+    for octet in &mut mac {
+        let part = octets.next().ok_or_else(invalid)?;
+        if part.len() != 2 {
+            return Err(invalid());
+        }
+        *octet = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    if octets.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac_addr() {
+        assert_eq!(
+            parse_mac_addr("00:11:22:aa:BB:cc").unwrap(),
+            [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]
+        );
+        assert_eq!(parse_mac_addr("00-11-22-33-44-55").unwrap()[5], 0x55);
+        for bad in [
+            "",
+            "00:11:22:33:44",
+            "00:11:22:33:44:55:66",
+            "0:11:22:33:44:55",
+        ] {
+            assert!(parse_mac_addr(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn destination_requires_ipv4() {
+        let config = InterfaceConfig {
+            destination: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ..Default::default()
+        };
+        assert!(DeviceBuilder::from_config(config).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let config: InterfaceConfig = serde_json::from_str(
+            r#"{
+                "name": "tun0",
+                "layer": "L3",
+                "mtu": 1400,
+                "ipv4": "10.0.0.1/24",
+                "ipv6": ["fd00::1/64"],
+                "linux": { "offload": true }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.name.as_deref(), Some("tun0"));
+        assert_eq!(config.layer, Some(Layer::L3));
+        assert_eq!(config.ipv4, Some("10.0.0.1/24".parse().unwrap()));
+        assert_eq!(config.ipv6, ["fd00::1/64".parse::<Ipv6Net>().unwrap()]);
+        assert_eq!(config.linux.offload, Some(true));
+        assert_eq!(config.windows, WindowsConfig::default());
+        assert!(serde_json::from_str::<InterfaceConfig>(r#"{"nmae": "tun0"}"#).is_err());
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<InterfaceConfig>(&json).unwrap(),
+            config
+        );
+    }
+}
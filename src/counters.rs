@@ -0,0 +1,147 @@
+/*!
+# Device Counters
+
+Packets and bytes the application exchanged with a device, counted in the process itself.
+The interface statistics of the operating system also include traffic of other processes
+and of the kernel (e.g. router solicitations), these only include what went through `recv`
+and `send` of this device and the queues cloned from it, which share the counters.
+
+Packets are counted by the methods the packet tap sees them on, as listed in the `pcap`
+module, on the sync and async device alike; a `try_` send that would block is not counted.
+On a Linux device built with `offload(true)`, `recv_multiple` counts each segment split from
+a GSO packet and `send_multiple` each segment the kernel splits a coalesced packet into,
+without the virtio network header. Counting takes a few relaxed atomic additions per packet;
+without the `counters` feature it is compiled out.
+
+# Example
+
+```no_run
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+let mut buf = [0u8; 1500];
+let len = dev.recv(&mut buf)?;
+dev.send(&buf[..len])?;
+let counters = dev.counters();
+println!(
+    "{} packets in, {} packets out",
+    counters.rx_packets, counters.tx_packets
+);
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A snapshot of the counters of a device, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DeviceCounters {
+    /// Packets received.
+    pub rx_packets: u64,
+    /// Bytes received, as copied into the buffers of the caller.
+    pub rx_bytes: u64,
+    /// Packets received that did not fit into the buffer: cut under
+    /// `TruncatePolicy::Truncate`, or flagged as truncated in the packet information header
+    /// returned by `recv_with_meta`.
+    pub rx_truncated: u64,
+    /// Packets sent.
+    pub tx_packets: u64,
+    /// Bytes sent, including any packet information or Ethernet header.
+    pub tx_bytes: u64,
+    /// Packets that failed to send.
+    pub tx_errors: u64,
+}
+
+/// Shared counters of a device.
+#[derive(Clone, Default)]
+pub(crate) struct Counters(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    rx_truncated: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_errors: AtomicU64,
+}
+
+impl Counters {
+    #[inline]
+    pub(crate) fn received(&self, len: usize) {
+        self.0.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.0.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    #[inline]
+    pub(crate) fn truncated(&self) {
+        self.0.rx_truncated.fetch_add(1, Ordering::Relaxed);
+    }
+    #[inline]
+    pub(crate) fn sent(&self, result: &io::Result<usize>) {
+        match result {
+            Ok(len) => {
+                self.0.tx_packets.fetch_add(1, Ordering::Relaxed);
+                self.0.tx_bytes.fetch_add(*len as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.0.tx_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    /// Counts a write that carried `packets` packets of `bytes` bytes in total, e.g. a GSO
+    /// packet the kernel splits into segments; if it failed, each of them counts as an error.
+    #[inline]
+    pub(crate) fn sent_segments(&self, result: &io::Result<usize>, packets: usize, bytes: usize) {
+        match result {
+            Ok(_) => {
+                self.0
+                    .tx_packets
+                    .fetch_add(packets as u64, Ordering::Relaxed);
+                self.0.tx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.0
+                    .tx_errors
+                    .fetch_add(packets as u64, Ordering::Relaxed);
+            }
+        }
+    }
+    fn snapshot(&self) -> DeviceCounters {
+        let c = &self.0;
+        DeviceCounters {
+            rx_packets: c.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: c.rx_bytes.load(Ordering::Relaxed),
+            rx_truncated: c.rx_truncated.load(Ordering::Relaxed),
+            tx_packets: c.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: c.tx_bytes.load(Ordering::Relaxed),
+            tx_errors: c.tx_errors.load(Ordering::Relaxed),
+        }
+    }
+    fn reset(&self) {
+        let c = &self.0;
+        for counter in [
+            &c.rx_packets,
+            &c.rx_bytes,
+            &c.rx_truncated,
+            &c.tx_packets,
+            &c.tx_bytes,
+            &c.tx_errors,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl crate::platform::DeviceImpl {
+    /// Returns a snapshot of the packets and bytes exchanged with this device, see the
+    /// [module documentation](crate::counters).
+    pub fn counters(&self) -> DeviceCounters {
+        self.counters.snapshot()
+    }
+    /// Sets all counters back to zero.
+    pub fn reset_counters(&self) {
+        self.counters.reset()
+    }
+}
@@ -0,0 +1,254 @@
+/*!
+# In-process Packet Demultiplexer
+
+[`Demux`] lets one device feed several independent consumers. Each consumer is
+registered with a filter and gets its own bounded queue. The read loop classifies
+every packet once into a [`PacketMeta`], evaluates the filters against it and hands a
+cheap [`Bytes`] clone to every matching consumer.
+
+A consumer whose queue is full does not stall the others: the packet is dropped for
+that consumer only and counted in [`Demux::dropped`].
+
+# Example
+
+```no_run
+use tun_rs::demux::Demux;
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+
+let mut demux = Demux::new();
+// DNS interception
+let dns = demux.register(256, |p| p.protocol == Some(17) && p.destination_port == Some(53));
+// Everything else
+let bulk = demux.register(4096, |p| p.destination_port != Some(53));
+
+std::thread::spawn(move || {
+    while let Ok(packet) = dns.recv() {
+        println!("dns query: {} bytes", packet.len());
+    }
+});
+std::thread::spawn(move || {
+    while let Ok(packet) = bulk.recv() {
+        println!("packet: {} bytes", packet.len());
+    }
+});
+// Runs until all receivers have been dropped.
+demux.run(&dev, 65536)?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::framing::IpVersion;
+use crate::SyncDevice;
+use bytes::Bytes;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// The result of classifying a packet, passed to every consumer filter.
+///
+/// Fields that cannot be determined (e.g. ports of a truncated packet or of a
+/// non-TCP/UDP protocol) are `None`.
+#[derive(Clone, Copy, Debug)]
+pub struct PacketMeta<'a> {
+    /// The raw packet.
+    pub data: &'a [u8],
+    /// IP version.
+    pub version: Option<IpVersion>,
+    /// IPv4 protocol or IPv6 next header.
+    pub protocol: Option<u8>,
+    /// Source address.
+    pub source: Option<IpAddr>,
+    /// Destination address.
+    pub destination: Option<IpAddr>,
+    /// Source port for TCP/UDP.
+    pub source_port: Option<u16>,
+    /// Destination port for TCP/UDP.
+    pub destination_port: Option<u16>,
+}
+
+impl<'a> PacketMeta<'a> {
+    /// Classifies an IP packet (without packet information header).
+    pub fn parse(data: &'a [u8]) -> Self {
+        let mut meta = PacketMeta {
+            data,
+            version: crate::framing::ip_version(data).ok(),
+            protocol: None,
+            source: None,
+            destination: None,
+            source_port: None,
+            destination_port: None,
+        };
+        let transport = match meta.version {
+            Some(IpVersion::V4) if data.len() >= 20 => {
+                let ihl = ((data[0] & 0x0f) as usize) * 4;
+                meta.protocol = Some(data[9]);
+                let src: [u8; 4] = data[12..16].try_into().unwrap();
+                let dst: [u8; 4] = data[16..20].try_into().unwrap();
+                meta.source = Some(Ipv4Addr::from(src).into());
+                meta.destination = Some(Ipv4Addr::from(dst).into());
+                data.get(ihl..)
+            }
+            Some(IpVersion::V6) if data.len() >= 40 => {
+                meta.protocol = Some(data[6]);
+                let src: [u8; 16] = data[8..24].try_into().unwrap();
+                let dst: [u8; 16] = data[24..40].try_into().unwrap();
+                meta.source = Some(Ipv6Addr::from(src).into());
+                meta.destination = Some(Ipv6Addr::from(dst).into());
+                data.get(40..)
+            }
+            _ => None,
+        };
+        if let (Some(IPPROTO_TCP | IPPROTO_UDP), Some(transport)) = (meta.protocol, transport) {
+            if transport.len() >= 4 {
+                meta.source_port = Some(u16::from_be_bytes([transport[0], transport[1]]));
+                meta.destination_port = Some(u16::from_be_bytes([transport[2], transport[3]]));
+            }
+        }
+        meta
+    }
+}
+
+type Filter = Box<dyn Fn(&PacketMeta<'_>) -> bool + Send + Sync>;
+
+struct Consumer {
+    filter: Filter,
+    tx: SyncSender<Bytes>,
+    closed: AtomicBool,
+}
+
+/// Fans packets out to multiple consumers, each with its own filter and bounded queue.
+#[derive(Default)]
+pub struct Demux {
+    consumers: Vec<Consumer>,
+    dropped: AtomicU64,
+}
+
+impl Demux {
+    /// Creates a demultiplexer without consumers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers a consumer that receives every packet for which `filter` returns `true`.
+    ///
+    /// `capacity` is the number of packets that can be queued for this consumer before
+    /// further matching packets are dropped for it.
+    pub fn register<F>(&mut self, capacity: usize, filter: F) -> Receiver<Bytes>
+    where
+        F: Fn(&PacketMeta<'_>) -> bool + Send + Sync + 'static,
+    {
+        let (tx, rx) = sync_channel(capacity);
+        self.consumers.push(Consumer {
+            filter: Box::new(filter),
+            tx,
+            closed: AtomicBool::new(false),
+        });
+        rx
+    }
+    /// Classifies `packet` once and queues it for every matching consumer.
+    ///
+    /// Returns the number of consumers the packet was queued for.
+    pub fn dispatch(&self, packet: &[u8]) -> usize {
+        let meta = PacketMeta::parse(packet);
+        let mut bytes: Option<Bytes> = None;
+        let mut delivered = 0;
+        for consumer in &self.consumers {
+            if consumer.closed.load(Ordering::Relaxed) || !(consumer.filter)(&meta) {
+                continue;
+            }
+            let bytes = bytes.get_or_insert_with(|| Bytes::copy_from_slice(packet));
+            match consumer.tx.try_send(bytes.clone()) {
+                Ok(()) => delivered += 1,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    consumer.closed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        delivered
+    }
+    /// Returns `true` once every registered consumer has dropped its receiver.
+    pub fn is_closed(&self) -> bool {
+        self.consumers
+            .iter()
+            .all(|c| c.closed.load(Ordering::Relaxed))
+    }
+    /// Number of packets dropped because a consumer's queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+    /// Reads packets from `device` and dispatches them until all consumers are gone.
+    ///
+    /// `buf_size` is the size of the receive buffer; it should be at least the MTU.
+    pub fn run(&self, device: &SyncDevice, buf_size: usize) -> io::Result<()> {
+        let mut buf = vec![0u8; buf_size];
+        while !self.is_closed() {
+            let len = device.recv(&mut buf)?;
+            self.dispatch(&buf[..len]);
+        }
+        Ok(())
+    }
+    /// Asynchronous version of [`run`](Self::run).
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub async fn run_async(&self, device: &crate::AsyncDevice, buf_size: usize) -> io::Result<()> {
+        let mut buf = vec![0u8; buf_size];
+        while !self.is_closed() {
+            let len = device.recv(&mut buf).await?;
+            self.dispatch(&buf[..len]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn udp_v4(dst_port: u16) -> Vec<u8> {
+        let mut p = vec![0u8; 28];
+        p[0] = 0x45;
+        p[9] = IPPROTO_UDP;
+        p[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        p[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        p[22..24].copy_from_slice(&dst_port.to_be_bytes());
+        p
+    }
+
+    #[test]
+    fn dispatch_by_filter() {
+        let mut demux = Demux::new();
+        let dns = demux.register(1, |p| p.destination_port == Some(53));
+        let all = demux.register(8, |_| true);
+
+        assert_eq!(demux.dispatch(&udp_v4(53)), 2);
+        assert_eq!(demux.dispatch(&udp_v4(80)), 1);
+        // The dns queue is full, only `all` gets it.
+        assert_eq!(demux.dispatch(&udp_v4(53)), 1);
+        assert_eq!(demux.dropped(), 1);
+
+        assert_eq!(dns.try_iter().count(), 1);
+        assert_eq!(all.try_iter().count(), 3);
+
+        drop(dns);
+        drop(all);
+        demux.dispatch(&udp_v4(53));
+        assert!(demux.is_closed());
+    }
+
+    #[test]
+    fn parse_meta() {
+        let packet = udp_v4(53);
+        let meta = PacketMeta::parse(&packet);
+        assert_eq!(meta.version, Some(IpVersion::V4));
+        assert_eq!(meta.protocol, Some(IPPROTO_UDP));
+        assert_eq!(meta.destination, Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(meta.destination_port, Some(53));
+    }
+}
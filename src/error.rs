@@ -0,0 +1,333 @@
+use std::fmt;
+use std::io;
+
+/// Structured error type for failures detected by this crate.
+///
+/// Builder and device methods keep returning [`io::Result`] so they compose with
+/// `std::io` and async runtimes. Errors detected by the crate itself carry an
+/// [`Error`] as their inner payload, which can be recovered by converting the
+/// [`io::Error`] back with [`Error::from`]:
+///
+/// ```no_run
+/// use tun_rs::DeviceBuilder;
+///
+/// match DeviceBuilder::new().name("a-name-that-is-far-too-long").build_sync() {
+///     Ok(_dev) => {}
+///     Err(e) => match tun_rs::Error::from(e) {
+///         tun_rs::Error::NameTooLong => eprintln!("pick a shorter name"),
+///         tun_rs::Error::PermissionDenied(_) => eprintln!("run as administrator/root"),
+///         other => eprintln!("error: {other}"),
+///     },
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The interface name exceeds the platform limit.
+    NameTooLong,
+    /// The Wintun ring capacity is outside the range accepted by the driver.
+    RingCapacity {
+        /// The requested capacity.
+        capacity: u32,
+        /// Minimum accepted capacity.
+        min: u32,
+        /// Maximum accepted capacity.
+        max: u32,
+    },
+    /// The MTU is outside the range supported by the backend, see `BackendKind::mtu_limits`.
+    MtuOutOfRange {
+        /// The requested MTU.
+        mtu: u16,
+        /// Minimum supported MTU.
+        min: u16,
+        /// Maximum supported MTU.
+        max: u16,
+    },
+    /// The interface has been disabled (e.g. via `shutdown`) and can no longer be used.
+    Disabled,
+    /// No usable driver was found for the requested device type.
+    ///
+    /// On Windows this is returned when neither wintun nor tap-windows can be loaded.
+    /// tun-rs has no driver-less fallback: Windows cannot create a network interface from
+    /// user mode, and a WFP redirect needs a signed callout driver of its own, so an emulated
+    /// backend could not offer the addresses, routes and MTU the device API is built around.
+    /// Applications can match on this error and explicitly switch to a user-space transport
+    /// of their own, e.g. a SOCKS or loopback UDP proxy:
+    ///
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// # fn run_tun(_: tun_rs::SyncDevice) -> std::io::Result<()> { Ok(()) }
+    /// # fn run_proxy() -> std::io::Result<()> { Ok(()) }
+    ///
+    /// match DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync() {
+    ///     Ok(dev) => run_tun(dev),
+    ///     Err(e) => match tun_rs::Error::from(e) {
+    ///         tun_rs::Error::DriverNotFound => run_proxy(),
+    ///         e => Err(e.into()),
+    ///     },
+    /// }?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    DriverNotFound,
+    /// The driver library (e.g. `wintun.dll`) is built for another architecture than the
+    /// process, e.g. an x64 DLL loaded by an ARM64 build.
+    DriverArchMismatch {
+        /// Architecture of the process.
+        expected: &'static str,
+        /// Architecture of the library.
+        found: &'static str,
+    },
+    /// The driver library (e.g. `wintun.dll`) failed the check set with `wintun_trust`, and
+    /// was not loaded.
+    UntrustedDriver {
+        /// The library that was refused.
+        path: std::path::PathBuf,
+        /// Why it was refused, e.g. `"unknown SHA-256 hash"`.
+        reason: &'static str,
+    },
+    /// An adopted file descriptor is not a TUN device (e.g. not a `utun` control socket).
+    InvalidFd {
+        /// What was wrong with the file descriptor.
+        reason: &'static str,
+    },
+    /// The setting cannot be changed on a live device; it has to be rebuilt with the new value.
+    RequiresRebuild {
+        /// The setting that was requested to change.
+        setting: &'static str,
+    },
+    /// The setting can only be changed while the interface is down, and bringing it down
+    /// was not allowed.
+    MustBounceInterface {
+        /// The setting that was requested to change.
+        setting: &'static str,
+    },
+    /// The operation is not available on this platform or for this kind of device.
+    Unsupported {
+        /// The operation that was requested, e.g. `"set_name"`.
+        operation: &'static str,
+    },
+    /// The requested `utun` unit is already taken by another interface.
+    ///
+    /// Use `utun_units()` to list the units in use, or request the name `"utun"` to let the
+    /// kernel pick the next free unit.
+    AlreadyInUse {
+        /// The conflicting unit number, e.g. `7` for `utun7`.
+        unit: u32,
+    },
+    /// Applying the builder configuration to a new device failed part way.
+    ///
+    /// The addresses added by the steps in `applied` have been removed again.
+    #[cfg(any(
+        target_os = "windows",
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    ))]
+    Config {
+        /// The step that failed.
+        failed: crate::ConfigStep,
+        /// The steps applied successfully before, in order.
+        applied: Vec<crate::ConfigStep>,
+        /// The error of the failed step.
+        source: io::Error,
+    },
+    /// The operating system rejected the operation due to insufficient privileges.
+    PermissionDenied(io::Error),
+    /// Any other I/O error.
+    Io(io::Error),
+}
+
+/// Result type alias using [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns the [`io::ErrorKind`] used when this error is converted into an [`io::Error`].
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::NameTooLong
+            | Error::RingCapacity { .. }
+            | Error::MtuOutOfRange { .. }
+            | Error::InvalidFd { .. } => io::ErrorKind::InvalidInput,
+            Error::Disabled => io::ErrorKind::Other,
+            Error::RequiresRebuild { .. } | Error::Unsupported { .. } => io::ErrorKind::Unsupported,
+            Error::DriverNotFound => io::ErrorKind::NotFound,
+            Error::DriverArchMismatch { .. } => io::ErrorKind::InvalidData,
+            Error::UntrustedDriver { .. } => io::ErrorKind::PermissionDenied,
+            Error::AlreadyInUse { .. } => io::ErrorKind::AddrInUse,
+            Error::MustBounceInterface { .. } => io::ErrorKind::ResourceBusy,
+            #[cfg(any(
+                target_os = "windows",
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "netbsd",
+            ))]
+            Error::Config { source: e, .. } => e.kind(),
+            Error::PermissionDenied(e) | Error::Io(e) => e.kind(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NameTooLong => f.write_str("name too long"),
+            Error::RingCapacity { capacity, min, max } => {
+                write!(f, "ring capacity {capacity} not in [{min},{max}]")
+            }
+            Error::MtuOutOfRange { mtu, min, max } => {
+                write!(f, "mtu {mtu} not in [{min},{max}]")
+            }
+            Error::Disabled => f.write_str("The interface has been disabled"),
+            Error::DriverNotFound => f.write_str("No driver found"),
+            Error::DriverArchMismatch { expected, found } => {
+                write!(f, "driver built for {found}, expected {expected}")
+            }
+            Error::UntrustedDriver { path, reason } => {
+                write!(f, "untrusted driver {}: {reason}", path.display())
+            }
+            Error::InvalidFd { reason } => write!(f, "invalid tun fd: {reason}"),
+            Error::RequiresRebuild { setting } => {
+                write!(
+                    f,
+                    "{setting} cannot be changed without rebuilding the device"
+                )
+            }
+            Error::MustBounceInterface { setting } => {
+                write!(f, "{setting} cannot be changed while the interface is up")
+            }
+            Error::Unsupported { operation } => write!(f, "{operation} is not supported"),
+            Error::AlreadyInUse { unit } => write!(f, "utun{unit} is already in use"),
+            #[cfg(any(
+                target_os = "windows",
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "netbsd",
+            ))]
+            Error::Config { failed, source, .. } => write!(f, "failed to apply {failed}: {source}"),
+            Error::PermissionDenied(e) | Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(any(
+                target_os = "windows",
+                all(target_os = "linux", not(target_env = "ohos")),
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "openbsd",
+                target_os = "netbsd",
+            ))]
+            Error::Config { source: e, .. } => Some(e),
+            Error::PermissionDenied(e) | Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return *e.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            Error::PermissionDenied(e)
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::PermissionDenied(e) | Error::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_through_io_error() {
+        let e: io::Error = Error::NameTooLong.into();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(e.to_string(), "name too long");
+        assert!(matches!(Error::from(e), Error::NameTooLong));
+
+        let e: io::Error = Error::AlreadyInUse { unit: 7 }.into();
+        assert_eq!(e.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(e.to_string(), "utun7 is already in use");
+        assert!(matches!(Error::from(e), Error::AlreadyInUse { unit: 7 }));
+
+        let e: io::Error = Error::Unsupported {
+            operation: "set_name",
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(e.to_string(), "set_name is not supported");
+
+        let e: io::Error = Error::MustBounceInterface {
+            setting: "mac_address",
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::ResourceBusy);
+        assert!(matches!(
+            Error::from(e),
+            Error::MustBounceInterface {
+                setting: "mac_address"
+            }
+        ));
+
+        let e: io::Error = Error::DriverArchMismatch {
+            expected: "aarch64",
+            found: "x86_64",
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "driver built for x86_64, expected aarch64");
+
+        let e: io::Error = Error::UntrustedDriver {
+            path: "wintun.dll".into(),
+            reason: "unknown SHA-256 hash",
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert!(matches!(Error::from(e), Error::UntrustedDriver { .. }));
+
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        {
+            let e: io::Error = Error::Config {
+                failed: crate::ConfigStep::Mtu(9000),
+                applied: vec![],
+                source: io::Error::from(io::ErrorKind::InvalidInput),
+            }
+            .into();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            assert!(e.to_string().starts_with("failed to apply mtu 9000: "));
+        }
+
+        let e = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(matches!(Error::from(e), Error::PermissionDenied(_)));
+        let e = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(matches!(Error::from(e), Error::Io(_)));
+    }
+}
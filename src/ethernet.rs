@@ -0,0 +1,426 @@
+/*!
+# Ethernet Frames
+
+TAP (L2) devices exchange Ethernet frames. [`recv_frame`](crate::SyncDevice::recv_frame)
+reads a frame and parses its header into a [`FrameMeta`], leaving the payload in place in
+the buffer; [`send_frame`](crate::SyncDevice::send_frame) writes the header described by a
+[`FrameMeta`] in front of a payload. Single 802.1Q VLAN tags are understood. [`MacAddr`]
+generates random locally administered addresses for new devices.
+
+# Example
+
+```no_run
+use tun_rs::ethernet::{FrameMeta, ETHERTYPE_ARP};
+use tun_rs::{DeviceBuilder, Layer};
+
+let dev = DeviceBuilder::new().layer(Layer::L2).build_sync()?;
+let mac = dev.mac_address()?;
+let mut buf = [0u8; 1514];
+loop {
+    let meta = dev.recv_frame(&mut buf)?;
+    if meta.ethertype == ETHERTYPE_ARP {
+        let payload = &buf[meta.payload_range()];
+        // ... build an ARP reply
+        let reply = FrameMeta::new(meta.source, mac, ETHERTYPE_ARP);
+        dev.send_frame(&reply, payload)?;
+    }
+}
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::SyncDevice;
+use std::borrow::Cow;
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Length of an Ethernet header without VLAN tag.
+pub const ETHERNET_HEADER_LEN: usize = 14;
+/// Length of an 802.1Q VLAN tag.
+pub(crate) const VLAN_TAG_LEN: usize = 4;
+/// Minimum length of an Ethernet frame, without the frame check sequence.
+pub const MIN_FRAME_LEN: usize = 60;
+
+/// EtherType of IPv4.
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+/// EtherType of ARP.
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+/// EtherType of IPv6.
+pub const ETHERTYPE_IPV6: u16 = 0x86dd;
+/// Tag protocol identifier of 802.1Q VLAN tags.
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// A MAC address, convertible to and from the `[u8; 6]` the device APIs take.
+///
+/// ```
+/// use tun_rs::ethernet::MacAddr;
+///
+/// let mac = MacAddr::random_locally_administered();
+/// assert!(mac.is_locally_administered() && !mac.is_multicast());
+/// let octets: [u8; 6] = mac.into();
+/// assert_eq!(MacAddr::from(octets), mac);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    /// The broadcast address `ff:ff:ff:ff:ff:ff`.
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+
+    /// Creates an address from its octets.
+    pub const fn new(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+    /// Returns the octets of the address.
+    pub const fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+    /// Returns a random unicast address with the locally administered bit set, which cannot
+    /// collide with a vendor-assigned address.
+    ///
+    /// Suitable for [`mac_addr`](crate::DeviceBuilder::mac_addr) when the device needs a
+    /// stable address that the caller picks and persists, rather than the one the OS assigns.
+    pub fn random_locally_administered() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        use std::sync::atomic::{AtomicU64, Ordering};
+        // `RandomState` is keyed from the OS random source; the counter and the time make
+        // every call hash something different.
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if let Ok(elapsed) = std::time::UNIX_EPOCH.elapsed() {
+            hasher.write_u128(elapsed.as_nanos());
+        }
+        let bytes = hasher.finish().to_le_bytes();
+        let mut octets = [0u8; 6];
+        octets.copy_from_slice(&bytes[..6]);
+        octets[0] = (octets[0] & !0x01) | 0x02;
+        Self(octets)
+    }
+    /// Returns whether the locally administered bit is set.
+    pub const fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+    /// Returns whether this is a group (broadcast or multicast) address.
+    pub const fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(mac: MacAddr) -> Self {
+        mac.0
+    }
+}
+
+impl std::fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+/// The Ethernet header of a frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct FrameMeta {
+    /// Destination MAC address.
+    pub destination: [u8; 6],
+    /// Source MAC address.
+    pub source: [u8; 6],
+    /// EtherType of the payload, after the VLAN tag if any.
+    pub ethertype: u16,
+    /// The tag control information (priority and VLAN id) of an 802.1Q tag.
+    pub vlan: Option<u16>,
+    /// Length of the whole frame; ignored by `send_frame`.
+    pub len: usize,
+}
+
+impl FrameMeta {
+    /// Creates the header of an untagged frame to send.
+    pub fn new(destination: [u8; 6], source: [u8; 6], ethertype: u16) -> Self {
+        Self {
+            destination,
+            source,
+            ethertype,
+            vlan: None,
+            len: 0,
+        }
+    }
+    /// Parses the Ethernet header of `frame`, or `None` if it is too short.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        if frame.len() < ETHERNET_HEADER_LEN {
+            return None;
+        }
+        let mut meta = Self {
+            destination: frame[0..6].try_into().unwrap(),
+            source: frame[6..12].try_into().unwrap(),
+            ethertype: u16::from_be_bytes([frame[12], frame[13]]),
+            vlan: None,
+            len: frame.len(),
+        };
+        if meta.ethertype == ETHERTYPE_VLAN {
+            if frame.len() < ETHERNET_HEADER_LEN + VLAN_TAG_LEN {
+                return None;
+            }
+            meta.vlan = Some(u16::from_be_bytes([frame[14], frame[15]]));
+            meta.ethertype = u16::from_be_bytes([frame[16], frame[17]]);
+        }
+        Some(meta)
+    }
+    /// Length of the header, including the VLAN tag.
+    pub fn header_len(&self) -> usize {
+        if self.vlan.is_some() {
+            ETHERNET_HEADER_LEN + VLAN_TAG_LEN
+        } else {
+            ETHERNET_HEADER_LEN
+        }
+    }
+    /// Range of the payload in the received frame.
+    pub fn payload_range(&self) -> Range<usize> {
+        self.header_len()..self.len
+    }
+    /// Returns whether the frame is addressed to a group (broadcast or multicast).
+    pub fn is_multicast(&self) -> bool {
+        self.destination[0] & 1 != 0
+    }
+    /// Writes the header into the first [`header_len`](Self::header_len) bytes of `buf`.
+    ///
+    /// # Panics
+    /// If `buf` is shorter than the header.
+    pub fn write_header(&self, buf: &mut [u8]) {
+        buf[0..6].copy_from_slice(&self.destination);
+        buf[6..12].copy_from_slice(&self.source);
+        let mut offset = 12;
+        if let Some(tci) = self.vlan {
+            buf[12..14].copy_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            buf[14..16].copy_from_slice(&tci.to_be_bytes());
+            offset += VLAN_TAG_LEN;
+        }
+        buf[offset..offset + 2].copy_from_slice(&self.ethertype.to_be_bytes());
+    }
+    fn frame(&self, payload: &[u8]) -> Vec<u8> {
+        let header_len = self.header_len();
+        let mut frame = vec![0; header_len + payload.len()];
+        self.write_header(&mut frame);
+        frame[header_len..].copy_from_slice(payload);
+        frame
+    }
+}
+
+/// Checks on the frames sent to a TAP device, enabled with
+/// [`set_frame_validation`](crate::platform::DeviceImpl::set_frame_validation).
+///
+/// Some platforms silently drop frames that could not have come off a wire, which makes a
+/// hand-built frame with a mistake in it hard to track down. With validation enabled, `send`
+/// fails with [`io::ErrorKind::InvalidInput`] instead for a frame that
+///
+/// - is shorter than its Ethernet header, including the VLAN tag,
+/// - has a group (multicast or broadcast) source address, or
+/// - is shorter than [`MIN_FRAME_LEN`], unless [`pad`](Self::pad) is set.
+///
+/// ```
+/// use tun_rs::ethernet::{FrameMeta, FrameValidation, ETHERTYPE_ARP, MIN_FRAME_LEN};
+///
+/// let mut frame = [0u8; 42];
+/// FrameMeta::new([0xff; 6], [2, 0, 0, 0, 0, 1], ETHERTYPE_ARP).write_header(&mut frame);
+/// assert!(FrameValidation::default().apply(&frame).is_err());
+/// let padded = FrameValidation { pad: true }.apply(&frame).unwrap();
+/// assert_eq!(padded.len(), MIN_FRAME_LEN);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct FrameValidation {
+    /// Pads frames shorter than [`MIN_FRAME_LEN`] with zeros instead of rejecting them.
+    pub pad: bool,
+}
+
+impl FrameValidation {
+    /// Validates `frame`, returning it unchanged or padded.
+    pub fn apply<'a>(&self, frame: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        let Some(meta) = FrameMeta::parse(frame) else {
+            return Err(invalid_frame("truncated Ethernet header"));
+        };
+        if meta.source[0] & 1 != 0 {
+            return Err(invalid_frame("group address as Ethernet source"));
+        }
+        if frame.len() >= MIN_FRAME_LEN {
+            return Ok(Cow::Borrowed(frame));
+        }
+        if !self.pad {
+            return Err(invalid_frame("Ethernet frame shorter than 60 bytes"));
+        }
+        let mut padded = frame.to_vec();
+        padded.resize(MIN_FRAME_LEN, 0);
+        Ok(Cow::Owned(padded))
+    }
+}
+
+fn invalid_frame(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Shared frame validation setting of a device.
+#[derive(Clone, Default)]
+pub(crate) struct FrameValidator(Arc<AtomicU8>);
+
+impl FrameValidator {
+    const OFF: u8 = 0;
+    const ON: u8 = 1;
+    const PAD: u8 = 2;
+
+    pub(crate) fn set(&self, validation: Option<FrameValidation>) {
+        let state = match validation {
+            None => Self::OFF,
+            Some(FrameValidation { pad: false }) => Self::ON,
+            Some(FrameValidation { pad: true }) => Self::PAD,
+        };
+        self.0.store(state, Ordering::Relaxed);
+    }
+    pub(crate) fn get(&self) -> Option<FrameValidation> {
+        match self.0.load(Ordering::Relaxed) {
+            Self::OFF => None,
+            state => Some(FrameValidation {
+                pad: state == Self::PAD,
+            }),
+        }
+    }
+    /// Validates a frame about to be written, or returns it as is if validation is off.
+    #[inline]
+    pub(crate) fn outbound<'a>(&self, frame: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        match self.get() {
+            None => Ok(Cow::Borrowed(frame)),
+            Some(validation) => validation.apply(frame),
+        }
+    }
+}
+
+fn check_tap(device: &crate::platform::DeviceImpl) -> io::Result<()> {
+    if !device.is_tap() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Ethernet frames are only available on TAP devices",
+        ));
+    }
+    Ok(())
+}
+
+fn parse_received(buf: &[u8]) -> io::Result<FrameMeta> {
+    FrameMeta::parse(buf)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated Ethernet header"))
+}
+
+impl crate::platform::DeviceImpl {
+    /// Enables (or with `None` disables) the checks of [`FrameValidation`] on the frames passed
+    /// to `send`, including those of [`send_frame`](SyncDevice::send_frame).
+    ///
+    /// `send` reports the length of the frame it was given, also when it was padded.
+    /// `send_multiple` validates each frame of the batch on Linux and macOS, writes the valid
+    /// ones and then fails with the last error. The other sends validate the frame as well,
+    /// vectored ones once it is gathered into one buffer, except on a Linux device built with
+    /// `offload(true)`, where only `send_multiple` does.
+    /// Queues cloned from this device share the setting. Fails with
+    /// [`io::ErrorKind::Unsupported`] on TUN devices.
+    pub fn set_frame_validation(&self, validation: Option<FrameValidation>) -> io::Result<()> {
+        check_tap(self)?;
+        self.frame_validation.set(validation);
+        Ok(())
+    }
+    /// Returns the frame validation enabled on the device, if any.
+    pub fn frame_validation(&self) -> Option<FrameValidation> {
+        self.frame_validation.get()
+    }
+}
+
+impl SyncDevice {
+    /// Receives an Ethernet frame into `buf` and returns its parsed header.
+    ///
+    /// The frame is `buf[..meta.len]` and its payload `buf[meta.payload_range()]`. Fails with
+    /// [`io::ErrorKind::Unsupported`] on TUN devices.
+    pub fn recv_frame(&self, buf: &mut [u8]) -> io::Result<FrameMeta> {
+        check_tap(&self.0)?;
+        let len = self.recv(buf)?;
+        parse_received(&buf[..len])
+    }
+    /// Sends `payload` in an Ethernet frame with the header described by `meta`.
+    ///
+    /// Returns the number of bytes written, including the header. Fails with
+    /// [`io::ErrorKind::Unsupported`] on TUN devices.
+    pub fn send_frame(&self, meta: &FrameMeta, payload: &[u8]) -> io::Result<usize> {
+        check_tap(&self.0)?;
+        self.send(&meta.frame(payload))
+    }
+}
+
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+impl crate::AsyncDevice {
+    /// Asynchronous version of [`SyncDevice::recv_frame`].
+    pub async fn recv_frame(&self, buf: &mut [u8]) -> io::Result<FrameMeta> {
+        check_tap(self)?;
+        let len = self.recv(buf).await?;
+        parse_received(&buf[..len])
+    }
+    /// Asynchronous version of [`SyncDevice::send_frame`].
+    pub async fn send_frame(&self, meta: &FrameMeta, payload: &[u8]) -> io::Result<usize> {
+        check_tap(self)?;
+        self.send(&meta.frame(payload)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_mac() {
+        let a = MacAddr::random_locally_administered();
+        let b = MacAddr::random_locally_administered();
+        assert_ne!(a, b);
+        assert!(a.is_locally_administered() && !a.is_multicast());
+        assert_eq!(
+            MacAddr([0x02, 0, 0xab, 1, 2, 3]).to_string(),
+            "02:00:ab:01:02:03"
+        );
+    }
+
+    #[test]
+    fn vlan_round_trip() {
+        let mut meta = FrameMeta::new([0xff; 6], [2, 0, 0, 0, 0, 1], ETHERTYPE_IPV4);
+        meta.vlan = Some(42);
+        let frame = meta.frame(&[0x45; 20]);
+        assert_eq!(frame.len(), 18 + 20);
+        assert_eq!(&frame[12..14], &[0x81, 0x00]);
+
+        let parsed = FrameMeta::parse(&frame).unwrap();
+        assert_eq!(parsed.vlan, Some(42));
+        assert_eq!(parsed.ethertype, ETHERTYPE_IPV4);
+        assert_eq!(parsed.payload_range(), 18..38);
+        assert!(parsed.is_multicast());
+        assert!(FrameMeta::parse(&frame[..16]).is_none());
+    }
+
+    #[test]
+    fn frame_validation() {
+        let validator = FrameValidator::default();
+        let mut frame = [0u8; 20];
+        FrameMeta::new([0xff; 6], [2, 0, 0, 0, 0, 1], ETHERTYPE_IPV4).write_header(&mut frame);
+        assert!(matches!(validator.outbound(&frame), Ok(Cow::Borrowed(_))));
+
+        validator.set(Some(FrameValidation::default()));
+        assert!(validator.outbound(&frame).is_err());
+        assert!(validator.outbound(&frame[..10]).is_err());
+        validator.set(Some(FrameValidation { pad: true }));
+        assert_eq!(validator.outbound(&frame).unwrap().len(), MIN_FRAME_LEN);
+        frame[6] = 0x01;
+        assert!(validator.outbound(&frame).is_err());
+
+        validator.set(None);
+        assert_eq!(validator.get(), None);
+    }
+}
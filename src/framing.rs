@@ -0,0 +1,313 @@
+/*!
+# Packet Framing Helpers
+
+Helpers to parse and construct the per-packet headers that TUN drivers prepend to
+packets when packet information is enabled.
+
+Each platform has its own header layout:
+
+- **Linux/Android**: `struct tun_pi` — 2 bytes of flags in host byte order followed by the
+  EtherType of the packet in network byte order. See [`TunPi`].
+- **Windows**: Wintun has no header, so with packet information enabled the library emulates
+  `struct tun_pi`, byte for byte as Linux would on the same machine. See [`TunPi`].
+- **macOS/iOS/tvOS, FreeBSD (with `TUNSIFHEAD`), OpenBSD, NetBSD**: a 4-byte address family
+  (`AF_INET`/`AF_INET6`) in network byte order. See [`AfHeader`].
+
+The library uses these same helpers internally, so code that works directly with raw file
+descriptors (e.g. an fd adopted from a system VPN framework) can produce and consume
+byte-identical headers.
+
+# Example
+
+```no_run
+use tun_rs::framing::{self, IpVersion};
+
+let packet = [0x45u8, 0, 0, 20]; // truncated IPv4 header
+assert_eq!(framing::ip_version(&packet)?, IpVersion::V4);
+
+// The header the current platform's driver expects in front of `packet`.
+let header = framing::header_for_packet(&packet)?;
+assert_eq!(header.len(), framing::HEADER_LEN);
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use std::io;
+
+/// Length of the packet information header, on every platform that uses one.
+pub const HEADER_LEN: usize = crate::PACKET_INFORMATION_LENGTH;
+
+/// `tun_pi` flag set on a received packet that was cut to fit the buffer.
+pub const TUN_PKT_STRIP: u16 = 0x0001;
+/// `tun_pi` protocol of an IPv4 packet.
+pub const ETH_P_IP: u16 = 0x0800;
+/// `tun_pi` protocol of an IPv6 packet.
+pub const ETH_P_IPV6: u16 = 0x86DD;
+
+/// IP version of a packet, inferred from the first nibble of the packet buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum IpVersion {
+    /// IPv4
+    V4,
+    /// IPv6
+    V6,
+}
+
+/// Infers the IP version of `packet` from the first nibble of the buffer.
+///
+/// Returns an error of kind [`io::ErrorKind::InvalidData`] if the buffer is empty
+/// or the version is neither 4 nor 6.
+pub fn ip_version(packet: &[u8]) -> io::Result<IpVersion> {
+    let Some(first) = packet.first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Zero-length data",
+        ));
+    };
+    match first >> 4 {
+        4 => Ok(IpVersion::V4),
+        6 => Ok(IpVersion::V6),
+        p => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("IP version {p}"),
+        )),
+    }
+}
+
+/// The Linux `struct tun_pi` header.
+///
+/// Present in front of each packet when the device is created with packet information
+/// enabled (i.e. without `IFF_NO_PI`), and emulated in the same layout on Windows.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TunPi {
+    /// Flags, e.g. [`TunPi::TUN_PKT_STRIP`]. Unlike `proto`, the kernel keeps them in host
+    /// byte order.
+    pub flags: u16,
+    /// EtherType of the packet, e.g. `0x0800` for IPv4 or `0x86DD` for IPv6.
+    pub proto: u16,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+impl TunPi {
+    /// Set by the kernel on receive when the packet was truncated because the buffer was too small.
+    pub const TUN_PKT_STRIP: u16 = TUN_PKT_STRIP;
+
+    /// Creates the header for an IP packet of the given version, with no flags set.
+    pub fn new(version: IpVersion) -> Self {
+        let proto = match version {
+            IpVersion::V4 => ETH_P_IP,
+            IpVersion::V6 => ETH_P_IPV6,
+        };
+        Self { flags: 0, proto }
+    }
+    /// Creates the header matching the IP version of `packet`.
+    pub fn for_packet(packet: &[u8]) -> io::Result<Self> {
+        Ok(Self::new(ip_version(packet)?))
+    }
+    /// Parses the header from the first [`HEADER_LEN`] bytes of `buf`.
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let Some(header) = buf.get(..HEADER_LEN) else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "buffer shorter than tun_pi header",
+            ));
+        };
+        Ok(Self::from_bytes([
+            header[0], header[1], header[2], header[3],
+        ]))
+    }
+    /// Reads the header from its on-wire representation, the inverse of
+    /// [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(header: [u8; HEADER_LEN]) -> Self {
+        Self {
+            flags: u16::from_ne_bytes([header[0], header[1]]),
+            proto: u16::from_be_bytes([header[2], header[3]]),
+        }
+    }
+    /// Returns the IP version indicated by the EtherType, if it is IPv4 or IPv6.
+    pub fn ip_version(&self) -> Option<IpVersion> {
+        match self.proto {
+            ETH_P_IP => Some(IpVersion::V4),
+            ETH_P_IPV6 => Some(IpVersion::V6),
+            _ => None,
+        }
+    }
+    /// Serializes the header into its on-wire representation.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let flags = self.flags.to_ne_bytes();
+        let proto = self.proto.to_be_bytes();
+        [flags[0], flags[1], proto[0], proto[1]]
+    }
+}
+
+/// The 4-byte address family header used by BSD-derived TUN drivers.
+///
+/// Used by `utun` on macOS/iOS/tvOS, by FreeBSD `tun(4)` in `TUNSIFHEAD` mode,
+/// and by OpenBSD/NetBSD `tun(4)`. The value is the platform's `AF_INET`/`AF_INET6`
+/// constant in network byte order.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct AfHeader {
+    /// Address family, e.g. `libc::AF_INET`.
+    pub family: u32,
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+impl AfHeader {
+    /// Creates the header for an IP packet of the given version.
+    pub fn new(version: IpVersion) -> Self {
+        let family = match version {
+            IpVersion::V4 => libc::AF_INET,
+            IpVersion::V6 => libc::AF_INET6,
+        };
+        Self {
+            family: family as u32,
+        }
+    }
+    /// Creates the header matching the IP version of `packet`.
+    pub fn for_packet(packet: &[u8]) -> io::Result<Self> {
+        Ok(Self::new(ip_version(packet)?))
+    }
+    /// Parses the header from the first [`HEADER_LEN`] bytes of `buf`.
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let Some(header) = buf.get(..HEADER_LEN) else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "buffer shorter than address family header",
+            ));
+        };
+        Ok(Self::from_bytes([
+            header[0], header[1], header[2], header[3],
+        ]))
+    }
+    /// Reads the header from its on-wire representation, the inverse of
+    /// [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(header: [u8; HEADER_LEN]) -> Self {
+        Self {
+            family: u32::from_be_bytes(header),
+        }
+    }
+    /// Returns the IP version indicated by the address family, if it is IPv4 or IPv6.
+    pub fn ip_version(&self) -> Option<IpVersion> {
+        match self.family as libc::c_int {
+            libc::AF_INET => Some(IpVersion::V4),
+            libc::AF_INET6 => Some(IpVersion::V6),
+            _ => None,
+        }
+    }
+    /// Serializes the header into its on-wire representation.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        self.family.to_be_bytes()
+    }
+}
+
+/// Returns the header the current platform's TUN driver expects in front of `packet`.
+///
+/// This is [`TunPi`] on Linux/Android and Windows and [`AfHeader`] on Apple platforms and
+/// the BSDs.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub fn header_for_packet(packet: &[u8]) -> io::Result<[u8; HEADER_LEN]> {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+    return Ok(TunPi::for_packet(packet)?.to_bytes());
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "windows")))]
+    return Ok(AfHeader::for_packet(packet)?.to_bytes());
+}
+
+/// Parses the platform header at the front of `buf` and returns the IP version it announces.
+///
+/// Returns `Ok(None)` if the header carries a protocol other than IPv4/IPv6.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub fn parse_header(buf: &[u8]) -> io::Result<Option<IpVersion>> {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+    return Ok(TunPi::parse(buf)?.ip_version());
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "windows")))]
+    return Ok(AfHeader::parse(buf)?.ip_version());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_version_from_first_nibble() {
+        assert_eq!(ip_version(&[0x45]).unwrap(), IpVersion::V4);
+        assert_eq!(ip_version(&[0x60]).unwrap(), IpVersion::V6);
+        assert!(ip_version(&[]).is_err());
+        assert!(ip_version(&[0x50]).is_err());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+    #[test]
+    fn tun_pi_round_trip() {
+        let pi = TunPi::for_packet(&[0x60]).unwrap();
+        assert_eq!(pi.to_bytes(), [0, 0, 0x86, 0xdd]);
+        assert_eq!(TunPi::parse(&pi.to_bytes()).unwrap(), pi);
+        assert_eq!(pi.ip_version(), Some(IpVersion::V6));
+        assert_eq!(
+            parse_header(&[0, 1, 0x08, 0x00]).unwrap(),
+            Some(IpVersion::V4)
+        );
+        assert!(TunPi::parse(&[0, 0]).is_err());
+        let strip = TunPi {
+            flags: TUN_PKT_STRIP,
+            proto: ETH_P_IP,
+        };
+        assert_eq!(TunPi::parse(&strip.to_bytes()).unwrap(), strip);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))]
+    #[test]
+    fn tun_pi_flags_host_endian() {
+        let strip = TunPi {
+            flags: TUN_PKT_STRIP,
+            proto: ETH_P_IPV6,
+        };
+        #[cfg(target_endian = "little")]
+        assert_eq!(strip.to_bytes(), [1, 0, 0x86, 0xdd]);
+        #[cfg(target_endian = "big")]
+        assert_eq!(strip.to_bytes(), [0, 1, 0x86, 0xdd]);
+        let flags = TUN_PKT_STRIP.to_ne_bytes();
+        assert_eq!(
+            TunPi::parse(&[flags[0], flags[1], 0x08, 0x00]).unwrap(),
+            TunPi {
+                flags: TUN_PKT_STRIP,
+                proto: ETH_P_IP,
+            }
+        );
+    }
+}
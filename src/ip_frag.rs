@@ -0,0 +1,944 @@
+/*!
+# IP Fragmentation and Reassembly
+
+Helpers for forwarding between interfaces with different MTUs, operating on plain packet
+buffers:
+
+- [`fragment`] splits an IPv4 packet into fragments that fit the MTU of the next hop
+  (RFC 791), or an IPv6 packet behind a Fragment Header (RFC 8200) when this host is its
+  source. Fragments are written one at a time into buffers of the caller, e.g. from a pool.
+- [`packet_too_big`] builds the ICMP "Fragmentation Needed" or ICMPv6 "Packet Too Big" error
+  that drives path MTU discovery (RFC 1191, RFC 8201), for IPv4 packets with the DF flag and
+  for IPv6 packets a router must not fragment.
+- [`Reassembler`] collects fragments into whole packets, dropping incomplete ones after a
+  timeout.
+
+```
+use std::time::Instant;
+use tun_rs::ip_frag::{fragment, Reassembler};
+
+// A 1000 byte UDP packet from 10.0.0.1 to 10.0.0.2.
+let mut packet = vec![0u8; 1000];
+packet[..4].copy_from_slice(&[0x45, 0, 0x03, 0xe8]);
+packet[8..10].copy_from_slice(&[64, 17]);
+packet[12..20].copy_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+
+let mut fragments = fragment(&packet, 576)?;
+let mut reassembler = Reassembler::default();
+let (mut buf, mut out) = ([0u8; 576], [0u8; 1500]);
+let mut whole = None;
+while let Some(len) = fragments.write_next(&mut buf)? {
+    assert!(len <= 576);
+    whole = reassembler.push(&buf[..len], Instant::now(), &mut out)?;
+}
+assert_eq!(&out[20..whole.unwrap()], &packet[20..]);
+# Ok::<(), std::io::Error>(())
+```
+
+Malformed packets fail with [`io::ErrorKind::InvalidData`]. Overlapping fragments are a
+known evasion technique and drop the whole datagram (RFC 5722).
+*/
+
+use crate::framing::{ip_version, IpVersion};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV4_MAX_HEADER_LEN: usize = 60;
+const IPV6_HEADER_LEN: usize = 40;
+const FRAGMENT_HEADER_LEN: usize = 8;
+const ICMP_HEADER_LEN: usize = 8;
+const MAX_PACKET_LEN: usize = 65535;
+
+const IPV4_DF: u16 = 0x4000;
+const IPV4_MF: u16 = 0x2000;
+const IPV4_OFFSET: u16 = 0x1fff;
+
+const PROTO_HOPOPTS: u8 = 0;
+const PROTO_ICMP: u8 = 1;
+const PROTO_ROUTING: u8 = 43;
+const PROTO_FRAGMENT: u8 = 44;
+const PROTO_ICMPV6: u8 = 58;
+const PROTO_DSTOPTS: u8 = 60;
+
+/// ICMP errors fit the minimum reassembly size of IPv4 (RFC 1812) and the minimum MTU of
+/// IPv6 (RFC 4443).
+const ICMP_MAX_LEN: usize = 576;
+const ICMPV6_MAX_LEN: usize = 1280;
+
+/// The fragments of a packet, produced by [`fragment`].
+#[derive(Debug)]
+pub struct Fragments<'a> {
+    /// A packet that fits the MTU, passed on as is.
+    whole: Option<&'a [u8]>,
+    /// The IPv4 header of the first fragment, or the unfragmentable part of an IPv6 packet.
+    header: &'a [u8],
+    /// The IPv4 header of the other fragments, with only the options that are copied.
+    later: [u8; IPV4_MAX_HEADER_LEN],
+    later_len: usize,
+    payload: &'a [u8],
+    pos: usize,
+    mtu: usize,
+    /// The IPv4 fragment offset of the packet, in bytes, when a fragment is fragmented again.
+    base: usize,
+    /// The MF flag of the packet, which the last fragment inherits.
+    more: bool,
+    v6: Option<Ipv6Fragment>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Ipv6Fragment {
+    id: u32,
+    next_header: u8,
+    /// The Next Header field that points to the Fragment Header.
+    nh_offset: usize,
+}
+
+/// Splits `packet` into fragments of at most `mtu` bytes.
+///
+/// A packet that already fits is returned as its only fragment. IPv4 fragments keep the
+/// Identification of the packet, and only the first one carries options that are not
+/// copied. IPv6 fragments get a new Identification and a Fragment Header after the
+/// unfragmentable part (the Hop-by-Hop Options and Routing headers).
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if an IPv4 packet with the DF flag does not
+/// fit, or `mtu` leaves no room for 8 bytes of payload; routers should answer the former
+/// with [`packet_too_big`], as they should any IPv6 packet that does not fit. Fails with
+/// [`io::ErrorKind::Unsupported`] for an IPv6 packet that is already a fragment.
+pub fn fragment(packet: &[u8], mtu: usize) -> io::Result<Fragments<'_>> {
+    let mut fragments = Fragments {
+        whole: None,
+        header: &[],
+        later: [0; IPV4_MAX_HEADER_LEN],
+        later_len: 0,
+        payload: &[],
+        pos: 0,
+        mtu,
+        base: 0,
+        more: false,
+        v6: None,
+    };
+    match ip_version(packet)? {
+        IpVersion::V4 => {
+            let (header_len, total_len) = ipv4_lengths(packet)?;
+            let packet = &packet[..total_len];
+            if total_len <= mtu {
+                fragments.whole = Some(packet);
+                return Ok(fragments);
+            }
+            let frag = u16::from_be_bytes([packet[6], packet[7]]);
+            if frag & IPV4_DF != 0 {
+                return Err(invalid_input("packet exceeds the MTU and has DF set"));
+            }
+            if mtu < header_len + 8 {
+                return Err(invalid_input("MTU too small"));
+            }
+            fragments.header = &packet[..header_len];
+            fragments.later_len = copied_options(fragments.header, &mut fragments.later)?;
+            fragments.payload = &packet[header_len..];
+            fragments.base = (frag & IPV4_OFFSET) as usize * 8;
+            fragments.more = frag & IPV4_MF != 0;
+        }
+        IpVersion::V6 => {
+            let end = ipv6_end(packet)?;
+            let packet = &packet[..end];
+            if end <= mtu {
+                fragments.whole = Some(packet);
+                return Ok(fragments);
+            }
+            let (split, nh_offset) = unfragmentable_len(packet)?;
+            if mtu < split + FRAGMENT_HEADER_LEN + 8 {
+                return Err(invalid_input("MTU too small"));
+            }
+            fragments.header = &packet[..split];
+            fragments.payload = &packet[split..];
+            fragments.v6 = Some(Ipv6Fragment {
+                id: next_id(),
+                next_header: packet[nh_offset],
+                nh_offset,
+            });
+        }
+    }
+    Ok(fragments)
+}
+
+impl Fragments<'_> {
+    /// Writes the next fragment to `out` and returns its length, or `None` once all have been
+    /// written.
+    ///
+    /// `out` must hold `mtu` bytes, or the length of a packet that fits.
+    pub fn write_next(&mut self, out: &mut [u8]) -> io::Result<Option<usize>> {
+        if let Some(packet) = self.whole.take() {
+            let out = out.get_mut(..packet.len()).ok_or_else(buffer_too_small)?;
+            out.copy_from_slice(packet);
+            return Ok(Some(packet.len()));
+        }
+        if self.pos >= self.payload.len() {
+            return Ok(None);
+        }
+        let header = match self.v6 {
+            Some(_) => self.header,
+            None if self.pos == 0 => self.header,
+            None => &self.later[..self.later_len],
+        };
+        let head_len = header.len() + self.v6.map_or(0, |_| FRAGMENT_HEADER_LEN);
+        let rest = self.payload.len() - self.pos;
+        let room = self.mtu - head_len;
+        let (len, last) = if rest <= room {
+            (rest, true)
+        } else {
+            (room & !7, false)
+        };
+        let total = head_len + len;
+        let out = out.get_mut(..total).ok_or_else(buffer_too_small)?;
+        out[..header.len()].copy_from_slice(header);
+        out[head_len..].copy_from_slice(&self.payload[self.pos..self.pos + len]);
+        match self.v6 {
+            None => {
+                let offset = (self.base + self.pos) / 8;
+                let more = !last || self.more;
+                let frag = if more { IPV4_MF } else { 0 } | offset as u16;
+                out[2..4].copy_from_slice(&(total as u16).to_be_bytes());
+                out[6..8].copy_from_slice(&frag.to_be_bytes());
+                set_ipv4_checksum(&mut out[..header.len()]);
+            }
+            Some(v6) => {
+                out[4..6].copy_from_slice(&((total - IPV6_HEADER_LEN) as u16).to_be_bytes());
+                out[v6.nh_offset] = PROTO_FRAGMENT;
+                let fragment = &mut out[header.len()..head_len];
+                fragment[0] = v6.next_header;
+                fragment[1] = 0;
+                let offset = self.pos as u16 | !last as u16;
+                fragment[2..4].copy_from_slice(&offset.to_be_bytes());
+                fragment[4..8].copy_from_slice(&v6.id.to_be_bytes());
+            }
+        }
+        self.pos += len;
+        Ok(Some(total))
+    }
+}
+
+/// Writes to `out` the ICMP error telling the source of `packet` that it exceeds `mtu`, and
+/// returns its length.
+///
+/// For IPv4 this is a "Fragmentation Needed" Destination Unreachable with the next-hop MTU
+/// (RFC 1191), for IPv6 a "Packet Too Big" (RFC 4443). The error is sent from `source`, an
+/// address of this host of the same family, and quotes as much of `packet` as the minimum
+/// MTU allows, so `out` needs 576 bytes for IPv4 and 1280 bytes for IPv6 at most. Write it
+/// back to the device the packet came from.
+pub fn packet_too_big(
+    packet: &[u8],
+    mtu: u16,
+    source: IpAddr,
+    out: &mut [u8],
+) -> io::Result<usize> {
+    match (ip_version(packet)?, source) {
+        (IpVersion::V4, IpAddr::V4(source)) => {
+            let (_, total_len) = ipv4_lengths(packet)?;
+            let quoted = total_len.min(ICMP_MAX_LEN - IPV4_HEADER_LEN - ICMP_HEADER_LEN);
+            let len = IPV4_HEADER_LEN + ICMP_HEADER_LEN + quoted;
+            let out = out.get_mut(..len).ok_or_else(buffer_too_small)?;
+            out.fill(0);
+            out[0] = 0x45;
+            out[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+            out[8] = 64;
+            out[9] = PROTO_ICMP;
+            out[12..16].copy_from_slice(&source.octets());
+            out[16..20].copy_from_slice(&packet[12..16]);
+            set_ipv4_checksum(&mut out[..IPV4_HEADER_LEN]);
+            let icmp = &mut out[IPV4_HEADER_LEN..];
+            icmp[0] = 3; // Destination Unreachable
+            icmp[1] = 4; // Fragmentation Needed and DF set
+            icmp[6..8].copy_from_slice(&mtu.to_be_bytes());
+            icmp[ICMP_HEADER_LEN..].copy_from_slice(&packet[..quoted]);
+            let checksum = checksum(0, icmp);
+            icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+            Ok(len)
+        }
+        (IpVersion::V6, IpAddr::V6(source)) => {
+            let end = ipv6_end(packet)?;
+            let quoted = end.min(ICMPV6_MAX_LEN - IPV6_HEADER_LEN - ICMP_HEADER_LEN);
+            let len = IPV6_HEADER_LEN + ICMP_HEADER_LEN + quoted;
+            let out = out.get_mut(..len).ok_or_else(buffer_too_small)?;
+            out.fill(0);
+            out[0] = 0x60;
+            let payload_len = len - IPV6_HEADER_LEN;
+            out[4..6].copy_from_slice(&(payload_len as u16).to_be_bytes());
+            out[6] = PROTO_ICMPV6;
+            out[7] = 64;
+            out[8..24].copy_from_slice(&source.octets());
+            out[24..40].copy_from_slice(&packet[8..24]);
+            let pseudo = sum(0, &out[8..40]) + payload_len as u32 + PROTO_ICMPV6 as u32;
+            let icmp = &mut out[IPV6_HEADER_LEN..];
+            icmp[0] = 2; // Packet Too Big
+            icmp[4..8].copy_from_slice(&(mtu as u32).to_be_bytes());
+            icmp[ICMP_HEADER_LEN..].copy_from_slice(&packet[..quoted]);
+            let checksum = checksum(pseudo, icmp);
+            icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+            Ok(len)
+        }
+        _ => Err(invalid_input("source address of the wrong family")),
+    }
+}
+
+/// Identifies the fragments of one datagram.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum Key {
+    V4 {
+        src: [u8; 4],
+        dst: [u8; 4],
+        proto: u8,
+        id: u16,
+    },
+    V6 {
+        src: [u8; 16],
+        dst: [u8; 16],
+        id: u32,
+    },
+}
+
+/// A fragment parsed by [`Reassembler::push`].
+struct Fragment<'a> {
+    key: Key,
+    /// The IPv4 header, or the IPv6 header and extension headers up to the Fragment Header.
+    header: &'a [u8],
+    next_header: u8,
+    nh_offset: usize,
+    offset: usize,
+    more: bool,
+    data: &'a [u8],
+}
+
+/// A datagram whose fragments are being collected.
+#[derive(Debug)]
+struct Pending {
+    deadline: Instant,
+    /// The header of the first fragment, once it arrived.
+    header: Vec<u8>,
+    next_header: u8,
+    nh_offset: usize,
+    data: Vec<u8>,
+    /// The byte ranges of the fragments received so far, sorted and disjoint. Adjacent ranges
+    /// are not merged, so a retransmitted fragment still matches its own range.
+    ranges: Vec<(usize, usize)>,
+    /// The length of the payload, known once the last fragment arrived.
+    total: Option<usize>,
+}
+
+/// Reassembles fragmented IPv4 and IPv6 packets.
+///
+/// Every received packet can be passed to [`push`](Self::push): packets that are not
+/// fragments come back unchanged, fragments are held until their datagram is complete.
+/// Datagrams still incomplete after the timeout are dropped, as are the oldest ones when
+/// more than `max_datagrams` are pending. The buffers of completed datagrams are reused.
+#[derive(Debug)]
+pub struct Reassembler {
+    pending: HashMap<Key, Pending>,
+    timeout: Duration,
+    max_datagrams: usize,
+    free: Vec<Vec<u8>>,
+}
+
+/// The Linux default of `net.ipv4.ipfrag_time`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_DATAGRAMS: usize = 256;
+/// Buffers of completed datagrams kept for reuse.
+const MAX_FREE: usize = 8;
+
+/// A timeout of 30 seconds and at most 256 pending datagrams.
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT)
+    }
+}
+
+impl Reassembler {
+    /// Creates a reassembler that drops datagrams `timeout` after their first fragment.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            timeout,
+            max_datagrams: DEFAULT_MAX_DATAGRAMS,
+            free: Vec::new(),
+        }
+    }
+    /// Sets how many incomplete datagrams are kept at most, 256 by default, which bounds
+    /// the memory a peer can tie up with fragments that never complete.
+    pub fn with_max_datagrams(mut self, max_datagrams: usize) -> Self {
+        self.max_datagrams = max_datagrams.max(1);
+        self
+    }
+    /// The number of incomplete datagrams.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+    /// Drops the datagrams whose timeout passed before `now`, and returns how many.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.pending.len();
+        let free = &mut self.free;
+        self.pending.retain(|_, pending| {
+            let keep = pending.deadline > now;
+            if !keep {
+                recycle(free, std::mem::take(&mut pending.data));
+            }
+            keep
+        });
+        before - self.pending.len()
+    }
+    /// Adds a received packet, which arrived at `now`.
+    ///
+    /// Returns the length of the packet written to `out` once a datagram is complete, or
+    /// right away for a packet that is not a fragment, and `None` while fragments are
+    /// missing. `out` must hold the reassembled packet, up to 65535 bytes (plus the IPv6
+    /// header). Expired datagrams are dropped first.
+    pub fn push(
+        &mut self,
+        packet: &[u8],
+        now: Instant,
+        out: &mut [u8],
+    ) -> io::Result<Option<usize>> {
+        self.expire(now);
+        let fragment = match parse_fragment(packet)? {
+            Ok(fragment) => fragment,
+            Err(whole) => {
+                let out = out.get_mut(..whole.len()).ok_or_else(buffer_too_small)?;
+                out.copy_from_slice(whole);
+                return Ok(Some(whole.len()));
+            }
+        };
+        let end = fragment.offset + fragment.data.len();
+        if fragment.more && fragment.data.len() % 8 != 0 {
+            return Err(invalid_data("fragment length not a multiple of 8"));
+        }
+        let header_limit = match fragment.key {
+            Key::V4 { .. } => 0,
+            Key::V6 { .. } => IPV6_HEADER_LEN,
+        };
+        if fragment.header.len() + end > MAX_PACKET_LEN + header_limit {
+            return Err(invalid_data("reassembled packet too long"));
+        }
+        if !self.pending.contains_key(&fragment.key) && self.pending.len() >= self.max_datagrams {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|(_, pending)| pending.deadline)
+                .map(|(key, _)| *key);
+            if let Some(key) = oldest {
+                self.drop_datagram(&key);
+            }
+        }
+        let deadline = now + self.timeout;
+        let free = &mut self.free;
+        let pending = self.pending.entry(fragment.key).or_insert_with(|| Pending {
+            deadline,
+            header: Vec::new(),
+            next_header: 0,
+            nh_offset: 0,
+            data: free.pop().unwrap_or_default(),
+            ranges: Vec::new(),
+            total: None,
+        });
+        if pending.ranges.contains(&(fragment.offset, end)) && !fragment.data.is_empty() {
+            // A retransmitted duplicate.
+            return Ok(None);
+        }
+        let overlaps = pending
+            .ranges
+            .iter()
+            .any(|&(start, stop)| fragment.offset < stop && start < end);
+        let beyond = |total: usize| end > total || (fragment.more && end == total);
+        let inconsistent = match (pending.total, fragment.more) {
+            (Some(total), false) => total != end,
+            (Some(total), true) => beyond(total),
+            (None, false) => pending.ranges.last().is_some_and(|&(_, stop)| stop > end),
+            (None, true) => false,
+        };
+        if overlaps || inconsistent {
+            self.drop_datagram(&fragment.key);
+            return Err(invalid_data(if overlaps {
+                "overlapping fragments"
+            } else {
+                "inconsistent fragment lengths"
+            }));
+        }
+        if pending.data.len() < end {
+            pending.data.resize(end, 0);
+        }
+        pending.data[fragment.offset..end].copy_from_slice(fragment.data);
+        insert_range(&mut pending.ranges, (fragment.offset, end));
+        if fragment.offset == 0 {
+            pending.header.clear();
+            pending.header.extend_from_slice(fragment.header);
+            pending.next_header = fragment.next_header;
+            pending.nh_offset = fragment.nh_offset;
+        }
+        if !fragment.more {
+            pending.total = Some(end);
+        }
+        let Some(total) = pending.total else {
+            return Ok(None);
+        };
+        if !covers(&pending.ranges, total) {
+            return Ok(None);
+        }
+        let pending = self
+            .pending
+            .remove(&fragment.key)
+            .expect("pending datagram");
+        let result = assemble(&pending, total, out);
+        recycle(&mut self.free, pending.data);
+        result.map(Some)
+    }
+    fn drop_datagram(&mut self, key: &Key) {
+        if let Some(pending) = self.pending.remove(key) {
+            recycle(&mut self.free, pending.data);
+        }
+    }
+}
+
+fn assemble(pending: &Pending, total: usize, out: &mut [u8]) -> io::Result<usize> {
+    let header = &pending.header[..];
+    let len = header.len() + total;
+    let out = out.get_mut(..len).ok_or_else(buffer_too_small)?;
+    out[..header.len()].copy_from_slice(header);
+    out[header.len()..].copy_from_slice(&pending.data[..total]);
+    if header[0] >> 4 == 4 {
+        let frag = u16::from_be_bytes([header[6], header[7]]) & IPV4_DF;
+        out[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        out[6..8].copy_from_slice(&frag.to_be_bytes());
+        set_ipv4_checksum(&mut out[..header.len()]);
+    } else {
+        out[4..6].copy_from_slice(&((len - IPV6_HEADER_LEN) as u16).to_be_bytes());
+        out[pending.nh_offset] = pending.next_header;
+    }
+    Ok(len)
+}
+
+/// Parses a fragment, or returns the packet itself if it is not one.
+fn parse_fragment(packet: &[u8]) -> io::Result<Result<Fragment<'_>, &[u8]>> {
+    match ip_version(packet)? {
+        IpVersion::V4 => {
+            let (header_len, total_len) = ipv4_lengths(packet)?;
+            let packet = &packet[..total_len];
+            let frag = u16::from_be_bytes([packet[6], packet[7]]);
+            if frag & (IPV4_MF | IPV4_OFFSET) == 0 {
+                return Ok(Err(packet));
+            }
+            Ok(Ok(Fragment {
+                key: Key::V4 {
+                    src: packet[12..16].try_into().unwrap(),
+                    dst: packet[16..20].try_into().unwrap(),
+                    proto: packet[9],
+                    id: u16::from_be_bytes([packet[4], packet[5]]),
+                },
+                header: &packet[..header_len],
+                next_header: 0,
+                nh_offset: 0,
+                offset: (frag & IPV4_OFFSET) as usize * 8,
+                more: frag & IPV4_MF != 0,
+                data: &packet[header_len..],
+            }))
+        }
+        IpVersion::V6 => {
+            let end = ipv6_end(packet)?;
+            let packet = &packet[..end];
+            let mut next = packet[6];
+            let mut nh_offset = 6;
+            let mut offset = IPV6_HEADER_LEN;
+            while matches!(next, PROTO_HOPOPTS | PROTO_ROUTING | PROTO_DSTOPTS) {
+                let len = ext_header_len(packet, offset)?;
+                next = packet[offset];
+                nh_offset = offset;
+                offset += len;
+            }
+            if next != PROTO_FRAGMENT {
+                return Ok(Err(packet));
+            }
+            let fragment = packet
+                .get(offset..offset + FRAGMENT_HEADER_LEN)
+                .ok_or_else(|| invalid_data("truncated Fragment Header"))?;
+            let field = u16::from_be_bytes([fragment[2], fragment[3]]);
+            Ok(Ok(Fragment {
+                key: Key::V6 {
+                    src: packet[8..24].try_into().unwrap(),
+                    dst: packet[24..40].try_into().unwrap(),
+                    id: u32::from_be_bytes(fragment[4..8].try_into().unwrap()),
+                },
+                header: &packet[..offset],
+                next_header: fragment[0],
+                nh_offset,
+                offset: (field & 0xfff8) as usize,
+                more: field & 1 != 0,
+                data: &packet[offset + FRAGMENT_HEADER_LEN..],
+            }))
+        }
+    }
+}
+
+/// Adds `range` to the sorted, disjoint `ranges`.
+fn insert_range(ranges: &mut Vec<(usize, usize)>, range: (usize, usize)) {
+    let index = ranges.partition_point(|&(start, _)| start < range.0);
+    ranges.insert(index, range);
+}
+
+/// Returns whether the sorted, disjoint `ranges` cover `0..total` without gaps.
+fn covers(ranges: &[(usize, usize)], total: usize) -> bool {
+    let mut next = 0;
+    for &(start, end) in ranges {
+        if start != next {
+            return false;
+        }
+        next = end;
+    }
+    next == total
+}
+
+fn recycle(free: &mut Vec<Vec<u8>>, mut buf: Vec<u8>) {
+    if free.len() < MAX_FREE {
+        buf.clear();
+        free.push(buf);
+    }
+}
+
+/// Returns the header length and total length of an IPv4 packet.
+fn ipv4_lengths(packet: &[u8]) -> io::Result<(usize, usize)> {
+    if packet.len() < IPV4_HEADER_LEN {
+        return Err(invalid_data("truncated IPv4 header"));
+    }
+    let header_len = (packet[0] & 0x0f) as usize * 4;
+    let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if header_len < IPV4_HEADER_LEN || total_len < header_len {
+        return Err(invalid_data("invalid IPv4 header length"));
+    }
+    if packet.len() < total_len {
+        return Err(invalid_data("truncated IPv4 packet"));
+    }
+    Ok((header_len, total_len))
+}
+
+/// Returns the end of an IPv6 packet, which may be followed by padding.
+fn ipv6_end(packet: &[u8]) -> io::Result<usize> {
+    if packet.len() < IPV6_HEADER_LEN {
+        return Err(invalid_data("truncated IPv6 header"));
+    }
+    let end = IPV6_HEADER_LEN + u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    if packet.len() < end {
+        return Err(invalid_data("truncated IPv6 packet"));
+    }
+    Ok(end)
+}
+
+fn ext_header_len(packet: &[u8], offset: usize) -> io::Result<usize> {
+    let len = match packet.get(offset + 1) {
+        Some(&len) => (len as usize + 1) * 8,
+        None => return Err(invalid_data("truncated extension header")),
+    };
+    if offset + len > packet.len() {
+        return Err(invalid_data("truncated extension header"));
+    }
+    Ok(len)
+}
+
+/// Returns the length of the unfragmentable part of an IPv6 packet: the header, the
+/// Hop-by-Hop Options and Routing headers and the Destination Options before the latter. Also
+/// returns the offset of the Next Header field the Fragment Header is inserted after.
+fn unfragmentable_len(packet: &[u8]) -> io::Result<(usize, usize)> {
+    let mut next = packet[6];
+    let mut offset = IPV6_HEADER_LEN;
+    let (mut split, mut nh_offset) = (IPV6_HEADER_LEN, 6);
+    while matches!(next, PROTO_HOPOPTS | PROTO_ROUTING | PROTO_DSTOPTS) {
+        let len = ext_header_len(packet, offset)?;
+        if next != PROTO_DSTOPTS {
+            split = offset + len;
+            nh_offset = offset;
+        }
+        next = packet[offset];
+        offset += len;
+    }
+    if next == PROTO_FRAGMENT {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "packet is already a fragment",
+        ));
+    }
+    Ok((split, nh_offset))
+}
+
+/// Copies the IPv4 header without the options that only the first fragment carries into
+/// `later`, and returns its length.
+fn copied_options(header: &[u8], later: &mut [u8; IPV4_MAX_HEADER_LEN]) -> io::Result<usize> {
+    later[..IPV4_HEADER_LEN].copy_from_slice(&header[..IPV4_HEADER_LEN]);
+    let mut len = IPV4_HEADER_LEN;
+    let mut i = IPV4_HEADER_LEN;
+    while i < header.len() {
+        match header[i] {
+            // End of Option List.
+            0 => break,
+            // No Operation.
+            1 => i += 1,
+            kind => {
+                let option_len = header.get(i + 1).copied().unwrap_or(0) as usize;
+                if option_len < 2 || i + option_len > header.len() {
+                    return Err(invalid_data("invalid IPv4 option"));
+                }
+                // The copied flag.
+                if kind & 0x80 != 0 {
+                    later[len..len + option_len].copy_from_slice(&header[i..i + option_len]);
+                    len += option_len;
+                }
+                i += option_len;
+            }
+        }
+    }
+    let padded = (len + 3) & !3;
+    later[len..padded].fill(0);
+    later[0] = 0x40 | (padded / 4) as u8;
+    Ok(padded)
+}
+
+/// A new IPv6 fragment Identification, unpredictable to others (RFC 7739).
+fn next_id() -> u32 {
+    static NEXT: OnceLock<AtomicU32> = OnceLock::new();
+    NEXT.get_or_init(|| AtomicU32::new(RandomState::new().build_hasher().finish() as u32))
+        .fetch_add(1, Ordering::Relaxed)
+}
+
+fn set_ipv4_checksum(header: &mut [u8]) {
+    header[10..12].fill(0);
+    let checksum = checksum(0, header);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+}
+
+fn sum(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+fn checksum(initial: u32, data: &[u8]) -> u16 {
+    let mut sum = sum(initial, data);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn buffer_too_small() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "buffer too small")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4(len: usize) -> Vec<u8> {
+        let mut packet: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        packet[..IPV4_HEADER_LEN].fill(0);
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        packet[4..6].copy_from_slice(&0x1234u16.to_be_bytes());
+        packet[8] = 64;
+        packet[9] = 17;
+        packet[12..20].copy_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        set_ipv4_checksum(&mut packet[..IPV4_HEADER_LEN]);
+        packet
+    }
+
+    fn ipv6(len: usize) -> Vec<u8> {
+        let mut packet: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        packet[..IPV6_HEADER_LEN].fill(0);
+        packet[0] = 0x60;
+        packet[4..6].copy_from_slice(&((len - IPV6_HEADER_LEN) as u16).to_be_bytes());
+        packet[6] = 17;
+        packet[7] = 64;
+        packet[8..24].copy_from_slice(&"fd00::1".parse::<std::net::Ipv6Addr>().unwrap().octets());
+        packet[24..40].copy_from_slice(&"fd00::2".parse::<std::net::Ipv6Addr>().unwrap().octets());
+        packet
+    }
+
+    fn split(packet: &[u8], mtu: usize) -> Vec<Vec<u8>> {
+        let mut fragments = fragment(packet, mtu).unwrap();
+        let mut buf = vec![0u8; mtu.max(packet.len())];
+        let mut out = Vec::new();
+        while let Some(len) = fragments.write_next(&mut buf).unwrap() {
+            assert!(len <= mtu);
+            out.push(buf[..len].to_vec());
+        }
+        out
+    }
+
+    fn reassemble(fragments: &[Vec<u8>]) -> Option<Vec<u8>> {
+        let mut reassembler = Reassembler::default();
+        let mut out = vec![0u8; 65575];
+        let now = Instant::now();
+        let mut whole = None;
+        for fragment in fragments {
+            if let Some(len) = reassembler.push(fragment, now, &mut out).unwrap() {
+                whole = Some(out[..len].to_vec());
+            }
+        }
+        whole
+    }
+
+    #[test]
+    fn ipv4_round_trip() {
+        let packet = ipv4(3000);
+        let mut fragments = split(&packet, 1280);
+        assert_eq!(fragments.len(), 3);
+        for fragment in &fragments {
+            assert_eq!(checksum(0, &fragment[..IPV4_HEADER_LEN]), 0);
+        }
+        fragments.reverse();
+        assert_eq!(reassemble(&fragments).unwrap(), packet);
+        // A packet that fits passes through both unchanged.
+        assert_eq!(split(&packet, 3000), std::slice::from_ref(&packet));
+        assert_eq!(reassemble(std::slice::from_ref(&packet)).unwrap(), packet);
+    }
+
+    #[test]
+    fn ipv4_refragment_and_options() {
+        let mut packet = ipv4(1000);
+        // A copied option (Security, 4 bytes) and one that is not (Record Route, 4 bytes).
+        let mut with_options = packet[..IPV4_HEADER_LEN].to_vec();
+        with_options.extend_from_slice(&[0x82, 4, 0, 0, 0x07, 4, 4, 0]);
+        with_options.extend_from_slice(&packet[IPV4_HEADER_LEN..]);
+        with_options[0] = 0x47;
+        with_options[2..4].copy_from_slice(&1008u16.to_be_bytes());
+        set_ipv4_checksum(&mut with_options[..28]);
+        let fragments = split(&with_options, 300);
+        assert_eq!(fragments[0][0], 0x47);
+        assert_eq!(fragments[1][0], 0x46);
+        assert_eq!(&fragments[1][20..24], &[0x82, 4, 0, 0]);
+
+        // Fragments of fragments reassemble into the original.
+        let halves = split(&packet, 600);
+        let quarters: Vec<_> = halves.iter().flat_map(|half| split(half, 300)).collect();
+        assert_eq!(quarters.len(), 5);
+        assert_eq!(reassemble(&quarters).unwrap(), packet);
+
+        packet[6] = 0x40; // DF
+        let err = fragment(&packet, 576).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn ipv6_round_trip() {
+        let mut packet = ipv6(3000);
+        // A Hop-by-Hop Options header stays in front of the Fragment Header.
+        packet.splice(40..40, [17, 0, 1, 4, 0, 0, 0, 0]);
+        packet[6] = PROTO_HOPOPTS;
+        packet[4..6].copy_from_slice(&(3000 - 40 + 8u16).to_be_bytes());
+        let fragments = split(&packet, 1280);
+        assert_eq!(fragments.len(), 3);
+        for fragment in &fragments {
+            assert_eq!(fragment[6], PROTO_HOPOPTS);
+            assert_eq!(fragment[40], PROTO_FRAGMENT);
+            assert_eq!(fragment[48], 17);
+        }
+        assert_eq!(reassemble(&fragments).unwrap(), packet);
+        let err = fragment(&fragments[0], 600).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn overlap_and_expiry() {
+        let packet = ipv4(2000);
+        let fragments = split(&packet, 1000);
+        let mut overlapping = fragments[1].clone();
+        // Move the second fragment 8 bytes back, into the first.
+        let frag = u16::from_be_bytes([overlapping[6], overlapping[7]]) - 1;
+        overlapping[6..8].copy_from_slice(&frag.to_be_bytes());
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let mut out = vec![0u8; 2000];
+        let now = Instant::now();
+        assert_eq!(
+            reassembler.push(&fragments[0], now, &mut out).unwrap(),
+            None
+        );
+        assert_eq!(
+            reassembler.push(&fragments[0], now, &mut out).unwrap(),
+            None
+        );
+        let err = reassembler.push(&overlapping, now, &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(reassembler.pending(), 0);
+
+        assert_eq!(
+            reassembler.push(&fragments[0], now, &mut out).unwrap(),
+            None
+        );
+        assert_eq!(reassembler.expire(now + Duration::from_secs(2)), 1);
+        let later = now + Duration::from_secs(3);
+        assert_eq!(
+            reassembler.push(&fragments[1], later, &mut out).unwrap(),
+            None
+        );
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    #[test]
+    fn retransmission_after_neighbour() {
+        let packet = ipv4(3000);
+        let fragments = split(&packet, 1000);
+        assert_eq!(fragments.len(), 4);
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let mut out = vec![0u8; 3000];
+        let now = Instant::now();
+        for fragment in [&fragments[0], &fragments[1], &fragments[0], &fragments[1]] {
+            assert_eq!(reassembler.push(fragment, now, &mut out).unwrap(), None);
+        }
+        assert_eq!(
+            reassembler.push(&fragments[3], now, &mut out).unwrap(),
+            None
+        );
+        assert_eq!(
+            reassembler.push(&fragments[1], now, &mut out).unwrap(),
+            None
+        );
+        let len = reassembler.push(&fragments[2], now, &mut out).unwrap();
+        assert_eq!(&out[..len.unwrap()], &packet[..]);
+    }
+
+    #[test]
+    fn too_big_errors() {
+        let packet = ipv4(1500);
+        let mut out = [0u8; 1280];
+        let len = packet_too_big(&packet, 1400, "10.0.0.254".parse().unwrap(), &mut out).unwrap();
+        assert_eq!(len, 576);
+        assert_eq!(checksum(0, &out[..20]), 0);
+        assert_eq!(checksum(0, &out[20..len]), 0);
+        assert_eq!(&out[16..20], &[10, 0, 0, 1]);
+        assert_eq!(&out[26..28], &1400u16.to_be_bytes());
+
+        let packet = ipv6(1500);
+        let len = packet_too_big(&packet, 1400, "fd00::fe".parse().unwrap(), &mut out).unwrap();
+        assert_eq!(len, 1280);
+        let pseudo = sum(0, &out[8..40]) + (len - 40) as u32 + PROTO_ICMPV6 as u32;
+        assert_eq!(checksum(pseudo, &out[40..len]), 0);
+        assert_eq!(&out[24..40], &packet[8..24]);
+
+        let err = packet_too_big(&packet, 1400, "10.0.0.254".parse().unwrap(), &mut out);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
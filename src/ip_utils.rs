@@ -0,0 +1,215 @@
+/*!
+# DSCP and ECN Helpers
+
+Read and rewrite the traffic class of IPv4 and IPv6 packets in place, e.g. on the buffer
+that is about to be passed to `send`. The traffic class is the IPv4 TOS byte or the IPv6
+Traffic Class field: six bits of DSCP (RFC 2474) followed by two bits of ECN (RFC 3168).
+
+IPv4 header checksums are fixed up incrementally (RFC 1624), so the packet stays valid
+without recomputing the checksum over the whole header. IPv6 has no header checksum.
+
+Tunnels typically copy the DSCP of the inner packet to the outer one, and propagate
+congestion marks between them:
+
+```
+use tun_rs::ip_utils::{self, Ecn};
+
+let mut inner = [0u8; 20];
+inner[0] = 0x45;
+inner[1] = 46 << 2; // DSCP EF
+let mut outer = [0u8; 40];
+outer[0] = 0x60;
+
+ip_utils::copy_dscp(&inner, &mut outer)?;
+ip_utils::set_ecn(&mut outer, Ecn::Ect0)?;
+assert_eq!(ip_utils::dscp(&outer)?, 46);
+assert_eq!(ip_utils::ecn(&outer)?, Ecn::Ect0);
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::framing::{ip_version, IpVersion};
+use std::io;
+
+/// The ECN codepoint of a packet (RFC 3168).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Ecn {
+    /// Not ECN-capable transport (`00`).
+    NotEct,
+    /// ECN-capable transport, ECT(1) (`01`).
+    Ect1,
+    /// ECN-capable transport, ECT(0) (`10`).
+    Ect0,
+    /// Congestion experienced (`11`).
+    Ce,
+}
+
+impl Ecn {
+    /// The codepoint from the two low bits of `bits`.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Ecn::NotEct,
+            0b01 => Ecn::Ect1,
+            0b10 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+    /// The two bits of the codepoint.
+    pub fn bits(self) -> u8 {
+        match self {
+            Ecn::NotEct => 0b00,
+            Ecn::Ect1 => 0b01,
+            Ecn::Ect0 => 0b10,
+            Ecn::Ce => 0b11,
+        }
+    }
+}
+
+/// Returns the traffic class of `packet`: the IPv4 TOS byte or the IPv6 Traffic Class.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if `packet` is not an IPv4 or IPv6 packet with
+/// a complete fixed header.
+pub fn traffic_class(packet: &[u8]) -> io::Result<u8> {
+    Ok(match checked_version(packet)? {
+        IpVersion::V4 => packet[1],
+        IpVersion::V6 => (packet[0] << 4) | (packet[1] >> 4),
+    })
+}
+
+/// Sets the traffic class of `packet`, fixing up the IPv4 header checksum.
+///
+/// Fails like [`traffic_class`].
+pub fn set_traffic_class(packet: &mut [u8], class: u8) -> io::Result<()> {
+    match checked_version(packet)? {
+        IpVersion::V4 => {
+            let old = u16::from_be_bytes([packet[0], packet[1]]);
+            packet[1] = class;
+            let new = u16::from_be_bytes([packet[0], packet[1]]);
+            let checksum = u16::from_be_bytes([packet[10], packet[11]]);
+            let checksum = update_checksum(checksum, old, new);
+            packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        IpVersion::V6 => {
+            packet[0] = (packet[0] & 0xf0) | (class >> 4);
+            packet[1] = (packet[1] & 0x0f) | (class << 4);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the DSCP of `packet`, the six high bits of the traffic class.
+pub fn dscp(packet: &[u8]) -> io::Result<u8> {
+    Ok(traffic_class(packet)? >> 2)
+}
+
+/// Sets the DSCP of `packet`, keeping its ECN bits. Only the six low bits of `dscp` are used;
+/// `0` clears it to the default class.
+pub fn set_dscp(packet: &mut [u8], dscp: u8) -> io::Result<()> {
+    let class = traffic_class(packet)?;
+    set_traffic_class(packet, (dscp << 2) | (class & 0b11))
+}
+
+/// Returns the ECN codepoint of `packet`.
+pub fn ecn(packet: &[u8]) -> io::Result<Ecn> {
+    Ok(Ecn::from_bits(traffic_class(packet)?))
+}
+
+/// Sets the ECN codepoint of `packet`, keeping its DSCP. [`Ecn::NotEct`] clears it.
+pub fn set_ecn(packet: &mut [u8], ecn: Ecn) -> io::Result<()> {
+    let class = traffic_class(packet)?;
+    set_traffic_class(packet, (class & !0b11) | ecn.bits())
+}
+
+/// Copies the DSCP of `from` to `to`, e.g. from the inner to the outer header of a tunneled
+/// packet. The ECN bits of `to` are kept. The packets may be of different IP versions.
+pub fn copy_dscp(from: &[u8], to: &mut [u8]) -> io::Result<()> {
+    set_dscp(to, dscp(from)?)
+}
+
+fn checked_version(packet: &[u8]) -> io::Result<IpVersion> {
+    let version = ip_version(packet)?;
+    let header_len = match version {
+        IpVersion::V4 => 20,
+        IpVersion::V6 => 40,
+    };
+    if packet.len() < header_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated IP header",
+        ));
+    }
+    Ok(version)
+}
+
+/// `HC' = ~(~HC + ~m + m')` of RFC 1624, for the 16-bit word `old` replaced by `new`.
+fn update_checksum(checksum: u16, old: u16, new: u16) -> u16 {
+    let mut sum = (!checksum as u32) + (!old as u32) + new as u32;
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_checksum(header: &[u8]) -> u16 {
+        let mut sum: u32 = header
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+            .sum();
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    fn ipv4() -> [u8; 20] {
+        let mut packet = [0u8; 20];
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&20u16.to_be_bytes());
+        packet[8] = 64;
+        packet[9] = 17;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        let checksum = header_checksum(&packet);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn ipv4_checksum_stays_valid() {
+        let mut packet = ipv4();
+        for (dscp, ecn) in [
+            (46, Ecn::Ce),
+            (0, Ecn::NotEct),
+            (63, Ecn::Ect1),
+            (10, Ecn::Ect0),
+        ] {
+            set_dscp(&mut packet, dscp).unwrap();
+            set_ecn(&mut packet, ecn).unwrap();
+            assert_eq!(
+                (super::dscp(&packet).unwrap(), super::ecn(&packet).unwrap()),
+                (dscp, ecn)
+            );
+            assert_eq!(header_checksum(&packet), 0);
+        }
+    }
+
+    #[test]
+    fn ipv6_traffic_class() {
+        let mut packet = [0u8; 40];
+        packet[0] = 0x60;
+        packet[1] = 0x0a; // flow label bits
+        set_traffic_class(&mut packet, 0xb9).unwrap();
+        assert_eq!(packet[0], 0x6b);
+        assert_eq!(packet[1], 0x9a);
+        assert_eq!(dscp(&packet).unwrap(), 0xb9 >> 2);
+        assert_eq!(ecn(&packet).unwrap(), Ecn::Ect1);
+        let mut outer = ipv4();
+        copy_dscp(&packet, &mut outer).unwrap();
+        assert_eq!(dscp(&outer).unwrap(), 0xb9 >> 2);
+        assert!(traffic_class(&packet[..39]).is_err());
+    }
+}
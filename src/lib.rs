@@ -169,11 +169,33 @@ On Linux, enable offload for improved throughput:
 
 ## Feature Flags
 
+- **`af_xdp`**: Enable the AF_XDP socket `af_xdp::XdpSocket` for veth/TAP topologies (Linux)
 - **`async`** (alias for `async_tokio`): Enable async support with Tokio runtime
 - **`async_tokio`**: Use Tokio for async I/O operations
 - **`async_io`**: Use async-io for async operations (async-std, smol, etc.)
 - **`async_framed`**: Enable framed I/O with futures
-- **`interruptible`**: Enable interruptible I/O operations
+- **`broker`**: Build devices in a privileged helper process, see [`broker`] (Unix)
+- **`counters`**: Count the packets and bytes the application exchanges with a device, see [`counters`]
+- **`ebpf`**: Enable attaching tc eBPF programs with `attach_tc_program` and queue steering programs with `set_steering_ebpf` (Linux)
+- **`embed-wintun`**: Embed `wintun.dll` into the binary on Windows, see the README
+- **`interruptible`**: Enable interruptible I/O operations (`recv_intr` and friends, also on `AsyncDevice`)
+- **`ip-frag`**: Enable IPv4/IPv6 fragmentation and reassembly in [`ip_frag`]
+- **`ip-utils`**: Enable the DSCP/ECN rewriting helpers in [`ip_utils`]
+- **`middleware`**: Enable composable hooks that inspect, rewrite or drop packets, see [`middleware`]
+- **`mss_clamp`**: Enable TCP MSS clamping of SYN packets, by hand or by the device, in [`mss_clamp`]
+- **`nat64`**: Enable the stateless NAT64/464XLAT translator in [`nat64`]
+- **`ndp_proxy`**: Enable `NdpProxy` for answering neighbour solicitations on an uplink (Linux, macOS)
+- **`pcap`**: Enable packet taps and pcap capture, see [`pcap`]
+- **`peer_routing`**: Enable the point-to-multipoint [`peer::PeerTable`]
+- **`serde`**: Implement `Serialize`/`Deserialize` for [`InterfaceConfig`], see [`DeviceBuilder::from_config`]
+- **`smoltcp`**: Enable the `smoltcp::phy::Device` adapters in `tun_rs::smoltcp`
+- **`switch`**: Enable the in-process learning Ethernet switch between TAP devices in [`switch`]
+- **`test-util`**: Enable the offload test and fuzzing harness in `tun_rs::testing` (Linux)
+- **`tokio_uring`**: Enable the io_uring based `uring::UringDevice` for tokio-uring (Linux)
+- **`tracing`**: Record spans and events about devices and their I/O with the `tracing` crate
+- **`tunnel`**: Enable [`tunnel::Tunnel`], pairing a device with a datagram transport
+- **`vhost-net`**: Enable the vhost-net ring API `vhost_net::VhostNet` for TUN/TAP devices (Linux)
+- **`winfw`**: Enable `add_firewall_rule_allow_all` for inbound allow rules of the Windows firewall (Windows)
 - **`experimental`**: Enable experimental features (unstable)
 
 ## Safety
@@ -202,6 +224,9 @@ Common error scenarios include:
 - Invalid configuration parameters
 */
 
+#[cfg(feature = "af_xdp")]
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub mod af_xdp;
 #[cfg(any(
     target_os = "windows",
     all(target_os = "linux", not(target_env = "ohos")),
@@ -211,7 +236,17 @@ Common error scenarios include:
     target_os = "netbsd",
 ))]
 pub use crate::builder::*;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub use crate::config::*;
 pub use crate::platform::*;
+pub use crate::pool::*;
 
 #[cfg_attr(docsrs, doc(cfg(any(feature = "async_io", feature = "async_tokio"))))]
 #[cfg(any(feature = "async_io", feature = "async_tokio"))]
@@ -221,6 +256,17 @@ mod async_device;
 #[cfg(any(feature = "async_io", feature = "async_tokio"))]
 pub use async_device::*;
 
+#[cfg(all(
+    feature = "broker",
+    any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )
+))]
+pub mod broker;
 #[cfg(any(
     target_os = "windows",
     all(target_os = "linux", not(target_env = "ohos")),
@@ -230,7 +276,106 @@ pub use async_device::*;
     target_os = "netbsd",
 ))]
 mod builder;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod config;
+#[cfg(feature = "counters")]
+pub mod counters;
+pub mod demux;
+mod error;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub mod ethernet;
+pub mod framing;
+#[cfg(feature = "ip-frag")]
+pub mod ip_frag;
+#[cfg(feature = "ip-utils")]
+pub mod ip_utils;
+#[cfg(feature = "middleware")]
+pub mod middleware;
+#[cfg(feature = "mss_clamp")]
+pub mod mss_clamp;
+pub mod multi_queue;
+#[cfg(feature = "nat64")]
+pub mod nat64;
+#[cfg(feature = "pcap")]
+pub mod pcap;
+#[cfg(feature = "peer_routing")]
+pub mod peer;
 mod platform;
+mod pool;
+#[cfg(feature = "smoltcp")]
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub mod smoltcp;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod splice;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub use splice::{splice, SpliceStats};
+#[cfg(feature = "switch")]
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub mod switch;
+#[cfg(feature = "test-util")]
+#[cfg(target_os = "linux")]
+pub mod testing;
+mod trace;
+#[cfg(feature = "tunnel")]
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub mod tunnel;
+#[cfg(feature = "tokio_uring")]
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub mod uring;
+#[cfg(feature = "vhost-net")]
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub mod vhost_net;
+
+pub use error::{Error, Result};
 
 /// Length of the protocol information header used on some platforms.
 ///
@@ -0,0 +1,261 @@
+/*!
+# Packet Middleware
+
+Hooks that inspect, rewrite or drop packets between the operating system and the caller,
+installed as a chain with `set_middleware`.
+
+Each [`PacketMiddleware`] sees every packet received (after the packet tap) and every packet
+sent (before the rate limit and the packet tap) through the device, on the sync and async
+device alike and whatever the call: `recv` and `send` with their variants, vectored,
+uninitialized, `try_`, polled, interruptible and deadline I/O, and `DeviceFramed`. The chain
+runs in order: the first middleware that drops a packet ends it. A dropped packet is skipped
+by a receive, which reads the next one (a `try_` receive fails with `WouldBlock` once none is
+left), and reported as sent by a send. While a chain is installed, vectored and uninitialized
+reads go through one contiguous buffer and vectored sends are gathered into one. A send that
+would block runs the chain again when it is retried.
+
+On Linux and macOS, `recv_multiple` and `send_multiple` run the chain too, once per packet.
+On a Linux device built with `offload(true)` that is each segment split from a GSO packet on
+receive and each packet before it is coalesced on send, and the chain runs there only: the
+single-packet calls, `send_with_hdr` and `send_with_checksum_offload` move the packets of
+such a device raw, behind their virtio network header. Frames injected with
+`send_as_outgoing` are seen when they are read back from the device.
+
+Received packets are handed over in the buffer of the caller, so they can grow up to its
+length. Sent packets are copied first and can only be rewritten in place or shortened.
+
+# Example
+
+```no_run
+use std::sync::atomic::{AtomicU64, Ordering};
+use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+use tun_rs::DeviceBuilder;
+
+/// Drops IPv6 and counts the rest.
+#[derive(Default)]
+struct Ipv4Only(AtomicU64);
+
+impl PacketMiddleware for Ipv4Only {
+    fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+        if packet.first().map(|b| b >> 4) != Some(4) {
+            return Verdict::Drop;
+        }
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Verdict::Pass
+    }
+}
+
+let dev = DeviceBuilder::new()
+    .ipv4("10.0.0.1", 24, None)
+    .build_sync()?
+    .with_middleware(vec![Box::new(Ipv4Only::default())]);
+let mut buf = [0u8; 1500];
+let len = dev.recv(&mut buf)?; // never an IPv6 packet
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// What a [`PacketMiddleware`] decides about a packet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Verdict {
+    /// Passes the packet, possibly rewritten, to the next middleware.
+    Pass,
+    /// Drops the packet.
+    Drop,
+}
+
+/// A packet passed through the middleware chain, dereferencing to its bytes.
+#[derive(Debug)]
+pub struct Packet<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Packet<'a> {
+    fn new(buf: &'a mut [u8], len: usize) -> Self {
+        Self { buf, len }
+    }
+    /// The length the packet can grow to.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+    /// Sets the length of the packet, keeping the bytes beyond the old length as they are.
+    ///
+    /// # Panics
+    ///
+    /// If `len` exceeds the [`capacity`](Self::capacity).
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.buf.len(), "packet length exceeds the capacity");
+        self.len = len;
+    }
+}
+
+impl Deref for Packet<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl DerefMut for Packet<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.len]
+    }
+}
+
+/// A hook on the I/O path of a device, see the [module documentation](self).
+///
+/// It runs on the thread or task doing the I/O and should not block. Both methods pass
+/// packets by default, so a middleware only implements the direction it cares about.
+pub trait PacketMiddleware: Send + Sync {
+    /// Called for a packet read from the device, before `recv` returns it.
+    fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+        let _ = packet;
+        Verdict::Pass
+    }
+    /// Called for a packet passed to `send`, before it is written to the device.
+    fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+        let _ = packet;
+        Verdict::Pass
+    }
+}
+
+/// Shared middleware slot of a device.
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareChain(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    enabled: AtomicBool,
+    chain: RwLock<Vec<Box<dyn PacketMiddleware>>>,
+}
+
+impl MiddlewareChain {
+    pub(crate) fn set(&self, chain: Vec<Box<dyn PacketMiddleware>>) {
+        let mut guard = self.0.chain.write().unwrap();
+        self.0.enabled.store(!chain.is_empty(), Ordering::Release);
+        *guard = chain;
+    }
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.enabled.load(Ordering::Acquire)
+    }
+    fn run(&self, packet: &mut Packet<'_>, recv: bool) -> Verdict {
+        for middleware in self.0.chain.read().unwrap().iter() {
+            let verdict = if recv {
+                middleware.on_recv(packet)
+            } else {
+                middleware.on_send(packet)
+            };
+            if verdict == Verdict::Drop {
+                return Verdict::Drop;
+            }
+        }
+        Verdict::Pass
+    }
+    /// Runs the chain over the `len` bytes received into `buf`, and returns the new length,
+    /// or `None` if the packet was dropped.
+    #[inline]
+    pub(crate) fn inbound(&self, buf: &mut [u8], len: usize) -> Option<usize> {
+        if !self.is_set() {
+            return Some(len);
+        }
+        let mut packet = Packet::new(buf, len);
+        match self.run(&mut packet, true) {
+            Verdict::Drop => None,
+            Verdict::Pass => Some(packet.len),
+        }
+    }
+    /// Runs the chain over a copy of `buf`, or returns `buf` itself if no middleware is
+    /// installed, and `None` if the packet was dropped.
+    #[inline]
+    pub(crate) fn outbound<'a>(&self, buf: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        if !self.is_set() {
+            return Some(Cow::Borrowed(buf));
+        }
+        let mut copy = buf.to_vec();
+        let mut packet = Packet::new(&mut copy, buf.len());
+        if self.run(&mut packet, false) == Verdict::Drop {
+            return None;
+        }
+        let len = packet.len;
+        copy.truncate(len);
+        Some(Cow::Owned(copy))
+    }
+}
+
+impl crate::platform::DeviceImpl {
+    /// Installs `chain`, run for every packet received or sent, replacing the installed one.
+    /// An empty chain removes it.
+    ///
+    /// Queues cloned from this device share the chain.
+    pub fn set_middleware(&self, chain: Vec<Box<dyn PacketMiddleware>>) {
+        self.middleware.set(chain)
+    }
+    /// Returns whether a middleware chain is installed.
+    pub fn has_middleware(&self) -> bool {
+        self.middleware.is_set()
+    }
+}
+
+impl crate::SyncDevice {
+    /// Installs `chain` with [`set_middleware`](crate::platform::DeviceImpl::set_middleware)
+    /// and returns the device, for use right after building it.
+    pub fn with_middleware(self, chain: Vec<Box<dyn PacketMiddleware>>) -> Self {
+        self.set_middleware(chain);
+        self
+    }
+}
+
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+impl crate::AsyncDevice {
+    /// Installs `chain` with [`set_middleware`](crate::platform::DeviceImpl::set_middleware)
+    /// and returns the device, for use right after building it.
+    pub fn with_middleware(self, chain: Vec<Box<dyn PacketMiddleware>>) -> Self {
+        self.set_middleware(chain);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Rewrite;
+
+    impl PacketMiddleware for Rewrite {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            packet[0] = 0xff;
+            packet.set_len(2);
+            Verdict::Pass
+        }
+        fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.is_empty() {
+                Verdict::Drop
+            } else {
+                Verdict::Pass
+            }
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let chain = MiddlewareChain::default();
+        let mut buf = [1u8, 2, 3, 4];
+        assert_eq!(chain.inbound(&mut buf, 6), Some(6));
+        assert!(matches!(chain.outbound(&buf), Some(Cow::Borrowed(_))));
+
+        chain.set(vec![Box::new(Rewrite)]);
+        assert_eq!(chain.inbound(&mut buf, 3), Some(2));
+        assert_eq!(buf, [0xff, 2, 3, 4]);
+        assert_eq!(chain.outbound(&[]), None);
+        assert_eq!(chain.outbound(&buf).as_deref(), Some(&buf[..]));
+
+        chain.set(Vec::new());
+        assert!(!chain.is_set());
+    }
+}
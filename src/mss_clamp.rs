@@ -0,0 +1,366 @@
+/*!
+# TCP MSS Clamping
+
+Lowers the MSS option of TCP SYN and SYN-ACK packets, like the `TCPMSS` target of iptables,
+so that the endpoints of a connection never send segments larger than what fits through a
+tunnel. This matters wherever path MTU discovery is broken, e.g. because ICMP "fragmentation
+needed" messages are filtered somewhere along the path: without it, connections stall as
+soon as a full-sized segment is sent.
+
+[`mss_clamp`] rewrites a single IP packet in place, and [`clamp_to_mtu`] does so with the
+largest MSS that fits into an MTU. The option is only ever lowered, and the TCP checksum is
+fixed up incrementally (RFC 1624). IPv6 extension headers are not followed, and only the
+first fragment of an IPv4 packet is looked at.
+
+Devices can also clamp on their own with `set_mss_clamp`: every packet received and sent
+through the device is then rewritten, by the same calls that run the middleware chain, before
+the chain sees a received packet and after it handled a sent one. `recv_multiple` and
+`send_multiple` clamp each packet of the batch on Linux and macOS; on a Linux device built
+with `offload(true)` that is each segment split from a GSO packet on receive and each packet
+before it is coalesced on send, so checksums are complete when the option is rewritten, and
+the single-packet calls leave the packets of such a device alone.
+
+# Example
+
+```no_run
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new()
+    .ipv4("10.0.0.1", 24, None)
+    .mtu(1420)
+    .build_sync()?;
+// 1420 minus 40 bytes of IPv6 and 20 of TCP headers, enough for either IP version.
+dev.set_mss_clamp(Some(1360))?;
+let mut buf = [0u8; 1500];
+let len = dev.recv(&mut buf)?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::framing::{ETH_P_IP, ETH_P_IPV6};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const IPPROTO_TCP: u8 = 6;
+const TCP_FLAG_SYN: u8 = 0x02;
+const TCP_OPT_END: u8 = 0;
+const TCP_OPT_NOP: u8 = 1;
+const TCP_OPT_MSS: u8 = 2;
+/// IPv4 and TCP headers without options.
+const IPV4_OVERHEAD: u16 = 40;
+/// IPv6 and TCP headers without options.
+const IPV6_OVERHEAD: u16 = 60;
+
+/// Lowers the MSS option of the TCP SYN or SYN-ACK in the IP packet `packet` to `mss`,
+/// keeping the TCP checksum valid, and returns whether it did.
+///
+/// Packets that are not TCP SYNs, or whose MSS is already at most `mss`, are left alone.
+///
+/// # Example
+/// ```
+/// use tun_rs::mss_clamp::mss_clamp;
+///
+/// // An ICMP packet is not touched.
+/// let mut packet = [0x45, 0, 0, 20, 0, 0, 0, 0, 64, 1, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2];
+/// assert!(!mss_clamp(&mut packet, 1360));
+/// ```
+pub fn mss_clamp(packet: &mut [u8], mss: u16) -> bool {
+    clamp(packet, mss, mss)
+}
+
+/// Lowers the MSS option to what fits into `mtu` for the IP version of `packet`, i.e. `mtu`
+/// minus 40 bytes for IPv4 and 60 for IPv6, like `--clamp-mss-to-pmtu` of iptables. See
+/// [`mss_clamp`].
+pub fn clamp_to_mtu(packet: &mut [u8], mtu: u16) -> bool {
+    clamp(
+        packet,
+        mtu.saturating_sub(IPV4_OVERHEAD),
+        mtu.saturating_sub(IPV6_OVERHEAD),
+    )
+}
+
+fn clamp(packet: &mut [u8], max_v4: u16, max_v6: u16) -> bool {
+    let Some(MssOption {
+        at,
+        check_at,
+        mss,
+        max,
+    }) = find_mss(packet, max_v4, max_v6)
+    else {
+        return false;
+    };
+    packet[at..at + 2].copy_from_slice(&max.to_be_bytes());
+    // RFC 1624: HC' = ~(~HC + ~m + m')
+    let check = u16::from_be_bytes([packet[check_at], packet[check_at + 1]]);
+    let mut sum = (!check) as u32 + (!mss) as u32 + max as u32;
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    packet[check_at..check_at + 2].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+    true
+}
+
+/// An MSS option above the maximum for its IP version.
+struct MssOption {
+    /// Offset of the option value in the IP packet.
+    at: usize,
+    /// Offset of the TCP checksum in the IP packet.
+    check_at: usize,
+    mss: u16,
+    max: u16,
+}
+
+fn find_mss(packet: &[u8], max_v4: u16, max_v6: u16) -> Option<MssOption> {
+    let (l4, protocol, max) = match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            // Only the first fragment has the TCP header.
+            if u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff != 0 {
+                return None;
+            }
+            ((packet[0] & 0x0f) as usize * 4, packet[9], max_v4)
+        }
+        // Extension headers are not followed.
+        Some(6) if packet.len() >= 40 => (40, packet[6], max_v6),
+        _ => return None,
+    };
+    if protocol != IPPROTO_TCP {
+        return None;
+    }
+    let tcp = packet.get(l4..)?;
+    if tcp.len() < 20 || tcp[13] & TCP_FLAG_SYN == 0 {
+        return None;
+    }
+    let header_len = ((tcp[12] >> 4) as usize * 4).min(tcp.len());
+    let mut i = 20;
+    while i < header_len {
+        match tcp[i] {
+            TCP_OPT_END => return None,
+            TCP_OPT_NOP => i += 1,
+            kind => {
+                let len = *tcp.get(i + 1)? as usize;
+                if len < 2 || i + len > header_len {
+                    return None;
+                }
+                if kind == TCP_OPT_MSS && len == 4 {
+                    let mss = u16::from_be_bytes([tcp[i + 2], tcp[i + 3]]);
+                    return (mss > max).then_some(MssOption {
+                        at: l4 + i + 2,
+                        check_at: l4 + 16,
+                        mss,
+                        max,
+                    });
+                }
+                i += len;
+            }
+        }
+    }
+    None
+}
+
+/// Where the IP packet starts in `packet`, after the packet information header if `pi` and
+/// the Ethernet header if `l2`, or `None` if it carries no IP packet.
+pub(crate) fn ip_offset(packet: &[u8], pi: bool, l2: bool) -> Option<usize> {
+    let mut offset = if pi {
+        crate::PACKET_INFORMATION_LENGTH
+    } else {
+        0
+    };
+    if l2 {
+        let ethertype = packet.get(offset + 12..offset + ETHERNET_HEADER_LEN)?;
+        let ethertype = u16::from_be_bytes([ethertype[0], ethertype[1]]);
+        if ethertype != ETH_P_IP && ethertype != ETH_P_IPV6 {
+            return None;
+        }
+        offset += ETHERNET_HEADER_LEN;
+    }
+    (offset < packet.len()).then_some(offset)
+}
+
+/// Shared MSS clamp setting of a device; `0` if clamping is off.
+#[derive(Clone, Default)]
+pub(crate) struct MssClamp(Arc<AtomicU16>);
+
+impl MssClamp {
+    fn get(&self) -> Option<u16> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            mss => Some(mss),
+        }
+    }
+}
+
+impl crate::platform::DeviceImpl {
+    /// Clamps the MSS option of TCP SYN and SYN-ACK packets received from and sent to the
+    /// device to `mss`, or stops clamping with `None`. See the
+    /// [module documentation](crate::mss_clamp).
+    ///
+    /// Queues cloned from this device share the setting. Fails with
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput) for an MSS of zero.
+    pub fn set_mss_clamp(&self, mss: Option<u16>) -> std::io::Result<()> {
+        if mss == Some(0) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the MSS must not be zero",
+            ));
+        }
+        self.mss_clamp.0.store(mss.unwrap_or(0), Ordering::Relaxed);
+        Ok(())
+    }
+    /// Returns the MSS that TCP SYN packets are clamped to, if any.
+    pub fn mss_clamp(&self) -> Option<u16> {
+        self.mss_clamp.get()
+    }
+    /// Clamps a packet of `len` bytes received into `buf`.
+    #[inline]
+    pub(crate) fn mss_clamp_inbound(&self, buf: &mut [u8], len: usize) {
+        let Some(mss) = self.mss_clamp.get() else {
+            return;
+        };
+        let packet = &mut buf[..len];
+        if let Some(offset) = self.ip_offset(packet) {
+            mss_clamp(&mut packet[offset..], mss);
+        }
+    }
+    /// Clamps a packet about to be written, copying it only if it is rewritten.
+    #[inline]
+    pub(crate) fn mss_clamp_outbound<'a>(&self, mut packet: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        let Some(mss) = self.mss_clamp.get() else {
+            return packet;
+        };
+        if let Some(offset) = self.ip_offset(&packet) {
+            if find_mss(&packet[offset..], mss, mss).is_some() {
+                mss_clamp(&mut packet.to_mut()[offset..], mss);
+            }
+        }
+        packet
+    }
+    fn ip_offset(&self, packet: &[u8]) -> Option<usize> {
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        let (pi, l2) = (self.packet_information(), self.is_tap());
+        #[cfg(any(target_os = "ios", target_os = "tvos"))]
+        let (pi, l2) = (self.packet_information(), false);
+        #[cfg(not(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        )))]
+        let (pi, l2) = (false, false);
+        ip_offset(packet, pi, l2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The one's complement sum of `data`, folded.
+    fn checksum(data: &[u8]) -> u16 {
+        let mut sum: u32 = data
+            .chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+            .sum();
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    /// An IPv4 TCP SYN from 10.0.0.1 to 10.0.0.2 with the MSS option `mss` and a valid
+    /// checksum.
+    fn syn(mss: u16) -> Vec<u8> {
+        let mut packet = vec![
+            0x45,
+            0,
+            0,
+            44,
+            0,
+            0,
+            0x40,
+            0,
+            64,
+            IPPROTO_TCP,
+            0,
+            0,
+            10,
+            0,
+            0,
+            1,
+            10,
+            0,
+            0,
+            2,
+        ];
+        let mut tcp = vec![0u8; 24];
+        tcp[..4].copy_from_slice(&[0x30, 0x39, 0, 80]);
+        tcp[12] = 6 << 4;
+        tcp[13] = TCP_FLAG_SYN;
+        tcp[20..24].copy_from_slice(&[TCP_OPT_MSS, 4, (mss >> 8) as u8, mss as u8]);
+        let mut pseudo = packet[12..20].to_vec();
+        pseudo.extend_from_slice(&[0, IPPROTO_TCP, 0, tcp.len() as u8]);
+        pseudo.extend_from_slice(&tcp);
+        let check = checksum(&pseudo);
+        tcp[16..18].copy_from_slice(&check.to_be_bytes());
+        packet.extend_from_slice(&tcp);
+        packet
+    }
+
+    fn tcp_checksum_ok(packet: &[u8]) -> bool {
+        let mut pseudo = packet[12..20].to_vec();
+        pseudo.extend_from_slice(&[0, IPPROTO_TCP, 0, (packet.len() - 20) as u8]);
+        pseudo.extend_from_slice(&packet[20..]);
+        checksum(&pseudo) == 0
+    }
+
+    #[test]
+    fn mss_lowered() {
+        let mut packet = syn(1460);
+        assert!(mss_clamp(&mut packet, 1360));
+        assert_eq!(u16::from_be_bytes([packet[42], packet[43]]), 1360);
+        assert!(tcp_checksum_ok(&packet));
+
+        // Already small enough.
+        let mut packet = syn(1200);
+        assert!(!mss_clamp(&mut packet, 1360));
+        // Not a SYN.
+        let mut packet = syn(1460);
+        packet[33] = 0x10;
+        assert!(!mss_clamp(&mut packet, 1360));
+        // A SYN-ACK.
+        let mut packet = syn(1460);
+        packet[33] = 0x12;
+        assert!(mss_clamp(&mut packet, 1360));
+    }
+
+    #[test]
+    fn mss_clamped_to_mtu() {
+        let mut packet = syn(1460);
+        assert!(clamp_to_mtu(&mut packet, 1420));
+        assert_eq!(u16::from_be_bytes([packet[42], packet[43]]), 1380);
+        assert!(tcp_checksum_ok(&packet));
+    }
+
+    #[test]
+    fn ip_offset_of_framings() {
+        assert_eq!(ip_offset(&[0x45; 20], false, false), Some(0));
+        assert_eq!(ip_offset(&[0x45; 20], true, false), Some(4));
+        let mut frame = [0u8; 34];
+        frame[12..14].copy_from_slice(&ETH_P_IP.to_be_bytes());
+        assert_eq!(ip_offset(&frame, false, true), Some(ETHERNET_HEADER_LEN));
+        frame[12..14].copy_from_slice(&0x0806u16.to_be_bytes());
+        assert_eq!(ip_offset(&frame, false, true), None);
+    }
+}
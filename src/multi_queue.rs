@@ -0,0 +1,329 @@
+/*!
+# Multi-queue Device Groups
+
+[`MultiQueueDevice`] owns all queues of one device and schedules packets across them:
+
+- [`MultiQueueDevice::send`] picks the queue of each packet by [`flow_hash`], a hash of its
+  flow (addresses, protocol and ports), so the packets of one connection keep their order.
+  Another scheduler can be set with [`MultiQueueDevice::with_scheduler`].
+- [`MultiQueueDevice::recv`] waits on all queues and reads from them in turn, so a busy queue
+  cannot starve the others.
+- [`MultiQueueDevice::queues`] hands out the individual queues for workers pinned to one
+  queue each.
+
+On Linux the queues are real `IFF_MULTI_QUEUE` queues, attached with
+[`SyncDevice::try_clone`]. Other platforms have a single queue per device; there the group
+emulates `N` queues that all share it, so the same code runs everywhere.
+
+# Example
+
+```no_run
+# #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+# {
+use tun_rs::multi_queue::MultiQueueDevice;
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new()
+    .ipv4("10.0.0.1", 24, None)
+    .multi_queue(true)
+    .build_sync()?;
+let group = MultiQueueDevice::new(dev, 4)?;
+
+// Pinned workers, one per queue ...
+for queue in group.queues().iter().cloned() {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 65536];
+        while let Ok(len) = queue.recv(&mut buf) {
+            queue.send(&buf[..len]).unwrap();
+        }
+    });
+}
+// ... or one loop over all of them.
+let mut buf = [0u8; 65536];
+let (len, queue) = group.recv(&mut buf)?;
+println!("{len} bytes from queue {queue}");
+group.send(&buf[..len])?;
+# }
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::demux::PacketMeta;
+use crate::SyncDevice;
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A device with several queues, with flow-hashed sends and fair receives.
+pub struct MultiQueueDevice {
+    queues: Vec<Arc<SyncDevice>>,
+    // The queue `recv` looks at first.
+    next: AtomicUsize,
+    scheduler: fn(&[u8]) -> u32,
+}
+
+impl MultiQueueDevice {
+    /// Creates a group of `queues` queues from `device`, which becomes the first queue.
+    ///
+    /// On Linux the device must have been built with `multi_queue(true)` if `queues` is more
+    /// than one. Elsewhere the queues are emulated by sharing `device`. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `queues` is zero.
+    pub fn new(device: SyncDevice, queues: usize) -> io::Result<Self> {
+        if queues == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a device group needs at least one queue",
+            ));
+        }
+        let device = Arc::new(device);
+        let mut group = Vec::with_capacity(queues);
+        for _ in 1..queues {
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            group.push(Arc::new(device.try_clone()?));
+            #[cfg(not(all(target_os = "linux", not(target_env = "ohos"))))]
+            group.push(device.clone());
+        }
+        group.insert(0, device);
+        Ok(Self {
+            queues: group,
+            next: AtomicUsize::new(0),
+            scheduler: flow_hash,
+        })
+    }
+    /// Creates a group from already attached queues of one device.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `queues` is empty.
+    pub fn from_queues(queues: Vec<SyncDevice>) -> io::Result<Self> {
+        if queues.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a device group needs at least one queue",
+            ));
+        }
+        Ok(Self {
+            queues: queues.into_iter().map(Arc::new).collect(),
+            next: AtomicUsize::new(0),
+            scheduler: flow_hash,
+        })
+    }
+    /// Replaces [`flow_hash`] as the function `send` picks queues with.
+    ///
+    /// A packet goes to queue `scheduler(packet) % queue_count()`.
+    pub fn with_scheduler(mut self, scheduler: fn(&[u8]) -> u32) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+    /// The number of queues.
+    pub fn queue_count(&self) -> usize {
+        self.queues.len()
+    }
+    /// The queues, for workers that each serve one queue.
+    pub fn queues(&self) -> &[Arc<SyncDevice>] {
+        &self.queues
+    }
+    /// The queue at `index`.
+    pub fn queue(&self, index: usize) -> Option<&Arc<SyncDevice>> {
+        self.queues.get(index)
+    }
+    /// The index of the queue `send` uses for `packet`.
+    ///
+    /// With the default scheduler both directions of a flow map to the same queue. Packets
+    /// that are not IP, or whose headers cannot be parsed, go to the first queue.
+    pub fn queue_for(&self, packet: &[u8]) -> usize {
+        (self.scheduler)(packet) as usize % self.queues.len()
+    }
+    /// Sends `packet` on the queue of its flow.
+    pub fn send(&self, packet: &[u8]) -> io::Result<usize> {
+        self.queues[self.queue_for(packet)].send(packet)
+    }
+    /// Receives a packet from the next queue that has one, and returns its length and the
+    /// index of the queue.
+    ///
+    /// The queues are visited round-robin, starting after the queue of the previous packet.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let start = self.next.load(Ordering::Relaxed) % self.queues.len();
+        let index = self.wait_readable(start)?;
+        let len = self.queues[index].recv(buf)?;
+        self.next.store(index + 1, Ordering::Relaxed);
+        Ok((len, index))
+    }
+    /// Waits until a queue is readable and returns the first one at or after `start`.
+    #[cfg(unix)]
+    fn wait_readable(&self, start: usize) -> io::Result<usize> {
+        use std::os::fd::AsRawFd;
+
+        if self.queues.len() == 1 {
+            return Ok(0);
+        }
+        let mut fds: Vec<libc::pollfd> = self
+            .queues
+            .iter()
+            .map(|queue| libc::pollfd {
+                fd: queue.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        loop {
+            let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) };
+            if res < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            let ready = (start..fds.len())
+                .chain(0..start)
+                .find(|&i| fds[i].revents != 0);
+            if let Some(index) = ready {
+                return Ok(index);
+            }
+        }
+    }
+    /// Emulated queues share one device, so any of them is as good as the others.
+    #[cfg(not(unix))]
+    fn wait_readable(&self, start: usize) -> io::Result<usize> {
+        Ok(start)
+    }
+}
+
+/// Hashes the flow of an IP packet: its addresses, protocol and ports.
+///
+/// Follows the kernel's skb flow hash: the 5-tuple is put in a canonical order, so both
+/// directions of a flow hash the same, and hashed with `jhash2`. The kernel seeds its hash
+/// randomly at boot, so the values differ from `skb->hash`, but packets are grouped the same
+/// way. Use it to spread packets over the queues of a multi-queue device, e.g. with
+/// `flow_hash(packet) % queues`, without reordering any flow.
+///
+/// Returns 0 for packets that are not IP or whose headers cannot be parsed, and never 0
+/// otherwise.
+///
+/// # Example
+/// ```
+/// use tun_rs::multi_queue::flow_hash;
+///
+/// let mut packet = [0u8; 28];
+/// packet[0] = 0x45;
+/// packet[2..4].copy_from_slice(&28u16.to_be_bytes());
+/// packet[9] = 17; // UDP
+/// packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+/// packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+/// let queue = flow_hash(&packet) % 4;
+/// # assert!(queue < 4);
+/// ```
+pub fn flow_hash(packet: &[u8]) -> u32 {
+    let meta = PacketMeta::parse(packet);
+    let (Some(source), Some(destination)) = (meta.source, meta.destination) else {
+        return 0;
+    };
+    let mut a = (source, meta.source_port.unwrap_or(0));
+    let mut b = (destination, meta.destination_port.unwrap_or(0));
+    // The kernel's `__flow_hash_consistentify`: lower address first, or lower port on a tie.
+    if b < a {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let mut words = [0u32; 10];
+    let ethertype: u32 = if source.is_ipv4() { 0x0800 } else { 0x86dd };
+    words[0] = ethertype << 16 | u32::from(meta.protocol.unwrap_or(0)) << 8;
+    words[1] = u32::from(a.1) << 16 | u32::from(b.1);
+    let mut len = 2;
+    for address in [a.0, b.0] {
+        match address {
+            IpAddr::V4(v4) => {
+                words[len] = u32::from(v4);
+                len += 1;
+            }
+            IpAddr::V6(v6) => {
+                for chunk in v6.octets().chunks_exact(4) {
+                    words[len] = u32::from_be_bytes(chunk.try_into().unwrap());
+                    len += 1;
+                }
+            }
+        }
+    }
+    match jhash2(&words[..len], 0) {
+        0 => 1,
+        hash => hash,
+    }
+}
+
+/// The kernel's `jhash2`, Bob Jenkins' lookup3 over 32-bit words.
+fn jhash2(mut k: &[u32], initval: u32) -> u32 {
+    let mut a = 0xdeadbeef_u32
+        .wrapping_add((k.len() as u32) << 2)
+        .wrapping_add(initval);
+    let (mut b, mut c) = (a, a);
+    while k.len() > 3 {
+        a = a.wrapping_add(k[0]);
+        b = b.wrapping_add(k[1]);
+        c = c.wrapping_add(k[2]);
+        // __jhash_mix
+        a = a.wrapping_sub(c) ^ c.rotate_left(4);
+        c = c.wrapping_add(b);
+        b = b.wrapping_sub(a) ^ a.rotate_left(6);
+        a = a.wrapping_add(c);
+        c = c.wrapping_sub(b) ^ b.rotate_left(8);
+        b = b.wrapping_add(a);
+        a = a.wrapping_sub(c) ^ c.rotate_left(16);
+        c = c.wrapping_add(b);
+        b = b.wrapping_sub(a) ^ a.rotate_left(19);
+        a = a.wrapping_add(c);
+        c = c.wrapping_sub(b) ^ b.rotate_left(4);
+        b = b.wrapping_add(a);
+        k = &k[3..];
+    }
+    if k.is_empty() {
+        return c;
+    }
+    a = a.wrapping_add(k[0]);
+    if let Some(&word) = k.get(1) {
+        b = b.wrapping_add(word);
+    }
+    if let Some(&word) = k.get(2) {
+        c = c.wrapping_add(word);
+    }
+    // __jhash_final
+    c = (c ^ b).wrapping_sub(b.rotate_left(14));
+    a = (a ^ c).wrapping_sub(c.rotate_left(11));
+    b = (b ^ a).wrapping_sub(a.rotate_left(25));
+    c = (c ^ b).wrapping_sub(b.rotate_left(16));
+    a = (a ^ c).wrapping_sub(c.rotate_left(4));
+    b = (b ^ a).wrapping_sub(a.rotate_left(14));
+    (c ^ b).wrapping_sub(b.rotate_left(24))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flow_hash, jhash2};
+
+    fn udp(source: [u8; 4], destination: [u8; 4], sport: u16, dport: u16) -> Vec<u8> {
+        let mut packet = vec![0u8; 28];
+        packet[0] = 0x45;
+        packet[2..4].copy_from_slice(&28u16.to_be_bytes());
+        packet[9] = 17;
+        packet[12..16].copy_from_slice(&source);
+        packet[16..20].copy_from_slice(&destination);
+        packet[20..22].copy_from_slice(&sport.to_be_bytes());
+        packet[22..24].copy_from_slice(&dport.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn flow_hash_is_symmetric() {
+        let forward = udp([10, 0, 0, 1], [10, 0, 0, 2], 4000, 53);
+        let reverse = udp([10, 0, 0, 2], [10, 0, 0, 1], 53, 4000);
+        let other = udp([10, 0, 0, 1], [10, 0, 0, 2], 4001, 53);
+        assert_eq!(flow_hash(&forward), flow_hash(&reverse));
+        assert_ne!(flow_hash(&forward), flow_hash(&other));
+        assert_eq!(flow_hash(&[0u8; 3]), 0);
+    }
+
+    #[test]
+    fn jhash2_matches_lookup3() {
+        // hashword() from lookup3.c.
+        assert_eq!(jhash2(&[], 0), 0xdeadbeef);
+        assert_eq!(jhash2(&[0], 0), 0x049396b8);
+    }
+}
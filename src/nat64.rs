@@ -0,0 +1,874 @@
+/*!
+# Stateless NAT64 Translation
+
+Translates packets between IPv4 and IPv6 as described in RFC 7915 (SIIT), with IPv4
+addresses embedded in IPv6 prefixes as described in RFC 6052. This is the job of the
+customer-side translator (CLAT) of 464XLAT (RFC 6877): IPv4 packets written by applications
+to the device leave as IPv6 packets towards the NAT64 of the network, and the IPv6 packets
+coming back are turned into IPv4 again.
+
+A [`Translator`] maps the IPv4 addresses of the host into a `local` prefix, and all other
+IPv4 addresses into the `remote` prefix of the NAT64, e.g. the well-known `64:ff9b::/96`
+or a prefix discovered with RFC 7050. IPv6 packets are only translated if their source is in
+the remote prefix and their destination in the local one.
+
+```
+use std::net::Ipv6Addr;
+use tun_rs::nat64::{Nat64Prefix, Translator, MAX_OVERHEAD};
+
+let local = Nat64Prefix::new("2001:db8:aaaa::".parse().unwrap(), 96)?;
+let translator = Translator::new(local, Nat64Prefix::WELL_KNOWN);
+
+// A UDP packet from 192.0.0.2 to 198.51.100.1, without payload or checksum.
+let mut packet = [0u8; 28];
+packet[..4].copy_from_slice(&[0x45, 0, 0, 28]);
+packet[8..10].copy_from_slice(&[64, 17]);
+packet[12..20].copy_from_slice(&[192, 0, 0, 2, 198, 51, 100, 1]);
+packet[24..26].copy_from_slice(&8u16.to_be_bytes());
+
+let mut out = [0u8; 28 + MAX_OVERHEAD];
+let len = translator.ipv4_to_ipv6(&packet, &mut out)?;
+assert_eq!(len, 48);
+let dst: [u8; 16] = out[24..40].try_into().unwrap();
+assert_eq!(Ipv6Addr::from(dst), "64:ff9b::198.51.100.1".parse::<Ipv6Addr>().unwrap());
+# Ok::<(), std::io::Error>(())
+```
+
+TCP and UDP checksums are fixed up incrementally for the new pseudo header (RFC 1624), so
+fragments are translated as well, with the IPv6 Fragment Header carrying the IPv4
+fragmentation fields and vice versa. ICMP and ICMPv6 messages are mapped onto each other as
+far as RFC 7915 allows, including the packet quoted by an error message. IPv4 options and
+IPv6 extension headers other than the Fragment Header are dropped.
+
+Packets that cannot be translated fail with [`io::ErrorKind::InvalidData`] if they are
+malformed, and with [`io::ErrorKind::Unsupported`] otherwise, e.g. for addresses outside
+the prefixes or ICMP types without an equivalent. Either way they should be dropped.
+*/
+
+use crate::framing::{ip_version, IpVersion};
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU16, Ordering};
+
+/// The most bytes a packet can grow by when translated: the larger IPv6 header plus a
+/// Fragment Header, or the larger IPv6 headers of an ICMP error and of the packet it quotes.
+pub const MAX_OVERHEAD: usize = 48;
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const FRAGMENT_HEADER_LEN: usize = 8;
+const ICMP_HEADER_LEN: usize = 8;
+/// ICMPv6 errors must fit in the minimum IPv6 MTU (RFC 4443).
+const IPV6_MIN_MTU: usize = 1280;
+/// Larger translated packets get the DF flag set (RFC 7915, section 5.1).
+const DF_THRESHOLD: usize = 1260;
+
+const PROTO_HOPOPTS: u8 = 0;
+const PROTO_ICMP: u8 = 1;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+const PROTO_ROUTING: u8 = 43;
+const PROTO_FRAGMENT: u8 = 44;
+const PROTO_ICMPV6: u8 = 58;
+const PROTO_DSTOPTS: u8 = 60;
+
+/// An IPv6 prefix that IPv4 addresses are embedded in, as described in RFC 6052.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Nat64Prefix {
+    octets: [u8; 16],
+    len: u8,
+}
+
+impl Nat64Prefix {
+    /// The well-known prefix `64:ff9b::/96`.
+    pub const WELL_KNOWN: Self = Self {
+        octets: [0, 0x64, 0xff, 0x9b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        len: 96,
+    };
+
+    /// Creates a prefix of `len` bits, which must be 32, 40, 48, 56, 64 or 96. Bits of
+    /// `prefix` past `len` are ignored.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] for other lengths, or if bits
+    /// 64 to 71 of a /96 prefix are set, which RFC 6052 reserves.
+    pub fn new(prefix: Ipv6Addr, len: u8) -> io::Result<Self> {
+        if !matches!(len, 32 | 40 | 48 | 56 | 64 | 96) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "NAT64 prefix length must be 32, 40, 48, 56, 64 or 96",
+            ));
+        }
+        let mut octets = prefix.octets();
+        if len == 96 && octets[8] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "bits 64 to 71 of a NAT64 prefix must be zero",
+            ));
+        }
+        octets[len as usize / 8..].fill(0);
+        Ok(Self { octets, len })
+    }
+    /// The prefix, with all bits past its length cleared.
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.octets.into()
+    }
+    /// The length of the prefix in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+    /// Embeds `addr` into the prefix.
+    pub fn embed(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.octets;
+        let mut at = self.len as usize / 8;
+        for octet in addr.octets() {
+            // Bits 64 to 71 are skipped.
+            if at == 8 {
+                at += 1;
+            }
+            octets[at] = octet;
+            at += 1;
+        }
+        octets.into()
+    }
+    /// Extracts the IPv4 address embedded in `addr`, or `None` if `addr` is not in the prefix.
+    pub fn extract(&self, addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        let octets = addr.octets();
+        let mut at = self.len as usize / 8;
+        if octets[..at] != self.octets[..at] || octets[8] != 0 {
+            return None;
+        }
+        let mut embedded = [0u8; 4];
+        for octet in &mut embedded {
+            if at == 8 {
+                at += 1;
+            }
+            *octet = octets[at];
+            at += 1;
+        }
+        Some(embedded.into())
+    }
+}
+
+impl fmt::Display for Nat64Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.prefix(), self.len)
+    }
+}
+
+/// A stateless IPv4/IPv6 translator.
+///
+/// The translator only keeps a counter for the Identification field of IPv4 packets, so a
+/// single instance can be shared by the tasks reading from and writing to the device.
+#[derive(Debug)]
+pub struct Translator {
+    local: Nat64Prefix,
+    remote: Nat64Prefix,
+    next_id: AtomicU16,
+}
+
+impl Translator {
+    /// Creates a translator mapping the IPv4 addresses of the host into `local` and all other
+    /// IPv4 addresses into `remote`, the prefix of the NAT64.
+    pub fn new(local: Nat64Prefix, remote: Nat64Prefix) -> Self {
+        Self {
+            local,
+            remote,
+            next_id: AtomicU16::new(0),
+        }
+    }
+    /// The prefix the IPv4 addresses of the host are mapped into.
+    pub fn local(&self) -> Nat64Prefix {
+        self.local
+    }
+    /// The prefix of the NAT64 all other IPv4 addresses are mapped into.
+    pub fn remote(&self) -> Nat64Prefix {
+        self.remote
+    }
+    /// Translates `packet` to the other IP version, see [`Translator::ipv4_to_ipv6`] and
+    /// [`Translator::ipv6_to_ipv4`].
+    pub fn translate(&self, packet: &[u8], out: &mut [u8]) -> io::Result<usize> {
+        match ip_version(packet)? {
+            IpVersion::V4 => self.ipv4_to_ipv6(packet, out),
+            IpVersion::V6 => self.ipv6_to_ipv4(packet, out),
+        }
+    }
+    /// Translates an IPv4 packet sent by the host into an IPv6 packet, written to `out`.
+    /// Returns the length of the IPv6 packet.
+    ///
+    /// `out` should be [`MAX_OVERHEAD`] bytes longer than `packet`.
+    pub fn ipv4_to_ipv6(&self, packet: &[u8], out: &mut [u8]) -> io::Result<usize> {
+        self.v4_to_v6(packet, out, &self.local, &self.remote, false)
+    }
+    /// Translates an IPv6 packet for the host into an IPv4 packet, written to `out`.
+    /// Returns the length of the IPv4 packet.
+    ///
+    /// `out` should be at least as long as `packet`.
+    pub fn ipv6_to_ipv4(&self, packet: &[u8], out: &mut [u8]) -> io::Result<usize> {
+        self.v6_to_v4(packet, out, &self.remote, &self.local, false)
+    }
+
+    /// `quoted` is set for the packet quoted by an ICMP error, which may be truncated and
+    /// goes the other way, so the caller swaps `src` and `dst`.
+    fn v4_to_v6(
+        &self,
+        packet: &[u8],
+        out: &mut [u8],
+        src: &Nat64Prefix,
+        dst: &Nat64Prefix,
+        quoted: bool,
+    ) -> io::Result<usize> {
+        if packet.len() < IPV4_HEADER_LEN || packet[0] >> 4 != 4 {
+            return Err(invalid_data("not an IPv4 packet"));
+        }
+        let header_len = (packet[0] & 0x0f) as usize * 4;
+        let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+        if header_len < IPV4_HEADER_LEN || total_len < header_len || packet.len() < header_len {
+            return Err(invalid_data("invalid IPv4 header length"));
+        }
+        let end = if quoted {
+            total_len.min(packet.len())
+        } else if total_len > packet.len() {
+            return Err(invalid_data("truncated IPv4 packet"));
+        } else {
+            total_len
+        };
+        let payload = &packet[header_len..end];
+        let frag = u16::from_be_bytes([packet[6], packet[7]]);
+        let more_fragments = frag & 0x2000 != 0;
+        let frag_offset = frag & 0x1fff;
+        let fragmented = more_fragments || frag_offset != 0;
+        let proto = packet[9];
+        let next_header = if proto == PROTO_ICMP {
+            PROTO_ICMPV6
+        } else {
+            proto
+        };
+        let mut addrs = [0u8; 32];
+        addrs[..16].copy_from_slice(&src.embed(ipv4_addr(&packet[12..16])).octets());
+        addrs[16..].copy_from_slice(&dst.embed(ipv4_addr(&packet[16..20])).octets());
+
+        let out_header_len = if fragmented {
+            IPV6_HEADER_LEN + FRAGMENT_HEADER_LEN
+        } else {
+            IPV6_HEADER_LEN
+        };
+        if out.len() < out_header_len {
+            return Err(buffer_too_small());
+        }
+        let (header, out_payload) = out.split_at_mut(out_header_len);
+        let payload_len = if proto == PROTO_ICMP {
+            if fragmented {
+                return Err(unsupported("fragmented ICMP packet"));
+            }
+            let len = self.icmp_to_icmpv6(payload, out_payload, src, dst, quoted)?;
+            if !quoted {
+                let pseudo = pseudo_header(&addrs, len, PROTO_ICMPV6);
+                let checksum = checksum(pseudo, &out_payload[..len]);
+                out_payload[2..4].copy_from_slice(&checksum.to_be_bytes());
+            }
+            len
+        } else {
+            let out_payload = out_payload
+                .get_mut(..payload.len())
+                .ok_or_else(buffer_too_small)?;
+            out_payload.copy_from_slice(payload);
+            if proto == PROTO_UDP && has_zero_udp_checksum(out_payload) {
+                // IPv6 requires UDP checksums, which can only be computed over a whole datagram.
+                if !quoted {
+                    if fragmented {
+                        return Err(unsupported("fragmented UDP packet without checksum"));
+                    }
+                    let pseudo = pseudo_header(&addrs, out_payload.len(), PROTO_UDP);
+                    let checksum = match checksum(pseudo, out_payload) {
+                        0 => 0xffff,
+                        checksum => checksum,
+                    };
+                    out_payload[6..8].copy_from_slice(&checksum.to_be_bytes());
+                }
+            } else if frag_offset == 0 {
+                fix_transport_checksum(proto, out_payload, &packet[12..20], &addrs);
+            }
+            payload.len()
+        };
+
+        let extension_len = out_header_len - IPV6_HEADER_LEN;
+        // A quoted packet keeps the length of the original one.
+        let payload_length = if quoted {
+            total_len - header_len + extension_len
+        } else {
+            payload_len + extension_len
+        };
+        let tos = packet[1];
+        header[..4].copy_from_slice(&[0x60 | tos >> 4, tos << 4, 0, 0]);
+        header[4..6].copy_from_slice(&(payload_length as u16).to_be_bytes());
+        header[6] = if fragmented {
+            PROTO_FRAGMENT
+        } else {
+            next_header
+        };
+        header[7] = packet[8];
+        header[8..40].copy_from_slice(&addrs);
+        if fragmented {
+            let offset = frag_offset << 3 | more_fragments as u16;
+            header[40..42].copy_from_slice(&[next_header, 0]);
+            header[42..44].copy_from_slice(&offset.to_be_bytes());
+            header[44..46].fill(0);
+            header[46..48].copy_from_slice(&packet[4..6]);
+        }
+        Ok(out_header_len + payload_len)
+    }
+
+    fn v6_to_v4(
+        &self,
+        packet: &[u8],
+        out: &mut [u8],
+        src: &Nat64Prefix,
+        dst: &Nat64Prefix,
+        quoted: bool,
+    ) -> io::Result<usize> {
+        if packet.len() < IPV6_HEADER_LEN || packet[0] >> 4 != 6 {
+            return Err(invalid_data("not an IPv6 packet"));
+        }
+        let payload_length = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+        let end = if quoted {
+            (IPV6_HEADER_LEN + payload_length).min(packet.len())
+        } else if IPV6_HEADER_LEN + payload_length > packet.len() {
+            return Err(invalid_data("truncated IPv6 packet"));
+        } else {
+            IPV6_HEADER_LEN + payload_length
+        };
+        let src_addr = src
+            .extract(ipv6_addr(&packet[8..24]))
+            .ok_or_else(|| unsupported("source address outside the NAT64 prefix"))?;
+        let dst_addr = dst
+            .extract(ipv6_addr(&packet[24..40]))
+            .ok_or_else(|| unsupported("destination address outside the NAT64 prefix"))?;
+        let mut addrs = [0u8; 8];
+        addrs[..4].copy_from_slice(&src_addr.octets());
+        addrs[4..].copy_from_slice(&dst_addr.octets());
+
+        let mut next_header = packet[6];
+        let mut at = IPV6_HEADER_LEN;
+        let mut fragment = None;
+        loop {
+            match next_header {
+                PROTO_HOPOPTS | PROTO_ROUTING | PROTO_DSTOPTS => {
+                    let extension = packet
+                        .get(at..at + 4)
+                        .ok_or_else(|| invalid_data("truncated extension header"))?;
+                    if next_header == PROTO_ROUTING && extension[3] != 0 {
+                        return Err(unsupported("routing header with segments left"));
+                    }
+                    next_header = extension[0];
+                    at += (extension[1] as usize + 1) * 8;
+                }
+                PROTO_FRAGMENT => {
+                    let extension = packet
+                        .get(at..at + FRAGMENT_HEADER_LEN)
+                        .ok_or_else(|| invalid_data("truncated extension header"))?;
+                    next_header = extension[0];
+                    let offset = u16::from_be_bytes([extension[2], extension[3]]);
+                    fragment = Some((offset, [extension[6], extension[7]]));
+                    at += FRAGMENT_HEADER_LEN;
+                }
+                _ => break,
+            }
+        }
+        if at > end {
+            return Err(invalid_data("truncated extension header"));
+        }
+        let payload = &packet[at..end];
+        let (id, frag) = match fragment {
+            Some((offset, id)) => (id, offset >> 3 | (offset & 1) << 13),
+            None => {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed).to_be_bytes();
+                let df = if IPV4_HEADER_LEN + payload.len() > DF_THRESHOLD {
+                    0x4000
+                } else {
+                    0
+                };
+                (id, df)
+            }
+        };
+        let fragmented = frag & 0x3fff != 0;
+        let proto = if next_header == PROTO_ICMPV6 {
+            PROTO_ICMP
+        } else {
+            next_header
+        };
+
+        if out.len() < IPV4_HEADER_LEN {
+            return Err(buffer_too_small());
+        }
+        let (header, out_payload) = out.split_at_mut(IPV4_HEADER_LEN);
+        let payload_len = if next_header == PROTO_ICMPV6 {
+            if fragmented {
+                return Err(unsupported("fragmented ICMPv6 packet"));
+            }
+            let len = self.icmpv6_to_icmp(payload, out_payload, src, dst, quoted)?;
+            if !quoted {
+                let checksum = checksum(0, &out_payload[..len]);
+                out_payload[2..4].copy_from_slice(&checksum.to_be_bytes());
+            }
+            len
+        } else {
+            let out_payload = out_payload
+                .get_mut(..payload.len())
+                .ok_or_else(buffer_too_small)?;
+            out_payload.copy_from_slice(payload);
+            // A zero UDP checksum stays zero, which IPv4 reads as no checksum.
+            if frag & 0x1fff == 0 && !(proto == PROTO_UDP && has_zero_udp_checksum(out_payload)) {
+                fix_transport_checksum(proto, out_payload, &packet[8..40], &addrs);
+            }
+            payload.len()
+        };
+
+        // A quoted packet keeps the length of the original one.
+        let total_len = if quoted {
+            IPV4_HEADER_LEN + payload_length.saturating_sub(at - IPV6_HEADER_LEN)
+        } else {
+            IPV4_HEADER_LEN + payload_len
+        };
+        let traffic_class = packet[0] << 4 | packet[1] >> 4;
+        header[..2].copy_from_slice(&[0x45, traffic_class]);
+        header[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+        header[4..6].copy_from_slice(&id);
+        header[6..8].copy_from_slice(&frag.to_be_bytes());
+        header[8..12].copy_from_slice(&[packet[7], proto, 0, 0]);
+        header[12..20].copy_from_slice(&addrs);
+        let checksum = checksum(0, header);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        Ok(IPV4_HEADER_LEN + payload_len)
+    }
+
+    /// Maps an ICMP message onto ICMPv6 (RFC 7915, section 4.2). The checksum is left zero.
+    fn icmp_to_icmpv6(
+        &self,
+        icmp: &[u8],
+        out: &mut [u8],
+        src: &Nat64Prefix,
+        dst: &Nat64Prefix,
+        quoted: bool,
+    ) -> io::Result<usize> {
+        if icmp.len() < ICMP_HEADER_LEN {
+            return Err(invalid_data("truncated ICMP header"));
+        }
+        let (icmp_type, code) = (icmp[0], icmp[1]);
+        let mut rest = [0u8; 4];
+        let (icmp_type, code, error) = match (icmp_type, code) {
+            (8, 0) => {
+                rest.copy_from_slice(&icmp[4..8]);
+                (128, 0, false)
+            }
+            (0, 0) => {
+                rest.copy_from_slice(&icmp[4..8]);
+                (129, 0, false)
+            }
+            (3, 0 | 1 | 5 | 6 | 7 | 8 | 11 | 12) => (1, 0, true),
+            (3, 9 | 10 | 13 | 15) => (1, 1, true),
+            (3, 3) => (1, 4, true),
+            (3, 2) => {
+                // Points at the Next Header field.
+                rest[3] = 6;
+                (4, 1, true)
+            }
+            (3, 4) => {
+                let mtu = u16::from_be_bytes([icmp[6], icmp[7]]) as u32 + 20;
+                rest = mtu.max(IPV6_MIN_MTU as u32).to_be_bytes();
+                (2, 0, true)
+            }
+            (11, 0 | 1) => (3, code, true),
+            (12, 0 | 2) => {
+                rest[3] = ipv4_to_ipv6_pointer(icmp[4])
+                    .ok_or_else(|| unsupported("untranslatable parameter problem pointer"))?;
+                (4, 0, true)
+            }
+            _ => {
+                return Err(unsupported(format!(
+                    "ICMP type {icmp_type} code {code} has no ICMPv6 equivalent"
+                )))
+            }
+        };
+        if error && quoted {
+            return Err(unsupported("ICMP error quoted by an ICMP error"));
+        }
+        let header = out
+            .get_mut(..ICMP_HEADER_LEN)
+            .ok_or_else(buffer_too_small)?;
+        header[..4].copy_from_slice(&[icmp_type, code, 0, 0]);
+        header[4..].copy_from_slice(&rest);
+        let body = &icmp[ICMP_HEADER_LEN..];
+        let body_len = if error {
+            let len = self.v4_to_v6(body, &mut out[ICMP_HEADER_LEN..], dst, src, true)?;
+            len.min(IPV6_MIN_MTU - IPV6_HEADER_LEN - ICMP_HEADER_LEN)
+        } else {
+            out.get_mut(ICMP_HEADER_LEN..ICMP_HEADER_LEN + body.len())
+                .ok_or_else(buffer_too_small)?
+                .copy_from_slice(body);
+            body.len()
+        };
+        Ok(ICMP_HEADER_LEN + body_len)
+    }
+
+    /// Maps an ICMPv6 message onto ICMP (RFC 7915, section 5.2). The checksum is left zero.
+    fn icmpv6_to_icmp(
+        &self,
+        icmp: &[u8],
+        out: &mut [u8],
+        src: &Nat64Prefix,
+        dst: &Nat64Prefix,
+        quoted: bool,
+    ) -> io::Result<usize> {
+        if icmp.len() < ICMP_HEADER_LEN {
+            return Err(invalid_data("truncated ICMPv6 header"));
+        }
+        let (icmp_type, code) = (icmp[0], icmp[1]);
+        let mut rest = [0u8; 4];
+        let (icmp_type, code, error) = match (icmp_type, code) {
+            (128, 0) => {
+                rest.copy_from_slice(&icmp[4..8]);
+                (8, 0, false)
+            }
+            (129, 0) => {
+                rest.copy_from_slice(&icmp[4..8]);
+                (0, 0, false)
+            }
+            (1, 0 | 2 | 3) => (3, 1, true),
+            (1, 1) => (3, 10, true),
+            (1, 4) => (3, 3, true),
+            (2, 0) => {
+                let mtu = u32::from_be_bytes([icmp[4], icmp[5], icmp[6], icmp[7]]);
+                let mtu = mtu.saturating_sub(20).min(u16::MAX as u32) as u16;
+                rest[2..].copy_from_slice(&mtu.to_be_bytes());
+                (3, 4, true)
+            }
+            (3, 0 | 1) => (11, code, true),
+            (4, 0) => {
+                let pointer = u32::from_be_bytes([icmp[4], icmp[5], icmp[6], icmp[7]]);
+                rest[0] = u8::try_from(pointer)
+                    .ok()
+                    .and_then(ipv6_to_ipv4_pointer)
+                    .ok_or_else(|| unsupported("untranslatable parameter problem pointer"))?;
+                (12, 0, true)
+            }
+            (4, 1) => (3, 2, true),
+            _ => {
+                return Err(unsupported(format!(
+                    "ICMPv6 type {icmp_type} code {code} has no ICMP equivalent"
+                )))
+            }
+        };
+        if error && quoted {
+            return Err(unsupported("ICMPv6 error quoted by an ICMPv6 error"));
+        }
+        let header = out
+            .get_mut(..ICMP_HEADER_LEN)
+            .ok_or_else(buffer_too_small)?;
+        header[..4].copy_from_slice(&[icmp_type, code, 0, 0]);
+        header[4..].copy_from_slice(&rest);
+        let body = &icmp[ICMP_HEADER_LEN..];
+        let body_len = if error {
+            self.v6_to_v4(body, &mut out[ICMP_HEADER_LEN..], dst, src, true)?
+        } else {
+            out.get_mut(ICMP_HEADER_LEN..ICMP_HEADER_LEN + body.len())
+                .ok_or_else(buffer_too_small)?
+                .copy_from_slice(body);
+            body.len()
+        };
+        Ok(ICMP_HEADER_LEN + body_len)
+    }
+}
+
+/// Figure 3 of RFC 7915.
+fn ipv4_to_ipv6_pointer(pointer: u8) -> Option<u8> {
+    match pointer {
+        0 | 1 => Some(pointer),
+        2 | 3 => Some(4),
+        8 => Some(7),
+        9 => Some(6),
+        12..=15 => Some(8),
+        16..=19 => Some(24),
+        _ => None,
+    }
+}
+
+/// Figure 6 of RFC 7915.
+fn ipv6_to_ipv4_pointer(pointer: u8) -> Option<u8> {
+    match pointer {
+        0 | 1 => Some(pointer),
+        4 | 5 => Some(2),
+        6 => Some(9),
+        7 => Some(8),
+        8..=23 => Some(12),
+        24..=39 => Some(16),
+        _ => None,
+    }
+}
+
+fn has_zero_udp_checksum(udp: &[u8]) -> bool {
+    udp.len() >= 8 && udp[6..8] == [0, 0]
+}
+
+/// Fixes up the TCP or UDP checksum of `payload` for the addresses of the pseudo header
+/// changing from `old` to `new`. A truncated header is left alone.
+fn fix_transport_checksum(proto: u8, payload: &mut [u8], old: &[u8], new: &[u8]) {
+    let at = match proto {
+        PROTO_TCP => 16,
+        PROTO_UDP => 6,
+        _ => return,
+    };
+    let Some(field) = payload.get_mut(at..at + 2) else {
+        return;
+    };
+    let checksum = u16::from_be_bytes([field[0], field[1]]);
+    // RFC 1624, eqn. 3: HC' = ~(~HC + ~m + m')
+    let sum = (!checksum) as u32 + (!fold(sum(0, old))) as u32 + fold(sum(0, new)) as u32;
+    let checksum = match !fold(sum) {
+        0 if proto == PROTO_UDP => 0xffff,
+        checksum => checksum,
+    };
+    field.copy_from_slice(&checksum.to_be_bytes());
+}
+
+fn pseudo_header(addrs: &[u8], len: usize, proto: u8) -> u32 {
+    sum(0, addrs) + (len as u32 >> 16) + (len as u32 & 0xffff) + proto as u32
+}
+
+fn sum(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+fn fold(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+fn checksum(initial: u32, data: &[u8]) -> u16 {
+    !fold(sum(initial, data))
+}
+
+fn ipv4_addr(octets: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])
+}
+
+fn ipv6_addr(octets: &[u8]) -> Ipv6Addr {
+    let mut addr = [0u8; 16];
+    addr.copy_from_slice(octets);
+    addr.into()
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn unsupported(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg.into())
+}
+
+fn buffer_too_small() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "buffer too small")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translator() -> Translator {
+        let local = Nat64Prefix::new("2001:db8:aaaa::".parse().unwrap(), 96).unwrap();
+        Translator::new(local, Nat64Prefix::WELL_KNOWN)
+    }
+
+    fn ipv4(proto: u8, src: [u8; 4], dst: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; IPV4_HEADER_LEN];
+        packet[0] = 0x45;
+        packet[1] = 0xb8;
+        packet[2..4].copy_from_slice(&((IPV4_HEADER_LEN + payload.len()) as u16).to_be_bytes());
+        packet[8] = 64;
+        packet[9] = proto;
+        packet[12..16].copy_from_slice(&src);
+        packet[16..20].copy_from_slice(&dst);
+        let checksum = checksum(0, &packet);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn udp(src: [u8; 4], dst: [u8; 4]) -> Vec<u8> {
+        let mut udp = vec![0u8; 8];
+        udp[..4].copy_from_slice(&[0x30, 0x39, 0x00, 0x35]);
+        udp[4..6].copy_from_slice(&12u16.to_be_bytes());
+        udp.extend_from_slice(b"ping");
+        let mut addrs = src.to_vec();
+        addrs.extend_from_slice(&dst);
+        let checksum = checksum(pseudo_header(&addrs, udp.len(), PROTO_UDP), &udp);
+        udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+        udp
+    }
+
+    fn ipv6_payload_valid(packet: &[u8], proto: u8) -> bool {
+        let payload = &packet[IPV6_HEADER_LEN..];
+        checksum(pseudo_header(&packet[8..40], payload.len(), proto), payload) == 0
+    }
+
+    #[test]
+    fn rfc6052_examples() {
+        let addr = Ipv4Addr::new(192, 0, 2, 33);
+        for (prefix, len, embedded) in [
+            ("2001:db8::", 32, "2001:db8:c000:221::"),
+            ("2001:db8:100::", 40, "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::", 48, "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::", 56, "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::", 64, "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::", 96, "2001:db8:122:344::192.0.2.33"),
+        ] {
+            let prefix = Nat64Prefix::new(prefix.parse().unwrap(), len).unwrap();
+            let embedded: Ipv6Addr = embedded.parse().unwrap();
+            assert_eq!(prefix.embed(addr), embedded);
+            assert_eq!(prefix.extract(embedded), Some(addr));
+        }
+        assert!(Nat64Prefix::new("2001:db8::".parse().unwrap(), 80).is_err());
+        assert_eq!(Nat64Prefix::WELL_KNOWN.to_string(), "64:ff9b::/96");
+        assert_eq!(
+            Nat64Prefix::WELL_KNOWN.extract("2001:db8::1".parse().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn udp_round_trip() {
+        let translator = translator();
+        let (src, dst) = ([192, 0, 0, 2], [198, 51, 100, 1]);
+        let packet = ipv4(PROTO_UDP, src, dst, &udp(src, dst));
+        let mut v6 = [0u8; 128];
+        let len = translator.ipv4_to_ipv6(&packet, &mut v6).unwrap();
+        let v6 = &v6[..len];
+        assert_eq!(len, packet.len() + 20);
+        assert_eq!((v6[0], v6[1], v6[6], v6[7]), (0x6b, 0x80, PROTO_UDP, 64));
+        assert!(ipv6_payload_valid(v6, PROTO_UDP));
+
+        // The reply comes back with the addresses swapped.
+        let mut reply = v6.to_vec();
+        reply[8..40].rotate_left(16);
+        let mut v4 = [0u8; 128];
+        let len = translator.ipv6_to_ipv4(&reply, &mut v4).unwrap();
+        let v4 = &v4[..len];
+        assert_eq!(len, packet.len());
+        assert_eq!(checksum(0, &v4[..IPV4_HEADER_LEN]), 0);
+        assert_eq!(&v4[12..16], &dst);
+        assert_eq!(&v4[16..20], &src);
+        let udp = &v4[IPV4_HEADER_LEN..];
+        assert_eq!(
+            checksum(pseudo_header(&v4[12..20], udp.len(), PROTO_UDP), udp),
+            0
+        );
+    }
+
+    #[test]
+    fn zero_udp_checksum_is_computed() {
+        let (src, dst) = ([192, 0, 0, 2], [198, 51, 100, 1]);
+        let mut udp = udp(src, dst);
+        udp[6..8].fill(0);
+        let packet = ipv4(PROTO_UDP, src, dst, &udp);
+        let mut v6 = [0u8; 128];
+        let len = translator().ipv4_to_ipv6(&packet, &mut v6).unwrap();
+        assert!(ipv6_payload_valid(&v6[..len], PROTO_UDP));
+    }
+
+    #[test]
+    fn fragments_carry_a_fragment_header() {
+        let (src, dst) = ([192, 0, 0, 2], [198, 51, 100, 1]);
+        let mut packet = ipv4(PROTO_UDP, src, dst, &udp(src, dst));
+        packet[4..6].copy_from_slice(&0x1234u16.to_be_bytes());
+        packet[6..8].copy_from_slice(&0x2000u16.to_be_bytes());
+        let translator = translator();
+        let mut v6 = [0u8; 128];
+        let len = translator.ipv4_to_ipv6(&packet, &mut v6).unwrap();
+        assert_eq!(len, packet.len() + 28);
+        assert_eq!(v6[6], PROTO_FRAGMENT);
+        assert_eq!(&v6[40..48], &[PROTO_UDP, 0, 0, 1, 0, 0, 0x12, 0x34]);
+
+        let mut reply = v6[..len].to_vec();
+        reply[8..40].rotate_left(16);
+        let mut v4 = [0u8; 128];
+        let len = translator.ipv6_to_ipv4(&reply, &mut v4).unwrap();
+        assert_eq!(len, packet.len());
+        assert_eq!(&v4[4..8], &[0x12, 0x34, 0x20, 0x00]);
+    }
+
+    #[test]
+    fn icmp_echo() {
+        let (src, dst) = ([192, 0, 0, 2], [198, 51, 100, 1]);
+        let mut echo = vec![8, 0, 0, 0, 0, 1, 0, 2, b'h', b'i'];
+        let checksum = checksum(0, &echo);
+        echo[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let packet = ipv4(PROTO_ICMP, src, dst, &echo);
+        let translator = translator();
+        let mut v6 = [0u8; 128];
+        let len = translator.ipv4_to_ipv6(&packet, &mut v6).unwrap();
+        let v6 = &v6[..len];
+        assert_eq!(v6[6], PROTO_ICMPV6);
+        assert_eq!(&v6[40..42], &[128, 0]);
+        assert!(ipv6_payload_valid(v6, PROTO_ICMPV6));
+
+        let mut reply = v6.to_vec();
+        reply[8..40].rotate_left(16);
+        reply[40] = 129;
+        let mut v4 = [0u8; 128];
+        let len = translator.ipv6_to_ipv4(&reply, &mut v4).unwrap();
+        assert_eq!(v4[9], PROTO_ICMP);
+        assert_eq!(v4[20], 0);
+        assert_eq!(super::checksum(0, &v4[20..len]), 0);
+        assert_eq!(&v4[24..len], &echo[4..]);
+    }
+
+    #[test]
+    fn icmpv6_error_quotes_translated_packet() {
+        let translator = translator();
+        let (src, dst) = ([192, 0, 0, 2], [198, 51, 100, 1]);
+        let packet = ipv4(PROTO_UDP, src, dst, &udp(src, dst));
+        let mut quoted = [0u8; 128];
+        let quoted_len = translator.ipv4_to_ipv6(&packet, &mut quoted).unwrap();
+
+        // Port unreachable, sent by the remote host for the packet above.
+        let mut error = vec![0x60, 0, 0, 0];
+        error.extend_from_slice(&((8 + quoted_len) as u16).to_be_bytes());
+        error.extend_from_slice(&[PROTO_ICMPV6, 64]);
+        error.extend_from_slice(&quoted[24..40]);
+        error.extend_from_slice(&quoted[8..24]);
+        error.extend_from_slice(&[1, 4, 0, 0, 0, 0, 0, 0]);
+        error.extend_from_slice(&quoted[..quoted_len]);
+
+        let mut v4 = [0u8; 256];
+        let len = translator.ipv6_to_ipv4(&error, &mut v4).unwrap();
+        assert_eq!(len, IPV4_HEADER_LEN + ICMP_HEADER_LEN + packet.len());
+        assert_eq!(&v4[20..22], &[3, 3]);
+        assert_eq!(checksum(0, &v4[20..len]), 0);
+        let inner = &v4[28..len];
+        assert_eq!(&inner[12..20], &packet[12..20]);
+        assert_eq!(&inner[20..], &packet[20..]);
+        assert_eq!(checksum(0, &inner[..IPV4_HEADER_LEN]), 0);
+    }
+
+    #[test]
+    fn rejects_addresses_outside_the_prefixes() {
+        let translator = translator();
+        let mut packet = [0u8; 48];
+        packet[0] = 0x60;
+        packet[4..6].copy_from_slice(&8u16.to_be_bytes());
+        packet[6] = PROTO_UDP;
+        packet[8..24].copy_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        packet[24..40].copy_from_slice(&translator.local().embed([192, 0, 0, 2].into()).octets());
+        let err = translator.translate(&packet, &mut [0u8; 64]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,228 @@
+/*!
+# Packet Capture
+
+Mirrors the packets read and written through a device into a callback or a pcap file,
+without running `tcpdump` on the (often dynamically named) interface.
+
+A tap installed with `set_tap` is called, on the sync and async device alike, for every
+packet received and sent through the device, whatever the call: `recv` and `send` with their
+variants (`recv_with_headroom`, `recv_with_meta`, `recv_uninit`, `recv_buf`, ...), vectored,
+`try_`, polled, interruptible and deadline I/O, and `DeviceFramed`, including the echo
+replies of the ICMP responder. `recv_multiple` and `send_multiple` call it once per packet on
+Linux and macOS; on a Linux device built with `offload(true)` that is each segment split from
+a GSO packet and each packet before it is coalesced, without the virtio network header.
+
+The tap is not called for `send_with_hdr`, `send_with_checksum_offload` and
+`send_as_outgoing`, whose frames it sees when they are read back, nor for single-packet I/O
+on a Linux device built with `offload(true)`, whose packets carry a virtio network header
+there.
+
+The tap sees the IP packet or Ethernet frame only: on a device with packet information, the
+header in front of each packet is left out.
+
+`capture_to` writes a pcap file with the link type matching the device:
+[`LinkType::Raw`] for TUN (L3) and [`LinkType::Ethernet`] for TAP (L2).
+
+# Example
+
+```no_run
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+dev.capture_to(std::fs::File::create("tun.pcap")?)?;
+// ... traffic ...
+dev.set_tap(None); // stops the capture and closes the file
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Direction of a packet passed to a [`PacketTapFn`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Direction {
+    /// Read from the device, i.e. sent by the local host into the tunnel.
+    Inbound,
+    /// Written to the device, i.e. delivered from the tunnel to the local host.
+    Outbound,
+}
+
+/// Callback mirroring the packets of a device, see `set_tap`.
+///
+/// It runs on the I/O path, so it should not block.
+pub type PacketTapFn = Arc<dyn Fn(Direction, &[u8]) + Send + Sync>;
+
+/// Shared tap slot of a device.
+#[derive(Clone, Default)]
+pub(crate) struct PacketTap(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    enabled: AtomicBool,
+    tap: RwLock<Option<PacketTapFn>>,
+}
+
+impl PacketTap {
+    pub(crate) fn set(&self, tap: Option<PacketTapFn>) {
+        let mut guard = self.0.tap.write().unwrap();
+        self.0.enabled.store(tap.is_some(), Ordering::Release);
+        *guard = tap;
+    }
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.enabled.load(Ordering::Acquire)
+    }
+    #[inline]
+    pub(crate) fn call(&self, direction: Direction, packet: &[u8]) {
+        if !self.is_set() {
+            return;
+        }
+        if let Some(tap) = self.0.tap.read().unwrap().as_ref() {
+            tap(direction, packet);
+        }
+    }
+}
+
+/// pcap link-layer header type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum LinkType {
+    /// `LINKTYPE_ETHERNET`, for TAP devices.
+    Ethernet,
+    /// `LINKTYPE_RAW`, IPv4 or IPv6 packets without link-layer header, for TUN devices.
+    Raw,
+}
+
+impl LinkType {
+    fn value(self) -> u32 {
+        match self {
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+        }
+    }
+}
+
+/// Maximum number of bytes stored per packet.
+const SNAPLEN: u32 = 65535;
+
+/// Writer of the classic pcap file format (microsecond timestamps).
+///
+/// The format has no field for the direction; use a custom [`PacketTapFn`] to keep it.
+pub struct PcapWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the pcap file header to `writer`.
+    pub fn new(mut writer: W, link_type: LinkType) -> io::Result<Self> {
+        let mut header = [0u8; 24];
+        header[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        header[4..6].copy_from_slice(&2u16.to_le_bytes());
+        header[6..8].copy_from_slice(&4u16.to_le_bytes());
+        // thiszone and sigfigs stay zero
+        header[16..20].copy_from_slice(&SNAPLEN.to_le_bytes());
+        header[20..24].copy_from_slice(&link_type.value().to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(Self { writer })
+    }
+    /// Appends `packet` with the current time as timestamp.
+    ///
+    /// Packets longer than 65535 bytes (e.g. GSO) are truncated.
+    pub fn write_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let captured = packet.len().min(SNAPLEN as usize);
+        let mut record = [0u8; 16];
+        record[0..4].copy_from_slice(&(now.as_secs() as u32).to_le_bytes());
+        record[4..8].copy_from_slice(&now.subsec_micros().to_le_bytes());
+        record[8..12].copy_from_slice(&(captured as u32).to_le_bytes());
+        record[12..16].copy_from_slice(&(packet.len() as u32).to_le_bytes());
+        self.writer.write_all(&record)?;
+        self.writer.write_all(&packet[..captured])
+    }
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+    /// Consumes the writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> PcapWriter<W> {
+    /// Turns the writer into a tap recording both directions.
+    ///
+    /// Write errors are logged and the packet is skipped.
+    pub fn into_tap(self) -> PacketTapFn {
+        let writer = Mutex::new(self);
+        Arc::new(move |_, packet| {
+            if let Err(e) = writer.lock().unwrap().write_packet(packet) {
+                log::warn!("failed to write packet capture: {e}");
+            }
+        })
+    }
+}
+
+impl crate::platform::DeviceImpl {
+    /// Installs `tap`, called for the packets received or sent as listed in the
+    /// [module documentation](crate::pcap), or removes it with `None`.
+    pub fn set_tap(&self, tap: Option<PacketTapFn>) {
+        self.packet_tap.set(tap)
+    }
+    /// Returns whether a tap is installed.
+    pub fn has_tap(&self) -> bool {
+        self.packet_tap.is_set()
+    }
+    /// The pcap link type of the packets of this device.
+    pub fn pcap_link_type(&self) -> LinkType {
+        if self.is_tap() {
+            LinkType::Ethernet
+        } else {
+            LinkType::Raw
+        }
+    }
+    /// Records every packet received or sent into a pcap file written to `writer`.
+    ///
+    /// Replaces any installed tap. Wrap files in a [`BufWriter`](std::io::BufWriter) for
+    /// busy devices; the capture ends when the tap is removed or replaced.
+    pub fn capture_to<W: Write + Send + 'static>(&self, writer: W) -> io::Result<()> {
+        let writer = PcapWriter::new(writer, self.pcap_link_type())?;
+        self.set_tap(Some(writer.into_tap()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcap_format() {
+        let mut writer = PcapWriter::new(Vec::new(), LinkType::Raw).unwrap();
+        writer.write_packet(&[0x45; 20]).unwrap();
+        let file = writer.into_inner();
+        assert_eq!(file.len(), 24 + 16 + 20);
+        assert_eq!(&file[0..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&file[20..24], &101u32.to_le_bytes());
+        assert_eq!(&file[32..36], &20u32.to_le_bytes());
+        assert_eq!(&file[36..40], &20u32.to_le_bytes());
+    }
+
+    #[test]
+    fn tap_slot() {
+        let tap = PacketTap::default();
+        tap.call(Direction::Inbound, b"dropped");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        tap.set(Some(Arc::new(move |direction, packet: &[u8]| {
+            sink.lock().unwrap().push((direction, packet.len()));
+        })));
+        tap.call(Direction::Outbound, b"abc");
+        tap.set(None);
+        tap.call(Direction::Inbound, b"dropped");
+        assert_eq!(*seen.lock().unwrap(), [(Direction::Outbound, 3)]);
+    }
+}
@@ -0,0 +1,219 @@
+/*!
+# Point-to-multipoint Peer Routing
+
+A single L3 device can serve many remote peers, as in a hub-and-spoke VPN. [`PeerTable`]
+maps destination networks to peer ids and picks the peer of each packet by parsing its IP
+header, using the longest matching prefix:
+
+- [`PeerTable::recv_with_peer`] reads a packet from the device and returns the peer it is
+  addressed to.
+- [`PeerTable::send_from_peer`] writes a packet received from a peer to the device, after
+  checking that its source address is routed to that peer, so one peer cannot spoof the
+  addresses of another.
+
+The table is purely user-space and can be shared between a reading and a writing thread.
+
+# Example
+
+```no_run
+use tun_rs::peer::PeerTable;
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+
+let peers = PeerTable::new();
+peers.insert("10.0.1.0/24".parse().unwrap(), 1u32);
+peers.insert("10.0.2.0/24".parse().unwrap(), 2u32);
+
+let mut buf = [0u8; 65536];
+loop {
+    match peers.recv_with_peer(&dev, &mut buf)? {
+        (len, Some(peer)) => println!("{len} bytes for peer {peer}"),
+        (_, None) => {} // no route, drop
+    }
+}
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::demux::PacketMeta;
+use crate::SyncDevice;
+use ipnet::IpNet;
+use std::io;
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+/// Destination network to peer id mapping with longest-prefix lookup.
+pub struct PeerTable<P> {
+    // Sorted by descending prefix length, so the first match is the longest one.
+    routes: RwLock<Vec<(IpNet, P)>>,
+}
+
+impl<P> Default for PeerTable<P> {
+    fn default() -> Self {
+        Self {
+            routes: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+impl<P: Clone + PartialEq> PeerTable<P> {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Routes `net` to `peer`, returning the peer it was previously routed to.
+    ///
+    /// Host bits of `net` are ignored.
+    pub fn insert(&self, net: IpNet, peer: P) -> Option<P> {
+        let net = net.trunc();
+        let mut routes = self.routes.write().unwrap();
+        if let Some((_, old)) = routes.iter_mut().find(|(n, _)| *n == net) {
+            return Some(std::mem::replace(old, peer));
+        }
+        let index = routes
+            .iter()
+            .position(|(n, _)| n.prefix_len() < net.prefix_len())
+            .unwrap_or(routes.len());
+        routes.insert(index, (net, peer));
+        None
+    }
+    /// Removes the route of `net`, returning its peer.
+    pub fn remove(&self, net: IpNet) -> Option<P> {
+        let net = net.trunc();
+        let mut routes = self.routes.write().unwrap();
+        let index = routes.iter().position(|(n, _)| *n == net)?;
+        Some(routes.remove(index).1)
+    }
+    /// Removes every route of `peer`, e.g. once it disconnected.
+    pub fn remove_peer(&self, peer: &P) {
+        self.routes.write().unwrap().retain(|(_, p)| p != peer);
+    }
+    /// Returns the peer of the longest prefix containing `addr`.
+    pub fn lookup(&self, addr: IpAddr) -> Option<P> {
+        self.routes
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(net, _)| net.contains(&addr))
+            .map(|(_, peer)| peer.clone())
+    }
+    /// Returns the peer `packet` is addressed to, based on its destination address.
+    pub fn peer_for(&self, packet: &[u8]) -> Option<P> {
+        self.lookup(PacketMeta::parse(packet).destination?)
+    }
+    /// Number of routes in the table.
+    pub fn len(&self) -> usize {
+        self.routes.read().unwrap().len()
+    }
+    /// Returns `true` if the table has no routes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Reads a packet from `device` and returns its length together with the peer it is
+    /// addressed to, or `None` if no route matches.
+    pub fn recv_with_peer(
+        &self,
+        device: &SyncDevice,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, Option<P>)> {
+        let len = device.recv(buf)?;
+        Ok((len, self.peer_for(&buf[..len])))
+    }
+    /// Writes `packet`, received from `peer`, to `device`.
+    ///
+    /// Fails with [`io::ErrorKind::PermissionDenied`] if the source address of `packet` is
+    /// not routed to `peer`.
+    pub fn send_from_peer(
+        &self,
+        device: &SyncDevice,
+        peer: &P,
+        packet: &[u8],
+    ) -> io::Result<usize> {
+        self.check_source(peer, packet)?;
+        device.send(packet)
+    }
+    /// Asynchronous version of [`recv_with_peer`](Self::recv_with_peer).
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub async fn recv_with_peer_async(
+        &self,
+        device: &crate::AsyncDevice,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, Option<P>)> {
+        let len = device.recv(buf).await?;
+        Ok((len, self.peer_for(&buf[..len])))
+    }
+    /// Asynchronous version of [`send_from_peer`](Self::send_from_peer).
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub async fn send_from_peer_async(
+        &self,
+        device: &crate::AsyncDevice,
+        peer: &P,
+        packet: &[u8],
+    ) -> io::Result<usize> {
+        self.check_source(peer, packet)?;
+        device.send(packet).await
+    }
+    fn check_source(&self, peer: &P, packet: &[u8]) -> io::Result<()> {
+        let source = PacketMeta::parse(packet)
+            .source
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an IP packet"))?;
+        if self.lookup(source).as_ref() != Some(peer) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("source address {source} is not routed to this peer"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet_v4(src: [u8; 4], dst: [u8; 4]) -> Vec<u8> {
+        let mut p = vec![0u8; 20];
+        p[0] = 0x45;
+        p[12..16].copy_from_slice(&src);
+        p[16..20].copy_from_slice(&dst);
+        p
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let table = PeerTable::new();
+        assert_eq!(table.insert("10.0.0.0/8".parse().unwrap(), "hub"), None);
+        assert_eq!(table.insert("10.1.0.9/16".parse().unwrap(), "a"), None);
+        assert_eq!(table.insert("10.1.2.0/24".parse().unwrap(), "b"), None);
+        assert_eq!(table.insert("10.1.0.0/16".parse().unwrap(), "c"), Some("a"));
+
+        assert_eq!(table.peer_for(&packet_v4([1; 4], [10, 1, 2, 3])), Some("b"));
+        assert_eq!(table.peer_for(&packet_v4([1; 4], [10, 1, 3, 3])), Some("c"));
+        assert_eq!(
+            table.peer_for(&packet_v4([1; 4], [10, 2, 0, 1])),
+            Some("hub")
+        );
+        assert_eq!(table.peer_for(&packet_v4([1; 4], [192, 168, 0, 1])), None);
+
+        assert_eq!(table.remove("10.1.2.0/24".parse().unwrap()), Some("b"));
+        assert_eq!(table.peer_for(&packet_v4([1; 4], [10, 1, 2, 3])), Some("c"));
+        table.remove_peer(&"c");
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn source_must_belong_to_peer() {
+        let table = PeerTable::new();
+        table.insert("10.0.1.0/24".parse().unwrap(), 1);
+        table.insert("10.0.2.0/24".parse().unwrap(), 2);
+        assert!(table
+            .check_source(&1, &packet_v4([10, 0, 1, 5], [10, 0, 0, 1]))
+            .is_ok());
+        let err = table
+            .check_source(&2, &packet_v4([10, 0, 1, 5], [10, 0, 0, 1]))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(table.check_source(&1, &[]).is_err());
+    }
+}
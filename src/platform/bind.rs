@@ -0,0 +1,177 @@
+use std::io;
+#[cfg(unix)]
+use std::os::fd::{AsFd, AsRawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, AsSocket};
+
+/// Pins `socket` to the interface with index `if_index`, so that its traffic leaves through
+/// that interface regardless of the routing table.
+///
+/// The typical use is the outer (transport) socket of a VPN: bound to the physical
+/// interface, its packets cannot be captured by the routes pointing into the tunnel, which
+/// would otherwise cause a routing loop.
+///
+/// - Linux/Android: `SO_BINDTODEVICE` (requires `CAP_NET_RAW`)
+/// - macOS/iOS/tvOS: `IP_BOUND_IF` / `IPV6_BOUND_IF`
+/// - Windows: `IP_UNICAST_IF` / `IPV6_UNICAST_IF`
+///
+/// Other platforms return [`io::ErrorKind::Unsupported`].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0")?;
+/// // Index of the physical uplink, e.g. from the default route.
+/// let uplink = 2;
+/// tun_rs::bind_to_interface(&socket, uplink)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(unix)]
+pub fn bind_to_interface<S: AsFd>(socket: &S, if_index: u32) -> io::Result<()> {
+    let fd = socket.as_fd().as_raw_fd();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+        if unsafe { libc::if_indextoname(if_index, name.as_mut_ptr()) }.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        setsockopt(fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, &name[..len])
+    }
+    #[cfg(target_vendor = "apple")]
+    {
+        let index = if_index as libc::c_int;
+        if socket_family(fd)? == libc::AF_INET6 {
+            setsockopt(fd, libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF, &[index])
+        } else {
+            setsockopt(fd, libc::IPPROTO_IP, libc::IP_BOUND_IF, &[index])
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+    {
+        _ = (fd, if_index);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "binding a socket to an interface is not supported on this platform",
+        ))
+    }
+}
+
+/// Pins `socket` to the interface with index `if_index`, so that its traffic leaves through
+/// that interface regardless of the routing table.
+///
+/// The typical use is the outer (transport) socket of a VPN: bound to the physical
+/// interface, its packets cannot be captured by the routes pointing into the tunnel, which
+/// would otherwise cause a routing loop.
+///
+/// Uses `IP_UNICAST_IF` for IPv4 sockets and `IPV6_UNICAST_IF` for IPv6 sockets.
+/// Dual-stack sockets get both.
+#[cfg(windows)]
+pub fn bind_to_interface<S: AsSocket>(socket: &S, if_index: u32) -> io::Result<()> {
+    use windows_sys::Win32::Networking::WinSock::{
+        getsockopt, setsockopt, AF_INET6, IPPROTO_IP, IPPROTO_IPV6, IPV6_UNICAST_IF, IP_UNICAST_IF,
+        SOCKET, SOCKET_ERROR, SOL_SOCKET, SO_PROTOCOL_INFOW, WSAPROTOCOL_INFOW,
+    };
+    let socket = socket.as_socket().as_raw_socket() as SOCKET;
+    let set = |level: i32, name: i32, value: u32| -> io::Result<()> {
+        let rs = unsafe {
+            setsockopt(
+                socket,
+                level,
+                name,
+                &value as *const u32 as *const u8,
+                size_of::<u32>() as i32,
+            )
+        };
+        if rs == SOCKET_ERROR {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    let mut info = WSAPROTOCOL_INFOW::default();
+    let mut len = size_of::<WSAPROTOCOL_INFOW>() as i32;
+    let rs = unsafe {
+        getsockopt(
+            socket,
+            SOL_SOCKET,
+            SO_PROTOCOL_INFOW,
+            &mut info as *mut WSAPROTOCOL_INFOW as *mut u8,
+            &mut len,
+        )
+    };
+    if rs == SOCKET_ERROR {
+        return Err(io::Error::last_os_error());
+    }
+    if info.iAddressFamily == AF_INET6 as i32 {
+        set(IPPROTO_IPV6, IPV6_UNICAST_IF, if_index)?;
+        // Also covers IPv4-mapped traffic of a dual-stack socket; fails on V6ONLY sockets.
+        _ = set(IPPROTO_IP, IP_UNICAST_IF, if_index.to_be());
+        Ok(())
+    } else {
+        // IP_UNICAST_IF takes the index in network byte order.
+        set(IPPROTO_IP, IP_UNICAST_IF, if_index.to_be())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+fn setsockopt<T>(
+    fd: std::os::fd::RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &[T],
+) -> io::Result<()> {
+    let rs = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            value.as_ptr() as *const libc::c_void,
+            size_of_val(value) as libc::socklen_t,
+        )
+    };
+    if rs < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_vendor = "apple")]
+fn socket_family(fd: std::os::fd::RawFd) -> io::Result<libc::c_int> {
+    unsafe {
+        let mut addr: libc::sockaddr_storage = std::mem::zeroed();
+        let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        if libc::getsockname(fd, &mut addr as *mut _ as *mut libc::sockaddr, &mut len) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(addr.ss_family as libc::c_int)
+    }
+}
+
+#[cfg(any(
+    windows,
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+impl crate::platform::DeviceImpl {
+    /// Pins `socket` to this interface, see [`bind_to_interface`](crate::bind_to_interface).
+    ///
+    /// Useful for sockets that must always go through the tunnel (e.g. the DNS resolver of a
+    /// split-tunnel setup), independently of the routes.
+    #[cfg(unix)]
+    pub fn bind_socket<S: AsFd>(&self, socket: &S) -> io::Result<()> {
+        bind_to_interface(socket, self.if_index()?)
+    }
+    /// Pins `socket` to this interface, see [`bind_to_interface`](crate::bind_to_interface).
+    ///
+    /// Useful for sockets that must always go through the tunnel (e.g. the DNS resolver of a
+    /// split-tunnel setup), independently of the routes.
+    #[cfg(windows)]
+    pub fn bind_socket<S: AsSocket>(&self, socket: &S) -> io::Result<()> {
+        bind_to_interface(socket, self.if_index()?)
+    }
+}
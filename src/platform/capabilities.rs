@@ -0,0 +1,109 @@
+use crate::platform::{BackendKind, DeviceImpl};
+use crate::Layer;
+use std::io;
+
+/// What a device supports, queried at runtime with `capabilities()`.
+///
+/// Lets generic code (e.g. middleware wrapping any device) pick batch sizes, buffer sizes
+/// and code paths without replicating the platform `cfg`s of this crate.
+///
+/// # Example
+/// ```no_run
+/// use tun_rs::DeviceBuilder;
+///
+/// let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+/// let caps = dev.capabilities()?;
+/// let mut buf = vec![0u8; caps.max_packet_size];
+/// if caps.tcp_gso {
+///     // use recv_multiple/send_multiple
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// The driver behind the device.
+    pub backend: BackendKind,
+    /// Whether the device carries IP packets (L3) or Ethernet frames (L2).
+    pub layer: Layer,
+    /// `recv_vectored`/`send_vectored` are available (Unix).
+    pub vectored_io: bool,
+    /// TCP segmentation offload is enabled; packets carry a virtio net header and
+    /// `recv_multiple`/`send_multiple` split and coalesce segments (Linux `offload(true)`).
+    pub tcp_gso: bool,
+    /// UDP segmentation offload is enabled in addition to TCP.
+    pub udp_gso: bool,
+    /// `recv_multiple` coalesces received segments (GRO), enabled together with `tcp_gso`.
+    pub gro: bool,
+    /// Further queues can be attached with `try_clone` (Linux `multi_queue(true)`).
+    pub multi_queue: bool,
+    /// Packets are exchanged through rings shared with the driver instead of system calls
+    /// (Wintun).
+    pub zero_copy: bool,
+    /// Buffer size `recv` needs to never truncate a packet: the MTU plus the link-layer and
+    /// packet information headers, or the largest offload super-packet.
+    pub max_packet_size: usize,
+    /// Version of the driver, where it can be queried (Wintun, tap-windows6, Linux `tun`).
+    pub driver_version: Option<String>,
+    /// Wintun runs in Windows 7 compatibility mode (`compat_win7`).
+    pub compat_win7: bool,
+    /// The adapter description shown by Windows can be set (Wintun outside the Windows 7
+    /// compatibility mode).
+    pub adapter_description: bool,
+}
+
+/// Length of an Ethernet header without VLAN tag.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+impl DeviceImpl {
+    /// Returns the capabilities of this device.
+    ///
+    /// Queries the MTU and, where available, the driver version, so it is not meant for the
+    /// hot path.
+    pub fn capabilities(&self) -> io::Result<Capabilities> {
+        let backend = self.backend_kind();
+        let layer = if self.is_tap() { Layer::L2 } else { Layer::L3 };
+        let mut max_packet_size = self.mtu()? as usize;
+        if layer == Layer::L2 {
+            max_packet_size += ETHERNET_HEADER_LEN;
+        }
+        if self.packet_information() {
+            max_packet_size += crate::PACKET_INFORMATION_LENGTH;
+        }
+        #[allow(unused_mut)]
+        let mut caps = Capabilities {
+            backend,
+            layer,
+            vectored_io: cfg!(unix),
+            tcp_gso: false,
+            udp_gso: false,
+            gro: false,
+            multi_queue: false,
+            zero_copy: backend == BackendKind::Wintun,
+            max_packet_size,
+            driver_version: None,
+            compat_win7: false,
+            adapter_description: false,
+        };
+        #[cfg(target_os = "linux")]
+        {
+            caps.tcp_gso = self.tcp_gso();
+            caps.udp_gso = self.udp_gso();
+            caps.gro = self.tcp_gso();
+            caps.multi_queue = self.is_multi_queue();
+            if caps.tcp_gso {
+                caps.max_packet_size = crate::VIRTIO_NET_HDR_LEN + self.max_gso_payload()?;
+            }
+            caps.driver_version = std::fs::read_to_string("/sys/module/tun/version")
+                .ok()
+                .map(|v| v.trim().to_string());
+        }
+        #[cfg(target_os = "windows")]
+        {
+            caps.driver_version = self.version().ok();
+            caps.compat_win7 = self.compat_win7();
+            caps.adapter_description = backend == BackendKind::Wintun && !caps.compat_win7;
+        }
+        Ok(caps)
+    }
+}
@@ -0,0 +1,223 @@
+use crate::platform::route;
+use crate::platform::DeviceImpl;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// What is undone when a device is dropped.
+///
+/// Set with [`DeviceBuilder::cleanup_policy`](crate::DeviceBuilder::cleanup_policy) or
+/// [`set_cleanup_policy`](DeviceImpl::set_cleanup_policy). Routes are removed first, then
+/// addresses, then the device is destroyed. Failures are logged and do not stop the remaining
+/// steps. Queues attached with `try_clone` share the policy of the device, and the cleanup
+/// runs when the last of them is dropped.
+///
+/// Some devices cannot outlive their handle whatever the policy: a macOS `utun` is destroyed
+/// when its socket is closed, and a Wintun adapter when the process that created it closes
+/// it. On Linux `destroy_device` is the persistent flag of the device
+/// ([`persist`](crate::SyncDevice::persist)).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct CleanupPolicy {
+    /// Remove all addresses of the device.
+    pub remove_addresses: bool,
+    /// Remove the routes through the device that were added to the routing table, e.g. by
+    /// [`route_guard`](DeviceImpl::route_guard) or the user. Routes the system derives from
+    /// the addresses go with `remove_addresses`.
+    pub remove_routes: bool,
+    /// Destroy the device.
+    pub destroy_device: bool,
+}
+
+impl CleanupPolicy {
+    /// Skips all cleanup, as set by [`leak`](DeviceImpl::leak).
+    pub const NONE: Self = Self {
+        remove_addresses: false,
+        remove_routes: false,
+        destroy_device: false,
+    };
+    /// Removes addresses and routes and destroys the device.
+    pub const ALL: Self = Self {
+        remove_addresses: true,
+        remove_routes: true,
+        destroy_device: true,
+    };
+}
+
+/// Only destroys the device, which takes its addresses and routes with it. Persistent
+/// (`persist(true)`) and attached (`attach_existing(true)`) devices default to
+/// [`CleanupPolicy::NONE`] instead.
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        Self {
+            remove_addresses: false,
+            remove_routes: false,
+            destroy_device: true,
+        }
+    }
+}
+
+/// The cleanup policy of a device, shared by its queues, each of which holds one `Cleanup`.
+#[derive(Debug)]
+pub(crate) struct Cleanup {
+    shared: Arc<Shared>,
+    /// Whether this queue has been counted out of `Shared::queues` yet.
+    released: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    policy: Mutex<CleanupPolicy>,
+    /// The queues that have not been dropped yet. Unlike the reference count of the `Arc`,
+    /// it is decremented and read in one step, so exactly one queue sees itself as the last.
+    queues: AtomicUsize,
+}
+
+impl Cleanup {
+    /// The policy set on the builder, or the default one. `keep` says whether the device is
+    /// persistent or attached, which are kept by default.
+    pub(crate) fn new(policy: Option<CleanupPolicy>, keep: bool) -> Self {
+        let policy = policy.unwrap_or(if keep {
+            CleanupPolicy::NONE
+        } else {
+            CleanupPolicy::default()
+        });
+        Self {
+            shared: Arc::new(Shared {
+                policy: Mutex::new(policy),
+                queues: AtomicUsize::new(1),
+            }),
+            released: AtomicBool::new(false),
+        }
+    }
+    fn get(&self) -> CleanupPolicy {
+        *self.shared.policy.lock().unwrap()
+    }
+    fn set(&self, policy: CleanupPolicy) {
+        *self.shared.policy.lock().unwrap() = policy;
+    }
+    /// Counts this queue out, returning whether it was the last one. Only the first call
+    /// counts.
+    fn release(&self) -> bool {
+        if self.released.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        self.shared.queues.fetch_sub(1, Ordering::AcqRel) == 1
+    }
+}
+
+impl Default for Cleanup {
+    fn default() -> Self {
+        Self::new(Some(CleanupPolicy::default()), false)
+    }
+}
+
+/// A new queue of the same device.
+impl Clone for Cleanup {
+    fn clone(&self) -> Self {
+        self.shared.queues.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: self.shared.clone(),
+            released: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Counts out queues dropped without running the cleanup, e.g. after `into_raw_fd`.
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl DeviceImpl {
+    /// Returns what is undone when the device is dropped.
+    pub fn cleanup_policy(&self) -> CleanupPolicy {
+        self.cleanup.get()
+    }
+    /// Sets what is undone when the device is dropped.
+    ///
+    /// On Linux this sets the persistent flag of the device to `!policy.destroy_device` right
+    /// away, which fails without `CAP_NET_ADMIN`.
+    pub fn set_cleanup_policy(&self, policy: CleanupPolicy) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        self.set_persist(!policy.destroy_device)?;
+        self.cleanup.set(policy);
+        Ok(())
+    }
+    /// Skips all cleanup when the device is dropped, leaving the device with its addresses and
+    /// routes behind where the platform allows it (see [`CleanupPolicy`]).
+    pub fn leak(&self) -> io::Result<()> {
+        self.set_cleanup_policy(CleanupPolicy::NONE)
+    }
+    /// Removes the routes and addresses as the policy says, and returns the policy for the
+    /// platform to destroy the device. Returns `None` while other queues of the device are
+    /// alive.
+    pub(crate) fn run_cleanup(&self) -> Option<CleanupPolicy> {
+        if !self.cleanup.release() {
+            return None;
+        }
+        let policy = self.cleanup.get();
+        if policy.remove_routes {
+            let routes = self.if_index().and_then(route::device_routes);
+            match routes {
+                Ok(routes) => {
+                    for route in routes {
+                        if let Err(e) = route::delete_route(&route) {
+                            log::warn!("failed to remove route {route:?}: {e}");
+                        }
+                    }
+                }
+                Err(e) => log::warn!("failed to list the routes of the device: {e}"),
+            }
+        }
+        if policy.remove_addresses {
+            match self.addresses() {
+                Ok(addresses) => {
+                    for address in addresses {
+                        if let Err(e) = self.remove_address(address) {
+                            log::warn!("failed to remove address {address}: {e}");
+                        }
+                    }
+                }
+                Err(e) => log::warn!("failed to list the addresses of the device: {e}"),
+            }
+        }
+        Some(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cleanup;
+    use std::sync::Barrier;
+
+    #[test]
+    fn last_queue_runs_the_cleanup() {
+        let first = Cleanup::default();
+        let second = first.clone();
+        assert!(!first.release());
+        // Releasing again, e.g. from `Drop` after `run_cleanup`, does not count twice.
+        assert!(!first.release());
+        assert!(second.release());
+    }
+
+    #[test]
+    fn concurrent_drops_run_the_cleanup_once() {
+        for _ in 0..100 {
+            let first = Cleanup::default();
+            let queues = [first.clone(), first];
+            let barrier = Barrier::new(2);
+            let last = std::thread::scope(|scope| {
+                let handles = queues.each_ref().map(|queue| {
+                    let barrier = &barrier;
+                    scope.spawn(move || {
+                        barrier.wait();
+                        queue.release()
+                    })
+                });
+                handles.map(|handle| handle.join().unwrap())
+            });
+            assert_eq!(last.iter().filter(|last| **last).count(), 1);
+        }
+    }
+}
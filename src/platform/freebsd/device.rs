@@ -1,3 +1,4 @@
+use crate::platform::{Cleanup, OperState};
 use crate::{
     builder::{DeviceConfig, Layer},
     platform::freebsd::sys::*,
@@ -22,7 +23,24 @@ use std::{ffi::CStr, io, mem, net::IpAddr, os::unix::io::AsRawFd, ptr, sync::RwL
 pub struct DeviceImpl {
     pub(crate) tun: Tun,
     pub op_lock: RwLock<()>,
+    pub(crate) cleanup: Cleanup,
     pub associate_route: AtomicBool,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    pub(crate) frame_validation: crate::ethernet::FrameValidator,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
 }
 impl IntoRawFd for DeviceImpl {
     fn into_raw_fd(mut self) -> RawFd {
@@ -36,13 +54,18 @@ impl Drop for DeviceImpl {
         if self.tun.fd.inner < 0 {
             return;
         }
+        let destroy = self
+            .run_cleanup()
+            .is_some_and(|policy| policy.destroy_device);
         let fd = self.tun.fd.inner;
         self.tun.fd.inner = -1;
         unsafe {
             // Try to destroy the interface before closing the fd.
             // Even if destroy fails, we must still close the fd to avoid leaking it.
-            if let (Ok(ctl), Ok(req)) = (ctl(), self.request()) {
-                _ = siocifdestroy(ctl.as_raw_fd(), &req);
+            if destroy {
+                if let (Ok(ctl), Ok(req)) = (ctl(), self.request()) {
+                    _ = siocifdestroy(ctl.as_raw_fd(), &req);
+                }
             }
             libc::close(fd);
         }
@@ -65,10 +88,7 @@ impl DeviceImpl {
         let dev_index = match config.dev_name.as_ref() {
             Some(tun_name) => {
                 if tun_name.len() > IFNAMSIZ {
-                    return Err(io::Error::new(
-                        ErrorKind::InvalidInput,
-                        "device name too long",
-                    ));
+                    return Err(crate::Error::NameTooLong.into());
                 }
                 match layer {
                     Layer::L2 => {
@@ -135,6 +155,23 @@ impl DeviceImpl {
         let device = DeviceImpl {
             tun,
             op_lock: RwLock::new(()),
+            cleanup: Cleanup::new(config.cleanup_policy, false),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(associate_route),
         };
         device.disable_deafult_sys_local_ipv6()?;
@@ -152,6 +189,23 @@ impl DeviceImpl {
         let dev = Self {
             tun,
             op_lock: RwLock::new(()),
+            cleanup: Cleanup::default(),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(true),
         };
         Ok(dev)
@@ -354,16 +408,21 @@ impl DeviceImpl {
         let _guard = self.op_lock.read().unwrap();
         self.name_impl()
     }
+    /// Returns the backend of this device: [`crate::BackendKind::BsdTun`] for `tun(4)`,
+    /// [`crate::BackendKind::BsdTap`] for `tap(4)`.
+    pub fn backend_kind(&self) -> crate::BackendKind {
+        match self.name_impl() {
+            Ok(name) if name.starts_with("tap") => crate::BackendKind::BsdTap,
+            _ => crate::BackendKind::BsdTun,
+        }
+    }
     /// Sets a new name for the network interface.
     pub fn set_name(&self, value: &str) -> std::io::Result<()> {
         use std::ffi::CString;
         let _guard = self.op_lock.write().unwrap();
         unsafe {
             if value.len() > IFNAMSIZ {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "device name too long",
-                ));
+                return Err(crate::Error::NameTooLong.into());
             }
             let mut req = self.request()?;
             let tun_name = CString::new(value)?;
@@ -396,7 +455,7 @@ impl DeviceImpl {
 
     /// Returns whether the TUN device is set to ignore packet information (PI).
     ///
-    /// When enabled, the device does not prepend the `struct tun_pi` header
+    /// When enabled, the device does not prepend the 4-byte address family header
     /// to packets, which can simplify packet processing in some cases.
     ///
     /// # Returns
@@ -410,9 +469,9 @@ impl DeviceImpl {
     }
     /// Sets whether the TUN device should ignore packet information (PI).
     ///
-    /// When `ignore_packet_info` is set to `true`, the TUN device does not
-    /// prepend the `struct tun_pi` header to packets. This can be useful
-    /// if the additional metadata is not needed.
+    /// When `ignore_packet_info` is set to `true`, the TUN device does not prepend the
+    /// address family header ([`AfHeader`](crate::framing::AfHeader)) to packets. This can be
+    /// useful if the additional metadata is not needed.
     ///
     /// # Parameters
     /// * `ign`
@@ -451,6 +510,34 @@ impl DeviceImpl {
             Ok(())
         }
     }
+    /// Returns whether the interface is administratively up (`IFF_UP`), see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.read().unwrap();
+        Ok(self.if_flags()? & IFF_UP as c_short != 0)
+    }
+    /// Returns the operational state of the interface, derived from `IFF_UP` and
+    /// `IFF_RUNNING`.
+    ///
+    /// An interface that is up but not running, e.g. a tun(4) or tap(4) device whose node is not open, is
+    /// [`OperState::LowerLayerDown`].
+    pub fn oper_state(&self) -> io::Result<OperState> {
+        let _guard = self.op_lock.read().unwrap();
+        let flags = self.if_flags()?;
+        Ok(if flags & IFF_UP as c_short == 0 {
+            OperState::Down
+        } else if flags & IFF_RUNNING as c_short == 0 {
+            OperState::LowerLayerDown
+        } else {
+            OperState::Up
+        })
+    }
+    fn if_flags(&self) -> io::Result<c_short> {
+        unsafe {
+            let mut req = self.request()?;
+            siocgifflags(ctl()?.as_raw_fd(), &mut req)?;
+            Ok(req.ifr_ifru.ifru_flags[0])
+        }
+    }
     /// Retrieves the current MTU (Maximum Transmission Unit) for the interface.
     pub fn mtu(&self) -> std::io::Result<u16> {
         let _guard = self.op_lock.read().unwrap();
@@ -649,6 +736,31 @@ impl DeviceImpl {
         let _guard = self.op_lock.write().unwrap();
         Self::enable_tunsifhead_impl(&self.tun.fd)
     }
+    /// Adds the device as a member of the existing bridge `bridge` (`ifconfig <bridge> addm`).
+    ///
+    /// Typically used with TAP devices, e.g. `dev.attach_to_bridge("bridge0")`.
+    pub fn attach_to_bridge(&self, bridge: &str) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        ifconfig(&[bridge, "addm", &self.name_impl()?])
+    }
+    /// Removes the device from the bridge `bridge` (`ifconfig <bridge> deletem`).
+    pub fn detach_from_bridge(&self, bridge: &str) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        ifconfig(&[bridge, "deletem", &self.name_impl()?])
+    }
+}
+
+fn ifconfig(args: &[&str]) -> io::Result<()> {
+    let out = std::process::Command::new("ifconfig").args(args).output()?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "ifconfig {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )))
+    }
 }
 
 impl From<Layer> for c_short {
@@ -0,0 +1,233 @@
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_ICMPV6: u8 = 58;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+const REPLY_TTL: u8 = 64;
+
+/// Shared state of the ICMP echo responder of a device: the prefixes it answers for.
+#[derive(Clone, Default)]
+pub(crate) struct IcmpResponder(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    /// Whether `prefixes` is non-empty, so that receiving does not take the lock.
+    enabled: AtomicBool,
+    prefixes: RwLock<Vec<IpNet>>,
+}
+
+impl IcmpResponder {
+    pub(crate) fn set(&self, prefixes: &[IpNet]) {
+        let mut guard = self.0.prefixes.write().unwrap();
+        self.0
+            .enabled
+            .store(!prefixes.is_empty(), Ordering::Release);
+        *guard = prefixes.to_vec();
+    }
+    pub(crate) fn enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Acquire)
+    }
+    pub(crate) fn prefixes(&self) -> Vec<IpNet> {
+        self.0.prefixes.read().unwrap().clone()
+    }
+    /// Turns `packet` into the reply in place if it is an ICMP/ICMPv6 echo request to an
+    /// address within one of the prefixes.
+    pub(crate) fn answer(&self, packet: &mut [u8]) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let Some(destination) = destination(packet) else {
+            return false;
+        };
+        let answered = self.0.prefixes.read().unwrap();
+        answered.iter().any(|net| net.contains(&destination)) && echo_reply(packet)
+    }
+}
+
+/// The destination address of an IPv4 or IPv6 packet.
+fn destination(packet: &[u8]) -> Option<IpAddr> {
+    match packet.first().map(|b| b >> 4) {
+        Some(4) => {
+            let octets: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            Some(Ipv4Addr::from(octets).into())
+        }
+        Some(6) => {
+            let octets: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            Some(Ipv6Addr::from(octets).into())
+        }
+        _ => None,
+    }
+}
+
+/// Rewrites an ICMP/ICMPv6 echo request into the matching echo reply in place.
+///
+/// Returns `false` and leaves `packet` untouched if it is not a well-formed,
+/// unfragmented echo request.
+pub(crate) fn echo_reply(packet: &mut [u8]) -> bool {
+    match packet.first().map(|b| b >> 4) {
+        Some(4) => echo_reply_v4(packet),
+        Some(6) => echo_reply_v6(packet),
+        _ => false,
+    }
+}
+
+fn echo_reply_v4(packet: &mut [u8]) -> bool {
+    if packet.len() < 20 {
+        return false;
+    }
+    let ihl = ((packet[0] & 0x0f) as usize) * 4;
+    let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    let fragmented = u16::from_be_bytes([packet[6], packet[7]]) & 0x3fff != 0;
+    if ihl < 20
+        || total_len > packet.len()
+        || total_len < ihl + 8
+        || fragmented
+        || packet[9] != IPPROTO_ICMP
+        || packet[ihl] != ICMP_ECHO_REQUEST
+    {
+        return false;
+    }
+    let packet = &mut packet[..total_len];
+    swap_addresses(packet, 12, 4);
+    packet[8] = REPLY_TTL;
+    packet[10..12].fill(0);
+    let csum = checksum(0, &packet[..ihl]);
+    packet[10..12].copy_from_slice(&csum.to_be_bytes());
+
+    let icmp = &mut packet[ihl..];
+    icmp[0] = ICMP_ECHO_REPLY;
+    icmp[2..4].fill(0);
+    let csum = checksum(0, icmp);
+    icmp[2..4].copy_from_slice(&csum.to_be_bytes());
+    true
+}
+
+fn echo_reply_v6(packet: &mut [u8]) -> bool {
+    if packet.len() < 48 {
+        return false;
+    }
+    let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    // Extension headers are not followed; such requests are passed through.
+    if 40 + payload_len > packet.len()
+        || payload_len < 8
+        || packet[6] != IPPROTO_ICMPV6
+        || packet[40] != ICMPV6_ECHO_REQUEST
+    {
+        return false;
+    }
+    let packet = &mut packet[..40 + payload_len];
+    swap_addresses(packet, 8, 16);
+    packet[7] = REPLY_TTL;
+
+    let (header, icmp) = packet.split_at_mut(40);
+    icmp[0] = ICMPV6_ECHO_REPLY;
+    icmp[2..4].fill(0);
+    // Pseudo header: source, destination, upper-layer length and next header.
+    let pseudo = sum(0, &header[8..40]) + payload_len as u32 + IPPROTO_ICMPV6 as u32;
+    let csum = checksum(pseudo, icmp);
+    icmp[2..4].copy_from_slice(&csum.to_be_bytes());
+    true
+}
+
+fn swap_addresses(packet: &mut [u8], offset: usize, len: usize) {
+    let (src, dst) = packet[offset..offset + 2 * len].split_at_mut(len);
+    src.swap_with_slice(dst);
+}
+
+fn sum(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+fn checksum(initial: u32, data: &[u8]) -> u16 {
+    let mut sum = sum(initial, data);
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verify(initial: u32, data: &[u8]) -> bool {
+        checksum(initial, data) == 0
+    }
+
+    #[test]
+    fn echo_reply_ipv4() {
+        let mut packet = [
+            &[0x45, 0, 0, 32, 0x12, 0x34, 0x40, 0, 63, IPPROTO_ICMP, 0, 0][..],
+            &[10, 0, 0, 1, 10, 0, 0, 2],
+            &[ICMP_ECHO_REQUEST, 0, 0, 0, 0, 1, 0, 7],
+            b"ping",
+        ]
+        .concat();
+        assert!(echo_reply(&mut packet));
+        assert_eq!(&packet[12..20], &[10, 0, 0, 2, 10, 0, 0, 1]);
+        assert_eq!(packet[20], ICMP_ECHO_REPLY);
+        assert!(verify(0, &packet[..20]));
+        assert!(verify(0, &packet[20..]));
+        // A reply is not answered again.
+        assert!(!echo_reply(&mut packet));
+    }
+
+    #[test]
+    fn echo_reply_ipv6() {
+        let mut packet = vec![0u8; 48];
+        packet[0] = 0x60;
+        packet[5] = 8;
+        packet[6] = IPPROTO_ICMPV6;
+        packet[7] = 255;
+        packet[8] = 0xfd;
+        packet[23] = 1;
+        packet[24] = 0xfd;
+        packet[39] = 2;
+        packet[40] = ICMPV6_ECHO_REQUEST;
+        assert!(echo_reply(&mut packet));
+        assert_eq!(packet[23], 2);
+        assert_eq!(packet[39], 1);
+        assert_eq!(packet[40], ICMPV6_ECHO_REPLY);
+        let pseudo = sum(0, &packet[8..40]) + 8 + IPPROTO_ICMPV6 as u32;
+        assert!(verify(pseudo, &packet[40..]));
+    }
+
+    #[test]
+    fn answer_only_listed_destinations() {
+        let request = [
+            &[0x45, 0, 0, 28, 0, 0, 0, 0, 64, IPPROTO_ICMP, 0, 0][..],
+            &[10, 0, 0, 1, 10, 0, 0, 2],
+            &[ICMP_ECHO_REQUEST, 0, 0, 0, 0, 1, 0, 7],
+        ]
+        .concat();
+        let responder = IcmpResponder::default();
+        assert!(!responder.answer(&mut request.clone()));
+        responder.set(&["10.0.0.2/32".parse().unwrap()]);
+        assert!(responder.answer(&mut request.clone()));
+        responder.set(&["10.0.0.3/32".parse().unwrap(), "fd00::/64".parse().unwrap()]);
+        let mut packet = request.clone();
+        assert!(!responder.answer(&mut packet));
+        assert_eq!(packet, request);
+    }
+
+    #[test]
+    fn ignore_other_packets() {
+        let mut udp = vec![0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17];
+        udp.resize(28, 0);
+        assert!(!echo_reply(&mut udp));
+        assert!(!echo_reply(&mut []));
+    }
+}
@@ -1,10 +1,15 @@
+use crate::platform::linux::netlink;
+#[cfg(feature = "counters")]
+use crate::platform::linux::offload::gso_segments;
 use crate::platform::linux::offload::{
-    gso_none_checksum, gso_split, handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_F_NEEDS_CSUM,
+    gso_none_checksum, gso_split, VirtioNetHdr, VIRTIO_NET_HDR_F_NEEDS_CSUM,
     VIRTIO_NET_HDR_GSO_NONE, VIRTIO_NET_HDR_GSO_TCPV4, VIRTIO_NET_HDR_GSO_TCPV6,
     VIRTIO_NET_HDR_GSO_UDP_L4, VIRTIO_NET_HDR_LEN,
 };
-use crate::platform::unix::device::{ctl, ctl_v6};
-use crate::platform::{ExpandBuffer, GROTable};
+use crate::platform::linux::offload::{gso_segment_count, MAX_GSO_PACKET_LEN};
+use crate::platform::linux::vtap::Vtap;
+use crate::platform::unix::device::ctl;
+use crate::platform::{BackendKind, Cleanup, CleanupPolicy, ExpandBuffer, GROTable, OperState};
 use crate::{
     builder::{DeviceConfig, Layer},
     platform::linux::sys::*,
@@ -14,18 +19,18 @@ use crate::{
     },
     ToIpv4Address, ToIpv4Netmask, ToIpv6Address, ToIpv6Netmask,
 };
-use ipnet::IpNet;
 use libc::{
-    self, c_char, c_short, ifreq, in6_ifreq, ARPHRD_ETHER, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_RUNNING,
-    IFF_TAP, IFF_TUN, IFF_UP, IFNAMSIZ, O_RDWR,
+    self, c_char, c_int, c_short, ifreq, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_RUNNING, IFF_TAP, IFF_TUN,
+    IFF_UP, IFNAMSIZ, IF_OPER_UNKNOWN, IF_OPER_UP, O_RDWR,
 };
+use std::borrow::Cow;
 use std::net::Ipv6Addr;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::{
     ffi::CString,
     io, mem,
-    net::{IpAddr, Ipv4Addr},
-    os::unix::io::{AsRawFd, RawFd},
+    net::IpAddr,
+    os::unix::io::{AsRawFd, IntoRawFd, RawFd},
     ptr,
 };
 
@@ -38,20 +43,75 @@ pub struct DeviceImpl {
     pub(crate) udp_gso: bool,
     flags: c_short,
     pub(crate) op_lock: Arc<RwLock<()>>,
+    pub(crate) cleanup: Cleanup,
+    /// Clear the persistent flag when the last queue is dropped, see
+    /// [`DeviceBuilder::unpersist_on_drop`](crate::DeviceBuilder::unpersist_on_drop).
+    unpersist_on_drop: bool,
+    /// Delete the macvtap/ipvtap link when the last queue is dropped, as the cleanup policy
+    /// says; a TUN/TAP device goes away with its fd.
+    delete_link: bool,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    pub(crate) frame_validation: crate::ethernet::FrameValidator,
+    /// `AF_PACKET` socket on the interface, opened by the first `send_as_outgoing`.
+    outgoing: OnceLock<Fd>,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
+}
+
+impl Drop for DeviceImpl {
+    fn drop(&mut self) {
+        // Handed over with `into_raw_fd`.
+        if self.tun.fd.inner < 0 {
+            return;
+        }
+        // The device itself is destroyed by closing the fd unless it is persistent.
+        let Some(policy) = self.run_cleanup() else {
+            return;
+        };
+        if self.unpersist_on_drop && policy.destroy_device {
+            if let Err(err) = unsafe { tunsetpersist(self.tun.fd.inner, 0) } {
+                log::warn!("failed to clear the persistent flag: {err:?}");
+            }
+        }
+        if self.delete_link && policy.destroy_device {
+            if let Err(err) = self.if_index_impl().and_then(netlink::delete_link) {
+                log::warn!("failed to delete the link: {err:?}");
+            }
+        }
+    }
+}
+impl IntoRawFd for DeviceImpl {
+    fn into_raw_fd(mut self) -> RawFd {
+        let fd = self.tun.fd.inner;
+        self.tun.fd.inner = -1;
+        fd
+    }
 }
 
 impl DeviceImpl {
     /// Create a new `Device` for the given `Configuration`.
     pub(crate) fn new(config: DeviceConfig) -> std::io::Result<Self> {
+        if let Some(vtap) = &config.vtap {
+            return Self::new_vtap(&config, vtap);
+        }
         let dev_name = match config.dev_name.as_ref() {
             Some(tun_name) => {
                 let tun_name = CString::new(tun_name.clone())?;
 
                 if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "device name too long",
-                    ));
+                    return Err(crate::Error::NameTooLong.into());
                 }
 
                 Some(tun_name)
@@ -59,6 +119,15 @@ impl DeviceImpl {
 
             None => None,
         };
+        let attach_existing = config.attach_existing.unwrap_or(false);
+        if let Some(dev_name) = dev_name.as_ref().filter(|_| attach_existing) {
+            if unsafe { libc::if_nametoindex(dev_name.as_ptr()) } == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("device {dev_name:?} does not exist"),
+                ));
+            }
+        }
 
         // Create the device node if it is missing.
         // Silently ignore errors, let opening the device report an error.
@@ -102,9 +171,39 @@ impl DeviceImpl {
                 0,
             );
             let tun_fd = Fd::new(fd)?;
+            let napi = config.napi.unwrap_or(false);
+            let napi_frags = config.napi_frags.unwrap_or(false);
+            if napi || napi_frags {
+                req.ifr_ifru.ifru_flags |= napi_flags(tun_fd.inner, napi_frags, device_type);
+            }
             if let Err(err) = tunsetiff(tun_fd.inner, &mut req as *mut _ as *mut _) {
                 return Err(io::Error::from(err));
             }
+            // Keep the interface alive after this process exits so that the next
+            // process can attach to it as well.
+            if attach_existing {
+                if let Err(err) = tunsetpersist(tun_fd.inner, 1) {
+                    return Err(io::Error::from(err));
+                }
+            }
+            if let Some(persist) = config.persist {
+                if let Err(err) = tunsetpersist(tun_fd.inner, persist as _) {
+                    return Err(io::Error::from(err));
+                }
+            }
+            if let Some(policy) = config.cleanup_policy {
+                if let Err(err) = tunsetpersist(tun_fd.inner, !policy.destroy_device as _) {
+                    return Err(io::Error::from(err));
+                }
+            }
+            #[cfg(feature = "ebpf")]
+            if let Some(prog) = &config.steering_ebpf {
+                use std::os::fd::AsRawFd;
+                crate::platform::linux::steering::set_steering_prog(
+                    tun_fd.inner,
+                    prog.as_raw_fd(),
+                )?;
+            }
             let (vnet_hdr, udp_gso) = if offload && libc::IFF_VNET_HDR != 0 {
                 // tunTCPOffloads were added in Linux v2.6. We require their support if IFF_VNET_HDR is set.
                 let tun_tcp_offloads = libc::TUN_F_CSUM | libc::TUN_F_TSO4 | libc::TUN_F_TSO6;
@@ -138,16 +237,121 @@ impl DeviceImpl {
                 (false, false)
             };
 
+            let unpersist_on_drop = !attach_existing && config.unpersist_on_drop.unwrap_or(false);
             let device = DeviceImpl {
                 tun: Tun::new(tun_fd),
                 vnet_hdr,
                 udp_gso,
                 flags: req.ifr_ifru.ifru_flags,
                 op_lock: Arc::new(RwLock::new(())),
+                // A device to unpersist is destroyed by default, even if persistent.
+                cleanup: Cleanup::new(
+                    config.cleanup_policy,
+                    !unpersist_on_drop && config.persist.unwrap_or(attach_existing),
+                ),
+                unpersist_on_drop,
+                delete_link: false,
+                egress_limit: Default::default(),
+                frame_validation: Default::default(),
+                outgoing: OnceLock::new(),
+                #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+                pushback: Default::default(),
+                #[cfg(feature = "icmp_responder")]
+                icmp_responder: Default::default(),
+                #[cfg(feature = "pcap")]
+                packet_tap: Default::default(),
+                #[cfg(feature = "counters")]
+                counters: Default::default(),
+                #[cfg(feature = "middleware")]
+                middleware: Default::default(),
+                #[cfg(feature = "mss_clamp")]
+                mss_clamp: Default::default(),
+                #[cfg(feature = "tracing")]
+                span: Default::default(),
             };
             Ok(device)
         }
     }
+    /// Creates the macvtap/ipvtap link of `config`, or opens it with `attach_existing`.
+    fn new_vtap(config: &DeviceConfig, vtap: &Vtap) -> io::Result<Self> {
+        #[cfg(feature = "ebpf")]
+        if config.steering_ebpf.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "steering programs are not available on macvtap and ipvtap devices",
+            ));
+        }
+        let attach_existing = config.attach_existing.unwrap_or(false);
+        let name = match &config.dev_name {
+            Some(name) => name.clone(),
+            None => vtap.free_name()?,
+        };
+        if !attach_existing {
+            vtap.create(&name)?;
+        }
+        let opened = vtap.open(&name).and_then(|fd| {
+            let offload = config.offload.unwrap_or(false);
+            let mut req: ifreq = unsafe { mem::zeroed() };
+            // The queue defaults to a 12-byte virtio-net header, which is only wanted with
+            // offload and then in the size the offload code uses.
+            let vnet_hdr = if offload { libc::IFF_VNET_HDR } else { 0 };
+            let flags = (IFF_TAP | IFF_NO_PI | vnet_hdr) as c_short;
+            req.ifr_ifru.ifru_flags = flags;
+            unsafe { tunsetiff(fd.inner, &mut req as *mut _ as *mut _) }?;
+            let mut udp_gso = false;
+            if offload {
+                let hdr_len = VIRTIO_NET_HDR_LEN as c_int;
+                unsafe { tunsetvnethdrsz(fd.inner, &hdr_len) }?;
+                let tun_tcp_offloads = libc::TUN_F_CSUM | libc::TUN_F_TSO4 | libc::TUN_F_TSO6;
+                let tun_udp_offloads = libc::TUN_F_USO4 | libc::TUN_F_USO6;
+                unsafe { tunsetoffload(fd.inner, tun_tcp_offloads as _) }?;
+                udp_gso =
+                    unsafe { tunsetoffload(fd.inner, (tun_tcp_offloads | tun_udp_offloads) as _) }
+                        .is_ok();
+            }
+            Ok((fd, flags, offload, udp_gso))
+        });
+        let (fd, flags, vnet_hdr, udp_gso) = match opened {
+            Ok(opened) => opened,
+            Err(err) => {
+                if !attach_existing {
+                    _ = crate::platform::linux::vtap::if_index(&name)
+                        .and_then(netlink::delete_link);
+                }
+                return Err(err);
+            }
+        };
+        Ok(DeviceImpl {
+            tun: Tun::new(fd),
+            vnet_hdr,
+            udp_gso,
+            flags,
+            op_lock: Arc::new(RwLock::new(())),
+            cleanup: Cleanup::new(
+                config.cleanup_policy,
+                config.persist.unwrap_or(attach_existing),
+            ),
+            unpersist_on_drop: false,
+            delete_link: true,
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            outgoing: OnceLock::new(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
+        })
+    }
     unsafe fn set_tcp_offloads(&self) -> io::Result<()> {
         let tun_tcp_offloads = libc::TUN_F_CSUM | libc::TUN_F_TSO4 | libc::TUN_F_TSO6;
         tunsetoffload(self.as_raw_fd(), tun_tcp_offloads as _)
@@ -162,12 +366,33 @@ impl DeviceImpl {
             .map_err(|e| e.into())
     }
     pub(crate) fn from_tun(tun: Tun) -> io::Result<Self> {
+        let flags = unsafe { flags(tun.as_raw_fd()) }.unwrap_or(0);
         Ok(Self {
             tun,
             vnet_hdr: false,
             udp_gso: false,
-            flags: 0,
+            flags,
             op_lock: Arc::new(RwLock::new(())),
+            cleanup: Cleanup::default(),
+            unpersist_on_drop: false,
+            delete_link: false,
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            outgoing: OnceLock::new(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
         })
     }
 
@@ -202,6 +427,26 @@ impl DeviceImpl {
                 udp_gso: self.udp_gso,
                 flags,
                 op_lock: self.op_lock.clone(),
+                cleanup: self.cleanup.clone(),
+                unpersist_on_drop: self.unpersist_on_drop,
+                delete_link: self.delete_link,
+                egress_limit: self.egress_limit.clone(),
+                frame_validation: self.frame_validation.clone(),
+                outgoing: OnceLock::new(),
+                #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+                pushback: Default::default(),
+                #[cfg(feature = "icmp_responder")]
+                icmp_responder: self.icmp_responder.clone(),
+                #[cfg(feature = "pcap")]
+                packet_tap: self.packet_tap.clone(),
+                #[cfg(feature = "counters")]
+                counters: self.counters.clone(),
+                #[cfg(feature = "middleware")]
+                middleware: self.middleware.clone(),
+                #[cfg(feature = "mss_clamp")]
+                mss_clamp: self.mss_clamp.clone(),
+                #[cfg(feature = "tracing")]
+                span: self.span.clone(),
             };
             if dev.vnet_hdr {
                 if dev.udp_gso {
@@ -214,6 +459,67 @@ impl DeviceImpl {
             Ok(dev)
         }
     }
+    /// Returns the backend of this device, which is always [`BackendKind::LinuxTun`] on Linux.
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::LinuxTun
+    }
+    /// Sends an Ethernet frame out of the TAP device as if the kernel had transmitted it.
+    ///
+    /// Writing to the device (`send`) injects frames into the kernel, which takes them as
+    /// received from the wire. This goes the other way: the frame is transmitted through an
+    /// `AF_PACKET` socket bound to the interface, so it shows up as outgoing in captures and
+    /// is read back from this device, letting a user-space switch hairpin traffic to its own
+    /// port. Fails with [`io::ErrorKind::Unsupported`] on TUN devices.
+    pub fn send_as_outgoing(&self, frame: &[u8]) -> io::Result<usize> {
+        if !self.is_tap() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "send_as_outgoing needs a TAP device",
+            ));
+        }
+        let fd = match self.outgoing.get() {
+            Some(fd) => fd,
+            None => {
+                let fd = packet_socket(self.if_index_impl()?)?;
+                // A concurrent caller may have won the race, its socket is just as good.
+                _ = self.outgoing.set(fd);
+                self.outgoing.get().unwrap()
+            }
+        };
+        fd.write(frame)
+    }
+    pub(crate) fn is_tap(&self) -> bool {
+        self.flags & IFF_TAP as c_short != 0
+    }
+    /// Length of the link-layer header in front of the IP packets that offload works on.
+    pub(crate) fn link_len(&self) -> usize {
+        if self.is_tap() {
+            crate::ethernet::ETHERNET_HEADER_LEN
+        } else {
+            0
+        }
+    }
+    pub(crate) fn is_multi_queue(&self) -> bool {
+        self.flags & IFF_MULTI_QUEUE as c_short != 0
+    }
+    /// Returns whether packets carry the 4-byte `struct tun_pi` header (`IFF_NO_PI` not set).
+    pub fn packet_information(&self) -> bool {
+        self.flags & IFF_NO_PI as c_short == 0
+    }
+    /// Changes whether packets carry the packet information header.
+    ///
+    /// On Linux the header is selected with `IFF_NO_PI` when the queue is attached and cannot
+    /// be toggled afterwards, so any change fails with [`crate::Error::RequiresRebuild`];
+    /// setting the current value is a no-op.
+    pub fn set_packet_information(&self, value: bool) -> io::Result<()> {
+        if value == self.packet_information() {
+            return Ok(());
+        }
+        Err(crate::Error::RequiresRebuild {
+            setting: "packet_information",
+        }
+        .into())
+    }
     /// Returns whether UDP Generic Segmentation Offload (GSO) is enabled.
     ///
     /// This is determined by the `udp_gso` flag in the device.
@@ -226,6 +532,53 @@ impl DeviceImpl {
     pub fn tcp_gso(&self) -> bool {
         self.vnet_hdr
     }
+    /// Returns the largest packet a single buffer passed to `send_multiple` may hold, not
+    /// counting `offset`.
+    ///
+    /// With offload enabled, `send_multiple` coalesces packets up to the 65535 bytes an IP
+    /// packet can have. Otherwise every packet is written as is and must fit into the MTU
+    /// (plus the Ethernet header on TAP devices and the packet information header, if any).
+    pub fn max_gso_payload(&self) -> io::Result<usize> {
+        if self.vnet_hdr {
+            return Ok(MAX_GSO_PACKET_LEN);
+        }
+        let mut len = self.mtu()? as usize;
+        if self.is_tap() {
+            len += 14;
+        }
+        if self.packet_information() {
+            len += crate::PACKET_INFORMATION_LENGTH;
+        }
+        Ok(len)
+    }
+    /// Returns how many output buffers `recv_multiple` may need, i.e. the most segments one
+    /// read can be split into.
+    ///
+    /// This is `1` without offload, and [`gso_segment_count`] of a maximum-size GSO packet
+    /// with offload. The original buffer of `recv_multiple` should hold
+    /// `VIRTIO_NET_HDR_LEN + max_gso_payload()` bytes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, VIRTIO_NET_HDR_LEN};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .offload(true)
+    ///     .build_sync()?;
+    /// let mtu = dev.mtu()? as usize;
+    /// let mut original_buffer = vec![0u8; VIRTIO_NET_HDR_LEN + dev.max_gso_payload()?];
+    /// let mut bufs = vec![vec![0u8; mtu]; dev.max_gso_segments()?];
+    /// let mut sizes = vec![0; bufs.len()];
+    /// let n = dev.recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn max_gso_segments(&self) -> io::Result<usize> {
+        if !self.vnet_hdr {
+            return Ok(1);
+        }
+        Ok(gso_segment_count(MAX_GSO_PACKET_LEN))
+    }
     /// Sets the transmit queue length for the network interface.
     ///
     /// This method constructs an interface request (`ifreq`) structure,
@@ -261,7 +614,9 @@ impl DeviceImpl {
     ///
     /// By default, TUN/TAP devices are destroyed when the process exits.
     /// Calling this method makes the device persist after the program terminates,
-    /// allowing it to be reused by other processes.
+    /// allowing it to be reused by other processes. This is the same as a
+    /// [`cleanup_policy`](Self::cleanup_policy) that does not destroy the device.
+    /// [`unpersist`](Self::unpersist) undoes it.
     ///
     /// # Example
     ///
@@ -282,9 +637,33 @@ impl DeviceImpl {
     /// # Ok::<(), std::io::Error>(())
     /// ```
     pub fn persist(&self) -> io::Result<()> {
+        let policy = CleanupPolicy {
+            destroy_device: false,
+            ..self.cleanup_policy()
+        };
+        self.set_cleanup_policy(policy)
+    }
+    /// Clears the persistent flag of the device, the counterpart of [`persist`](Self::persist).
+    ///
+    /// The device is then destroyed when its last fd is closed, which makes this the same as a
+    /// [`cleanup_policy`](Self::cleanup_policy) that destroys the device. Use it to reclaim a
+    /// device a previous process left persistent.
+    pub fn unpersist(&self) -> io::Result<()> {
+        let policy = CleanupPolicy {
+            destroy_device: true,
+            ..self.cleanup_policy()
+        };
+        self.set_cleanup_policy(policy)
+    }
+    /// Returns whether the persistent flag of the device is set.
+    pub fn is_persistent(&self) -> io::Result<bool> {
+        let flags = unsafe { flags(self.as_raw_fd())? };
+        Ok(flags & libc::IFF_PERSIST as c_short != 0)
+    }
+    pub(crate) fn set_persist(&self, persist: bool) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
         unsafe {
-            if let Err(err) = tunsetpersist(self.as_raw_fd(), &1) {
+            if let Err(err) = tunsetpersist(self.as_raw_fd(), persist as _) {
                 Err(io::Error::from(err))
             } else {
                 Ok(())
@@ -422,41 +801,64 @@ impl DeviceImpl {
     ) -> io::Result<usize> {
         self.send_multiple0(gro_table, bufs, offset, |tun, buf| tun.send(buf))
     }
-    pub(crate) fn send_multiple0<B: ExpandBuffer, W: FnMut(&Tun, &[u8]) -> io::Result<usize>>(
-        &self,
-        gro_table: &mut GROTable,
-        bufs: &mut [B],
-        mut offset: usize,
-        mut write_f: W,
-    ) -> io::Result<usize> {
-        gro_table.reset();
-        if bufs.is_empty() {
-            return Ok(0);
+    /// Sends `packet` preceded by the virtio network header `hdr`, e.g. one requesting
+    /// checksum offload from [`VirtioNetHdr::checksum_offload`]. Returns the number of bytes
+    /// of `packet` that were sent.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] unless offload is enabled.
+    pub fn send_with_hdr(&self, hdr: &VirtioNetHdr, packet: &[u8]) -> io::Result<usize> {
+        if !self.vnet_hdr {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "offload is not enabled",
+            ));
         }
-        if bufs.len() > u16::MAX as usize {
+        let mut hdr_buf = [0u8; VIRTIO_NET_HDR_LEN];
+        hdr.encode(&mut hdr_buf)?;
+        let n = self
+            .tun
+            .send_vectored(&[io::IoSlice::new(&hdr_buf), io::IoSlice::new(packet)])?;
+        Ok(n.saturating_sub(VIRTIO_NET_HDR_LEN))
+    }
+    /// Sends `packet`, a TCP or UDP packet whose transport checksum has not been computed,
+    /// letting the kernel compute it when offload is enabled and computing it here otherwise.
+    ///
+    /// `packet` is modified in place, and is an Ethernet frame on a TAP device. See
+    /// [`VirtioNetHdr::checksum_offload`].
+    pub fn send_with_checksum_offload(&self, packet: &mut [u8]) -> io::Result<usize> {
+        let link_len = self.link_len();
+        let Some(ip_packet) = packet.get_mut(link_len..) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                "too many packet buffers",
+                "frame is shorter than its link-layer header",
             ));
-        }
+        };
+        let mut hdr = VirtioNetHdr::checksum_offload(ip_packet)?;
+        hdr.csum_start += link_len as u16;
+        hdr.hdr_len += link_len as u16;
         if self.vnet_hdr {
-            handle_gro(
-                bufs,
-                offset,
-                &mut gro_table.tcp_gro_table,
-                &mut gro_table.udp_gro_table,
-                self.udp_gso,
-                &mut gro_table.to_write,
-            )?;
-            offset -= VIRTIO_NET_HDR_LEN;
-        } else {
-            for i in 0..bufs.len() {
-                gro_table.to_write.push(i);
-            }
+            return self.send_with_hdr(&hdr, packet);
         }
-
+        let start = hdr.csum_start as usize;
+        let at = start + hdr.csum_offset as usize;
+        // The field holds the pseudo-header checksum, which is what a full sum must start from.
+        let mut csum = !crate::platform::linux::checksum::checksum(&packet[start..], 0);
+        if csum == 0 && hdr.csum_offset == 6 {
+            // A zero UDP checksum means "no checksum".
+            csum = 0xffff;
+        }
+        packet[at..at + 2].copy_from_slice(&csum.to_be_bytes());
+        self.tun.send(packet)
+    }
+    pub(crate) fn send_multiple0<B: ExpandBuffer, W: FnMut(&Tun, &[u8]) -> io::Result<usize>>(
+        &self,
+        gro_table: &mut GROTable,
+        bufs: &mut [B],
+        offset: usize,
+        mut write_f: W,
+    ) -> io::Result<usize> {
+        let (offset, mut err) = self.prepare_send_multiple(gro_table, bufs, offset)?;
         let mut total = 0;
-        let mut err = Ok(());
         for buf_idx in &gro_table.to_write {
             let Some(buf) = bufs[*buf_idx].as_ref().get(offset..) else {
                 return Err(io::Error::new(
@@ -464,7 +866,8 @@ impl DeviceImpl {
                     "invalid offset",
                 ));
             };
-            match write_f(&self.tun, buf) {
+            self.egress_limit.wait(buf.len());
+            match self.count_sent_multiple(buf, write_f(&self.tun, buf)) {
                 Ok(n) => {
                     total += n;
                 }
@@ -481,6 +884,110 @@ impl DeviceImpl {
         err?;
         Ok(total)
     }
+    /// Counts the result of writing `buf` for `send_multiple`.
+    ///
+    /// With offload, `buf` starts with a virtio network header and may be a GSO packet: it is
+    /// counted once per segment the kernel splits it into, with the bytes of those segments.
+    #[inline]
+    pub(crate) fn count_sent_multiple(
+        &self,
+        _buf: &[u8],
+        result: io::Result<usize>,
+    ) -> io::Result<usize> {
+        #[cfg(feature = "counters")]
+        if self.vnet_hdr {
+            let (packets, bytes) = gso_segments(_buf);
+            self.counters.sent_segments(&result, packets, bytes);
+        } else {
+            self.counters.sent(&result);
+        }
+        result
+    }
+    /// Runs the send hooks over each packet of `bufs`, then coalesces them if offload is
+    /// enabled, leaving the indices of the buffers to write in `gro_table.to_write`.
+    ///
+    /// Returns the offset of the data to write in those buffers, and the error of the last
+    /// packet rejected by frame validation.
+    pub(crate) fn prepare_send_multiple<B: ExpandBuffer>(
+        &self,
+        gro_table: &mut GROTable,
+        bufs: &mut [B],
+        offset: usize,
+    ) -> io::Result<(usize, io::Result<()>)> {
+        if bufs.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many packet buffers",
+            ));
+        }
+        let (kept, err) = self.outbound_segments(bufs, offset)?;
+        let bufs = &mut bufs[..kept];
+        if bufs.is_empty() {
+            gro_table.reset();
+            return Ok((offset, err));
+        }
+        if self.vnet_hdr {
+            gro_table.apply_gro_link(bufs, offset, self.link_len(), self.udp_gso)?;
+            crate::trace::dev_event!(
+                trace,
+                self,
+                packets = bufs.len(),
+                writes = gro_table.to_write.len(),
+                "gro coalesced"
+            );
+            Ok((offset - VIRTIO_NET_HDR_LEN, err))
+        } else {
+            gro_table.reset();
+            for i in 0..bufs.len() {
+                gro_table.to_write.push(i);
+            }
+            Ok((offset, err))
+        }
+    }
+    /// Runs the middleware chain, the MSS clamp and frame validation over the packet at
+    /// `offset` in each of `bufs`, writing rewritten packets back, and passes the result to
+    /// the packet tap.
+    ///
+    /// Packets dropped or rejected are moved behind the others, keeping the order of the
+    /// rest. Returns how many are left, and the error of the last packet rejected.
+    fn outbound_segments<B: ExpandBuffer>(
+        &self,
+        bufs: &mut [B],
+        offset: usize,
+    ) -> io::Result<(usize, io::Result<()>)> {
+        let mut kept = 0;
+        let mut err = Ok(());
+        for i in 0..bufs.len() {
+            let rewritten = {
+                let Some(packet) = bufs[i].as_ref().get(offset..) else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid offset",
+                    ));
+                };
+                let Some(packet) = self.middleware_outbound(packet) else {
+                    continue;
+                };
+                let frame = match self.validate_outbound(&packet) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        err = Err(e);
+                        continue;
+                    }
+                };
+                self.tap_outbound(&frame);
+                let changed = matches!(packet, Cow::Owned(_)) || matches!(frame, Cow::Owned(_));
+                changed.then(|| frame.into_owned())
+            };
+            if let Some(frame) = rewritten {
+                bufs[i].buf_resize(offset + frame.len(), 0);
+                bufs[i].as_mut()[offset..].copy_from_slice(&frame);
+            }
+            bufs.swap(kept, i);
+            kept += 1;
+        }
+        Ok((kept, err))
+    }
     /// Receives multiple packets in a batch with GSO (Generic Segmentation Offload) splitting.
     ///
     /// When offload is enabled, this method can receive large GSO packets from the TUN device
@@ -574,31 +1081,36 @@ impl DeviceImpl {
         if bufs.is_empty() || bufs.len() != sizes.len() {
             return Err(io::Error::other("bufs error"));
         }
-        if self.vnet_hdr {
-            let len = read_f(&self.tun, original_buffer)?;
-            if len <= VIRTIO_NET_HDR_LEN {
-                Err(io::Error::other(format!(
-                    "length of packet ({len}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
-                )))?
-            }
-            let hdr = VirtioNetHdr::decode(&original_buffer[..VIRTIO_NET_HDR_LEN])?;
-            self.handle_virtio_read(
-                hdr,
-                &mut original_buffer[VIRTIO_NET_HDR_LEN..len],
-                bufs,
-                sizes,
-                offset,
-            )
-        } else {
-            let Some(buf) = bufs[0].as_mut().get_mut(offset..) else {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "invalid offset",
-                ));
+        loop {
+            let num = if self.vnet_hdr {
+                let len = read_f(&self.tun, original_buffer)?;
+                if len <= VIRTIO_NET_HDR_LEN {
+                    Err(io::Error::other(format!(
+                        "length of packet ({len}) <= VIRTIO_NET_HDR_LEN ({VIRTIO_NET_HDR_LEN})",
+                    )))?
+                }
+                let hdr = VirtioNetHdr::decode(&original_buffer[..VIRTIO_NET_HDR_LEN])?;
+                self.handle_virtio_read(
+                    hdr,
+                    &mut original_buffer[VIRTIO_NET_HDR_LEN..len],
+                    bufs,
+                    sizes,
+                    offset,
+                )?
+            } else {
+                let Some(buf) = bufs[0].as_mut().get_mut(offset..) else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid offset",
+                    ));
+                };
+                sizes[0] = read_f(&self.tun, buf)?;
+                1
             };
-            let len = read_f(&self.tun, buf)?;
-            sizes[0] = len;
-            Ok(1)
+            let num = self.inbound_segments(bufs, sizes, offset, num);
+            if num > 0 {
+                return Ok(num);
+            }
         }
     }
     /// https://github.com/WireGuard/wireguard-go/blob/12269c2761734b15625017d8565745096325392f/tun/tun_linux.go#L375
@@ -654,6 +1166,18 @@ impl DeviceImpl {
                 "virtioNetHdr.gsoSize must be non-zero",
             ));
         }
+        // The Ethernet header of a TAP frame is split off here and copied in front of every
+        // segment; csum_start counts from the start of the frame.
+        let link_len = self.link_len();
+        if (hdr.csum_start as usize) < link_len || len <= link_len {
+            Err(io::Error::other(format!(
+                "virtioNetHdr.csumStart ({}) is inside the link-layer header",
+                hdr.csum_start
+            )))?
+        }
+        hdr.csum_start -= link_len as u16;
+        let (link, input) = input.split_at_mut(link_len);
+        let len = input.len();
         if hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV4
             && hdr.gso_type != VIRTIO_NET_HDR_GSO_TCPV6
             && hdr.gso_type != VIRTIO_NET_HDR_GSO_UDP_L4
@@ -728,23 +1252,29 @@ impl DeviceImpl {
                 c_sum_at + 1,
             )))?
         }
-        gso_split(input, hdr, bufs, sizes, offset, ip_version == 6)
+        let num = gso_split(input, hdr, bufs, sizes, offset + link_len, ip_version == 6)?;
+        crate::trace::dev_event!(
+            trace,
+            self,
+            gso_type = hdr.gso_type,
+            gso_size = hdr.gso_size,
+            len,
+            segments = num,
+            "gso split"
+        );
+        for (buf, size) in bufs.iter_mut().zip(sizes.iter_mut()).take(num) {
+            buf.as_mut()[offset..offset + link_len].copy_from_slice(link);
+            *size += link_len;
+        }
+        Ok(num)
     }
     pub fn remove_address_v6_impl(&self, addr: Ipv6Addr, prefix: u8) -> io::Result<()> {
-        unsafe {
-            let if_index = self.if_index_impl()?;
-            let ctl = ctl_v6()?;
-            let mut ifrv6: in6_ifreq = mem::zeroed();
-            ifrv6.ifr6_ifindex = if_index as i32;
-            ifrv6.ifr6_prefixlen = prefix as _;
-            ifrv6.ifr6_addr = sockaddr_union::from(std::net::SocketAddr::new(addr.into(), 0))
-                .addr6
-                .sin6_addr;
-            if let Err(err) = siocdifaddr_in6(ctl.as_raw_fd(), &ifrv6) {
-                return Err(io::Error::from(err));
-            }
-        }
-        Ok(())
+        let address = netlink::Address {
+            address: addr.into(),
+            prefix,
+            destination: None,
+        };
+        netlink::delete_address(self.if_index_impl()?, &address)
     }
 }
 
@@ -753,62 +1283,16 @@ impl DeviceImpl {
     unsafe fn request(&self) -> io::Result<ifreq> {
         request(&self.name_impl()?)
     }
-    fn set_address_v4(&self, addr: Ipv4Addr) -> io::Result<()> {
-        unsafe {
-            let mut req = self.request()?;
-            ipaddr_to_sockaddr(addr, 0, &mut req.ifr_ifru.ifru_addr, OVERWRITE_SIZE);
-            if let Err(err) = siocsifaddr(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-        }
-        Ok(())
-    }
-    fn set_netmask(&self, value: Ipv4Addr) -> io::Result<()> {
-        unsafe {
-            let mut req = self.request()?;
-            ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_netmask, OVERWRITE_SIZE);
-            if let Err(err) = siocsifnetmask(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(())
-        }
-    }
-
-    fn set_destination(&self, value: Ipv4Addr) -> io::Result<()> {
-        unsafe {
-            let mut req = self.request()?;
-            ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_dstaddr, OVERWRITE_SIZE);
-            if let Err(err) = siocsifdstaddr(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(())
-        }
-    }
-
     /// Retrieves the name of the network interface.
     pub(crate) fn name_impl(&self) -> io::Result<String> {
         unsafe { name(self.as_raw_fd()) }
     }
 
-    fn ifru_flags(&self) -> io::Result<i16> {
-        unsafe {
-            let ctl = ctl()?;
-            let mut req = self.request()?;
-
-            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(req.ifr_ifru.ifru_flags)
-        }
-    }
-
     fn remove_all_address_v4(&self) -> io::Result<()> {
-        let interface = netconfig_rs::Interface::try_from_index(self.if_index_impl()?)
-            .map_err(io::Error::from)?;
-        let list = interface.addresses().map_err(io::Error::from)?;
-        for x in list {
-            if x.addr().is_ipv4() {
-                interface.remove_address(x).map_err(io::Error::from)?;
+        let if_index = self.if_index_impl()?;
+        for address in netlink::addresses(if_index)? {
+            if address.address.is_ipv4() {
+                netlink::delete_address(if_index, &address)?;
             }
         }
         Ok(())
@@ -857,7 +1341,7 @@ impl DeviceImpl {
             let tun_name = CString::new(value)?;
 
             if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
-                return Err(io::Error::new(io::ErrorKind::InvalidInput, "name too long"));
+                return Err(crate::Error::NameTooLong.into());
             }
 
             let mut req = self.request()?;
@@ -879,35 +1363,72 @@ impl DeviceImpl {
     /// The interface is considered running if both the IFF_UP and IFF_RUNNING flags are set.
     pub fn is_running(&self) -> io::Result<bool> {
         let _guard = self.op_lock.read().unwrap();
-        let flags = self.ifru_flags()?;
-        Ok(flags & (IFF_UP | IFF_RUNNING) as c_short == (IFF_UP | IFF_RUNNING) as c_short)
+        let flags = netlink::link(self.if_index_impl()?)?.flags;
+        let running = (IFF_UP | IFF_RUNNING) as u32;
+        Ok(flags & running == running)
     }
     /// Enables or disables the network interface.
     ///
-    /// If `value` is true, the interface is enabled by setting the IFF_UP and IFF_RUNNING flags.
-    /// If false, the IFF_UP flag is cleared. The change is applied using a system call.
+    /// The interface is brought up or down with an `RTM_NEWLINK` request changing `IFF_UP`.
     pub fn enabled(&self, value: bool) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        unsafe {
-            let ctl = ctl()?;
-            let mut req = self.request()?;
-
-            if let Err(err) = siocgifflags(ctl.as_raw_fd(), &mut req) {
-                return Err(io::Error::from(err));
-            }
-
-            if value {
-                req.ifr_ifru.ifru_flags |= (IFF_UP | IFF_RUNNING) as c_short;
-            } else {
-                req.ifr_ifru.ifru_flags &= !(IFF_UP as c_short);
-            }
-
-            if let Err(err) = siocsifflags(ctl.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-
-            Ok(())
+        netlink::set_link_up(self.if_index_impl()?, value)
+    }
+    /// Returns whether the interface is administratively up (`IFF_UP`), see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.read().unwrap();
+        let flags = netlink::link(self.if_index_impl()?)?.flags;
+        Ok(flags & IFF_UP as u32 != 0)
+    }
+    /// Returns the operational state of the interface (`IFLA_OPERSTATE`).
+    ///
+    /// The kernel reports a TUN/TAP device without carrier as `IF_OPER_DOWN`; while the
+    /// interface is administratively up this is returned as [`OperState::LowerLayerDown`],
+    /// like `IF_OPER_NOTPRESENT`, `IF_OPER_TESTING` and `IF_OPER_DORMANT`. A device that never
+    /// changed its carrier reports `IF_OPER_UNKNOWN`.
+    pub fn oper_state(&self) -> io::Result<OperState> {
+        let _guard = self.op_lock.read().unwrap();
+        let link = netlink::link(self.if_index_impl()?)?;
+        if link.flags & IFF_UP as u32 == 0 {
+            return Ok(OperState::Down);
         }
+        Ok(match link.oper_state.map(c_int::from) {
+            Some(IF_OPER_UP) => OperState::Up,
+            Some(IF_OPER_UNKNOWN) | None => OperState::Unknown,
+            Some(_) => OperState::LowerLayerDown,
+        })
+    }
+    /// Turns the carrier of the interface on or off (`TUNSETCARRIER`).
+    ///
+    /// Without carrier the interface stays administratively up but its operational state is
+    /// [`OperState::LowerLayerDown`] and the kernel stops sending packets to it, which
+    /// simulates a link flap, e.g. to test how routing daemons react.
+    ///
+    /// Requires Linux 4.19 or later. Works on TUN and TAP devices.
+    pub fn set_carrier(&self, carrier: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        unsafe { tunsetcarrier(self.as_raw_fd(), &(carrier as c_int)) }?;
+        Ok(())
+    }
+    /// Sets the send buffer of this queue (`TUNSETSNDBUF`), in bytes.
+    ///
+    /// Bounds the memory of the packets written to the queue that the kernel has not
+    /// processed yet; a write beyond it blocks, or fails with `WouldBlock` on a non-blocking
+    /// device. The kernel default is unlimited (`INT_MAX`), so this mostly serves to apply
+    /// backpressure to a writer rather than to raise throughput. Values above `INT_MAX` are
+    /// clamped; 0 is rejected by the kernel.
+    ///
+    /// Each queue of a multi-queue device has its own buffer.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        let size = c_int::try_from(size).unwrap_or(c_int::MAX);
+        unsafe { tunsetsndbuf(self.as_raw_fd(), &size) }?;
+        Ok(())
+    }
+    /// Returns the send buffer of this queue (`TUNGETSNDBUF`), in bytes.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        let mut size: c_int = 0;
+        unsafe { tungetsndbuf(self.as_raw_fd(), &mut size) }?;
+        Ok(size as usize)
     }
     /// Retrieves the broadcast address of the network interface.
     ///
@@ -984,13 +1505,33 @@ impl DeviceImpl {
         destination: Option<IPv4>,
     ) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
+        let address = netlink::Address {
+            address: address.ipv4()?.into(),
+            prefix: netmask.prefix()?,
+            destination: destination.map(|v| v.ipv4()).transpose()?.map(IpAddr::V4),
+        };
         self.remove_all_address_v4()?;
-        self.set_address_v4(address.ipv4()?)?;
-        self.set_netmask(netmask.netmask()?)?;
-        if let Some(destination) = destination {
-            self.set_destination(destination.ipv4()?)?;
-        }
-        Ok(())
+        netlink::add_address(self.if_index_impl()?, &address)
+    }
+    /// Sets the IPv4 network address like [`set_network_address`](Self::set_network_address),
+    /// with `broadcast` as its broadcast address instead of the last address of the network.
+    ///
+    /// The address and its broadcast address are added in one request.
+    pub fn set_network_address_with_broadcast<IPv4: ToIpv4Address, Netmask: ToIpv4Netmask>(
+        &self,
+        address: IPv4,
+        netmask: Netmask,
+        broadcast: IPv4,
+    ) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let address = netlink::Address {
+            address: address.ipv4()?.into(),
+            prefix: netmask.prefix()?,
+            destination: None,
+        };
+        let broadcast = broadcast.ipv4()?;
+        self.remove_all_address_v4()?;
+        netlink::add_address_with(self.if_index_impl()?, &address, Some(broadcast), 0)
     }
     /// Add IPv4 network address and netmask to the interface.
     ///
@@ -1020,11 +1561,12 @@ impl DeviceImpl {
         netmask: Netmask,
     ) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        let interface = netconfig_rs::Interface::try_from_index(self.if_index_impl()?)
-            .map_err(io::Error::from)?;
-        interface
-            .add_address(IpNet::new_assert(address.ipv4()?.into(), netmask.prefix()?))
-            .map_err(io::Error::from)
+        let address = netlink::Address {
+            address: address.ipv4()?.into(),
+            prefix: netmask.prefix()?,
+            destination: None,
+        };
+        netlink::add_address(self.if_index_impl()?, &address)
     }
     /// Removes an IP address from the interface.
     ///
@@ -1056,29 +1598,10 @@ impl DeviceImpl {
     /// ```
     pub fn remove_address(&self, addr: IpAddr) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        match addr {
-            IpAddr::V4(_) => {
-                let interface = netconfig_rs::Interface::try_from_index(self.if_index_impl()?)
-                    .map_err(io::Error::from)?;
-                let list = interface.addresses().map_err(io::Error::from)?;
-                for x in list {
-                    if x.addr() == addr {
-                        interface.remove_address(x).map_err(io::Error::from)?;
-                    }
-                }
-            }
-            IpAddr::V6(addr_v6) => {
-                let addrs = crate::platform::get_if_addrs_by_name(self.name_impl()?)?;
-                for x in addrs {
-                    if let Some(ip_addr) = x.address.ip_addr() {
-                        if ip_addr == addr {
-                            if let Some(netmask) = x.address.netmask() {
-                                let prefix = ipnet::ip_mask_to_prefix(netmask).unwrap_or(0);
-                                self.remove_address_v6_impl(addr_v6, prefix)?
-                            }
-                        }
-                    }
-                }
+        let if_index = self.if_index_impl()?;
+        for address in netlink::addresses(if_index)? {
+            if address.address == addr {
+                netlink::delete_address(if_index, &address)?;
             }
         }
         Ok(())
@@ -1113,45 +1636,43 @@ impl DeviceImpl {
         netmask: Netmask,
     ) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        unsafe {
-            let if_index = self.if_index_impl()?;
-            let ctl = ctl_v6()?;
-            let mut ifrv6: in6_ifreq = mem::zeroed();
-            ifrv6.ifr6_ifindex = if_index as i32;
-            ifrv6.ifr6_prefixlen = netmask.prefix()? as u32;
-            ifrv6.ifr6_addr =
-                sockaddr_union::from(std::net::SocketAddr::new(addr.ipv6()?.into(), 0))
-                    .addr6
-                    .sin6_addr;
-            if let Err(err) = siocsifaddr_in6(ctl.as_raw_fd(), &ifrv6) {
-                return Err(io::Error::from(err));
-            }
-        }
-        Ok(())
+        let address = netlink::Address {
+            address: addr.ipv6()?.into(),
+            prefix: netmask.prefix()?,
+            destination: None,
+        };
+        netlink::add_address(self.if_index_impl()?, &address)
+    }
+    /// Adds an IPv6 address like [`add_address_v6`](Self::add_address_v6), with the given
+    /// kernel flags.
+    pub fn add_address_v6_with_flags<IPv6: ToIpv6Address, Netmask: ToIpv6Netmask>(
+        &self,
+        addr: IPv6,
+        netmask: Netmask,
+        flags: crate::Ipv6AddressFlags,
+    ) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let address = netlink::Address {
+            address: addr.ipv6()?.into(),
+            prefix: netmask.prefix()?,
+            destination: None,
+        };
+        netlink::add_address_with(self.if_index_impl()?, &address, None, flags.bits())
     }
     /// Retrieves the current MTU (Maximum Transmission Unit) for the interface.
     ///
-    /// This function constructs an interface request and uses a system call (via `siocgifmtu`)
-    /// to obtain the MTU. The result is then converted to a u16.
+    /// The MTU is read from the `IFLA_MTU` attribute of the link.
     pub fn mtu(&self) -> io::Result<u16> {
         let _guard = self.op_lock.read().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-
-            if let Err(err) = siocgifmtu(ctl()?.as_raw_fd(), &mut req) {
-                return Err(io::Error::from(err));
-            }
-
-            req.ifr_ifru
-                .ifru_mtu
-                .try_into()
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))
-        }
+        let mtu = netlink::link(self.if_index_impl()?)?
+            .mtu
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "link has no MTU"))?;
+        mtu.try_into()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}")))
     }
     /// Sets the MTU (Maximum Transmission Unit) for the interface.
     ///
-    /// This function creates an interface request, sets the `ifru_mtu` field to the new value,
-    /// and then applies it via a system call.
+    /// The MTU is changed with an `RTM_NEWLINK` request.
     ///
     /// # Example
     ///
@@ -1173,55 +1694,220 @@ impl DeviceImpl {
     /// ```
     pub fn set_mtu(&self, value: u16) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-            req.ifr_ifru.ifru_mtu = value as i32;
-
-            if let Err(err) = siocsifmtu(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
-            }
-            Ok(())
-        }
+        netlink::set_mtu(self.if_index_impl()?, value as u32)
     }
     /// Sets the MAC (hardware) address for the interface.
     ///
-    /// This function constructs an interface request and copies the provided MAC address
-    /// into the hardware address field. It then applies the change via a system call.
-    /// This operation is typically supported only for TAP devices.
+    /// The address is changed with an `RTM_NEWLINK` request. This operation is typically
+    /// supported only for TAP devices.
+    ///
+    /// Kernels that cannot change the address of a running interface fail with `EBUSY`; the
+    /// interface is then brought down, changed and brought up again. Routes via the interface
+    /// are removed by the kernel when it goes down. Use
+    /// [`set_mac_address_live`](Self::set_mac_address_live) to refuse that.
     pub fn set_mac_address(&self, eth_addr: [u8; ETHER_ADDR_LEN as usize]) -> io::Result<()> {
         let _guard = self.op_lock.write().unwrap();
-        unsafe {
-            let mut req = self.request()?;
-            req.ifr_ifru.ifru_hwaddr.sa_family = ARPHRD_ETHER;
-            req.ifr_ifru.ifru_hwaddr.sa_data[0..ETHER_ADDR_LEN as usize]
-                .copy_from_slice(eth_addr.map(|c| c as _).as_slice());
-            if let Err(err) = siocsifhwaddr(ctl()?.as_raw_fd(), &req) {
-                return Err(io::Error::from(err));
+        let if_index = self.if_index_impl()?;
+        match netlink::set_mac(if_index, &eth_addr) {
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                netlink::set_link_up(if_index, false)?;
+                let result = netlink::set_mac(if_index, &eth_addr);
+                netlink::set_link_up(if_index, true)?;
+                result
             }
-            Ok(())
+            result => result,
         }
     }
+    /// Sets the MAC address like [`set_mac_address`](Self::set_mac_address), without ever
+    /// bringing the interface down.
+    ///
+    /// Fails with [`Error::MustBounceInterface`](crate::Error::MustBounceInterface) if the
+    /// kernel only accepts the change while the interface is down.
+    pub fn set_mac_address_live(&self, eth_addr: [u8; ETHER_ADDR_LEN as usize]) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        netlink::set_mac(self.if_index_impl()?, &eth_addr).map_err(|e| {
+            if e.raw_os_error() == Some(libc::EBUSY) {
+                crate::Error::MustBounceInterface {
+                    setting: "mac_address",
+                }
+                .into()
+            } else {
+                e
+            }
+        })
+    }
     /// Retrieves the MAC (hardware) address of the interface.
     ///
-    /// This function queries the MAC address by the interface name using a helper function.
-    /// An error is returned if the MAC address cannot be found.
+    /// The address is read from the `IFLA_ADDRESS` attribute of the link. A TUN device has no
+    /// hardware address, for which all zeros are returned, as `SIOCGIFHWADDR` does.
     pub fn mac_address(&self) -> io::Result<[u8; ETHER_ADDR_LEN as usize]> {
         let _guard = self.op_lock.read().unwrap();
+        match netlink::link(self.if_index_impl()?)?.mac {
+            None => Ok([0; ETHER_ADDR_LEN as usize]),
+            Some(mac) => mac.try_into().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "hardware address is not an Ethernet address",
+                )
+            }),
+        }
+    }
+    /// Enables or disables IPv6 stateless address autoconfiguration on the interface.
+    ///
+    /// Disabling it stops the kernel from accepting router advertisements on the tunnel,
+    /// so no RA-derived addresses, default routes or prefix routes are installed.
+    /// Writes `accept_ra` and `autoconf` under `/proc/sys/net/ipv6/conf/<name>/`.
+    pub fn set_ipv6_autoconf(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let name = self.name_impl()?;
+        let value = if value { "1" } else { "0" };
+        write_ipv6_conf(&name, "accept_ra", value)?;
+        write_ipv6_conf(&name, "autoconf", value)
+    }
+    /// Enables or disables IPv6 duplicate address detection on the interface.
+    ///
+    /// Without DAD a new address is usable at once instead of after about a second; affects
+    /// addresses added afterwards. Writes `accept_dad` under `/proc/sys/net/ipv6/conf/<name>/`.
+    pub fn set_ipv6_dad(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let name = self.name_impl()?;
+        write_ipv6_conf(&name, "accept_dad", if value { "1" } else { "0" })
+    }
+    /// Enables or disables IPv6 privacy extensions (RFC 4941) on the interface: temporary
+    /// addresses generated alongside, and preferred over, autoconfigured ones.
+    ///
+    /// Writes `use_tempaddr` (`2` or `0`) under `/proc/sys/net/ipv6/conf/<name>/`.
+    pub fn set_ipv6_privacy(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let name = self.name_impl()?;
+        write_ipv6_conf(&name, "use_tempaddr", if value { "2" } else { "0" })
+    }
+    /// Enables or disables IPv6 forwarding on the interface.
+    ///
+    /// Writes `forwarding` under `/proc/sys/net/ipv6/conf/<name>/`. Note that enabling
+    /// forwarding makes the kernel ignore router advertisements unless `accept_ra` is `2`.
+    pub fn set_ipv6_forwarding(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        let name = self.name_impl()?;
+        write_ipv6_conf(&name, "forwarding", if value { "1" } else { "0" })
+    }
+    /// Adds the device as a port of the existing bridge `bridge` (`SIOCBRADDIF`).
+    ///
+    /// Typically used with TAP devices, e.g. `dev.attach_to_bridge("br0")`.
+    pub fn attach_to_bridge(&self, bridge: &str) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        if bridge.len() >= IFNAMSIZ {
+            return Err(crate::Error::NameTooLong.into());
+        }
         unsafe {
-            let mut req = self.request()?;
+            let mut req = request(bridge)?;
+            req.ifr_ifru.ifru_ifindex = self.if_index_impl()? as _;
+            if let Err(err) = siocbraddif(ctl()?.as_raw_fd(), &req) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+    /// Removes the device from the bridge `bridge` (`SIOCBRDELIF`).
+    pub fn detach_from_bridge(&self, bridge: &str) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        if bridge.len() >= IFNAMSIZ {
+            return Err(crate::Error::NameTooLong.into());
+        }
+        unsafe {
+            let mut req = request(bridge)?;
+            req.ifr_ifru.ifru_ifindex = self.if_index_impl()? as _;
+            if let Err(err) = siocbrdelif(ctl()?.as_raw_fd(), &req) {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+}
 
-            siocgifhwaddr(ctl()?.as_raw_fd(), &mut req).map_err(io::Error::from)?;
+fn write_ipv6_conf(name: &str, key: &str, value: &str) -> io::Result<()> {
+    std::fs::write(format!("/proc/sys/net/ipv6/conf/{name}/{key}"), value)
+}
 
-            let hw = &req.ifr_ifru.ifru_hwaddr.sa_data;
+/// Opens an `AF_PACKET` socket that transmits on interface `if_index` and receives nothing.
+fn packet_socket(if_index: u32) -> io::Result<Fd> {
+    unsafe {
+        // Protocol 0 keeps the socket from being handed any incoming frames.
+        let fd = Fd::new(libc::socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            0,
+        ))?;
+        let mut addr: libc::sockaddr_ll = mem::zeroed();
+        addr.sll_family = libc::AF_PACKET as _;
+        addr.sll_ifindex = if_index as _;
+        if libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const _ as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
 
-            let mut mac = [0u8; ETHER_ADDR_LEN as usize];
-            for (i, b) in hw.iter().take(6).enumerate() {
-                mac[i] = *b as u8;
-            }
+/// Returns the `IFF_*` flags supported by the kernel's TUN driver (`TUNGETFEATURES`).
+///
+/// Use this to probe for optional features such as `IFF_NAPI` before requesting them.
+///
+/// # Example
+///
+/// ```no_run
+/// let features = tun_rs::tun_features()?;
+/// if features & libc::IFF_NAPI != 0 {
+///     println!("NAPI is supported");
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn tun_features() -> io::Result<libc::c_int> {
+    unsafe {
+        let fd = libc::open(
+            c"/dev/net/tun".as_ptr() as *const _,
+            O_RDWR | libc::O_CLOEXEC,
+            0,
+        );
+        let fd = Fd::new(fd)?;
+        tun_features_fd(fd.inner)
+    }
+}
 
-            Ok(mac)
+unsafe fn tun_features_fd(fd: RawFd) -> io::Result<libc::c_int> {
+    let mut features: libc::c_uint = 0;
+    tungetfeatures(fd, &mut features)?;
+    Ok(features as libc::c_int)
+}
+
+/// Computes the NAPI flags to request, dropping the ones the kernel cannot provide.
+unsafe fn napi_flags(fd: RawFd, napi_frags: bool, device_type: c_short) -> c_short {
+    let features = match tun_features_fd(fd) {
+        Ok(features) => features,
+        Err(err) => {
+            log::warn!("failed to probe TUN features, NAPI disabled: {err:?}");
+            return 0;
         }
+    };
+    if features & libc::IFF_NAPI == 0 {
+        log::warn!("IFF_NAPI is not supported by this kernel");
+        return 0;
     }
+    let mut flags = libc::IFF_NAPI;
+    if napi_frags {
+        if features & libc::IFF_NAPI_FRAGS == 0 {
+            log::warn!("IFF_NAPI_FRAGS is not supported by this kernel");
+        } else if device_type != IFF_TAP as c_short {
+            log::warn!("IFF_NAPI_FRAGS is only available in TAP mode");
+        } else {
+            flags |= libc::IFF_NAPI_FRAGS;
+        }
+    }
+    flags as c_short
 }
 
 unsafe fn name(fd: RawFd) -> io::Result<String> {
@@ -1234,6 +1920,14 @@ unsafe fn name(fd: RawFd) -> io::Result<String> {
     Ok(tun_name)
 }
 
+unsafe fn flags(fd: RawFd) -> io::Result<c_short> {
+    let mut req: ifreq = mem::zeroed();
+    if let Err(err) = tungetiff(fd, &mut req as *mut _ as *mut _) {
+        return Err(io::Error::from(err));
+    }
+    Ok(req.ifr_ifru.ifru_flags)
+}
+
 unsafe fn request(name: &str) -> io::Result<ifreq> {
     let mut req: ifreq = mem::zeroed();
     ptr::copy_nonoverlapping(
@@ -2,16 +2,29 @@ mod sys;
 
 mod checksum;
 mod device;
+#[cfg(feature = "ndp_proxy")]
+pub(crate) mod ndp_proxy;
+pub(crate) mod netlink;
 pub(crate) mod offload;
+pub(crate) mod route;
+#[cfg(feature = "ebpf")]
+pub(crate) mod steering;
+#[cfg(feature = "ebpf")]
+mod tc;
+pub(crate) mod vtap;
 #[doc(hidden)]
 pub use checksum::{checksum, checksum_no_fold};
-pub use device::DeviceImpl;
+pub use device::{tun_features, DeviceImpl};
+pub use offload::gso_segment_count;
 pub use offload::ExpandBuffer;
-pub use offload::GROTable;
 pub use offload::IDEAL_BATCH_SIZE;
 pub use offload::VIRTIO_NET_HDR_LEN;
 #[doc(hidden)]
 pub use offload::{
-    gso_split, handle_gro, VirtioNetHdr, VIRTIO_NET_HDR_GSO_TCPV4, VIRTIO_NET_HDR_GSO_TCPV6,
+    gso_split, handle_gro, VIRTIO_NET_HDR_GSO_TCPV4, VIRTIO_NET_HDR_GSO_TCPV6,
     VIRTIO_NET_HDR_GSO_UDP_L4,
 };
+pub use offload::{GROTable, GroStats, VirtioNetHdr};
+#[cfg(feature = "ebpf")]
+pub use tc::TcDirection;
+pub use vtap::{IpvtapMode, MacvtapMode};
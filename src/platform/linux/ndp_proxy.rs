@@ -0,0 +1,45 @@
+//! Proxy neighbour entries over rtnetlink and the `proxy_ndp` switch.
+
+use crate::platform::linux::netlink::{ip_bytes, Message};
+use std::io;
+use std::net::Ipv6Addr;
+
+const NDMSG_LEN: usize = 12;
+
+fn request(msg_type: u16, flags: i32, if_index: u32, address: Ipv6Addr) -> io::Result<()> {
+    let mut body = [0u8; NDMSG_LEN];
+    body[0] = libc::AF_INET6 as u8;
+    body[4..8].copy_from_slice(&if_index.to_ne_bytes());
+    body[8..10].copy_from_slice(&libc::NUD_PERMANENT.to_ne_bytes());
+    body[10] = libc::NTF_PROXY;
+    let mut msg = Message::new(msg_type, flags, &body);
+    msg.attr(libc::NDA_DST, &ip_bytes(address.into()));
+    msg.send()?;
+    Ok(())
+}
+
+/// Publishes `address` on the interface `if_index` (`ip -6 neigh add proxy`).
+pub(crate) fn add_proxy(if_index: u32, address: Ipv6Addr) -> io::Result<()> {
+    let flags = libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_REPLACE;
+    request(libc::RTM_NEWNEIGH, flags, if_index, address)
+}
+
+/// Withdraws `address` from the interface `if_index` (`ip -6 neigh del proxy`).
+pub(crate) fn delete_proxy(if_index: u32, address: Ipv6Addr) -> io::Result<()> {
+    let flags = libc::NLM_F_REQUEST | libc::NLM_F_ACK;
+    request(libc::RTM_DELNEIGH, flags, if_index, address)
+}
+
+fn proxy_ndp_path(name: &str) -> String {
+    format!("/proc/sys/net/ipv6/conf/{name}/proxy_ndp")
+}
+
+/// Reads `net.ipv6.conf.<name>.proxy_ndp`.
+pub(crate) fn proxy_ndp(name: &str) -> io::Result<bool> {
+    Ok(std::fs::read_to_string(proxy_ndp_path(name))?.trim() != "0")
+}
+
+/// Writes `net.ipv6.conf.<name>.proxy_ndp`.
+pub(crate) fn set_proxy_ndp(name: &str, enabled: bool) -> io::Result<()> {
+    std::fs::write(proxy_ndp_path(name), if enabled { "1" } else { "0" })
+}
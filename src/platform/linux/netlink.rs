@@ -0,0 +1,390 @@
+//! Minimal rtnetlink client for the link, address and route operations of a device.
+
+use std::io;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+pub(crate) const NLMSG_HDR_LEN: usize = 16;
+const IFINFOMSG_LEN: usize = 16;
+const IFADDRMSG_LEN: usize = 8;
+const IFA_FLAGS: u16 = 8;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_LINK: u16 = 5;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_INFO_KIND: u16 = 1;
+const IFLA_INFO_DATA: u16 = 2;
+pub(crate) const IFA_F_NODAD: u32 = 0x02;
+pub(crate) const IFA_F_OPTIMISTIC: u32 = 0x04;
+pub(crate) const IFA_F_MANAGETEMPADDR: u32 = 0x100;
+pub(crate) const IFA_F_NOPREFIXROUTE: u32 = 0x200;
+
+/// A netlink request under construction: the header, a fixed-size message and attributes.
+pub(crate) struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    pub(crate) fn new(msg_type: u16, flags: i32, body: &[u8]) -> Self {
+        let mut buf = vec![0u8; NLMSG_HDR_LEN];
+        buf[4..6].copy_from_slice(&msg_type.to_ne_bytes());
+        buf[6..8].copy_from_slice(&(flags as u16).to_ne_bytes());
+        buf[8..12].copy_from_slice(&1u32.to_ne_bytes());
+        buf.extend_from_slice(body);
+        buf.resize(align(buf.len()), 0);
+        Self { buf }
+    }
+    pub(crate) fn attr(&mut self, kind: u16, data: &[u8]) -> &mut Self {
+        push_attr(&mut self.buf, kind, data);
+        self
+    }
+    /// Sends the request and returns the payloads of the replies, without their headers.
+    ///
+    /// Collects all parts of a multipart (dump) reply and fails on an error reply.
+    pub(crate) fn send(mut self) -> io::Result<Vec<Vec<u8>>> {
+        let len = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&len.to_ne_bytes());
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut kernel: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as _;
+        let res = unsafe {
+            libc::sendto(
+                fd.as_raw_fd(),
+                self.buf.as_ptr().cast(),
+                self.buf.len(),
+                0,
+                (&kernel as *const libc::sockaddr_nl).cast(),
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut replies = Vec::new();
+        let mut buf = vec![0u8; 32 * 1024];
+        loop {
+            let len = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut data = &buf[..len as usize];
+            let mut more = false;
+            while data.len() >= NLMSG_HDR_LEN {
+                let msg_len = u32::from_ne_bytes(data[0..4].try_into().unwrap()) as usize;
+                if msg_len < NLMSG_HDR_LEN || msg_len > data.len() {
+                    return Err(truncated());
+                }
+                let msg_type = u16::from_ne_bytes([data[4], data[5]]);
+                let flags = u16::from_ne_bytes([data[6], data[7]]);
+                let payload = &data[NLMSG_HDR_LEN..msg_len];
+                match msg_type as i32 {
+                    libc::NLMSG_DONE => return Ok(replies),
+                    libc::NLMSG_ERROR => {
+                        if payload.len() < 4 {
+                            return Err(truncated());
+                        }
+                        let errno = i32::from_ne_bytes(payload[0..4].try_into().unwrap());
+                        if errno != 0 {
+                            return Err(io::Error::from_raw_os_error(-errno));
+                        }
+                        // The acknowledgement ends the exchange.
+                        return Ok(replies);
+                    }
+                    _ => replies.push(payload.to_vec()),
+                }
+                more |= flags as i32 & libc::NLM_F_MULTI != 0;
+                data = &data[align(msg_len).min(data.len())..];
+            }
+            if !more {
+                return Ok(replies);
+            }
+        }
+    }
+}
+
+/// Appends an attribute to `buf`, which is either a message or the payload of a nested
+/// attribute.
+pub(crate) fn push_attr(buf: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    buf.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(align(buf.len()), 0);
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "truncated netlink reply")
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// The attributes following the fixed-size message of `len` bytes in `payload`.
+pub(crate) fn attrs(payload: &[u8], len: usize) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut data = payload.get(len..).unwrap_or_default();
+    std::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+        let attr_len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let kind = u16::from_ne_bytes([data[2], data[3]]);
+        if attr_len < 4 || attr_len > data.len() {
+            return None;
+        }
+        let value = &data[4..attr_len];
+        data = &data[align(attr_len).min(data.len())..];
+        Some((kind, value))
+    })
+}
+
+pub(crate) fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+pub(crate) fn ip_from_bytes(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(data).unwrap())),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(data).unwrap())),
+        _ => None,
+    }
+}
+
+fn family(ip: IpAddr) -> u8 {
+    if ip.is_ipv4() {
+        libc::AF_INET as u8
+    } else {
+        libc::AF_INET6 as u8
+    }
+}
+
+/// The state of a link.
+pub(crate) struct Link {
+    pub(crate) flags: u32,
+    pub(crate) mtu: Option<u32>,
+    pub(crate) mac: Option<Vec<u8>>,
+    /// `IF_OPER_*` of RFC 2863.
+    pub(crate) oper_state: Option<u8>,
+}
+
+fn ifinfomsg(if_index: u32, flags: u32, change: u32) -> [u8; IFINFOMSG_LEN] {
+    let mut msg = [0u8; IFINFOMSG_LEN];
+    msg[0] = libc::AF_UNSPEC as u8;
+    msg[4..8].copy_from_slice(&(if_index as i32).to_ne_bytes());
+    msg[8..12].copy_from_slice(&flags.to_ne_bytes());
+    msg[12..16].copy_from_slice(&change.to_ne_bytes());
+    msg
+}
+
+/// Queries the flags, MTU and hardware address of a link.
+pub(crate) fn link(if_index: u32) -> io::Result<Link> {
+    let body = ifinfomsg(if_index, 0, 0);
+    let replies = Message::new(libc::RTM_GETLINK, libc::NLM_F_REQUEST, &body).send()?;
+    let payload = replies
+        .into_iter()
+        .find(|payload| payload.len() >= IFINFOMSG_LEN)
+        .ok_or_else(truncated)?;
+    let mut link = Link {
+        flags: u32::from_ne_bytes(payload[8..12].try_into().unwrap()),
+        mtu: None,
+        mac: None,
+        oper_state: None,
+    };
+    for (kind, value) in attrs(&payload, IFINFOMSG_LEN) {
+        match kind {
+            libc::IFLA_MTU if value.len() == 4 => {
+                link.mtu = Some(u32::from_ne_bytes(value.try_into().unwrap()));
+            }
+            libc::IFLA_ADDRESS => link.mac = Some(value.to_vec()),
+            libc::IFLA_OPERSTATE if value.len() == 1 => link.oper_state = Some(value[0]),
+            _ => {}
+        }
+    }
+    Ok(link)
+}
+
+fn set_link(if_index: u32, flags: u32, change: u32, attr: Option<(u16, &[u8])>) -> io::Result<()> {
+    let body = ifinfomsg(if_index, flags, change);
+    let mut msg = Message::new(
+        libc::RTM_NEWLINK,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK,
+        &body,
+    );
+    if let Some((kind, data)) = attr {
+        msg.attr(kind, data);
+    }
+    msg.send()?;
+    Ok(())
+}
+
+/// Brings a link up or down.
+pub(crate) fn set_link_up(if_index: u32, up: bool) -> io::Result<()> {
+    let flags = if up { libc::IFF_UP as u32 } else { 0 };
+    set_link(if_index, flags, libc::IFF_UP as u32, None)
+}
+
+pub(crate) fn set_mtu(if_index: u32, mtu: u32) -> io::Result<()> {
+    set_link(if_index, 0, 0, Some((libc::IFLA_MTU, &mtu.to_ne_bytes())))
+}
+
+pub(crate) fn set_mac(if_index: u32, mac: &[u8]) -> io::Result<()> {
+    set_link(if_index, 0, 0, Some((libc::IFLA_ADDRESS, mac)))
+}
+
+/// Creates the link `name` of type `kind` on top of the link `lower`, with `data` as the
+/// type-specific attributes (`IFLA_INFO_DATA`).
+pub(crate) fn create_link(name: &str, lower: u32, kind: &str, data: &[u8]) -> io::Result<()> {
+    let body = ifinfomsg(0, 0, 0);
+    let mut msg = Message::new(
+        libc::RTM_NEWLINK,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+        &body,
+    );
+    let mut name = name.as_bytes().to_vec();
+    name.push(0);
+    msg.attr(IFLA_IFNAME, &name);
+    msg.attr(IFLA_LINK, &lower.to_ne_bytes());
+    let mut info = Vec::new();
+    push_attr(&mut info, IFLA_INFO_KIND, kind.as_bytes());
+    push_attr(&mut info, IFLA_INFO_DATA, data);
+    msg.attr(IFLA_LINKINFO, &info);
+    msg.send()?;
+    Ok(())
+}
+
+/// Deletes a link.
+pub(crate) fn delete_link(if_index: u32) -> io::Result<()> {
+    let body = ifinfomsg(if_index, 0, 0);
+    Message::new(
+        libc::RTM_DELLINK,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK,
+        &body,
+    )
+    .send()?;
+    Ok(())
+}
+
+/// An address of a link.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Address {
+    pub(crate) address: IpAddr,
+    pub(crate) prefix: u8,
+    /// The peer of a point-to-point address.
+    pub(crate) destination: Option<IpAddr>,
+}
+
+fn ifaddrmsg(if_index: u32, address: IpAddr, prefix: u8) -> [u8; IFADDRMSG_LEN] {
+    let mut msg = [0u8; IFADDRMSG_LEN];
+    msg[0] = family(address);
+    msg[1] = prefix;
+    msg[4..8].copy_from_slice(&if_index.to_ne_bytes());
+    msg
+}
+
+/// Lists the addresses of a link.
+pub(crate) fn addresses(if_index: u32) -> io::Result<Vec<Address>> {
+    let body = [libc::AF_UNSPEC as u8, 0, 0, 0, 0, 0, 0, 0];
+    let replies = Message::new(
+        libc::RTM_GETADDR,
+        libc::NLM_F_REQUEST | libc::NLM_F_DUMP,
+        &body,
+    )
+    .send()?;
+    let mut list = Vec::new();
+    for payload in replies {
+        if payload.len() < IFADDRMSG_LEN
+            || u32::from_ne_bytes(payload[4..8].try_into().unwrap()) != if_index
+        {
+            continue;
+        }
+        let (mut local, mut address) = (None, None);
+        for (kind, value) in attrs(&payload, IFADDRMSG_LEN) {
+            match kind {
+                libc::IFA_LOCAL => local = ip_from_bytes(value),
+                libc::IFA_ADDRESS => address = ip_from_bytes(value),
+                _ => {}
+            }
+        }
+        // IFA_LOCAL is only present, and then the local address, on point-to-point links.
+        let entry = match (local, address) {
+            (Some(local), Some(address)) => Address {
+                address: local,
+                prefix: payload[1],
+                destination: (local != address).then_some(address),
+            },
+            (Some(address), None) | (None, Some(address)) => Address {
+                address,
+                prefix: payload[1],
+                destination: None,
+            },
+            (None, None) => continue,
+        };
+        list.push(entry);
+    }
+    Ok(list)
+}
+
+/// Adds an address, with the broadcast address of its network for IPv4 without a peer.
+pub(crate) fn add_address(if_index: u32, address: &Address) -> io::Result<()> {
+    add_address_with(if_index, address, None, 0)
+}
+
+/// Adds an address like [`add_address`], with `broadcast` in place of the one derived from
+/// the network and the `IFA_F_*` `flags`, all in one request.
+pub(crate) fn add_address_with(
+    if_index: u32,
+    address: &Address,
+    broadcast: Option<Ipv4Addr>,
+    flags: u32,
+) -> io::Result<()> {
+    let mut body = ifaddrmsg(if_index, address.address, address.prefix);
+    // The flags that fit `ifa_flags`, the full set goes in `IFA_FLAGS`.
+    body[2] = flags as u8;
+    let mut msg = Message::new(
+        libc::RTM_NEWADDR,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+        &body,
+    );
+    msg.attr(libc::IFA_LOCAL, &ip_bytes(address.address));
+    let peer = address.destination.unwrap_or(address.address);
+    msg.attr(libc::IFA_ADDRESS, &ip_bytes(peer));
+    if let Some(broadcast) = broadcast {
+        msg.attr(libc::IFA_BROADCAST, &broadcast.octets());
+    } else if let (IpAddr::V4(ip), None) = (address.address, address.destination) {
+        if address.prefix < 31 {
+            let host_mask = u32::MAX.checked_shr(address.prefix as u32).unwrap_or(0);
+            let broadcast = u32::from(ip) | host_mask;
+            msg.attr(libc::IFA_BROADCAST, &broadcast.to_be_bytes());
+        }
+    }
+    if flags > u8::MAX as u32 {
+        msg.attr(IFA_FLAGS, &flags.to_ne_bytes());
+    }
+    msg.send()?;
+    Ok(())
+}
+
+/// Removes an address; the prefix length must match the one it was added with.
+pub(crate) fn delete_address(if_index: u32, address: &Address) -> io::Result<()> {
+    let body = ifaddrmsg(if_index, address.address, address.prefix);
+    let mut msg = Message::new(
+        libc::RTM_DELADDR,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK,
+        &body,
+    );
+    msg.attr(libc::IFA_LOCAL, &ip_bytes(address.address));
+    msg.send()?;
+    Ok(())
+}
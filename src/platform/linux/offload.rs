@@ -97,6 +97,7 @@ loop {
 */
 
 /// https://github.com/WireGuard/wireguard-go/blob/master/tun/offload_linux.go
+use crate::ethernet::{ETHERNET_HEADER_LEN, ETHERTYPE_IPV4, ETHERTYPE_IPV6};
 use crate::platform::linux::checksum::{checksum, pseudo_header_checksum_no_fold};
 use byteorder::{BigEndian, ByteOrder};
 use bytes::BytesMut;
@@ -307,6 +308,71 @@ impl VirtioNetHdr {
             Ok(())
         }
     }
+
+    /// Prepares `packet`, a TCP or UDP packet over IPv4 or IPv6 (without extension headers),
+    /// for checksum offload and returns the header that requests it.
+    ///
+    /// The transport checksum field is set to the pseudo-header checksum, which the kernel
+    /// completes over the transport header and payload when the packet is sent with the
+    /// header, e.g. by [`send_with_hdr`](crate::DeviceImpl::send_with_hdr). Fails with
+    /// [`io::ErrorKind::InvalidInput`] for other packets.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, VirtioNetHdr};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .offload(true)
+    ///     .build_sync()?;
+    /// let mut packet = vec![0u8; 1500]; // a locally generated TCP or UDP packet
+    /// let hdr = VirtioNetHdr::checksum_offload(&mut packet)?;
+    /// dev.send_with_hdr(&hdr, &packet)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn checksum_offload(packet: &mut [u8]) -> io::Result<VirtioNetHdr> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        let (iph_len, protocol, addr_len, src_addr_at) = match packet.first().map(|b| b >> 4) {
+            Some(4) if packet.len() >= 20 => (
+                ((packet[0] & 0x0f) as usize) * 4,
+                packet[9],
+                4,
+                IPV4_SRC_ADDR_OFFSET,
+            ),
+            Some(6) if packet.len() >= 40 => (40, packet[6], 16, IPV6_SRC_ADDR_OFFSET),
+            _ => return Err(invalid("not an IP packet")),
+        };
+        let (csum_offset, transport_len) = match protocol as i32 {
+            IPPROTO_TCP if packet.len() >= iph_len + 20 => {
+                (16, ((packet[iph_len + 12] >> 4) as usize) * 4)
+            }
+            IPPROTO_UDP if packet.len() >= iph_len + UDP_H_LEN => (6, UDP_H_LEN),
+            _ => return Err(invalid("not a TCP or UDP packet")),
+        };
+        if iph_len < 20
+            || packet.len() < iph_len + transport_len
+            || packet.len() > u16::MAX as usize
+        {
+            return Err(invalid("malformed packet"));
+        }
+        let psum = pseudo_header_checksum_no_fold(
+            protocol,
+            &packet[src_addr_at..src_addr_at + addr_len],
+            &packet[src_addr_at + addr_len..src_addr_at + addr_len * 2],
+            (packet.len() - iph_len) as u16,
+        );
+        let csum_at = iph_len + csum_offset;
+        BigEndian::write_u16(&mut packet[csum_at..csum_at + 2], checksum(&[], psum));
+        Ok(VirtioNetHdr {
+            flags: VIRTIO_NET_HDR_F_NEEDS_CSUM,
+            gso_type: VIRTIO_NET_HDR_GSO_NONE,
+            hdr_len: (iph_len + transport_len) as u16,
+            gso_size: 0,
+            csum_start: iph_len as u16,
+            csum_offset: csum_offset as u16,
+        })
+    }
 }
 
 /// Size of the virtio network header in bytes (12 bytes).
@@ -332,6 +398,58 @@ impl VirtioNetHdr {
 /// ```
 pub const VIRTIO_NET_HDR_LEN: usize = std::mem::size_of::<VirtioNetHdr>();
 
+/// Largest GSO packet: the IP total length is a 16-bit field.
+pub(crate) const MAX_GSO_PACKET_LEN: usize = 65535;
+
+/// Returns the most packets [`gso_split`] can split a GSO packet of `len` bytes into.
+///
+/// The bound follows from the kernel's segmentation limits: a TCP segment carries at least
+/// 48 bytes of payload (`TCP_MIN_SND_MSS`) behind at least 40 bytes of IPv4 and TCP headers,
+/// and a UDP GSO packet is split into at most 128 datagrams (`UDP_MAX_SEGMENTS`). Typical
+/// traffic, segmented for the MTU, produces far fewer segments.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+/// # {
+/// assert_eq!(tun_rs::gso_segment_count(65535), 1365);
+/// assert_eq!(tun_rs::gso_segment_count(1000), 128);
+/// # }
+/// ```
+pub fn gso_segment_count(len: usize) -> usize {
+    const TCP_MIN_SND_MSS: usize = 48;
+    const TCP_MIN_HEADERS_LEN: usize = 20 + 20;
+    const UDP_MAX_SEGMENTS: usize = 128;
+    const UDP_MIN_HEADERS_LEN: usize = 20 + 8;
+    let tcp = len
+        .saturating_sub(TCP_MIN_HEADERS_LEN)
+        .div_ceil(TCP_MIN_SND_MSS);
+    let udp = len
+        .saturating_sub(UDP_MIN_HEADERS_LEN)
+        .min(UDP_MAX_SEGMENTS);
+    tcp.max(udp).max(1)
+}
+
+/// Returns how many segments a packet written behind a virtio network header is split into
+/// and their length in total, headers included.
+#[cfg(feature = "counters")]
+pub(crate) fn gso_segments(buf: &[u8]) -> (usize, usize) {
+    let len = buf.len().saturating_sub(VIRTIO_NET_HDR_LEN);
+    let Ok(hdr) = VirtioNetHdr::decode(buf) else {
+        return (1, len);
+    };
+    // VIRTIO_NET_HDR_GSO_ECN, set on top of the GSO type.
+    const GSO_ECN: u8 = 0x80;
+    let gso_size = hdr.gso_size as usize;
+    if hdr.gso_type & !GSO_ECN == VIRTIO_NET_HDR_GSO_NONE || gso_size == 0 {
+        return (1, len);
+    }
+    let hdr_len = (hdr.hdr_len as usize).min(len);
+    let segments = (len - hdr_len).div_ceil(gso_size).max(1);
+    (segments, len + (segments - 1) * hdr_len)
+}
+
 /// Identifier for a TCP flow used in Generic Receive Offload (GRO).
 ///
 /// This structure uniquely identifies a TCP connection for packet coalescing.
@@ -393,6 +511,7 @@ pub struct TcpFlowKey {
 pub struct TcpGROTable {
     items_by_flow: HashMap<TcpFlowKey, Vec<TcpGROItem>>,
     items_pool: Vec<Vec<TcpGROItem>>,
+    max_segments: u16,
 }
 
 impl Default for TcpGROTable {
@@ -410,6 +529,7 @@ impl TcpGROTable {
         TcpGROTable {
             items_by_flow: HashMap::with_capacity(IDEAL_BATCH_SIZE),
             items_pool,
+            max_segments: u16::MAX,
         }
     }
 }
@@ -550,6 +670,7 @@ pub struct UdpFlowKey {
 pub struct UdpGROTable {
     items_by_flow: HashMap<UdpFlowKey, Vec<UdpGROItem>>,
     items_pool: Vec<Vec<UdpGROItem>>,
+    max_segments: u16,
 }
 
 impl Default for UdpGROTable {
@@ -567,6 +688,7 @@ impl UdpGROTable {
         UdpGROTable {
             items_by_flow: HashMap::with_capacity(IDEAL_BATCH_SIZE),
             items_pool,
+            max_segments: u16::MAX,
         }
     }
 }
@@ -705,6 +827,10 @@ fn ip_headers_can_coalesce(pkt_a: &[u8], pkt_b: &[u8]) -> bool {
             // cannot coalesce with unequal Traffic class values
             return false;
         }
+        if pkt_a[1] & 0x0f != pkt_b[1] & 0x0f || pkt_a[2..4] != pkt_b[2..4] {
+            // cannot coalesce with unequal Flow label values
+            return false;
+        }
         if pkt_a[7] != pkt_b[7] {
             // cannot coalesce with unequal Hop limit values
             return false;
@@ -1004,6 +1130,32 @@ const IPV4_SRC_ADDR_OFFSET: usize = 12;
 const IPV6_SRC_ADDR_OFFSET: usize = 8;
 // maxUint16         = 1<<16 - 1
 
+/// Whether the link-layer header in front of a packet, if any, is an Ethernet header of an
+/// IP packet. Other frames of a TAP device are never coalesced.
+fn link_header_is_ip(link: &[u8]) -> bool {
+    match link.len() {
+        0 => true,
+        ETHERNET_HEADER_LEN => matches!(
+            u16::from_be_bytes([link[12], link[13]]),
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the packets at `i` and `j` have the same link-layer header, so that one can carry
+/// the payload of the other.
+fn link_headers_equal<B: AsRef<[u8]>>(
+    bufs: &[B],
+    offset: usize,
+    link_len: usize,
+    i: usize,
+    j: usize,
+) -> bool {
+    let link = offset..offset + link_len;
+    bufs[i].as_ref()[link.clone()] == bufs[j].as_ref()[link]
+}
+
 #[derive(PartialEq, Eq)]
 enum GroResult {
     Noop,
@@ -1019,11 +1171,13 @@ enum GroResult {
 fn tcp_gro<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    link_len: usize,
     pkt_i: usize,
     table: &mut TcpGROTable,
     is_v6: bool,
 ) -> GroResult {
-    let pkt_storage = bufs[pkt_i].as_ref()[offset..].to_vec();
+    let ip_offset = offset + link_len;
+    let pkt_storage = bufs[pkt_i].as_ref()[ip_offset..].to_vec();
     let pkt = pkt_storage.as_slice();
     if pkt.len() > u16::MAX as usize {
         // A valid IPv4 or IPv6 packet will never exceed this.
@@ -1097,6 +1251,7 @@ fn tcp_gro<B: ExpandBuffer>(
         addr_len = 16;
     }
 
+    let max_segments = table.max_segments as usize;
     let items = if let Some(items) = table.lookup_or_insert(
         pkt,
         src_addr_offset,
@@ -1120,6 +1275,12 @@ fn tcp_gro<B: ExpandBuffer>(
         // sequence number perspective, however once an item is inserted into
         // the table it is never compared across other items later.
         let item = &mut items[i];
+        if item.num_merged as usize + 1 >= max_segments {
+            continue;
+        }
+        if !link_headers_equal(bufs, offset, link_len, item.bufs_index as usize, pkt_i) {
+            continue;
+        }
         let can = tcp_packets_can_coalesce(
             pkt,
             iph_len as u8,
@@ -1129,14 +1290,14 @@ fn tcp_gro<B: ExpandBuffer>(
             gso_size,
             item,
             bufs,
-            offset,
+            ip_offset,
         );
 
         match can {
             CanCoalesce::Unavailable => {}
             _ => {
                 let result = coalesce_tcp_packets(
-                    can, pkt, pkt_i, gso_size, seq, psh_set, item, bufs, offset, is_v6,
+                    can, pkt, pkt_i, gso_size, seq, psh_set, item, bufs, ip_offset, is_v6,
                 );
 
                 match result {
@@ -1185,6 +1346,7 @@ fn tcp_gro<B: ExpandBuffer>(
 ///
 /// * `bufs` - Mutable slice of packet buffers that were processed by GRO
 /// * `offset` - Offset where packet data begins (typically [`VIRTIO_NET_HDR_LEN`])
+/// * `link_len` - Length of the link-layer header in front of the IP packet (`0` for TUN)
 /// * `table` - The TCP GRO table containing coalescing metadata
 ///
 /// # What It Does
@@ -1217,6 +1379,7 @@ fn tcp_gro<B: ExpandBuffer>(
 pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    link_len: usize,
     table: &TcpGROTable,
 ) -> io::Result<()> {
     for items in table.items_by_flow.values() {
@@ -1224,14 +1387,14 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
             if item.num_merged > 0 {
                 let mut hdr = VirtioNetHdr {
                     flags: VIRTIO_NET_HDR_F_NEEDS_CSUM,
-                    hdr_len: (item.iph_len + item.tcph_len) as u16,
+                    hdr_len: (link_len + item.iph_len as usize + item.tcph_len as usize) as u16,
                     gso_size: item.gso_size,
-                    csum_start: item.iph_len as u16,
+                    csum_start: (link_len + item.iph_len as usize) as u16,
                     csum_offset: 16,
                     gso_type: 0, // Will be set later
                 };
                 let buf = bufs[item.bufs_index as usize].as_mut();
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + link_len..];
                 let pkt_len = pkt.len();
 
                 // Calculate the pseudo header checksum and place it at the TCP
@@ -1265,7 +1428,7 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
 
                 hdr.encode(&mut buf[offset - VIRTIO_NET_HDR_LEN..])?;
 
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + link_len..];
 
                 let psum = pseudo_header_checksum_no_fold(
                     IPPROTO_TCP as _,
@@ -1275,7 +1438,7 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
                 );
                 let tcp_csum = checksum(&[], psum);
                 BigEndian::write_u16(
-                    &mut pkt[(hdr.csum_start + hdr.csum_offset) as usize..],
+                    &mut pkt[item.iph_len as usize + hdr.csum_offset as usize..],
                     tcp_csum,
                 );
             } else {
@@ -1294,6 +1457,7 @@ pub fn apply_tcp_coalesce_accounting<B: ExpandBuffer>(
 pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    link_len: usize,
     table: &UdpGROTable,
 ) -> io::Result<()> {
     for items in table.items_by_flow.values() {
@@ -1301,15 +1465,15 @@ pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
             if item.num_merged > 0 {
                 let hdr = VirtioNetHdr {
                     flags: VIRTIO_NET_HDR_F_NEEDS_CSUM, // this turns into CHECKSUM_PARTIAL in the skb
-                    hdr_len: item.iph_len as u16 + UDP_H_LEN as u16,
+                    hdr_len: (link_len + item.iph_len as usize + UDP_H_LEN) as u16,
                     gso_size: item.gso_size,
-                    csum_start: item.iph_len as u16,
+                    csum_start: (link_len + item.iph_len as usize) as u16,
                     csum_offset: 6,
                     gso_type: VIRTIO_NET_HDR_GSO_UDP_L4,
                 };
 
                 let buf = bufs[item.bufs_index as usize].as_mut();
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + link_len..];
                 let pkt_len = pkt.len();
 
                 // Calculate the pseudo header checksum and place it at the UDP
@@ -1341,7 +1505,7 @@ pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
                 }
 
                 hdr.encode(&mut buf[offset - VIRTIO_NET_HDR_LEN..])?;
-                let pkt = &mut buf[offset..];
+                let pkt = &mut buf[offset + link_len..];
                 // Recalculate the UDP len field value
                 BigEndian::write_u16(
                     &mut pkt[(item.iph_len as usize + 4)..(item.iph_len as usize + 6)],
@@ -1357,7 +1521,7 @@ pub fn apply_udp_coalesce_accounting<B: ExpandBuffer>(
 
                 let udp_csum = checksum(&[], psum);
                 BigEndian::write_u16(
-                    &mut pkt[(hdr.csum_start + hdr.csum_offset) as usize..],
+                    &mut pkt[item.iph_len as usize + hdr.csum_offset as usize..],
                     udp_csum,
                 );
             } else {
@@ -1414,11 +1578,13 @@ const UDP_H_LEN: usize = 8;
 fn udp_gro<B: ExpandBuffer>(
     bufs: &mut [B],
     offset: usize,
+    link_len: usize,
     pkt_i: usize,
     table: &mut UdpGROTable,
     is_v6: bool,
 ) -> GroResult {
-    let pkt_storage = bufs[pkt_i].as_ref()[offset..].to_vec();
+    let ip_offset = offset + link_len;
+    let pkt_storage = bufs[pkt_i].as_ref()[ip_offset..].to_vec();
     let pkt = pkt_storage.as_slice();
     if pkt.len() > u16::MAX as usize {
         // A valid IPv4 or IPv6 packet will never exceed this.
@@ -1459,6 +1625,7 @@ fn udp_gro<B: ExpandBuffer>(
         (IPV4_SRC_ADDR_OFFSET, 4)
     };
 
+    let max_segments = table.max_segments as usize;
     let items = table.lookup_or_insert(
         pkt,
         src_addr_offset,
@@ -1476,11 +1643,15 @@ fn udp_gro<B: ExpandBuffer>(
     // Only check the last item to prevent reordering packets for a flow.
     let items_len = items.len();
     let item = &mut items[items_len - 1];
-    let can = udp_packets_can_coalesce(pkt, iph_len as u8, gso_size, item, bufs, offset);
+    let can = if link_headers_equal(bufs, offset, link_len, item.bufs_index as usize, pkt_i) {
+        udp_packets_can_coalesce(pkt, iph_len as u8, gso_size, item, bufs, ip_offset)
+    } else {
+        CanCoalesce::Unavailable
+    };
     let mut pkt_csum_known_invalid = false;
 
-    if can == CanCoalesce::Append {
-        match coalesce_udp_packets(pkt, item, bufs, offset, is_v6) {
+    if can == CanCoalesce::Append && (item.num_merged as usize + 1) < max_segments {
+        match coalesce_udp_packets(pkt, item, bufs, ip_offset, is_v6) {
             CoalesceResult::Success => {
                 // 前面是引用，这里不需要再更新
                 // table.update_at(*item, items_len - 1);
@@ -1497,7 +1668,7 @@ fn udp_gro<B: ExpandBuffer>(
             _ => {}
         }
     }
-    let pkt = &bufs[pkt_i].as_ref()[offset..];
+    let pkt = &bufs[pkt_i].as_ref()[ip_offset..];
     // Failed to coalesce; store the packet in the flow.
     table.insert(
         pkt,
@@ -1587,6 +1758,22 @@ pub fn handle_gro<B: ExpandBuffer>(
     can_udp_gro: bool,
     to_write: &mut Vec<usize>,
 ) -> io::Result<()> {
+    handle_gro_link(bufs, offset, 0, tcp_table, udp_table, can_udp_gro, to_write)
+}
+
+/// [`handle_gro`] for packets behind a `link_len` bytes long link-layer header: the Ethernet
+/// header of a TAP device, or none. The virtio header still goes right before `offset`, and
+/// only frames with the same link-layer header are coalesced.
+fn handle_gro_link<B: ExpandBuffer>(
+    bufs: &mut [B],
+    offset: usize,
+    link_len: usize,
+    tcp_table: &mut TcpGROTable,
+    udp_table: &mut UdpGROTable,
+    can_udp_gro: bool,
+    to_write: &mut Vec<usize>,
+) -> io::Result<()> {
+    let ip_offset = offset + link_len;
     if bufs.len() > u16::MAX as usize {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
@@ -1595,18 +1782,23 @@ pub fn handle_gro<B: ExpandBuffer>(
     }
     let bufs_len = bufs.len();
     for i in 0..bufs_len {
-        if offset < VIRTIO_NET_HDR_LEN || offset >= bufs[i].as_ref().len() {
+        if offset < VIRTIO_NET_HDR_LEN || ip_offset >= bufs[i].as_ref().len() {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "invalid offset",
             ));
         }
 
-        let result = match packet_is_gro_candidate(&bufs[i].as_ref()[offset..], can_udp_gro) {
-            GroCandidateType::Tcp4GRO => tcp_gro(bufs, offset, i, tcp_table, false),
-            GroCandidateType::Tcp6GRO => tcp_gro(bufs, offset, i, tcp_table, true),
-            GroCandidateType::Udp4GRO => udp_gro(bufs, offset, i, udp_table, false),
-            GroCandidateType::Udp6GRO => udp_gro(bufs, offset, i, udp_table, true),
+        let candidate = if link_header_is_ip(&bufs[i].as_ref()[offset..ip_offset]) {
+            packet_is_gro_candidate(&bufs[i].as_ref()[ip_offset..], can_udp_gro)
+        } else {
+            GroCandidateType::NotGRO
+        };
+        let result = match candidate {
+            GroCandidateType::Tcp4GRO => tcp_gro(bufs, offset, link_len, i, tcp_table, false),
+            GroCandidateType::Tcp6GRO => tcp_gro(bufs, offset, link_len, i, tcp_table, true),
+            GroCandidateType::Udp4GRO => udp_gro(bufs, offset, link_len, i, udp_table, false),
+            GroCandidateType::Udp6GRO => udp_gro(bufs, offset, link_len, i, udp_table, true),
             GroCandidateType::NotGRO => GroResult::Noop,
         };
 
@@ -1624,8 +1816,8 @@ pub fn handle_gro<B: ExpandBuffer>(
         }
     }
 
-    let err_tcp = apply_tcp_coalesce_accounting(bufs, offset, tcp_table);
-    let err_udp = apply_udp_coalesce_accounting(bufs, offset, udp_table);
+    let err_tcp = apply_tcp_coalesce_accounting(bufs, offset, link_len, tcp_table);
+    let err_udp = apply_udp_coalesce_accounting(bufs, offset, link_len, udp_table);
     err_tcp?;
     err_udp?;
     Ok(())
@@ -2035,11 +2227,47 @@ pub fn gso_none_checksum(in_buf: &mut [u8], csum_start: u16, csum_offset: u16) {
 /// # Thread Safety
 ///
 /// `GROTable` is not thread-safe. Use one instance per thread or protect with a mutex.
-#[derive(Default)]
+///
+/// # Statistics and Tuning
+///
+/// [`stats`](Self::stats) reports how well packets are coalesced, e.g. for metrics.
+/// [`set_max_segments`](Self::set_max_segments) bounds the size of coalesced packets and
+/// [`set_udp_gro`](Self::set_udp_gro) turns UDP coalescing off, e.g. for latency sensitive
+/// traffic.
 pub struct GROTable {
     pub(crate) to_write: Vec<usize>,
     pub(crate) tcp_gro_table: TcpGROTable,
     pub(crate) udp_gro_table: UdpGROTable,
+    udp_gro: bool,
+    stats: GroStats,
+}
+
+impl Default for GROTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Coalescing counters of a [`GROTable`], accumulated over all `send_multiple` calls since
+/// the table was created or [`reset_stats`](GROTable::reset_stats) was called.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GroStats {
+    /// Packets passed to GRO.
+    pub packets_in: u64,
+    /// Packets written after coalescing.
+    pub packets_out: u64,
+    /// Number of TCP and UDP flows seen in the last batch.
+    pub flows_active: usize,
+}
+
+impl GroStats {
+    /// Average number of packets merged into one written packet, `1.0` without coalescing.
+    pub fn coalesce_ratio(&self) -> f64 {
+        if self.packets_out == 0 {
+            return 1.0;
+        }
+        self.packets_in as f64 / self.packets_out as f64
+    }
 }
 
 impl GROTable {
@@ -2048,6 +2276,8 @@ impl GROTable {
             to_write: Vec::with_capacity(IDEAL_BATCH_SIZE),
             tcp_gro_table: TcpGROTable::new(),
             udp_gro_table: UdpGROTable::new(),
+            udp_gro: true,
+            stats: GroStats::default(),
         }
     }
     pub(crate) fn reset(&mut self) {
@@ -2055,6 +2285,38 @@ impl GROTable {
         self.tcp_gro_table.reset();
         self.udp_gro_table.reset();
     }
+    /// Returns the coalescing counters.
+    pub fn stats(&self) -> GroStats {
+        self.stats
+    }
+    /// Sets all counters back to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = GroStats::default();
+    }
+    /// Limits the number of segments merged into one packet, per flow.
+    ///
+    /// `1` disables coalescing. The default, `u16::MAX`, leaves the limit to the 64 KiB
+    /// packet size.
+    pub fn set_max_segments(&mut self, max_segments: u16) {
+        let max_segments = max_segments.max(1);
+        self.tcp_gro_table.max_segments = max_segments;
+        self.udp_gro_table.max_segments = max_segments;
+    }
+    /// Returns the limit set with [`set_max_segments`](Self::set_max_segments).
+    pub fn max_segments(&self) -> u16 {
+        self.tcp_gro_table.max_segments
+    }
+    /// Enables or disables UDP coalescing; TCP is not affected.
+    ///
+    /// UDP is only coalesced if the device was created with UDP GSO support in the first
+    /// place. Enabled by default.
+    pub fn set_udp_gro(&mut self, enabled: bool) {
+        self.udp_gro = enabled;
+    }
+    /// Returns whether UDP coalescing is enabled.
+    pub fn udp_gro(&self) -> bool {
+        self.udp_gro
+    }
 
     #[doc(hidden)]
     pub fn apply_gro<B: ExpandBuffer>(
@@ -2062,16 +2324,33 @@ impl GROTable {
         bufs: &mut [B],
         offset: usize,
         can_udp_gro: bool,
+    ) -> io::Result<()> {
+        self.apply_gro_link(bufs, offset, 0, can_udp_gro)
+    }
+    /// [`apply_gro`](Self::apply_gro) for frames starting with a `link_len` bytes long
+    /// link-layer header.
+    pub(crate) fn apply_gro_link<B: ExpandBuffer>(
+        &mut self,
+        bufs: &mut [B],
+        offset: usize,
+        link_len: usize,
+        can_udp_gro: bool,
     ) -> io::Result<()> {
         self.reset();
-        handle_gro(
+        handle_gro_link(
             bufs,
             offset,
+            link_len,
             &mut self.tcp_gro_table,
             &mut self.udp_gro_table,
-            can_udp_gro,
+            can_udp_gro && self.udp_gro,
             &mut self.to_write,
-        )
+        )?;
+        self.stats.packets_in += bufs.len() as u64;
+        self.stats.packets_out += self.to_write.len() as u64;
+        self.stats.flows_active =
+            self.tcp_gro_table.items_by_flow.len() + self.udp_gro_table.items_by_flow.len();
+        Ok(())
     }
 }
 
@@ -2197,6 +2476,44 @@ impl ExpandBuffer for &mut Vec<u8> {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "counters")]
+    #[test]
+    fn gso_segments_counts_each_segment_with_its_headers() {
+        let mut buf = vec![0u8; VIRTIO_NET_HDR_LEN + 40 + 2500];
+        assert_eq!(gso_segments(&buf), (1, 2540));
+        VirtioNetHdr {
+            gso_type: VIRTIO_NET_HDR_GSO_TCPV4,
+            hdr_len: 40,
+            gso_size: 1000,
+            ..Default::default()
+        }
+        .encode(&mut buf)
+        .unwrap();
+        // 1000 + 1000 + 500 bytes of payload, each behind the 40 bytes of headers.
+        assert_eq!(gso_segments(&buf), (3, 2620));
+    }
+
+    #[test]
+    fn checksum_offload_completes_to_valid_checksum() {
+        let mut pkt = vec![0u8; 20 + 8 + 5];
+        pkt[0] = 0x45;
+        pkt[2..4].copy_from_slice(&33u16.to_be_bytes());
+        pkt[9] = IPPROTO_UDP as u8;
+        pkt[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        pkt[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        pkt[24..26].copy_from_slice(&13u16.to_be_bytes());
+        pkt[28..].copy_from_slice(b"hello");
+        let hdr = VirtioNetHdr::checksum_offload(&mut pkt).unwrap();
+        assert_eq!(hdr.flags, VIRTIO_NET_HDR_F_NEEDS_CSUM);
+        assert_eq!((hdr.csum_start, hdr.csum_offset), (20, 6));
+        // What the kernel does with the header.
+        let at = (hdr.csum_start + hdr.csum_offset) as usize;
+        let csum = !checksum(&pkt[hdr.csum_start as usize..], 0);
+        BigEndian::write_u16(&mut pkt[at..at + 2], csum);
+        assert!(checksum_valid(&pkt, 20, IPPROTO_UDP as u8, false));
+        assert!(VirtioNetHdr::checksum_offload(&mut [0x45, 0, 0]).is_err());
+    }
+
     fn make_ipv4_tcp_packet(seq: u32, payload_len: usize) -> Vec<u8> {
         const IPH_LEN: usize = 20;
         const TCPH_LEN: usize = 20;
@@ -2267,6 +2584,129 @@ mod tests {
             .unwrap();
     }
 
+    fn tcp_batch(segments: u32) -> Vec<Vec<u8>> {
+        (0..segments)
+            .map(|i| {
+                let mut buf = Vec::with_capacity(65536);
+                buf.resize(VIRTIO_NET_HDR_LEN, 0);
+                buf.extend_from_slice(&make_ipv4_tcp_packet(1 + i * 100, 100));
+                buf
+            })
+            .collect()
+    }
+
+    fn eth_tcp_batch(segments: u32, src_macs: &[u8]) -> Vec<Vec<u8>> {
+        (0..segments)
+            .map(|i| {
+                let mut buf = Vec::with_capacity(65536);
+                buf.resize(VIRTIO_NET_HDR_LEN, 0);
+                buf.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+                buf.extend_from_slice(&[0x02, 0, 0, 0, 0, src_macs[i as usize]]);
+                buf.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+                buf.extend_from_slice(&make_ipv4_tcp_packet(1 + i * 100, 100));
+                buf
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gro_coalesces_ethernet_frames_with_the_same_header() {
+        let mut table = GROTable::new();
+        let mut bufs = eth_tcp_batch(3, &[1, 1, 2]);
+        let eth = bufs[0][VIRTIO_NET_HDR_LEN..VIRTIO_NET_HDR_LEN + ETHERNET_HEADER_LEN].to_vec();
+        table
+            .apply_gro_link(&mut bufs, VIRTIO_NET_HDR_LEN, ETHERNET_HEADER_LEN, false)
+            .unwrap();
+
+        // The third frame comes from another MAC and is left alone.
+        assert_eq!(table.to_write, vec![0, 2]);
+        let buf = &bufs[0];
+        let hdr = VirtioNetHdr::decode(buf).unwrap();
+        assert_eq!(hdr.gso_type, VIRTIO_NET_HDR_GSO_TCPV4);
+        assert_eq!(hdr.gso_size, 100);
+        assert_eq!(hdr.csum_start as usize, ETHERNET_HEADER_LEN + 20);
+        assert_eq!(hdr.hdr_len as usize, ETHERNET_HEADER_LEN + 40);
+        let frame = &buf[VIRTIO_NET_HDR_LEN..];
+        assert_eq!(frame[..ETHERNET_HEADER_LEN], eth[..]);
+        assert_eq!(frame.len(), ETHERNET_HEADER_LEN + 40 + 200);
+        let ip = &frame[ETHERNET_HEADER_LEN..];
+        assert_eq!(u16::from_be_bytes([ip[2], ip[3]]), 240);
+        let hdr = VirtioNetHdr::decode(&bufs[2]).unwrap();
+        assert_eq!(hdr.gso_type, VIRTIO_NET_HDR_GSO_NONE);
+    }
+
+    #[test]
+    fn gro_skips_non_ip_ethernet_frames() {
+        let mut table = GROTable::new();
+        let mut bufs = eth_tcp_batch(2, &[1, 1]);
+        for buf in &mut bufs {
+            let at = VIRTIO_NET_HDR_LEN + 12;
+            buf[at..at + 2].copy_from_slice(&crate::ethernet::ETHERTYPE_ARP.to_be_bytes());
+        }
+        table
+            .apply_gro_link(&mut bufs, VIRTIO_NET_HDR_LEN, ETHERNET_HEADER_LEN, false)
+            .unwrap();
+        assert_eq!(table.to_write, vec![0, 1]);
+    }
+
+    #[test]
+    fn gso_segment_count_bounds() {
+        assert_eq!(gso_segment_count(0), 1);
+        assert_eq!(gso_segment_count(29), 1);
+        assert_eq!(gso_segment_count(100), 72);
+        assert_eq!(gso_segment_count(1500), 128);
+        assert_eq!(gso_segment_count(MAX_GSO_PACKET_LEN), 1365);
+    }
+
+    #[test]
+    fn gso_split_fits_smallest_segments() {
+        let mut input = make_ipv4_tcp_packet(1, MAX_GSO_PACKET_LEN - 40);
+        let hdr = VirtioNetHdr {
+            gso_type: VIRTIO_NET_HDR_GSO_TCPV4,
+            hdr_len: 40,
+            gso_size: 48,
+            csum_start: 20,
+            csum_offset: 16,
+            ..Default::default()
+        };
+        let count = gso_segment_count(MAX_GSO_PACKET_LEN);
+        let mut out = vec![vec![0u8; 40 + 48]; count];
+        let mut sizes = vec![0usize; count];
+
+        let n = gso_split(&mut input, hdr, &mut out, &mut sizes, 0, false).unwrap();
+
+        assert_eq!(n, count);
+    }
+
+    #[test]
+    fn gro_stats_and_max_segments() {
+        let mut table = GROTable::new();
+        let mut bufs = tcp_batch(4);
+        table
+            .apply_gro(&mut bufs, VIRTIO_NET_HDR_LEN, false)
+            .unwrap();
+        let stats = table.stats();
+        assert_eq!((stats.packets_in, stats.packets_out), (4, 1));
+        assert_eq!(stats.flows_active, 1);
+        assert_eq!(stats.coalesce_ratio(), 4.0);
+
+        table.reset_stats();
+        table.set_max_segments(2);
+        let mut bufs = tcp_batch(4);
+        table
+            .apply_gro(&mut bufs, VIRTIO_NET_HDR_LEN, false)
+            .unwrap();
+        assert_eq!(table.stats().packets_out, 2);
+
+        table.set_max_segments(0);
+        assert_eq!(table.max_segments(), 1);
+        let mut bufs = tcp_batch(4);
+        table
+            .apply_gro(&mut bufs, VIRTIO_NET_HDR_LEN, false)
+            .unwrap();
+        assert_eq!(table.stats().packets_out, 2 + 4);
+    }
+
     #[test]
     fn gso_split_rejects_zero_gso_size() {
         let mut input = make_ipv4_tcp_packet(1, 128);
@@ -0,0 +1,146 @@
+//! Adding, removing and looking up routes over rtnetlink.
+
+use crate::platform::linux::netlink::{attrs, ip_bytes, ip_from_bytes, Message};
+use crate::platform::route::Route;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const RTMSG_LEN: usize = 12;
+
+/// Adds `route` to the main table.
+pub(crate) fn add_route(route: &Route) -> io::Result<()> {
+    let flags = libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL;
+    request(libc::RTM_NEWROUTE, flags, route)?;
+    Ok(())
+}
+
+/// Removes `route` from the main table.
+pub(crate) fn delete_route(route: &Route) -> io::Result<()> {
+    let flags = libc::NLM_F_REQUEST | libc::NLM_F_ACK;
+    request(libc::RTM_DELROUTE, flags, route)?;
+    Ok(())
+}
+
+/// Lists the unicast routes of the main table through `if_index`, except those the kernel
+/// adds for the addresses of the interface.
+pub(crate) fn device_routes(if_index: u32) -> io::Result<Vec<Route>> {
+    let mut body = vec![libc::AF_UNSPEC as u8, 0, 0, 0, 0, 0, 0, 0];
+    body.extend_from_slice(&0u32.to_ne_bytes());
+    let replies = Message::new(
+        libc::RTM_GETROUTE,
+        libc::NLM_F_REQUEST | libc::NLM_F_DUMP,
+        &body,
+    )
+    .send()?;
+    let mut routes = Vec::new();
+    for reply in replies {
+        if reply.len() < RTMSG_LEN
+            || reply[4] != libc::RT_TABLE_MAIN
+            || reply[5] == libc::RTPROT_KERNEL
+            || reply[7] != libc::RTN_UNICAST
+        {
+            continue;
+        }
+        let (mut destination, mut gateway, mut oif) = (None, None, 0);
+        for (kind, data) in attrs(&reply, RTMSG_LEN) {
+            match kind {
+                libc::RTA_DST => destination = ip_from_bytes(data),
+                libc::RTA_GATEWAY => gateway = ip_from_bytes(data),
+                libc::RTA_OIF if data.len() == 4 => {
+                    oif = u32::from_ne_bytes(data.try_into().unwrap());
+                }
+                _ => {}
+            }
+        }
+        if oif != if_index {
+            continue;
+        }
+        // A default route has no destination attribute.
+        let destination = match (destination, reply[0] as i32) {
+            (Some(destination), _) => destination,
+            (None, libc::AF_INET) => Ipv4Addr::UNSPECIFIED.into(),
+            (None, libc::AF_INET6) => Ipv6Addr::UNSPECIFIED.into(),
+            (None, _) => continue,
+        };
+        routes.push(Route {
+            destination,
+            prefix: reply[1],
+            gateway,
+            if_index,
+        });
+    }
+    Ok(routes)
+}
+
+/// Asks the kernel which route it would use to reach `destination`.
+pub(crate) fn find_route(destination: IpAddr) -> io::Result<Route> {
+    let query = Route {
+        destination,
+        prefix: host_prefix(destination),
+        gateway: None,
+        if_index: 0,
+    };
+    let replies = request(libc::RTM_GETROUTE, libc::NLM_F_REQUEST, &query)?;
+    let reply = replies
+        .into_iter()
+        .find(|reply| reply.len() >= RTMSG_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated netlink reply"))?;
+    let mut route = query;
+    for (kind, data) in attrs(&reply, RTMSG_LEN) {
+        match kind {
+            libc::RTA_OIF if data.len() == 4 => {
+                route.if_index = u32::from_ne_bytes(data.try_into().unwrap());
+            }
+            libc::RTA_GATEWAY => route.gateway = ip_from_bytes(data),
+            _ => {}
+        }
+    }
+    Ok(route)
+}
+
+fn host_prefix(ip: IpAddr) -> u8 {
+    if ip.is_ipv4() {
+        32
+    } else {
+        128
+    }
+}
+
+/// Sends one route message and returns the kernel's replies.
+fn request(msg_type: u16, flags: i32, route: &Route) -> io::Result<Vec<Vec<u8>>> {
+    let family = if route.destination.is_ipv4() {
+        libc::AF_INET
+    } else {
+        libc::AF_INET6
+    };
+    let scope = match route.gateway {
+        _ if msg_type == libc::RTM_DELROUTE => libc::RT_SCOPE_NOWHERE,
+        None => libc::RT_SCOPE_LINK,
+        Some(_) => libc::RT_SCOPE_UNIVERSE,
+    };
+    let mut body = if msg_type == libc::RTM_GETROUTE {
+        // A lookup is keyed by the destination only.
+        vec![family as u8, route.prefix, 0, 0, 0, 0, 0, 0]
+    } else {
+        vec![
+            family as u8,
+            route.prefix,
+            0,
+            0,
+            libc::RT_TABLE_MAIN,
+            libc::RTPROT_STATIC,
+            scope,
+            libc::RTN_UNICAST,
+        ]
+    };
+    body.extend_from_slice(&0u32.to_ne_bytes());
+    let mut msg = Message::new(msg_type, flags, &body);
+    msg.attr(libc::RTA_DST, &ip_bytes(route.destination));
+    if let Some(gateway) = route.gateway {
+        msg.attr(libc::RTA_GATEWAY, &ip_bytes(gateway));
+    }
+    if route.if_index != 0 {
+        msg.attr(libc::RTA_OIF, &route.if_index.to_ne_bytes());
+    }
+    msg.send()
+}
@@ -0,0 +1,55 @@
+//! Queue selection of multi-queue devices by an eBPF program (`TUNSETSTEERINGEBPF`).
+
+use crate::platform::linux::sys::tunsetsteeringebpf;
+use crate::platform::DeviceImpl;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+
+/// Sets the steering program of the device behind `fd` to `prog`, or removes it with `-1`.
+pub(crate) fn set_steering_prog(fd: RawFd, prog: RawFd) -> io::Result<()> {
+    let mut prog = prog;
+    unsafe { tunsetsteeringebpf(fd, &mut prog) }?;
+    Ok(())
+}
+
+impl DeviceImpl {
+    /// Sets a loaded `BPF_PROG_TYPE_SOCKET_FILTER` program as the queue steering program of
+    /// the device.
+    ///
+    /// The kernel runs the program for every packet it routes to the device, and reads the
+    /// packet from the queue with the index the program returns, modulo the number of
+    /// queues. This replaces the default flow hashing, e.g. to pin flows to the queue of the
+    /// worker that owns them. The program applies to the device, so setting it on any queue
+    /// affects all of them, and setting it again replaces it. The kernel takes its own
+    /// reference, so `prog` may be closed afterwards. Requires `CAP_NET_ADMIN`.
+    ///
+    /// To have the program see the first packet, set it when building the device with
+    /// [`DeviceBuilder::steering_ebpf`](crate::DeviceBuilder::steering_ebpf).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    /// # fn run(prog: std::os::fd::OwnedFd) -> std::io::Result<()> {
+    /// use tun_rs::multi_queue::MultiQueueDevice;
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .multi_queue(true)
+    ///     .build_sync()?;
+    /// dev.set_steering_ebpf(&prog)?;
+    /// let group = MultiQueueDevice::new(dev, 4)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_steering_ebpf<P: AsFd>(&self, prog: &P) -> io::Result<()> {
+        set_steering_prog(self.tun.fd.inner, prog.as_fd().as_raw_fd())
+    }
+    /// Removes the steering program set with
+    /// [`set_steering_ebpf`](Self::set_steering_ebpf), so queues are picked by flow hash
+    /// again.
+    pub fn clear_steering_ebpf(&self) -> io::Result<()> {
+        set_steering_prog(self.tun.fd.inner, -1)
+    }
+}
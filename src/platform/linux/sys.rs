@@ -1,5 +1,5 @@
-use libc::{c_int, ifreq, in6_ifreq};
-use nix::{ioctl_read, ioctl_read_bad, ioctl_write_ptr, ioctl_write_ptr_bad};
+use libc::{c_int, c_uint, ifreq, in6_ifreq};
+use nix::{ioctl_read, ioctl_read_bad, ioctl_write_int, ioctl_write_ptr, ioctl_write_ptr_bad};
 
 ioctl_read_bad!(siocgifflags, 0x8913, ifreq);
 ioctl_write_ptr_bad!(siocsifflags, 0x8914, ifreq);
@@ -23,12 +23,22 @@ ioctl_read_bad!(siocgifhwaddr, 0x8927, ifreq);
 ioctl_write_ptr_bad!(siocsifhwaddr, 0x8924, ifreq);
 ioctl_read_bad!(tx_queue_len, 0x8942, ifreq);
 ioctl_write_ptr_bad!(change_tx_queue_len, 0x8943, ifreq);
+ioctl_write_ptr_bad!(siocbraddif, 0x89a2, ifreq);
+ioctl_write_ptr_bad!(siocbrdelif, 0x89a3, ifreq);
 
 ioctl_read!(tungetiff, b'T', 210, c_int);
+ioctl_read!(tungetfeatures, b'T', 207, c_uint);
+ioctl_read!(tungetsndbuf, b'T', 211, c_int);
 
 ioctl_write_ptr!(tunsetiff, b'T', 202, c_int);
-ioctl_write_ptr!(tunsetpersist, b'T', 203, c_int);
+// TUNSETPERSIST takes the flag by value, not through a pointer.
+ioctl_write_int!(tunsetpersist, b'T', 203);
+ioctl_write_ptr!(tunsetcarrier, b'T', 226, c_int);
 ioctl_write_ptr!(tunsetowner, b'T', 204, c_int);
 ioctl_write_ptr!(tunsetgroup, b'T', 206, c_int);
 ioctl_write_ptr!(tunsetoffload, b'T', 208, c_int);
 ioctl_write_ptr!(tunsetvnethdrsz, b'T', 216, c_int);
+ioctl_write_ptr!(tunsetsndbuf, b'T', 212, c_int);
+// TUNSETSTEERINGEBPF is declared `_IOR` although the kernel reads the fd from user space.
+#[cfg(feature = "ebpf")]
+ioctl_read!(tunsetsteeringebpf, b'T', 224, c_int);
@@ -0,0 +1,144 @@
+//! eBPF classifiers on the `clsact` qdisc of a device, installed over rtnetlink.
+
+use crate::platform::linux::netlink::{push_attr, Message};
+use crate::platform::DeviceImpl;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd};
+
+const TCMSG_LEN: usize = 20;
+const TC_H_CLSACT: u32 = 0xFFFF_FFF1;
+const TC_H_MIN_INGRESS: u32 = 0xFFF2;
+const TC_H_MIN_EGRESS: u32 = 0xFFF3;
+const TCA_BPF_FD: u16 = 6;
+const TCA_BPF_NAME: u16 = 7;
+const TCA_BPF_FLAGS: u16 = 8;
+const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1;
+/// The filter this crate installs, so that attaching again replaces it.
+const FILTER_HANDLE: u32 = 1;
+const FILTER_PRIO: u32 = 1;
+
+/// The hook of the `clsact` qdisc a program runs on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum TcDirection {
+    /// Packets the application writes to the device, which the kernel receives.
+    Ingress,
+    /// Packets the kernel routes to the device, which the application reads.
+    Egress,
+}
+
+impl TcDirection {
+    fn parent(self) -> u32 {
+        let minor = match self {
+            TcDirection::Ingress => TC_H_MIN_INGRESS,
+            TcDirection::Egress => TC_H_MIN_EGRESS,
+        };
+        (TC_H_CLSACT & 0xFFFF_0000) | minor
+    }
+}
+
+fn tcmsg(if_index: u32, handle: u32, parent: u32, info: u32) -> [u8; TCMSG_LEN] {
+    let mut body = [0u8; TCMSG_LEN];
+    body[0] = libc::AF_UNSPEC as u8;
+    body[4..8].copy_from_slice(&if_index.to_ne_bytes());
+    body[8..12].copy_from_slice(&handle.to_ne_bytes());
+    body[12..16].copy_from_slice(&parent.to_ne_bytes());
+    body[16..20].copy_from_slice(&info.to_ne_bytes());
+    body
+}
+
+fn filter_info() -> u32 {
+    (FILTER_PRIO << 16) | (libc::ETH_P_ALL as u16).to_be() as u32
+}
+
+/// Adds the `clsact` qdisc (`tc qdisc add dev <name> clsact`), keeping one that exists.
+fn add_clsact(if_index: u32) -> io::Result<()> {
+    let body = tcmsg(if_index, TC_H_CLSACT & 0xFFFF_0000, TC_H_CLSACT, 0);
+    let mut msg = Message::new(
+        libc::RTM_NEWQDISC,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+        &body,
+    );
+    msg.attr(libc::TCA_KIND, b"clsact\0");
+    match msg.send() {
+        Err(e) if e.raw_os_error() != Some(libc::EEXIST) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Installs a direct-action `bpf` classifier
+/// (`tc filter replace dev <name> <direction> bpf da fd <prog_fd>`).
+fn replace_filter(if_index: u32, prog_fd: i32, direction: TcDirection) -> io::Result<()> {
+    let body = tcmsg(if_index, FILTER_HANDLE, direction.parent(), filter_info());
+    let mut msg = Message::new(
+        libc::RTM_NEWTFILTER,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK | libc::NLM_F_CREATE | libc::NLM_F_REPLACE,
+        &body,
+    );
+    msg.attr(libc::TCA_KIND, b"bpf\0");
+    let mut options = Vec::new();
+    push_attr(&mut options, TCA_BPF_FD, &(prog_fd as u32).to_ne_bytes());
+    push_attr(&mut options, TCA_BPF_NAME, b"tun-rs\0");
+    push_attr(
+        &mut options,
+        TCA_BPF_FLAGS,
+        &TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes(),
+    );
+    msg.attr(libc::TCA_OPTIONS, &options);
+    msg.send()?;
+    Ok(())
+}
+
+/// Removes the classifier installed by `replace_filter`.
+fn delete_filter(if_index: u32, direction: TcDirection) -> io::Result<()> {
+    let body = tcmsg(if_index, 0, direction.parent(), filter_info());
+    let mut msg = Message::new(
+        libc::RTM_DELTFILTER,
+        libc::NLM_F_REQUEST | libc::NLM_F_ACK,
+        &body,
+    );
+    msg.attr(libc::TCA_KIND, b"bpf\0");
+    msg.send()?;
+    Ok(())
+}
+
+impl DeviceImpl {
+    /// Attaches a loaded `BPF_PROG_TYPE_SCHED_CLS` program to the device in direct-action
+    /// mode, adding the `clsact` qdisc first if the device has none.
+    ///
+    /// The program returns a `TC_ACT_*` verdict for each packet, so it can drop, rewrite or
+    /// redirect traffic. Attaching again in the same direction atomically replaces the
+    /// program. The kernel takes its own reference, so `prog` may be closed afterwards; the
+    /// program stays attached until [`detach_tc_program`](Self::detach_tc_program) or the
+    /// device is destroyed. Requires `CAP_NET_ADMIN`.
+    ///
+    /// To have the program see the very first packet, build the device with
+    /// [`enable(false)`](crate::DeviceBuilder::enable) and bring it up after attaching.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+    /// # fn run(prog: std::os::fd::OwnedFd) -> std::io::Result<()> {
+    /// use tun_rs::{DeviceBuilder, TcDirection};
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .enable(false)
+    ///     .build_sync()?;
+    /// dev.attach_tc_program(&prog, TcDirection::Egress)?;
+    /// dev.enabled(true)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn attach_tc_program<P: AsFd>(&self, prog: &P, direction: TcDirection) -> io::Result<()> {
+        let if_index = self.if_index()?;
+        add_clsact(if_index)?;
+        replace_filter(if_index, prog.as_fd().as_raw_fd(), direction)
+    }
+    /// Detaches the program attached with
+    /// [`attach_tc_program`](Self::attach_tc_program) in `direction`. The `clsact` qdisc
+    /// is left in place.
+    pub fn detach_tc_program(&self, direction: TcDirection) -> io::Result<()> {
+        delete_filter(self.if_index()?, direction)
+    }
+}
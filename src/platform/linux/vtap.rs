@@ -0,0 +1,158 @@
+//! macvtap and ipvtap devices: TAP character devices on top of a physical interface.
+//!
+//! Unlike a TAP device of `/dev/net/tun`, whose frames only reach the host stack, a macvtap
+//! or ipvtap device sends and receives through its lower interface, so frames written to it
+//! go out on the wire without a bridge in between. The link is created over rtnetlink and
+//! its queue is the character device `/dev/tapN`, `N` being the interface index, which speaks
+//! the same ioctls as `/dev/net/tun`.
+
+use crate::platform::linux::netlink;
+use crate::platform::unix::Fd;
+use std::ffi::CString;
+use std::io;
+
+const IFLA_MACVLAN_MODE: u16 = 1;
+const IFLA_IPVLAN_MODE: u16 = 1;
+
+/// How a macvtap device forwards frames between itself, the other macvlan and macvtap
+/// devices on the same lower interface and the lower interface itself.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum MacvtapMode {
+    /// No frames are exchanged with the other devices on the lower interface (`private`).
+    Private,
+    /// Frames for the other devices on the lower interface are sent out and rely on the
+    /// adjacent switch to reflect them (`vepa`), the default of the kernel.
+    #[default]
+    Vepa,
+    /// Frames for the other devices on the lower interface are delivered directly (`bridge`).
+    Bridge,
+    /// The lower interface is given to this device alone, which may then change its MAC
+    /// address and enable promiscuous mode (`passthru`).
+    Passthru,
+}
+
+impl MacvtapMode {
+    fn raw(self) -> u32 {
+        match self {
+            MacvtapMode::Private => 1,
+            MacvtapMode::Vepa => 2,
+            MacvtapMode::Bridge => 4,
+            MacvtapMode::Passthru => 8,
+        }
+    }
+}
+
+/// The layer at which an ipvtap device shares the lower interface, whose MAC address all
+/// ipvtap devices on it use.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum IpvtapMode {
+    /// Frames are switched by their IP destination after the Ethernet header is processed
+    /// (`l2`), the default of the kernel.
+    #[default]
+    L2,
+    /// Packets are routed by the IP layer of the lower interface; no broadcast or multicast
+    /// (`l3`).
+    L3,
+    /// Like `L3`, with the packets passing netfilter of the namespace of the lower
+    /// interface (`l3s`).
+    L3s,
+}
+
+impl IpvtapMode {
+    fn raw(self) -> u16 {
+        match self {
+            IpvtapMode::L2 => 0,
+            IpvtapMode::L3 => 1,
+            IpvtapMode::L3s => 2,
+        }
+    }
+}
+
+/// The kind of virtual TAP device to create on `lower`, set with
+/// [`DeviceBuilder::macvtap`](crate::DeviceBuilder::macvtap) or
+/// [`DeviceBuilder::ipvtap`](crate::DeviceBuilder::ipvtap).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Vtap {
+    pub(crate) lower: String,
+    pub(crate) kind: VtapKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum VtapKind {
+    Macvtap(MacvtapMode),
+    Ipvtap(IpvtapMode),
+}
+
+impl Vtap {
+    /// The link type, which is also the directory of the character device in sysfs.
+    fn kind(&self) -> &'static str {
+        match self.kind {
+            VtapKind::Macvtap(_) => "macvtap",
+            VtapKind::Ipvtap(_) => "ipvtap",
+        }
+    }
+    /// The first name of the form `macvtapN` or `ipvtapN` not taken by an interface.
+    pub(crate) fn free_name(&self) -> io::Result<String> {
+        (0..1024)
+            .map(|unit| format!("{}{unit}", self.kind()))
+            .find(|name| if_index(name).is_err())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::AddrInUse))
+    }
+    /// Creates the link `name` on the lower interface.
+    pub(crate) fn create(&self, name: &str) -> io::Result<()> {
+        let lower = if_index(&self.lower).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("lower device {:?} does not exist", self.lower),
+            )
+        })?;
+        let mut data = Vec::new();
+        match self.kind {
+            VtapKind::Macvtap(mode) => {
+                netlink::push_attr(&mut data, IFLA_MACVLAN_MODE, &mode.raw().to_ne_bytes())
+            }
+            VtapKind::Ipvtap(mode) => {
+                netlink::push_attr(&mut data, IFLA_IPVLAN_MODE, &mode.raw().to_ne_bytes())
+            }
+        }
+        netlink::create_link(name, lower, self.kind(), &data)
+    }
+    /// Opens a queue of the link `name`, creating its device node if udev has not.
+    pub(crate) fn open(&self, name: &str) -> io::Result<Fd> {
+        let index = if_index(name)?;
+        let path = format!("/dev/tap{index}");
+        if let Ok(false) = std::fs::exists(&path) {
+            // Silently ignore errors, opening the device reports them.
+            if let Ok(dev) = std::fs::read_to_string(format!(
+                "/sys/class/net/{name}/{}/tap{index}/dev",
+                self.kind()
+            )) {
+                if let Some((major, minor)) = dev.trim().split_once(':') {
+                    if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+                        let c_path = CString::new(path.clone())?;
+                        unsafe {
+                            libc::mknod(
+                                c_path.as_ptr(),
+                                0o600 | libc::S_IFCHR,
+                                libc::makedev(major, minor),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        let c_path = CString::new(path)?;
+        let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        Fd::new(fd)
+    }
+}
+
+pub(crate) fn if_index(name: &str) -> io::Result<u32> {
+    let c_name = CString::new(name)?;
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => Err(io::Error::last_os_error()),
+        index => Ok(index),
+    }
+}
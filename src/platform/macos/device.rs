@@ -1,3 +1,4 @@
+use crate::platform::{Cleanup, OperState};
 use crate::{
     builder::DeviceConfig,
     platform::{macos::sys::*, unix::sockaddr_union},
@@ -13,6 +14,7 @@ use crate::platform::ETHER_ADDR_LEN;
 use libc::{self, c_char, c_short, IFF_RUNNING, IFF_UP};
 use std::io::ErrorKind;
 use std::net::Ipv4Addr;
+use std::os::unix::io::{IntoRawFd, RawFd};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{io, mem, net::IpAddr, os::unix::io::AsRawFd, ptr, sync::RwLock};
 
@@ -20,13 +22,53 @@ use std::{io, mem, net::IpAddr, os::unix::io::AsRawFd, ptr, sync::RwLock};
 pub struct DeviceImpl {
     pub(crate) tun: TunTap,
     pub(crate) op_lock: RwLock<()>,
+    pub(crate) cleanup: Cleanup,
     pub(crate) associate_route: AtomicBool,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    pub(crate) frame_validation: crate::ethernet::FrameValidator,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
+}
+
+impl Drop for DeviceImpl {
+    fn drop(&mut self) {
+        // Handed over with `into_raw_fd`.
+        if self.tun.as_raw_fd() < 0 {
+            return;
+        }
+        // A utun goes with its fd, so only a feth pair can be kept.
+        let destroy = self
+            .run_cleanup()
+            .is_some_and(|policy| policy.destroy_device);
+        if let TunTap::Tap(tap) = &mut self.tun {
+            tap.set_destroy(destroy);
+        }
+    }
+}
+impl IntoRawFd for DeviceImpl {
+    fn into_raw_fd(mut self) -> RawFd {
+        self.tun.take_raw_fd()
+    }
 }
 
 impl DeviceImpl {
     /// Create a new `Device` for the given `Configuration`.
     pub(crate) fn new(config: DeviceConfig) -> io::Result<Self> {
         let associate_route = config.associate_route;
+        let keep = config.persist.unwrap_or(false) || config.attach_existing.unwrap_or(false);
+        let cleanup = Cleanup::new(config.cleanup_policy, keep);
         let tun_tap = TunTap::new(config)?;
         let associate_route = if tun_tap.is_tun() {
             associate_route.unwrap_or(true)
@@ -36,6 +78,23 @@ impl DeviceImpl {
         let device_impl = DeviceImpl {
             tun: tun_tap,
             op_lock: RwLock::new(()),
+            cleanup,
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(associate_route),
         };
         Ok(device_impl)
@@ -44,6 +103,23 @@ impl DeviceImpl {
         Ok(Self {
             tun: TunTap::Tun(tun),
             op_lock: RwLock::new(()),
+            cleanup: Cleanup::default(),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(true),
         })
     }
@@ -187,6 +263,165 @@ impl DeviceImpl {
         let _guard = self.op_lock.read().unwrap();
         self.name_impl()
     }
+    /// Renaming is not available on macOS: `utun` and `feth` interfaces keep the name they
+    /// were created with. Always fails with [`Error::Unsupported`](crate::Error::Unsupported).
+    pub fn set_name(&self, value: &str) -> io::Result<()> {
+        let _ = value;
+        Err(crate::Error::Unsupported {
+            operation: "set_name",
+        }
+        .into())
+    }
+    /// Returns the unit number of a `utun` device, e.g. `7` for `utun7`, or `None` for
+    /// `feth` devices.
+    pub fn utun_unit(&self) -> io::Result<Option<u32>> {
+        if !self.tun.is_tun() {
+            return Ok(None);
+        }
+        let name = self.name()?;
+        Ok(name.strip_prefix("utun").and_then(|unit| unit.parse().ok()))
+    }
+    /// Returns the name of the peer of a `feth` device, the interface its frames are read
+    /// from and written to, e.g. `feth1` for a device named `feth0`.
+    ///
+    /// [`name`](Self::name) is the interface that carries the addresses and routes. Fails
+    /// with [`io::ErrorKind::Unsupported`] on `utun` devices, which have no peer.
+    pub fn peer_name(&self) -> io::Result<String> {
+        match &self.tun {
+            TunTap::Tap(tap) => Ok(tap.peer_name().clone()),
+            TunTap::Tun(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only feth devices have a peer",
+            )),
+        }
+    }
+    /// Sets the send buffer (`SO_SNDBUF`) of the socket packets are written to, in bytes:
+    /// the `utun` control socket, or the NDRV socket of a `feth` device (see also
+    /// [`DeviceBuilderGuard::ndrv_send_buffer_size`](crate::DeviceBuilderGuard::ndrv_send_buffer_size)).
+    ///
+    /// The kernel caps the size at the `kern.ipc.maxsockbuf` sysctl.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        set_sockopt_size(self.tun.write_fd(), libc::SO_SNDBUF, size)
+    }
+    /// Returns the send buffer (`SO_SNDBUF`) of the socket packets are written to, in bytes.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        sockopt_size(self.tun.write_fd(), libc::SO_SNDBUF)
+    }
+    /// Sets the receive buffer (`SO_RCVBUF`) of the `utun` control socket, in bytes: how much
+    /// the kernel queues for the device before dropping packets.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] on `feth` devices, which read from a BPF
+    /// buffer sized when the device is built, see
+    /// [`DeviceBuilderGuard::bpf_buffer_size`](crate::DeviceBuilderGuard::bpf_buffer_size).
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        set_sockopt_size(self.utun_fd()?, libc::SO_RCVBUF, size)
+    }
+    /// Returns the receive buffer (`SO_RCVBUF`) of the `utun` control socket, in bytes.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] on `feth` devices.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        sockopt_size(self.utun_fd()?, libc::SO_RCVBUF)
+    }
+    fn utun_fd(&self) -> io::Result<RawFd> {
+        match &self.tun {
+            TunTap::Tun(tun) => Ok(tun.as_raw_fd()),
+            TunTap::Tap(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "feth devices are read through BPF, not a socket",
+            )),
+        }
+    }
+    /// Returns the backend of this device: [`crate::BackendKind::Utun`] for TUN,
+    /// [`crate::BackendKind::Feth`] for TAP.
+    pub fn backend_kind(&self) -> crate::BackendKind {
+        if self.tun.is_tun() {
+            crate::BackendKind::Utun
+        } else {
+            crate::BackendKind::Feth
+        }
+    }
+    /// Sends an Ethernet frame out of the TAP device as if the host had transmitted it.
+    ///
+    /// Writing to the device (`send`) injects frames towards the host, which takes them as
+    /// received from the wire. This goes the other way: the frame leaves the `feth`
+    /// interface through a second NDRV socket and is read back from this device, letting a
+    /// user-space switch hairpin traffic to its own port. Fails with
+    /// [`io::ErrorKind::Unsupported`] on TUN devices.
+    pub fn send_as_outgoing(&self, frame: &[u8]) -> io::Result<usize> {
+        match &self.tun {
+            TunTap::Tap(tap) => tap.send_as_outgoing(frame),
+            TunTap::Tun(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "send_as_outgoing needs a TAP device",
+            )),
+        }
+    }
+    /// Receives a batch of packets, mirroring the Linux `recv_multiple`.
+    ///
+    /// A TAP (`feth`) device returns every packet of one BPF read in one call, starting with
+    /// packets left over from an earlier [`recv`](crate::SyncDevice::recv); a TUN (`utun`) device returns
+    /// one packet per call. Packet `i` is written to `bufs[i][offset..]` and its length to
+    /// `sizes[i]`, and the number of packets is returned. `original_buffer` is unused, as
+    /// macOS has no offload.
+    ///
+    /// Each packet goes through the ICMP responder, the packet tap and the middleware chain
+    /// as with [`recv`](crate::SyncDevice::recv); packets answered or dropped are left out.
+    pub fn recv_multiple<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        _original_buffer: &mut [u8],
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        offset: usize,
+    ) -> io::Result<usize> {
+        loop {
+            let num = self.tun.recv_multiple(bufs, sizes, offset)?;
+            let num = self.inbound_segments(bufs, sizes, offset, num);
+            if num > 0 {
+                return Ok(num);
+            }
+        }
+    }
+    /// Sends a batch of packets, mirroring the Linux `send_multiple` without the `GROTable`.
+    ///
+    /// `bufs[i][offset..]` is sent as one packet, and the total number of bytes sent is
+    /// returned. Each packet is a separate write, so every TAP (`feth`) frame must fit in the
+    /// MTU plus its 14-byte Ethernet header; larger frames are rejected by the kernel. A failed
+    /// packet does not stop the rest of the batch, and the last error is returned.
+    ///
+    /// Each packet goes through the middleware chain, frame validation and the packet tap as
+    /// with [`send`](crate::SyncDevice::send).
+    pub fn send_multiple<B: AsRef<[u8]>>(&self, bufs: &[B], offset: usize) -> io::Result<usize> {
+        let mut total = 0;
+        let mut err = Ok(());
+        for buf in bufs {
+            let Some(buf) = buf.as_ref().get(offset..) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid offset",
+                ));
+            };
+            let Some(packet) = self.middleware_outbound(buf) else {
+                total += buf.len();
+                continue;
+            };
+            let frame = match self.validate_outbound(&packet) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    err = Err(e);
+                    continue;
+                }
+            };
+            self.egress_limit.wait(frame.len());
+            self.tap_outbound(&frame);
+            match self.count_sent(self.tun.send(&frame)) {
+                Ok(n) => total += n.min(packet.len()),
+                Err(e) if e.raw_os_error() == Some(libc::EBADF) => return Err(e),
+                Err(e) => err = Err(e),
+            }
+        }
+        err?;
+        Ok(total)
+    }
     /// System behavior:
     /// On macOS, adding an IP to a feth interface will automatically add a route,
     /// while adding an IP to an utun interface will not.
@@ -233,6 +468,34 @@ impl DeviceImpl {
             Ok(())
         }
     }
+    /// Returns whether the interface is administratively up (`IFF_UP`), see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.read().unwrap();
+        Ok(self.if_flags()? & IFF_UP as c_short != 0)
+    }
+    /// Returns the operational state of the interface, derived from `IFF_UP` and
+    /// `IFF_RUNNING`.
+    ///
+    /// An interface that is up but not running, e.g. one without link, is
+    /// [`OperState::LowerLayerDown`].
+    pub fn oper_state(&self) -> io::Result<OperState> {
+        let _guard = self.op_lock.read().unwrap();
+        let flags = self.if_flags()?;
+        Ok(if flags & IFF_UP as c_short == 0 {
+            OperState::Down
+        } else if flags & IFF_RUNNING as c_short == 0 {
+            OperState::LowerLayerDown
+        } else {
+            OperState::Up
+        })
+    }
+    fn if_flags(&self) -> io::Result<c_short> {
+        unsafe {
+            let mut req = self.request()?;
+            siocgifflags(ctl()?.as_raw_fd(), &mut req)?;
+            Ok(req.ifr_ifru.ifru_flags)
+        }
+    }
     /// Retrieves the current MTU (Maximum Transmission Unit) for the interface.
     pub fn mtu(&self) -> io::Result<u16> {
         let _guard = self.op_lock.read().unwrap();
@@ -422,4 +685,100 @@ impl DeviceImpl {
         let _guard = self.op_lock.read().unwrap();
         self.tun.mac_address()
     }
+    /// Enables or disables IPv6 stateless address autoconfiguration on the interface
+    /// (`ifconfig <name> inet6 [-]autoconf`).
+    ///
+    /// Disabling it stops the interface from accepting router advertisements, so no
+    /// RA-derived addresses or routes are installed on the tunnel.
+    pub fn set_ipv6_autoconf(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        unsafe {
+            let mut req = self.request_v6()?;
+            let rs = if value {
+                siocautoconf_start(ctl_v6()?.as_raw_fd(), &mut req)
+            } else {
+                siocautoconf_stop(ctl_v6()?.as_raw_fd(), &mut req)
+            };
+            if let Err(err) = rs {
+                return Err(io::Error::from(err));
+            }
+        }
+        Ok(())
+    }
+    /// Enables or disables IPv6 forwarding (`net.inet6.ip6.forwarding`).
+    ///
+    /// macOS has no per-interface forwarding switch, so this changes the system-wide setting.
+    pub fn set_ipv6_forwarding(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        set_sysctl_int(c"net.inet6.ip6.forwarding", value.into())
+    }
+    /// Enables or disables IPv6 duplicate address detection (`net.inet6.ip6.dad_count`, 1 or
+    /// 0).
+    ///
+    /// Without DAD a new address is usable at once instead of after about a second; affects
+    /// addresses added afterwards. macOS has no per-interface switch, so this changes the
+    /// system-wide setting.
+    pub fn set_ipv6_dad(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        set_sysctl_int(c"net.inet6.ip6.dad_count", value.into())
+    }
+    /// Enables or disables IPv6 privacy extensions, i.e. temporary addresses
+    /// (`net.inet6.ip6.use_tempaddr`).
+    ///
+    /// macOS has no per-interface switch, so this changes the system-wide setting.
+    pub fn set_ipv6_privacy(&self, value: bool) -> io::Result<()> {
+        let _guard = self.op_lock.write().unwrap();
+        set_sysctl_int(c"net.inet6.ip6.use_tempaddr", value.into())
+    }
+}
+
+fn set_sysctl_int(name: &std::ffi::CStr, value: libc::c_int) -> io::Result<()> {
+    let rs = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &value as *const libc::c_int as *mut libc::c_void,
+            mem::size_of::<libc::c_int>(),
+        )
+    };
+    if rs < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_sockopt_size(fd: RawFd, opt: libc::c_int, size: usize) -> io::Result<()> {
+    let size = libc::c_int::try_from(size).unwrap_or(libc::c_int::MAX);
+    let rs = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            opt,
+            &size as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rs != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn sockopt_size(fd: RawFd, opt: libc::c_int) -> io::Result<usize> {
+    let mut size: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let rs = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            opt,
+            &mut size as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rs != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size as usize)
 }
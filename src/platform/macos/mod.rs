@@ -5,3 +5,21 @@ mod tap;
 mod tuntap;
 
 pub use self::device::DeviceImpl;
+#[cfg(feature = "ndp_proxy")]
+pub(crate) use self::tap::run_command;
+#[cfg(any(feature = "async_tokio", feature = "async_io"))]
+pub(crate) use self::tap::{is_no_buffer_space, ENOBUFS_BACKOFF};
+
+/// Returns the unit numbers of the existing `utun` interfaces, in ascending order.
+///
+/// `utun7` is reported as `7`. Useful to pick a unit before building a device with a
+/// specific name; to let the kernel choose, build with the name `"utun"` instead, which
+/// does not race with other processes.
+pub fn utun_units() -> std::io::Result<Vec<u32>> {
+    let mut units: Vec<u32> = getifaddrs::getifaddrs()?
+        .filter_map(|interface| interface.name.strip_prefix("utun")?.parse().ok())
+        .collect();
+    units.sort_unstable();
+    units.dedup();
+    Ok(units)
+}
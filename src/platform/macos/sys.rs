@@ -217,6 +217,9 @@ ioctl_write_ptr!(siocsifaddr_in6, b'i', 12, in6_ifreq);
 ioctl_readwrite!(siocgifaddr_in6, b'i', 33, in6_ifreq);
 ioctl_write_ptr!(siocdifaddr_in6, b'i', 25, in6_ifreq);
 
+ioctl_readwrite!(siocautoconf_start, b'i', 132, in6_ifreq);
+ioctl_readwrite!(siocautoconf_stop, b'i', 133, in6_ifreq);
+
 ioctl_readwrite!(siocifcreate, b'i', 120, ifreq);
 
 ioctl_write_ptr!(siocsiflladdr, b'i', 60, ifreq);
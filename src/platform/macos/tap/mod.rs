@@ -35,6 +35,7 @@ link https://www.zerotier.com/blog/how-zerotier-eliminated-kernel-extensions-on-
 use crate::builder::DeviceConfig;
 use crate::platform::macos::sys::siocifcreate;
 use crate::platform::unix::Fd;
+use crate::TruncatePolicy;
 use bytes::buf::UninitSlice;
 use bytes::BytesMut;
 use libc::{ifreq, IFNAMSIZ};
@@ -43,12 +44,36 @@ use std::collections::VecDeque;
 use std::ffi::{CStr, CString};
 use std::io;
 use std::io::{IoSlice, IoSliceMut};
+use std::mem::MaybeUninit;
 use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 
 const FETH: &str = "feth";
 const BUFFER_LEN: usize = 131072;
 const BPF_HDR_SIZE: usize = std::mem::size_of::<libc::bpf_hdr>();
+/// How long to wait before retrying a send that failed with `ENOBUFS`. Unlike a full socket
+/// buffer, a full interface queue raises no event when it drains.
+pub(crate) const ENOBUFS_BACKOFF: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// The error of a send that found the interface queue full, of kind `WouldBlock`.
+#[derive(Debug)]
+struct NoBufferSpace;
+impl std::fmt::Display for NoBufferSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no buffer space available (ENOBUFS)")
+    }
+}
+impl std::error::Error for NoBufferSpace {}
+
+/// Whether `e` is a send failing with `ENOBUFS`, which readiness does not announce the end of.
+#[cfg(any(
+    feature = "interruptible",
+    feature = "async_tokio",
+    feature = "async_io"
+))]
+pub(crate) fn is_no_buffer_space(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<NoBufferSpace>())
+}
 
 #[inline]
 fn next_bpf_step(bh_hdrlen: usize, bh_caplen: usize) -> Option<usize> {
@@ -76,6 +101,11 @@ pub struct Tap {
     peer_feth: Feth,
     dev_feth: Feth,
     buffer: Mutex<VecDeque<BytesMut>>,
+    /// The BPF read buffer, only locked with `buffer` held.
+    bpf_buffer: Mutex<Box<[u8]>>,
+    truncate_policy: TruncatePolicy,
+    /// NDRV socket on the virtual interface itself, opened by the first `send_as_outgoing`.
+    s_outgoing_fd: OnceLock<Fd>,
 }
 struct Feth {
     is_drop: bool,
@@ -91,20 +121,39 @@ impl Drop for Feth {
 }
 impl IntoRawFd for Tap {
     fn into_raw_fd(mut self) -> RawFd {
-        self.peer_feth.is_drop = false;
-        self.dev_feth.is_drop = false;
-        self.s_bpf_fd.into_raw_fd()
+        self.take_raw_fd()
     }
 }
 impl Tap {
+    /// Gives up the fd, keeping the `feth` pair.
+    pub(crate) fn take_raw_fd(&mut self) -> RawFd {
+        self.set_destroy(false);
+        let fd = self.s_bpf_fd.inner;
+        self.s_bpf_fd.inner = -1;
+        fd
+    }
+    /// Whether the `feth` pair is destroyed on drop.
+    pub(crate) fn set_destroy(&mut self, destroy: bool) {
+        self.peer_feth.is_drop = destroy;
+        self.dev_feth.is_drop = destroy;
+    }
     pub fn new(config: &DeviceConfig) -> io::Result<Tap> {
         unsafe {
             let s_ndrv_fd = libc::socket(libc::AF_NDRV, libc::SOCK_RAW, 0);
             let s_ndrv_fd = Fd::new(s_ndrv_fd)?;
             _ = s_ndrv_fd.set_cloexec();
+            let attach_existing = config.attach_existing.unwrap_or(false);
+            let reuse_dev = config.reuse_dev.unwrap_or(true) || attach_existing;
+            let persist = config.persist.unwrap_or(false) || attach_existing;
             let mut ifr = new_ifreq(config.dev_name.as_ref())?;
+            if attach_existing && libc::if_nametoindex(ifr.ifr_name.as_ptr()) == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the feth device to attach to does not exist",
+                ));
+            }
             if let Err(e) = siocifcreate(s_ndrv_fd.inner, &mut ifr) {
-                if e != Errno::EEXIST || !config.reuse_dev.unwrap_or(true) {
+                if e != Errno::EEXIST || !reuse_dev {
                     return Err(e.into());
                 }
             }
@@ -113,13 +162,31 @@ impl Tap {
                 .to_string_lossy()
                 .into_owned();
             let dev_feth = Feth {
-                is_drop: !config.persist.unwrap_or(false),
+                is_drop: !persist,
                 name: dev_name,
             };
             std::thread::sleep(std::time::Duration::from_millis(1));
-            let mut peer_ifr = new_ifreq(config.peer_feth.as_ref())?;
+            let mut peer_ifr = match &config.peer_feth {
+                // Without a name, attach to the interface the device is already peered with.
+                None if attach_existing => match feth_peer(&dev_feth.name)? {
+                    Some(peer) => new_ifreq_str(&peer)?,
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "the feth device to attach to has no peer",
+                        ))
+                    }
+                },
+                peer => new_ifreq(peer.as_ref())?,
+            };
+            if attach_existing && libc::if_nametoindex(peer_ifr.ifr_name.as_ptr()) == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the peer feth device to attach to does not exist",
+                ));
+            }
             if let Err(e) = siocifcreate(s_ndrv_fd.inner, &mut peer_ifr) {
-                if e != Errno::EEXIST || !config.reuse_dev.unwrap_or(true) {
+                if e != Errno::EEXIST || !reuse_dev {
                     return Err(e.into());
                 }
             }
@@ -127,55 +194,56 @@ impl Tap {
                 .to_string_lossy()
                 .into_owned();
             let peer_feth = Feth {
-                is_drop: !config.persist.unwrap_or(false),
+                is_drop: !persist,
                 name: peer_name,
             };
             std::thread::sleep(std::time::Duration::from_millis(1));
-            run_command("ifconfig", &[&peer_feth.name, "peer", &dev_feth.name])?;
-            let mut nd: libc::sockaddr_ndrv = std::mem::zeroed();
-            nd.snd_len = size_of::<libc::sockaddr_ndrv>() as u8;
-            nd.snd_family = libc::AF_NDRV as u8;
-            // Ensure the name fits within snd_name to prevent buffer overflow
-            let name_bytes = peer_feth.name.as_bytes();
-            if name_bytes.len() > nd.snd_name.len() {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!(
-                        "Interface name '{}' is too long (max {} bytes)",
-                        peer_feth.name,
-                        nd.snd_name.len()
-                    ),
-                ));
-            }
-            nd.snd_name[..name_bytes.len()].copy_from_slice(name_bytes);
-            if libc::bind(
-                s_ndrv_fd.inner,
-                &nd as *const _ as *const libc::sockaddr,
-                size_of::<libc::sockaddr_ndrv>() as u32,
-            ) != 0
-            {
-                return Err(io::Error::last_os_error());
+            // An adopted pair keeps its peering, which `ifconfig peer` would tear down first.
+            if !attach_existing || feth_peer(&peer_feth.name)?.as_ref() != Some(&dev_feth.name) {
+                run_command("ifconfig", &[&peer_feth.name, "peer", &dev_feth.name])?;
             }
-            if libc::connect(
-                s_ndrv_fd.inner,
-                &nd as *const _ as *const libc::sockaddr,
-                size_of::<libc::sockaddr_ndrv>() as u32,
-            ) != 0
-            {
-                return Err(io::Error::last_os_error());
+            bind_ndrv(&s_ndrv_fd, &peer_feth.name)?;
+            if let Some(size) = config.ndrv_send_buffer_size {
+                let size = libc::c_int::try_from(size).unwrap_or(libc::c_int::MAX);
+                let rs = libc::setsockopt(
+                    s_ndrv_fd.inner,
+                    libc::SOL_SOCKET,
+                    libc::SO_SNDBUF,
+                    &size as *const _ as *const libc::c_void,
+                    size_of::<libc::c_int>() as libc::socklen_t,
+                );
+                if rs != 0 {
+                    return Err(io::Error::last_os_error());
+                }
             }
             let s_bpf_fd = open_bpf()?;
-            let mut buffer_len = BUFFER_LEN;
+            let mut buffer_len = config.bpf_buffer_size.unwrap_or(BUFFER_LEN) as libc::c_uint;
             let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCSBLEN, &mut buffer_len);
             if rs != 0 {
                 return Err(io::Error::last_os_error());
             }
+            // The kernel clamps the size, and reads must use exactly the size it settled on.
+            let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCGBLEN, &mut buffer_len);
+            if rs != 0 {
+                return Err(io::Error::last_os_error());
+            }
             let mut enable = 1i32;
             let mut disable = 0i32;
-            let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCIMMEDIATE, &mut enable);
+            let mut immediate = config.bpf_immediate.unwrap_or(true) as i32;
+            let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCIMMEDIATE, &mut immediate);
             if rs != 0 {
                 return Err(io::Error::last_os_error());
             }
+            if let Some(timeout) = config.bpf_read_timeout {
+                let mut tv = libc::timeval {
+                    tv_sec: timeout.as_secs() as _,
+                    tv_usec: timeout.subsec_micros() as _,
+                };
+                let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCSRTIMEOUT, &mut tv);
+                if rs != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
             let rs = libc::ioctl(s_bpf_fd.inner, libc::BIOCSSEESENT, &mut disable);
             if rs != 0 {
                 return Err(io::Error::last_os_error());
@@ -199,12 +267,16 @@ impl Tap {
                 dev_feth,
                 peer_feth,
                 buffer: Default::default(),
+                bpf_buffer: Mutex::new(vec![0; buffer_len as usize].into_boxed_slice()),
+                truncate_policy: config.truncate_policy.unwrap_or_default(),
+                s_outgoing_fd: OnceLock::new(),
             })
         }
     }
-    // pub fn as_s_ndrv_fd(&self) -> RawFd {
-    //     self.s_ndrv_fd.as_raw_fd()
-    // }
+    /// The NDRV socket packets are written to.
+    pub(crate) fn ndrv_fd(&self) -> RawFd {
+        self.s_ndrv_fd.as_raw_fd()
+    }
     // pub fn as_s_bpf_fd(&self) -> RawFd {
     //     self.s_bpf_fd.as_raw_fd()
     // }
@@ -222,15 +294,53 @@ impl Tap {
         self.s_ndrv_fd.set_nonblocking(nonblocking)?;
         Ok(())
     }
+    /// Writes a frame to the NDRV socket.
+    ///
+    /// When the interface queue is full the socket fails with `ENOBUFS` even though it polls
+    /// writable. A blocking device retries after [`ENOBUFS_BACKOFF`], a non-blocking one
+    /// reports it as `WouldBlock`, see [`is_no_buffer_space`].
     #[inline]
     pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.s_ndrv_fd.write(buf)
+        self.send_ndrv(|fd| fd.write(buf))
     }
     #[inline]
     pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.s_ndrv_fd.writev(bufs)
+        self.send_ndrv(|fd| fd.writev(bufs))
+    }
+    fn send_ndrv(&self, f: impl Fn(&Fd) -> io::Result<usize>) -> io::Result<usize> {
+        loop {
+            match f(&self.s_ndrv_fd) {
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    if self.s_ndrv_fd.is_nonblocking()? {
+                        return Err(io::Error::new(io::ErrorKind::WouldBlock, NoBufferSpace));
+                    }
+                    std::thread::sleep(ENOBUFS_BACKOFF);
+                }
+                rs => return rs,
+            }
+        }
+    }
+    /// Transmits `buf` from the virtual interface towards its peer, where the BPF reader
+    /// picks it up as if the host had sent it.
+    pub fn send_as_outgoing(&self, buf: &[u8]) -> io::Result<usize> {
+        let fd = match self.s_outgoing_fd.get() {
+            Some(fd) => fd,
+            None => {
+                let fd = Fd::new(unsafe { libc::socket(libc::AF_NDRV, libc::SOCK_RAW, 0) })?;
+                _ = fd.set_cloexec();
+                bind_ndrv(&fd, &self.dev_feth.name)?;
+                // A concurrent caller may have won the race, its socket is just as good.
+                _ = self.s_outgoing_fd.set(fd);
+                self.s_outgoing_fd.get().unwrap()
+            }
+        };
+        fd.write(buf)
     }
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_with_len(buf).map(|(len, _)| len)
+    }
+    /// Receives a packet, returning the number of bytes copied and the length of the packet.
+    pub fn recv_with_len(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         let mut guard = self.buffer.lock().unwrap();
         if guard.is_empty() {
             self.recv_to_buffer(&mut guard)?;
@@ -242,14 +352,14 @@ impl Tap {
                 "recv buffer is empty",
             ));
         };
-        if buf.len() < buffer.len() {
+        let Some(copy_len) = self.truncate_policy.apply(buffer.len(), buf.len()) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "buffer too small",
             ));
-        }
-        buf[..buffer.len()].copy_from_slice(&buffer);
-        Ok(buffer.len())
+        };
+        buf[..copy_len].copy_from_slice(&buffer[..copy_len]);
+        Ok((copy_len, buffer.len()))
     }
     pub fn recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
         let mut guard = self.buffer.lock().unwrap();
@@ -263,19 +373,36 @@ impl Tap {
                 "recv buffer is empty",
             ));
         };
-        if buf.len() < buffer.len() {
+        let Some(copy_len) = self.truncate_policy.apply(buffer.len(), buf.len()) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "buffer too small",
             ));
-        }
+        };
         unsafe {
-            std::ptr::copy_nonoverlapping(buffer.as_ptr(), buf.as_mut_ptr(), buffer.len());
+            std::ptr::copy_nonoverlapping(buffer.as_ptr(), buf.as_mut_ptr(), copy_len);
+        }
+        Ok(copy_len)
+    }
+    /// Discards the buffered packets and those queued on the BPF fd, returning how many.
+    pub fn drain(&self) -> io::Result<usize> {
+        let mut guard = self.buffer.lock().unwrap();
+        let mut count = guard.len();
+        guard.clear();
+        while self.s_bpf_fd.is_readable()? {
+            match self.recv_to_buffer(&mut guard) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+            count += guard.len();
+            guard.clear();
         }
-        Ok(buffer.len())
+        Ok(count)
     }
     fn recv_to_buffer(&self, bufs: &mut VecDeque<BytesMut>) -> io::Result<()> {
-        let mut buffer = [0; BUFFER_LEN];
+        let mut buffer = self.bpf_buffer.lock().unwrap();
         let len = self.s_bpf_fd.read(&mut buffer)?;
         if len > 0 {
             let mut p = 0;
@@ -303,53 +430,49 @@ impl Tap {
         }
         Ok(())
     }
-    #[allow(dead_code)]
+    /// Receives the packets of one BPF read, `bufs[i][offset..]` holding packet `i` and
+    /// `sizes[i]` its length.
+    ///
+    /// Packets left over from an earlier read are returned first, and the fd is only read
+    /// when none are left. A packet that does not fit is returned by the next call.
     pub fn recv_multiple<B: AsRef<[u8]> + AsMut<[u8]>>(
         &self,
         bufs: &mut [B],
         sizes: &mut [usize],
+        offset: usize,
     ) -> io::Result<usize> {
-        if bufs.is_empty() || sizes.len() < bufs.len() {
-            return Err(io::Error::other("sizes must be at least as long as bufs"));
+        if bufs.is_empty() || bufs.len() != sizes.len() {
+            return Err(io::Error::other("bufs error"));
+        }
+        let mut guard = self.buffer.lock().unwrap();
+        if guard.is_empty() {
+            self.recv_to_buffer(&mut guard)?;
         }
-        let mut buffer = [0; BUFFER_LEN];
-        let len = self.s_bpf_fd.read(&mut buffer)?;
         let mut num = 0;
-        if len > 0 {
-            let mut p = 0;
-            while p < len {
-                let remaining_bytes = len - p;
-                if remaining_bytes < BPF_HDR_SIZE {
+        for (dst, size) in bufs.iter_mut().zip(sizes.iter_mut()) {
+            let Some(packet) = guard.front() else {
+                break;
+            };
+            let Some(dst) = dst.as_mut().get_mut(offset..) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid offset",
+                ));
+            };
+            let Some(copy_len) = self.truncate_policy.apply(packet.len(), dst.len()) else {
+                if num > 0 {
                     break;
                 }
-                // SAFETY: We use read_unaligned to avoid UB from misaligned access.
-                let hdr: libc::bpf_hdr = unsafe {
-                    std::ptr::read_unaligned(buffer.as_ptr().add(p) as *const libc::bpf_hdr)
-                };
-                let bh_caplen = hdr.bh_caplen as usize;
-                let bh_hdrlen = hdr.bh_hdrlen as usize;
-                if bh_caplen > 0 && p + bh_hdrlen + bh_caplen <= len {
-                    let buf = &buffer[p + bh_hdrlen..p + bh_hdrlen + bh_caplen];
-                    if let Some(dst) = bufs.get_mut(num) {
-                        let dst = dst.as_mut();
-                        if dst.len() < buf.len() {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "buffer too small",
-                            ));
-                        }
-                        dst[..buf.len()].copy_from_slice(buf);
-                        sizes[num] = buf.len();
-                        num += 1;
-                    } else {
-                        break;
-                    }
-                }
-                let Some(step) = next_bpf_step(bh_hdrlen, bh_caplen) else {
-                    break;
-                };
-                p += step;
-            }
+                guard.pop_front();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "buffer too small",
+                ));
+            };
+            dst[..copy_len].copy_from_slice(&packet[..copy_len]);
+            *size = copy_len;
+            guard.pop_front();
+            num += 1;
         }
         Ok(num)
     }
@@ -365,26 +488,59 @@ impl Tap {
                 "recv buffer is empty",
             ));
         };
-        let len: usize = bufs.iter().map(|v| v.len()).sum();
-        if len < buf.len() {
+        let total: usize = bufs.iter().map(|v| v.len()).sum();
+        let Some(copy_len) = self.truncate_policy.apply(buf.len(), total) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "buffer too small",
             ));
-        }
+        };
         let mut pos = 0;
         for b in bufs {
-            let n = b.len().min(buf.len() - pos);
+            let n = b.len().min(copy_len - pos);
             if n == 0 {
                 break;
             }
             b[..n].copy_from_slice(&buf[pos..pos + n]);
             pos += n;
-            if pos == buf.len() {
+            if pos == copy_len {
                 break;
             }
         }
-        Ok(pos)
+        Ok(copy_len)
+    }
+    pub fn recv_vectored_uninit(&self, bufs: &mut [&mut [MaybeUninit<u8>]]) -> io::Result<usize> {
+        let mut guard = self.buffer.lock().unwrap();
+        if guard.is_empty() {
+            self.recv_to_buffer(&mut guard)?;
+        }
+
+        let Some(buf) = guard.pop_front() else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "recv buffer is empty",
+            ));
+        };
+        let total: usize = bufs.iter().map(|v| v.len()).sum();
+        let Some(copy_len) = self.truncate_policy.apply(buf.len(), total) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "buffer too small",
+            ));
+        };
+        let mut pos = 0;
+        for b in bufs {
+            let n = b.len().min(copy_len - pos);
+            if n == 0 {
+                break;
+            }
+            UninitSlice::uninit(&mut b[..n]).copy_from_slice(&buf[pos..pos + n]);
+            pos += n;
+            if pos == copy_len {
+                break;
+            }
+        }
+        Ok(copy_len)
     }
     #[cfg(feature = "interruptible")]
     #[inline]
@@ -436,7 +592,11 @@ impl Tap {
     ) -> io::Result<usize> {
         loop {
             self.wait_writable_interruptible(event)?;
-            return match self.s_ndrv_fd.write(buf) {
+            return match self.send(buf) {
+                Err(ref e) if is_no_buffer_space(e) => {
+                    std::thread::sleep(ENOBUFS_BACKOFF);
+                    continue;
+                }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                     continue;
                 }
@@ -453,7 +613,11 @@ impl Tap {
     ) -> io::Result<usize> {
         loop {
             self.wait_writable_interruptible(event)?;
-            return match self.s_ndrv_fd.writev(bufs) {
+            return match self.send_vectored(bufs) {
+                Err(ref e) if is_no_buffer_space(e) => {
+                    std::thread::sleep(ENOBUFS_BACKOFF);
+                    continue;
+                }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                     continue;
                 }
@@ -499,6 +663,58 @@ fn open_bpf() -> io::Result<Fd> {
         "No available /dev/bpf",
     ))
 }
+/// Returns the interface the `feth` interface `name` is peered with, from the `peer:` line
+/// `ifconfig` prints for it.
+fn feth_peer(name: &str) -> io::Result<Option<String>> {
+    let out = std::process::Command::new("ifconfig").arg(name).output()?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(io::Error::other(format!("ifconfig failed with: \"{err}\"")));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("peer:"))
+        .map(|peer| peer.trim().to_string()))
+}
+/// Binds and connects an NDRV socket to the interface `name`, so that writes to it are
+/// transmitted by that interface.
+fn bind_ndrv(fd: &Fd, name: &str) -> io::Result<()> {
+    unsafe {
+        let mut nd: libc::sockaddr_ndrv = std::mem::zeroed();
+        nd.snd_len = size_of::<libc::sockaddr_ndrv>() as u8;
+        nd.snd_family = libc::AF_NDRV as u8;
+        // Ensure the name fits within snd_name to prevent buffer overflow
+        let name_bytes = name.as_bytes();
+        if name_bytes.len() > nd.snd_name.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Interface name '{}' is too long (max {} bytes)",
+                    name,
+                    nd.snd_name.len()
+                ),
+            ));
+        }
+        nd.snd_name[..name_bytes.len()].copy_from_slice(name_bytes);
+        if libc::bind(
+            fd.inner,
+            &nd as *const _ as *const libc::sockaddr,
+            size_of::<libc::sockaddr_ndrv>() as u32,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::connect(
+            fd.inner,
+            &nd as *const _ as *const libc::sockaddr,
+            size_of::<libc::sockaddr_ndrv>() as u32,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
 fn new_ifreq(name: Option<&String>) -> io::Result<ifreq> {
     if let Some(name) = name {
         new_ifreq_str(name.as_str())
@@ -509,7 +725,7 @@ fn new_ifreq(name: Option<&String>) -> io::Result<ifreq> {
 fn new_ifreq_str(name: &str) -> io::Result<ifreq> {
     let bytes = name.as_bytes();
     if bytes.len() >= IFNAMSIZ {
-        return Err(io::Error::new(io::ErrorKind::InvalidInput, "name too long"));
+        return Err(crate::Error::NameTooLong.into());
     }
     if bytes.len() < 4 || &bytes[..4] != FETH.as_bytes() {
         return Err(io::Error::new(
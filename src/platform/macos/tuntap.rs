@@ -15,6 +15,7 @@ use libc::{
 };
 use std::ffi::{c_void, CStr};
 use std::io::{ErrorKind, IoSlice, IoSliceMut};
+use std::mem::MaybeUninit;
 use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
 use std::{io, mem, ptr};
 
@@ -29,16 +30,17 @@ impl TunTap {
         let packet_information = config.packet_information.unwrap_or(false);
         match layer {
             Layer::L2 => Ok(TunTap::Tap(Tap::new(&config)?)),
+            Layer::L3 if config.attach_existing.unwrap_or(false) => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "a utun cannot be re-opened by name, pass the device fd instead",
+            )),
             Layer::L3 => {
                 let id = config
                     .dev_name
                     .as_ref()
                     .map(|tun_name| {
                         if tun_name.len() > IFNAMSIZ {
-                            return Err(io::Error::new(
-                                ErrorKind::InvalidInput,
-                                "device name too long",
-                            ));
+                            return Err(crate::Error::NameTooLong.into());
                         }
                         if !tun_name.starts_with("utun") {
                             return Err(io::Error::new(
@@ -46,6 +48,10 @@ impl TunTap {
                                 "device name must start with utun",
                             ));
                         }
+                        if tun_name == "utun" {
+                            // Explicitly request the next free unit.
+                            return Ok(0);
+                        }
                         tun_name[4..]
                             .parse::<u32>()
                             .map(|v| v + 1)
@@ -85,7 +91,11 @@ impl TunTap {
 
                     let address = &addr as *const libc::sockaddr_ctl as *const sockaddr;
                     if libc::connect(tun.inner, address, mem::size_of_val(&addr) as socklen_t) < 0 {
-                        return Err(io::Error::last_os_error());
+                        let err = io::Error::last_os_error();
+                        if id != 0 && err.raw_os_error() == Some(libc::EBUSY) {
+                            return Err(crate::Error::AlreadyInUse { unit: id - 1 }.into());
+                        }
+                        return Err(err);
                     }
 
                     let mut tun_name = [0u8; 64];
@@ -137,6 +147,13 @@ impl TunTap {
             TunTap::Tap(tap) => Ok(tap.name().to_string()),
         }
     }
+    /// The fd packets are written to: the `utun` socket, or the NDRV socket of a `feth` pair.
+    pub(crate) fn write_fd(&self) -> RawFd {
+        match &self {
+            TunTap::Tun(tun) => tun.as_raw_fd(),
+            TunTap::Tap(tap) => tap.ndrv_fd(),
+        }
+    }
     pub(crate) fn is_tun(&self) -> bool {
         match &self {
             TunTap::Tun(_) => true,
@@ -169,6 +186,12 @@ impl TunTap {
             TunTap::Tap(tap) => tap.send_vectored(bufs),
         }
     }
+    pub fn drain(&self) -> io::Result<usize> {
+        match &self {
+            TunTap::Tun(tun) => tun.drain(),
+            TunTap::Tap(tap) => tap.drain(),
+        }
+    }
     #[inline]
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
         match &self {
@@ -176,6 +199,14 @@ impl TunTap {
             TunTap::Tap(tap) => tap.recv(buf),
         }
     }
+    /// Receives a packet, returning the number of bytes copied and the length of the packet.
+    #[inline]
+    pub fn recv_with_len(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        match &self {
+            TunTap::Tun(tun) => tun.recv(buf).map(|len| (len, len)),
+            TunTap::Tap(tap) => tap.recv_with_len(buf),
+        }
+    }
     #[inline]
     pub fn recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
         match &self {
@@ -190,6 +221,36 @@ impl TunTap {
             TunTap::Tap(tap) => tap.recv_vectored(bufs),
         }
     }
+    #[inline]
+    pub fn recv_vectored_uninit(&self, bufs: &mut [&mut [MaybeUninit<u8>]]) -> io::Result<usize> {
+        match &self {
+            TunTap::Tun(tun) => tun.recv_vectored_uninit(bufs),
+            TunTap::Tap(tap) => tap.recv_vectored_uninit(bufs),
+        }
+    }
+    pub fn recv_multiple<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        offset: usize,
+    ) -> io::Result<usize> {
+        match &self {
+            TunTap::Tun(tun) => {
+                if bufs.is_empty() || bufs.len() != sizes.len() {
+                    return Err(io::Error::other("bufs error"));
+                }
+                let Some(buf) = bufs[0].as_mut().get_mut(offset..) else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid offset",
+                    ));
+                };
+                sizes[0] = tun.recv(buf)?;
+                Ok(1)
+            }
+            TunTap::Tap(tap) => tap.recv_multiple(bufs, sizes, offset),
+        }
+    }
     #[cfg(feature = "interruptible")]
     #[inline]
     pub(crate) fn read_interruptible(
@@ -382,10 +443,20 @@ impl AsRawFd for TunTap {
     }
 }
 impl IntoRawFd for TunTap {
-    fn into_raw_fd(self) -> RawFd {
+    fn into_raw_fd(mut self) -> RawFd {
+        self.take_raw_fd()
+    }
+}
+impl TunTap {
+    /// Gives up the fd, keeping the device.
+    pub(crate) fn take_raw_fd(&mut self) -> RawFd {
         match self {
-            TunTap::Tun(tun) => tun.into_raw_fd(),
-            TunTap::Tap(tap) => tap.into_raw_fd(),
+            TunTap::Tun(tun) => {
+                let fd = tun.fd.inner;
+                tun.fd.inner = -1;
+                fd
+            }
+            TunTap::Tap(tap) => tap.take_raw_fd(),
         }
     }
 }
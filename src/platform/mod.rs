@@ -16,6 +16,8 @@ pub use self::unix::DeviceImpl;
 #[cfg(unix)]
 #[cfg(feature = "interruptible")]
 pub use unix::InterruptEvent;
+#[cfg(unix)]
+pub use unix::{recv_fd, send_fd};
 #[cfg(windows)]
 #[cfg(feature = "interruptible")]
 pub use windows::InterruptEvent;
@@ -32,7 +34,7 @@ pub use self::freebsd::DeviceImpl;
 #[cfg(target_os = "macos")]
 pub(crate) mod macos;
 #[cfg(target_os = "macos")]
-pub use self::macos::DeviceImpl;
+pub use self::macos::{utun_units, DeviceImpl};
 #[cfg(target_os = "openbsd")]
 pub(crate) mod openbsd;
 #[cfg(target_os = "openbsd")]
@@ -45,22 +47,852 @@ pub use self::netbsd::DeviceImpl;
 
 #[cfg(target_os = "windows")]
 pub(crate) mod windows;
+#[cfg(all(target_os = "windows", feature = "winfw"))]
+pub use self::windows::{add_firewall_rule_allow_all, FirewallRule};
 #[cfg(target_os = "windows")]
-pub use self::windows::DeviceImpl;
+pub use self::windows::{DeviceImpl, DriverVersion, NetworkCategory, SessionRestart, WintunTrust};
 
 #[cfg(target_vendor = "apple")]
 pub mod apple;
 
+mod bind;
+pub use bind::bind_to_interface;
+#[cfg(feature = "icmp_responder")]
+mod icmp_responder;
+#[cfg(feature = "icmp_responder")]
+pub(crate) use icmp_responder::IcmpResponder;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod capabilities;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub use capabilities::Capabilities;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod route;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub use route::{find_route, Route, RouteGuard};
+#[cfg(all(
+    feature = "ndp_proxy",
+    any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos"
+    )
+))]
+mod ndp_proxy;
+#[cfg(all(
+    feature = "ndp_proxy",
+    any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos"
+    )
+))]
+pub use ndp_proxy::NdpProxy;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod cleanup;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub(crate) use cleanup::Cleanup;
+#[cfg(any(
+    target_os = "windows",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+pub use cleanup::CleanupPolicy;
+mod rate_limit;
+pub use rate_limit::RateLimit;
+pub(crate) use rate_limit::RateLimiter;
+
 use getifaddrs::Interface;
-#[cfg(unix)]
+#[cfg(any(unix, target_os = "windows"))]
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::Deref;
 #[cfg(unix)]
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
 
+/// Copies the first `len` bytes of `bufs` into one buffer.
+fn gather<'a>(bufs: impl Iterator<Item = &'a [u8]>, len: usize) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(len);
+    for buf in bufs {
+        let n = buf.len().min(len - packet.len());
+        packet.extend_from_slice(&buf[..n]);
+    }
+    packet
+}
+
+/// Copies `packet` into `bufs` in order, as far as they reach.
+pub(crate) fn scatter(packet: &[u8], bufs: &mut [IoSliceMut<'_>]) {
+    let mut rest = packet;
+    for buf in bufs {
+        if rest.is_empty() {
+            break;
+        }
+        let len = buf.len().min(rest.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        rest = &rest[len..];
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) const ETHER_ADDR_LEN: u8 = 6;
 
+/// The kernel driver (or OS facility) backing a device.
+///
+/// Several platforms offer more than one way to create a virtual interface, e.g. Wintun vs
+/// tap-windows on Windows, or `utun` vs a `feth` pair on macOS. The active backend can be
+/// queried at runtime with `backend_kind()`, which is useful for telemetry and for choosing
+/// feature paths that are only efficient on a particular backend.
+///
+/// # Example
+///
+/// ```no_run
+/// use tun_rs::{BackendKind, DeviceBuilder};
+///
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .build_sync()?;
+/// if dev.backend_kind() == BackendKind::LinuxTun {
+///     // e.g. enable batch APIs
+/// }
+/// println!("backend: {}", dev.backend_kind());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum BackendKind {
+    /// The Linux TUN/TAP driver (`/dev/net/tun`), used for both L2 and L3.
+    LinuxTun,
+    /// The Wintun driver on Windows (L3).
+    Wintun,
+    /// The tap-windows6 driver on Windows (L2).
+    TapWindows,
+    /// The `utun` kernel control interface on macOS (L3).
+    Utun,
+    /// A pair of `feth` interfaces driven via BPF/NDRV on macOS (L2).
+    Feth,
+    /// The `tun(4)` driver on FreeBSD, OpenBSD or NetBSD (L3).
+    BsdTun,
+    /// The `tap(4)` driver on FreeBSD, OpenBSD or NetBSD (L2).
+    BsdTap,
+    /// A file descriptor handed over by the system VPN framework
+    /// (Android `VpnService`, iOS/tvOS `NEPacketTunnelProvider`, OpenHarmony).
+    Fd,
+}
+
+impl BackendKind {
+    /// Returns a short, stable identifier for the backend, suitable for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackendKind::LinuxTun => "linux-tun",
+            BackendKind::Wintun => "wintun",
+            BackendKind::TapWindows => "tap-windows",
+            BackendKind::Utun => "utun",
+            BackendKind::Feth => "feth",
+            BackendKind::BsdTun => "bsd-tun",
+            BackendKind::BsdTap => "bsd-tap",
+            BackendKind::Fd => "fd",
+        }
+    }
+}
+
+impl BackendKind {
+    /// Returns the `(min, max)` MTU accepted by the backend, in bytes.
+    ///
+    /// The builder checks the requested MTU against these limits and fails with
+    /// [`Error::MtuOutOfRange`](crate::Error::MtuOutOfRange) before creating the device.
+    /// No backend goes beyond 65535, the largest IP packet without jumbograms, so MTUs are
+    /// `u16` throughout the crate.
+    pub fn mtu_limits(&self) -> (u16, u16) {
+        match self {
+            // ETH_MIN_MTU and the u16 limit of the tun driver.
+            BackendKind::LinuxTun => (68, 65535),
+            // The smallest MTU of a Windows IPv4 interface, WINTUN_MAX_IP_PACKET_SIZE.
+            BackendKind::Wintun => (576, 65535),
+            // The range of the MTU parameter in the tap-windows6 INF.
+            BackendKind::TapWindows => (100, 1500),
+            // IF_MINMTU / IF_MAXMTU
+            BackendKind::Utun | BackendKind::Feth => (72, 65535),
+            // TUNMRU is 16384 on OpenBSD.
+            #[cfg(target_os = "openbsd")]
+            BackendKind::BsdTun | BackendKind::BsdTap => (68, 16384),
+            #[cfg(not(target_os = "openbsd"))]
+            BackendKind::BsdTun | BackendKind::BsdTap => (72, 65535),
+            BackendKind::Fd => (68, 65535),
+        }
+    }
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The operational state of an interface, i.e. whether it can pass packets, as opposed to
+/// its administrative state set with `enabled`.
+///
+/// Returned by `oper_state`. The states follow RFC 2863; states a platform does not report
+/// separately map to the closest of these.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum OperState {
+    /// The interface is up and has a carrier.
+    Up,
+    /// The interface is administratively down.
+    Down,
+    /// The interface is up but has no carrier, e.g. no process has the device open, or the
+    /// carrier was turned off with `set_carrier`.
+    LowerLayerDown,
+    /// The platform does not know the state.
+    Unknown,
+}
+
+/// The packet information header of a received packet, returned by
+/// [`SyncDevice::recv_with_meta`].
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct PacketInformation {
+    /// The packet did not fit into the buffer and was cut (`TUN_PKT_STRIP`).
+    ///
+    /// Only Linux reports this; elsewhere a packet that does not fit is cut without notice.
+    pub truncated: bool,
+    /// The raw flags, `tun_pi.flags` on Linux and always `0` elsewhere.
+    pub flags: u16,
+    /// The protocol of the packet: the EtherType (`0x0800`, `0x86dd`, ...) on Linux, the
+    /// address family (`AF_INET`, `AF_INET6`) on Apple platforms and the BSDs.
+    pub protocol: u32,
+}
+
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+impl PacketInformation {
+    /// Parses the header as the kernel wrote it.
+    pub(crate) fn parse(head: [u8; crate::PACKET_INFORMATION_LENGTH]) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let pi = crate::framing::TunPi::from_bytes(head);
+            PacketInformation {
+                truncated: pi.flags & crate::framing::TunPi::TUN_PKT_STRIP != 0,
+                flags: pi.flags,
+                protocol: pi.proto as u32,
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        PacketInformation {
+            truncated: false,
+            flags: 0,
+            protocol: crate::framing::AfHeader::from_bytes(head).family,
+        }
+    }
+}
+
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+impl DeviceImpl {
+    /// Receives a packet without its packet information header, which is parsed instead.
+    pub(crate) fn recv_with_info(
+        &self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, PacketInformation)> {
+        #[cfg(target_os = "linux")]
+        let has_header = self.packet_information();
+        // The header is emulated on the TUN devices of the other platforms, which always
+        // receive it from the kernel.
+        #[cfg(not(target_os = "linux"))]
+        let has_header = !matches!(self.backend_kind(), BackendKind::BsdTap | BackendKind::Feth);
+        if !has_header {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the device does not receive a packet information header",
+            ));
+        }
+        let (len, head) = self.tun.recv_with_head(buf)?;
+        Ok((len, PacketInformation::parse(head)))
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+impl DeviceImpl {
+    /// Returns whether packets exchanged with the application carry the 4-byte packet
+    /// information header.
+    ///
+    /// Always `false` for TAP devices.
+    pub fn packet_information(&self) -> bool {
+        #[cfg(target_os = "windows")]
+        {
+            self.emulated_packet_information()
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            !matches!(self.backend_kind(), BackendKind::BsdTap | BackendKind::Feth)
+                && !self.ignore_packet_info()
+        }
+    }
+    /// Changes whether packets exchanged with the application carry the packet information
+    /// header, taking effect for the next `send`/`recv`.
+    ///
+    /// On macOS, the BSDs and Windows the header is emulated by the library, so it can be
+    /// toggled on a live TUN device. Requesting it on a TAP device returns
+    /// [`io::ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported).
+    pub fn set_packet_information(&self, value: bool) -> std::io::Result<()> {
+        if value == self.packet_information() {
+            return Ok(());
+        }
+        #[cfg(target_os = "windows")]
+        if self.set_emulated_packet_information(value) {
+            return Ok(());
+        }
+        #[cfg(not(target_os = "windows"))]
+        if !matches!(self.backend_kind(), BackendKind::BsdTap | BackendKind::Feth) {
+            self.set_ignore_packet_info(!value);
+            return Ok(());
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "packet information is only available on TUN devices",
+        ))
+    }
+}
+
+impl DeviceImpl {
+    /// Sets (or with `None` removes) the egress rate limit of the device.
+    ///
+    /// The limit is enforced in `send` and `send_multiple` of both the synchronous and
+    /// asynchronous devices: packets exceeding the token bucket are delayed, not dropped.
+    /// Clones created with `try_clone` share the same bucket.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, RateLimit};
+    ///
+    /// let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+    /// // 10 Mbit/s with bursts of up to 64 KiB
+    /// dev.set_egress_limit(Some(RateLimit {
+    ///     bps: 10_000_000,
+    ///     burst: 65536,
+    /// }))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn set_egress_limit(&self, limit: Option<RateLimit>) -> std::io::Result<()> {
+        self.egress_limit.set(limit)
+    }
+    /// Returns the current egress rate limit, if any.
+    pub fn egress_limit(&self) -> Option<RateLimit> {
+        self.egress_limit.get()
+    }
+    /// Returns the `(min, max)` MTU supported by the backend of this device.
+    ///
+    /// See [`BackendKind::mtu_limits`].
+    pub fn mtu_limits(&self) -> (u16, u16) {
+        self.backend_kind().mtu_limits()
+    }
+    /// Returns whether the hooks (packet tap, counters, MSS clamp, middleware chain, ICMP
+    /// responder and frame validation) run on single-packet I/O.
+    ///
+    /// They do not on Linux devices built with `offload(true)`, whose packets carry a
+    /// virtio network header there and may be GSO super-packets; the hooks see their
+    /// segments through `recv_multiple` and `send_multiple` instead.
+    #[inline]
+    pub(crate) fn hooks_plain_io(&self) -> bool {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        return !self.vnet_hdr;
+        #[cfg(not(all(target_os = "linux", not(target_env = "ohos"))))]
+        true
+    }
+    /// Passes a packet read from the device to the packet tap, if any, and counts it.
+    #[inline]
+    pub(crate) fn tap_inbound(&self, _packet: &[u8]) {
+        self.count_received(_packet.len());
+        #[cfg(feature = "pcap")]
+        if self.packet_tap.is_set() {
+            self.packet_tap.call(
+                crate::pcap::Direction::Inbound,
+                self.without_packet_information(_packet),
+            );
+        }
+    }
+    /// Like [`tap_inbound`](Self::tap_inbound) for a packet already read without its packet
+    /// information header.
+    #[inline]
+    pub(crate) fn tap_inbound_bare(&self, _packet: &[u8]) {
+        self.count_received(_packet.len());
+        #[cfg(feature = "pcap")]
+        self.packet_tap
+            .call(crate::pcap::Direction::Inbound, _packet);
+    }
+    /// The IP packet in a buffer exchanged with the application, which starts with the packet
+    /// information header if the device has one. The packet tap only sees the IP packet.
+    #[cfg(feature = "pcap")]
+    fn without_packet_information<'a>(&self, packet: &'a [u8]) -> &'a [u8] {
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        if self.packet_information() {
+            return packet
+                .get(crate::PACKET_INFORMATION_LENGTH..)
+                .unwrap_or_default();
+        }
+        packet
+    }
+    /// Like [`tap_outbound`](Self::tap_outbound) for the first `len` bytes of `bufs`.
+    #[inline]
+    pub(crate) fn tap_outbound_vectored(&self, _bufs: &[IoSlice<'_>], _len: usize) {
+        #[cfg(feature = "pcap")]
+        if self.packet_tap.is_set() {
+            let packet = gather(_bufs.iter().map(|buf| &buf[..]), _len);
+            self.packet_tap.call(
+                crate::pcap::Direction::Outbound,
+                self.without_packet_information(&packet),
+            );
+        }
+    }
+    /// Passes a packet written by a `try_` or vectored send to the packet tap and counts it,
+    /// unless the write would have blocked.
+    #[inline]
+    pub(crate) fn finish_send(
+        &self,
+        buf: &[u8],
+        result: std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        match &result {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return result,
+            Ok(_) => self.tap_outbound(buf),
+            Err(_) => {}
+        }
+        self.count_sent(result)
+    }
+    /// Like [`finish_send`](Self::finish_send) for a packet gathered from `bufs`.
+    #[inline]
+    pub(crate) fn finish_send_vectored(
+        &self,
+        bufs: &[IoSlice<'_>],
+        result: std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        match &result {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return result,
+            Ok(len) => self.tap_outbound_vectored(bufs, *len),
+            Err(_) => {}
+        }
+        self.count_sent(result)
+    }
+    /// Counts a packet of `len` bytes read from the device.
+    #[inline]
+    pub(crate) fn count_received(&self, _len: usize) {
+        #[cfg(feature = "counters")]
+        self.counters.received(_len);
+    }
+    /// Counts a received packet that did not fit into the buffer, if `_truncated`.
+    #[inline]
+    pub(crate) fn count_truncated(&self, _truncated: bool) {
+        #[cfg(feature = "counters")]
+        if _truncated {
+            self.counters.truncated();
+        }
+    }
+    /// Counts the result of writing a packet to the device, and returns it.
+    #[inline]
+    pub(crate) fn count_sent(&self, result: std::io::Result<usize>) -> std::io::Result<usize> {
+        #[cfg(feature = "counters")]
+        self.counters.sent(&result);
+        result
+    }
+    /// Passes a packet written to the device to the packet tap, if any.
+    #[inline]
+    pub(crate) fn tap_outbound(&self, _packet: &[u8]) {
+        #[cfg(feature = "pcap")]
+        if self.packet_tap.is_set() {
+            self.packet_tap.call(
+                crate::pcap::Direction::Outbound,
+                self.without_packet_information(_packet),
+            );
+        }
+    }
+    /// Runs the MSS clamp and the middleware chain, if any, over a packet of `len` bytes read
+    /// into `buf`.
+    ///
+    /// Returns the length of the packet to hand to the caller, or `None` if it was dropped.
+    #[inline]
+    pub(crate) fn middleware_inbound(&self, _buf: &mut [u8], len: usize) -> Option<usize> {
+        #[cfg(feature = "mss_clamp")]
+        self.mss_clamp_inbound(_buf, len);
+        #[cfg(feature = "middleware")]
+        return self.middleware.inbound(_buf, len);
+        #[cfg(not(feature = "middleware"))]
+        Some(len)
+    }
+    /// Runs the middleware chain and the MSS clamp, if any, over a packet about to be written.
+    ///
+    /// Returns the packet to write, or `None` if it was dropped.
+    #[inline]
+    pub(crate) fn middleware_outbound<'a>(
+        &self,
+        buf: &'a [u8],
+    ) -> Option<std::borrow::Cow<'a, [u8]>> {
+        #[cfg(feature = "middleware")]
+        let packet = self.middleware.outbound(buf)?;
+        #[cfg(not(feature = "middleware"))]
+        let packet = std::borrow::Cow::Borrowed(buf);
+        #[cfg(feature = "mss_clamp")]
+        let packet = self.mss_clamp_outbound(packet);
+        Some(packet)
+    }
+    /// Validates a frame about to be written, if frame validation is enabled.
+    #[inline]
+    pub(crate) fn validate_outbound<'a>(
+        &self,
+        buf: &'a [u8],
+    ) -> std::io::Result<std::borrow::Cow<'a, [u8]>> {
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        return self.frame_validation.outbound(buf);
+        #[cfg(not(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        )))]
+        Ok(std::borrow::Cow::Borrowed(buf))
+    }
+    /// Returns whether the receive hooks look at the bytes of each packet, i.e. the packet
+    /// tap, the ICMP responder, the MSS clamp or the middleware chain is installed.
+    ///
+    /// Vectored and uninitialized reads then go through one contiguous buffer.
+    pub(crate) fn inspects_inbound(&self) -> bool {
+        if !self.hooks_plain_io() {
+            return false;
+        }
+        #[cfg(feature = "pcap")]
+        if self.packet_tap.is_set() {
+            return true;
+        }
+        #[cfg(feature = "icmp_responder")]
+        if self.icmp_responder.enabled() {
+            return true;
+        }
+        #[cfg(feature = "mss_clamp")]
+        if self.mss_clamp().is_some() {
+            return true;
+        }
+        #[cfg(feature = "middleware")]
+        if self.middleware.is_set() {
+            return true;
+        }
+        false
+    }
+    /// Returns whether the send hooks look at the bytes of each packet, i.e. the packet tap,
+    /// the middleware chain, the MSS clamp or frame validation is installed.
+    ///
+    /// Vectored writes then gather the packet into one buffer first.
+    pub(crate) fn inspects_outbound(&self) -> bool {
+        if !self.hooks_plain_io() {
+            return false;
+        }
+        #[cfg(feature = "pcap")]
+        if self.packet_tap.is_set() {
+            return true;
+        }
+        #[cfg(feature = "middleware")]
+        if self.middleware.is_set() {
+            return true;
+        }
+        #[cfg(feature = "mss_clamp")]
+        if self.mss_clamp().is_some() {
+            return true;
+        }
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        if self.frame_validation.get().is_some() {
+            return true;
+        }
+        false
+    }
+    /// Runs the receive hooks over a packet of `len` bytes read into `buf`: answers it if it
+    /// is an echo request for the ICMP responder, passes it to the packet tap, then runs the
+    /// MSS clamp and the middleware chain.
+    ///
+    /// Returns the length of the packet to hand to the caller, or `None` if it was answered
+    /// or dropped.
+    pub(crate) fn inbound(&self, buf: &mut [u8], len: usize) -> Option<usize> {
+        #[cfg(feature = "icmp_responder")]
+        if self.icmp_responder.answer(&mut buf[..len]) {
+            if let Err(e) = self.send_echo_reply(&buf[..len]) {
+                log::warn!("failed to send ICMP echo reply: {e}");
+            }
+            return None;
+        }
+        self.tap_inbound(&buf[..len]);
+        self.middleware_inbound(buf, len)
+    }
+    /// Reads packets into `buf` with `read` until one passes [`inbound`](Self::inbound), and
+    /// returns its length.
+    ///
+    /// A `read` that would block ends the loop with its `WouldBlock` error, so that `try_`
+    /// and polled reads go through here as well.
+    pub(crate) fn recv_hooked(
+        &self,
+        buf: &mut [u8],
+        mut read: impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        if !self.hooks_plain_io() {
+            return read(buf);
+        }
+        loop {
+            let len = read(buf)?;
+            if let Some(len) = self.inbound(buf, len) {
+                return Ok(len);
+            }
+        }
+    }
+    /// Like [`recv_hooked`](Self::recv_hooked) for a packet scattered across `bufs`.
+    ///
+    /// If the hooks [inspect](Self::inspects_inbound) the packet, it is read into a
+    /// contiguous buffer and copied into `bufs` once it passed them.
+    pub(crate) fn recv_vectored_hooked(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        mut readv: impl FnMut(&mut [IoSliceMut<'_>]) -> std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        if !self.inspects_inbound() {
+            let len = readv(bufs)?;
+            if self.hooks_plain_io() {
+                self.count_received(len);
+            }
+            return Ok(len);
+        }
+        let mut packet = vec![0u8; bufs.iter().map(|buf| buf.len()).sum()];
+        let len = self.recv_hooked(&mut packet, |packet| readv(&mut [IoSliceMut::new(packet)]))?;
+        scatter(&packet[..len], bufs);
+        Ok(len)
+    }
+    /// Writes a packet with `write` after running the middleware chain, the MSS clamp and
+    /// frame validation over it, then passes it to the packet tap and counts it unless the
+    /// write would have blocked.
+    ///
+    /// A packet dropped by the chain is reported as sent.
+    pub(crate) fn send_hooked(
+        &self,
+        buf: &[u8],
+        write: impl FnOnce(&[u8]) -> std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        if !self.hooks_plain_io() {
+            return write(buf);
+        }
+        let Some(packet) = self.middleware_outbound(buf) else {
+            return Ok(buf.len());
+        };
+        let len = packet.len();
+        let frame = self.validate_outbound(&packet)?;
+        self.finish_send(&frame, write(&frame)).map(|n| n.min(len))
+    }
+    /// Like [`send_hooked`](Self::send_hooked) for a packet gathered from `bufs`.
+    ///
+    /// If the hooks [inspect](Self::inspects_outbound) the packet, it is copied into one
+    /// buffer and written from there.
+    pub(crate) fn send_vectored_hooked(
+        &self,
+        bufs: &[IoSlice<'_>],
+        writev: impl FnOnce(&[IoSlice<'_>]) -> std::io::Result<usize>,
+    ) -> std::io::Result<usize> {
+        if !self.inspects_outbound() {
+            let result = writev(bufs);
+            if !self.hooks_plain_io() {
+                return result;
+            }
+            return self.finish_send_vectored(bufs, result);
+        }
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let packet = gather(bufs.iter().map(|buf| &buf[..]), len);
+        self.send_hooked(&packet, |packet| writev(&[IoSlice::new(packet)]))
+    }
+}
+
+#[cfg(feature = "icmp_responder")]
+impl DeviceImpl {
+    /// Writes an echo reply built by the ICMP responder, behind an empty virtio network
+    /// header on a Linux device with offload.
+    ///
+    /// The reply is not delayed by the egress limit, since the request may have been read by
+    /// an async or non-blocking call, but its bytes are taken from the bucket.
+    fn send_echo_reply(&self, packet: &[u8]) -> std::io::Result<usize> {
+        let Some(packet) = self.middleware_outbound(packet) else {
+            return Ok(0);
+        };
+        let _ = self.egress_limit.reserve(packet.len());
+        self.tap_outbound(&packet);
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if self.vnet_hdr {
+            let hdr = crate::platform::offload::VirtioNetHdr::default();
+            return self.count_sent(self.send_with_hdr(&hdr, &packet));
+        }
+        self.count_sent(self.send(&packet))
+    }
+}
+
+#[cfg(any(
+    feature = "icmp_responder",
+    feature = "smoltcp",
+    feature = "pcap",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+#[cfg(not(all(target_os = "linux", not(target_env = "ohos"))))]
+impl DeviceImpl {
+    /// Whether the device carries Ethernet frames (L2).
+    pub(crate) fn is_tap(&self) -> bool {
+        matches!(
+            self.backend_kind(),
+            BackendKind::Feth | BackendKind::BsdTap | BackendKind::TapWindows
+        )
+    }
+}
+
+#[cfg(feature = "icmp_responder")]
+impl DeviceImpl {
+    /// Sets the prefixes the built-in ICMP echo responder answers for, or disables it with an
+    /// empty slice.
+    ///
+    /// ICMP and ICMPv6 echo requests read from the device whose destination lies within one
+    /// of `prefixes` are answered by writing the echo reply back to it, and are not returned
+    /// to the caller. This lets operators ping the tunnel endpoint, e.g. the peer address of a
+    /// point-to-point link, even if the application does not handle ICMP itself. Requests to
+    /// any other destination are returned as usual: answering them would make every host
+    /// routed through the tunnel look reachable. Note that the operating system answers pings
+    /// to the interface's own address, so those never reach the device.
+    ///
+    /// Requests are answered by every receive of the sync and async device, the same calls
+    /// that run the middleware chain, and by `recv_multiple` on Linux and macOS. On a Linux
+    /// device built with `offload(true)` only the batched reads answer, once per segment,
+    /// writing each reply behind an empty virtio network header. `recv_with_meta` returns
+    /// echo requests unanswered on a device with packet information. Replies are written
+    /// right away, without waiting for the egress limit.
+    ///
+    /// Only available for TUN (L3) devices without packet information header.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+    /// dev.set_icmp_responder(&["10.0.0.2/32".parse().unwrap()])?;
+    /// let mut buf = [0u8; 1500];
+    /// // `ping 10.0.0.2` is answered here without returning from `recv`, `ping 10.0.0.3` is
+    /// // returned
+    /// let len = dev.recv(&mut buf)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn set_icmp_responder(&self, prefixes: &[ipnet::IpNet]) -> std::io::Result<()> {
+        if !prefixes.is_empty() && self.is_tap() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the ICMP responder is only available on TUN devices",
+            ));
+        }
+        self.icmp_responder.set(prefixes);
+        Ok(())
+    }
+    /// Returns the prefixes the built-in ICMP echo responder answers for, empty if it is
+    /// disabled.
+    pub fn icmp_responder(&self) -> Vec<ipnet::IpNet> {
+        self.icmp_responder.prefixes()
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) fn get_if_addrs_by_name(if_name: String) -> std::io::Result<Vec<Interface>> {
     let addrs = getifaddrs::getifaddrs()?;
@@ -102,6 +934,29 @@ pub(crate) fn get_if_addrs_by_name(if_name: String) -> std::io::Result<Vec<Inter
 #[repr(transparent)]
 pub struct SyncDevice(pub(crate) DeviceImpl);
 
+/// Returns `buf` without its first `headroom` and last `tailroom` bytes.
+pub(crate) fn reserved_slice(
+    buf: &mut [u8],
+    headroom: usize,
+    tailroom: usize,
+) -> std::io::Result<&mut [u8]> {
+    let end = buf.len().checked_sub(tailroom);
+    match end {
+        Some(end) if headroom <= end => Ok(&mut buf[headroom..end]),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "headroom and tailroom exceed the buffer length",
+        )),
+    }
+}
+
+/// Zeroes `buf` and returns it as initialized bytes.
+pub(crate) fn zeroed(buf: &mut [std::mem::MaybeUninit<u8>]) -> &mut [u8] {
+    buf.fill(std::mem::MaybeUninit::new(0));
+    // SAFETY: every byte was just initialized.
+    unsafe { &mut *(buf as *mut [std::mem::MaybeUninit<u8>] as *mut [u8]) }
+}
+
 impl SyncDevice {
     /// Creates a `SyncDevice` from a raw file descriptor.
     ///
@@ -163,7 +1018,25 @@ impl SyncDevice {
     /// ```
     #[cfg(unix)]
     pub unsafe fn from_fd(fd: RawFd) -> std::io::Result<Self> {
-        Ok(SyncDevice(DeviceImpl::from_fd(fd)?))
+        let device = DeviceImpl::from_fd(fd)?;
+        #[cfg(feature = "tracing")]
+        device.init_span();
+        Ok(SyncDevice(device))
+    }
+    /// Like [`from_fd`](Self::from_fd), but validates that `fd` is a TUN device first.
+    ///
+    /// On Apple platforms the fd must be a `utun` control socket, on Linux/Android it must be
+    /// attached to a TUN/TAP interface. If the check fails, the fd is left open and an
+    /// error carrying [`Error::InvalidFd`](crate::Error::InvalidFd) is returned.
+    ///
+    /// # Safety
+    /// The fd must not be owned by anything else; on success, ownership moves to the device.
+    #[cfg(unix)]
+    pub unsafe fn from_fd_checked(fd: RawFd) -> std::io::Result<Self> {
+        let device = DeviceImpl::from_fd_checked(fd)?;
+        #[cfg(feature = "tracing")]
+        device.init_span();
+        Ok(SyncDevice(device))
     }
     /// # Safety
     /// The fd passed in must be a valid, open file descriptor.
@@ -194,7 +1067,126 @@ impl SyncDevice {
     /// Blocking the current thread if no packet is available
     #[inline]
     pub fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.recv(buf)
+        self.0.recv_hooked(buf, |buf| self.0.recv(buf))
+    }
+    /// Receives a packet into `buf`, placing it at offset `headroom`.
+    ///
+    /// The first `headroom` bytes of `buf` are left untouched, so that encapsulation headers
+    /// (outer IP/UDP, crypto, ...) can later be written in front of the packet without a copy.
+    /// Returns the packet length; the packet is `buf[headroom..headroom + len]`.
+    ///
+    /// For batched reads on Linux, [`recv_multiple`](Self::recv_multiple) takes an `offset`
+    /// that serves the same purpose.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// let tun = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_sync()?;
+    /// const HEADROOM: usize = 28; // outer IPv4 + UDP
+    /// let mut buf = [0u8; HEADROOM + 1500];
+    /// let len = tun.recv_with_headroom(&mut buf, HEADROOM)?;
+    /// // Write the outer headers in place, then send `&buf[..HEADROOM + len]`.
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[inline]
+    pub fn recv_with_headroom(&self, buf: &mut [u8], headroom: usize) -> std::io::Result<usize> {
+        self.recv_with_reserve(buf, headroom, 0)
+    }
+    /// Like [`recv_with_headroom`](Self::recv_with_headroom), but additionally keeps the last
+    /// `tailroom` bytes of `buf` free (e.g. for an authentication tag appended in place).
+    ///
+    /// Returns an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput) if
+    /// `headroom + tailroom` exceeds the buffer length.
+    #[inline]
+    pub fn recv_with_reserve(
+        &self,
+        buf: &mut [u8],
+        headroom: usize,
+        tailroom: usize,
+    ) -> std::io::Result<usize> {
+        let buf = reserved_slice(buf, headroom, tailroom)?;
+        self.recv(buf)
+    }
+    /// Receives a packet into `buf` together with its packet information header.
+    ///
+    /// The header is not copied into `buf`, whether or not `recv` would include it. Unlike
+    /// `recv`, this tells whether the packet was cut to fit `buf` on Linux, where the device
+    /// must be built with `packet_information(true)`. On macOS and the BSDs it works on any
+    /// TUN device. Otherwise it fails with [`Unsupported`](std::io::ErrorKind::Unsupported).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .packet_information(true)
+    ///     .build_sync()?;
+    /// let mut buf = [0u8; 1500];
+    /// let (len, info) = dev.recv_with_meta(&mut buf)?;
+    /// if info.truncated {
+    ///     eprintln!("dropping a truncated packet");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(any(
+        all(target_os = "linux", not(target_env = "ohos")),
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    ))]
+    pub fn recv_with_meta(&self, buf: &mut [u8]) -> std::io::Result<(usize, PacketInformation)> {
+        if !self.0.hooks_plain_io() {
+            return self.0.recv_with_info(buf);
+        }
+        loop {
+            let (len, info) = self.0.recv_with_info(buf)?;
+            self.0.count_truncated(info.truncated);
+            // Without the header the packet is what `recv` would return.
+            let kept = if self.0.packet_information() {
+                self.0.tap_inbound_bare(&buf[..len]);
+                self.0.middleware_inbound(buf, len)
+            } else {
+                self.0.inbound(buf, len)
+            };
+            if let Some(len) = kept {
+                return Ok((len, info));
+            }
+        }
+    }
+    /// Receives a packet into `buf` like [`recv`](Self::recv), and also returns the length of
+    /// the packet before it was cut to fit `buf`, like `recvmsg` with `MSG_TRUNC`.
+    ///
+    /// The first value is the number of bytes in `buf`. The second one only exceeds it for a
+    /// packet cut under [`TruncatePolicy::Truncate`](crate::TruncatePolicy::Truncate);
+    /// under the default policy such a packet is dropped and the receive fails.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::{DeviceBuilder, TruncatePolicy};
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .truncate_policy(TruncatePolicy::Truncate)
+    ///     .build_sync()?;
+    /// let mut buf = [0u8; 576];
+    /// let (len, packet_len) = dev.recv_with_len(&mut buf)?;
+    /// if packet_len > len {
+    ///     eprintln!("kept {len} of {packet_len} bytes");
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub fn recv_with_len(&self, buf: &mut [u8]) -> std::io::Result<(usize, usize)> {
+        loop {
+            let (len, packet_len) = self.0.recv_with_len(buf)?;
+            if let Some(len) = self.0.inbound(buf, len) {
+                return Ok((len, packet_len));
+            }
+        }
     }
     /// Sends data from the provided buffer to the device.
     ///
@@ -213,7 +1205,28 @@ impl SyncDevice {
     /// ```
     #[inline]
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.send(buf)
+        if !self.0.hooks_plain_io() {
+            self.0.egress_limit.wait(buf.len());
+            return self.0.send(buf);
+        }
+        let Some(packet) = self.0.middleware_outbound(buf) else {
+            return Ok(buf.len());
+        };
+        let len = packet.len();
+        let frame = self.0.validate_outbound(&packet)?;
+        let buf = &frame[..];
+        self.0.egress_limit.wait(buf.len());
+        self.0.tap_outbound(buf);
+        self.0.count_sent(self.0.send(buf)).map(|n| n.min(len))
+    }
+    /// Sends a frame to the host as if it had arrived on the wire.
+    ///
+    /// This is what [`send`](Self::send) does; the name spells out the direction for
+    /// user-space switches that also inject frames the other way with `send_as_outgoing`,
+    /// available on TAP devices on Linux and macOS.
+    #[inline]
+    pub fn send_as_incoming(&self, frame: &[u8]) -> std::io::Result<usize> {
+        self.send(frame)
     }
     /// Attempts to receive data from the device in a non-blocking fashion.
     ///
@@ -221,7 +1234,7 @@ impl SyncDevice {
     #[cfg(target_os = "windows")]
     #[inline]
     pub fn try_recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.try_recv(buf)
+        self.0.recv_hooked(buf, |buf| self.0.try_recv(buf))
     }
     /// Attempts to send data to the device in a non-blocking fashion.
     ///
@@ -229,7 +1242,82 @@ impl SyncDevice {
     #[cfg(target_os = "windows")]
     #[inline]
     pub fn try_send(&self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.try_send(buf)
+        self.0.send_hooked(buf, |buf| self.0.try_send(buf))
+    }
+    /// Attempts to receive one packet into multiple buffers in a non-blocking fashion.
+    ///
+    /// The packet is scattered across `bufs` in order, as with `recv_vectored` on Unix.
+    /// Returns the number of bytes read or an error if the operation would block.
+    #[cfg(target_os = "windows")]
+    #[inline]
+    pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.0
+            .recv_vectored_hooked(bufs, |bufs| self.0.try_recv_vectored(bufs))
+    }
+    /// Attempts to send the contents of multiple buffers as one packet in a non-blocking
+    /// fashion.
+    ///
+    /// Returns the number of bytes written or an error if the operation would block.
+    #[cfg(target_os = "windows")]
+    #[inline]
+    pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.0
+            .send_vectored_hooked(bufs, |bufs| self.0.try_send_vectored(bufs))
+    }
+    /// Receives a packet, blocking until one arrives or `deadline` passes.
+    ///
+    /// Unlike a timeout, the deadline bounds the total time spent in the call, however many
+    /// spurious wakeups occur.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(n)` - Successfully read `n` bytes
+    /// - `Err(e)` with `ErrorKind::TimedOut` - No packet arrived before the deadline
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "windows")]
+    /// # {
+    /// use std::time::{Duration, Instant};
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_sync()?;
+    ///
+    /// let mut buf = vec![0u8; 1500];
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    /// match dev.recv_deadline(&mut buf, deadline) {
+    ///     Ok(n) => println!("Received {} bytes", n),
+    ///     Err(e) if e.kind() == std::io::ErrorKind::TimedOut => println!("No packet"),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(target_os = "windows")]
+    pub fn recv_deadline(
+        &self,
+        buf: &mut [u8],
+        deadline: std::time::Instant,
+    ) -> std::io::Result<usize> {
+        self.0
+            .recv_hooked(buf, |buf| self.0.recv_deadline(buf, None, Some(deadline)))
+    }
+    /// Sends a packet, blocking while the device cannot take it until `deadline` passes.
+    ///
+    /// Returns an error of kind [`TimedOut`](std::io::ErrorKind::TimedOut) if the packet
+    /// could not be queued before the deadline. [`send`](Self::send) behaves like this with
+    /// a fixed deadline of five seconds on Wintun.
+    #[cfg(target_os = "windows")]
+    pub fn send_deadline(
+        &self,
+        buf: &[u8],
+        deadline: std::time::Instant,
+    ) -> std::io::Result<usize> {
+        self.0
+            .send_hooked(buf, |buf| self.0.send_deadline(buf, None, Some(deadline)))
     }
     /// Shuts down the device on Windows.
     ///
@@ -268,7 +1356,8 @@ impl SyncDevice {
     /// This method is only available when the `interruptible` feature is enabled.
     #[cfg(feature = "interruptible")]
     pub fn recv_intr(&self, buf: &mut [u8], event: &InterruptEvent) -> std::io::Result<usize> {
-        self.0.read_interruptible(buf, event, None)
+        self.0
+            .recv_hooked(buf, |buf| self.0.read_interruptible(buf, event, None))
     }
 
     /// Like [`recv_intr`](Self::recv_intr), but with an optional timeout.
@@ -328,23 +1417,27 @@ impl SyncDevice {
         event: &InterruptEvent,
         timeout: Option<std::time::Duration>,
     ) -> std::io::Result<usize> {
-        self.0.read_interruptible(buf, event, timeout)
+        self.0
+            .recv_hooked(buf, |buf| self.0.read_interruptible(buf, event, timeout))
     }
     /// Like [`recv_intr`](Self::recv_intr), but reads into multiple buffers.
     ///
     /// This function behaves the same as [`recv_intr`](Self::recv_intr),
     /// but uses `readv` to fill the provided set of non-contiguous buffers.
     ///
+    /// On **Windows** the packet is read into a temporary buffer and copied into `bufs`.
+    ///
     /// # Feature
     ///
     /// This method is only available when the `interruptible` feature is enabled.
-    #[cfg(all(unix, feature = "interruptible"))]
+    #[cfg(feature = "interruptible")]
     pub fn recv_vectored_intr(
         &self,
         bufs: &mut [IoSliceMut<'_>],
         event: &InterruptEvent,
     ) -> std::io::Result<usize> {
-        self.0.readv_interruptible(bufs, event, None)
+        self.0
+            .recv_vectored_hooked(bufs, |bufs| self.0.readv_interruptible(bufs, event, None))
     }
 
     /// Like [`recv_vectored_intr`](Self::recv_vectored_intr), but with an optional timeout.
@@ -397,14 +1490,16 @@ impl SyncDevice {
     /// # Feature
     ///
     /// This method is only available when the `interruptible` feature is enabled.
-    #[cfg(all(unix, feature = "interruptible"))]
+    #[cfg(feature = "interruptible")]
     pub fn recv_vectored_intr_timeout(
         &self,
         bufs: &mut [IoSliceMut<'_>],
         event: &InterruptEvent,
         timeout: Option<std::time::Duration>,
     ) -> std::io::Result<usize> {
-        self.0.readv_interruptible(bufs, event, timeout)
+        self.0.recv_vectored_hooked(bufs, |bufs| {
+            self.0.readv_interruptible(bufs, event, timeout)
+        })
     }
     #[cfg(feature = "interruptible")]
     pub fn wait_readable_intr(&self, event: &InterruptEvent) -> std::io::Result<()> {
@@ -469,13 +1564,63 @@ impl SyncDevice {
     }
     #[cfg(feature = "interruptible")]
     pub fn send_intr(&self, buf: &[u8], event: &InterruptEvent) -> std::io::Result<usize> {
-        self.0.write_interruptible(buf, event)
+        self.0
+            .send_hooked(buf, |buf| self.0.write_interruptible(buf, event))
+    }
+
+    /// Like [`recv_deadline`](Self::recv_deadline), but can also be interrupted by `event`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(n)` - Successfully read `n` bytes
+    /// - `Err(e)` with `ErrorKind::Interrupted` - Operation was interrupted by the event
+    /// - `Err(e)` with `ErrorKind::TimedOut` - No packet arrived before the deadline
+    ///
+    /// # Feature
+    ///
+    /// This method is only available when the `interruptible` feature is enabled.
+    #[cfg(all(target_os = "windows", feature = "interruptible"))]
+    pub fn recv_intr_deadline(
+        &self,
+        buf: &mut [u8],
+        event: &InterruptEvent,
+        deadline: std::time::Instant,
+    ) -> std::io::Result<usize> {
+        self.0.recv_hooked(buf, |buf| {
+            self.0
+                .recv_deadline(buf, Some(&event.handle), Some(deadline))
+        })
+    }
+
+    /// Like [`send_deadline`](Self::send_deadline), but can also be interrupted by `event`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(n)` - Successfully sent `n` bytes
+    /// - `Err(e)` with `ErrorKind::Interrupted` - Operation was interrupted by the event
+    /// - `Err(e)` with `ErrorKind::TimedOut` - The packet could not be queued before the deadline
+    ///
+    /// # Feature
+    ///
+    /// This method is only available when the `interruptible` feature is enabled.
+    #[cfg(all(target_os = "windows", feature = "interruptible"))]
+    pub fn send_intr_deadline(
+        &self,
+        buf: &[u8],
+        event: &InterruptEvent,
+        deadline: std::time::Instant,
+    ) -> std::io::Result<usize> {
+        self.0.send_hooked(buf, |buf| {
+            self.0
+                .send_deadline(buf, Some(&event.handle), Some(deadline))
+        })
     }
 
     /// Sends data to the device from multiple buffers using vectored I/O, with interruption support.
     ///
     /// Like [`send_intr`](Self::send_intr), but uses `writev` to send from multiple
-    /// non-contiguous buffers in a single operation.
+    /// non-contiguous buffers in a single operation. On **Windows** the buffers are copied
+    /// into one packet first.
     ///
     /// # Arguments
     ///
@@ -518,13 +1663,14 @@ impl SyncDevice {
     /// # Feature
     ///
     /// This method is only available when the `interruptible` feature is enabled.
-    #[cfg(all(unix, feature = "interruptible"))]
+    #[cfg(feature = "interruptible")]
     pub fn send_vectored_intr(
         &self,
         bufs: &[IoSlice<'_>],
         event: &InterruptEvent,
     ) -> std::io::Result<usize> {
-        self.0.writev_interruptible(bufs, event)
+        self.0
+            .send_vectored_hooked(bufs, |bufs| self.0.writev_interruptible(bufs, event))
     }
 
     /// Waits for the device to become writable, with interruption support.
@@ -609,7 +1755,89 @@ impl SyncDevice {
     /// ```
     #[cfg(unix)]
     pub fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
-        self.0.recv_vectored(bufs)
+        self.0
+            .recv_vectored_hooked(bufs, |bufs| self.0.recv_vectored(bufs))
+    }
+    /// Receives a packet into `buf` without requiring it to be initialized.
+    ///
+    /// Saves zeroing large receive buffers in hot loops. Returns the packet length; the
+    /// first `len` bytes of `buf` (at most `buf.len()`) are initialized afterwards.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::mem::MaybeUninit;
+    /// use tun_rs::DeviceBuilder;
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_sync()?;
+    /// let mut buf = [MaybeUninit::<u8>::uninit(); 65536];
+    /// let len = dev.recv_uninit(&mut buf)?;
+    /// // SAFETY: the device initialized the first `len` bytes.
+    /// let packet = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len) };
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn recv_uninit(&self, buf: &mut [std::mem::MaybeUninit<u8>]) -> std::io::Result<usize> {
+        if self.0.inspects_inbound() {
+            return self.recv(zeroed(buf));
+        }
+        let len = self.0.recv_uninit(bytes::buf::UninitSlice::uninit(buf))?;
+        if self.0.hooks_plain_io() {
+            self.0.count_received(len);
+        }
+        Ok(len)
+    }
+    /// Like [`recv_vectored`](Self::recv_vectored), into buffers that may be uninitialized.
+    ///
+    /// The packet fills the buffers in order; as many bytes as the returned length (at most
+    /// the total length of `bufs`) are initialized afterwards.
+    #[cfg(unix)]
+    pub fn recv_vectored_uninit(
+        &self,
+        bufs: &mut [&mut [std::mem::MaybeUninit<u8>]],
+    ) -> std::io::Result<usize> {
+        if self.0.inspects_inbound() {
+            let mut bufs: Vec<IoSliceMut<'_>> = bufs
+                .iter_mut()
+                .map(|buf| IoSliceMut::new(zeroed(buf)))
+                .collect();
+            return self.recv_vectored(&mut bufs);
+        }
+        let len = self.0.recv_vectored_uninit(bufs)?;
+        if self.0.hooks_plain_io() {
+            self.0.count_received(len);
+        }
+        Ok(len)
+    }
+    /// Receives a packet into the spare capacity of `buf` and advances it past the packet.
+    ///
+    /// The packet is read into [`BufMut::chunk_mut`](bytes::BufMut::chunk_mut), so reserve at
+    /// least one MTU first, e.g. with `BytesMut::reserve`. Returns the packet length; a packet
+    /// longer than the chunk is truncated like with [`recv`](Self::recv).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use bytes::BytesMut;
+    /// use tun_rs::DeviceBuilder;
+    /// let dev = DeviceBuilder::new()
+    ///     .ipv4("10.0.0.1", 24, None)
+    ///     .build_sync()?;
+    /// let mut buf = BytesMut::with_capacity(64 * 1500);
+    /// for _ in 0..64 {
+    ///     dev.recv_buf(&mut buf)?;
+    ///     // Each packet is appended, without zeroing the capacity first.
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(unix)]
+    pub fn recv_buf<B: bytes::BufMut>(&self, buf: &mut B) -> std::io::Result<usize> {
+        let chunk = buf.chunk_mut();
+        let capacity = chunk.len();
+        // SAFETY: `recv_uninit` only writes initialized bytes into the chunk.
+        let len = self.recv_uninit(unsafe { chunk.as_uninit_slice_mut() })?;
+        // SAFETY: the first `len` bytes of the chunk, as far as it reaches, are initialized.
+        unsafe { buf.advance_mut(len.min(capacity)) };
+        Ok(len)
     }
     /// Sends data to the device from multiple buffers using vectored I/O.
     ///
@@ -642,7 +1870,8 @@ impl SyncDevice {
     /// ```
     #[cfg(unix)]
     pub fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
-        self.0.send_vectored(bufs)
+        self.0
+            .send_vectored_hooked(bufs, |bufs| self.0.send_vectored(bufs))
     }
     /// Checks whether the device is currently operating in nonblocking mode.
     ///
@@ -0,0 +1,208 @@
+use crate::platform::DeviceImpl;
+use std::io;
+use std::net::Ipv6Addr;
+
+/// Proxy NDP entries on an uplink interface for addresses routed into a device, removed on
+/// drop. Created by [`ndp_proxy`](DeviceImpl::ndp_proxy).
+///
+/// When a prefix that is on-link on the uplink (e.g. a part of the /64 of an Ethernet segment)
+/// is routed into the tunnel, the neighbours on the uplink still resolve its addresses with
+/// neighbour solicitations, which nobody answers. A proxy entry makes the host answer them
+/// with its own link-layer address, so that the traffic reaches it and is forwarded into the
+/// device. Proxy entries are per address: publish each address in use behind the tunnel.
+///
+/// - Linux: enables `net.ipv6.conf.<uplink>.proxy_ndp` and adds the entries of
+///   `ip -6 neigh add proxy <address> dev <uplink>`. The sysctl is restored on drop if it was
+///   enabled here.
+/// - macOS: adds the entries of `ndp -s <address> <uplink MAC> proxy`.
+///
+/// IPv6 forwarding must be enabled for the forwarded traffic to get through.
+#[derive(Debug)]
+pub struct NdpProxy {
+    uplink: String,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    uplink_index: u32,
+    addresses: Vec<Ipv6Addr>,
+    restore_proxy_ndp: bool,
+}
+
+impl NdpProxy {
+    /// The name of the uplink interface the addresses are published on.
+    pub fn uplink(&self) -> &str {
+        &self.uplink
+    }
+    /// The published addresses, in publication order.
+    pub fn addresses(&self) -> &[Ipv6Addr] {
+        &self.addresses
+    }
+    /// Publishes `address` as well. Publishing an address twice is a no-op.
+    pub fn add(&mut self, address: Ipv6Addr) -> io::Result<()> {
+        if self.addresses.contains(&address) {
+            return Ok(());
+        }
+        sys::add_proxy(self, address)?;
+        self.addresses.push(address);
+        Ok(())
+    }
+    /// Withdraws `address`. Fails with [`io::ErrorKind::NotFound`] if it was not published
+    /// through this proxy.
+    pub fn remove(&mut self, address: Ipv6Addr) -> io::Result<()> {
+        let pos = self
+            .addresses
+            .iter()
+            .position(|a| *a == address)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "the address is not published")
+            })?;
+        sys::delete_proxy(self, address)?;
+        self.addresses.remove(pos);
+        Ok(())
+    }
+    /// Withdraws all addresses, returning the first error. Dropping the proxy does the same
+    /// but only logs errors.
+    pub fn close(mut self) -> io::Result<()> {
+        self.withdraw_all()
+    }
+    fn withdraw_all(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        while let Some(address) = self.addresses.pop() {
+            if let Err(e) = sys::delete_proxy(self, address) {
+                log::warn!("failed to withdraw proxy NDP entry {address}: {e}");
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        if std::mem::take(&mut self.restore_proxy_ndp) {
+            if let Err(e) = sys::set_proxy_ndp(&self.uplink, false) {
+                log::warn!("failed to disable proxy_ndp on {}: {e}", self.uplink);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for NdpProxy {
+    fn drop(&mut self) {
+        _ = self.withdraw_all();
+    }
+}
+
+impl DeviceImpl {
+    /// Answers neighbour solicitations on the interface `uplink` for `addresses`, which are
+    /// routed into this device, and returns a guard that withdraws them again when dropped.
+    ///
+    /// See [`NdpProxy`] for how this is done on each platform. If an address cannot be
+    /// published, the ones published so far are withdrawn and the error is returned. Fails
+    /// with [`io::ErrorKind::InvalidInput`] if `uplink` is this device.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new().ipv6("2001:db8::1", 128).build_sync()?;
+    /// // 2001:db8::/64 is on-link on eth0; 2001:db8::100 is a client behind the tunnel.
+    /// let mut proxy = dev.ndp_proxy("eth0", &["2001:db8::100".parse().unwrap()])?;
+    /// proxy.add("2001:db8::101".parse().unwrap())?;
+    /// // ... run the tunnel; the entries are withdrawn when `proxy` is dropped.
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn ndp_proxy(&self, uplink: &str, addresses: &[Ipv6Addr]) -> io::Result<NdpProxy> {
+        if self.name()? == uplink {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the uplink is the device itself",
+            ));
+        }
+        let c_uplink = std::ffi::CString::new(uplink)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        let uplink_index = unsafe { libc::if_nametoindex(c_uplink.as_ptr()) };
+        if uplink_index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut proxy = NdpProxy {
+            uplink: uplink.to_string(),
+            uplink_index,
+            addresses: Vec::new(),
+            restore_proxy_ndp: false,
+        };
+        if !sys::proxy_ndp(uplink)? {
+            sys::set_proxy_ndp(uplink, true)?;
+            proxy.restore_proxy_ndp = true;
+        }
+        for &address in addresses {
+            proxy.add(address)?;
+        }
+        Ok(proxy)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::NdpProxy;
+    use crate::platform::linux::ndp_proxy;
+    use std::io;
+    use std::net::Ipv6Addr;
+
+    pub(super) use ndp_proxy::{proxy_ndp, set_proxy_ndp};
+
+    pub(super) fn add_proxy(proxy: &NdpProxy, address: Ipv6Addr) -> io::Result<()> {
+        ndp_proxy::add_proxy(proxy.uplink_index, address)
+    }
+
+    pub(super) fn delete_proxy(proxy: &NdpProxy, address: Ipv6Addr) -> io::Result<()> {
+        ndp_proxy::delete_proxy(proxy.uplink_index, address)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::NdpProxy;
+    use crate::platform::macos::run_command;
+    use std::io;
+    use std::net::Ipv6Addr;
+
+    /// macOS answers for proxy entries without a global switch.
+    pub(super) fn proxy_ndp(_name: &str) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    pub(super) fn set_proxy_ndp(_name: &str, _enabled: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn uplink_mac(name: &str) -> io::Result<String> {
+        getifaddrs::getifaddrs()?
+            .filter(|v| v.name == name)
+            .find_map(|v| match v.address {
+                getifaddrs::Address::Mac(mac) => Some(mac),
+                _ => None,
+            })
+            .map(|mac| {
+                let octets: Vec<String> = mac.iter().map(|b| format!("{b:02x}")).collect();
+                octets.join(":")
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the uplink has no MAC address"))
+    }
+
+    pub(super) fn add_proxy(proxy: &NdpProxy, address: Ipv6Addr) -> io::Result<()> {
+        let mac = uplink_mac(&proxy.uplink)?;
+        run_command("ndp", &["-s", &scoped(proxy, address), &mac, "proxy"])
+    }
+
+    pub(super) fn delete_proxy(proxy: &NdpProxy, address: Ipv6Addr) -> io::Result<()> {
+        run_command("ndp", &["-d", &scoped(proxy, address)])
+    }
+
+    /// Link-local addresses need the uplink as their scope.
+    fn scoped(proxy: &NdpProxy, address: Ipv6Addr) -> String {
+        if address.segments()[0] & 0xffc0 == 0xfe80 {
+            format!("{address}%{}", proxy.uplink)
+        } else {
+            address.to_string()
+        }
+    }
+}
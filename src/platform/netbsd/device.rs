@@ -1,3 +1,4 @@
+use crate::platform::{Cleanup, OperState};
 use crate::{
     builder::{DeviceConfig, Layer},
     platform::netbsd::sys::*,
@@ -22,7 +23,24 @@ pub struct DeviceImpl {
     name: String,
     pub(crate) tun: Tun,
     pub(crate) op_lock: RwLock<()>,
+    pub(crate) cleanup: Cleanup,
     pub(crate) associate_route: AtomicBool,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    pub(crate) frame_validation: crate::ethernet::FrameValidator,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
 }
 impl IntoRawFd for DeviceImpl {
     fn into_raw_fd(mut self) -> RawFd {
@@ -36,6 +54,12 @@ impl Drop for DeviceImpl {
         if self.tun.fd.inner < 0 {
             return;
         }
+        if !self
+            .run_cleanup()
+            .is_some_and(|policy| policy.destroy_device)
+        {
+            return;
+        }
         unsafe {
             // Try to destroy the interface before the fd is closed by Fd::drop.
             if let (Ok(ctl), Ok(req)) = (ctl(), self.request()) {
@@ -81,15 +105,29 @@ impl DeviceImpl {
             name,
             tun,
             op_lock: RwLock::new(()),
+            cleanup: Cleanup::new(config.cleanup_policy, false),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(associate_route),
         })
     }
     fn check_name(layer: Layer, dev_name: &str) -> io::Result<()> {
         if dev_name.len() > IFNAMSIZ {
-            return Err(io::Error::new(
-                ErrorKind::InvalidInput,
-                "device name too long",
-            ));
+            return Err(crate::Error::NameTooLong.into());
         }
         let device_prefix = match layer {
             Layer::L2 => "tap",
@@ -241,6 +279,23 @@ impl DeviceImpl {
             name,
             tun,
             op_lock: RwLock::new(()),
+            cleanup: Cleanup::default(),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
             associate_route: AtomicBool::new(true),
         })
     }
@@ -396,6 +451,24 @@ impl DeviceImpl {
         let _guard = self.op_lock.read().unwrap();
         self.name_impl()
     }
+    /// Renaming is not available: `tun(4)` and `tap(4)` interfaces keep the name of their
+    /// device node. Always fails with [`Error::Unsupported`](crate::Error::Unsupported).
+    pub fn set_name(&self, value: &str) -> io::Result<()> {
+        let _ = value;
+        Err(crate::Error::Unsupported {
+            operation: "set_name",
+        }
+        .into())
+    }
+    /// Returns the backend of this device: [`crate::BackendKind::BsdTun`] for `tun(4)`,
+    /// [`crate::BackendKind::BsdTap`] for `tap(4)`.
+    pub fn backend_kind(&self) -> crate::BackendKind {
+        if self.name.starts_with("tap") {
+            crate::BackendKind::BsdTap
+        } else {
+            crate::BackendKind::BsdTun
+        }
+    }
     /// If false, the program will not modify or manage routes in any way, allowing the system to handle all routing natively.
     /// If true (default), the program will automatically add or remove routes to provide consistent routing behavior across all platforms.
     /// Set this to be false to obtain the platform's default routing behavior.
@@ -433,6 +506,34 @@ impl DeviceImpl {
             Ok(())
         }
     }
+    /// Returns whether the interface is administratively up (`IFF_UP`), see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> io::Result<bool> {
+        let _guard = self.op_lock.read().unwrap();
+        Ok(self.if_flags()? & IFF_UP as c_short != 0)
+    }
+    /// Returns the operational state of the interface, derived from `IFF_UP` and
+    /// `IFF_RUNNING`.
+    ///
+    /// An interface that is up but not running, e.g. a tun(4) or tap(4) device whose node is not open, is
+    /// [`OperState::LowerLayerDown`].
+    pub fn oper_state(&self) -> io::Result<OperState> {
+        let _guard = self.op_lock.read().unwrap();
+        let flags = self.if_flags()?;
+        Ok(if flags & IFF_UP as c_short == 0 {
+            OperState::Down
+        } else if flags & IFF_RUNNING as c_short == 0 {
+            OperState::LowerLayerDown
+        } else {
+            OperState::Up
+        })
+    }
+    fn if_flags(&self) -> io::Result<c_short> {
+        unsafe {
+            let mut req = self.request()?;
+            siocgifflags(ctl()?.as_raw_fd(), &mut req)?;
+            Ok(req.ifr_ifru.ifru_flags)
+        }
+    }
     /// Retrieves the current MTU (Maximum Transmission Unit) for the interface.
     pub fn mtu(&self) -> io::Result<u16> {
         let _guard = self.op_lock.read().unwrap();
@@ -668,7 +769,7 @@ impl DeviceImpl {
 
     /// Returns whether the TUN device is set to ignore packet information (PI).
     ///
-    /// When enabled, the device does not prepend the `struct tun_pi` header
+    /// When enabled, the device does not prepend the 4-byte address family header
     /// to packets, which can simplify packet processing in some cases.
     ///
     /// # Returns
@@ -682,9 +783,9 @@ impl DeviceImpl {
     }
     /// Sets whether the TUN device should ignore packet information (PI).
     ///
-    /// When `ignore_packet_info` is set to `true`, the TUN device does not
-    /// prepend the `struct tun_pi` header to packets. This can be useful
-    /// if the additional metadata is not needed.
+    /// When `ignore_packet_info` is set to `true`, the TUN device does not prepend the
+    /// address family header ([`AfHeader`](crate::framing::AfHeader)) to packets. This can be
+    /// useful if the additional metadata is not needed.
     ///
     /// # Parameters
     /// * `ign`
@@ -0,0 +1,107 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token-bucket egress rate limit, see `set_egress_limit` on the device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct RateLimit {
+    /// Sustained rate in bits per second.
+    pub bps: u64,
+    /// Maximum burst in bytes, i.e. the bucket size.
+    ///
+    /// Should be at least the MTU (or the largest GSO packet when offload is enabled),
+    /// otherwise every packet has to wait for the bucket to refill past empty.
+    pub burst: u64,
+}
+
+struct Bucket {
+    limit: RateLimit,
+    /// Available bytes; negative when packets have been admitted ahead of time.
+    tokens: f64,
+    last: Instant,
+}
+
+/// Shared egress limiter state of a device.
+#[derive(Clone, Default)]
+pub(crate) struct RateLimiter(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    enabled: AtomicBool,
+    bucket: Mutex<Option<Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn set(&self, limit: Option<RateLimit>) -> io::Result<()> {
+        if let Some(limit) = limit {
+            if limit.bps == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "rate limit bps must be greater than 0",
+                ));
+            }
+        }
+        let mut bucket = self.0.bucket.lock().unwrap();
+        *bucket = limit.map(|limit| Bucket {
+            limit,
+            tokens: limit.burst as f64,
+            last: Instant::now(),
+        });
+        self.0.enabled.store(limit.is_some(), Ordering::Release);
+        Ok(())
+    }
+    pub(crate) fn get(&self) -> Option<RateLimit> {
+        self.0.bucket.lock().unwrap().as_ref().map(|b| b.limit)
+    }
+    /// Admits `len` bytes and returns how long the caller must wait before sending them.
+    pub(crate) fn reserve(&self, len: usize) -> Option<Duration> {
+        if !self.0.enabled.load(Ordering::Acquire) {
+            return None;
+        }
+        let mut guard = self.0.bucket.lock().unwrap();
+        let bucket = guard.as_mut()?;
+        let rate = bucket.limit.bps as f64 / 8.0;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.last = now;
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(bucket.limit.burst as f64);
+        bucket.tokens -= len as f64;
+        if bucket.tokens >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(-bucket.tokens / rate))
+        }
+    }
+    /// Blocks the current thread until `len` bytes may be sent.
+    pub(crate) fn wait(&self, len: usize) {
+        if let Some(delay) = self.reserve(len) {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::default();
+        assert!(limiter.reserve(1_000_000).is_none());
+        limiter
+            .set(Some(RateLimit {
+                bps: 8_000,
+                burst: 1500,
+            }))
+            .unwrap();
+        // The burst is admitted immediately.
+        assert!(limiter.reserve(1500).is_none());
+        // 1000 bytes at 1000 bytes/s take about a second.
+        let delay = limiter.reserve(1000).unwrap();
+        assert!(delay > Duration::from_millis(900) && delay <= Duration::from_secs(1));
+        assert!(limiter.set(Some(RateLimit { bps: 0, burst: 0 })).is_err());
+        limiter.set(None).unwrap();
+        assert!(limiter.reserve(1_000_000).is_none());
+    }
+}
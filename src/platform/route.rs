@@ -0,0 +1,204 @@
+use crate::platform::DeviceImpl;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A route of the system routing table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Route {
+    /// Destination network address.
+    pub destination: IpAddr,
+    /// Prefix length of the destination network.
+    pub prefix: u8,
+    /// Next hop, or `None` for a route on the link of the interface.
+    pub gateway: Option<IpAddr>,
+    /// Index of the outgoing interface.
+    pub if_index: u32,
+}
+
+/// Returns the route the system would currently use to reach `destination`.
+///
+/// The returned route is the host route of `destination` through the gateway and interface
+/// that were selected, which is what is needed to pin traffic to them. Its `if_index` can
+/// also be passed to [`bind_to_interface`](crate::bind_to_interface).
+pub fn find_route(destination: IpAddr) -> io::Result<Route> {
+    sys::find_route(destination)
+}
+
+/// The routes through the interface `if_index` that were added to the routing table, leaving
+/// out those the system derives from its addresses.
+pub(crate) fn device_routes(if_index: u32) -> io::Result<Vec<Route>> {
+    sys::device_routes(if_index)
+}
+
+pub(crate) fn delete_route(route: &Route) -> io::Result<()> {
+    sys::delete_route(route)
+}
+
+/// The routes installed by [`route_guard`](DeviceImpl::route_guard), removed on drop.
+#[derive(Debug)]
+pub struct RouteGuard {
+    routes: Vec<Route>,
+}
+
+impl RouteGuard {
+    /// The installed routes, in installation order.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+    /// Removes the routes, returning the first error. Dropping the guard does the same but
+    /// only logs errors.
+    pub fn remove(mut self) -> io::Result<()> {
+        self.remove_routes()
+    }
+    fn remove_routes(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        while let Some(route) = self.routes.pop() {
+            if let Err(e) = sys::delete_route(&route) {
+                log::warn!("failed to remove route {route:?}: {e}");
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for RouteGuard {
+    fn drop(&mut self) {
+        _ = self.remove_routes();
+    }
+}
+
+impl DeviceImpl {
+    /// Installs the routes a full-tunnel VPN needs to avoid routing loops, and returns a guard
+    /// that removes them again when dropped.
+    ///
+    /// - A host route to `server` (the VPN server) via the gateway and interface currently
+    ///   used to reach it, so that the tunnel's own transport bypasses the tunnel.
+    /// - With `override_default`, the routes `0.0.0.0/1` and `128.0.0.0/1` (and `::/1` and
+    ///   `8000::/1`) into this device for each address family the device has an address of,
+    ///   not counting IPv6 link-local addresses. Being more specific than the default route,
+    ///   they capture all other traffic without replacing the default route.
+    ///
+    /// If a route cannot be installed, the routes installed so far are removed and the error
+    /// is returned. Fails with [`io::ErrorKind::InvalidInput`] if `server` is already routed
+    /// through this device.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new().ipv4("10.0.0.2", 24, None).build_sync()?;
+    /// let _routes = dev.route_guard("203.0.113.1".parse().unwrap(), true)?;
+    /// // ... run the tunnel; the routes are removed when `_routes` is dropped.
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn route_guard(&self, server: IpAddr, override_default: bool) -> io::Result<RouteGuard> {
+        let if_index = self.if_index()?;
+        let uplink = find_route(server)?;
+        if uplink.if_index == if_index {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the server is routed through the device",
+            ));
+        }
+        let mut routes = vec![uplink];
+        if override_default {
+            let addresses = self.addresses()?;
+            if addresses.iter().any(|ip| ip.is_ipv4()) {
+                routes.push(half_route(Ipv4Addr::UNSPECIFIED.into(), if_index));
+                routes.push(half_route(Ipv4Addr::new(128, 0, 0, 0).into(), if_index));
+            }
+            let routable_v6 = |ip: &IpAddr| match ip {
+                IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 != 0xfe80,
+                IpAddr::V4(_) => false,
+            };
+            if addresses.iter().any(routable_v6) {
+                routes.push(half_route(Ipv6Addr::UNSPECIFIED.into(), if_index));
+                let upper = Ipv6Addr::new(0x8000, 0, 0, 0, 0, 0, 0, 0);
+                routes.push(half_route(upper.into(), if_index));
+            }
+        }
+        let mut guard = RouteGuard { routes: Vec::new() };
+        for route in routes {
+            sys::add_route(&route)?;
+            guard.routes.push(route);
+        }
+        Ok(guard)
+    }
+}
+
+fn half_route(destination: IpAddr, if_index: u32) -> Route {
+    Route {
+        destination,
+        prefix: 1,
+        gateway: None,
+        if_index,
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+use crate::platform::linux::route as sys;
+#[cfg(target_os = "windows")]
+use crate::platform::windows::ffi as sys;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+mod sys {
+    use super::Route;
+    use std::io;
+    use std::net::IpAddr;
+
+    fn manager_route(route: &Route) -> route_manager::Route {
+        let manager_route = route_manager::Route::new(route.destination, route.prefix)
+            .with_if_index(route.if_index);
+        match route.gateway {
+            Some(gateway) => manager_route.with_gateway(gateway),
+            None => manager_route,
+        }
+    }
+
+    pub(crate) fn add_route(route: &Route) -> io::Result<()> {
+        let mut manager = route_manager::RouteManager::new()?;
+        manager.add(&manager_route(route))
+    }
+
+    pub(crate) fn delete_route(route: &Route) -> io::Result<()> {
+        let mut manager = route_manager::RouteManager::new()?;
+        manager.delete(&manager_route(route))
+    }
+
+    pub(crate) fn device_routes(if_index: u32) -> io::Result<Vec<Route>> {
+        let mut manager = route_manager::RouteManager::new()?;
+        let routes = manager
+            .list()?
+            .into_iter()
+            .filter(|route| route.if_index() == Some(if_index))
+            .map(|route| Route {
+                destination: route.destination(),
+                prefix: route.prefix(),
+                gateway: route.gateway(),
+                if_index,
+            })
+            .collect();
+        Ok(routes)
+    }
+
+    pub(crate) fn find_route(destination: IpAddr) -> io::Result<Route> {
+        let mut manager = route_manager::RouteManager::new()?;
+        let route = manager.find_route(&destination)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no route to the destination")
+        })?;
+        Ok(Route {
+            destination,
+            prefix: if destination.is_ipv4() { 32 } else { 128 },
+            gateway: route.gateway(),
+            if_index: route.if_index().unwrap_or(0),
+        })
+    }
+}
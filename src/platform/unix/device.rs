@@ -8,9 +8,10 @@ use bytes::buf::UninitSlice;
     target_os = "openbsd",
     target_os = "netbsd",
 ))]
-use libc::{AF_INET, AF_INET6, SOCK_DGRAM};
+use libc::{AF_INET, SOCK_DGRAM};
 use std::io;
 use std::io::{IoSlice, IoSliceMut};
+use std::mem::MaybeUninit;
 use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd};
 
 impl FromRawFd for DeviceImpl {
@@ -42,13 +43,80 @@ impl AsFd for DeviceImpl {
         unsafe { BorrowedFd::borrow_raw(self.as_raw_fd()) }
     }
 }
-#[cfg(not(any(target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+#[cfg(not(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
 impl std::os::unix::io::IntoRawFd for DeviceImpl {
     fn into_raw_fd(self) -> RawFd {
         self.tun.into_raw_fd()
     }
 }
+/// Checks that an adopted `fd` refers to a TUN device.
+///
+/// On Apple platforms the fd must be a `utun` control socket (`UTUN_OPT_IFNAME` succeeds),
+/// on Linux/Android it must be attached to an interface (`TUNGETIFF` succeeds).
+/// Elsewhere only the validity of the fd is checked.
+pub(crate) fn check_tun_fd(fd: RawFd) -> io::Result<()> {
+    let invalid =
+        |reason: &'static str| -> io::Result<()> { Err(crate::Error::InvalidFd { reason }.into()) };
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let file_type = stat.st_mode & libc::S_IFMT;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos"))]
+    {
+        if file_type != libc::S_IFSOCK {
+            return invalid("not a socket, a utun fd is a PF_SYSTEM control socket");
+        }
+        let mut name = [0u8; libc::IFNAMSIZ];
+        let mut len = name.len() as libc::socklen_t;
+        let rs = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SYSPROTO_CONTROL,
+                libc::UTUN_OPT_IFNAME,
+                name.as_mut_ptr() as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rs < 0 || !name.starts_with(b"utun") {
+            return invalid("socket is not connected to a utun interface");
+        }
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        if file_type != libc::S_IFCHR {
+            return invalid("not a character device, expected /dev/net/tun");
+        }
+        let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(fd, libc::TUNGETIFF as _, &mut req) } < 0 {
+            return invalid("not attached to a TUN/TAP interface");
+        }
+    }
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "tvos",
+        target_os = "linux",
+        target_os = "android"
+    )))]
+    let _ = (file_type, invalid);
+    Ok(())
+}
 impl DeviceImpl {
+    /// Like [`from_fd`](Self::from_fd), but first validates the fd with [`check_tun_fd`].
+    ///
+    /// # Safety
+    /// The fd passed in must be an owned file descriptor.
+    pub(crate) unsafe fn from_fd_checked(fd: RawFd) -> io::Result<Self> {
+        check_tun_fd(fd)?;
+        DeviceImpl::from_fd(fd)
+    }
     /// # Safety
     /// The fd passed in must be an owned file descriptor; in particular, it must be open.
     pub(crate) unsafe fn from_fd(fd: RawFd) -> io::Result<Self> {
@@ -75,10 +143,27 @@ impl DeviceImpl {
     /// Recv a packet from tun device
     #[inline]
     pub(crate) fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.tun.recv(buf)
+        #[cfg(target_os = "macos")]
+        return self.recv_with_len(buf).map(|(len, _)| len);
+        #[cfg(not(target_os = "macos"))]
+        {
+            let result = self.tun.recv(buf);
+            crate::trace::dev_event!(trace, self, ?result, "read");
+            result
+        }
+    }
+    /// Recv a packet, returning the number of bytes copied and the length of the packet,
+    /// which only differ for `feth` TAP devices under `TruncatePolicy::Truncate`
+    #[cfg(target_os = "macos")]
+    pub(crate) fn recv_with_len(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let result = self.tun.recv_with_len(buf);
+        crate::trace::dev_event!(trace, self, ?result, "read");
+        if let Ok((len, packet_len)) = &result {
+            self.count_truncated(packet_len > len);
+        }
+        result
     }
     #[inline]
-    #[allow(dead_code)]
     pub(crate) fn recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
         self.tun.recv_uninit(buf)
     }
@@ -86,16 +171,40 @@ impl DeviceImpl {
     pub(crate) fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
         self.tun.recv_vectored(bufs)
     }
+    #[inline]
+    pub(crate) fn recv_vectored_uninit(
+        &self,
+        bufs: &mut [&mut [MaybeUninit<u8>]],
+    ) -> io::Result<usize> {
+        self.tun.recv_vectored_uninit(bufs)
+    }
 
     /// Send a packet to tun device
     #[inline]
     pub(crate) fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.tun.send(buf)
+        let result = self.tun.send(buf);
+        crate::trace::dev_event!(trace, self, ?result, "write");
+        result
     }
     #[inline]
     pub(crate) fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
         self.tun.send_vectored(bufs)
     }
+    /// Discards all packets waiting to be received and returns how many were dropped.
+    ///
+    /// Packets are read into a small scratch buffer rather than copied out in full, and the
+    /// call returns as soon as the queue is empty, even on a blocking device. Packets left
+    /// behind by `AsyncDevice::peek` are discarded as well.
+    pub fn drain(&self) -> io::Result<usize> {
+        #[allow(unused_mut)]
+        let mut count = self.tun.drain()?;
+        #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+        {
+            count += self.pushback.clear();
+        }
+        crate::trace::dev_event!(debug, self, count, "drained");
+        Ok(count)
+    }
     #[cfg(feature = "interruptible")]
     pub(crate) fn read_interruptible(
         &self,
@@ -182,11 +291,42 @@ impl DeviceImpl {
             .collect())
     }
 }
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos"
+))]
+impl DeviceImpl {
+    /// Runs the receive hooks over each of the `num` packets read into `bufs`: answers ICMP
+    /// echo requests, passes the packets to the packet tap, then runs the MSS clamp and the
+    /// middleware chain.
+    ///
+    /// Packets answered or dropped are moved behind the others, keeping the order of the
+    /// rest. Returns how many are left.
+    pub(crate) fn inbound_segments<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        offset: usize,
+        num: usize,
+    ) -> usize {
+        let mut kept = 0;
+        for i in 0..num {
+            let buf = &mut bufs[i].as_mut()[offset..];
+            let Some(len) = self.inbound(buf, sizes[i]) else {
+                continue;
+            };
+            bufs.swap(kept, i);
+            sizes[kept] = len;
+            kept += 1;
+        }
+        kept
+    }
+}
 #[cfg(any(target_os = "macos", target_os = "ios", target_os = "tvos",))]
 impl DeviceImpl {
     /// Returns whether the TUN device is set to ignore packet information (PI).
     ///
-    /// When enabled, the device does not prepend the `struct tun_pi` header
+    /// When enabled, the device does not prepend the 4-byte address family header
     /// to packets, which can simplify packet processing in some cases.
     ///
     /// # Returns
@@ -200,9 +340,9 @@ impl DeviceImpl {
     }
     /// Sets whether the TUN device should ignore packet information (PI).
     ///
-    /// When `ignore_packet_info` is set to `true`, the TUN device does not
-    /// prepend the `struct tun_pi` header to packets. This can be useful
-    /// if the additional metadata is not needed.
+    /// When `ignore_packet_info` is set to `true`, the TUN device does not prepend the
+    /// address family header ([`AfHeader`](crate::framing::AfHeader)) to packets. This can be
+    /// useful if the additional metadata is not needed.
     ///
     /// # Parameters
     /// * `ign` - If `true`, the TUN device will ignore packet information.
@@ -229,18 +369,17 @@ pub(crate) unsafe fn ctl() -> io::Result<Fd> {
     _ = fd.set_cloexec();
     Ok(fd)
 }
-#[cfg(any(
-    all(target_os = "linux", not(target_env = "ohos")),
-    target_os = "freebsd",
-    target_os = "openbsd",
-    target_os = "netbsd",
-))]
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd",))]
 pub(crate) unsafe fn ctl_v6() -> io::Result<Fd> {
-    Fd::new(libc::socket(AF_INET6, SOCK_DGRAM | libc::SOCK_CLOEXEC, 0))
+    Fd::new(libc::socket(
+        libc::AF_INET6,
+        SOCK_DGRAM | libc::SOCK_CLOEXEC,
+        0,
+    ))
 }
 #[cfg(target_os = "macos")]
 pub(crate) unsafe fn ctl_v6() -> io::Result<Fd> {
-    let fd = Fd::new(libc::socket(AF_INET6, SOCK_DGRAM, 0))?;
+    let fd = Fd::new(libc::socket(libc::AF_INET6, SOCK_DGRAM, 0))?;
     _ = fd.set_cloexec();
     Ok(fd)
 }
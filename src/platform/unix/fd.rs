@@ -57,6 +57,26 @@ impl Fd {
             Ok(())
         }
     }
+    /// Whether a read would not block right now, polled without waiting.
+    pub(crate) fn is_readable(&self) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.inner,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            match unsafe { libc::poll(&mut fd, 1, 0) } {
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+                0 => return Ok(false),
+                _ => return Ok(fd.revents & libc::POLLIN != 0),
+            }
+        }
+    }
     /// Enable non-blocking mode
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         let mut nonblocking = nonblocking as libc::c_int;
@@ -103,14 +123,6 @@ impl Fd {
         Ok(amount as usize)
     }
     #[inline]
-    #[cfg(any(
-        target_os = "macos",
-        target_os = "ios",
-        target_os = "tvos",
-        target_os = "freebsd",
-        target_os = "openbsd",
-        target_os = "netbsd"
-    ))]
     pub(crate) fn readv_raw(&self, bufs: &mut [libc::iovec]) -> io::Result<usize> {
         if bufs.len() > max_iov() {
             return Err(io::Error::from(io::ErrorKind::InvalidInput));
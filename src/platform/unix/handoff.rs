@@ -0,0 +1,137 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+const CMSG_BUF_LEN: usize = 64;
+
+/// Sends a device file descriptor to another process over a Unix domain socket (`SCM_RIGHTS`).
+///
+/// This is the hand-over path for hot-standby takeover on platforms where an interface
+/// cannot be re-opened by name (e.g. `utun` on macOS, or the BSDs): the old process sends
+/// the fd, and the new process receives it with [`recv_fd`] and wraps it with
+/// [`SyncDevice::from_fd`](crate::SyncDevice::from_fd). The interface, its addresses and
+/// routes stay in place as long as one of the processes holds the fd.
+///
+/// The caller keeps ownership of `fd`; it can be closed once the peer has received it.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::os::fd::AsRawFd;
+/// use std::os::unix::net::UnixStream;
+/// use tun_rs::DeviceBuilder;
+///
+/// let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+/// let stream = UnixStream::connect("/run/my-vpn/takeover.sock")?;
+/// tun_rs::send_fd(&stream, dev.as_raw_fd())?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn send_fd(socket: &UnixStream, fd: RawFd) -> io::Result<()> {
+    // At least one byte of regular data must accompany the control message.
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let mut cmsg_buf = [0u64; CMSG_BUF_LEN / 8];
+    unsafe {
+        let space = libc::CMSG_SPACE(size_of::<RawFd>() as _) as usize;
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as _) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+
+        if libc::sendmsg(socket.as_raw_fd(), &msg, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Receives a device file descriptor sent with [`send_fd`].
+///
+/// The returned fd can be turned into a device with
+/// [`SyncDevice::from_fd`](crate::SyncDevice::from_fd).
+///
+/// # Example
+///
+/// ```no_run
+/// use std::os::fd::IntoRawFd;
+/// use std::os::unix::net::UnixListener;
+/// use tun_rs::SyncDevice;
+///
+/// let listener = UnixListener::bind("/run/my-vpn/takeover.sock")?;
+/// let (stream, _) = listener.accept()?;
+/// let fd = tun_rs::recv_fd(&stream)?;
+/// // SAFETY: the fd was just received and is owned by us.
+/// let dev = unsafe { SyncDevice::from_fd(fd.into_raw_fd())? };
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn recv_fd(socket: &UnixStream) -> io::Result<OwnedFd> {
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let mut cmsg_buf = [0u64; CMSG_BUF_LEN / 8];
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = CMSG_BUF_LEN as _;
+
+        let n = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd);
+                let fd = OwnedFd::from_raw_fd(fd);
+                let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFD);
+                if flags >= 0 {
+                    libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, flags | libc::FD_CLOEXEC);
+                }
+                return Ok(fd);
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "no file descriptor received",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::fd::IntoRawFd;
+
+    #[test]
+    fn pass_fd_over_socketpair() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut c, d) = UnixStream::pair().unwrap();
+        send_fd(&a, d.as_raw_fd()).unwrap();
+        drop(d);
+        let received = recv_fd(&b).unwrap();
+        let mut d = unsafe { UnixStream::from_raw_fd(received.into_raw_fd()) };
+        c.write_all(b"ok").unwrap();
+        let mut buf = [0u8; 2];
+        d.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ok");
+    }
+}
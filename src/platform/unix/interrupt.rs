@@ -636,4 +636,10 @@ impl InterruptEvent {
     fn as_event_fd(&self) -> libc::c_int {
         self.read_fd.as_raw_fd()
     }
+    /// Duplicates the read end of the pipe, which stays readable while the event is triggered.
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) fn try_clone_event_fd(&self) -> io::Result<std::os::fd::OwnedFd> {
+        // SAFETY: `read_fd` is open for the lifetime of `self`.
+        unsafe { std::os::fd::BorrowedFd::borrow_raw(self.as_event_fd()) }.try_clone_to_owned()
+    }
 }
@@ -26,6 +26,8 @@ mod interrupt;
 pub use interrupt::InterruptEvent;
 mod tun;
 pub(crate) use self::tun::Tun;
+mod handoff;
+pub use handoff::{recv_fd, send_fd};
 
 pub(crate) mod device;
 
@@ -45,6 +47,21 @@ pub struct DeviceImpl {
     pub(crate) tun: Tun,
     #[allow(dead_code)]
     pub(crate) op_lock: std::sync::RwLock<()>,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
 }
 #[cfg(all(
     unix,
@@ -62,6 +79,25 @@ impl DeviceImpl {
         Ok(Self {
             tun,
             op_lock: std::sync::RwLock::new(()),
+            egress_limit: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
         })
     }
+    /// Returns the backend of this device, which is always [`crate::BackendKind::Fd`] here.
+    pub fn backend_kind(&self) -> crate::BackendKind {
+        crate::BackendKind::Fd
+    }
 }
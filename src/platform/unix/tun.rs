@@ -10,6 +10,7 @@ use crate::platform::unix::Fd;
 use crate::PACKET_INFORMATION_LENGTH as PIL;
 use bytes::buf::UninitSlice;
 use std::io::{self, IoSlice, IoSliceMut};
+use std::mem::MaybeUninit;
 use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
 #[cfg(any(
     target_os = "macos",
@@ -31,15 +32,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
     target_os = "netbsd",
 ))]
 pub(crate) fn is_ipv6(buf: &[u8]) -> std::io::Result<bool> {
-    use std::io::{Error, ErrorKind::InvalidData};
-    if buf.is_empty() {
-        return Err(Error::new(InvalidData, "Zero-length data"));
-    }
-    match buf[0] >> 4 {
-        4 => Ok(false),
-        6 => Ok(true),
-        p => Err(Error::new(InvalidData, format!("IP version {p}"))),
-    }
+    Ok(crate::framing::ip_version(buf)? == crate::framing::IpVersion::V6)
 }
 #[cfg(any(
     target_os = "macos",
@@ -49,36 +42,66 @@ pub(crate) fn is_ipv6(buf: &[u8]) -> std::io::Result<bool> {
     target_os = "freebsd",
     target_os = "netbsd",
 ))]
-pub(crate) fn generate_packet_information(_ipv6: bool) -> [u8; PIL] {
-    #[cfg(any(target_os = "linux", target_os = "android"))]
-    const TUN_PROTO_IP6: [u8; PIL] = (libc::ETH_P_IPV6 as u32).to_be_bytes();
-    #[cfg(any(target_os = "linux", target_os = "android"))]
-    const TUN_PROTO_IP4: [u8; PIL] = (libc::ETH_P_IP as u32).to_be_bytes();
-
-    #[cfg(any(
-        target_os = "macos",
-        target_os = "ios",
-        target_os = "tvos",
-        target_os = "openbsd",
-        target_os = "freebsd",
-        target_os = "netbsd",
-    ))]
-    const TUN_PROTO_IP6: [u8; PIL] = (libc::AF_INET6 as u32).to_be_bytes();
-    #[cfg(any(
-        target_os = "macos",
-        target_os = "ios",
-        target_os = "tvos",
-        target_os = "openbsd",
-        target_os = "freebsd",
-        target_os = "netbsd",
-    ))]
-    const TUN_PROTO_IP4: [u8; PIL] = (libc::AF_INET as u32).to_be_bytes();
-
-    if _ipv6 {
-        TUN_PROTO_IP6
-    } else {
-        TUN_PROTO_IP4
+pub(crate) fn generate_packet_information(ipv6: bool) -> [u8; PIL] {
+    use crate::framing::{AfHeader, IpVersion};
+    let version = if ipv6 { IpVersion::V6 } else { IpVersion::V4 };
+    AfHeader::new(version).to_bytes()
+}
+/// `iovec`s for `head` followed by the non-empty slices of `bufs`.
+fn uninit_iovecs(
+    head: Option<&mut [MaybeUninit<u8>]>,
+    bufs: &mut [&mut [MaybeUninit<u8>]],
+) -> Vec<libc::iovec> {
+    head.into_iter()
+        .chain(bufs.iter_mut().map(|buf| &mut **buf))
+        .filter(|buf| !buf.is_empty())
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut _,
+            iov_len: buf.len(),
+        })
+        .collect()
+}
+/// The header for the packet in `bufs`, whose IP version is in the first byte of the first
+/// non-empty slice.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "openbsd",
+    target_os = "freebsd",
+    target_os = "netbsd",
+))]
+fn packet_information_for(bufs: &[IoSlice<'_>]) -> io::Result<[u8; PIL]> {
+    let first = bufs
+        .iter()
+        .find(|b| !b.is_empty())
+        .map_or(&[][..], |b| &**b);
+    Ok(generate_packet_information(is_ipv6(first)?))
+}
+/// Puts the packet information `head` in front of `bufs`, for the `readv`/`writev` that
+/// hides the header from the application.
+///
+/// Empty slices are left out, so that they neither count against `IOV_MAX` nor shift the
+/// packet: the application's first non-empty slice always receives the first byte of the
+/// IP packet, as on Linux without packet information.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "openbsd",
+    target_os = "freebsd",
+    target_os = "netbsd",
+))]
+fn with_packet_information<B: std::ops::Deref<Target = [u8]>>(
+    head: B,
+    bufs: impl Iterator<Item = B>,
+) -> io::Result<Vec<B>> {
+    let mut iov = vec![head];
+    iov.extend(bufs.filter(|buf| !buf.is_empty()));
+    if iov.len() > crate::platform::unix::fd::max_iov() {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput));
     }
+    Ok(iov)
 }
 
 pub(crate) struct Tun {
@@ -112,6 +135,23 @@ impl Tun {
     pub fn is_nonblocking(&self) -> io::Result<bool> {
         self.fd.is_nonblocking()
     }
+    /// Reads and discards the packets queued on the fd, returning how many.
+    ///
+    /// Each packet is read into a small scratch buffer, large enough for the packet
+    /// information or virtio-net header; the kernel drops the part that does not fit.
+    pub(crate) fn drain(&self) -> io::Result<usize> {
+        let mut scratch = [0u8; 64];
+        let mut count = 0;
+        while self.fd.is_readable()? {
+            match self.fd.read(&mut scratch) {
+                Ok(_) => count += 1,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(count)
+    }
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.fd.set_nonblocking(nonblocking)
     }
@@ -170,19 +210,9 @@ impl Tun {
     #[inline]
     pub(crate) fn send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
         if self.ignore_packet_info() {
-            if crate::platform::unix::fd::max_iov() - 1 < bufs.len() {
-                return Err(io::Error::from(io::ErrorKind::InvalidInput));
-            }
-            let buf = bufs
-                .iter()
-                .find(|b| !b.is_empty())
-                .map_or(&[][..], |b| &**b);
-            let ipv6 = is_ipv6(buf)?;
-            let head = generate_packet_information(ipv6);
-            let mut iov_block = Vec::with_capacity(bufs.len() + 1);
-            iov_block.push(IoSlice::new(&head));
-            iov_block.extend(bufs.iter().copied());
-            let len = self.fd.writev(&iov_block)?;
+            let head = packet_information_for(bufs)?;
+            let iov = with_packet_information(IoSlice::new(&head), bufs.iter().copied())?;
+            let len = self.fd.writev(&iov)?;
             Ok(len.saturating_sub(PIL))
         } else {
             self.fd.writev(bufs)
@@ -261,6 +291,26 @@ impl Tun {
             self.fd.read_uninit(buf)
         }
     }
+    /// Reads a packet into `buf` and its packet information header into the returned array,
+    /// whatever [`ignore_packet_info`](Self::ignore_packet_info) says.
+    ///
+    /// The caller must know that the fd delivers the header.
+    #[allow(dead_code)]
+    pub(crate) fn recv_with_head(
+        &self,
+        buf: &mut [u8],
+    ) -> io::Result<(usize, [u8; crate::PACKET_INFORMATION_LENGTH])> {
+        let mut head = [0u8; crate::PACKET_INFORMATION_LENGTH];
+        let bufs = &mut [IoSliceMut::new(&mut head), IoSliceMut::new(buf)];
+        let len = self.fd.readv(bufs)?;
+        if len < head.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "packet shorter than the packet information header",
+            ));
+        }
+        Ok((len - head.len(), head))
+    }
     #[cfg(not(any(
         target_os = "macos",
         target_os = "ios",
@@ -284,21 +334,39 @@ impl Tun {
     #[inline]
     pub(crate) fn recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
         if self.ignore_packet_info() {
-            if crate::platform::unix::fd::max_iov() - 1 < bufs.len() {
-                return Err(io::Error::from(io::ErrorKind::InvalidInput));
-            }
             let mut head = [0u8; PIL];
-            let mut iov_block = Vec::with_capacity(bufs.len() + 1);
-            iov_block.push(IoSliceMut::new(&mut head));
-            for buf in bufs.iter_mut() {
-                iov_block.push(IoSliceMut::new(buf.as_mut()));
-            }
-            let len = self.fd.readv(&mut iov_block)?;
+            let mut iov = with_packet_information(
+                IoSliceMut::new(&mut head),
+                bufs.iter_mut().map(|buf| IoSliceMut::new(buf)),
+            )?;
+            let len = self.fd.readv(&mut iov)?;
             Ok(len.saturating_sub(PIL))
         } else {
             self.fd.readv(bufs)
         }
     }
+    /// Like `recv_vectored`, into buffers that may be uninitialized.
+    pub(crate) fn recv_vectored_uninit(
+        &self,
+        bufs: &mut [&mut [MaybeUninit<u8>]],
+    ) -> io::Result<usize> {
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "openbsd",
+            target_os = "freebsd",
+            target_os = "netbsd",
+        ))]
+        if self.ignore_packet_info() {
+            let mut head = [MaybeUninit::uninit(); PIL];
+            let mut iov = uninit_iovecs(Some(&mut head), bufs);
+            let len = self.fd.readv_raw(&mut iov)?;
+            return Ok(len.saturating_sub(PIL));
+        }
+        let mut iov = uninit_iovecs(None, bufs);
+        self.fd.readv_raw(&mut iov)
+    }
     #[cfg(any(
         target_os = "macos",
         target_os = "ios",
@@ -406,18 +474,12 @@ impl Tun {
         timeout: Option<std::time::Duration>,
     ) -> io::Result<usize> {
         if self.ignore_packet_info() {
-            if crate::platform::unix::fd::max_iov() - 1 < bufs.len() {
-                return Err(io::Error::from(io::ErrorKind::InvalidInput));
-            }
             let mut head = [0u8; PIL];
-            let mut iov_block = Vec::with_capacity(bufs.len() + 1);
-            iov_block.push(IoSliceMut::new(&mut head));
-            for buf in bufs.iter_mut() {
-                iov_block.push(IoSliceMut::new(buf.as_mut()));
-            }
-            let len = self
-                .fd
-                .readv_interruptible(&mut iov_block, event, timeout)?;
+            let mut iov = with_packet_information(
+                IoSliceMut::new(&mut head),
+                bufs.iter_mut().map(|buf| IoSliceMut::new(buf)),
+            )?;
+            let len = self.fd.readv_interruptible(&mut iov, event, timeout)?;
             Ok(len.saturating_sub(PIL))
         } else {
             self.fd.readv_interruptible(bufs, event, timeout)
@@ -514,19 +576,9 @@ impl Tun {
         event: &crate::InterruptEvent,
     ) -> io::Result<usize> {
         if self.ignore_packet_info() {
-            if crate::platform::unix::fd::max_iov() - 1 < bufs.len() {
-                return Err(io::Error::from(io::ErrorKind::InvalidInput));
-            }
-            let buf = bufs
-                .iter()
-                .find(|b| !b.is_empty())
-                .map_or(&[][..], |b| &**b);
-            let ipv6 = is_ipv6(buf)?;
-            let head = generate_packet_information(ipv6);
-            let mut iov_block = Vec::with_capacity(bufs.len() + 1);
-            iov_block.push(IoSlice::new(&head));
-            iov_block.extend(bufs.iter().copied());
-            let len = self.fd.writev_interruptible(&iov_block, event)?;
+            let head = packet_information_for(bufs)?;
+            let iov = with_packet_information(IoSlice::new(&head), bufs.iter().copied())?;
+            let len = self.fd.writev_interruptible(&iov, event)?;
             Ok(len.saturating_sub(PIL))
         } else {
             self.fd.writev_interruptible(bufs, event)
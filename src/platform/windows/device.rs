@@ -1,17 +1,24 @@
 use crate::builder::DeviceConfig;
 use crate::platform::windows::dns;
-use crate::platform::windows::netsh;
 use crate::platform::windows::tap::TapDevice;
-use crate::platform::windows::tun::{check_adapter_if_orphaned_devices, TunDevice};
-use crate::platform::ETHER_ADDR_LEN;
+use crate::platform::windows::tun::{
+    check_adapter_if_orphaned_devices, is_windows_seven, set_adapter_description, DllSource,
+    TunDevice,
+};
+use crate::platform::windows::NetworkCategory;
+use crate::platform::{Cleanup, OperState, ETHER_ADDR_LEN};
 use crate::{Layer, ToIpv4Address, ToIpv4Netmask, ToIpv6Address, ToIpv6Netmask};
 use bytes::buf::UninitSlice;
 use getifaddrs::Interface;
 use ipnet::IpNet;
 use std::collections::HashSet;
 use std::io;
+use std::io::{IoSlice, IoSliceMut};
 use std::net::IpAddr;
+use std::os::windows::io::{OwnedHandle, RawHandle};
+use std::path::PathBuf;
 use std::sync::RwLock;
+use std::time::Instant;
 use windows_sys::core::GUID;
 use windows_sys::Win32::NetworkManagement::Ndis::NET_LUID_LH;
 
@@ -22,6 +29,28 @@ pub(crate) const GUID_NETWORK_ADAPTER: GUID = GUID {
     data4: [0xbf, 0xc1, 0x08, 0x00, 0x2b, 0xe1, 0x03, 0x18],
 };
 
+/// Hardware id of the tap-windows6 driver.
+const HARDWARE_ID: &str = "tap0901";
+
+/// Version of the driver backing a device, see [`DeviceImpl::driver_version`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DriverVersion {
+    pub major: u32,
+    pub minor: u32,
+    /// Whether the driver is a debug build, only ever set for tap-windows6.
+    pub debug: bool,
+}
+
+impl std::fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.debug {
+            write!(f, " (debug)")?;
+        }
+        Ok(())
+    }
+}
+
 pub(crate) enum Driver {
     Tun(TunDevice),
     Tap(TapDevice),
@@ -31,6 +60,35 @@ pub(crate) enum Driver {
 pub struct DeviceImpl {
     lock: RwLock<()>,
     pub(crate) driver: Driver,
+    pub(crate) cleanup: Cleanup,
+    pub(crate) egress_limit: crate::platform::RateLimiter,
+    pub(crate) frame_validation: crate::ethernet::FrameValidator,
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) pushback: crate::async_device::Pushback,
+    #[cfg(feature = "icmp_responder")]
+    pub(crate) icmp_responder: crate::platform::IcmpResponder,
+    #[cfg(feature = "pcap")]
+    pub(crate) packet_tap: crate::pcap::PacketTap,
+    #[cfg(feature = "counters")]
+    pub(crate) counters: crate::counters::Counters,
+    #[cfg(feature = "middleware")]
+    pub(crate) middleware: crate::middleware::MiddlewareChain,
+    #[cfg(feature = "mss_clamp")]
+    pub(crate) mss_clamp: crate::mss_clamp::MssClamp,
+    #[cfg(feature = "tracing")]
+    pub(crate) span: crate::trace::DeviceSpan,
+}
+
+impl Drop for DeviceImpl {
+    fn drop(&mut self) {
+        // Wintun removes an adapter this process created when it is closed.
+        let destroy = self
+            .run_cleanup()
+            .is_some_and(|policy| policy.destroy_device);
+        if let Driver::Tap(tap) = &mut self.driver {
+            tap.set_delete_on_drop(destroy);
+        }
+    }
 }
 
 impl DeviceImpl {
@@ -42,13 +100,32 @@ impl DeviceImpl {
             .into_iter()
             .map(|v| v.description)
             .collect();
+        let attach_existing = config.attach_existing.unwrap_or(false);
+        let cleanup = Cleanup::new(
+            config.cleanup_policy,
+            config.persist.unwrap_or(false) || attach_existing,
+        );
+        if let Some(name) = config.dev_name.as_ref().filter(|_| attach_existing) {
+            if !interfaces.contains(name) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("The network adapter [{name}] does not exist."),
+                ));
+            }
+        }
         let device = if layer == Layer::L3 {
             let wintun_log = config.wintun_log.unwrap_or(false);
-            let wintun_file = config.wintun_file.as_deref().unwrap_or("wintun.dll");
+            let trust = config.wintun_trust.clone().unwrap_or_default();
+            let dll = DllSource {
+                file: config.wintun_file.as_deref(),
+                search_paths: config.wintun_search_paths.as_deref().unwrap_or_default(),
+                trust: &trust,
+            };
             let ring_capacity = config.ring_capacity.unwrap_or(0x20_0000);
             let delete_driver = config.delete_driver.unwrap_or(false);
+            let compat_win7 = config.compat_win7.unwrap_or_else(is_windows_seven);
             let mut attempts = 0;
-            let tun_device = loop {
+            let mut tun_device = loop {
                 let default_name = format!("tun{count}");
                 count += 1;
                 let name = config.dev_name.as_deref().unwrap_or(&default_name);
@@ -59,11 +136,12 @@ impl DeviceImpl {
                     }
 
                     // Resolves an issue where there are orphaned adapters. fixes #33
-                    let is_orphaned_adapter = check_adapter_if_orphaned_devices(name);
+                    let is_orphaned_adapter =
+                        !attach_existing && check_adapter_if_orphaned_devices(name, compat_win7);
                     if !is_orphaned_adapter {
                         // Try to open an existing Wintun adapter.
                         break TunDevice::open(
-                            wintun_file,
+                            &dll,
                             name,
                             ring_capacity,
                             delete_driver,
@@ -71,17 +149,33 @@ impl DeviceImpl {
                         )?;
                     }
                 }
-                let description = config.description.as_deref().unwrap_or(name);
+                let tunnel_type = config
+                    .tunnel_type
+                    .as_deref()
+                    .or(config.description.as_deref())
+                    .unwrap_or(name);
                 match TunDevice::create(
-                    wintun_file,
+                    &dll,
                     name,
-                    description,
+                    tunnel_type,
                     config.device_guid,
                     ring_capacity,
                     delete_driver,
                     wintun_log,
                 ) {
-                    Ok(tun_device) => break tun_device,
+                    Ok(tun_device) => {
+                        // Wintun describes the adapter by its tunnel type, so a separate
+                        // description has to be set afterwards.
+                        if let (Some(_), Some(description)) =
+                            (&config.tunnel_type, &config.description)
+                        {
+                            if let Err(e) = set_adapter_description(name, description, compat_win7)
+                            {
+                                log::warn!("failed to set the description of {name}: {e}");
+                            }
+                        }
+                        break tun_device;
+                    }
                     Err(e) => {
                         if attempts > 3 {
                             Err(e)?
@@ -90,16 +184,35 @@ impl DeviceImpl {
                     }
                 }
             };
+            tun_device.set_truncate_policy(config.truncate_policy.unwrap_or_default());
+            tun_device.set_compat_win7(compat_win7);
+            tun_device.set_packet_information(config.packet_information.unwrap_or(false));
 
             DeviceImpl {
                 lock: RwLock::new(()),
+                cleanup,
+                egress_limit: Default::default(),
+                frame_validation: Default::default(),
+                #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+                pushback: Default::default(),
+                #[cfg(feature = "icmp_responder")]
+                icmp_responder: Default::default(),
+                #[cfg(feature = "pcap")]
+                packet_tap: Default::default(),
+                #[cfg(feature = "counters")]
+                counters: Default::default(),
+                #[cfg(feature = "middleware")]
+                middleware: Default::default(),
+                #[cfg(feature = "mss_clamp")]
+                mss_clamp: Default::default(),
+                #[cfg(feature = "tracing")]
+                span: Default::default(),
                 driver: Driver::Tun(tun_device),
             }
         } else if layer == Layer::L2 {
-            const HARDWARE_ID: &str = "tap0901";
-            let persist = config.persist.unwrap_or(false);
+            let persist = config.persist.unwrap_or(false) || attach_existing;
 
-            let tap = loop {
+            let mut tap = loop {
                 let default_name = format!("tap{count}");
                 let name = config.dev_name.as_deref().unwrap_or(&default_name);
                 if interfaces.contains(name) {
@@ -111,8 +224,9 @@ impl DeviceImpl {
                             "The network adapter [{name}] already exists."
                         )))?
                     }
-                    let tap =
-                        TapDevice::open(HARDWARE_ID, name, persist, config.mac_address.as_ref())?;
+                    // Attaching leaves the MAC address alone, as on the other platforms.
+                    let mac = config.mac_address.as_ref().filter(|_| !attach_existing);
+                    let tap = TapDevice::open(HARDWARE_ID, name, persist, mac)?;
                     break tap;
                 }
                 let tap = TapDevice::create(HARDWARE_ID, persist, config.mac_address.as_ref())?;
@@ -123,8 +237,26 @@ impl DeviceImpl {
                 }
                 break tap;
             };
+            tap.set_truncate_policy(config.truncate_policy.unwrap_or_default());
             DeviceImpl {
                 lock: RwLock::new(()),
+                cleanup,
+                egress_limit: Default::default(),
+                frame_validation: Default::default(),
+                #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+                pushback: Default::default(),
+                #[cfg(feature = "icmp_responder")]
+                icmp_responder: Default::default(),
+                #[cfg(feature = "pcap")]
+                packet_tap: Default::default(),
+                #[cfg(feature = "counters")]
+                counters: Default::default(),
+                #[cfg(feature = "middleware")]
+                middleware: Default::default(),
+                #[cfg(feature = "mss_clamp")]
+                mss_clamp: Default::default(),
+                #[cfg(feature = "tracing")]
+                span: Default::default(),
                 driver: Driver::Tap(tap),
             }
         } else {
@@ -132,11 +264,38 @@ impl DeviceImpl {
         };
         Ok(device)
     }
-    #[cfg(any(
-        feature = "interruptible",
-        feature = "async_tokio",
-        feature = "async_io"
-    ))]
+    /// Wraps a borrowed tap-windows6 handle, which is left open on drop.
+    ///
+    /// # Safety
+    /// See [`TapDevice::borrow_raw`].
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub(crate) unsafe fn borrow_raw_handle(handle: RawHandle) -> io::Result<Self> {
+        let mut tap = TapDevice::borrow_raw(HARDWARE_ID, handle)?;
+        tap.set_truncate_policy(Default::default());
+        Ok(DeviceImpl {
+            lock: RwLock::new(()),
+            // Someone else's device, nothing is undone.
+            cleanup: Cleanup::new(None, true),
+            egress_limit: Default::default(),
+            frame_validation: Default::default(),
+            #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+            pushback: Default::default(),
+            #[cfg(feature = "icmp_responder")]
+            icmp_responder: Default::default(),
+            #[cfg(feature = "pcap")]
+            packet_tap: Default::default(),
+            #[cfg(feature = "counters")]
+            counters: Default::default(),
+            #[cfg(feature = "middleware")]
+            middleware: Default::default(),
+            #[cfg(feature = "mss_clamp")]
+            mss_clamp: Default::default(),
+            #[cfg(feature = "tracing")]
+            span: Default::default(),
+            driver: Driver::Tap(tap),
+        })
+    }
+    #[cfg(feature = "interruptible")]
     pub(crate) fn wait_readable_interruptible(
         &self,
         event: &crate::platform::windows::InterruptEvent,
@@ -147,6 +306,36 @@ impl DeviceImpl {
             Driver::Tun(tun) => tun.wait_readable_interruptible(&event.handle, timeout),
         }
     }
+    /// The events of the pending tap-windows6 read and write, signaled on their completion, or
+    /// `None` for Wintun.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn tap_overlapped_events(
+        &self,
+    ) -> Option<(
+        std::sync::Arc<std::os::windows::io::OwnedHandle>,
+        std::sync::Arc<std::os::windows::io::OwnedHandle>,
+    )> {
+        match &self.driver {
+            Driver::Tap(tap) => Some((tap.read_event(), tap.write_event())),
+            Driver::Tun(_) => None,
+        }
+    }
+    /// Owned duplicates of the Wintun read event and shutdown event, to be waited on together
+    /// until either is signaled. Query again after each wait, as the read event changes with
+    /// the session.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn wintun_read_wait_events(
+        &self,
+    ) -> io::Result<(
+        std::os::windows::io::OwnedHandle,
+        std::os::windows::io::OwnedHandle,
+    )> {
+        let _guard = self.lock.read().unwrap();
+        match &self.driver {
+            Driver::Tun(tun) => tun.read_wait_events(),
+            Driver::Tap(_) => Err(io::Error::from(io::ErrorKind::Unsupported)),
+        }
+    }
     #[cfg(feature = "interruptible")]
     pub(crate) fn read_interruptible(
         &self,
@@ -154,30 +343,97 @@ impl DeviceImpl {
         event: &crate::InterruptEvent,
         timeout: Option<std::time::Duration>,
     ) -> io::Result<usize> {
-        loop {
-            self.wait_readable_interruptible(event, timeout)?;
-            match self.try_recv(buf) {
-                Ok(rs) => {
-                    return Ok(rs);
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    continue;
-                }
-                Err(e) => return Err(e),
-            }
-        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        self.recv_deadline(buf, Some(&event.handle), deadline)
+    }
+    /// Reads a whole packet into one buffer the size of `bufs` and scatters it, since
+    /// neither driver reads into several buffers.
+    #[cfg(feature = "interruptible")]
+    pub(crate) fn readv_interruptible(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        event: &crate::InterruptEvent,
+        timeout: Option<std::time::Duration>,
+    ) -> io::Result<usize> {
+        let mut buf = vec![0u8; bufs.iter().map(|buf| buf.len()).sum()];
+        let len = self.read_interruptible(&mut buf, event, timeout)?;
+        crate::platform::scatter(&buf[..len], bufs);
+        Ok(len)
     }
     /// Recv a packet from tun device
     pub(crate) fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        match &self.driver {
+        self.recv_with_len(buf).map(|(len, _)| len)
+    }
+    /// Recv a packet, returning the number of bytes copied and the length of the packet
+    pub(crate) fn recv_with_len(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let result = match &self.driver {
             Driver::Tap(tap) => tap.read(buf),
             Driver::Tun(tun) => tun.recv(buf),
-        }
+        };
+        crate::trace::dev_event!(trace, self, ?result, "read");
+        self.received_len(result)
+    }
+    /// Recv a packet, failing with `TimedOut` once `deadline` passes
+    pub(crate) fn recv_deadline(
+        &self,
+        buf: &mut [u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        let result = match &self.driver {
+            Driver::Tap(tap) => tap.read_deadline(buf, interrupt_event, deadline),
+            Driver::Tun(tun) => tun.recv_deadline(buf, interrupt_event, deadline),
+        };
+        self.received_len(result).map(|(len, _)| len)
     }
     pub(crate) fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        match &self.driver {
+        let result = match &self.driver {
             Driver::Tap(tap) => tap.try_read(buf),
             Driver::Tun(tun) => tun.try_recv(buf),
+        };
+        crate::trace::dev_event!(trace, self, ?result, "try read");
+        self.received_len(result).map(|(len, _)| len)
+    }
+    /// Counts a packet cut under `TruncatePolicy::Truncate`.
+    fn received_len(&self, result: io::Result<(usize, usize)>) -> io::Result<(usize, usize)> {
+        if let Ok((len, packet_len)) = &result {
+            self.count_truncated(packet_len > len);
+        }
+        result
+    }
+    /// Discards all packets waiting to be received and returns how many were dropped.
+    ///
+    /// With wintun the packets are released from the receive ring without being copied; the
+    /// TAP driver has no such shortcut, so its packets are read into a scratch buffer. The
+    /// call returns as soon as the queue is empty. Packets left behind by
+    /// `AsyncDevice::peek` are discarded as well.
+    pub fn drain(&self) -> io::Result<usize> {
+        #[allow(unused_mut)]
+        let mut count = match &self.driver {
+            Driver::Tap(tap) => {
+                let mut scratch = vec![0u8; 65536];
+                let mut count = 0;
+                loop {
+                    match tap.try_read(&mut scratch) {
+                        Ok(_) => count += 1,
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break count,
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            Driver::Tun(tun) => tun.drain()?,
+        };
+        #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+        {
+            count += self.pushback.clear();
+        }
+        crate::trace::dev_event!(debug, self, count, "drained");
+        Ok(count)
+    }
+    pub(crate) fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        match &self.driver {
+            Driver::Tap(tap) => tap.try_read_vectored(bufs),
+            Driver::Tun(tun) => tun.try_recv_vectored(bufs),
         }
     }
     #[allow(dead_code)]
@@ -190,16 +446,14 @@ impl DeviceImpl {
 
     /// Send a packet to tun device
     pub(crate) fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        match &self.driver {
+        let result = match &self.driver {
             Driver::Tap(tap) => tap.write(buf),
             Driver::Tun(tun) => tun.send(buf),
-        }
+        };
+        crate::trace::dev_event!(trace, self, ?result, "write");
+        result
     }
-    #[cfg(any(
-        feature = "interruptible",
-        feature = "async_tokio",
-        feature = "async_io"
-    ))]
+    #[cfg(feature = "interruptible")]
     pub(crate) fn write_interruptible(
         &self,
         buf: &[u8],
@@ -210,10 +464,61 @@ impl DeviceImpl {
             Driver::Tun(tun) => tun.send_interruptible(buf, &event.handle),
         }
     }
-    pub(crate) fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+    /// Gathers `bufs` into one buffer and sends it as one packet.
+    #[cfg(feature = "interruptible")]
+    pub(crate) fn writev_interruptible(
+        &self,
+        bufs: &[IoSlice<'_>],
+        event: &crate::platform::windows::InterruptEvent,
+    ) -> io::Result<usize> {
+        let mut buf = Vec::with_capacity(bufs.iter().map(|buf| buf.len()).sum());
+        for b in bufs {
+            buf.extend_from_slice(b);
+        }
+        self.write_interruptible(&buf, event)
+    }
+    /// Send a packet, failing with `TimedOut` if it cannot be queued before `deadline`
+    pub(crate) fn send_deadline(
+        &self,
+        buf: &[u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
         match &self.driver {
+            Driver::Tap(tap) => tap.write_deadline(buf, interrupt_event, deadline),
+            Driver::Tun(tun) => tun.send_deadline(buf, interrupt_event, deadline),
+        }
+    }
+    pub(crate) fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+        let result = match &self.driver {
             Driver::Tap(tap) => tap.try_write(buf),
             Driver::Tun(tun) => tun.try_send(buf),
+        };
+        crate::trace::dev_event!(trace, self, ?result, "try write");
+        result
+    }
+    pub(crate) fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match &self.driver {
+            Driver::Tap(tap) => tap.try_write_vectored(bufs),
+            Driver::Tun(tun) => tun.try_send_vectored(bufs),
+        }
+    }
+    /// Whether Wintun packets carry the emulated packet information header; `false` for TAP.
+    pub(crate) fn emulated_packet_information(&self) -> bool {
+        match &self.driver {
+            Driver::Tun(tun) => tun.packet_information(),
+            Driver::Tap(_) => false,
+        }
+    }
+    /// Switches the emulated packet information header of a Wintun device, returning `false`
+    /// for TAP, which has none.
+    pub(crate) fn set_emulated_packet_information(&self, value: bool) -> bool {
+        match &self.driver {
+            Driver::Tun(tun) => {
+                tun.set_packet_information(value);
+                true
+            }
+            Driver::Tap(_) => false,
         }
     }
     pub(crate) fn shutdown(&self) -> io::Result<()> {
@@ -255,17 +560,65 @@ impl DeviceImpl {
         let _guard = self.lock.read().unwrap();
         self.name_impl()
     }
-    /// Sets a new name for the device.
+    /// Returns the backend of this device: [`crate::BackendKind::Wintun`] for TUN,
+    /// [`crate::BackendKind::TapWindows`] for TAP.
+    pub fn backend_kind(&self) -> crate::BackendKind {
+        match &self.driver {
+            Driver::Tun(_) => crate::BackendKind::Wintun,
+            Driver::Tap(_) => crate::BackendKind::TapWindows,
+        }
+    }
+    /// Returns the event Wintun signals when packets can be received.
+    ///
+    /// Allows applications with their own `WaitForMultipleObjects` loop to wait for packets
+    /// the way `AsyncDevice` does: wait on this handle (together with
+    /// [`shutdown_event_handle`](Self::shutdown_event_handle)), then call `try_recv` until it
+    /// returns [`WouldBlock`](io::ErrorKind::WouldBlock) before waiting again, as the event is
+    /// only set once the ring runs empty.
+    ///
+    /// The handle belongs to the Wintun session: do not close it, and query it again after
+    /// `enabled(true)`. Fails with [`Error::Disabled`](crate::Error::Disabled) while the device
+    /// is disabled, and with [`io::ErrorKind::Unsupported`] for TAP devices.
+    pub fn read_event_handle(&self) -> io::Result<RawHandle> {
+        let _guard = self.lock.read().unwrap();
+        match &self.driver {
+            Driver::Tun(tun) => tun.read_event(),
+            Driver::Tap(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "read event is only available for Wintun devices",
+            )),
+        }
+    }
+    /// Returns the event signaled when the device is shut down or disabled, after which
+    /// [`read_event_handle`](Self::read_event_handle) is no longer valid.
+    ///
+    /// The handle lives as long as the device; do not close it. Fails with
+    /// [`io::ErrorKind::Unsupported`] for TAP devices.
+    pub fn shutdown_event_handle(&self) -> io::Result<RawHandle> {
+        match &self.driver {
+            Driver::Tun(tun) => Ok(tun.shutdown_event()),
+            Driver::Tap(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "shutdown event is only available for Wintun devices",
+            )),
+        }
+    }
+    /// Sets a new name (interface alias) for the device.
+    ///
+    /// Works for TUN and TAP devices alike, through `NciSetConnectionName` where available and
+    /// `netsh` otherwise. Only the alias changes: the Wintun session or TAP handle keeps
+    /// running and no packets are dropped, as with address changes, so there is nothing to
+    /// queue around the call. I/O in other threads is not blocked while it runs.
     ///
-    /// This method first checks if the current name is different from the desired one. If it is,
-    /// it uses the `netsh` command to update the interface name.
+    /// Fails with [`io::ErrorKind::InvalidInput`] for an empty name or one longer than 256
+    /// characters.
     pub fn set_name(&self, value: &str) -> io::Result<()> {
-        let _guard = self.lock.write().unwrap();
+        let _guard = self.lock.read().unwrap();
         let name = self.name_impl()?;
         if value == name {
             return Ok(());
         }
-        netsh::set_interface_name(&name, value)
+        super::rename::set_interface_alias(&self.luid_impl(), &name, value)
     }
     /// Retrieves the interface index (if_index) of the device.
     ///
@@ -281,16 +634,111 @@ impl DeviceImpl {
         let _guard = self.lock.read().unwrap();
         Ok(self.luid_impl())
     }
-    /// Enables or disables the device.
+    /// Retrieves the interface GUID of the device.
     ///
-    /// For a TUN device, disabling is not supported and will return an error.
-    /// For a TAP device, this calls the appropriate method to set the device status.
+    /// The value has the same layout as the one passed to `device_guid` when building the device.
+    pub fn if_guid(&self) -> io::Result<u128> {
+        let _guard = self.lock.read().unwrap();
+        let guid = super::ffi::luid_to_guid(&self.luid_impl())?;
+        Ok(((guid.data1 as u128) << 96)
+            | ((guid.data2 as u128) << 80)
+            | ((guid.data3 as u128) << 64)
+            | u64::from_be_bytes(guid.data4) as u128)
+    }
+    /// Enables or disables the device, see [`set_link_up`](Self::set_link_up).
     pub fn enabled(&self, value: bool) -> io::Result<()> {
+        self.set_link_up(value)
+    }
+    /// Brings the link of the device up or down, whichever driver backs the adapter.
+    ///
+    /// For a TUN device this starts or ends the Wintun session: while it is ended, packets
+    /// are neither sent nor received and blocked calls fail with
+    /// [`Error::Disabled`](crate::Error::Disabled). For a TAP device this sets the media status
+    /// to connected or disconnected (cable unplugged).
+    pub fn set_link_up(&self, up: bool) -> io::Result<()> {
         let _guard = self.lock.write().unwrap();
-        match &self.driver {
-            Driver::Tun(tun) => tun.enabled(value),
-            Driver::Tap(tap) => tap.set_status(value),
-        }
+        let result = match &self.driver {
+            Driver::Tun(tun) => tun.enabled(up),
+            Driver::Tap(tap) => tap.set_status(up),
+        };
+        crate::trace::dev_event!(debug, self, up, ?result, "link state set");
+        result
+    }
+    /// Returns whether the link is up, as last set by [`set_link_up`](Self::set_link_up).
+    pub fn link_up(&self) -> io::Result<bool> {
+        let _guard = self.lock.read().unwrap();
+        Ok(match &self.driver {
+            Driver::Tun(tun) => tun.is_enabled(),
+            Driver::Tap(tap) => tap.status(),
+        })
+    }
+    /// Returns whether the interface is administratively up, see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> io::Result<bool> {
+        let _guard = self.lock.read().unwrap();
+        Ok(super::ffi::get_interface_status(&self.luid_impl())?.0)
+    }
+    /// Returns the operational state of the interface (`IfOperStatus`).
+    ///
+    /// A TAP device whose media status is disconnected is [`OperState::LowerLayerDown`].
+    /// `IfOperStatusTesting`, `IfOperStatusDormant` and `IfOperStatusNotPresent` are reported
+    /// as [`OperState::LowerLayerDown`] too.
+    pub fn oper_state(&self) -> io::Result<OperState> {
+        use windows_sys::Win32::NetworkManagement::Ndis::{
+            IfOperStatusDown, IfOperStatusUnknown, IfOperStatusUp,
+        };
+        let _guard = self.lock.read().unwrap();
+        let (_, status) = super::ffi::get_interface_status(&self.luid_impl())?;
+        Ok(match status {
+            IfOperStatusUp => OperState::Up,
+            IfOperStatusDown => OperState::Down,
+            IfOperStatusUnknown => OperState::Unknown,
+            _ => OperState::LowerLayerDown,
+        })
+    }
+    /// Ends the Wintun session and starts a new one, e.g. to recover from a stalled ring,
+    /// and reports the packets that were left in the receive ring.
+    ///
+    /// Ending a session discards the packets in its rings. With `drain`, the packets waiting in
+    /// the receive ring are first read and handed to it, so that they can still be processed;
+    /// otherwise they are counted in [`SessionRestart::dropped_packets`]. Wintun does not report
+    /// the packets still queued in the send ring, so they are not counted.
+    ///
+    /// Blocked `recv` calls fail with [`Error::Disabled`](crate::Error::Disabled) during the
+    /// restart. If the new session cannot be started, the device is left disabled and
+    /// `enabled(true)` retries.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] for TAP devices and with
+    /// [`Error::Disabled`](crate::Error::Disabled) if the device is disabled.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use tun_rs::DeviceBuilder;
+    ///
+    /// let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+    /// let mut pending = Vec::new();
+    /// let mut keep = |packet: &[u8]| pending.push(packet.to_vec());
+    /// let restart = dev.restart_session(Some(&mut keep))?;
+    /// assert_eq!(restart.drained_packets, pending.len());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// # Platform
+    ///
+    /// Windows only, Wintun (L3) devices.
+    pub fn restart_session(
+        &self,
+        drain: Option<&mut dyn FnMut(&[u8])>,
+    ) -> io::Result<crate::SessionRestart> {
+        let _guard = self.lock.write().unwrap();
+        let result = match &self.driver {
+            Driver::Tun(tun) => tun.restart_session(drain),
+            Driver::Tap(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "session restart is only available on Wintun devices",
+            )),
+        };
+        crate::trace::dev_event!(debug, self, ?result, "session restarted");
+        result
     }
     /// Retrieves all IP addresses associated with this device.
     ///
@@ -444,12 +892,42 @@ impl DeviceImpl {
         let _guard = self.lock.write().unwrap();
         super::ffi::set_interface_mtu(self.if_index_impl()?, mtu as _, false)
     }
-    /// Sets the MAC address for the device.
+    /// Enables or disables IPv6 router discovery on the interface
+    /// (`netsh interface ipv6 set interface <index> routerdiscovery=...`).
+    ///
+    /// Disabling it stops the interface from processing router advertisements, so no
+    /// RA-derived addresses or routes are installed on the tunnel.
+    pub fn set_ipv6_autoconf(&self, value: bool) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        super::ffi::set_interface_router_discovery(self.if_index_impl()?, value)
+    }
+    /// Enables or disables IPv6 duplicate address detection on the interface
+    /// (`DadTransmits` of its IPv6 interface entry, 1 or 0).
+    ///
+    /// Without DAD a new address is usable at once instead of after about a second; affects
+    /// addresses added afterwards.
+    pub fn set_ipv6_dad(&self, value: bool) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        super::ffi::set_interface_dad_transmits(self.if_index_impl()?, value.into())
+    }
+    /// Enables or disables IPv6 privacy extensions, i.e. temporary addresses
+    /// (`netsh interface ipv6 set privacy state=...`).
     ///
-    /// Attempting to set a MAC address will result in an error.
+    /// Windows has no per-interface switch, so this changes the system-wide setting.
+    pub fn set_ipv6_privacy(&self, value: bool) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        super::netsh::set_ipv6_privacy(value)
+    }
+    /// Enables or disables IPv6 forwarding on the interface
+    /// (`netsh interface ipv6 set interface <index> forwarding=...`).
+    pub fn set_ipv6_forwarding(&self, value: bool) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        super::ffi::set_interface_forwarding(self.if_index_impl()?, value, false)
+    }
+    /// Sets the MAC address for the device.
     ///
-    /// #Note:
-    /// set a MAC address is only supported when creating a TUN/TAP device.
+    /// This operation is only supported for TAP devices. The adapter is restarted to pick up
+    /// the address, which cancels pending reads and writes and briefly takes the link down.
     pub fn set_mac_address(&self, eth_addr: [u8; ETHER_ADDR_LEN as usize]) -> io::Result<()> {
         let _guard = self.lock.write().unwrap();
         match &self.driver {
@@ -501,14 +979,55 @@ impl DeviceImpl {
         let _guard = self.lock.write().unwrap();
         super::ffi::set_interface_metric(self.if_index_impl()?, metric as u32)
     }
+    /// Sets the routing metric for IPv6 only, see [`set_metric`](Self::set_metric), which
+    /// sets it for both address families.
+    pub fn set_metric_v6(&self, metric: u16) -> io::Result<()> {
+        let _guard = self.lock.write().unwrap();
+        super::ffi::set_interface_metric_v6(self.if_index_impl()?, metric as u32)
+    }
+    /// Sets the category of the network the interface is connected to, which selects the
+    /// Windows Firewall profile that applies to it.
+    ///
+    /// VPN adapters usually end up in an unidentified network of the
+    /// [`Public`](NetworkCategory::Public) category, whose firewall profile blocks inbound
+    /// connections. Windows only identifies the network once the interface is up and has an
+    /// address, so this fails with [`io::ErrorKind::NotFound`] right after the device is
+    /// built; retry after a moment. The category is lost when the network is identified anew,
+    /// e.g. after the adapter is recreated.
+    ///
+    /// # Platform
+    ///
+    /// Windows only. Requires administrator privileges.
+    pub fn set_network_category(&self, category: NetworkCategory) -> io::Result<()> {
+        let _guard = self.lock.read().unwrap();
+        let guid = super::ffi::luid_to_guid(&self.luid_impl())?;
+        super::network_list::set_network_category(&guid, category)
+    }
+    /// Returns the category of the network the interface is connected to, see
+    /// [`set_network_category`](Self::set_network_category).
+    pub fn network_category(&self) -> io::Result<NetworkCategory> {
+        let _guard = self.lock.read().unwrap();
+        let guid = super::ffi::luid_to_guid(&self.luid_impl())?;
+        super::network_list::network_category(&guid)
+    }
+    /// Returns whether Wintun runs in Windows 7 compatibility mode, see
+    /// [`DeviceBuilder::compat_win7`](crate::DeviceBuilder::compat_win7).
+    ///
+    /// Always `false` for TAP devices, which behave the same on all versions.
+    pub fn compat_win7(&self) -> bool {
+        match &self.driver {
+            Driver::Tun(tun) => tun.compat_win7(),
+            Driver::Tap(_) => false,
+        }
+    }
     /// Retrieves the version of the underlying driver.
     ///
-    /// For TUN devices, this directly queries the driver version.
+    /// For TUN devices, this is the Wintun driver version as `major.minor`.
     /// For TAP devices, the version is composed of several components joined by dots.
     pub fn version(&self) -> io::Result<String> {
         let _guard = self.lock.read().unwrap();
         match &self.driver {
-            Driver::Tun(tun) => tun.version(),
+            Driver::Tun(tun) => tun.driver_version().map(|v| v.to_string()),
             Driver::Tap(tap) => tap.get_version().map(|v| {
                 v.iter()
                     .map(|v| v.to_string())
@@ -517,6 +1036,32 @@ impl DeviceImpl {
             }),
         }
     }
+    /// Retrieves the version of the running driver, Wintun or tap-windows6.
+    pub fn driver_version(&self) -> io::Result<DriverVersion> {
+        let _guard = self.lock.read().unwrap();
+        match &self.driver {
+            Driver::Tun(tun) => tun.driver_version(),
+            Driver::Tap(tap) => tap.driver_version(),
+        }
+    }
+    /// Returns the capacity in bytes of the Wintun session rings, or `None` for TAP devices.
+    ///
+    /// This is the value passed to `ring_capacity` when building the device.
+    pub fn ring_capacity(&self) -> Option<u32> {
+        match &self.driver {
+            Driver::Tun(tun) => Some(tun.ring_capacity()),
+            Driver::Tap(_) => None,
+        }
+    }
+    /// Returns the full path `wintun.dll` was loaded from, or `None` for TAP devices.
+    ///
+    /// Useful to tell which copy of the DLL the search order or `wintun_file` picked up.
+    pub fn wintun_dll_path(&self) -> Option<PathBuf> {
+        match &self.driver {
+            Driver::Tun(tun) => Some(tun.dll_path().to_path_buf()),
+            Driver::Tap(_) => None,
+        }
+    }
     /// Set DNS servers for the current device (supports primary and secondary DNS)
     /// dns_servers: A priority-ordered list of DNS servers (must be all IPv4 or all IPv6)
     pub fn set_dns_servers(&self, dns_servers: &[IpAddr]) -> io::Result<()> {
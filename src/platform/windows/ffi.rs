@@ -1,22 +1,27 @@
+use crate::platform::route::Route;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::os::windows::io::{FromRawHandle, OwnedHandle, RawHandle};
 use std::{io, mem, ptr};
 
 use windows_sys::Win32::Foundation::{
-    ERROR_IO_INCOMPLETE, ERROR_IO_PENDING, ERROR_OBJECT_ALREADY_EXISTS, NO_ERROR,
+    ERROR_IO_INCOMPLETE, ERROR_IO_PENDING, ERROR_OBJECT_ALREADY_EXISTS, NO_ERROR, WAIT_FAILED,
+    WAIT_OBJECT_0, WAIT_TIMEOUT,
 };
 use windows_sys::Win32::NetworkManagement::IpHelper::{
     CreateIpForwardEntry2, CreateUnicastIpAddressEntry, DeleteIpForwardEntry2,
-    DeleteUnicastIpAddressEntry, FreeMibTable, GetIpForwardTable2, GetIpInterfaceEntry,
-    GetIpInterfaceTable, GetUnicastIpAddressTable, InitializeIpForwardEntry,
+    DeleteUnicastIpAddressEntry, FreeMibTable, GetBestRoute2, GetIpForwardTable2,
+    GetIpInterfaceEntry, GetIpInterfaceTable, GetUnicastIpAddressTable, InitializeIpForwardEntry,
     InitializeUnicastIpAddressEntry, SetIpInterfaceEntry, MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2,
     MIB_IPINTERFACE_ROW, MIB_IPINTERFACE_TABLE, MIB_UNICASTIPADDRESS_ROW,
     MIB_UNICASTIPADDRESS_TABLE,
 };
 use windows_sys::Win32::Networking::WinSock::{
-    NlroManual, AF_INET, AF_INET6, MIB_IPPROTO_NETMGMT, SOCKADDR_INET,
+    NlroManual, RouterDiscoveryDisabled, RouterDiscoveryEnabled, AF_INET, AF_INET6, AF_UNSPEC,
+    MIB_IPPROTO_NETMGMT, SOCKADDR_INET,
+};
+use windows_sys::Win32::System::Threading::{
+    ResetEvent, SetEvent, WaitForMultipleObjects, INFINITE,
 };
-use windows_sys::Win32::System::Threading::{ResetEvent, SetEvent};
 use windows_sys::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
 use windows_sys::{
     core::{BOOL, GUID},
@@ -37,10 +42,11 @@ use windows_sys::{
         },
         NetworkManagement::{
             IpHelper::{
-                ConvertInterfaceAliasToLuid, ConvertInterfaceLuidToAlias,
-                ConvertInterfaceLuidToGuid, ConvertInterfaceLuidToIndex,
+                ConvertInterfaceAliasToLuid, ConvertInterfaceIndexToLuid,
+                ConvertInterfaceLuidToAlias, ConvertInterfaceLuidToGuid,
+                ConvertInterfaceLuidToIndex, GetIfEntry2, MIB_IF_ROW2,
             },
-            Ndis::NET_LUID_LH,
+            Ndis::{IF_OPER_STATUS, NET_IF_ADMIN_STATUS_UP, NET_LUID_LH},
         },
         Storage::FileSystem::{
             CreateFileW, ReadFile, WriteFile, FILE_CREATION_DISPOSITION, FILE_FLAGS_AND_ATTRIBUTES,
@@ -109,6 +115,15 @@ pub fn luid_to_index(luid: &NET_LUID_LH) -> io::Result<u32> {
     }
 }
 
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+pub fn index_to_luid(index: u32) -> io::Result<NET_LUID_LH> {
+    let mut luid = unsafe { mem::zeroed() };
+    match unsafe { ConvertInterfaceIndexToLuid(index, &mut luid) } {
+        0 => Ok(luid),
+        _err => Err(io::Error::last_os_error()),
+    }
+}
+
 pub fn luid_to_guid(luid: &NET_LUID_LH) -> io::Result<GUID> {
     let mut guid = unsafe { mem::zeroed() };
     match unsafe { ConvertInterfaceLuidToGuid(luid, &mut guid) } {
@@ -142,6 +157,33 @@ pub fn wait_for_single_object(handle: RawHandle, timeout: u32) -> io::Result<()>
         }
     }
 }
+/// Waits until one of `handles` is signaled or `deadline` passes.
+///
+/// Returns the index of the signaled handle, or `None` once the deadline has passed.
+pub fn wait_for_multiple_objects(
+    handles: &[RawHandle],
+    deadline: Option<std::time::Instant>,
+) -> io::Result<Option<usize>> {
+    // Round up, so that the wait does not end before the deadline.
+    let timeout = deadline
+        .map(|d| {
+            let nanos = d
+                .saturating_duration_since(std::time::Instant::now())
+                .as_nanos();
+            nanos.div_ceil(1_000_000).min((INFINITE - 1) as u128) as u32
+        })
+        .unwrap_or(INFINITE);
+    let result =
+        unsafe { WaitForMultipleObjects(handles.len() as u32, handles.as_ptr(), 0, timeout) };
+    match result {
+        WAIT_FAILED => Err(io::Error::last_os_error()),
+        WAIT_TIMEOUT => Ok(None),
+        _ if result < WAIT_OBJECT_0 + handles.len() as u32 => {
+            Ok(Some((result - WAIT_OBJECT_0) as usize))
+        }
+        _ => Err(io::Error::last_os_error()),
+    }
+}
 pub fn set_event(handle: RawHandle) -> io::Result<()> {
     unsafe {
         if FALSE == SetEvent(handle) {
@@ -657,6 +699,18 @@ fn sockaddr_inet_from_ip(ip: IpAddr) -> SOCKADDR_INET {
     sa
 }
 
+fn ip_from_sockaddr_inet(sa: &SOCKADDR_INET) -> Option<IpAddr> {
+    unsafe {
+        match sa.si_family {
+            AF_INET => Some(IpAddr::V4(Ipv4Addr::from(
+                sa.Ipv4.sin_addr.S_un.S_addr.to_ne_bytes(),
+            ))),
+            AF_INET6 => Some(IpAddr::V6(Ipv6Addr::from(sa.Ipv6.sin6_addr.u.Byte))),
+            _ => None,
+        }
+    }
+}
+
 /// Maps a Win32 status code (`NETIOAPI_API` / `WIN32_ERROR`) to an `io::Result`.
 pub(crate) fn win_result(code: u32) -> io::Result<()> {
     if code == NO_ERROR {
@@ -666,6 +720,16 @@ pub(crate) fn win_result(code: u32) -> io::Result<()> {
     }
 }
 
+/// Returns whether the interface is administratively up, and its operational status.
+pub fn get_interface_status(luid: &NET_LUID_LH) -> io::Result<(bool, IF_OPER_STATUS)> {
+    let mut row = MIB_IF_ROW2 {
+        InterfaceLuid: *luid,
+        ..Default::default()
+    };
+    win_result(unsafe { GetIfEntry2(&mut row) })?;
+    Ok((row.AdminStatus == NET_IF_ADMIN_STATUS_UP, row.OperStatus))
+}
+
 /// Sets the interface metric (routing cost) for both IPv4 and IPv6 by interface index.
 pub fn set_interface_metric(index: u32, metric: u32) -> io::Result<()> {
     for family in [AF_INET, AF_INET6] {
@@ -686,8 +750,44 @@ pub fn set_interface_metric(index: u32, metric: u32) -> io::Result<()> {
     Ok(())
 }
 
+/// Sets the IPv6 interface metric by interface index, leaving the IPv4 one as it is.
+pub fn set_interface_metric_v6(index: u32, metric: u32) -> io::Result<()> {
+    modify_ip_interface(index, false, |row| {
+        row.Metric = metric;
+        row.UseAutomaticMetric = false;
+    })
+}
+
 /// Sets the MTU (`NlMtu`) of the interface for the given family by interface index.
 pub fn set_interface_mtu(index: u32, mtu: u32, is_v4: bool) -> io::Result<()> {
+    modify_ip_interface(index, is_v4, |row| row.NlMtu = mtu)
+}
+
+/// Enables or disables router discovery (RA processing) on the IPv6 interface.
+pub fn set_interface_router_discovery(index: u32, enabled: bool) -> io::Result<()> {
+    modify_ip_interface(index, false, |row| {
+        row.RouterDiscoveryBehavior = if enabled {
+            RouterDiscoveryEnabled
+        } else {
+            RouterDiscoveryDisabled
+        };
+    })
+}
+
+/// Sets how many neighbor solicitations IPv6 duplicate address detection sends, 0 to skip it.
+pub fn set_interface_dad_transmits(index: u32, transmits: u32) -> io::Result<()> {
+    modify_ip_interface(index, false, |row| row.DadTransmits = transmits)
+}
+
+pub fn set_interface_forwarding(index: u32, enabled: bool, is_v4: bool) -> io::Result<()> {
+    modify_ip_interface(index, is_v4, |row| row.ForwardingEnabled = enabled)
+}
+
+fn modify_ip_interface(
+    index: u32,
+    is_v4: bool,
+    f: impl FnOnce(&mut MIB_IPINTERFACE_ROW),
+) -> io::Result<()> {
     let mut row = MIB_IPINTERFACE_ROW {
         Family: if is_v4 { AF_INET } else { AF_INET6 },
         InterfaceIndex: index,
@@ -695,7 +795,7 @@ pub fn set_interface_mtu(index: u32, mtu: u32, is_v4: bool) -> io::Result<()> {
     };
     win_result(unsafe { GetIpInterfaceEntry(&mut row) })?;
 
-    row.NlMtu = mtu;
+    f(&mut row);
     // `GetIpInterfaceEntry` returns a `SitePrefixLength` that `SetIpInterfaceEntry`
     // rejects (notably for IPv4); reset it to 0 before writing back. This is the
     // conventional workaround and is harmless for IPv6, where site prefixes are unused.
@@ -754,6 +854,83 @@ pub fn add_address(
     Ok(())
 }
 
+fn forward_row(route: &Route) -> MIB_IPFORWARD_ROW2 {
+    let mut row = MIB_IPFORWARD_ROW2::default();
+    unsafe { InitializeIpForwardEntry(&mut row) };
+    row.InterfaceIndex = route.if_index;
+    row.DestinationPrefix.Prefix = sockaddr_inet_from_ip(route.destination);
+    row.DestinationPrefix.PrefixLength = route.prefix;
+    // See `add_address`: the initial value 255 is rejected.
+    row.SitePrefixLength = 0;
+    // An on-link route has the unspecified address of the destination family as next hop.
+    let unspecified = if route.destination.is_ipv4() {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    };
+    row.NextHop = sockaddr_inet_from_ip(route.gateway.unwrap_or(unspecified));
+    row.Metric = 0;
+    row.Protocol = MIB_IPPROTO_NETMGMT;
+    row.Origin = NlroManual;
+    row
+}
+
+/// Adds `route` to the routing table.
+pub fn add_route(route: &Route) -> io::Result<()> {
+    win_result(unsafe { CreateIpForwardEntry2(&forward_row(route)) })
+}
+
+/// Removes `route` from the routing table.
+pub fn delete_route(route: &Route) -> io::Result<()> {
+    win_result(unsafe { DeleteIpForwardEntry2(&forward_row(route)) })
+}
+
+/// Lists the manually added routes through the interface `index`.
+pub fn device_routes(index: u32) -> io::Result<Vec<Route>> {
+    let mut table: *mut MIB_IPFORWARD_TABLE2 = ptr::null_mut();
+    win_result(unsafe { GetIpForwardTable2(AF_UNSPEC, &mut table) })?;
+
+    let routes = unsafe {
+        std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize)
+    }
+    .iter()
+    .filter(|row| row.InterfaceIndex == index && row.Protocol == MIB_IPPROTO_NETMGMT)
+    .filter_map(|row| {
+        Some(Route {
+            destination: ip_from_sockaddr_inet(&row.DestinationPrefix.Prefix)?,
+            prefix: row.DestinationPrefix.PrefixLength,
+            gateway: ip_from_sockaddr_inet(&row.NextHop).filter(|ip| !ip.is_unspecified()),
+            if_index: index,
+        })
+    })
+    .collect();
+    unsafe { FreeMibTable(table as _) };
+    Ok(routes)
+}
+
+/// Returns the route Windows would use to reach `destination`.
+pub fn find_route(destination: IpAddr) -> io::Result<Route> {
+    let mut row = MIB_IPFORWARD_ROW2::default();
+    let mut source = SOCKADDR_INET::default();
+    win_result(unsafe {
+        GetBestRoute2(
+            ptr::null(),
+            0,
+            ptr::null(),
+            &sockaddr_inet_from_ip(destination),
+            0,
+            &mut row,
+            &mut source,
+        )
+    })?;
+    Ok(Route {
+        destination,
+        prefix: if destination.is_ipv4() { 32 } else { 128 },
+        gateway: ip_from_sockaddr_inet(&row.NextHop).filter(|ip| !ip.is_unspecified()),
+        if_index: row.InterfaceIndex,
+    })
+}
+
 /// Removes a single unicast address from the interface.
 pub fn remove_address(index: u32, address: IpAddr) -> io::Result<()> {
     let mut row = MIB_UNICASTIPADDRESS_ROW::default();
@@ -0,0 +1,272 @@
+//! Inbound allow rules of Windows Defender Firewall for an interface.
+//!
+//! A new adapter usually ends up in the public firewall profile, where inbound traffic is
+//! dropped without a notice, which is rarely what a development setup or a dedicated device
+//! wants. Rules are managed through the `INetFwPolicy2` COM interface, whose methods are
+//! called through hand-written vtables like the ones of the Network List Manager.
+
+use std::ffi::c_void;
+use std::io;
+use std::ptr;
+
+use windows_sys::core::{BSTR, HRESULT};
+use windows_sys::Win32::Foundation::{SysAllocString, SysFreeString, VARIANT_BOOL, VARIANT_TRUE};
+use windows_sys::Win32::NetworkManagement::WindowsFirewall::{
+    NetFwPolicy2, NetFwRule, NET_FW_ACTION_ALLOW, NET_FW_PROFILE2_ALL, NET_FW_RULE_DIR_IN,
+};
+use windows_sys::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows_sys::Win32::System::Ole::{SafeArrayCreateVector, SafeArrayPutElement};
+use windows_sys::Win32::System::Variant::{VariantClear, VARIANT, VT_ARRAY, VT_BSTR, VT_VARIANT};
+
+use super::network_list::{check, ComInit, ComPtr, DispatchVtbl, Unused};
+
+const IID_INET_FW_POLICY2: windows_sys::core::GUID =
+    windows_sys::core::GUID::from_u128(0x98325047_c671_4174_8d81_defcd3f03186);
+const IID_INET_FW_RULE: windows_sys::core::GUID =
+    windows_sys::core::GUID::from_u128(0xaf230d27_baba_4e42_aced_f524f22cfce2);
+
+/// Group the rules are listed under in the firewall console.
+const GROUPING: &str = "tun-rs";
+
+/// A firewall rule added by [`add_firewall_rule_allow_all`], removed when dropped.
+#[derive(Debug)]
+pub struct FirewallRule {
+    name: String,
+}
+
+impl FirewallRule {
+    /// The name of the rule as shown in the firewall console.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Removes the rule now, reporting an error that dropping it would only log.
+    pub fn remove(mut self) -> io::Result<()> {
+        let name = std::mem::take(&mut self.name);
+        remove_rule(&name)
+    }
+    /// Keeps the rule when this value is dropped, e.g. for a persistent device.
+    pub fn keep(mut self) {
+        self.name.clear();
+    }
+}
+
+impl Drop for FirewallRule {
+    fn drop(&mut self) {
+        if self.name.is_empty() {
+            return;
+        }
+        if let Err(e) = remove_rule(&self.name) {
+            log::warn!("failed to remove firewall rule {:?}: {e}", self.name);
+        }
+    }
+}
+
+/// Adds a Windows Defender Firewall rule allowing all inbound traffic on the interface
+/// `interface_alias`, in all profiles, until the returned [`FirewallRule`] is dropped.
+///
+/// Requires administrator rights. A rule of the same name left behind by a process that did
+/// not exit cleanly is replaced.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(all(target_os = "windows", feature = "winfw"))]
+/// # {
+/// use tun_rs::DeviceBuilder;
+///
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .build_sync()?;
+/// let _rule = tun_rs::add_firewall_rule_allow_all(&dev.name()?)?;
+/// # }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn add_firewall_rule_allow_all(interface_alias: &str) -> io::Result<FirewallRule> {
+    let name = format!("tun-rs: allow inbound on {interface_alias}");
+    with_rules(|rules| {
+        let rule: ComPtr<RuleVtbl> = unsafe {
+            let mut rule = ptr::null_mut();
+            check(CoCreateInstance(
+                &NetFwRule,
+                ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IID_INET_FW_RULE,
+                &mut rule,
+            ))?;
+            ComPtr::from_raw(rule)?
+        };
+        let bstr_name = Bstr::new(&name)?;
+        let grouping = Bstr::new(GROUPING)?;
+        let interfaces = interfaces_variant(interface_alias)?;
+        let (raw, vtbl) = (rule.as_raw(), rule.vtbl());
+        unsafe {
+            check((vtbl.put_name)(raw, bstr_name.0))?;
+            check((vtbl.put_grouping)(raw, grouping.0))?;
+            check((vtbl.put_direction)(raw, NET_FW_RULE_DIR_IN))?;
+            check((vtbl.put_action)(raw, NET_FW_ACTION_ALLOW))?;
+            check((vtbl.put_profiles)(raw, NET_FW_PROFILE2_ALL))?;
+            check((vtbl.put_interfaces)(raw, interfaces.0))?;
+            check((vtbl.put_enabled)(raw, VARIANT_TRUE))?;
+            // Rules are not unique by name, so a stale one would linger next to the new one.
+            _ = (rules.vtbl().remove)(rules.as_raw(), bstr_name.0);
+            check((rules.vtbl().add)(rules.as_raw(), raw))
+        }
+    })?;
+    Ok(FirewallRule { name })
+}
+
+fn remove_rule(name: &str) -> io::Result<()> {
+    let name = Bstr::new(name)?;
+    with_rules(|rules| check(unsafe { (rules.vtbl().remove)(rules.as_raw(), name.0) }))
+}
+
+/// Passes the rule collection of the firewall policy to `f`.
+fn with_rules<R>(f: impl FnOnce(&ComPtr<RulesVtbl>) -> io::Result<R>) -> io::Result<R> {
+    let _com = ComInit::new()?;
+    let policy: ComPtr<Policy2Vtbl> = unsafe {
+        let mut policy = ptr::null_mut();
+        check(CoCreateInstance(
+            &NetFwPolicy2,
+            ptr::null_mut(),
+            CLSCTX_INPROC_SERVER,
+            &IID_INET_FW_POLICY2,
+            &mut policy,
+        ))?;
+        ComPtr::from_raw(policy)?
+    };
+    let rules: ComPtr<RulesVtbl> = unsafe {
+        let mut rules = ptr::null_mut();
+        check((policy.vtbl().get_rules)(policy.as_raw(), &mut rules))?;
+        ComPtr::from_raw(rules)?
+    };
+    f(&rules)
+}
+
+/// An owned `BSTR`.
+struct Bstr(BSTR);
+
+impl Bstr {
+    fn new(s: &str) -> io::Result<Self> {
+        let wide: Vec<u16> = s.encode_utf16().chain(Some(0)).collect();
+        let bstr = unsafe { SysAllocString(wide.as_ptr()) };
+        if bstr.is_null() {
+            return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+        }
+        Ok(Bstr(bstr))
+    }
+}
+
+impl Drop for Bstr {
+    fn drop(&mut self) {
+        unsafe { SysFreeString(self.0) };
+    }
+}
+
+/// An owned `VARIANT`.
+struct Variant(VARIANT);
+
+impl Drop for Variant {
+    fn drop(&mut self) {
+        unsafe { VariantClear(&mut self.0) };
+    }
+}
+
+/// The array of interface names `put_Interfaces` takes: a `VARIANT` holding a `SAFEARRAY` of
+/// `VARIANT`s of type `BSTR`.
+fn interfaces_variant(interface_alias: &str) -> io::Result<Variant> {
+    let mut element = Variant(VARIANT::default());
+    let alias = Bstr::new(interface_alias)?;
+    unsafe {
+        element.0.Anonymous.Anonymous.vt = VT_BSTR;
+        // The variant owns the string from here on.
+        element.0.Anonymous.Anonymous.Anonymous.bstrVal = alias.0;
+        std::mem::forget(alias);
+        let array = SafeArrayCreateVector(VT_VARIANT, 0, 1);
+        if array.is_null() {
+            return Err(io::Error::from(io::ErrorKind::OutOfMemory));
+        }
+        let mut interfaces = Variant(VARIANT::default());
+        interfaces.0.Anonymous.Anonymous.vt = VT_ARRAY | VT_VARIANT;
+        interfaces.0.Anonymous.Anonymous.Anonymous.parray = array;
+        // Copies the element, which is freed on return.
+        let index = 0i32;
+        check(SafeArrayPutElement(
+            array,
+            &index,
+            &element.0 as *const VARIANT as *const c_void,
+        ))?;
+        Ok(interfaces)
+    }
+}
+
+/// `INetFwPolicy2`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct Policy2Vtbl {
+    dispatch: DispatchVtbl,
+    get_current_profile_types: Unused,
+    get_firewall_enabled: Unused,
+    put_firewall_enabled: Unused,
+    get_excluded_interfaces: Unused,
+    put_excluded_interfaces: Unused,
+    get_block_all_inbound_traffic: Unused,
+    put_block_all_inbound_traffic: Unused,
+    get_notifications_disabled: Unused,
+    put_notifications_disabled: Unused,
+    get_unicast_responses_to_multicast_broadcast_disabled: Unused,
+    put_unicast_responses_to_multicast_broadcast_disabled: Unused,
+    get_rules: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> HRESULT,
+}
+
+/// `INetFwRules`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct RulesVtbl {
+    dispatch: DispatchVtbl,
+    get_count: Unused,
+    add: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+    remove: unsafe extern "system" fn(*mut c_void, BSTR) -> HRESULT,
+}
+
+/// `INetFwRule`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct RuleVtbl {
+    dispatch: DispatchVtbl,
+    get_name: Unused,
+    put_name: unsafe extern "system" fn(*mut c_void, BSTR) -> HRESULT,
+    get_description: Unused,
+    put_description: Unused,
+    get_application_name: Unused,
+    put_application_name: Unused,
+    get_service_name: Unused,
+    put_service_name: Unused,
+    get_protocol: Unused,
+    put_protocol: Unused,
+    get_local_ports: Unused,
+    put_local_ports: Unused,
+    get_remote_ports: Unused,
+    put_remote_ports: Unused,
+    get_local_addresses: Unused,
+    put_local_addresses: Unused,
+    get_remote_addresses: Unused,
+    put_remote_addresses: Unused,
+    get_icmp_types_and_codes: Unused,
+    put_icmp_types_and_codes: Unused,
+    get_direction: Unused,
+    put_direction: unsafe extern "system" fn(*mut c_void, i32) -> HRESULT,
+    get_interfaces: Unused,
+    put_interfaces: unsafe extern "system" fn(*mut c_void, VARIANT) -> HRESULT,
+    get_interface_types: Unused,
+    put_interface_types: Unused,
+    get_enabled: Unused,
+    put_enabled: unsafe extern "system" fn(*mut c_void, VARIANT_BOOL) -> HRESULT,
+    get_grouping: Unused,
+    put_grouping: unsafe extern "system" fn(*mut c_void, BSTR) -> HRESULT,
+    get_profiles: Unused,
+    put_profiles: unsafe extern "system" fn(*mut c_void, i32) -> HRESULT,
+    get_edge_traversal: Unused,
+    put_edge_traversal: Unused,
+    get_action: Unused,
+    put_action: unsafe extern "system" fn(*mut c_void, i32) -> HRESULT,
+}
@@ -1,6 +1,6 @@
 use crate::platform::windows::ffi;
 use std::io;
-use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::os::windows::io::{AsRawHandle, OwnedHandle, RawHandle};
 use std::sync::Mutex;
 
 pub struct InterruptEvent {
@@ -37,3 +37,10 @@ impl InterruptEvent {
         ffi::reset_event(self.handle.as_raw_handle())
     }
 }
+
+/// The manual-reset event set by `trigger`, for waiting on it in an own event loop.
+impl AsRawHandle for InterruptEvent {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.handle.as_raw_handle()
+    }
+}
@@ -1,6 +1,8 @@
 mod device;
 mod dns;
 pub(crate) mod ffi;
+#[cfg(feature = "winfw")]
+mod firewall;
 #[cfg(any(
     feature = "interruptible",
     feature = "async_tokio",
@@ -8,6 +10,8 @@ pub(crate) mod ffi;
 ))]
 mod interrupt;
 mod netsh;
+mod network_list;
+mod rename;
 mod tap;
 mod tun;
 #[cfg(any(
@@ -17,4 +21,8 @@ mod tun;
 ))]
 pub use interrupt::InterruptEvent;
 
-pub use device::DeviceImpl;
+pub use device::{DeviceImpl, DriverVersion};
+#[cfg(feature = "winfw")]
+pub use firewall::{add_firewall_rule_allow_all, FirewallRule};
+pub use network_list::NetworkCategory;
+pub use tun::{SessionRestart, WintunTrust};
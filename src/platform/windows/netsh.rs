@@ -44,6 +44,10 @@ fn output(cmd: &str, out: Output) -> io::Result<()> {
     }
     Ok(())
 }
+pub fn set_ipv6_privacy(enabled: bool) -> io::Result<()> {
+    let state = if enabled { "enabled" } else { "disabled" };
+    exe_cmd(&format!("netsh interface ipv6 set privacy state={state}"))
+}
 pub fn set_primary_dns(index: u32, address: IpAddr) -> io::Result<()> {
     let (family, addr_str) = match address {
         IpAddr::V4(v4) => ("ipv4", v4.to_string()),
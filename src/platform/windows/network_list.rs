@@ -0,0 +1,270 @@
+//! Network category (firewall profile) of an interface via the Network List Manager.
+//!
+//! Windows assigns every identified network a category, which selects the firewall profile
+//! that applies to it. A VPN adapter without a gateway usually ends up as an "Unidentified
+//! network" in the public category, where inbound traffic is blocked. The category is only
+//! exposed through the `INetworkListManager` COM interface, which `windows-sys` does not
+//! declare, so the few methods used here are called through hand-written vtables.
+
+use std::ffi::c_void;
+use std::io;
+use std::ptr::{self, NonNull};
+
+use windows_sys::core::{GUID, HRESULT};
+use windows_sys::Win32::Foundation::RPC_E_CHANGED_MODE;
+use windows_sys::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED,
+};
+
+/// The category of the network an interface is connected to, which selects the firewall
+/// profile applied to it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum NetworkCategory {
+    /// Untrusted network, e.g. a public hotspot. The default for unidentified networks.
+    Public,
+    /// Trusted network, e.g. at home.
+    Private,
+    /// Network of an Active Directory domain the host has authenticated to. It cannot be set.
+    DomainAuthenticated,
+}
+
+impl NetworkCategory {
+    fn from_raw(category: i32) -> io::Result<Self> {
+        match category {
+            NLM_NETWORK_CATEGORY_PUBLIC => Ok(NetworkCategory::Public),
+            NLM_NETWORK_CATEGORY_PRIVATE => Ok(NetworkCategory::Private),
+            NLM_NETWORK_CATEGORY_DOMAIN_AUTHENTICATED => Ok(NetworkCategory::DomainAuthenticated),
+            category => Err(io::Error::other(format!(
+                "unknown network category {category}"
+            ))),
+        }
+    }
+    fn into_raw(self) -> io::Result<i32> {
+        match self {
+            NetworkCategory::Public => Ok(NLM_NETWORK_CATEGORY_PUBLIC),
+            NetworkCategory::Private => Ok(NLM_NETWORK_CATEGORY_PRIVATE),
+            NetworkCategory::DomainAuthenticated => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the domain category is assigned by Windows and cannot be set",
+            )),
+        }
+    }
+}
+
+const NLM_NETWORK_CATEGORY_PUBLIC: i32 = 0;
+const NLM_NETWORK_CATEGORY_PRIVATE: i32 = 1;
+const NLM_NETWORK_CATEGORY_DOMAIN_AUTHENTICATED: i32 = 2;
+
+const CLSID_NETWORK_LIST_MANAGER: GUID = GUID::from_u128(0xdcb00c01_570f_4a9b_8d69_199fdba5723b);
+const IID_INETWORK_LIST_MANAGER: GUID = GUID::from_u128(0xdcb00000_570f_4a9b_8d69_199fdba5723b);
+
+/// Sets the category of the network the adapter `adapter_id` is connected to.
+pub fn set_network_category(adapter_id: &GUID, category: NetworkCategory) -> io::Result<()> {
+    let category = category.into_raw()?;
+    with_network(adapter_id, |network| {
+        check(unsafe { (network.vtbl().set_category)(network.as_raw(), category) })
+    })
+}
+
+/// Returns the category of the network the adapter `adapter_id` is connected to.
+pub fn network_category(adapter_id: &GUID) -> io::Result<NetworkCategory> {
+    with_network(adapter_id, |network| {
+        let mut category = 0;
+        check(unsafe { (network.vtbl().get_category)(network.as_raw(), &mut category) })?;
+        NetworkCategory::from_raw(category)
+    })
+}
+
+/// Finds the network the adapter is connected to and passes it to `f`.
+fn with_network<R>(
+    adapter_id: &GUID,
+    f: impl FnOnce(&ComPtr<NetworkVtbl>) -> io::Result<R>,
+) -> io::Result<R> {
+    let _com = ComInit::new()?;
+    let manager: ComPtr<NetworkListManagerVtbl> = unsafe {
+        let mut manager = ptr::null_mut();
+        check(CoCreateInstance(
+            &CLSID_NETWORK_LIST_MANAGER,
+            ptr::null_mut(),
+            CLSCTX_ALL,
+            &IID_INETWORK_LIST_MANAGER,
+            &mut manager,
+        ))?;
+        ComPtr::from_raw(manager)?
+    };
+    let connections: ComPtr<EnumNetworkConnectionsVtbl> = unsafe {
+        let mut connections = ptr::null_mut();
+        check((manager.vtbl().get_network_connections)(
+            manager.as_raw(),
+            &mut connections,
+        ))?;
+        ComPtr::from_raw(connections)?
+    };
+    loop {
+        let connection: ComPtr<NetworkConnectionVtbl> = unsafe {
+            let mut connection = ptr::null_mut();
+            let mut fetched = 0;
+            check((connections.vtbl().next)(
+                connections.as_raw(),
+                1,
+                &mut connection,
+                &mut fetched,
+            ))?;
+            if fetched == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the interface is not connected to a network yet",
+                ));
+            }
+            ComPtr::from_raw(connection)?
+        };
+        let mut id: GUID = unsafe { std::mem::zeroed() };
+        check(unsafe { (connection.vtbl().get_adapter_id)(connection.as_raw(), &mut id) })?;
+        if !guid_eq(&id, adapter_id) {
+            continue;
+        }
+        let network: ComPtr<NetworkVtbl> = unsafe {
+            let mut network = ptr::null_mut();
+            check((connection.vtbl().get_network)(
+                connection.as_raw(),
+                &mut network,
+            ))?;
+            ComPtr::from_raw(network)?
+        };
+        return f(&network);
+    }
+}
+
+fn guid_eq(a: &GUID, b: &GUID) -> bool {
+    (a.data1, a.data2, a.data3, a.data4) == (b.data1, b.data2, b.data3, b.data4)
+}
+
+pub(super) fn check(hr: HRESULT) -> io::Result<()> {
+    if hr < 0 {
+        Err(io::Error::from_raw_os_error(hr))
+    } else {
+        Ok(())
+    }
+}
+
+/// Initializes COM on the current thread for as long as it lives, unless the thread already
+/// joined a single-threaded apartment, which works just as well.
+pub(super) struct ComInit(bool);
+
+impl ComInit {
+    pub(super) fn new() -> io::Result<ComInit> {
+        match unsafe { CoInitializeEx(ptr::null(), COINIT_MULTITHREADED as u32) } {
+            RPC_E_CHANGED_MODE => Ok(ComInit(false)),
+            hr => check(hr).map(|_| ComInit(true)),
+        }
+    }
+}
+
+impl Drop for ComInit {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+/// An owned COM interface pointer whose vtable starts with `V`.
+pub(super) struct ComPtr<V>(NonNull<*const V>);
+
+impl<V> ComPtr<V> {
+    /// # Safety
+    ///
+    /// `ptr` must be an owned reference to an interface whose vtable starts with `V`.
+    pub(super) unsafe fn from_raw(ptr: *mut c_void) -> io::Result<Self> {
+        NonNull::new(ptr.cast())
+            .map(ComPtr)
+            .ok_or_else(|| io::Error::other("null COM interface pointer"))
+    }
+    pub(super) fn as_raw(&self) -> *mut c_void {
+        self.0.as_ptr().cast()
+    }
+    pub(super) fn vtbl(&self) -> &V {
+        unsafe { &**self.0.as_ptr() }
+    }
+}
+
+impl<V> Drop for ComPtr<V> {
+    fn drop(&mut self) {
+        unsafe {
+            let vtbl = *self.0.as_ptr().cast::<*const UnknownVtbl>();
+            ((*vtbl).release)(self.as_raw());
+        }
+    }
+}
+
+pub(super) type Unused = usize;
+
+#[allow(dead_code)]
+#[repr(C)]
+struct UnknownVtbl {
+    query_interface: Unused,
+    add_ref: Unused,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub(super) struct DispatchVtbl {
+    unknown: UnknownVtbl,
+    get_type_info_count: Unused,
+    get_type_info: Unused,
+    get_ids_of_names: Unused,
+    invoke: Unused,
+}
+
+/// `INetworkListManager`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct NetworkListManagerVtbl {
+    dispatch: DispatchVtbl,
+    get_networks: Unused,
+    get_network: Unused,
+    get_network_connections: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> HRESULT,
+}
+
+/// `IEnumNetworkConnections`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct EnumNetworkConnectionsVtbl {
+    dispatch: DispatchVtbl,
+    new_enum: Unused,
+    next: unsafe extern "system" fn(*mut c_void, u32, *mut *mut c_void, *mut u32) -> HRESULT,
+}
+
+/// `INetworkConnection`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct NetworkConnectionVtbl {
+    dispatch: DispatchVtbl,
+    get_network: unsafe extern "system" fn(*mut c_void, *mut *mut c_void) -> HRESULT,
+    is_connected_to_internet: Unused,
+    is_connected: Unused,
+    get_connectivity: Unused,
+    get_connection_id: Unused,
+    get_adapter_id: unsafe extern "system" fn(*mut c_void, *mut GUID) -> HRESULT,
+}
+
+/// `INetwork`, up to the methods used.
+#[allow(dead_code)]
+#[repr(C)]
+struct NetworkVtbl {
+    dispatch: DispatchVtbl,
+    get_name: Unused,
+    set_name: Unused,
+    get_description: Unused,
+    set_description: Unused,
+    get_network_id: Unused,
+    get_domain_type: Unused,
+    get_network_connections: Unused,
+    get_time_created_and_connected: Unused,
+    is_connected_to_internet: Unused,
+    is_connected: Unused,
+    get_connectivity: Unused,
+    get_category: unsafe extern "system" fn(*mut c_void, *mut i32) -> HRESULT,
+    set_category: unsafe extern "system" fn(*mut c_void, i32) -> HRESULT,
+}
@@ -0,0 +1,62 @@
+//! Interface renaming via `NciSetConnectionName`.
+//!
+//! Windows has no documented API to change the alias of an interface. `nci.dll` exports
+//! `NciSetConnectionName`, which is what the network connections folder and Wintun itself
+//! use to name adapters, so it is resolved at run time; when it is unavailable, we fall back
+//! to `netsh interface set interface`. Either way only the alias changes: the adapter, its
+//! addresses and a running Wintun session or TAP handle are left alone.
+
+use std::io;
+use std::sync::OnceLock;
+
+use libloading::os::windows::{Library, Symbol, LOAD_LIBRARY_SEARCH_SYSTEM32};
+use windows_sys::core::GUID;
+use windows_sys::Win32::NetworkManagement::Ndis::NET_LUID_LH;
+
+use super::ffi;
+use super::netsh;
+
+/// Longest alias an interface can have, in UTF-16 units (`IF_MAX_STRING_SIZE`).
+const MAX_ALIAS_LEN: usize = 256;
+
+/// Signature of `nci!NciSetConnectionName`.
+type NciSetConnectionNameFn = unsafe extern "system" fn(guid: *const GUID, name: *const u16) -> u32;
+
+/// Cached `NciSetConnectionName`, `None` if `nci.dll` does not export it.
+static NCI_SET_CONNECTION_NAME: OnceLock<Option<NciSetConnectionNameFn>> = OnceLock::new();
+
+fn nci_set_connection_name() -> Option<NciSetConnectionNameFn> {
+    *NCI_SET_CONNECTION_NAME.get_or_init(|| {
+        // Load `nci.dll` from `System32` only, to avoid DLL search-order hijacking.
+        let library =
+            unsafe { Library::load_with_flags("nci.dll", LOAD_LIBRARY_SEARCH_SYSTEM32) }.ok()?;
+        let func = unsafe {
+            // SAFETY: the signature matches the one Wintun declares for the export.
+            let symbol: Symbol<NciSetConnectionNameFn> =
+                library.get(b"NciSetConnectionName\0").ok()?;
+            *symbol
+        };
+        // Leaked like `iphlpapi.dll` in `dns`: `func` must stay valid for the process.
+        std::mem::forget(library);
+        Some(func)
+    })
+}
+
+/// Renames the interface `luid`, currently called `old_name`, to `new_name`.
+pub fn set_interface_alias(luid: &NET_LUID_LH, old_name: &str, new_name: &str) -> io::Result<()> {
+    if new_name.is_empty() || new_name.encode_utf16().count() > MAX_ALIAS_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("interface name must be 1 to {MAX_ALIAS_LEN} characters long"),
+        ));
+    }
+    match nci_set_connection_name() {
+        Some(set_connection_name) => {
+            let guid = ffi::luid_to_guid(luid)?;
+            let name = ffi::encode_utf16(new_name);
+            // SAFETY: `guid` and the NUL-terminated `name` outlive the call.
+            ffi::win_result(unsafe { set_connection_name(&guid, name.as_ptr()) })
+        }
+        None => netsh::set_interface_name(old_name, new_name),
+    }
+}
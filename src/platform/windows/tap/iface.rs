@@ -96,7 +96,7 @@ pub fn create_interface(component_id: &str) -> io::Result<NET_LUID_LH> {
     }
 
     if driver_version == 0 {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "No driver found"));
+        return Err(crate::Error::DriverNotFound.into());
     }
 
     let uninstaller = guard((), |_| {
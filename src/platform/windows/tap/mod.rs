@@ -1,8 +1,10 @@
 use crate::platform::windows::tap::overlapped::{ReadOverlapped, WriteOverlapped};
-use crate::platform::windows::{ffi, netsh};
+use crate::platform::windows::{ffi, DriverVersion};
 use bytes::buf::UninitSlice;
+use std::io::{IoSlice, IoSliceMut};
 use std::os::windows::io::{AsRawHandle, OwnedHandle};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::{io, time};
 use windows_sys::Win32::Foundation::HANDLE;
 use windows_sys::Win32::NetworkManagement::Ndis::NET_LUID_LH;
@@ -13,10 +15,13 @@ mod overlapped;
 
 pub struct TapDevice {
     tap_interface: TapInterface,
-    handle: Arc<OwnedHandle>,
+    /// Replaced when `set_mac` restarts the adapter.
+    handle: RwLock<Arc<OwnedHandle>>,
     index: u32,
     read_io_overlapped: Mutex<ReadOverlapped>,
     write_io_overlapped: Mutex<WriteOverlapped>,
+    /// The last media status set, the driver cannot be asked for it.
+    connected: AtomicBool,
 }
 pub(crate) const READ_BUFFER_SIZE: usize = 14 + 65536;
 unsafe impl Send for TapDevice {}
@@ -36,14 +41,19 @@ struct TapInterface {
     need_delete: bool,
 }
 
-fn get_version(handle: HANDLE) -> io::Result<[u64; 3]> {
-    let in_version: [u64; 3] = [0; 3];
-    let mut out_version: [u64; 3] = [0; 3];
+/// Queries the driver version as `[major, minor, debug]`.
+fn get_version(handle: HANDLE) -> io::Result<[u32; 3]> {
+    let in_version: [u32; 3] = [0; 3];
+    let mut out_version: [u32; 3] = [0; 3];
     ffi::device_io_control(handle, TAP_IOCTL_GET_VERSION, &in_version, &mut out_version)
         .map(|_| out_version)
 }
 
 impl TapDevice {
+    /// Whether the adapter is deleted on drop.
+    pub(crate) fn set_delete_on_drop(&mut self, delete: bool) {
+        self.tap_interface.need_delete = delete;
+    }
     pub fn luid(&self) -> NET_LUID_LH {
         self.tap_interface.luid
     }
@@ -106,10 +116,11 @@ impl TapDevice {
         tap_interface.need_delete = !persist;
         Ok(Self {
             tap_interface,
-            handle,
+            handle: RwLock::new(handle),
             index,
             read_io_overlapped: Mutex::new(read_io_overlapped),
             write_io_overlapped: Mutex::new(write_io_overlapped),
+            connected: AtomicBool::new(false),
         })
     }
 
@@ -145,12 +156,67 @@ impl TapDevice {
         Ok(Self {
             index,
             tap_interface,
-            handle,
+            handle: RwLock::new(handle),
+            read_io_overlapped: Mutex::new(read_io_overlapped),
+            write_io_overlapped: Mutex::new(write_io_overlapped),
+            connected: AtomicBool::new(false),
+        })
+    }
+
+    /// Wraps an open handle of a tap-windows device without taking ownership of it.
+    ///
+    /// A handle does not name its adapter, so the adapter is found by the MAC address the
+    /// driver reports for it.
+    ///
+    /// # Safety
+    /// `handle` must be a tap-windows device handle opened for overlapped I/O that stays open
+    /// while the returned device exists.
+    #[cfg(any(feature = "async_io", feature = "async_tokio"))]
+    pub unsafe fn borrow_raw(
+        component_id: &str,
+        handle: std::os::windows::io::RawHandle,
+    ) -> io::Result<Self> {
+        use std::os::windows::io::FromRawHandle;
+        let mut mac = [0u8; 6];
+        ffi::device_io_control(handle, TAP_IOCTL_GET_MAC, &(), &mut mac)?;
+        let index = getifaddrs::getifaddrs()?
+            .find(|interface| interface.address.mac_addr() == Some(mac))
+            .and_then(|interface| interface.index)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no adapter has the MAC address of the handle",
+                )
+            })?;
+        let luid = ffi::index_to_luid(index)?;
+        iface::check_interface(component_id, &luid)?;
+        let handle = Arc::new(OwnedHandle::from_raw_handle(handle));
+        // The handle belongs to the caller, so one reference is leaked to never close it.
+        std::mem::forget(handle.clone());
+        let read_io_overlapped = ReadOverlapped::new(handle.clone())?;
+        let write_io_overlapped = WriteOverlapped::new(handle.clone())?;
+        Ok(Self {
+            tap_interface: TapInterface {
+                luid,
+                component_id: component_id.to_string(),
+                need_delete: false,
+            },
+            handle: RwLock::new(handle),
+            index,
             read_io_overlapped: Mutex::new(read_io_overlapped),
             write_io_overlapped: Mutex::new(write_io_overlapped),
+            connected: AtomicBool::new(false),
         })
     }
 
+    /// Sets what a read does with a frame larger than the buffer.
+    pub fn set_truncate_policy(&mut self, policy: crate::TruncatePolicy) {
+        self.read_io_overlapped
+            .get_mut()
+            .unwrap()
+            .set_truncate_policy(policy);
+    }
+
     /// Sets the status of the interface to disconnected.
     /// Equivalent to `.set_status(false)`
     pub fn down(&self) -> io::Result<()> {
@@ -161,20 +227,68 @@ impl TapDevice {
     pub fn get_mac(&self) -> io::Result<[u8; 6]> {
         let mut mac = [0; 6];
         ffi::device_io_control(
-            self.handle.as_raw_handle(),
+            self.handle.read().unwrap().as_raw_handle(),
             TAP_IOCTL_GET_MAC,
             &(),
             &mut mac,
         )
         .map(|_| mac)
     }
-    pub fn set_mac(&self, _mac: &[u8; 6]) -> io::Result<()> {
-        Err(io::Error::from(io::ErrorKind::Unsupported))?
+    /// Sets the mac of the interface.
+    ///
+    /// The driver only reads the `NetworkAddress` registry value when the adapter starts, so
+    /// this restarts the adapter and reopens it. Pending reads and writes are cancelled, and
+    /// the media status is restored.
+    pub fn set_mac(&self, mac: &[u8; 6]) -> io::Result<()> {
+        let luid = &self.tap_interface.luid;
+        let component_id = &self.tap_interface.component_id;
+        let guid = ffi::string_from_guid(&ffi::luid_to_guid(luid)?)?;
+        let mac: String = mac.iter().map(|b| format!("{b:02X}")).collect();
+        iface::set_adapter_mac_by_guid(&guid, &mac)?;
+        // No reads or writes while the handle is replaced.
+        let mut read = self.read_io_overlapped.lock().unwrap();
+        let mut write = self.write_io_overlapped.lock().unwrap();
+        let mut handle = self.handle.write().unwrap();
+        iface::enable_adapter(component_id, luid, false)?;
+        std::thread::sleep(time::Duration::from_millis(20));
+        iface::enable_adapter(component_id, luid, true)?;
+        // The device file reappears once the driver has started the adapter again.
+        let start = time::Instant::now();
+        let new_handle = loop {
+            match iface::open_interface(luid) {
+                Ok(new_handle) if get_version(new_handle.as_raw_handle()).is_ok() => {
+                    break Arc::new(new_handle)
+                }
+                _ if start.elapsed() > time::Duration::from_secs(3) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "Interface timed out",
+                    ));
+                }
+                _ => std::thread::sleep(time::Duration::from_millis(20)),
+            }
+        };
+        read.set_file_handle(new_handle.clone())?;
+        write.set_file_handle(new_handle.clone())?;
+        *handle = new_handle;
+        drop(handle);
+        if self.status() {
+            self.set_status(true)?;
+        }
+        Ok(())
     }
 
     /// Retrieve the version of the driver
-    pub fn get_version(&self) -> io::Result<[u64; 3]> {
-        get_version(self.handle.as_raw_handle())
+    pub fn get_version(&self) -> io::Result<[u32; 3]> {
+        get_version(self.handle.read().unwrap().as_raw_handle())
+    }
+    pub fn driver_version(&self) -> io::Result<DriverVersion> {
+        let [major, minor, debug] = self.get_version()?;
+        Ok(DriverVersion {
+            major,
+            minor,
+            debug: debug != 0,
+        })
     }
 
     // ///Retieve the mtu of the interface
@@ -193,7 +307,7 @@ impl TapDevice {
     /// Set the name of the interface
     pub fn set_name(&self, newname: &str) -> io::Result<()> {
         let name = self.get_name()?;
-        netsh::set_interface_name(&name, newname)
+        super::rename::set_interface_alias(&self.tap_interface.luid, &name, newname)
     }
 
     // /// Set the ip of the interface
@@ -214,17 +328,19 @@ impl TapDevice {
         let status: u32 = if status { 1 } else { 0 };
         let mut out_status: u32 = 0;
         ffi::device_io_control(
-            self.handle.as_raw_handle(),
+            self.handle.read().unwrap().as_raw_handle(),
             TAP_IOCTL_SET_MEDIA_STATUS,
             &status,
             &mut out_status,
-        )
+        )?;
+        self.connected.store(status != 0, Ordering::Relaxed);
+        Ok(())
     }
-    #[cfg(any(
-        feature = "interruptible",
-        feature = "async_tokio",
-        feature = "async_io"
-    ))]
+    /// Returns the media status last set with [`set_status`](Self::set_status).
+    pub fn status(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+    #[cfg(feature = "interruptible")]
     pub fn wait_readable_interruptible(
         &self,
         interrupt_event: &OwnedHandle,
@@ -235,6 +351,24 @@ impl TapDevice {
         drop(guard);
         event.wait_interruptible(interrupt_event, timeout)
     }
+    /// The event signaled when the pending read completes, and while no read is pending.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn read_event(&self) -> Arc<OwnedHandle> {
+        self.read_io_overlapped
+            .lock()
+            .unwrap()
+            .overlapped_event()
+            .handle()
+    }
+    /// The event signaled when the pending write completes, and while no write is pending.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn write_event(&self) -> Arc<OwnedHandle> {
+        self.write_io_overlapped
+            .lock()
+            .unwrap()
+            .overlapped_event()
+            .handle()
+    }
     pub fn wait_readable(&self) -> io::Result<()> {
         let guard = self.read_io_overlapped.lock().unwrap();
         let event_handle = guard.overlapped_event();
@@ -242,7 +376,8 @@ impl TapDevice {
         event_handle.wait()
     }
 
-    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+    /// Reads a frame, returning the number of bytes copied and the length of the frame.
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         let Ok(mut guard) = self.read_io_overlapped.try_lock() else {
             return Err(io::Error::from(io::ErrorKind::WouldBlock));
         };
@@ -261,7 +396,19 @@ impl TapDevice {
         };
         guard.try_write(buf)
     }
-    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+    pub fn try_read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let Ok(mut guard) = self.read_io_overlapped.try_lock() else {
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        };
+        guard.try_read_vectored(bufs)
+    }
+    pub fn try_write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let Ok(mut guard) = self.write_io_overlapped.try_lock() else {
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        };
+        guard.try_write_vectored(bufs)
+    }
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         loop {
             match self.try_read(buf) {
                 Ok(len) => return Ok(len),
@@ -275,6 +422,36 @@ impl TapDevice {
         let mut guard = self.write_io_overlapped.lock().unwrap();
         guard.write(buf)
     }
+    /// Like [`read`](Self::read), but fails with `TimedOut` once `deadline` passes.
+    pub(crate) fn read_deadline(
+        &self,
+        buf: &mut [u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<time::Instant>,
+    ) -> io::Result<(usize, usize)> {
+        loop {
+            match self.try_read(buf) {
+                Ok(len) => return Ok(len),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+            let guard = self.read_io_overlapped.lock().unwrap();
+            let event = guard.overlapped_event();
+            drop(guard);
+            event.wait_deadline(interrupt_event, deadline)?
+        }
+    }
+    /// Like [`write`](Self::write), but fails with `TimedOut` if the previous write is still
+    /// pending at `deadline`.
+    pub(crate) fn write_deadline(
+        &self,
+        buf: &[u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<time::Instant>,
+    ) -> io::Result<usize> {
+        let mut guard = self.write_io_overlapped.lock().unwrap();
+        guard.write_deadline(buf, interrupt_event, deadline)
+    }
 
     #[allow(dead_code)]
     pub(crate) fn write_interruptible(
@@ -1,15 +1,20 @@
 use crate::platform::windows::ffi;
 use crate::platform::windows::tap::READ_BUFFER_SIZE;
+use crate::TruncatePolicy;
 use bytes::buf::UninitSlice;
 use bytes::BytesMut;
 use std::io;
+use std::io::{IoSlice, IoSliceMut};
 use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::ptr;
 use std::sync::Arc;
-use windows_sys::Win32::System::Threading::{WaitForMultipleObjects, INFINITE};
+use std::time::Instant;
+use windows_sys::Win32::System::Threading::INFINITE;
 use windows_sys::Win32::System::IO::OVERLAPPED;
 pub(crate) struct ReadOverlapped {
     read_buffer: BytesMut,
     inner: OwnedOVERLAPPED,
+    truncate_policy: TruncatePolicy,
 }
 impl ReadOverlapped {
     pub fn new(file_handle: Arc<OwnedHandle>) -> io::Result<ReadOverlapped> {
@@ -17,16 +22,41 @@ impl ReadOverlapped {
         Ok(Self {
             read_buffer: BytesMut::zeroed(READ_BUFFER_SIZE),
             inner,
+            truncate_policy: TruncatePolicy::Error,
         })
     }
-    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    pub fn set_truncate_policy(&mut self, policy: TruncatePolicy) {
+        self.truncate_policy = policy;
+    }
+    /// Moves to a reopened device handle, cancelling the pending read.
+    pub fn set_file_handle(&mut self, file_handle: Arc<OwnedHandle>) -> io::Result<()> {
+        self.inner.set_file_handle(file_handle)
+    }
+    /// Reads a frame, returning the number of bytes copied and the length of the frame.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         self.try_read_raw(buf.as_mut_ptr(), buf.len())
     }
     #[allow(dead_code)]
     pub fn try_read_uninit(&mut self, buf: &mut UninitSlice) -> io::Result<usize> {
         self.try_read_raw(buf.as_mut_ptr(), buf.len())
+            .map(|(len, _)| len)
+    }
+    pub fn try_read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let dst_len = bufs.iter().map(|buf| buf.len()).sum();
+        self.try_read_with(dst_len, |packet| crate::platform::scatter(packet, bufs))
+            .map(|(len, _)| len)
+    }
+    fn try_read_raw(&mut self, dst: *mut u8, dst_len: usize) -> io::Result<(usize, usize)> {
+        self.try_read_with(dst_len, |packet| unsafe {
+            std::ptr::copy_nonoverlapping(packet.as_ptr(), dst, packet.len());
+        })
     }
-    fn try_read_raw(&mut self, dst: *mut u8, dst_len: usize) -> io::Result<usize> {
+    /// Completes a read into `dst_len` bytes, passing the part of the packet that fits to `copy`.
+    fn try_read_with(
+        &mut self,
+        dst_len: usize,
+        copy: impl FnOnce(&[u8]),
+    ) -> io::Result<(usize, usize)> {
         let inner = &mut self.inner;
         let result = if inner.no_pending_io {
             inner.reset()?;
@@ -49,16 +79,14 @@ impl ReadOverlapped {
         match result {
             Ok(len) => {
                 inner.no_pending_io = true;
-                if len > dst_len {
+                let Some(copy_len) = self.truncate_policy.apply(len, dst_len) else {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidInput,
                         "receive buffer too small",
                     ));
-                }
-                unsafe {
-                    std::ptr::copy_nonoverlapping(self.read_buffer.as_ptr(), dst, len);
-                }
-                Ok(len)
+                };
+                copy(&self.read_buffer[..copy_len]);
+                Ok((copy_len, len))
             }
             Err(e) => {
                 if e.kind() != io::ErrorKind::WouldBlock {
@@ -86,12 +114,26 @@ impl WriteOverlapped {
             inner,
         })
     }
+    /// Moves to a reopened device handle, cancelling the pending write.
+    pub fn set_file_handle(&mut self, file_handle: Arc<OwnedHandle>) -> io::Result<()> {
+        self.inner.set_file_handle(file_handle)
+    }
     pub fn try_write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if !self.finish_pending_nonblocking()? {
             return Err(io::Error::from(io::ErrorKind::WouldBlock));
         }
         self.submit(buf)
     }
+    pub fn try_write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if !self.finish_pending_nonblocking()? {
+            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        }
+        self.submit_with(|buffer| {
+            for buf in bufs {
+                buffer.extend_from_slice(buf);
+            }
+        })
+    }
     pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.finish_pending_blocking();
         self.submit(buf)
@@ -101,14 +143,26 @@ impl WriteOverlapped {
         buf: &[u8],
         interrupt_event: &OwnedHandle,
     ) -> io::Result<usize> {
-        self.finish_pending_interruptible(interrupt_event)?;
+        self.write_deadline(buf, Some(interrupt_event), None)
+    }
+    pub fn write_deadline(
+        &mut self,
+        buf: &[u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        self.finish_pending_deadline(interrupt_event, deadline)?;
         self.submit(buf)
     }
     fn submit(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.submit_with(|buffer| buffer.extend_from_slice(buf))
+    }
+    fn submit_with(&mut self, fill: impl FnOnce(&mut BytesMut)) -> io::Result<usize> {
         let inner = &mut self.inner;
         inner.reset()?;
         self.read_buffer.clear();
-        self.read_buffer.extend_from_slice(buf);
+        fill(&mut self.read_buffer);
+        let len = self.read_buffer.len();
         match ffi::try_write_file(
             inner.file_handle.as_raw_handle(),
             &mut inner.overlapped,
@@ -120,7 +174,7 @@ impl WriteOverlapped {
             }
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
                 inner.no_pending_io = false;
-                Ok(buf.len())
+                Ok(len)
             }
             Err(e) => {
                 inner.no_pending_io = true;
@@ -159,12 +213,16 @@ impl WriteOverlapped {
         }
         inner.no_pending_io = true;
     }
-    fn finish_pending_interruptible(&mut self, interrupt_event: &OwnedHandle) -> io::Result<()> {
+    fn finish_pending_deadline(
+        &mut self,
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<()> {
         if self.inner.no_pending_io {
             return Ok(());
         }
         self.overlapped_event()
-            .wait_interruptible(interrupt_event, None)?;
+            .wait_deadline(interrupt_event, deadline)?;
         self.finish_pending_blocking();
         Ok(())
     }
@@ -204,6 +262,17 @@ impl OwnedOVERLAPPED {
         })
     }
 
+    /// Replaces the file handle, keeping the event that waiters hold on to.
+    pub fn set_file_handle(&mut self, file_handle: Arc<OwnedHandle>) -> io::Result<()> {
+        if !self.no_pending_io {
+            // Waits for the cancelled I/O to let go of the OVERLAPPED.
+            _ = ffi::cancel_io_overlapped(self.file_handle.as_raw_handle(), self.as_overlapped());
+            self.no_pending_io = true;
+        }
+        self.file_handle = file_handle;
+        // Signaled while no I/O is pending, as after `new`.
+        ffi::set_event(self.event_handle.as_raw_handle())
+    }
     pub fn as_overlapped(&self) -> &OVERLAPPED {
         &self.overlapped
     }
@@ -216,6 +285,10 @@ pub struct OverlappedEvent {
     event: Arc<OwnedHandle>,
 }
 impl OverlappedEvent {
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub fn handle(&self) -> Arc<OwnedHandle> {
+        self.event.clone()
+    }
     pub fn wait(&self) -> io::Result<()> {
         ffi::wait_for_single_object(self.event.as_raw_handle(), INFINITE)
     }
@@ -224,32 +297,27 @@ impl OverlappedEvent {
         interrupt_event: &OwnedHandle,
         timeout: Option<std::time::Duration>,
     ) -> io::Result<()> {
-        let handles = [self.event.as_raw_handle(), interrupt_event.as_raw_handle()];
-        unsafe {
-            let wait_ret = WaitForMultipleObjects(
-                2,
-                handles.as_ptr(),
-                0,
-                timeout
-                    .map(|t| t.as_millis().min(INFINITE as _) as _)
-                    .unwrap_or(INFINITE),
-            );
-            match wait_ret {
-                windows_sys::Win32::Foundation::WAIT_OBJECT_0 => Ok(()),
-                windows_sys::Win32::Foundation::WAIT_TIMEOUT => {
-                    Err(io::Error::from(io::ErrorKind::TimedOut))
-                }
-                _ => {
-                    if wait_ret == windows_sys::Win32::Foundation::WAIT_OBJECT_0 + 1 {
-                        Err(io::Error::new(
-                            io::ErrorKind::Interrupted,
-                            "trigger interrupt",
-                        ))
-                    } else {
-                        Err(io::Error::last_os_error())
-                    }
-                }
-            }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        self.wait_deadline(Some(interrupt_event), deadline)
+    }
+    /// Waits for the event until `deadline`, failing with `TimedOut` once it passes.
+    pub fn wait_deadline(
+        &self,
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<()> {
+        let handles = [
+            self.event.as_raw_handle(),
+            interrupt_event.map_or(ptr::null_mut(), |e| e.as_raw_handle()),
+        ];
+        let len = if interrupt_event.is_some() { 2 } else { 1 };
+        match ffi::wait_for_multiple_objects(&handles[..len], deadline)? {
+            Some(0) => Ok(()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "trigger interrupt",
+            )),
+            None => Err(io::Error::from(io::ErrorKind::TimedOut)),
         }
     }
 }
@@ -2,7 +2,10 @@ use std::{io, mem, ptr};
 
 use crate::windows::{
     device::GUID_NETWORK_ADAPTER,
-    ffi::{decode_utf16, destroy_device_info_list, encode_utf16, enum_device_info},
+    ffi::{
+        decode_utf16, destroy_device_info_list, encode_utf16, enum_device_info,
+        set_device_registry_property,
+    },
 };
 use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
 use windows_sys::Win32::Foundation::{DEVPROPKEY, ERROR_INSUFFICIENT_BUFFER};
@@ -12,7 +15,8 @@ use windows_sys::{
         Devices::{
             DeviceAndDriverInstallation::{
                 CM_Get_DevNode_Status, SetupDiGetClassDevsExW, SetupDiGetDevicePropertyW,
-                CM_DEVNODE_STATUS_FLAGS, CR_SUCCESS, DN_HAS_PROBLEM, HDEVINFO, SP_DEVINFO_DATA,
+                CM_DEVNODE_STATUS_FLAGS, CR_SUCCESS, DN_HAS_PROBLEM, HDEVINFO, SPDRP_FRIENDLYNAME,
+                SP_DEVINFO_DATA,
             },
             Properties::DEVPROPID_FIRST_USABLE,
         },
@@ -42,8 +46,10 @@ pub const DEVPKEY_Wintun_OwningProcess: DEVPROPKEY = DEVPROPKEY {
     pid: DEVPROPID_FIRST_USABLE + 3,
 };
 
-pub fn check_adapter_if_orphaned_devices(adapter_name: &str) -> bool {
-    if is_windows_seven() {
+/// Whether `adapter_name` is left over from a process that is gone; `win7` selects the
+/// Windows 7 check by owning process.
+pub fn check_adapter_if_orphaned_devices(adapter_name: &str, win7: bool) -> bool {
+    if win7 {
         return super::adapter_win7::check_adapter_if_orphaned_devices_win7(adapter_name);
     }
 
@@ -100,6 +106,64 @@ pub fn check_adapter_if_orphaned_devices(adapter_name: &str) -> bool {
     is_orphaned_adapter
 }
 
+/// Sets the friendly name of the Wintun adapter `adapter_name`, which Windows shows as its
+/// description.
+pub fn set_adapter_description(
+    adapter_name: &str,
+    description: &str,
+    win7: bool,
+) -> io::Result<()> {
+    if win7 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "setting the description is not supported on Windows 7",
+        ));
+    }
+
+    let device_name = encode_utf16("SWD\\Wintun");
+    let dev_info = unsafe {
+        SetupDiGetClassDevsExW(
+            &GUID_NETWORK_ADAPTER,
+            device_name.as_ptr(),
+            ptr::null_mut(),
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if dev_info == INVALID_HANDLE_VALUE as isize {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut index = 0;
+    let result = loop {
+        match enum_device_info(dev_info, index) {
+            Some(Ok(devinfo_data)) => {
+                if get_device_name(dev_info, &devinfo_data).is_ok_and(|name| name == adapter_name) {
+                    break set_device_registry_property(
+                        dev_info,
+                        &devinfo_data,
+                        SPDRP_FRIENDLYNAME,
+                        description,
+                    );
+                }
+            }
+            Some(Err(_)) => {}
+            None => {
+                break Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "wintun adapter not found",
+                ))
+            }
+        }
+        index += 1;
+    };
+
+    _ = destroy_device_info_list(dev_info);
+    result
+}
+
 pub fn get_device_name(devinfo: HDEVINFO, devinfo_data: &SP_DEVINFO_DATA) -> io::Result<String> {
     let mut prop_type: u32 = 0;
     let mut required_size: u32 = 0;
@@ -143,7 +207,8 @@ pub fn get_device_name(devinfo: HDEVINFO, devinfo_data: &SP_DEVINFO_DATA) -> io:
     Ok(decode_utf16(&buf))
 }
 
-fn is_windows_seven() -> bool {
+/// Whether the OS is Windows 7 (or Server 2008 R2), where Wintun needs the compatibility mode.
+pub fn is_windows_seven() -> bool {
     let mut info = OSVERSIONINFOA {
         dwOSVersionInfoSize: mem::size_of::<OSVERSIONINFOA>() as u32,
         dwMajorVersion: 0,
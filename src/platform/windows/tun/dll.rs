@@ -0,0 +1,425 @@
+//! Locating and loading the `wintun.dll` matching the architecture of the process.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::wintun_raw;
+
+/// File name used when no `wintun_file` is set.
+pub const DEFAULT_WINTUN_FILE: &str = "wintun.dll";
+
+/// Publisher of the official `wintun.dll` builds.
+const OFFICIAL_PUBLISHER: &str = "WireGuard LLC";
+
+/// How `wintun.dll` is checked before it is loaded, see
+/// [`DeviceBuilder::wintun_trust`](crate::DeviceBuilder::wintun_trust).
+///
+/// A DLL that fails the check is not loaded, and the device fails to build with
+/// [`Error::UntrustedDriver`](crate::Error::UntrustedDriver).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum WintunTrust {
+    /// Loads any DLL that is found, the default.
+    #[default]
+    Any,
+    /// Requires a valid Authenticode signature by `publisher`, the simple display name of the
+    /// signing certificate, e.g. `"WireGuard LLC"`.
+    Signed {
+        /// Name of the signer.
+        publisher: String,
+    },
+    /// Requires the SHA-256 hash of the DLL to be one of these.
+    Sha256(Vec<[u8; 32]>),
+}
+
+impl WintunTrust {
+    /// Requires the signature of the official builds from wintun.net, by WireGuard LLC.
+    pub fn official() -> Self {
+        WintunTrust::Signed {
+            publisher: OFFICIAL_PUBLISHER.to_string(),
+        }
+    }
+}
+
+/// Where `wintun.dll` is looked for and how it is checked.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DllSource<'a> {
+    /// `wintun_file`, if set; [`DEFAULT_WINTUN_FILE`] or the embedded DLL otherwise.
+    pub file: Option<&'a str>,
+    /// `wintun_search_paths`, replacing the default lookup if not empty.
+    pub search_paths: &'a [PathBuf],
+    pub trust: &'a WintunTrust,
+}
+
+/// Directory of the current architecture in the official distribution (`wintun/bin/<dir>`).
+#[cfg(target_arch = "x86_64")]
+const ARCH_DIR: &str = "amd64";
+#[cfg(target_arch = "x86")]
+const ARCH_DIR: &str = "x86";
+#[cfg(target_arch = "aarch64")]
+const ARCH_DIR: &str = "arm64";
+#[cfg(target_arch = "arm")]
+const ARCH_DIR: &str = "arm";
+
+/// PE machine type of the current architecture.
+#[cfg(target_arch = "x86_64")]
+const MACHINE: u16 = 0x8664;
+#[cfg(target_arch = "x86")]
+const MACHINE: u16 = 0x014c;
+#[cfg(target_arch = "aarch64")]
+const MACHINE: u16 = 0xaa64;
+#[cfg(target_arch = "arm")]
+const MACHINE: u16 = 0x01c4;
+
+#[cfg(all(feature = "embed-wintun", not(docsrs)))]
+static EMBEDDED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/wintun.dll"));
+
+fn arch_name(machine: u16) -> &'static str {
+    match machine {
+        0x8664 => "x86_64",
+        0x014c => "x86",
+        0xaa64 => "aarch64",
+        0x01c4 => "arm",
+        _ => "unknown",
+    }
+}
+
+/// Reads the machine type from the PE header of `path`.
+fn pe_machine(path: &Path) -> io::Result<u16> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a PE file");
+    let mut file = File::open(path)?;
+    let mut dos_header = [0u8; 64];
+    file.read_exact(&mut dos_header)?;
+    if !dos_header.starts_with(b"MZ") {
+        return Err(invalid());
+    }
+    let nt_offset = u32::from_le_bytes(dos_header[0x3c..0x40].try_into().unwrap());
+    file.seek(SeekFrom::Start(nt_offset as u64))?;
+    let mut nt_header = [0u8; 6];
+    file.read_exact(&mut nt_header)?;
+    if !nt_header.starts_with(b"PE\0\0") {
+        return Err(invalid());
+    }
+    Ok(u16::from_le_bytes([nt_header[4], nt_header[5]]))
+}
+
+/// Resolves a bare file name next to the executable, preferring a DLL of the right
+/// architecture over the layout of the official zip (`<arch>/` or `wintun/bin/<arch>/`).
+///
+/// Paths with a directory, and names not found there, are left to the DLL search order.
+fn resolve(path: &str) -> PathBuf {
+    let given = Path::new(path);
+    if given.parent().is_some_and(|p| !p.as_os_str().is_empty()) {
+        return given.to_path_buf();
+    }
+    let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    else {
+        return given.to_path_buf();
+    };
+    let candidates = [
+        exe_dir.join(given),
+        exe_dir.join(ARCH_DIR).join(given),
+        exe_dir
+            .join("wintun")
+            .join("bin")
+            .join(ARCH_DIR)
+            .join(given),
+    ];
+    candidates
+        .iter()
+        .find(|candidate| pe_machine(candidate).is_ok_and(|machine| machine == MACHINE))
+        .or_else(|| candidates.iter().find(|candidate| candidate.exists()))
+        .cloned()
+        .unwrap_or_else(|| given.to_path_buf())
+}
+
+/// Writes the embedded `wintun.dll` into the temporary directory and returns its path.
+///
+/// The directory is named after the content, so processes built with different DLLs
+/// never overwrite a library that is loaded by another one.
+#[cfg(all(feature = "embed-wintun", not(docsrs)))]
+fn extract_embedded() -> io::Result<PathBuf> {
+    // FNV-1a
+    let hash = EMBEDDED.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    let dir = std::env::temp_dir().join(format!("tun-rs-wintun-{ARCH_DIR}-{hash:016x}"));
+    let path = dir.join(DEFAULT_WINTUN_FILE);
+    if std::fs::read(&path).is_ok_and(|data| data == EMBEDDED) {
+        return Ok(path);
+    }
+    std::fs::create_dir_all(&dir)?;
+    let tmp = dir.join(format!("wintun.{}.tmp", std::process::id()));
+    std::fs::write(&tmp, EMBEDDED)?;
+    if let Err(e) = std::fs::rename(&tmp, &path) {
+        let _ = std::fs::remove_file(&tmp);
+        // Another process may have extracted it in the meantime.
+        if !std::fs::read(&path).is_ok_and(|data| data == EMBEDDED) {
+            return Err(e);
+        }
+    }
+    Ok(path)
+}
+
+/// Loads `wintun.dll` as configured by `source`, returning it along with the full path it was
+/// loaded from.
+///
+/// Fails with [`Error::DriverArchMismatch`](crate::Error::DriverArchMismatch) if the DLL was
+/// built for another architecture, with
+/// [`Error::UntrustedDriver`](crate::Error::UntrustedDriver) if it fails the trust check and
+/// with [`Error::DriverNotFound`](crate::Error::DriverNotFound) if it cannot be loaded
+/// otherwise. With search paths, the first DLL among them that loads is used.
+pub(crate) fn load(source: &DllSource<'_>) -> io::Result<(wintun_raw::wintun, PathBuf)> {
+    if !source.search_paths.is_empty() {
+        let mut error = None;
+        for path in source.search_paths {
+            let candidate = if path.is_dir() {
+                path.join(source.file.unwrap_or(DEFAULT_WINTUN_FILE))
+            } else {
+                path.clone()
+            };
+            if !candidate.is_file() {
+                continue;
+            }
+            match load_from(&candidate, source.trust) {
+                Ok(loaded) => return Ok(loaded),
+                Err(e) => {
+                    log::warn!("skipping {}: {e}", candidate.display());
+                    error = Some(e);
+                }
+            }
+        }
+        return Err(error.unwrap_or_else(|| crate::Error::DriverNotFound.into()));
+    }
+    #[cfg(all(feature = "embed-wintun", not(docsrs)))]
+    let resolved = match source.file {
+        Some(path) => resolve(path),
+        None => extract_embedded()?,
+    };
+    #[cfg(not(all(feature = "embed-wintun", not(docsrs))))]
+    let resolved = resolve(source.file.unwrap_or(DEFAULT_WINTUN_FILE));
+    // A bare name would be checked and loaded from wherever the search order finds it first,
+    // which includes the working directory.
+    let bare = resolved
+        .parent()
+        .is_none_or(|dir| dir.as_os_str().is_empty());
+    if *source.trust != WintunTrust::Any && (bare || !resolved.is_file()) {
+        log::warn!("{} not found next to the executable", resolved.display());
+        Err(crate::Error::DriverNotFound)?;
+    }
+    load_from(&resolved, source.trust)
+}
+
+fn load_from(path: &Path, trust: &WintunTrust) -> io::Result<(wintun_raw::wintun, PathBuf)> {
+    if let Ok(machine) = pe_machine(path) {
+        if machine != MACHINE {
+            log::warn!(
+                "{} is built for {}, not {}",
+                path.display(),
+                arch_name(machine),
+                arch_name(MACHINE)
+            );
+            Err(crate::Error::DriverArchMismatch {
+                expected: arch_name(MACHINE),
+                found: arch_name(machine),
+            })?;
+        }
+    }
+    // Keeps the checked file open without sharing write or delete access, so that it cannot
+    // be replaced before it is loaded.
+    let checked = match trust {
+        WintunTrust::Any => None,
+        trust => {
+            let path = std::path::absolute(path)?;
+            let file = open_locked(&path)?;
+            verify(&file, &path, trust).map_err(|reason| {
+                log::warn!("refusing to load {}: {reason}", path.display());
+                crate::Error::UntrustedDriver {
+                    path: path.clone(),
+                    reason,
+                }
+            })?;
+            Some((file, path))
+        }
+    };
+    let path = match &checked {
+        Some((_, absolute)) => absolute.as_path(),
+        None => path,
+    };
+    let win_tun = unsafe { wintun_raw::wintun::new(path) }.map_err(|e| {
+        log::warn!("failed to load {}: {e}", path.display());
+        io::Error::from(crate::Error::DriverNotFound)
+    })?;
+    let loaded = module_path(path).unwrap_or_else(|| path.to_path_buf());
+    Ok((win_tun, loaded))
+}
+
+fn open_locked(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use windows_sys::Win32::Storage::FileSystem::FILE_SHARE_READ;
+
+    std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path)
+}
+
+/// Checks the opened DLL against `trust`, returning why it is not trusted otherwise.
+fn verify(file: &File, path: &Path, trust: &WintunTrust) -> Result<(), &'static str> {
+    match trust {
+        WintunTrust::Any => Ok(()),
+        WintunTrust::Signed { publisher } => match signer(file, path)? {
+            Some(signer) if signer == *publisher => Ok(()),
+            _ => Err("signed by another publisher"),
+        },
+        WintunTrust::Sha256(hashes) => {
+            let mut data = Vec::new();
+            (&*file)
+                .read_to_end(&mut data)
+                .map_err(|_| "failed to read the file")?;
+            let hash = sha256(&data).ok_or("failed to hash the file")?;
+            if hashes.contains(&hash) {
+                Ok(())
+            } else {
+                Err("unknown SHA-256 hash")
+            }
+        }
+    }
+}
+
+/// Verifies the Authenticode signature of the file and returns the name of its signer.
+fn signer(file: &File, path: &Path) -> Result<Option<String>, &'static str> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Security::WinTrust::{
+        WinVerifyTrust, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WINTRUST_DATA_0,
+        WINTRUST_FILE_INFO, WTD_CACHE_ONLY_URL_RETRIEVAL, WTD_CHOICE_FILE, WTD_REVOKE_NONE,
+        WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut file_info = WINTRUST_FILE_INFO {
+        cbStruct: size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: wide.as_ptr(),
+        hFile: file.as_raw_handle(),
+        pgKnownSubject: std::ptr::null_mut(),
+    };
+    let mut data = WINTRUST_DATA {
+        cbStruct: size_of::<WINTRUST_DATA>() as u32,
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: WTD_CHOICE_FILE,
+        Anonymous: WINTRUST_DATA_0 {
+            pFile: &mut file_info,
+        },
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        dwProvFlags: WTD_CACHE_ONLY_URL_RETRIEVAL,
+        ..Default::default()
+    };
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+    let data_ptr = &mut data as *mut WINTRUST_DATA as *mut std::ffi::c_void;
+    unsafe {
+        let status = WinVerifyTrust(std::ptr::null_mut(), &mut action, data_ptr);
+        let signer = if status == 0 {
+            signer_name(data.hWVTStateData)
+        } else {
+            None
+        };
+        // Releases the state data.
+        data.dwStateAction = WTD_STATEACTION_CLOSE;
+        WinVerifyTrust(std::ptr::null_mut(), &mut action, data_ptr);
+        if status != 0 {
+            return Err("no valid Authenticode signature");
+        }
+        Ok(signer)
+    }
+}
+
+/// The simple display name of the certificate that signed a verified file.
+unsafe fn signer_name(state: windows_sys::Win32::Foundation::HANDLE) -> Option<String> {
+    use windows_sys::Win32::Security::Cryptography::{
+        CertGetNameStringW, CERT_NAME_SIMPLE_DISPLAY_TYPE,
+    };
+    use windows_sys::Win32::Security::WinTrust::{
+        WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData,
+    };
+
+    let provider = WTHelperProvDataFromStateData(state);
+    if provider.is_null() {
+        return None;
+    }
+    let signer = WTHelperGetProvSignerFromChain(provider, 0, 0, 0);
+    if signer.is_null() || (*signer).csCertChain == 0 {
+        return None;
+    }
+    let cert = (*(*signer).pasCertChain).pCert;
+    let mut name = [0u16; 256];
+    let len = CertGetNameStringW(
+        cert,
+        CERT_NAME_SIMPLE_DISPLAY_TYPE,
+        0,
+        std::ptr::null(),
+        name.as_mut_ptr(),
+        name.len() as u32,
+    ) as usize;
+    // The length includes the terminating null.
+    (len > 1).then(|| String::from_utf16_lossy(&name[..len - 1]))
+}
+
+fn sha256(data: &[u8]) -> Option<[u8; 32]> {
+    use windows_sys::Win32::Security::Cryptography::{
+        BCryptCloseAlgorithmProvider, BCryptCreateHash, BCryptDestroyHash, BCryptFinishHash,
+        BCryptHashData, BCryptOpenAlgorithmProvider, BCRYPT_SHA256_ALGORITHM,
+    };
+
+    let mut hash = [0u8; 32];
+    unsafe {
+        let mut alg = std::ptr::null_mut();
+        if BCryptOpenAlgorithmProvider(&mut alg, BCRYPT_SHA256_ALGORITHM, std::ptr::null(), 0) != 0
+        {
+            return None;
+        }
+        let mut handle = std::ptr::null_mut();
+        let mut ok = BCryptCreateHash(
+            alg,
+            &mut handle,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null(),
+            0,
+            0,
+        ) == 0;
+        if ok {
+            ok = data
+                .chunks(u32::MAX as usize)
+                .all(|chunk| BCryptHashData(handle, chunk.as_ptr(), chunk.len() as u32, 0) == 0)
+                && BCryptFinishHash(handle, hash.as_mut_ptr(), hash.len() as u32, 0) == 0;
+            BCryptDestroyHash(handle);
+        }
+        BCryptCloseAlgorithmProvider(alg, 0);
+        ok.then_some(hash)
+    }
+}
+
+/// The full path of the loaded module `path`, which may be a bare name found through the DLL
+/// search order.
+fn module_path(path: &Path) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use windows_sys::Win32::System::LibraryLoader::{GetModuleFileNameW, GetModuleHandleW};
+
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let module = unsafe { GetModuleHandleW(name.as_ptr()) };
+    if module.is_null() {
+        return None;
+    }
+    let mut buf = vec![0u16; 32768];
+    let len = unsafe { GetModuleFileNameW(module, buf.as_mut_ptr(), buf.len() as u32) } as usize;
+    if len == 0 || len >= buf.len() {
+        return None;
+    }
+    Some(OsString::from_wide(&buf[..len]).into())
+}
@@ -1,25 +1,33 @@
 use bytes::buf::UninitSlice;
-use std::os::windows::io::{AsRawHandle, OwnedHandle};
+use std::io::{IoSlice, IoSliceMut};
+use std::os::windows::io::{AsRawHandle, OwnedHandle, RawHandle};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
 use std::{io, ptr};
 use windows_sys::core::GUID;
 use windows_sys::Win32::Foundation::{
     GetLastError, ERROR_BUFFER_OVERFLOW, ERROR_HANDLE_EOF, ERROR_INVALID_DATA, ERROR_NO_MORE_ITEMS,
-    WAIT_FAILED, WAIT_OBJECT_0,
 };
 use windows_sys::Win32::NetworkManagement::Ndis::NET_LUID_LH;
-use windows_sys::Win32::System::Threading::{WaitForMultipleObjects, INFINITE};
 
+use crate::framing::TunPi;
 use crate::platform::windows::ffi;
 use crate::platform::windows::ffi::encode_utf16;
+use crate::platform::windows::DriverVersion;
+use crate::TruncatePolicy;
+use crate::PACKET_INFORMATION_LENGTH as PIL;
 
 mod adapter;
 mod adapter_win7;
+mod dll;
 mod wintun_log;
 mod wintun_raw;
 
-pub use adapter::check_adapter_if_orphaned_devices;
+pub use adapter::{check_adapter_if_orphaned_devices, is_windows_seven, set_adapter_description};
+pub(crate) use dll::DllSource;
+pub use dll::{WintunTrust, DEFAULT_WINTUN_FILE};
 
 /// The maximum size of wintun's internal ring buffer (in bytes)
 pub const MAX_RING_CAPACITY: u32 = 0x400_0000;
@@ -30,6 +38,19 @@ pub const MIN_RING_CAPACITY: u32 = 0x2_0000;
 /// Maximum pool name length including zero terminator
 pub const MAX_POOL: usize = 256;
 
+/// What happened to the packets of the old session in
+/// [`restart_session`](crate::DeviceImpl::restart_session).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct SessionRestart {
+    /// Packets that were waiting in the receive ring and were discarded.
+    pub dropped_packets: usize,
+    /// Total size of the discarded packets, in bytes.
+    pub dropped_bytes: usize,
+    /// Packets that were waiting in the receive ring and were handed to the caller.
+    pub drained_packets: usize,
+}
+
 pub struct TunDevice {
     index: u32,
     luid: NET_LUID_LH,
@@ -40,9 +61,15 @@ struct WinTunAdapter {
     handle: wintun_raw::WINTUN_ADAPTER_HANDLE,
     event: OwnedHandle,
     ring_capacity: u32,
+    /// Where `wintun.dll` was loaded from.
+    dll_path: PathBuf,
     state: State,
     session: RwLock<Option<WinTunSession>>,
     delete_driver: bool,
+    truncate_policy: TruncatePolicy,
+    compat_win7: bool,
+    /// Whether packets carry an emulated `tun_pi` header, shared with the sessions.
+    packet_information: Arc<AtomicBool>,
 }
 unsafe impl Send for WinTunAdapter {}
 unsafe impl Sync for WinTunAdapter {}
@@ -50,6 +77,7 @@ struct WinTunSession {
     win_tun: Arc<wintun_raw::wintun>,
     handle: wintun_raw::WINTUN_SESSION_HANDLE,
     read_event: wintun_raw::HANDLE,
+    packet_information: Arc<AtomicBool>,
 }
 impl Drop for WinTunAdapter {
     fn drop(&mut self) {
@@ -73,7 +101,7 @@ impl State {
         if self.is_enabled() {
             Ok(())
         } else {
-            Err(io::Error::other("The interface has been disabled"))
+            Err(crate::Error::Disabled.into())
         }
     }
     fn is_disabled(&self) -> bool {
@@ -111,74 +139,189 @@ impl WinTunAdapter {
         let _guard = self.state.lock();
         if self.state.is_disabled() {
             let mut session = self.session.write().unwrap();
-            unsafe {
-                let session_handle = self
-                    .win_tun
-                    .WintunStartSession(self.handle, self.ring_capacity);
-                if session_handle.is_null() {
-                    Err(io::Error::last_os_error())?
-                }
-                let read_event_handle = self.win_tun.WintunGetReadWaitEvent(session_handle);
-                if read_event_handle.is_null() {
-                    self.win_tun.WintunEndSession(session_handle);
-                    Err(io::Error::last_os_error())?
-                }
-
-                let wintun_session = WinTunSession {
-                    win_tun: self.win_tun.clone(),
-                    handle: session_handle,
-                    read_event: read_event_handle,
-                };
-                session.replace(wintun_session);
-            }
+            session.replace(self.start_session()?);
             self.state.enable();
         }
         Ok(())
     }
-    fn version(&self) -> io::Result<String> {
+    fn start_session(&self) -> io::Result<WinTunSession> {
+        unsafe {
+            let session_handle = self
+                .win_tun
+                .WintunStartSession(self.handle, self.ring_capacity);
+            if session_handle.is_null() {
+                Err(io::Error::last_os_error())?
+            }
+            let read_event_handle = self.win_tun.WintunGetReadWaitEvent(session_handle);
+            if read_event_handle.is_null() {
+                self.win_tun.WintunEndSession(session_handle);
+                Err(io::Error::last_os_error())?
+            }
+            crate::trace::event!(
+                debug,
+                ring_capacity = self.ring_capacity,
+                "wintun session started"
+            );
+            Ok(WinTunSession {
+                win_tun: self.win_tun.clone(),
+                handle: session_handle,
+                read_event: read_event_handle,
+                packet_information: self.packet_information.clone(),
+            })
+        }
+    }
+    /// Ends the session and starts a new one. The packets left in the receive ring are handed
+    /// to `pending` if given, and discarded otherwise.
+    ///
+    /// If the new session cannot be started the device is left disabled, as after `disable`.
+    fn restart(&self, mut pending: Option<&mut dyn FnMut(&[u8])>) -> io::Result<SessionRestart> {
+        let _guard = self.state.lock();
+        self.state.check()?;
+        // Blocked receivers hold the session; the shutdown event makes them return with
+        // `Error::Disabled`, as the device is disabled until the new session is up.
+        self.state.disable();
+        if let Err(e) = ffi::set_event(self.event.as_raw_handle()) {
+            self.state.enable();
+            return Err(e);
+        }
+        let mut session = self.session.write().unwrap();
+        if let Err(e) = ffi::reset_event(self.event.as_raw_handle()) {
+            self.state.enable();
+            return Err(e);
+        }
+        let mut restart = SessionRestart::default();
+        if let Some(old) = session.take() {
+            old.drain(|packet| match pending.as_mut() {
+                Some(pending) => {
+                    pending(packet);
+                    restart.drained_packets += 1;
+                }
+                None => {
+                    restart.dropped_packets += 1;
+                    restart.dropped_bytes += packet.len();
+                }
+            });
+        }
+        session.replace(self.start_session()?);
+        self.state.enable();
+        Ok(restart)
+    }
+    fn driver_version(&self) -> io::Result<DriverVersion> {
         let version = unsafe { self.win_tun.WintunGetRunningDriverVersion() };
-        let v = version.to_be_bytes();
-        Ok(format!(
-            "{}.{}",
-            u16::from_be_bytes([v[0], v[1]]),
-            u16::from_be_bytes([v[2], v[3]])
-        ))
+        if version == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(DriverVersion {
+            major: version >> 16,
+            minor: version & 0xffff,
+            debug: false,
+        })
     }
     fn send(&self, buf: &[u8], event: Option<&OwnedHandle>) -> io::Result<usize> {
+        // A ring that stays full this long means the adapter no longer reads it.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        self.send_deadline(buf, event, Some(deadline))
+    }
+    fn send_deadline(
+        &self,
+        buf: &[u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
         let guard = self.session.read().unwrap();
         if let Some(session) = guard.as_ref() {
-            return session.send(buf, &self.state, event);
+            return session.send_deadline(buf, &self.state, &self.event, interrupt_event, deadline);
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
     }
-    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        self.recv_deadline(buf, None, None)
+    }
+    fn recv_deadline(
+        &self,
+        buf: &mut [u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<(usize, usize)> {
         let guard = self.session.read().unwrap();
         if let Some(session) = guard.as_ref() {
-            return session.recv(&self.event, buf);
+            return session.recv_deadline(
+                &self.event,
+                buf,
+                self.truncate_policy,
+                interrupt_event,
+                deadline,
+            );
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
     }
     fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
         let guard = self.session.read().unwrap();
         if let Some(session) = guard.as_ref() {
             return session.try_send(buf);
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
+    }
+    fn try_recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let guard = self.session.read().unwrap();
+        if let Some(session) = guard.as_ref() {
+            return session.try_recv(buf, self.truncate_policy);
+        }
+        Err(crate::Error::Disabled.into())
+    }
+    fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let guard = self.session.read().unwrap();
+        if let Some(session) = guard.as_ref() {
+            return session.try_send_vectored(bufs);
+        }
+        Err(crate::Error::Disabled.into())
+    }
+    fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let guard = self.session.read().unwrap();
+        if let Some(session) = guard.as_ref() {
+            return session.try_recv_vectored(bufs, self.truncate_policy);
+        }
+        Err(crate::Error::Disabled.into())
     }
-    fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    fn drain(&self) -> io::Result<usize> {
         let guard = self.session.read().unwrap();
         if let Some(session) = guard.as_ref() {
-            return session.try_recv(buf);
+            let mut count = 0;
+            session.drain(|_| count += 1);
+            return Ok(count);
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
     }
     #[allow(dead_code)]
     fn try_recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
         let guard = self.session.read().unwrap();
         if let Some(session) = guard.as_ref() {
-            return session.try_recv_uninit(buf);
+            return session.try_recv_uninit(buf, self.truncate_policy);
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
+    }
+    fn read_event(&self) -> io::Result<RawHandle> {
+        let guard = self.session.read().unwrap();
+        if let Some(session) = guard.as_ref() {
+            return Ok(session.read_event as RawHandle);
+        }
+        Err(crate::Error::Disabled.into())
+    }
+    /// Duplicates the session's read event and the shutdown event, so that they can be waited
+    /// on by the thread pool without the session being kept alive.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    fn read_wait_events(&self) -> io::Result<(OwnedHandle, OwnedHandle)> {
+        let guard = self.session.read().unwrap();
+        // `disable` marks the state before signaling the shutdown event, so a wait created
+        // after the signal fails here instead of missing it.
+        self.state.check()?;
+        if let Some(session) = guard.as_ref() {
+            let read_event = unsafe {
+                std::os::windows::io::BorrowedHandle::borrow_raw(session.read_event as RawHandle)
+            };
+            return Ok((read_event.try_clone_to_owned()?, self.event.try_clone()?));
+        }
+        Err(crate::Error::Disabled.into())
     }
     fn wait_readable_interruptible(
         &self,
@@ -189,7 +332,7 @@ impl WinTunAdapter {
         if let Some(session) = guard.as_ref() {
             return session.wait_readable_interruptible(&self.event, interrupt_event, timeout);
         }
-        Err(io::Error::other("The interface has been disabled"))
+        Err(crate::Error::Disabled.into())
     }
 }
 
@@ -198,79 +341,97 @@ impl Drop for WinTunSession {
         unsafe {
             self.win_tun.WintunEndSession(self.handle);
         }
+        crate::trace::event!(debug, "wintun session ended");
     }
 }
 
 impl WinTunSession {
-    fn send(&self, buf: &[u8], state: &State, event: Option<&OwnedHandle>) -> io::Result<usize> {
-        let start = std::time::Instant::now();
-        let timeout = std::time::Duration::from_secs(5);
-        let mut backoff = std::time::Duration::from_millis(0);
+    /// Sends `buf`, retrying while the send ring is full until `deadline` passes.
+    ///
+    /// Wintun has no event for free ring space, so a full ring is polled with a backoff
+    /// of up to 10ms, waiting on the shutdown and interrupt events in between.
+    fn send_deadline(
+        &self,
+        buf: &[u8],
+        state: &State,
+        inner_event: &OwnedHandle,
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        let mut backoff = Duration::from_millis(1);
         loop {
-            return match self.try_send(buf) {
-                Ok(len) => Ok(len),
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    state.check()?;
-                    if start.elapsed() > timeout {
-                        return Err(io::Error::from(io::ErrorKind::TimedOut));
-                    }
-                    if let Some(event) = event {
-                        if ffi::wait_for_single_object(event.as_raw_handle(), 0).is_ok() {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Interrupted,
-                                "trigger interrupt",
-                            ));
-                        }
-                    }
-                    // Exponential backoff: 0, 1, 2, 4, 8, capped at 10ms
-                    if backoff.is_zero() {
-                        std::hint::spin_loop();
-                        backoff = std::time::Duration::from_millis(1);
-                    } else {
-                        std::thread::sleep(backoff);
-                        backoff = (backoff * 2).min(std::time::Duration::from_millis(10));
-                    }
-                    continue;
-                }
-                Err(e) => Err(e),
-            };
+            match self.try_send(buf) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => return rs,
+            }
+            state.check()?;
+            let now = Instant::now();
+            if deadline.is_some_and(|d| d <= now) {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            }
+            crate::trace::event!(trace, ?backoff, "send ring full");
+            let retry = now + backoff;
+            let wait_until = deadline.map_or(retry, |d| d.min(retry));
+            backoff = (backoff * 2).min(Duration::from_millis(10));
+            wait_events(inner_event, None, interrupt_event, Some(wait_until))?;
         }
     }
-    fn recv(&self, inner_event: &OwnedHandle, buf: &mut [u8]) -> io::Result<usize> {
+    /// Receives a packet, blocking on the read event until `deadline` passes.
+    fn recv_deadline(
+        &self,
+        inner_event: &OwnedHandle,
+        buf: &mut [u8],
+        policy: TruncatePolicy,
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<(usize, usize)> {
         loop {
-            // Limit spin iterations to reduce CPU waste; use yield_now after a few spins
-            for i in 0..16 {
-                return match self.try_recv(buf) {
-                    Ok(n) => Ok(n),
-                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                        if i >= 4 {
-                            std::thread::yield_now();
-                        } else {
-                            std::hint::spin_loop();
-                        }
-                        continue;
-                    }
-                    Err(e) => Err(e),
-                };
+            match self.try_recv(buf, policy) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                rs => return rs,
+            }
+            if deadline.is_some_and(|d| d <= Instant::now()) {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            }
+            let read_event = Some(self.read_event);
+            if !wait_events(inner_event, read_event, interrupt_event, deadline)? {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
             }
-            // After spin attempts, block on the read event (also signaled on disable)
-            self.wait_readable(inner_event)?;
         }
     }
     fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
-        if buf.len() > u32::MAX as usize {
+        self.try_send_vectored(&[IoSlice::new(buf)])
+    }
+    /// Gathers `bufs` straight into one packet of the send ring.
+    ///
+    /// With packet information the first [`PIL`] bytes are the emulated header, which is
+    /// left out of the packet but counted in the returned length, as on Linux.
+    fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let mut skip = 0;
+        if self.packet_information.load(Ordering::Relaxed) {
+            if total < PIL {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "packet shorter than the packet information header",
+                ));
+            }
+            skip = PIL;
+        }
+        let len = total - skip;
+        if len > u32::MAX as usize {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!(
                     "Buffer too large: {} bytes exceeds maximum size of {} bytes",
-                    buf.len(),
+                    len,
                     u32::MAX
                 ),
             ));
         }
         let win_tun = &self.win_tun;
         let handle = self.handle;
-        let bytes_ptr = unsafe { win_tun.WintunAllocateSendPacket(handle, buf.len() as u32) };
+        let bytes_ptr = unsafe { win_tun.WintunAllocateSendPacket(handle, len as u32) };
         if bytes_ptr.is_null() {
             match unsafe { GetLastError() } {
                 ERROR_HANDLE_EOF => Err(std::io::Error::from(io::ErrorKind::WriteZero)),
@@ -279,19 +440,76 @@ impl WinTunSession {
                 e => Err(io::Error::from_raw_os_error(e as i32)),
             }
         } else {
-            unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), bytes_ptr, buf.len()) };
+            let mut offset = 0;
+            for buf in bufs {
+                let cut = skip.min(buf.len());
+                skip -= cut;
+                let buf = &buf[cut..];
+                unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), bytes_ptr.add(offset), buf.len()) };
+                offset += buf.len();
+            }
             unsafe { win_tun.WintunSendPacket(handle, bytes_ptr) };
-            Ok(buf.len())
+            Ok(total)
         }
     }
-    fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.try_recv_raw(buf.as_mut_ptr(), buf.len())
+    /// Receives a packet, returning the number of bytes copied and the length of the packet.
+    fn try_recv(&self, buf: &mut [u8], policy: TruncatePolicy) -> io::Result<(usize, usize)> {
+        self.try_recv_raw(buf.as_mut_ptr(), buf.len(), policy)
+    }
+    /// Passes each packet left in the receive ring to `f`.
+    fn drain(&self, mut f: impl FnMut(&[u8])) {
+        loop {
+            let mut size = 0u32;
+            let ptr = unsafe { self.win_tun.WintunReceivePacket(self.handle, &mut size) };
+            if ptr.is_null() {
+                return;
+            }
+            f(unsafe { std::slice::from_raw_parts(ptr, size as usize) });
+            unsafe { self.win_tun.WintunReleaseReceivePacket(self.handle, ptr) };
+        }
     }
     #[allow(dead_code)]
-    fn try_recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
-        self.try_recv_raw(buf.as_mut_ptr(), buf.len())
+    fn try_recv_uninit(&self, buf: &mut UninitSlice, policy: TruncatePolicy) -> io::Result<usize> {
+        self.try_recv_raw(buf.as_mut_ptr(), buf.len(), policy)
+            .map(|(len, _)| len)
+    }
+    /// Scatters a packet of the receive ring straight into `bufs`.
+    fn try_recv_vectored(
+        &self,
+        bufs: &mut [IoSliceMut<'_>],
+        policy: TruncatePolicy,
+    ) -> io::Result<usize> {
+        let dst_len = bufs.iter().map(|buf| buf.len()).sum();
+        self.try_recv_with(dst_len, policy, |head, packet| {
+            if head.is_empty() {
+                crate::platform::scatter(packet, bufs)
+            } else {
+                crate::platform::scatter(&[head, packet].concat(), bufs)
+            }
+        })
+        .map(|(len, _)| len)
     }
-    fn try_recv_raw(&self, dst: *mut u8, dst_len: usize) -> io::Result<usize> {
+    fn try_recv_raw(
+        &self,
+        dst: *mut u8,
+        dst_len: usize,
+        policy: TruncatePolicy,
+    ) -> io::Result<(usize, usize)> {
+        self.try_recv_with(dst_len, policy, |head, packet| unsafe {
+            ptr::copy_nonoverlapping(head.as_ptr(), dst, head.len());
+            ptr::copy_nonoverlapping(packet.as_ptr(), dst.add(head.len()), packet.len())
+        })
+    }
+    /// Receives a packet into `dst_len` bytes, passing the part that fits to `copy`, after
+    /// the part of the emulated packet information header that fits.
+    ///
+    /// Returns the number of bytes copied and the length of the packet with its header.
+    fn try_recv_with(
+        &self,
+        dst_len: usize,
+        policy: TruncatePolicy,
+        copy: impl FnOnce(&[u8], &[u8]),
+    ) -> io::Result<(usize, usize)> {
         let mut size = 0u32;
 
         let win_tun = &self.win_tun;
@@ -306,89 +524,85 @@ impl WinTunSession {
                 e => Err(io::Error::from_raw_os_error(e as i32)),
             };
         }
-        let size = size as usize;
-        if size > dst_len {
+        let packet = unsafe { std::slice::from_raw_parts(ptr, size as usize) };
+        let head_len = if self.packet_information.load(Ordering::Relaxed) {
+            PIL
+        } else {
+            0
+        };
+        let len = head_len + packet.len();
+        let Some(copy_len) = policy.apply(len, dst_len) else {
             unsafe { win_tun.WintunReleaseReceivePacket(handle, ptr) };
             use std::io::{Error, ErrorKind::InvalidInput};
             return Err(Error::new(InvalidInput, "destination buffer too small"));
+        };
+        // Like Linux, the header names the EtherType, 0 if the packet is not IP, and
+        // flags a packet cut to fit.
+        let mut pi = TunPi::for_packet(packet).unwrap_or_default();
+        if copy_len < len {
+            pi.flags |= TunPi::TUN_PKT_STRIP;
         }
-        unsafe { ptr::copy_nonoverlapping(ptr, dst, size) };
+        let head = pi.to_bytes();
+        copy(
+            &head[..copy_len.min(head_len)],
+            &packet[..copy_len.saturating_sub(head_len)],
+        );
         unsafe { win_tun.WintunReleaseReceivePacket(handle, ptr) };
-        Ok(size)
+        Ok((copy_len, len))
     }
     fn wait_readable_interruptible(
         &self,
         inner_event: &OwnedHandle,
         interrupt_event: &OwnedHandle,
-        timeout: Option<std::time::Duration>,
+        timeout: Option<Duration>,
     ) -> io::Result<()> {
-        //Wait on both the read handle and the shutdown handle so that we stop when requested
-        let handles = [
-            self.read_event,
-            inner_event.as_raw_handle(),
-            interrupt_event.as_raw_handle(),
-        ];
-        let result = unsafe {
-            //SAFETY: We abide by the requirements of WaitForMultipleObjects, handles is a
-            //pointer to valid, aligned, stack memory
-            WaitForMultipleObjects(
-                3,
-                &handles as _,
-                0,
-                timeout
-                    .map(|t| t.as_millis().min(INFINITE as _) as u32)
-                    .unwrap_or(INFINITE),
-            )
-        };
-        match result {
-            WAIT_FAILED => Err(io::Error::last_os_error()),
-            windows_sys::Win32::Foundation::WAIT_TIMEOUT => {
-                Err(io::Error::from(io::ErrorKind::TimedOut))
-            }
-            _ => {
-                if result == WAIT_OBJECT_0 {
-                    //We have data!
-                    Ok(())
-                } else if result == WAIT_OBJECT_0 + 1 {
-                    Err(io::Error::other("The interface has been disabled"))
-                } else if result == WAIT_OBJECT_0 + 2 {
-                    Err(io::Error::new(
-                        io::ErrorKind::Interrupted,
-                        "trigger interrupt",
-                    ))
-                } else {
-                    Err(io::Error::last_os_error())
-                }
-            }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let read_event = Some(self.read_event);
+        if wait_events(inner_event, read_event, Some(interrupt_event), deadline)? {
+            Ok(())
+        } else {
+            Err(io::Error::from(io::ErrorKind::TimedOut))
         }
     }
-    fn wait_readable(&self, inner_event: &OwnedHandle) -> io::Result<()> {
-        //Wait on both the read handle and the shutdown handle so that we stop when requested
-        let handles = [self.read_event, inner_event.as_raw_handle()];
-        let result = unsafe {
-            //SAFETY: We abide by the requirements of WaitForMultipleObjects, handles is a
-            //pointer to valid, aligned, stack memory
-            WaitForMultipleObjects(2, &handles as _, 0, INFINITE)
-        };
-        match result {
-            WAIT_FAILED => Err(io::Error::last_os_error()),
-            _ => {
-                if result == WAIT_OBJECT_0 {
-                    //We have data!
-                    Ok(())
-                } else if result == WAIT_OBJECT_0 + 1 {
-                    Err(io::Error::other("The interface has been disabled"))
-                } else {
-                    Err(io::Error::last_os_error())
-                }
-            }
-        }
+}
+
+/// Waits on the shutdown event, the read event and the interrupt event until `deadline`.
+///
+/// Returns `Ok(true)` when the read event is signaled and `Ok(false)` when the deadline
+/// passes first.
+fn wait_events(
+    inner_event: &OwnedHandle,
+    read_event: Option<RawHandle>,
+    interrupt_event: Option<&OwnedHandle>,
+    deadline: Option<Instant>,
+) -> io::Result<bool> {
+    let mut handles = [inner_event.as_raw_handle(); 3];
+    let mut len = 1;
+    let read_index = read_event.map(|handle| {
+        handles[len] = handle;
+        len += 1;
+        len - 1
+    });
+    let interrupt_index = interrupt_event.map(|event| {
+        handles[len] = event.as_raw_handle();
+        len += 1;
+        len - 1
+    });
+    match ffi::wait_for_multiple_objects(&handles[..len], deadline)? {
+        None => Ok(false),
+        Some(0) => Err(crate::Error::Disabled.into()),
+        Some(i) if Some(i) == read_index => Ok(true),
+        Some(i) if Some(i) == interrupt_index => Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "trigger interrupt",
+        )),
+        Some(_) => unreachable!(),
     }
 }
 
 impl TunDevice {
     pub fn open(
-        wintun_path: &str,
+        source: &DllSource<'_>,
         name: &str,
         ring_capacity: u32,
         delete_driver: bool,
@@ -396,19 +610,21 @@ impl TunDevice {
     ) -> std::io::Result<Self> {
         let range = MIN_RING_CAPACITY..=MAX_RING_CAPACITY;
         if !range.contains(&ring_capacity) {
-            Err(io::Error::other(format!(
-                "ring capacity {ring_capacity} not in [{MIN_RING_CAPACITY},{MAX_RING_CAPACITY}]"
-            )))?;
+            Err(crate::Error::RingCapacity {
+                capacity: ring_capacity,
+                min: MIN_RING_CAPACITY,
+                max: MAX_RING_CAPACITY,
+            })?;
         }
         let name_utf16 = encode_utf16(name);
         if name_utf16.len() > MAX_POOL {
-            Err(io::Error::other("name too long"))?;
+            Err(crate::Error::NameTooLong)?;
         }
 
         unsafe {
             let event = ffi::create_event()?;
 
-            let win_tun = wintun_raw::wintun::new(wintun_path).map_err(io::Error::other)?;
+            let (win_tun, dll_path) = dll::load(source)?;
             if wintun_log {
                 wintun_log::set_default_logger_if_unset(&win_tun);
             }
@@ -425,8 +641,12 @@ impl TunDevice {
                 state: State::default(),
                 event,
                 ring_capacity,
+                dll_path,
                 session: Default::default(),
                 delete_driver,
+                truncate_policy: TruncatePolicy::Error,
+                compat_win7: is_windows_seven(),
+                packet_information: Default::default(),
             };
             // SAFETY: wintun_raw::NET_LUID and windows_sys::NET_LUID_LH are both
             // 8-byte unions representing the same Windows NET_LUID_LH structure.
@@ -446,7 +666,7 @@ impl TunDevice {
         }
     }
     pub fn create(
-        wintun_path: &str,
+        source: &DllSource<'_>,
         name: &str,
         description: &str,
         guid: Option<u128>,
@@ -456,14 +676,16 @@ impl TunDevice {
     ) -> std::io::Result<Self> {
         let range = MIN_RING_CAPACITY..=MAX_RING_CAPACITY;
         if !range.contains(&ring_capacity) {
-            Err(io::Error::other(format!(
-                "ring capacity {ring_capacity} not in [{MIN_RING_CAPACITY},{MAX_RING_CAPACITY}]"
-            )))?;
+            Err(crate::Error::RingCapacity {
+                capacity: ring_capacity,
+                min: MIN_RING_CAPACITY,
+                max: MAX_RING_CAPACITY,
+            })?;
         }
         let name_utf16 = encode_utf16(name);
         let description_utf16 = encode_utf16(description);
         if name_utf16.len() > MAX_POOL {
-            Err(io::Error::other("name too long"))?;
+            Err(crate::Error::NameTooLong)?;
         }
         if description_utf16.len() > MAX_POOL {
             Err(io::Error::other("tunnel type too long"))?;
@@ -471,7 +693,7 @@ impl TunDevice {
         unsafe {
             let event = ffi::create_event()?;
 
-            let win_tun = wintun_raw::wintun::new(wintun_path).map_err(io::Error::other)?;
+            let (win_tun, dll_path) = dll::load(source)?;
             if wintun_log {
                 wintun_log::set_default_logger_if_unset(&win_tun);
             }
@@ -508,8 +730,12 @@ impl TunDevice {
                 state: State::default(),
                 event,
                 ring_capacity,
+                dll_path,
                 session: Default::default(),
                 delete_driver,
+                truncate_policy: TruncatePolicy::Error,
+                compat_win7: is_windows_seven(),
+                packet_information: Default::default(),
             };
             // SAFETY: wintun_raw::NET_LUID and windows_sys::NET_LUID_LH are both
             // 8-byte unions representing the same Windows NET_LUID_LH structure.
@@ -527,6 +753,28 @@ impl TunDevice {
     pub fn luid(&self) -> NET_LUID_LH {
         self.luid
     }
+    pub fn set_truncate_policy(&mut self, policy: TruncatePolicy) {
+        self.win_tun_adapter.truncate_policy = policy;
+    }
+    /// Returns whether packets carry the emulated packet information header.
+    pub fn packet_information(&self) -> bool {
+        self.win_tun_adapter
+            .packet_information
+            .load(Ordering::Relaxed)
+    }
+    /// Sets whether packets carry the emulated packet information header, taking effect
+    /// for the next send or receive.
+    pub fn set_packet_information(&self, value: bool) {
+        self.win_tun_adapter
+            .packet_information
+            .store(value, Ordering::Relaxed);
+    }
+    pub fn set_compat_win7(&mut self, compat_win7: bool) {
+        self.win_tun_adapter.compat_win7 = compat_win7;
+    }
+    pub fn compat_win7(&self) -> bool {
+        self.win_tun_adapter.compat_win7
+    }
     pub fn index(&self) -> u32 {
         self.index
     }
@@ -553,28 +801,79 @@ impl TunDevice {
         self.win_tun_adapter
             .wait_readable_interruptible(interrupt_event, timeout)
     }
+    /// Receives a packet, returning the number of bytes copied and the length of the packet.
     #[inline]
-    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         self.win_tun_adapter.recv(buf)
     }
     #[inline]
+    pub(crate) fn recv_deadline(
+        &self,
+        buf: &mut [u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<(usize, usize)> {
+        self.win_tun_adapter
+            .recv_deadline(buf, interrupt_event, deadline)
+    }
+    #[inline]
+    pub(crate) fn send_deadline(
+        &self,
+        buf: &[u8],
+        interrupt_event: Option<&OwnedHandle>,
+        deadline: Option<Instant>,
+    ) -> io::Result<usize> {
+        self.win_tun_adapter
+            .send_deadline(buf, interrupt_event, deadline)
+    }
+    #[inline]
     pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
         self.win_tun_adapter.try_send(buf)
     }
     #[inline]
-    pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
         self.win_tun_adapter.try_recv(buf)
     }
     #[inline]
+    pub fn try_send_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.win_tun_adapter.try_send_vectored(bufs)
+    }
+    #[inline]
+    pub fn try_recv_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        self.win_tun_adapter.try_recv_vectored(bufs)
+    }
+    #[inline]
     #[allow(dead_code)]
     pub(crate) fn try_recv_uninit(&self, buf: &mut UninitSlice) -> io::Result<usize> {
         self.win_tun_adapter.try_recv_uninit(buf)
     }
+    /// Releases the packets left in the receive ring without copying them, returning how many.
+    #[inline]
+    pub fn drain(&self) -> io::Result<usize> {
+        self.win_tun_adapter.drain()
+    }
     pub fn shutdown(&self) -> io::Result<()> {
         self.win_tun_adapter.disable()
     }
-    pub fn version(&self) -> io::Result<String> {
-        self.win_tun_adapter.version()
+    pub fn read_event(&self) -> io::Result<RawHandle> {
+        self.win_tun_adapter.read_event()
+    }
+    pub fn shutdown_event(&self) -> RawHandle {
+        self.win_tun_adapter.event.as_raw_handle()
+    }
+    /// Owned duplicates of the read event and the shutdown event, for registered waits.
+    #[cfg(any(feature = "async_tokio", feature = "async_io"))]
+    pub(crate) fn read_wait_events(&self) -> io::Result<(OwnedHandle, OwnedHandle)> {
+        self.win_tun_adapter.read_wait_events()
+    }
+    pub fn driver_version(&self) -> io::Result<DriverVersion> {
+        self.win_tun_adapter.driver_version()
+    }
+    pub fn ring_capacity(&self) -> u32 {
+        self.win_tun_adapter.ring_capacity
+    }
+    pub fn dll_path(&self) -> &Path {
+        &self.win_tun_adapter.dll_path
     }
     pub fn enabled(&self, value: bool) -> io::Result<()> {
         if value {
@@ -583,4 +882,14 @@ impl TunDevice {
             self.win_tun_adapter.disable()
         }
     }
+    /// Returns whether a session is running, see [`enabled`](Self::enabled).
+    pub fn is_enabled(&self) -> bool {
+        self.win_tun_adapter.state.is_enabled()
+    }
+    pub fn restart_session(
+        &self,
+        pending: Option<&mut dyn FnMut(&[u8])>,
+    ) -> io::Result<SessionRestart> {
+        self.win_tun_adapter.restart(pending)
+    }
 }
@@ -0,0 +1,411 @@
+use bytes::Bytes;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+/// A fixed set of packet buffers that are recycled instead of freed.
+///
+/// All buffers are carved out of one allocation made up front, so receiving and sending
+/// packets at a high rate causes no allocator traffic. [`PacketPool::get`] hands out a
+/// [`PoolBuf`], which goes back to the pool when dropped. Cloning a `PacketPool` is cheap
+/// and yields a handle to the same buffers; the memory is freed once the last handle and the
+/// last outstanding buffer are gone.
+///
+/// `PoolBuf` implements [`ExpandBuffer`](crate::ExpandBuffer) on Linux, so the same buffers
+/// can be filled by `recv_multiple` and passed on to `send_multiple`. [`PoolBuf::freeze`]
+/// turns a buffer into [`Bytes`] that can be shared between tasks, and
+/// `async_framed::PoolCodec` makes `DeviceFramed` receive into pool buffers. A full pool is
+/// not grown: `get` returns `None` until buffers are returned.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+/// # {
+/// use tun_rs::{DeviceBuilder, GROTable, PacketPool, IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+///
+/// let dev = DeviceBuilder::new()
+///     .ipv4("10.0.0.1", 24, None)
+///     .with(|builder| {
+///         builder.offload(true);
+///     })
+///     .build_sync()?;
+///
+/// let pool = PacketPool::new(4 * IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN + 65535);
+/// let mut original_buffer = vec![0; VIRTIO_NET_HDR_LEN + 65535];
+/// let mut sizes = vec![0; IDEAL_BATCH_SIZE];
+/// let mut gro_table = GROTable::default();
+/// loop {
+///     let mut bufs: Vec<_> = (0..IDEAL_BATCH_SIZE).map_while(|_| pool.get()).collect();
+///     for buf in &mut bufs {
+///         buf.set_len(buf.capacity());
+///     }
+///     let num = dev.recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, VIRTIO_NET_HDR_LEN)?;
+///     bufs.truncate(num);
+///     for (buf, size) in bufs.iter_mut().zip(&sizes) {
+///         buf.set_len(VIRTIO_NET_HDR_LEN + size);
+///     }
+///     // Reflect the packets back, e.g. after rewriting them.
+///     dev.send_multiple(&mut gro_table, &mut bufs, VIRTIO_NET_HDR_LEN)?;
+///     // Dropping `bufs` returns the buffers to the pool.
+/// }
+/// # }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct PacketPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    memory: Memory,
+    buf_size: usize,
+    count: usize,
+    free: Mutex<Vec<usize>>,
+}
+
+// SAFETY: every buffer of the region is handed out to at most one `PoolBuf` at a time, so
+// the region is never accessed from two places at once.
+unsafe impl Send for PoolInner {}
+unsafe impl Sync for PoolInner {}
+
+/// The region all buffers of a pool live in. It is always initialized.
+struct Memory {
+    base: NonNull<u8>,
+    len: usize,
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    mapped: bool,
+}
+
+impl Memory {
+    fn heap(len: usize) -> Memory {
+        let slice = Box::into_raw(vec![0u8; len].into_boxed_slice());
+        Memory {
+            // SAFETY: `Box::into_raw` never returns null.
+            base: unsafe { NonNull::new_unchecked(slice.cast::<u8>()) },
+            len,
+            #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+            mapped: false,
+        }
+    }
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    fn huge_pages(len: usize) -> std::io::Result<Memory> {
+        let len = len.max(1).next_multiple_of(HUGE_PAGE_SIZE);
+        // SAFETY: a new anonymous mapping is created; no existing memory is affected.
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB,
+                -1,
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Memory {
+            // SAFETY: a successful mmap never returns null.
+            base: unsafe { NonNull::new_unchecked(addr.cast::<u8>()) },
+            len,
+            mapped: true,
+        })
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+        if self.mapped {
+            // SAFETY: the mapping was created by `Memory::huge_pages` and no buffer refers to
+            // it any more.
+            unsafe { libc::munmap(self.base.as_ptr().cast(), self.len) };
+            return;
+        }
+        let slice = std::ptr::slice_from_raw_parts_mut(self.base.as_ptr(), self.len);
+        // SAFETY: the slice was leaked by `Memory::heap` and no buffer refers to it any more.
+        drop(unsafe { Box::from_raw(slice) });
+    }
+}
+
+/// The size of the huge pages used by [`PacketPool::with_huge_pages`].
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+impl PacketPool {
+    /// Creates a pool of `count` buffers of `buf_size` bytes each, allocated on the heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count * buf_size` overflows `usize`.
+    pub fn new(count: usize, buf_size: usize) -> PacketPool {
+        Self::with_memory(
+            count,
+            buf_size,
+            Memory::heap(Self::region_len(count, buf_size)),
+        )
+    }
+    /// Creates a pool like [`PacketPool::new`], backed by 2 MB huge pages to reduce TLB
+    /// misses when cycling through many buffers.
+    ///
+    /// The region is rounded up to a multiple of [`HUGE_PAGE_SIZE`]. Fails if the system
+    /// has not reserved enough huge pages (`vm.nr_hugepages`), in which case callers usually
+    /// fall back to [`PacketPool::new`].
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    pub fn with_huge_pages(count: usize, buf_size: usize) -> std::io::Result<PacketPool> {
+        let memory = Memory::huge_pages(Self::region_len(count, buf_size))?;
+        Ok(Self::with_memory(count, buf_size, memory))
+    }
+    fn region_len(count: usize, buf_size: usize) -> usize {
+        count
+            .checked_mul(buf_size)
+            .expect("packet pool size overflows usize")
+    }
+    fn with_memory(count: usize, buf_size: usize, memory: Memory) -> PacketPool {
+        // Handed out from the back, so buffers are used in address order.
+        let free = (0..count).rev().collect();
+        PacketPool {
+            inner: Arc::new(PoolInner {
+                memory,
+                buf_size,
+                count,
+                free: Mutex::new(free),
+            }),
+        }
+    }
+    /// Takes an empty buffer out of the pool, or returns `None` if all are in use.
+    pub fn get(&self) -> Option<PoolBuf> {
+        let index = self.inner.free.lock().unwrap().pop()?;
+        Some(PoolBuf {
+            pool: self.inner.clone(),
+            index,
+            len: 0,
+        })
+    }
+    /// The number of buffers currently in the pool.
+    pub fn available(&self) -> usize {
+        self.inner.free.lock().unwrap().len()
+    }
+    /// The total number of buffers, in the pool or handed out.
+    pub fn count(&self) -> usize {
+        self.inner.count
+    }
+    /// The capacity of every buffer.
+    pub fn buf_size(&self) -> usize {
+        self.inner.buf_size
+    }
+}
+
+impl fmt::Debug for PacketPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketPool")
+            .field("count", &self.count())
+            .field("buf_size", &self.buf_size())
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+/// A buffer of a [`PacketPool`], returned to the pool when dropped.
+///
+/// It behaves like a `Vec<u8>` whose capacity is fixed to the buffer size of the pool:
+/// growing it past the capacity panics. Its memory is always initialized, so the length can
+/// be set directly, e.g. to the capacity before receiving into it.
+pub struct PoolBuf {
+    pool: Arc<PoolInner>,
+    index: usize,
+    len: usize,
+}
+
+impl PoolBuf {
+    /// The number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Returns `true` if the buffer holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// The fixed capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.pool.buf_size
+    }
+    /// Sets the length of the buffer. Bytes past the old length keep whatever they held
+    /// before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` exceeds the capacity.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(
+            len <= self.capacity(),
+            "length {len} exceeds the capacity {} of the pool buffer",
+            self.capacity()
+        );
+        self.len = len;
+    }
+    /// Shortens the buffer to `len` bytes. Has no effect if `len` is not shorter.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+    /// Empties the buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    /// Resizes the buffer to `new_len` bytes, filling new bytes with `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` exceeds the capacity.
+    pub fn resize(&mut self, new_len: usize, value: u8) {
+        let old_len = self.len;
+        self.set_len(new_len);
+        if new_len > old_len {
+            self[old_len..].fill(value);
+        }
+    }
+    /// Appends `src` to the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer would exceed its capacity.
+    pub fn extend_from_slice(&mut self, src: &[u8]) {
+        let old_len = self.len;
+        self.set_len(old_len + src.len());
+        self[old_len..].copy_from_slice(src);
+    }
+    /// Converts the buffer into [`Bytes`] holding its contents, without copying.
+    ///
+    /// Clones and slices of the `Bytes` share the buffer, which goes back to the pool once
+    /// the last of them is dropped.
+    pub fn freeze(self) -> Bytes {
+        Bytes::from_owner(self)
+    }
+    fn as_ptr(&self) -> *mut u8 {
+        // SAFETY: `index < count`, so the offset stays within the region.
+        unsafe {
+            self.pool
+                .memory
+                .base
+                .as_ptr()
+                .add(self.index * self.pool.buf_size)
+        }
+    }
+}
+
+impl Deref for PoolBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the buffer is only reachable through this handle and always initialized.
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for PoolBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the buffer is only reachable through this handle and always initialized.
+        unsafe { std::slice::from_raw_parts_mut(self.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for PoolBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for PoolBuf {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl Drop for PoolBuf {
+    fn drop(&mut self) {
+        if let Ok(mut free) = self.pool.free.lock() {
+            free.push(self.index);
+        }
+    }
+}
+
+impl fmt::Debug for PoolBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolBuf")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+impl crate::ExpandBuffer for PoolBuf {
+    fn buf_capacity(&self) -> usize {
+        self.capacity()
+    }
+    fn buf_resize(&mut self, new_len: usize, value: u8) {
+        self.resize(new_len, value)
+    }
+    fn buf_extend_from_slice(&mut self, src: &[u8]) {
+        self.extend_from_slice(src)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_recycled() {
+        let pool = PacketPool::new(2, 64);
+        let mut a = pool.get().unwrap();
+        let b = pool.get().unwrap();
+        assert!(pool.get().is_none());
+        assert_eq!(pool.available(), 0);
+
+        a.extend_from_slice(b"hello");
+        a.resize(8, 0xff);
+        assert_eq!(&a[..], b"hello\xff\xff\xff");
+        assert_ne!(a.as_ptr(), b.as_ptr());
+        drop(a);
+        assert_eq!(pool.available(), 1);
+        let c = pool.get().unwrap();
+        assert!(c.is_empty());
+        assert_eq!(c.capacity(), 64);
+    }
+
+    #[test]
+    fn buffers_outlive_the_pool() {
+        let pool = PacketPool::new(1, 16);
+        let mut buf = pool.get().unwrap();
+        drop(pool);
+        buf.set_len(16);
+        buf.fill(1);
+        assert_eq!(buf.iter().sum::<u8>(), 16);
+    }
+
+    #[test]
+    fn frozen_buffers_are_shared() {
+        let pool = PacketPool::new(1, 16);
+        let mut buf = pool.get().unwrap();
+        buf.extend_from_slice(b"shared");
+        let bytes = buf.freeze();
+        let tail = bytes.slice(3..);
+        drop(bytes);
+        assert_eq!(pool.available(), 0);
+        assert_eq!(&tail[..], b"red");
+        let clone = tail.clone();
+        drop(tail);
+        assert_eq!(pool.available(), 0);
+        drop(clone);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn capacity_is_fixed() {
+        let pool = PacketPool::new(1, 4);
+        pool.get().unwrap().extend_from_slice(b"hello");
+    }
+}
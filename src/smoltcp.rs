@@ -0,0 +1,287 @@
+/*!
+# smoltcp Integration
+
+[`SmolDevice`] implements [`smoltcp::phy::Device`](::smoltcp::phy::Device) on top of a
+[`SyncDevice`], so a user-space TCP/IP stack can run directly on the interface. The medium
+follows the layer of the device: [`Medium::Ip`] for TUN (L3) and [`Medium::Ethernet`] for
+TAP (L2).
+
+Packets are read into and written from two buffers owned by the adapter, which smoltcp
+parses and fills in place; there is no extra copy layer. The device is switched to
+nonblocking mode, so [`Interface::poll`](::smoltcp::iface::Interface::poll) never blocks.
+
+[`AsyncSmolDevice`] does the same for an [`AsyncDevice`](crate::AsyncDevice) and adds
+[`readable`](AsyncSmolDevice::readable) to wait for the next packet instead of busy-polling.
+
+Offload (`offload(true)` on Linux) must be disabled, smoltcp expects plain packets.
+
+# Example
+
+```no_run
+use smoltcp::iface::{Config, Interface, SocketSet};
+use smoltcp::time::Instant;
+use smoltcp::wire::{HardwareAddress, IpAddress, IpCidr};
+use tun_rs::smoltcp::SmolDevice;
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+let mut dev = SmolDevice::new(dev)?;
+
+let mut iface = Interface::new(Config::new(HardwareAddress::Ip), &mut dev, Instant::now());
+iface.update_ip_addrs(|addrs| {
+    addrs.push(IpCidr::new(IpAddress::v4(10, 0, 0, 2), 24)).unwrap();
+});
+let mut sockets = SocketSet::new(vec![]);
+loop {
+    iface.poll(Instant::now(), &mut dev, &mut sockets);
+    // ... drive the sockets, sleep for iface.poll_delay()
+}
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::SyncDevice;
+use ::smoltcp::phy::{self, DeviceCapabilities, Medium};
+use ::smoltcp::time::Instant;
+use std::borrow::Borrow;
+use std::io;
+
+/// Length of an Ethernet header, counted in the MTU of [`Medium::Ethernet`].
+const ETHERNET_HEADER_LEN: usize = 14;
+
+/// Nonblocking packet I/O of the devices an adapter can wrap.
+trait PacketIo {
+    fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+    fn try_send(&self, buf: &[u8]) -> io::Result<usize>;
+}
+
+impl PacketIo for SyncDevice {
+    fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(unix)]
+        return self.recv(buf);
+        #[cfg(windows)]
+        return SyncDevice::try_recv(self, buf);
+    }
+    fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+        #[cfg(unix)]
+        return self.send(buf);
+        #[cfg(windows)]
+        return SyncDevice::try_send(self, buf);
+    }
+}
+
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+impl PacketIo for crate::AsyncDevice {
+    fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        crate::AsyncDevice::try_recv(self, buf)
+    }
+    fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
+        crate::AsyncDevice::try_send(self, buf)
+    }
+}
+
+/// Medium, MTU and packet buffers shared by both adapters.
+struct Buffers {
+    medium: Medium,
+    mtu: usize,
+    rx: Vec<u8>,
+    tx: Vec<u8>,
+}
+
+impl Buffers {
+    fn new(device: &crate::DeviceImpl) -> io::Result<Self> {
+        let (medium, mtu) = if device.is_tap() {
+            (
+                Medium::Ethernet,
+                device.mtu()? as usize + ETHERNET_HEADER_LEN,
+            )
+        } else {
+            (Medium::Ip, device.mtu()? as usize)
+        };
+        Ok(Self {
+            medium,
+            mtu,
+            rx: vec![0; mtu],
+            tx: Vec::with_capacity(mtu),
+        })
+    }
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = self.medium;
+        caps.max_transmission_unit = self.mtu;
+        caps
+    }
+    fn receive<'a, D: PacketIo>(
+        &'a mut self,
+        device: &'a D,
+    ) -> Option<(RxToken<'a>, TxToken<'a, D>)> {
+        match device.try_recv(&mut self.rx) {
+            Ok(len) => Some((
+                RxToken(&self.rx[..len]),
+                TxToken {
+                    device,
+                    buf: &mut self.tx,
+                },
+            )),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::WouldBlock {
+                    log::warn!("failed to receive packet: {e}");
+                }
+                None
+            }
+        }
+    }
+    fn transmit<'a, D: PacketIo>(&'a mut self, device: &'a D) -> TxToken<'a, D> {
+        TxToken {
+            device,
+            buf: &mut self.tx,
+        }
+    }
+}
+
+/// A received packet, handed to smoltcp by reference.
+pub struct RxToken<'a>(&'a [u8]);
+
+impl phy::RxToken for RxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.0)
+    }
+}
+
+/// A packet smoltcp builds in the transmit buffer, written to the device on return.
+pub struct TxToken<'a, D> {
+    device: &'a D,
+    buf: &'a mut Vec<u8>,
+}
+
+impl<D: PacketIo> phy::TxToken for TxToken<'_, D> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.buf.resize(len, 0);
+        let r = f(self.buf);
+        // A full device queue drops the packet, as a congested link would.
+        if let Err(e) = self.device.try_send(self.buf) {
+            if e.kind() != io::ErrorKind::WouldBlock {
+                log::warn!("failed to send packet: {e}");
+            }
+        }
+        r
+    }
+}
+
+/// [`smoltcp::phy::Device`](::smoltcp::phy::Device) adapter for a [`SyncDevice`].
+pub struct SmolDevice<D = SyncDevice> {
+    device: D,
+    buffers: Buffers,
+}
+
+impl<D: Borrow<SyncDevice>> SmolDevice<D> {
+    /// Wraps `device`, switching it to nonblocking mode on Unix.
+    ///
+    /// The medium is [`Medium::Ethernet`] for L2 devices and [`Medium::Ip`] otherwise.
+    pub fn new(device: D) -> io::Result<Self> {
+        #[cfg(unix)]
+        device.borrow().set_nonblocking(true)?;
+        let buffers = Buffers::new(&device.borrow().0)?;
+        Ok(Self { device, buffers })
+    }
+    /// The medium reported to smoltcp.
+    pub fn medium(&self) -> Medium {
+        self.buffers.medium
+    }
+    /// Returns a reference to the wrapped device.
+    pub fn get_ref(&self) -> &D {
+        &self.device
+    }
+    /// Consumes the adapter, returning the wrapped device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: Borrow<SyncDevice>> phy::Device for SmolDevice<D> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, SyncDevice>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.buffers.receive(self.device.borrow())
+    }
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(self.buffers.transmit(self.device.borrow()))
+    }
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.buffers.capabilities()
+    }
+}
+
+/// [`smoltcp::phy::Device`](::smoltcp::phy::Device) adapter for an
+/// [`AsyncDevice`](crate::AsyncDevice).
+///
+/// Polling the interface never waits; await [`readable`](Self::readable) (together with a
+/// timer for [`Interface::poll_delay`](::smoltcp::iface::Interface::poll_delay)) between
+/// polls to be woken up when the next packet arrives.
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+pub struct AsyncSmolDevice<D = crate::AsyncDevice> {
+    device: D,
+    buffers: Buffers,
+}
+
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+impl<D: Borrow<crate::AsyncDevice>> AsyncSmolDevice<D> {
+    /// Wraps `device`.
+    ///
+    /// The medium is [`Medium::Ethernet`] for L2 devices and [`Medium::Ip`] otherwise.
+    pub fn new(device: D) -> io::Result<Self> {
+        let buffers = Buffers::new(device.borrow())?;
+        Ok(Self { device, buffers })
+    }
+    /// Waits until a packet can be received.
+    pub async fn readable(&self) -> io::Result<()> {
+        self.device.borrow().readable().await
+    }
+    /// The medium reported to smoltcp.
+    pub fn medium(&self) -> Medium {
+        self.buffers.medium
+    }
+    /// Returns a reference to the wrapped device.
+    pub fn get_ref(&self) -> &D {
+        &self.device
+    }
+    /// Consumes the adapter, returning the wrapped device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+#[cfg(any(feature = "async_io", feature = "async_tokio"))]
+impl<D: Borrow<crate::AsyncDevice>> phy::Device for AsyncSmolDevice<D> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, crate::AsyncDevice>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.buffers.receive(self.device.borrow())
+    }
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(self.buffers.transmit(self.device.borrow()))
+    }
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.buffers.capabilities()
+    }
+}
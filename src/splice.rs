@@ -0,0 +1,167 @@
+//! Forwarding packets between two devices, see [`splice`].
+
+use crate::SyncDevice;
+use std::io;
+
+/// Large enough for any packet of any backend: a 64 KiB GSO packet behind an Ethernet, a
+/// virtio-net and a packet information header.
+const BUF_LEN: usize = 14 + 10 + 4 + 65536;
+
+/// What [`splice`] forwarded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SpliceStats {
+    /// Packets read from the first device and written to the second.
+    pub a_to_b: u64,
+    /// Packets read from the second device and written to the first.
+    pub b_to_a: u64,
+    /// Packets read that the other device refused, e.g. because they exceed its MTU.
+    pub dropped: u64,
+}
+
+impl SpliceStats {
+    /// Packets read from either device, forwarded or dropped.
+    pub fn packets(&self) -> u64 {
+        self.a_to_b + self.b_to_a + self.dropped
+    }
+}
+
+/// Forwards packets between `a` and `b` in both directions until `budget` packets have been
+/// read, and returns what was forwarded.
+///
+/// Packets are passed on unchanged, so both devices must frame them the same way: the same
+/// layer and packet information setting, and on Linux offload enabled on both or neither.
+/// With offload on both, GSO packets of up to 64 KiB are forwarded whole and segmented by the
+/// kernel only where needed, which makes bulk TCP between the devices cheap. A packet the
+/// other device refuses is counted as dropped; a failing read ends the forwarding.
+///
+/// One buffer is reused for every packet. On Unix one thread waits on both devices with
+/// `poll`; on Windows each direction runs on its own thread, which notices that the budget
+/// is spent within 100 ms.
+///
+/// # Example
+///
+/// ```no_run
+/// use tun_rs::DeviceBuilder;
+///
+/// let left = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+/// let right = DeviceBuilder::new().ipv4("10.0.1.1", 24, None).build_sync()?;
+/// // A patch panel between the two interfaces.
+/// let stats = tun_rs::splice(&left, &right, usize::MAX)?;
+/// println!("{stats:?}");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn splice(a: &SyncDevice, b: &SyncDevice, budget: usize) -> io::Result<SpliceStats> {
+    if a.packet_information() != b.packet_information() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "packet information must be enabled on both devices or neither",
+        ));
+    }
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    if a.tcp_gso() != b.tcp_gso() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "offload must be enabled on both devices or neither",
+        ));
+    }
+    splice_impl(a, b, budget)
+}
+
+#[cfg(unix)]
+fn splice_impl(a: &SyncDevice, b: &SyncDevice, budget: usize) -> io::Result<SpliceStats> {
+    use std::os::fd::AsRawFd;
+
+    let mut stats = SpliceStats::default();
+    let mut buf = vec![0u8; BUF_LEN];
+    let mut fds = [a.as_raw_fd(), b.as_raw_fd()].map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    while stats.packets() < budget as u64 {
+        let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) };
+        if res < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        // One packet per ready device and round, so that neither direction starves the other.
+        for (i, (from, to)) in [(a, b), (b, a)].into_iter().enumerate() {
+            if fds[i].revents == 0 || stats.packets() >= budget as u64 {
+                continue;
+            }
+            if fds[i].revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0
+                && fds[i].revents & libc::POLLIN == 0
+            {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let len = match from.recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            forward(to, &buf[..len], i == 0, &mut stats);
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(windows)]
+fn splice_impl(a: &SyncDevice, b: &SyncDevice, budget: usize) -> io::Result<SpliceStats> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    // Packets each direction may still read. A direction claims one before forwarding it.
+    let remaining = AtomicUsize::new(budget);
+    let claim = || {
+        remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+    };
+    let run = |from: &SyncDevice, to: &SyncDevice, a_to_b: bool| -> io::Result<SpliceStats> {
+        let mut stats = SpliceStats::default();
+        let mut buf = vec![0u8; BUF_LEN];
+        while remaining.load(Ordering::Acquire) > 0 {
+            let deadline = Instant::now() + Duration::from_millis(100);
+            let len = match from.recv_deadline(&mut buf, deadline) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => {
+                    remaining.store(0, Ordering::Release);
+                    return Err(e);
+                }
+            };
+            // The other direction spent the budget while this one waited.
+            if !claim() {
+                break;
+            }
+            forward(to, &buf[..len], a_to_b, &mut stats);
+        }
+        Ok(stats)
+    };
+    let (ab, ba) = std::thread::scope(|s| {
+        let ba = s.spawn(|| run(b, a, false));
+        let ab = run(a, b, true);
+        (ab, ba.join().expect("splice thread panicked"))
+    });
+    let (ab, ba) = (ab?, ba?);
+    Ok(SpliceStats {
+        a_to_b: ab.a_to_b,
+        b_to_a: ba.b_to_a,
+        dropped: ab.dropped + ba.dropped,
+    })
+}
+
+fn forward(to: &SyncDevice, packet: &[u8], a_to_b: bool, stats: &mut SpliceStats) {
+    match to.send(packet) {
+        Ok(_) if a_to_b => stats.a_to_b += 1,
+        Ok(_) => stats.b_to_a += 1,
+        Err(e) => {
+            log::debug!("splice dropped a packet of {} bytes: {e}", packet.len());
+            stats.dropped += 1;
+        }
+    }
+}
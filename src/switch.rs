@@ -0,0 +1,623 @@
+/*!
+# Virtual Switch
+
+[`Switch`] forwards Ethernet frames between any number of ports, like a learning bridge: it
+remembers the port each source MAC address was last seen on, sends frames for a known
+address to that port only, and floods the others (unknown, broadcast and multicast) to
+every other port. Learned addresses age out, and their number is bounded (see
+[`Switch::set_fdb_capacity`]). A port is anything implementing [`SwitchPort`]: a TAP [`SyncDevice`] or,
+for tests without root, one end of a [`MemoryPort`] pair.
+
+By default a port is VLAN-unaware ([`PortMode::Transparent`]) and frames pass unchanged,
+tags included. Ports in [`PortMode::Access`] or [`PortMode::Trunk`] put frames into VLANs
+instead, tagging and untagging them on the way out, and only exchange frames with ports of
+the same VLAN.
+
+# Example
+
+```no_run
+use tun_rs::switch::{PortMode, Switch};
+use tun_rs::{DeviceBuilder, Layer};
+
+let switch = Switch::new();
+for vlan in [10, 10, 20] {
+    let dev = DeviceBuilder::new().layer(Layer::L2).build_sync()?;
+    switch.add_port(dev, PortMode::Access(vlan));
+}
+// One thread per port, until a device fails.
+switch.run()?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::ethernet::{FrameMeta, MacAddr, ETHERNET_HEADER_LEN, ETHERTYPE_VLAN, VLAN_TAG_LEN};
+use crate::SyncDevice;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Large enough for any frame a TAP device hands out.
+const BUF_LEN: usize = 65536;
+/// How long a learned address is kept without a frame from it, as on most bridges.
+const DEFAULT_AGING: Duration = Duration::from_secs(300);
+/// How many addresses are learned at most, so that a port sending from random source
+/// addresses cannot grow the table without bound.
+const DEFAULT_FDB_CAPACITY: usize = 8192;
+const VID_MASK: u16 = 0x0fff;
+
+/// A port of a [`Switch`]: something frames can be read from and written to.
+pub trait SwitchPort: Send + Sync {
+    /// Receives a frame into `buf`, blocking until one is available, and returns its length.
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+    /// Sends a frame.
+    fn send(&self, frame: &[u8]) -> io::Result<usize>;
+    /// Sends several frames; by default one [`send`](Self::send) at a time.
+    ///
+    /// Stops at the first error and returns it.
+    fn send_batch(&self, frames: &[&[u8]]) -> io::Result<()> {
+        for frame in frames {
+            self.send(frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl SwitchPort for SyncDevice {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        SyncDevice::recv(self, buf)
+    }
+    fn send(&self, frame: &[u8]) -> io::Result<usize> {
+        SyncDevice::send(self, frame)
+    }
+}
+
+impl<P: SwitchPort + ?Sized> SwitchPort for Arc<P> {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).recv(buf)
+    }
+    fn send(&self, frame: &[u8]) -> io::Result<usize> {
+        (**self).send(frame)
+    }
+    fn send_batch(&self, frames: &[&[u8]]) -> io::Result<()> {
+        (**self).send_batch(frames)
+    }
+}
+
+/// One end of an in-memory link: what one end sends, the other receives.
+///
+/// Once one end is dropped, `recv` on the other fails with
+/// [`io::ErrorKind::BrokenPipe`] after the frames in flight, and `send` fails at once.
+pub struct MemoryPort {
+    tx: Mutex<Sender<Vec<u8>>>,
+    rx: Mutex<Receiver<Vec<u8>>>,
+}
+
+impl MemoryPort {
+    /// Creates the two ends of a link.
+    pub fn pair() -> (MemoryPort, MemoryPort) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        let end = |tx, rx| MemoryPort {
+            tx: Mutex::new(tx),
+            rx: Mutex::new(rx),
+        };
+        (end(a_tx, a_rx), end(b_tx, b_rx))
+    }
+    /// Like [`recv`](SwitchPort::recv), but fails with [`io::ErrorKind::WouldBlock`] instead
+    /// of blocking.
+    pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.rx.lock().unwrap().try_recv() {
+            Ok(frame) => Ok(copy_frame(&frame, buf)),
+            Err(TryRecvError::Empty) => Err(io::ErrorKind::WouldBlock.into()),
+            Err(TryRecvError::Disconnected) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+/// Copies the part of `frame` that fits into `buf`.
+fn copy_frame(frame: &[u8], buf: &mut [u8]) -> usize {
+    let len = frame.len().min(buf.len());
+    buf[..len].copy_from_slice(&frame[..len]);
+    len
+}
+
+impl SwitchPort for MemoryPort {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.rx.lock().unwrap().recv() {
+            Ok(frame) => Ok(copy_frame(&frame, buf)),
+            Err(_) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+    fn send(&self, frame: &[u8]) -> io::Result<usize> {
+        self.tx
+            .lock()
+            .unwrap()
+            .send(frame.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(frame.len())
+    }
+}
+
+/// How a port treats VLAN tags.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum PortMode {
+    /// Ignores VLANs: frames are forwarded unchanged, and only between transparent ports.
+    #[default]
+    Transparent,
+    /// Carries the untagged frames of one VLAN; tagged frames received are dropped.
+    Access(u16),
+    /// Carries the tagged frames of every VLAN. Untagged frames belong to the native VLAN,
+    /// if any, and are dropped otherwise.
+    Trunk {
+        /// The VLAN whose frames are exchanged untagged.
+        native: Option<u16>,
+    },
+}
+
+impl PortMode {
+    /// The VLAN a frame received on the port belongs to (`None` for transparent ports) and
+    /// the priority bits of its tag, or `None` if the port does not take it.
+    fn ingress(self, meta: &FrameMeta) -> Option<(Option<u16>, u16)> {
+        let tci = meta.vlan.unwrap_or(0);
+        // A priority tag (VLAN 0) counts as untagged.
+        let tagged = meta.vlan.is_some() && tci & VID_MASK != 0;
+        match self {
+            PortMode::Transparent => Some((None, 0)),
+            PortMode::Access(vid) if !tagged => Some((Some(vid), tci & !VID_MASK)),
+            PortMode::Access(_) => None,
+            PortMode::Trunk { .. } if tagged => Some((Some(tci & VID_MASK), tci & !VID_MASK)),
+            PortMode::Trunk { native } => native.map(|vid| (Some(vid), tci & !VID_MASK)),
+        }
+    }
+    /// How a frame of `vlan` leaves the port: `Some(tagged)`, or `None` if the port is not
+    /// in that VLAN.
+    fn egress(self, vlan: Option<u16>) -> Option<bool> {
+        match (self, vlan) {
+            (PortMode::Transparent, None) => Some(false),
+            (PortMode::Access(vid), Some(vlan)) if vid == vlan => Some(false),
+            (PortMode::Trunk { native }, Some(vlan)) => Some(native != Some(vlan)),
+            _ => None,
+        }
+    }
+}
+
+/// Identifies a port of a [`Switch`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct PortId(usize);
+
+impl PortId {
+    /// The index of the port, counting the ports added to the switch from 0.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Counters of a [`Switch`], see [`Switch::stats`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SwitchStats {
+    /// Frames received on any port.
+    pub received: u64,
+    /// Frames sent to the one port their destination was learned on.
+    pub forwarded: u64,
+    /// Frames sent to every other port of their VLAN.
+    pub flooded: u64,
+    /// Frames not sent anywhere because their destination is on the port they came from.
+    pub filtered: u64,
+    /// Frames dropped: malformed, not taken by the port's VLAN mode, or failing to send.
+    pub dropped: u64,
+}
+
+/// A learned address: the VLAN (`None` for transparent ports) and the MAC address.
+type FdbKey = (Option<u16>, MacAddr);
+
+struct Port {
+    port: Box<dyn SwitchPort>,
+    mode: PortMode,
+}
+
+#[derive(Default)]
+struct Counters {
+    received: AtomicU64,
+    forwarded: AtomicU64,
+    flooded: AtomicU64,
+    filtered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A learning Ethernet switch between [`SwitchPort`]s, see the [module documentation](self).
+///
+/// The switch can be shared between threads: [`run_port`](Self::run_port) serves one port
+/// and is meant to run on a thread of its own, [`run`](Self::run) does this for all ports.
+pub struct Switch {
+    ports: RwLock<Vec<Option<Arc<Port>>>>,
+    fdb: Mutex<HashMap<FdbKey, (PortId, Instant)>>,
+    fdb_capacity: AtomicUsize,
+    aging: RwLock<Duration>,
+    counters: Counters,
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Switch {
+    /// Creates a switch without ports.
+    pub fn new() -> Self {
+        Switch {
+            ports: RwLock::new(Vec::new()),
+            fdb: Mutex::new(HashMap::new()),
+            fdb_capacity: AtomicUsize::new(DEFAULT_FDB_CAPACITY),
+            aging: RwLock::new(DEFAULT_AGING),
+            counters: Counters::default(),
+        }
+    }
+    /// Sets how long a learned address is remembered without a frame from it, 300 seconds
+    /// by default.
+    pub fn set_aging(&self, aging: Duration) {
+        *self.aging.write().unwrap() = aging;
+    }
+    /// Sets how many addresses are learned at most, 8192 by default.
+    ///
+    /// When the table is full, the addresses that have aged out are forgotten first; if none
+    /// has, new addresses are not learned and frames to them are flooded until room is made.
+    pub fn set_fdb_capacity(&self, capacity: usize) {
+        self.fdb_capacity.store(capacity, Ordering::Relaxed);
+    }
+    /// Adds a port and returns its id.
+    ///
+    /// Frames are expected to be Ethernet frames, so a device must be a TAP device; the
+    /// frames of a TUN device are dropped as malformed.
+    pub fn add_port<P: SwitchPort + 'static>(&self, port: P, mode: PortMode) -> PortId {
+        let mut ports = self.ports.write().unwrap();
+        ports.push(Some(Arc::new(Port {
+            port: Box::new(port),
+            mode,
+        })));
+        PortId(ports.len() - 1)
+    }
+    /// Removes a port and forgets the addresses learned on it. A [`run_port`](Self::run_port)
+    /// serving it returns after its next frame.
+    pub fn remove_port(&self, id: PortId) {
+        if let Some(slot) = self.ports.write().unwrap().get_mut(id.0) {
+            *slot = None;
+        }
+        self.fdb.lock().unwrap().retain(|_, (port, _)| *port != id);
+    }
+    /// Returns the port `mac` was last seen on in `vlan` (`None` for transparent ports).
+    pub fn lookup(&self, mac: MacAddr, vlan: Option<u16>) -> Option<PortId> {
+        let aging = *self.aging.read().unwrap();
+        let fdb = self.fdb.lock().unwrap();
+        fdb.get(&(vlan, mac))
+            .filter(|(_, seen)| seen.elapsed() < aging)
+            .map(|(port, _)| *port)
+    }
+    /// Remembers that `key` was seen on `port`, making room in a full table by forgetting the
+    /// addresses that have aged out.
+    fn learn(&self, key: FdbKey, port: PortId) {
+        let mut fdb = self.fdb.lock().unwrap();
+        if let Some(entry) = fdb.get_mut(&key) {
+            *entry = (port, Instant::now());
+            return;
+        }
+        let capacity = self.fdb_capacity.load(Ordering::Relaxed);
+        if fdb.len() >= capacity {
+            let aging = *self.aging.read().unwrap();
+            fdb.retain(|_, (_, seen)| seen.elapsed() < aging);
+            if fdb.len() >= capacity {
+                return;
+            }
+        }
+        fdb.insert(key, (port, Instant::now()));
+    }
+    /// Forgets all learned addresses.
+    pub fn flush(&self) {
+        self.fdb.lock().unwrap().clear();
+    }
+    /// Returns the counters of the switch.
+    pub fn stats(&self) -> SwitchStats {
+        let c = &self.counters;
+        SwitchStats {
+            received: c.received.load(Ordering::Relaxed),
+            forwarded: c.forwarded.load(Ordering::Relaxed),
+            flooded: c.flooded.load(Ordering::Relaxed),
+            filtered: c.filtered.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+        }
+    }
+    /// Switches a frame received on `ingress`, and returns the number of ports it was sent to.
+    ///
+    /// This is what [`run_port`](Self::run_port) does with every frame it reads; call it
+    /// directly to feed frames from elsewhere. Fails with [`io::ErrorKind::NotFound`] if
+    /// there is no such port.
+    pub fn forward(&self, ingress: PortId, frame: &[u8]) -> io::Result<usize> {
+        self.forward_batch(ingress, &[frame])
+    }
+    /// Switches several frames received on `ingress`, handing the frames for each port to its
+    /// [`send_batch`](SwitchPort::send_batch) at once, and returns the number of frames sent.
+    pub fn forward_batch(&self, ingress: PortId, frames: &[&[u8]]) -> io::Result<usize> {
+        let ports = self.ports.read().unwrap();
+        let Some(Some(port)) = ports.get(ingress.0) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no such switch port",
+            ));
+        };
+        let c = &self.counters;
+        let mut out: Vec<Vec<Cow<'_, [u8]>>> = vec![Vec::new(); ports.len()];
+        for frame in frames {
+            c.received.fetch_add(1, Ordering::Relaxed);
+            let Some((meta, (vlan, priority))) =
+                FrameMeta::parse(frame).and_then(|meta| Some((meta, port.mode.ingress(&meta)?)))
+            else {
+                c.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            let source = MacAddr(meta.source);
+            if !source.is_multicast() {
+                self.learn((vlan, source), ingress);
+            }
+            let destination = MacAddr(meta.destination);
+            let known = (!destination.is_multicast())
+                .then(|| self.lookup(destination, vlan))
+                .flatten();
+            let egress = |id: usize| {
+                let mode = ports[id].as_ref()?.mode;
+                let tagged = mode.egress(vlan)?;
+                let tci = vlan.filter(|_| tagged).map(|vid| vid | priority);
+                Some(retag(frame, &meta, tci))
+            };
+            match known {
+                Some(id) if id == ingress => {
+                    c.filtered.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(id) => match egress(id.0) {
+                    Some(frame) => {
+                        out[id.0].push(frame);
+                        c.forwarded.fetch_add(1, Ordering::Relaxed);
+                    }
+                    None => {
+                        c.filtered.fetch_add(1, Ordering::Relaxed);
+                    }
+                },
+                None => {
+                    for id in (0..ports.len()).filter(|id| *id != ingress.0) {
+                        if let Some(frame) = egress(id) {
+                            out[id].push(frame);
+                        }
+                    }
+                    c.flooded.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        let mut sent = 0;
+        for (id, frames) in out.iter().enumerate() {
+            let Some(Some(port)) = ports.get(id).filter(|_| !frames.is_empty()) else {
+                continue;
+            };
+            let frames: Vec<&[u8]> = frames.iter().map(|frame| &frame[..]).collect();
+            match port.port.send_batch(&frames) {
+                Ok(()) => sent += frames.len(),
+                Err(e) => {
+                    log::debug!("switch port {id} failed to send: {e}");
+                    c.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(sent)
+    }
+    /// Reads frames from the port `id` and switches them, until reading fails or the port
+    /// is removed.
+    ///
+    /// Returns the error that ended it, or `Ok` if the port was removed.
+    pub fn run_port(&self, id: PortId) -> io::Result<()> {
+        let port = match self.ports.read().unwrap().get(id.0) {
+            Some(Some(port)) => port.clone(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no such switch port",
+                ))
+            }
+        };
+        let mut buf = vec![0u8; BUF_LEN];
+        loop {
+            let len = match port.port.recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            match self.forward(id, &buf[..len]) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                result => result?,
+            };
+        }
+    }
+    /// Serves every port on a thread of its own until all of them end, see
+    /// [`run_port`](Self::run_port), and returns the first error.
+    ///
+    /// Ports added while it runs are not served.
+    pub fn run(&self) -> io::Result<()> {
+        let ids: Vec<PortId> = (0..self.ports.read().unwrap().len()).map(PortId).collect();
+        std::thread::scope(|scope| {
+            let threads: Vec<_> = ids
+                .into_iter()
+                .map(|id| scope.spawn(move || self.run_port(id)))
+                .collect();
+            let mut result = Ok(());
+            for thread in threads {
+                let r = thread
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("a switch port thread panicked")));
+                if result.is_ok() {
+                    result = r.or_else(|e| match e.kind() {
+                        io::ErrorKind::NotFound => Ok(()),
+                        _ => Err(e),
+                    });
+                }
+            }
+            result
+        })
+    }
+}
+
+/// `frame` with its VLAN tag replaced by `tci`, or removed if `None`.
+fn retag<'a>(frame: &'a [u8], meta: &FrameMeta, tci: Option<u16>) -> Cow<'a, [u8]> {
+    const TAG: usize = ETHERNET_HEADER_LEN - 2;
+    match (meta.vlan, tci) {
+        (None, None) => Cow::Borrowed(frame),
+        (Some(old), Some(new)) if old == new => Cow::Borrowed(frame),
+        (Some(_), Some(new)) => {
+            let mut frame = frame.to_vec();
+            frame[TAG + 2..TAG + 4].copy_from_slice(&new.to_be_bytes());
+            Cow::Owned(frame)
+        }
+        (Some(_), None) => {
+            let mut untagged = Vec::with_capacity(frame.len() - VLAN_TAG_LEN);
+            untagged.extend_from_slice(&frame[..TAG]);
+            untagged.extend_from_slice(&frame[TAG + VLAN_TAG_LEN..]);
+            Cow::Owned(untagged)
+        }
+        (None, Some(new)) => {
+            let mut tagged = Vec::with_capacity(frame.len() + VLAN_TAG_LEN);
+            tagged.extend_from_slice(&frame[..TAG]);
+            tagged.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            tagged.extend_from_slice(&new.to_be_bytes());
+            tagged.extend_from_slice(&frame[TAG..]);
+            Cow::Owned(tagged)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(destination: u8, source: u8, vlan: Option<u16>) -> Vec<u8> {
+        let mut meta = FrameMeta::new([destination; 6], [source; 6], 0x0800);
+        meta.vlan = vlan;
+        let mut frame = vec![0u8; meta.header_len() + 4];
+        meta.write_header(&mut frame);
+        frame
+    }
+
+    fn received(host: &MemoryPort) -> Vec<Vec<u8>> {
+        let mut buf = [0u8; 64];
+        std::iter::from_fn(|| host.try_recv(&mut buf).ok().map(|len| buf[..len].to_vec())).collect()
+    }
+
+    #[test]
+    fn learning() {
+        let switch = Switch::new();
+        let hosts: Vec<MemoryPort> = (0..3)
+            .map(|_| {
+                let (port, host) = MemoryPort::pair();
+                switch.add_port(port, PortMode::Transparent);
+                host
+            })
+            .collect();
+        let (a, b) = (PortId(0), PortId(1));
+        // Unknown destination: flooded.
+        let ab = frame(0x02, 0x04, None);
+        assert_eq!(switch.forward(a, &ab).unwrap(), 2);
+        assert_eq!(received(&hosts[1]), [&ab[..]]);
+        assert_eq!(received(&hosts[2]), [&ab[..]]);
+        assert_eq!(switch.lookup(MacAddr([0x04; 6]), None), Some(a));
+        // The reply goes to the learned port only.
+        let ba = frame(0x04, 0x02, None);
+        assert_eq!(switch.forward(b, &ba).unwrap(), 1);
+        assert_eq!(received(&hosts[0]), [ba]);
+        assert!(received(&hosts[2]).is_empty());
+        // A frame for a host on the ingress port goes nowhere.
+        assert_eq!(switch.forward(b, &frame(0x02, 0x06, None)).unwrap(), 0);
+        assert_eq!(switch.stats().filtered, 1);
+
+        switch.remove_port(a);
+        assert_eq!(switch.lookup(MacAddr([0x04; 6]), None), None);
+        assert_eq!(
+            switch.forward(a, &ab).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn vlans() {
+        let switch = Switch::new();
+        let modes = [
+            PortMode::Access(10),
+            PortMode::Access(20),
+            PortMode::Trunk { native: Some(20) },
+        ];
+        let hosts: Vec<MemoryPort> = modes
+            .into_iter()
+            .map(|mode| {
+                let (port, host) = MemoryPort::pair();
+                switch.add_port(port, mode);
+                host
+            })
+            .collect();
+        // VLAN 10 leaves the trunk tagged, and VLAN 20 does not reach access port 10.
+        switch.forward(PortId(0), &frame(0xff, 0x02, None)).unwrap();
+        assert_eq!(received(&hosts[2]), [frame(0xff, 0x02, Some(10))]);
+        assert!(received(&hosts[1]).is_empty());
+        // The native VLAN leaves the trunk untagged; a tag from the trunk is removed.
+        switch.forward(PortId(1), &frame(0xff, 0x04, None)).unwrap();
+        assert_eq!(received(&hosts[2]), [frame(0xff, 0x04, None)]);
+        switch
+            .forward(PortId(2), &frame(0xff, 0x06, Some(10)))
+            .unwrap();
+        assert_eq!(received(&hosts[0]), [frame(0xff, 0x06, None)]);
+        assert!(received(&hosts[1]).is_empty());
+        // Tagged frames on an access port are dropped.
+        switch
+            .forward(PortId(0), &frame(0xff, 0x02, Some(10)))
+            .unwrap();
+        assert_eq!(switch.stats().dropped, 1);
+    }
+
+    #[test]
+    fn run_port() {
+        let switch = Switch::new();
+        let (port_a, host_a) = MemoryPort::pair();
+        let (port_b, host_b) = MemoryPort::pair();
+        switch.add_port(port_a, PortMode::Transparent);
+        switch.add_port(port_b, PortMode::Transparent);
+        let ab = frame(0x02, 0x04, None);
+        host_a.send(&ab).unwrap();
+        drop(host_a);
+        // Ends with the broken link once the frame is switched.
+        let err = switch.run_port(PortId(0)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(received(&host_b), [ab]);
+    }
+
+    #[test]
+    fn fdb_capacity() {
+        let switch = Switch::new();
+        let (port, _host) = MemoryPort::pair();
+        let a = switch.add_port(port, PortMode::Transparent);
+        switch.set_fdb_capacity(4);
+        // A flood of source addresses only fills the table.
+        for source in 0..64 {
+            switch.forward(a, &frame(0xff, source * 2, None)).unwrap();
+        }
+        assert_eq!(switch.fdb.lock().unwrap().len(), 4);
+        assert_eq!(switch.lookup(MacAddr([0; 6]), None), Some(a));
+        assert_eq!(switch.lookup(MacAddr([8; 6]), None), None);
+        // Known addresses are still refreshed in a full table.
+        switch.forward(a, &frame(0xff, 0, None)).unwrap();
+        assert_eq!(switch.fdb.lock().unwrap().len(), 4);
+
+        // Once the entries have aged out, they make room for new addresses.
+        switch.set_aging(Duration::ZERO);
+        switch.forward(a, &frame(0xff, 8, None)).unwrap();
+        let fdb = switch.fdb.lock().unwrap();
+        assert_eq!(fdb.len(), 1);
+        assert!(fdb.contains_key(&(None, MacAddr([8; 6]))));
+    }
+}
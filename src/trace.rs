@@ -0,0 +1,84 @@
+//! Instrumentation with the `tracing` crate, behind the `tracing` feature.
+//!
+//! Every device carries a `tun` span with its `name` and `index`, created when it is built.
+//! Events about a device are recorded in that span, so a subscriber can tell the queues and
+//! devices of a process apart without the events repeating those fields:
+//!
+//! - `debug`: configuration steps, session start/stop and other rare state changes.
+//! - `trace`: individual reads and writes, readiness that turned out to be spurious, and
+//!   the GSO/GRO decisions of the offload path.
+//!
+//! Without the feature the macros expand to nothing.
+
+/// Records an event at `$level` in the span of the device `$dev`.
+///
+/// `$dev` is anything with a `span: DeviceSpan` field, in practice a `DeviceImpl`.
+macro_rules! dev_event {
+    ($level:ident, $dev:expr, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        match $dev.span.id() {
+            Some(id) => ::tracing::$level!(parent: id, $($arg)+),
+            None => ::tracing::$level!($($arg)+),
+        }
+    };
+}
+#[allow(unused_imports)]
+pub(crate) use dev_event;
+
+/// Records an event at `$level` in the current span, for code that has no device at hand.
+#[allow(unused_macros)]
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::$level!($($arg)+);
+    };
+}
+#[allow(unused_imports)]
+pub(crate) use event;
+
+/// The `tun` span of a device, shared by the queues cloned from it.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub(crate) struct DeviceSpan(std::sync::OnceLock<tracing::Span>);
+
+#[cfg(feature = "tracing")]
+impl DeviceSpan {
+    /// Creates the span; later calls keep the first one.
+    pub(crate) fn init(&self, name: Option<&str>, index: Option<u32>) {
+        _ = self
+            .0
+            .get_or_init(|| tracing::debug_span!("tun", name, index));
+    }
+    /// The id of the span, `None` before [`init`](Self::init) or if no subscriber wants it.
+    pub(crate) fn id(&self) -> Option<tracing::Id> {
+        self.0.get().and_then(tracing::Span::id)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl crate::platform::DeviceImpl {
+    /// Creates the `tun` span of the device from its name and index.
+    ///
+    /// Must not be called with `op_lock` held, as looking up the index takes it.
+    pub(crate) fn init_span(&self) {
+        #[cfg(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        ))]
+        self.span
+            .init(self.name().ok().as_deref(), self.if_index().ok());
+        #[cfg(not(any(
+            target_os = "windows",
+            all(target_os = "linux", not(target_env = "ohos")),
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+        )))]
+        self.span.init(None, None);
+    }
+}
@@ -0,0 +1,478 @@
+/*!
+# Tunnels
+
+[`Tunnel`] pairs a device with a [`PacketTransport`], e.g. an encrypted UDP session, and
+pumps packets both ways: what the device hands out is sent as one datagram per packet, and
+every datagram received is written to the device. Packets are carried as the device frames
+them, so both ends of a tunnel need the same layer and packet information setting.
+
+On the way the tunnel takes care of what most VPN-like applications otherwise write by hand:
+
+- **MTU clamping**: packets larger than the tunnel MTU, the device MTU unless set with
+  [`Tunnel::mtu`], are dropped instead of being handed to a transport that would fragment
+  or refuse them.
+- **MSS fixup**: the MSS option of TCP SYN packets in both directions is lowered to what fits
+  into the tunnel MTU, so TCP connections through the tunnel never send larger segments.
+- **Batching**: packets ready on the device are passed to
+  [`PacketTransport::send_batch`] together; on Linux with `offload(true)` GSO packets are
+  segmented with `recv_multiple`, and received batches are coalesced with `send_multiple`.
+- **Lifecycle**: [`Tunnel::on_start`] and [`Tunnel::on_stop`] run when the tunnel starts and
+  ends, and [`Tunnel::stop`] ends it from another thread.
+
+The transport does the encryption; the tunnel only moves packets.
+
+# Example
+
+```no_run
+use std::net::UdpSocket;
+use std::time::Duration;
+use tun_rs::tunnel::Tunnel;
+use tun_rs::DeviceBuilder;
+
+let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+let socket = UdpSocket::bind("0.0.0.0:51820")?;
+socket.connect("203.0.113.7:51820")?;
+// Lets `stop` take effect while no datagram arrives.
+socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+let tunnel = Tunnel::new(dev, socket)
+    .mtu(1420)
+    .on_start(|dev| println!("tunnel up on {:?}", dev.name()))
+    .on_stop(|result| println!("tunnel down: {result:?}"));
+tunnel.run()?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::ethernet::ETHERNET_HEADER_LEN;
+use crate::mss_clamp::clamp_to_mtu;
+use crate::{Layer, SyncDevice};
+use std::io;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Large enough for any datagram.
+const BUF_LEN: usize = 65536;
+/// Packets read from the device before they are passed to the transport together.
+const BATCH: usize = 64;
+/// How often a blocked direction checks whether the tunnel was stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The other side of a [`Tunnel`]: something datagrams can be sent over and received from.
+pub trait PacketTransport: Send + Sync {
+    /// Sends one datagram.
+    fn send(&self, datagram: &[u8]) -> io::Result<usize>;
+    /// Receives one datagram into `buf` and returns its length.
+    ///
+    /// Failing with [`io::ErrorKind::WouldBlock`] or [`io::ErrorKind::TimedOut`] is not an
+    /// error: the tunnel checks whether it was stopped and calls again. A transport that
+    /// blocks until a datagram arrives delays [`Tunnel::stop`] until then.
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+    /// Sends several datagrams and returns how many were sent; by default one
+    /// [`send`](Self::send) at a time.
+    ///
+    /// Stops at the first failure, which is returned if no datagram was sent.
+    fn send_batch(&self, datagrams: &[&[u8]]) -> io::Result<usize> {
+        for (i, datagram) in datagrams.iter().enumerate() {
+            if let Err(e) = self.send(datagram) {
+                return if i == 0 { Err(e) } else { Ok(i) };
+            }
+        }
+        Ok(datagrams.len())
+    }
+    /// Receives up to `bufs.len()` datagrams, datagram `i` into `bufs[i]` with its length in
+    /// `sizes[i]`, and returns their number; by default one [`recv`](Self::recv).
+    fn recv_batch(&self, bufs: &mut [&mut [u8]], sizes: &mut [usize]) -> io::Result<usize> {
+        match (bufs.first_mut(), sizes.first_mut()) {
+            (Some(buf), Some(size)) => {
+                *size = self.recv(buf)?;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+/// A connected UDP socket, see [`UdpSocket::connect`].
+impl PacketTransport for UdpSocket {
+    fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        UdpSocket::send(self, datagram)
+    }
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        UdpSocket::recv(self, buf)
+    }
+}
+
+impl<T: PacketTransport + ?Sized> PacketTransport for Arc<T> {
+    fn send(&self, datagram: &[u8]) -> io::Result<usize> {
+        (**self).send(datagram)
+    }
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).recv(buf)
+    }
+    fn send_batch(&self, datagrams: &[&[u8]]) -> io::Result<usize> {
+        (**self).send_batch(datagrams)
+    }
+    fn recv_batch(&self, bufs: &mut [&mut [u8]], sizes: &mut [usize]) -> io::Result<usize> {
+        (**self).recv_batch(bufs, sizes)
+    }
+}
+
+/// Counters of a [`Tunnel`], see [`Tunnel::stats`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TunnelStats {
+    /// Packets read from the device and sent over the transport.
+    pub outbound: u64,
+    /// Datagrams received from the transport and written to the device.
+    pub inbound: u64,
+    /// Packets from the device dropped for exceeding the tunnel MTU.
+    pub oversized: u64,
+    /// TCP SYN packets whose MSS option was lowered, in either direction.
+    pub mss_clamped: u64,
+    /// Packets the transport or the device refused.
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    outbound: AtomicU64,
+    inbound: AtomicU64,
+    oversized: AtomicU64,
+    mss_clamped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+type StartHook = Box<dyn Fn(&SyncDevice) + Send + Sync>;
+type StopHook = Box<dyn Fn(&io::Result<()>) + Send + Sync>;
+
+/// A device paired with a [`PacketTransport`], see the [module documentation](self).
+pub struct Tunnel<T> {
+    device: SyncDevice,
+    transport: T,
+    mtu: Option<u16>,
+    mss_fixup: bool,
+    on_start: Option<StartHook>,
+    on_stop: Option<StopHook>,
+    stopped: AtomicBool,
+    counters: Counters,
+}
+
+impl<T: PacketTransport> Tunnel<T> {
+    /// Pairs `device` with `transport`.
+    pub fn new(device: SyncDevice, transport: T) -> Self {
+        Self {
+            device,
+            transport,
+            mtu: None,
+            mss_fixup: true,
+            on_start: None,
+            on_stop: None,
+            stopped: AtomicBool::new(false),
+            counters: Counters::default(),
+        }
+    }
+    /// Sets the largest IP packet the tunnel carries, usually the path MTU of the transport
+    /// minus its overhead; defaults to the MTU of the device when the tunnel starts.
+    pub fn mtu(mut self, mtu: u16) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+    /// Sets whether the MSS option of TCP SYN packets is lowered to fit the tunnel MTU;
+    /// defaults to `true`.
+    pub fn mss_fixup(mut self, mss_fixup: bool) -> Self {
+        self.mss_fixup = mss_fixup;
+        self
+    }
+    /// Sets a hook run by [`run`](Self::run) before any packet is forwarded.
+    pub fn on_start<F: Fn(&SyncDevice) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+    /// Sets a hook run by [`run`](Self::run) with its result once both directions ended.
+    pub fn on_stop<F: Fn(&io::Result<()>) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_stop = Some(Box::new(f));
+        self
+    }
+    /// The device of the tunnel.
+    pub fn device(&self) -> &SyncDevice {
+        &self.device
+    }
+    /// The transport of the tunnel.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+    /// Returns the counters since the tunnel was created.
+    pub fn stats(&self) -> TunnelStats {
+        let c = &self.counters;
+        TunnelStats {
+            outbound: c.outbound.load(Ordering::Relaxed),
+            inbound: c.inbound.load(Ordering::Relaxed),
+            oversized: c.oversized.load(Ordering::Relaxed),
+            mss_clamped: c.mss_clamped.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+        }
+    }
+    /// Makes [`run`](Self::run) return `Ok` within about 100 ms, or once the transport
+    /// returns from `recv`.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+    /// Returns whether the tunnel was stopped, by [`stop`](Self::stop) or a failure.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+    /// Forwards packets in both directions, each on a thread of its own, until the tunnel
+    /// is stopped or reading from the device or the transport fails.
+    ///
+    /// Returns the error that ended it, or `Ok` if it was stopped. Stopping is final: `run`
+    /// on a stopped tunnel returns at once.
+    pub fn run(&self) -> io::Result<()> {
+        if let Some(on_start) = &self.on_start {
+            on_start(&self.device);
+        }
+        let result = self.run_directions();
+        if let Some(on_stop) = &self.on_stop {
+            on_stop(&result);
+        }
+        result
+    }
+    fn run_directions(&self) -> io::Result<()> {
+        let caps = self.device.capabilities()?;
+        let path = Path {
+            mtu: match self.mtu {
+                Some(mtu) => mtu,
+                None => self.device.mtu()?,
+            },
+            pi: self.device.packet_information(),
+            l2: caps.layer == Layer::L2,
+            gso: caps.tcp_gso,
+        };
+        std::thread::scope(|scope| {
+            let outbound = scope.spawn(|| self.until_failure(self.run_outbound(path)));
+            let inbound = self.until_failure(self.run_inbound(path));
+            let outbound = outbound
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("the tunnel thread panicked")));
+            outbound.and(inbound)
+        })
+    }
+    /// Stops the other direction when one fails.
+    fn until_failure(&self, result: io::Result<()>) -> io::Result<()> {
+        if result.is_err() {
+            self.stop();
+        }
+        result
+    }
+    fn run_outbound(&self, path: Path) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if path.gso {
+            return self.run_outbound_gso(path);
+        }
+        let caps = self.device.capabilities()?;
+        let mut bufs = vec![vec![0u8; caps.max_packet_size]; BATCH];
+        let mut sizes = [0usize; BATCH];
+        while !self.is_stopped() {
+            let mut n = 0;
+            while n < BATCH {
+                // Waits for the first packet only; the others are what is already queued.
+                let timeout = if n == 0 {
+                    POLL_INTERVAL
+                } else {
+                    Duration::ZERO
+                };
+                match recv_within(&self.device, &mut bufs[n], timeout)? {
+                    Some(len) => sizes[n] = len,
+                    None => break,
+                }
+                n += 1;
+            }
+            self.forward_outbound(&mut bufs[..n], &sizes[..n], path);
+        }
+        Ok(())
+    }
+    /// Segments the GSO packets of an offload device to the MTU before they are sent.
+    #[cfg(target_os = "linux")]
+    fn run_outbound_gso(&self, path: Path) -> io::Result<()> {
+        use crate::{IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+        let mut original = vec![0u8; VIRTIO_NET_HDR_LEN + 65535];
+        let segment_len = self.device.mtu()? as usize + ETHERNET_HEADER_LEN;
+        let mut bufs = vec![vec![0u8; segment_len]; IDEAL_BATCH_SIZE];
+        let mut sizes = vec![0usize; IDEAL_BATCH_SIZE];
+        while !self.is_stopped() {
+            if !wait_readable(&self.device, POLL_INTERVAL)? {
+                continue;
+            }
+            let n = match self
+                .device
+                .recv_multiple(&mut original, &mut bufs, &mut sizes, 0)
+            {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.forward_outbound(&mut bufs[..n], &sizes[..n], path);
+        }
+        Ok(())
+    }
+    fn forward_outbound(&self, bufs: &mut [Vec<u8>], sizes: &[usize], path: Path) {
+        let c = &self.counters;
+        let mut batch = Vec::with_capacity(bufs.len());
+        for (buf, &len) in bufs.iter_mut().zip(sizes) {
+            let packet = &mut buf[..len];
+            if let Some(offset) = path.ip_offset(packet) {
+                if packet.len() - offset > path.mtu as usize {
+                    c.oversized.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                if self.mss_fixup && clamp_to_mtu(&mut packet[offset..], path.mtu) {
+                    c.mss_clamped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            batch.push(&*packet);
+        }
+        if batch.is_empty() {
+            return;
+        }
+        let sent = self.transport.send_batch(&batch).unwrap_or(0);
+        c.outbound.fetch_add(sent as u64, Ordering::Relaxed);
+        c.dropped
+            .fetch_add((batch.len() - sent) as u64, Ordering::Relaxed);
+    }
+    fn run_inbound(&self, path: Path) -> io::Result<()> {
+        // An offload device takes a virtio net header in front of each packet.
+        #[cfg(target_os = "linux")]
+        let headroom = if path.gso {
+            crate::VIRTIO_NET_HDR_LEN
+        } else {
+            0
+        };
+        #[cfg(not(target_os = "linux"))]
+        let headroom = 0;
+        #[cfg(target_os = "linux")]
+        let mut gro_table = crate::GROTable::default();
+        let c = &self.counters;
+        let mut bufs = vec![vec![0u8; headroom + BUF_LEN]; BATCH];
+        let mut sizes = [0usize; BATCH];
+        while !self.is_stopped() {
+            let mut slices: Vec<&mut [u8]> =
+                bufs.iter_mut().map(|buf| &mut buf[headroom..]).collect();
+            let n = match self.transport.recv_batch(&mut slices, &mut sizes) {
+                Ok(n) => n,
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted => continue,
+                    _ => return Err(e),
+                },
+            };
+            for (buf, &len) in bufs.iter_mut().zip(&sizes[..n]) {
+                let packet = &mut buf[headroom..headroom + len];
+                if let Some(offset) = path.ip_offset(packet) {
+                    if self.mss_fixup && clamp_to_mtu(&mut packet[offset..], path.mtu) {
+                        c.mss_clamped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            #[cfg(target_os = "linux")]
+            if path.gso {
+                // `send_multiple` takes each packet as a whole buffer, writes its header into
+                // the headroom and may append coalesced packets; the buffers are restored to
+                // their full length afterwards.
+                for (buf, &len) in bufs.iter_mut().zip(&sizes[..n]) {
+                    buf.truncate(headroom + len);
+                }
+                match self
+                    .device
+                    .send_multiple(&mut gro_table, &mut bufs[..n], headroom)
+                {
+                    Ok(_) => c.inbound.fetch_add(n as u64, Ordering::Relaxed),
+                    Err(_) => c.dropped.fetch_add(n as u64, Ordering::Relaxed),
+                };
+                for buf in &mut bufs[..n] {
+                    buf.resize(headroom + BUF_LEN, 0);
+                }
+                continue;
+            }
+            for (buf, &len) in bufs.iter().zip(&sizes[..n]) {
+                match self.device.send(&buf[..len]) {
+                    Ok(_) => c.inbound.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => c.dropped.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the device frames packets, and the MTU of the tunnel.
+#[derive(Clone, Copy)]
+struct Path {
+    mtu: u16,
+    pi: bool,
+    l2: bool,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    gso: bool,
+}
+
+impl Path {
+    /// Where the IP packet starts in `packet`, if it carries one.
+    fn ip_offset(self, packet: &[u8]) -> Option<usize> {
+        crate::mss_clamp::ip_offset(packet, self.pi, self.l2)
+    }
+}
+
+/// Receives a packet, waiting at most `timeout`; `None` if none arrived.
+#[cfg(unix)]
+fn recv_within(
+    device: &SyncDevice,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> io::Result<Option<usize>> {
+    if !wait_readable(device, timeout)? {
+        return Ok(None);
+    }
+    match device.recv(buf) {
+        Ok(len) => Ok(Some(len)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn recv_within(
+    device: &SyncDevice,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> io::Result<Option<usize>> {
+    match device.recv_deadline(buf, std::time::Instant::now() + timeout) {
+        Ok(len) => Ok(Some(len)),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Waits at most `timeout` for the device to become readable.
+#[cfg(unix)]
+fn wait_readable(device: &SyncDevice, timeout: Duration) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+    let mut fds = libc::pollfd {
+        fd: device.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `fds` is a valid pollfd.
+    match unsafe { libc::poll(&mut fds, 1, timeout.as_millis() as libc::c_int) } {
+        n if n < 0 => {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            Err(e)
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
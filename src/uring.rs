@@ -0,0 +1,159 @@
+/*!
+# tokio-uring Support
+
+[`UringDevice`] drives the TUN file descriptor through io_uring on a
+[tokio-uring](https://docs.rs/tokio-uring) runtime. It is independent of
+[`AsyncDevice`](crate::AsyncDevice), which relies on epoll readiness.
+
+tokio-uring uses owned buffers: [`recv`](UringDevice::recv) and [`send`](UringDevice::send)
+take the buffer by value and hand it back together with the result, because the kernel
+accesses it while the operation is in flight.
+
+Offload (`offload(true)`) is not supported; packets are read and written one by one.
+
+# Example
+
+```no_run
+use tokio_uring::buf::BoundedBuf;
+use tun_rs::uring::UringDevice;
+use tun_rs::DeviceBuilder;
+
+tokio_uring::start(async {
+    let dev = DeviceBuilder::new().ipv4("10.0.0.1", 24, None).build_sync()?;
+    let dev = UringDevice::new(dev)?;
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (res, b) = dev.recv(buf).await;
+        let len = res?;
+        let (res, b) = dev.send(b.slice(..len)).await;
+        res?;
+        buf = b.into_inner();
+    }
+    # Ok::<(), std::io::Error>(())
+})?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::platform::DeviceImpl;
+use crate::SyncDevice;
+use std::borrow::Cow;
+use std::io;
+use std::ops::Deref;
+use std::os::fd::AsFd;
+use tokio_uring::buf::{BoundedBuf, BoundedBufMut};
+use tokio_uring::fs::File;
+use tokio_uring::BufResult;
+
+/// TUN/TAP device using the owned-buffer I/O of tokio-uring.
+///
+/// Configuration methods are available through `Deref`, as on [`SyncDevice`].
+pub struct UringDevice {
+    device: SyncDevice,
+    file: File,
+}
+
+impl UringDevice {
+    /// Wraps `device`, which is switched to blocking mode.
+    ///
+    /// io_uring completes reads asynchronously on a blocking descriptor; on a nonblocking one it
+    /// would fail with `WouldBlock` instead. Must be called within a tokio-uring runtime.
+    pub fn new(device: SyncDevice) -> io::Result<Self> {
+        if device.vnet_hdr {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "offload is not supported by UringDevice",
+            ));
+        }
+        device.set_nonblocking(false)?;
+        let fd = device.as_fd().try_clone_to_owned()?;
+        let file = File::from_std(std::fs::File::from(fd));
+        Ok(Self { device, file })
+    }
+    /// Receives a packet into `buf`, returning its length and the buffer.
+    ///
+    /// The packet is written from the start of `buf`; pass a slice to read into a region of it.
+    /// The hooks run as on [`SyncDevice::recv`]: a packet answered by the ICMP responder or
+    /// dropped by the middleware is not returned, and the next one is read instead.
+    pub async fn recv<T: BoundedBufMut>(&self, mut buf: T) -> BufResult<usize, T> {
+        loop {
+            let (res, b) = self.file.read_at(buf, 0).await;
+            buf = b;
+            let len = match res {
+                Ok(len) => len,
+                Err(e) => return (Err(e), buf),
+            };
+            // SAFETY: the first `bytes_init` bytes of `buf`, which hold the packet, are
+            // initialized, and `buf` is not otherwise accessed while `packet` is live.
+            let packet =
+                unsafe { std::slice::from_raw_parts_mut(buf.stable_mut_ptr(), buf.bytes_init()) };
+            if let Some(len) = self.device.inbound(packet, len) {
+                return (Ok(len), buf);
+            }
+        }
+    }
+    /// Sends the initialized bytes of `buf` as one packet, returning the written length and
+    /// the buffer.
+    ///
+    /// The hooks run as on [`SyncDevice::send`]; a packet rewritten by them is written from a
+    /// separate buffer, and `buf` is handed back unchanged.
+    pub async fn send<T: BoundedBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let len = buf.bytes_init();
+        // SAFETY: `bytes_init` bytes starting at `stable_ptr` are initialized.
+        let packet = unsafe { std::slice::from_raw_parts(buf.stable_ptr(), len) };
+        let Some(out) = self.device.middleware_outbound(packet) else {
+            return (Ok(len), buf);
+        };
+        let packet_len = out.len();
+        let rewritten = match out {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(packet) => Some(packet),
+        };
+        let validated = match self
+            .device
+            .validate_outbound(rewritten.as_deref().unwrap_or(packet))
+        {
+            Ok(Cow::Borrowed(_)) => None,
+            Ok(Cow::Owned(frame)) => Some(frame),
+            Err(e) => return (Err(e), buf),
+        };
+        let frame = validated.or(rewritten);
+        if let Some(delay) = self
+            .device
+            .egress_limit
+            .reserve(frame.as_ref().map_or(len, Vec::len))
+        {
+            tokio::time::sleep(delay).await;
+        }
+        let (res, buf) = match frame {
+            None => {
+                let (res, buf) = self.file.write_at(buf, 0).submit().await;
+                // SAFETY: as above, for the buffer handed back by the write.
+                let packet = unsafe { std::slice::from_raw_parts(buf.stable_ptr(), len) };
+                (self.device.finish_send(packet, res), buf)
+            }
+            Some(frame) => {
+                let (res, frame) = self.file.write_at(frame, 0).submit().await;
+                (self.device.finish_send(&frame, res), buf)
+            }
+        };
+        (res.map(|n| n.min(packet_len)), buf)
+    }
+    /// Returns a reference to the wrapped device.
+    pub fn get_ref(&self) -> &SyncDevice {
+        &self.device
+    }
+    /// Closes the io_uring descriptor and returns the wrapped device.
+    pub async fn into_inner(self) -> io::Result<SyncDevice> {
+        self.file.close().await?;
+        Ok(self.device)
+    }
+}
+
+impl Deref for UringDevice {
+    type Target = DeviceImpl;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device.0
+    }
+}
@@ -0,0 +1,670 @@
+/*!
+# vhost-net Acceleration
+
+[`VhostNet`] attaches `/dev/vhost-net` to a TUN/TAP device, so the packets of the device are
+moved by a kernel worker thread between the device and two virtio rings in the memory of the
+process, the way the packets of a virtual machine are. The application exchanges packets
+with the rings instead of reading and writing the device file descriptor, without a system
+call per packet: sending only wakes up the worker if it is idle, and receiving only waits on
+an eventfd when the receive ring is empty.
+
+The rings and packet buffers live in one anonymous mapping owned by [`VhostNet`]. The vhost
+worker adds and strips the virtio-net header itself, so the device must be built without
+`offload`. This needs access to `/dev/vhost-net` (usually the `kvm` group or root) and the
+`vhost_net` kernel module.
+
+While a [`VhostNet`] exists, the worker competes with reads of the device file descriptor,
+so the device should only be used for configuration.
+
+# Example
+
+```no_run
+use tun_rs::vhost_net::VhostNetBuilder;
+use tun_rs::{DeviceBuilder, Layer};
+
+let dev = DeviceBuilder::new()
+    .layer(Layer::L2)
+    .ipv4("10.0.0.1", 24, None)
+    .build_sync()?;
+let mut vhost = VhostNetBuilder::new().queue_size(256).build(&dev)?;
+let mut bufs = vec![vec![0u8; 2048]; 64];
+let mut sizes = vec![0; 64];
+loop {
+    let n = vhost.recv_batch(&mut bufs, &mut sizes)?;
+    let frames: Vec<&[u8]> = (0..n).map(|i| &bufs[i][..sizes[i]]).collect();
+    // ... handle the frames, here they are reflected
+    vhost.send_batch(&frames)?;
+}
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::platform::DeviceImpl;
+use std::io;
+use std::mem::size_of;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{fence, AtomicU16, Ordering};
+
+const VHOST_VIRTIO: u8 = 0xAF;
+/// Makes vhost add and strip the virtio-net header instead of the device.
+const VHOST_NET_F_VIRTIO_NET_HDR: u64 = 1 << 27;
+/// Size of `struct virtio_net_hdr` without mergeable receive buffers.
+const VIRTIO_NET_HDR_LEN: usize = 10;
+const VRING_DESC_F_WRITE: u16 = 2;
+const VRING_USED_F_NO_NOTIFY: u16 = 1;
+const RX_QUEUE: u32 = 0;
+const TX_QUEUE: u32 = 1;
+const MAX_QUEUE_SIZE: u16 = 32768;
+
+#[repr(C)]
+struct VhostVringState {
+    index: u32,
+    num: u32,
+}
+
+#[repr(C)]
+struct VhostVringFile {
+    index: u32,
+    fd: i32,
+}
+
+#[repr(C)]
+struct VhostVringAddr {
+    index: u32,
+    flags: u32,
+    desc_user_addr: u64,
+    used_user_addr: u64,
+    avail_user_addr: u64,
+    log_guest_addr: u64,
+}
+
+/// `struct vhost_memory` with a single region.
+#[repr(C)]
+struct VhostMemory {
+    nregions: u32,
+    padding: u32,
+    guest_phys_addr: u64,
+    memory_size: u64,
+    userspace_addr: u64,
+    flags_padding: u64,
+}
+
+nix::ioctl_read!(vhost_get_features, VHOST_VIRTIO, 0x00, u64);
+nix::ioctl_write_ptr!(vhost_set_features, VHOST_VIRTIO, 0x00, u64);
+nix::ioctl_none!(vhost_set_owner, VHOST_VIRTIO, 0x01);
+// The request number encodes the size of the header, without the regions.
+nix::ioctl_write_ptr_bad!(
+    vhost_set_mem_table,
+    nix::request_code_write!(VHOST_VIRTIO, 0x03, 8),
+    VhostMemory
+);
+nix::ioctl_write_ptr!(vhost_set_vring_num, VHOST_VIRTIO, 0x10, VhostVringState);
+nix::ioctl_write_ptr!(vhost_set_vring_addr, VHOST_VIRTIO, 0x11, VhostVringAddr);
+nix::ioctl_write_ptr!(vhost_set_vring_base, VHOST_VIRTIO, 0x12, VhostVringState);
+nix::ioctl_write_ptr!(vhost_set_vring_kick, VHOST_VIRTIO, 0x20, VhostVringFile);
+nix::ioctl_write_ptr!(vhost_set_vring_call, VHOST_VIRTIO, 0x21, VhostVringFile);
+nix::ioctl_write_ptr!(vhost_net_set_backend, VHOST_VIRTIO, 0x30, VhostVringFile);
+
+/// Builder of a [`VhostNet`].
+#[derive(Clone, Debug)]
+pub struct VhostNetBuilder {
+    queue_size: u16,
+    buffer_size: usize,
+}
+
+impl Default for VhostNetBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VhostNetBuilder {
+    /// Creates a builder with the default sizes.
+    pub fn new() -> Self {
+        Self {
+            queue_size: 256,
+            buffer_size: 2048,
+        }
+    }
+    /// Sets the number of entries of the receive and the send ring, a power of two up to
+    /// 32768; defaults to 256.
+    pub fn queue_size(mut self, queue_size: u16) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+    /// Sets the size of a packet buffer; defaults to 2048. Received packets larger than a
+    /// buffer are dropped by the kernel, and larger packets cannot be sent.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+    /// Opens `/dev/vhost-net` and makes it the packet path of `device`.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the device was built with `offload`.
+    pub fn build(self, device: &DeviceImpl) -> io::Result<VhostNet> {
+        VhostNet::new(self, device)
+    }
+}
+
+struct Mmap {
+    addr: *mut u8,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(len: usize) -> io::Result<Self> {
+        // SAFETY: a new mapping is created; no existing memory is affected.
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_POPULATE,
+                -1,
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            addr: addr.cast(),
+            len,
+        })
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `Mmap::new` and is not referenced any more.
+        unsafe { libc::munmap(self.addr.cast(), self.len) };
+    }
+}
+
+/// A split virtqueue: the descriptor table, the available ring written by the application,
+/// the used ring written by vhost and one buffer per descriptor.
+///
+/// The rings use native byte order, as vhost does without `VIRTIO_F_VERSION_1`.
+struct Virtqueue {
+    desc: *mut u8,
+    avail: *mut u8,
+    used: *mut u8,
+    buffers: *mut u8,
+    size: u16,
+    buffer_size: usize,
+    /// The next entry of the used ring to consume.
+    last_used: u16,
+    /// The next entry of the available ring to produce.
+    next_avail: u16,
+    kick: OwnedFd,
+    call: OwnedFd,
+}
+
+impl Virtqueue {
+    /// The number of bytes the queue takes of the mapping.
+    fn len(size: u16, buffer_size: usize) -> usize {
+        let (_, _, buffers) = Self::offsets(size);
+        (buffers + size as usize * buffer_size).next_multiple_of(4096)
+    }
+    /// The offsets of the available ring, the used ring and the buffers from the
+    /// descriptor table.
+    fn offsets(size: u16) -> (usize, usize, usize) {
+        let n = size as usize;
+        let avail = 16 * n;
+        let used = (avail + 6 + 2 * n).next_multiple_of(4);
+        let buffers = (used + 6 + 8 * n).next_multiple_of(64);
+        (avail, used, buffers)
+    }
+    /// # Safety
+    ///
+    /// `base` must point to [`Virtqueue::len`] bytes of zeroed memory that outlive the queue.
+    unsafe fn new(base: *mut u8, size: u16, buffer_size: usize) -> io::Result<Self> {
+        let (avail, used, buffers) = Self::offsets(size);
+        Ok(Self {
+            desc: base,
+            avail: base.add(avail),
+            used: base.add(used),
+            buffers: base.add(buffers),
+            size,
+            buffer_size,
+            last_used: 0,
+            next_avail: 0,
+            kick: eventfd()?,
+            call: eventfd()?,
+        })
+    }
+    fn buffer(&self, id: u16) -> *mut u8 {
+        // SAFETY: descriptor ids are below the queue size, each with a buffer.
+        unsafe { self.buffers.add(id as usize * self.buffer_size) }
+    }
+    /// Points descriptor `id` at `len` bytes of its buffer.
+    fn set_desc(&self, id: u16, len: usize, flags: u16) {
+        // SAFETY: descriptors are 16 bytes: addr u64, len u32, flags u16, next u16.
+        unsafe {
+            let desc = self.desc.add(16 * id as usize);
+            desc.cast::<u64>().write(self.buffer(id) as u64);
+            desc.add(8).cast::<u32>().write(len as u32);
+            desc.add(12).cast::<u16>().write(flags);
+            desc.add(14).cast::<u16>().write(0);
+        }
+    }
+    fn avail_idx(&self) -> &AtomicU16 {
+        // SAFETY: the index follows the flags of the available ring, 2-byte aligned.
+        unsafe { &*self.avail.add(2).cast::<AtomicU16>() }
+    }
+    fn used_flags(&self) -> &AtomicU16 {
+        // SAFETY: the flags start the used ring, 4-byte aligned.
+        unsafe { &*self.used.cast::<AtomicU16>() }
+    }
+    fn used_idx(&self) -> &AtomicU16 {
+        // SAFETY: the index follows the flags of the used ring.
+        unsafe { &*self.used.add(2).cast::<AtomicU16>() }
+    }
+    /// The number of entries vhost has put into the used ring that were not consumed yet.
+    fn pending(&self) -> u16 {
+        self.used_idx()
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.last_used)
+    }
+    /// Takes the next entry of the used ring: a descriptor id and the bytes written to it.
+    fn pop_used(&mut self) -> (u16, usize) {
+        let slot = (self.last_used % self.size) as usize;
+        self.last_used = self.last_used.wrapping_add(1);
+        // SAFETY: used elements are 8 bytes: id u32, len u32, after flags and index.
+        unsafe {
+            let elem = self.used.add(4 + 8 * slot);
+            let id = elem.cast::<u32>().read();
+            let len = elem.add(4).cast::<u32>().read();
+            (id as u16, len as usize)
+        }
+    }
+    /// Puts descriptor `id` into the available ring, visible to vhost after `publish`.
+    fn push_avail(&mut self, id: u16) {
+        let slot = (self.next_avail % self.size) as usize;
+        self.next_avail = self.next_avail.wrapping_add(1);
+        // SAFETY: the ring entries follow the flags and index of the available ring.
+        unsafe { self.avail.add(4 + 2 * slot).cast::<u16>().write(id) };
+    }
+    /// Publishes the entries pushed so far and wakes up vhost unless it is polling the ring.
+    fn publish(&self) -> io::Result<()> {
+        self.avail_idx().store(self.next_avail, Ordering::Release);
+        // The flags must be read after the index is visible, or a worker going idle in
+        // between is never woken up.
+        fence(Ordering::SeqCst);
+        if self.used_flags().load(Ordering::Acquire) & VRING_USED_F_NO_NOTIFY != 0 {
+            return Ok(());
+        }
+        let value = 1u64;
+        // SAFETY: an eventfd is written 8 bytes at a time.
+        let res = unsafe {
+            libc::write(
+                self.kick.as_raw_fd(),
+                (&value as *const u64).cast(),
+                size_of::<u64>(),
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    /// Waits until vhost signals the call eventfd and resets it.
+    fn wait(&self) -> io::Result<()> {
+        let mut fds = libc::pollfd {
+            fd: self.call.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fds` is a valid pollfd.
+        if unsafe { libc::poll(&mut fds, 1, -1) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+        let mut value = 0u64;
+        // SAFETY: an eventfd is read 8 bytes at a time; it is nonblocking.
+        unsafe {
+            libc::read(
+                self.call.as_raw_fd(),
+                (&mut value as *mut u64).cast(),
+                size_of::<u64>(),
+            )
+        };
+        Ok(())
+    }
+    /// Registers the queue with vhost as queue `index`.
+    fn register(&self, vhost: RawFd, index: u32) -> io::Result<()> {
+        let state = VhostVringState {
+            index,
+            num: self.size as u32,
+        };
+        let base = VhostVringState { index, num: 0 };
+        let addr = VhostVringAddr {
+            index,
+            flags: 0,
+            desc_user_addr: self.desc as u64,
+            used_user_addr: self.used as u64,
+            avail_user_addr: self.avail as u64,
+            log_guest_addr: 0,
+        };
+        let kick = VhostVringFile {
+            index,
+            fd: self.kick.as_raw_fd(),
+        };
+        let call = VhostVringFile {
+            index,
+            fd: self.call.as_raw_fd(),
+        };
+        // SAFETY: all arguments are valid structs of the sizes the requests encode.
+        unsafe {
+            vhost_set_vring_num(vhost, &state)?;
+            vhost_set_vring_base(vhost, &base)?;
+            vhost_set_vring_addr(vhost, &addr)?;
+            vhost_set_vring_kick(vhost, &kick)?;
+            vhost_set_vring_call(vhost, &call)?;
+        }
+        Ok(())
+    }
+}
+
+/// The virtio rings of a TUN/TAP device served by the vhost-net kernel worker.
+///
+/// Created with [`VhostNetBuilder`]. Dropping it detaches vhost from the device.
+pub struct VhostNet {
+    // Closed first, which stops the worker before the rings are unmapped.
+    fd: OwnedFd,
+    rx: Virtqueue,
+    tx: Virtqueue,
+    /// Descriptors of the send queue not handed to vhost.
+    free_tx: Vec<u16>,
+    nonblocking: bool,
+    _mem: Mmap,
+}
+
+// SAFETY: the raw pointers refer to the mapping owned by the value, which is only accessed
+// through `&mut self`.
+unsafe impl Send for VhostNet {}
+
+impl VhostNet {
+    fn new(builder: VhostNetBuilder, device: &DeviceImpl) -> io::Result<Self> {
+        let VhostNetBuilder {
+            queue_size,
+            buffer_size,
+        } = builder;
+        if !queue_size.is_power_of_two() || queue_size > MAX_QUEUE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "queue size must be a power of two up to 32768",
+            ));
+        }
+        if buffer_size == 0 || buffer_size > u32::MAX as usize - VIRTIO_NET_HDR_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid buffer size",
+            ));
+        }
+        if device.vnet_hdr {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "vhost-net cannot serve a device built with offload",
+            ));
+        }
+        // SAFETY: the path is a valid C string.
+        let fd = unsafe { libc::open(c"/dev/vhost-net".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just opened and is owned by nothing else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let vhost = fd.as_raw_fd();
+        // SAFETY: the requests take no argument or a u64.
+        unsafe {
+            vhost_set_owner(vhost)?;
+            let mut features = 0u64;
+            vhost_get_features(vhost, &mut features)?;
+            if features & VHOST_NET_F_VIRTIO_NET_HDR == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "vhost-net does not handle the virtio-net header",
+                ));
+            }
+            vhost_set_features(vhost, &VHOST_NET_F_VIRTIO_NET_HDR)?;
+        }
+
+        let stride = (VIRTIO_NET_HDR_LEN + buffer_size).next_multiple_of(16);
+        let queue_len = Virtqueue::len(queue_size, stride);
+        let mem = Mmap::new(2 * queue_len)?;
+        // Addresses in the rings are the addresses of the process.
+        let table = VhostMemory {
+            nregions: 1,
+            padding: 0,
+            guest_phys_addr: mem.addr as u64,
+            memory_size: mem.len as u64,
+            userspace_addr: mem.addr as u64,
+            flags_padding: 0,
+        };
+        // SAFETY: `table` is a memory table with one region.
+        unsafe { vhost_set_mem_table(vhost, &table)? };
+        // SAFETY: the mapping holds two zeroed queues and is owned by the result.
+        let (mut rx, tx) = unsafe {
+            (
+                Virtqueue::new(mem.addr, queue_size, stride)?,
+                Virtqueue::new(mem.addr.add(queue_len), queue_size, stride)?,
+            )
+        };
+        rx.register(vhost, RX_QUEUE)?;
+        tx.register(vhost, TX_QUEUE)?;
+        for id in 0..queue_size {
+            rx.set_desc(id, stride, VRING_DESC_F_WRITE);
+            rx.push_avail(id);
+        }
+        let backend = device.as_raw_fd();
+        for index in [RX_QUEUE, TX_QUEUE] {
+            // SAFETY: the argument is a valid `vhost_vring_file`.
+            unsafe { vhost_net_set_backend(vhost, &VhostVringFile { index, fd: backend })? };
+        }
+        rx.publish()?;
+        Ok(Self {
+            fd,
+            rx,
+            tx,
+            free_tx: (0..queue_size).rev().collect(),
+            nonblocking: false,
+            _mem: mem,
+        })
+    }
+    /// Sets whether `recv_batch`, `recv_with` and `send_batch` fail with
+    /// [`io::ErrorKind::WouldBlock`] instead of waiting. Defaults to `false`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+    /// Receives up to `max` packets, passing each as a slice of its ring buffer to `f`
+    /// without copying it, and returns their number.
+    ///
+    /// Waits until at least one packet is available unless nonblocking.
+    pub fn recv_with<F: FnMut(&[u8])>(&mut self, max: usize, mut f: F) -> io::Result<usize> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = loop {
+            let n = (self.rx.pending() as usize).min(max);
+            if n > 0 {
+                break n;
+            }
+            if self.nonblocking {
+                return Err(io::Error::from(io::ErrorKind::WouldBlock));
+            }
+            self.rx.wait()?;
+        };
+        for _ in 0..n {
+            let (id, len) = self.rx.pop_used();
+            let len = len.clamp(VIRTIO_NET_HDR_LEN, self.rx.buffer_size);
+            // SAFETY: vhost wrote `len` bytes to the buffer, which is not handed back
+            // before the callback returns.
+            let packet = unsafe {
+                std::slice::from_raw_parts(
+                    self.rx.buffer(id).add(VIRTIO_NET_HDR_LEN),
+                    len - VIRTIO_NET_HDR_LEN,
+                )
+            };
+            f(packet);
+            self.rx.push_avail(id);
+        }
+        self.rx.publish()?;
+        Ok(n)
+    }
+    /// Receives up to `bufs.len()` packets, copying packet `i` into `bufs[i]` and its length
+    /// into `sizes[i]`, and returns their number.
+    ///
+    /// Packets larger than their buffer are truncated. Waits until at least one packet is
+    /// available unless nonblocking.
+    pub fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        let max = bufs.len().min(sizes.len());
+        let mut i = 0;
+        self.recv_with(max, |packet| {
+            let buf = bufs[i].as_mut();
+            let len = packet.len().min(buf.len());
+            buf[..len].copy_from_slice(&packet[..len]);
+            sizes[i] = len;
+            i += 1;
+        })
+    }
+    /// Sends as many of `packets` as there are free descriptors and returns their number.
+    ///
+    /// Waits until at least one descriptor is free unless nonblocking. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if a packet does not fit into a buffer.
+    pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        let max_len = self.tx.buffer_size - VIRTIO_NET_HDR_LEN;
+        if packets.iter().any(|packet| packet.as_ref().len() > max_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet larger than the buffer size",
+            ));
+        }
+        if packets.is_empty() {
+            return Ok(0);
+        }
+        let n = loop {
+            self.reclaim_tx();
+            let n = packets.len().min(self.free_tx.len());
+            if n > 0 {
+                break n;
+            }
+            if self.nonblocking {
+                return Err(io::Error::from(io::ErrorKind::WouldBlock));
+            }
+            self.tx.wait()?;
+        };
+        for packet in &packets[..n] {
+            let packet = packet.as_ref();
+            let id = self.free_tx.pop().expect("counted above");
+            let buf = self.tx.buffer(id);
+            // SAFETY: the descriptor is not owned by vhost and the packet fits after the
+            // header, which is all zero: no checksum or segmentation offload.
+            unsafe {
+                ptr::write_bytes(buf, 0, VIRTIO_NET_HDR_LEN);
+                ptr::copy_nonoverlapping(
+                    packet.as_ptr(),
+                    buf.add(VIRTIO_NET_HDR_LEN),
+                    packet.len(),
+                );
+            }
+            self.tx.set_desc(id, VIRTIO_NET_HDR_LEN + packet.len(), 0);
+            self.tx.push_avail(id);
+        }
+        self.tx.publish()?;
+        Ok(n)
+    }
+    fn reclaim_tx(&mut self) {
+        for _ in 0..self.tx.pending() {
+            let (id, _) = self.tx.pop_used();
+            self.free_tx.push(id);
+        }
+    }
+}
+
+impl AsRawFd for VhostNet {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for VhostNet {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+fn eventfd() -> io::Result<OwnedFd> {
+    // SAFETY: creates a new file descriptor.
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just created and is owned by nothing else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtqueue_layout() {
+        for size in [1, 64, 256, 1024] {
+            let n = size as usize;
+            let (avail, used, buffers) = Virtqueue::offsets(size);
+            assert_eq!(avail, 16 * n);
+            assert_eq!(used % 4, 0);
+            assert!(used >= avail + 6 + 2 * n);
+            assert_eq!(buffers % 64, 0);
+            assert!(buffers >= used + 6 + 8 * n);
+            let len = Virtqueue::len(size, 2048);
+            assert_eq!(len % 4096, 0);
+            assert!(len >= buffers + n * 2048);
+        }
+        assert_eq!(Virtqueue::offsets(256), (4096, 4616, 6720));
+        assert_eq!(Virtqueue::len(256, 2048), 532480);
+    }
+
+    #[test]
+    fn virtqueue_ring_wraparound() {
+        const SIZE: u16 = 4;
+        let mut mem = vec![0u64; Virtqueue::len(SIZE, 16) / 8];
+        // SAFETY: `mem` is zeroed, large enough and outlives the queue.
+        let mut queue = unsafe { Virtqueue::new(mem.as_mut_ptr().cast(), SIZE, 16) }.unwrap();
+        queue.next_avail = u16::MAX - 1;
+        queue.last_used = u16::MAX - 1;
+
+        for id in 0..SIZE {
+            queue.push_avail(id);
+        }
+        assert_eq!(queue.next_avail, 2);
+        // SAFETY: the ring entries follow the flags and index of the available ring.
+        let ring = |slot: usize| unsafe { queue.avail.add(4 + 2 * slot).cast::<u16>().read() };
+        assert_eq!([ring(2), ring(3), ring(0), ring(1)], [0, 1, 2, 3]);
+
+        for (i, id) in (0..SIZE).enumerate() {
+            let slot = (u16::MAX - 1).wrapping_add(i as u16) % SIZE;
+            // SAFETY: used elements are 8 bytes: id u32, len u32, after flags and index.
+            unsafe {
+                let elem = queue.used.add(4 + 8 * slot as usize);
+                elem.cast::<u32>().write(id as u32);
+                elem.add(4).cast::<u32>().write(100 + id as u32);
+            }
+        }
+        queue.used_idx().store(2, Ordering::Release);
+        assert_eq!(queue.pending(), SIZE);
+        for id in 0..SIZE {
+            assert_eq!(queue.pop_used(), (id, 100 + id as usize));
+        }
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(queue.last_used, 2);
+    }
+}
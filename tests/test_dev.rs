@@ -403,6 +403,30 @@ fn test_op() {
     assert!(device.is_running().unwrap());
 }
 
+#[cfg(any(
+    target_os = "windows",
+    target_os = "macos",
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+))]
+#[test]
+fn test_ipv4_tuple() {
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.4.100", 24, None)
+        .ipv4_tuple(&[("10.26.5.100", 24), ("10.26.6.100", 24)])
+        .build_sync()
+        .unwrap();
+    let vec = device.addresses().unwrap();
+    for ip in ["10.26.4.100", "10.26.5.100", "10.26.6.100"] {
+        assert!(
+            vec.contains(&ip.parse::<std::net::IpAddr>().unwrap()),
+            "{ip} not found in {vec:?}"
+        );
+    }
+}
+
 #[cfg(any(
     target_os = "windows",
     target_os = "macos",
@@ -421,6 +445,15 @@ fn create_tun() {
     let device = DeviceBuilder::new().name(name).build_sync().unwrap();
     let dev_name = device.name().unwrap();
     assert_eq!(dev_name.as_str(), name);
+    #[cfg(target_os = "windows")]
+    let backend = tun_rs::BackendKind::Wintun;
+    #[cfg(target_os = "macos")]
+    let backend = tun_rs::BackendKind::Utun;
+    #[cfg(target_os = "linux")]
+    let backend = tun_rs::BackendKind::LinuxTun;
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    let backend = tun_rs::BackendKind::BsdTun;
+    assert_eq!(device.backend_kind(), backend);
     #[cfg(unix)]
     {
         use std::os::fd::IntoRawFd;
@@ -455,6 +488,15 @@ fn create_tap() {
         .unwrap();
     let dev_name = device.name().unwrap();
     assert_eq!(dev_name.as_str(), name);
+    #[cfg(target_os = "windows")]
+    let backend = tun_rs::BackendKind::TapWindows;
+    #[cfg(target_os = "macos")]
+    let backend = tun_rs::BackendKind::Feth;
+    #[cfg(target_os = "linux")]
+    let backend = tun_rs::BackendKind::LinuxTun;
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    let backend = tun_rs::BackendKind::BsdTap;
+    assert_eq!(device.backend_kind(), backend);
     #[cfg(all(unix, not(target_os = "macos")))]
     {
         use std::os::fd::IntoRawFd;
@@ -488,6 +530,14 @@ fn test_windows_new_apis() {
     let luid_value = unsafe { luid.Value };
     assert_ne!(luid_value, 0, "LUID must be non-zero for a live adapter");
 
+    // ── 1b. compat_win7 ──────────────────────────────────────────────────────
+    // The Windows 7 compatibility mode follows the OS version unless forced.
+    let caps = device
+        .capabilities()
+        .expect("capabilities() should succeed");
+    assert_eq!(caps.compat_win7, device.compat_win7());
+    assert_eq!(caps.adapter_description, !caps.compat_win7);
+
     // ── 2. set_metric() ──────────────────────────────────────────────────────
     // Now uses GetIpInterfaceEntry / SetIpInterfaceEntry for both AF_INET and
     // AF_INET6.  No public read-back getter exists, so we assert the call
@@ -742,3 +792,2348 @@ fn test_offload_mask_cleared_on_reattach_without_offload() {
          (device-wide TUN_F_CSUM not cleared)"
     );
 }
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_attach_existing() {
+    use std::process::Command;
+
+    const NAME: &str = "tunattach";
+    let _ = Command::new("ip").args(["link", "delete", NAME]).status();
+
+    let err = DeviceBuilder::new()
+        .name(NAME)
+        .attach_existing(true)
+        .build_sync()
+        .err()
+        .expect("attach to a missing device must fail");
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    let dev1 = DeviceBuilder::new()
+        .name(NAME)
+        .ipv4("10.26.7.100", 24, None)
+        .build_sync()
+        .unwrap();
+    dev1.persist().unwrap();
+    drop(dev1);
+
+    // The address configured by the first process must survive the takeover.
+    let dev2 = DeviceBuilder::new()
+        .name(NAME)
+        .ipv4("10.26.8.100", 24, None)
+        .attach_existing(true)
+        .build_sync()
+        .unwrap();
+    let addresses = dev2.addresses().unwrap();
+    drop(dev2);
+    let _ = Command::new("ip").args(["link", "delete", NAME]).status();
+
+    assert!(addresses.contains(&"10.26.7.100".parse().unwrap()));
+    assert!(!addresses.contains(&"10.26.8.100".parse().unwrap()));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_cleanup_policy() {
+    use std::process::Command;
+    use tun_rs::CleanupPolicy;
+
+    const NAME: &str = "tuncleanup";
+    let _ = Command::new("ip").args(["link", "delete", NAME]).status();
+    let exists = || {
+        Command::new("ip")
+            .args(["link", "show", NAME])
+            .output()
+            .unwrap()
+            .status
+            .success()
+    };
+
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .ipv4("10.26.12.100", 24, None)
+        .cleanup_policy(CleanupPolicy {
+            remove_addresses: true,
+            remove_routes: true,
+            destroy_device: false,
+        })
+        .build_sync()
+        .unwrap();
+    let route = "10.26.13.0/24";
+    assert!(Command::new("ip")
+        .args(["route", "add", route, "dev", NAME])
+        .status()
+        .unwrap()
+        .success());
+    drop(dev);
+    assert!(exists());
+    let routes = Command::new("ip")
+        .args(["route", "show", "dev", NAME])
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&routes.stdout).contains("10.26.13.0"));
+
+    // Attached devices are kept by default; leaking keeps the device too.
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .attach_existing(true)
+        .build_sync()
+        .unwrap();
+    assert!(dev.addresses().unwrap().iter().all(|ip| ip.is_ipv6()));
+    assert_eq!(dev.cleanup_policy(), CleanupPolicy::NONE);
+    dev.set_cleanup_policy(CleanupPolicy::ALL).unwrap();
+    dev.leak().unwrap();
+    drop(dev);
+    assert!(exists());
+
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .attach_existing(true)
+        .cleanup_policy(CleanupPolicy::default())
+        .build_sync()
+        .unwrap();
+    drop(dev);
+    assert!(!exists());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_from_fd_checked() {
+    use std::os::fd::{AsRawFd, IntoRawFd};
+
+    let file = std::fs::File::open("/dev/null").unwrap();
+    let err = unsafe { SyncDevice::from_fd_checked(file.as_raw_fd()) }
+        .err()
+        .expect("/dev/null is not a tun fd");
+    assert!(matches!(
+        tun_rs::Error::from(err),
+        tun_rs::Error::InvalidFd { .. }
+    ));
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.9.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let name = dev.name().unwrap();
+    let fd = dev.into_raw_fd();
+    let dev = unsafe { SyncDevice::from_fd_checked(fd) }.unwrap();
+    assert_eq!(dev.name().unwrap(), name);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_napi() {
+    // Falls back gracefully when the kernel lacks IFF_NAPI/IFF_NAPI_FRAGS.
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.10.100", 24, None)
+        .napi(true)
+        .napi_frags(true)
+        .build_sync()
+        .unwrap();
+    assert!(dev.name().is_ok());
+    assert!(tun_rs::tun_features().is_ok());
+}
+
+#[cfg(any(
+    all(target_os = "linux", not(target_env = "ohos")),
+    target_os = "macos"
+))]
+#[test]
+fn test_bind_socket() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.11.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+    dev.bind_socket(&socket).unwrap();
+    socket.send_to(b"ping", "10.26.11.101:9").unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_ipv6_autoconf() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.12.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let name = dev.name().unwrap();
+    dev.set_ipv6_autoconf(false).unwrap();
+    dev.set_ipv6_forwarding(false).unwrap();
+    let conf = |key: &str| {
+        std::fs::read_to_string(format!("/proc/sys/net/ipv6/conf/{name}/{key}")).unwrap()
+    };
+    assert_eq!(conf("accept_ra").trim(), "0");
+    assert_eq!(conf("autoconf").trim(), "0");
+    assert_eq!(conf("forwarding").trim(), "0");
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_packet_information_toggle() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.13.100", 24, None)
+        .build_sync()
+        .unwrap();
+    assert!(!dev.packet_information());
+    dev.set_packet_information(false).unwrap();
+    let err = dev.set_packet_information(true).unwrap_err();
+    assert!(matches!(
+        tun_rs::Error::from(err),
+        tun_rs::Error::RequiresRebuild { .. }
+    ));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_attach_to_bridge() {
+    use std::process::Command;
+    const BRIDGE: &str = "tun-rs-br0";
+    let _ = Command::new("ip").args(["link", "delete", BRIDGE]).status();
+    let status = Command::new("ip")
+        .args(["link", "add", BRIDGE, "type", "bridge"])
+        .status();
+    if !status.is_ok_and(|s| s.success()) {
+        // bridge support unavailable
+        return;
+    }
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .build_sync()
+        .unwrap();
+    let name = dev.name().unwrap();
+    dev.attach_to_bridge(BRIDGE).unwrap();
+    let master = std::fs::read_link(format!("/sys/class/net/{name}/master")).unwrap();
+    assert!(master.ends_with(BRIDGE));
+    dev.detach_from_bridge(BRIDGE).unwrap();
+    assert!(std::fs::read_link(format!("/sys/class/net/{name}/master")).is_err());
+    let _ = Command::new("ip").args(["link", "delete", BRIDGE]).status();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_jumbo_mtu() {
+    let Err(err) = DeviceBuilder::new().mtu(32).build_sync() else {
+        panic!("mtu below the minimum was accepted");
+    };
+    assert!(matches!(
+        tun_rs::Error::from(err),
+        tun_rs::Error::MtuOutOfRange { mtu: 32, .. }
+    ));
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.15.100", 24, None)
+        .mtu(16000)
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.mtu().unwrap(), 16000);
+    assert_eq!(dev.mtu_limits(), (68, 65535));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "icmp_responder")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_icmp_responder() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.14.100", 24, None)
+        .build_sync()
+        .unwrap();
+    dev.set_icmp_responder(&["10.26.14.101/32".parse().unwrap()])
+        .unwrap();
+    assert_eq!(dev.icmp_responder().len(), 1);
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0; 65535];
+        loop {
+            let len = dev.recv(&mut buf).unwrap();
+            // Only echo requests to other destinations surface here.
+            if let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]) {
+                if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Icmp {
+                    _ = tx.send(ipv4.get_destination().octets());
+                }
+            }
+        }
+    });
+    let reply = ping([10, 26, 14, 101]).expect("no echo reply received");
+    // IPv4 header followed by the echo reply
+    assert_eq!(&reply[12..16], &[10, 26, 14, 101]);
+    assert_eq!(reply[20], 0);
+
+    assert!(ping([10, 26, 14, 102]).is_none());
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(2)).unwrap(),
+        [10, 26, 14, 102]
+    );
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "icmp_responder")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_icmp_responder_offload() {
+    use tun_rs::{IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.50.100", 24, None)
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    dev.set_icmp_responder(&["10.26.50.101/32".parse().unwrap()])
+        .unwrap();
+    std::thread::spawn(move || {
+        let mut original_buffer = vec![0; VIRTIO_NET_HDR_LEN + 65535];
+        let mut bufs = vec![vec![0u8; 1500]; IDEAL_BATCH_SIZE];
+        let mut sizes = vec![0; IDEAL_BATCH_SIZE];
+        loop {
+            let num = dev
+                .recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0)
+                .unwrap();
+            // Echo requests never surface here.
+            for i in 0..num {
+                if let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&bufs[i][..sizes[i]]) {
+                    assert_ne!(ipv4.get_next_level_protocol(), IpNextHeaderProtocols::Icmp);
+                }
+            }
+        }
+    });
+    let reply = ping([10, 26, 50, 101]).expect("no echo reply received");
+    assert_eq!(&reply[12..16], &[10, 26, 50, 101]);
+    assert_eq!(reply[20], 0);
+}
+
+/// Sends an echo request to `dst` through a raw ICMP socket and returns the reply, if one
+/// arrives within two seconds.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "icmp_responder")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+fn ping(dst: [u8; 4]) -> Option<[u8; 64]> {
+    let request = [8u8, 0, 0xf7, 0xfe, 0, 1, 0, 0];
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: 0,
+        sin_addr: libc::in_addr {
+            s_addr: u32::from_ne_bytes(dst),
+        },
+        sin_zero: [0; 8],
+    };
+    let mut reply = [0u8; 64];
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_RAW, libc::IPPROTO_ICMP);
+        assert!(fd >= 0);
+        let timeout = libc::timeval {
+            tv_sec: 2,
+            tv_usec: 0,
+        };
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const _ as *const libc::c_void,
+            size_of::<libc::timeval>() as _,
+        );
+        let n = libc::sendto(
+            fd,
+            request.as_ptr() as *const libc::c_void,
+            request.len(),
+            0,
+            &addr as *const _ as *const libc::sockaddr,
+            size_of::<libc::sockaddr_in>() as _,
+        );
+        assert_eq!(n, request.len() as isize);
+        let n = libc::recv(fd, reply.as_mut_ptr() as *mut libc::c_void, reply.len(), 0);
+        libc::close(fd);
+        if n != 28 {
+            return None;
+        }
+    }
+    Some(reply)
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "smoltcp")]
+#[test]
+fn test_smoltcp_device() {
+    use smoltcp::iface::{Config, Interface, SocketSet};
+    use smoltcp::phy::{Device, Medium};
+    use smoltcp::socket::tcp;
+    use smoltcp::time::Instant;
+    use smoltcp::wire::{HardwareAddress, IpAddress, IpCidr};
+    use tun_rs::smoltcp::SmolDevice;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.16.100", 24, None)
+        .mtu(1400)
+        .build_sync()
+        .unwrap();
+    let mut dev = SmolDevice::new(dev).unwrap();
+    assert_eq!(dev.medium(), Medium::Ip);
+    assert_eq!(dev.capabilities().max_transmission_unit, 1400);
+
+    let mut iface = Interface::new(Config::new(HardwareAddress::Ip), &mut dev, Instant::now());
+    iface.update_ip_addrs(|addrs| {
+        addrs
+            .push(IpCidr::new(IpAddress::v4(10, 26, 16, 101), 24))
+            .unwrap();
+    });
+    let mut sockets = SocketSet::new(vec![]);
+    let mut socket = tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; 4096]),
+        tcp::SocketBuffer::new(vec![0; 4096]),
+    );
+    socket.listen(7).unwrap();
+    let handle = sockets.add(socket);
+
+    // The kernel connects to the smoltcp stack behind the device.
+    let client = std::thread::spawn(|| {
+        let timeout = std::time::Duration::from_secs(5);
+        std::net::TcpStream::connect_timeout(&"10.26.16.101:7".parse().unwrap(), timeout)
+    });
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while sockets.get::<tcp::Socket>(handle).state() != tcp::State::Established {
+        assert!(std::time::Instant::now() < deadline, "no connection");
+        iface.poll(Instant::now(), &mut dev, &mut sockets);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    client.join().unwrap().unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "pcap")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_packet_tap() {
+    use std::sync::{Arc, Mutex};
+    use tun_rs::pcap::{Direction, LinkType};
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.17.100", 24, None)
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.pcap_link_type(), LinkType::Raw);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    dev.set_tap(Some(Arc::new(move |direction, packet: &[u8]| {
+        sink.lock().unwrap().push((direction, packet.len()));
+    })));
+    assert!(dev.has_tap());
+
+    let socket = std::net::UdpSocket::bind("10.26.17.100:0").unwrap();
+    socket.send_to(b"tap", "10.26.17.101:9").unwrap();
+    let mut buf = [0; 65535];
+    let len = loop {
+        let len = dev.recv(&mut buf).unwrap();
+        if let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]) {
+            if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp {
+                break len;
+            }
+        }
+    };
+    // Swapping the addresses keeps both checksums valid.
+    let (src, dst) = (buf[12..16].to_vec(), buf[16..20].to_vec());
+    buf[12..16].copy_from_slice(&dst);
+    buf[16..20].copy_from_slice(&src);
+    dev.send(&buf[..len]).unwrap();
+
+    dev.set_tap(None);
+    assert!(!dev.has_tap());
+    let seen = seen.lock().unwrap();
+    assert!(seen.contains(&(Direction::Inbound, len)));
+    assert_eq!(seen.last(), Some(&(Direction::Outbound, len)));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "pcap")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_packet_tap_packet_information() {
+    use std::sync::{Arc, Mutex};
+    use tun_rs::pcap::{Direction, LinkType};
+    use tun_rs::PACKET_INFORMATION_LENGTH as PI;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.54.100", 24, None)
+        .packet_information(true)
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.pcap_link_type(), LinkType::Raw);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    dev.set_tap(Some(Arc::new(move |direction, packet: &[u8]| {
+        sink.lock().unwrap().push((direction, packet.to_vec()));
+    })));
+
+    let socket = std::net::UdpSocket::bind("10.26.54.100:0").unwrap();
+    socket.send_to(b"tap", "10.26.54.101:9").unwrap();
+    let mut buf = [0; 65535];
+    let len = loop {
+        let len = dev.recv(&mut buf).unwrap();
+        if let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[PI..len]) {
+            if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp {
+                break len;
+            }
+        }
+    };
+    // Swapping the addresses keeps both checksums valid.
+    let (src, dst) = (
+        buf[PI + 12..PI + 16].to_vec(),
+        buf[PI + 16..PI + 20].to_vec(),
+    );
+    buf[PI + 12..PI + 16].copy_from_slice(&dst);
+    buf[PI + 16..PI + 20].copy_from_slice(&src);
+    dev.send(&buf[..len]).unwrap();
+
+    dev.set_tap(None);
+    // The tap sees the IP packets, without the packet information header.
+    let seen = seen.lock().unwrap();
+    assert!(seen
+        .iter()
+        .all(|(_, packet)| packet[0] >> 4 == 4 || packet[0] >> 4 == 6));
+    assert_eq!(
+        seen.last(),
+        Some(&(Direction::Outbound, buf[PI..len].to_vec()))
+    );
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "pcap")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_packet_tap_vectored() {
+    use std::io::{IoSlice, IoSliceMut};
+    use std::sync::{Arc, Mutex};
+    use tun_rs::pcap::Direction;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.51.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    dev.set_tap(Some(Arc::new(move |direction, packet: &[u8]| {
+        sink.lock().unwrap().push((direction, packet.to_vec()));
+    })));
+
+    let socket = std::net::UdpSocket::bind("10.26.51.100:0").unwrap();
+    socket.send_to(b"vectored", "10.26.51.101:9").unwrap();
+    let (mut head, mut tail) = ([0; 20], [0; 65535]);
+    let len = loop {
+        let mut bufs = [IoSliceMut::new(&mut head), IoSliceMut::new(&mut tail)];
+        let len = dev.recv_vectored(&mut bufs).unwrap();
+        if head[0] >> 4 == 4 && head[9] == 17 {
+            break len;
+        }
+    };
+    let mut packet = head.to_vec();
+    packet.extend_from_slice(&tail[..len - head.len()]);
+    assert!(seen
+        .lock()
+        .unwrap()
+        .contains(&(Direction::Inbound, packet.clone())));
+
+    // Swapping the addresses keeps both checksums valid.
+    let src: [u8; 4] = packet[12..16].try_into().unwrap();
+    packet.copy_within(16..20, 12);
+    packet[16..20].copy_from_slice(&src);
+    let (header, payload) = packet.split_at(20);
+    let sent = [IoSlice::new(header), IoSlice::new(payload)];
+    let reply = [header, payload].concat();
+    dev.send_vectored(&sent).unwrap();
+    assert_eq!(
+        seen.lock().unwrap().last(),
+        Some(&(Direction::Outbound, reply))
+    );
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_frame_validation() {
+    use tun_rs::ethernet::{FrameMeta, FrameValidation, ETHERTYPE_ARP};
+
+    let tun = DeviceBuilder::new().build_sync().unwrap();
+    let err = tun
+        .set_frame_validation(Some(FrameValidation::default()))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .build_sync()
+        .unwrap();
+    let arp = FrameMeta::new([0xff; 6], dev.mac_address().unwrap(), ETHERTYPE_ARP);
+    dev.set_frame_validation(Some(FrameValidation::default()))
+        .unwrap();
+    let err = dev.send_frame(&arp, &[0; 28]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    dev.set_frame_validation(Some(FrameValidation { pad: true }))
+        .unwrap();
+    assert_eq!(dev.send_frame(&arp, &[0; 28]).unwrap(), 42);
+    assert_eq!(dev.frame_validation(), Some(FrameValidation { pad: true }));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_macvtap() {
+    use std::process::Command;
+    use tun_rs::ethernet::{FrameMeta, ETHERTYPE_ARP};
+    const LOWER: &str = "tun-rs-veth0";
+    const NAME: &str = "tun-rs-mvt0";
+    let _ = Command::new("ip").args(["link", "delete", LOWER]).status();
+    let status = Command::new("ip")
+        .args([
+            "link",
+            "add",
+            LOWER,
+            "type",
+            "veth",
+            "peer",
+            "name",
+            "tun-rs-veth1",
+        ])
+        .status();
+    if !status.is_ok_and(|s| s.success()) {
+        // veth support unavailable
+        return;
+    }
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .macvtap(LOWER, tun_rs::MacvtapMode::Bridge)
+        .build_sync();
+    let dev = match dev {
+        Ok(dev) => dev,
+        Err(e) => {
+            // macvtap support unavailable
+            let _ = Command::new("ip").args(["link", "delete", LOWER]).status();
+            assert_eq!(e.raw_os_error(), Some(libc::EOPNOTSUPP), "{e}");
+            return;
+        }
+    };
+    assert_eq!(dev.name().unwrap(), NAME);
+    let lower = std::fs::read_link(format!("/sys/class/net/{NAME}/lower_{LOWER}"));
+    assert!(lower.is_ok());
+    let arp = FrameMeta::new([0xff; 6], dev.mac_address().unwrap(), ETHERTYPE_ARP);
+    assert_eq!(dev.send_frame(&arp, &[0; 46]).unwrap(), 60);
+    drop(dev);
+    assert!(!std::path::Path::new(&format!("/sys/class/net/{NAME}")).exists());
+    let _ = Command::new("ip").args(["link", "delete", LOWER]).status();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_capabilities() {
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .mtu(1400)
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    let caps = dev.capabilities().unwrap();
+    assert_eq!(caps.backend, tun_rs::BackendKind::LinuxTun);
+    assert_eq!(caps.layer, tun_rs::Layer::L2);
+    assert!(caps.vectored_io && caps.multi_queue);
+    assert!(!caps.tcp_gso && !caps.zero_copy);
+    assert!(!caps.compat_win7 && !caps.adapter_description);
+    assert_eq!(caps.max_packet_size, 1400 + 14);
+
+    let dev = DeviceBuilder::new().offload(true).build_sync().unwrap();
+    let caps = dev.capabilities().unwrap();
+    assert_eq!(caps.layer, tun_rs::Layer::L3);
+    assert!(caps.tcp_gso && caps.gro && !caps.multi_queue);
+    assert_eq!(caps.max_packet_size, tun_rs::VIRTIO_NET_HDR_LEN + 65535);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_tap_frames() {
+    use tun_rs::ethernet::{FrameMeta, ETHERTYPE_ARP, ETHERTYPE_IPV4};
+
+    let tun = DeviceBuilder::new().build_sync().unwrap();
+    assert!(tun.recv_frame(&mut [0; 1514]).is_err());
+
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .ipv4("10.26.18.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let mac = dev.mac_address().unwrap();
+    // Resolving the peer makes the kernel send an ARP request.
+    let socket = std::net::UdpSocket::bind("10.26.18.100:0").unwrap();
+    socket.send_to(b"frame", "10.26.18.101:9").unwrap();
+    let mut buf = [0; 1514];
+    let meta = loop {
+        let meta = dev.recv_frame(&mut buf).unwrap();
+        if meta.ethertype == ETHERTYPE_ARP {
+            break meta;
+        }
+    };
+    assert_eq!(meta.source, mac);
+    assert!(meta.is_multicast());
+    assert_eq!(meta.payload_range(), 14..meta.len);
+
+    let frame = FrameMeta::new(mac, [2, 0, 0, 0, 0, 1], ETHERTYPE_IPV4);
+    assert_eq!(dev.send_frame(&frame, &[0x45; 20]).unwrap(), 34);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_name_hint() {
+    let dev = DeviceBuilder::new()
+        .name_hint("hint vpn/0 with a long tail")
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.name().unwrap(), "hintvpn0withalo");
+    // Taken names move on to the next free unit.
+    let next = DeviceBuilder::new()
+        .name_hint("hintvpn0withalo")
+        .build_sync()
+        .unwrap();
+    assert_eq!(next.name().unwrap(), "hintvpn0withal0");
+    // An explicit name wins over the hint.
+    let named = DeviceBuilder::new()
+        .name("hintnamed0")
+        .name_hint("hintignored0")
+        .build_sync()
+        .unwrap();
+    assert_eq!(named.name().unwrap(), "hintnamed0");
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_send_as_outgoing() {
+    let tun = DeviceBuilder::new().build_sync().unwrap();
+    let err = tun.send_as_outgoing(&[0; 60]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .ipv4("10.26.30.100", 24, None)
+        .build_sync()
+        .unwrap();
+    // Local experimental ethertype, which the kernel leaves alone.
+    let mut frame = [0u8; 60];
+    frame[..6].copy_from_slice(&[2, 0, 0, 0, 0, 1]);
+    frame[6..12].copy_from_slice(&dev.mac_address().unwrap());
+    frame[12..14].copy_from_slice(&[0x88, 0xb5]);
+    frame[14..20].copy_from_slice(b"hairpn");
+    assert_eq!(dev.send_as_outgoing(&frame).unwrap(), 60);
+    let mut buf = [0; 1514];
+    loop {
+        let len = dev.recv(&mut buf).unwrap();
+        if buf[12..14] == [0x88, 0xb5] {
+            assert_eq!(&buf[..len], &frame[..]);
+            break;
+        }
+    }
+    assert_eq!(dev.send_as_incoming(&frame).unwrap(), 60);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "async_tokio")]
+#[tokio::test]
+async fn test_async_recv_or() {
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.41.100", 24, None)
+        .build_async()
+        .unwrap();
+    let mut buf = [0u8; 1500];
+    // Apart from router solicitations the device stays quiet, so a read times out soon.
+    let err = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Err(e) = device.recv_or(&mut buf, Duration::from_millis(100)).await {
+                return e;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    let socket = tokio::net::UdpSocket::bind("10.26.41.100:0").await.unwrap();
+    socket
+        .send_to(b"recv_or", "10.26.41.101:8080")
+        .await
+        .unwrap();
+    loop {
+        let len = device
+            .recv_or(&mut buf, Duration::from_secs(5))
+            .await
+            .unwrap();
+        if buf[..len].ends_with(b"recv_or") {
+            break;
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_drain() {
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.42.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.42.100:0").unwrap();
+    for _ in 0..5 {
+        socket.send_to(b"drain", "10.26.42.101:8080").unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(device.drain().unwrap() >= 5);
+    // The device is blocking, yet draining an empty queue returns right away.
+    device.drain().unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "async_tokio", feature = "interruptible"))]
+#[tokio::test]
+async fn test_async_recv_intr() {
+    use tun_rs::InterruptEvent;
+
+    let device = DeviceBuilder::new()
+        .ipv4("10.26.19.100", 24, None)
+        .build_async()
+        .unwrap();
+    let event = Arc::new(InterruptEvent::new().unwrap());
+    let trigger = event.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        trigger.trigger().unwrap();
+    });
+    let mut buf = [0; 1500];
+    // The device stays quiet apart from router solicitations, so keep reading until interrupted.
+    let err = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Err(e) = device.recv_intr(&mut buf, &event).await {
+                return e;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+    let err = device.readable_intr(&event).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+
+    event.reset().unwrap();
+    device.writable_intr(&event).await.unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_config_rollback() {
+    use tun_rs::ConfigStep;
+
+    let Err(err) = DeviceBuilder::new()
+        .ipv4("10.26.20.100", 24, None)
+        .ipv6("fd26:20::1", 64)
+        // Multicast addresses cannot be assigned to an interface.
+        .ipv6("ff02::26", 64)
+        .build_sync()
+    else {
+        panic!("assigning a multicast address must fail");
+    };
+    let tun_rs::Error::Config {
+        failed, applied, ..
+    } = tun_rs::Error::from(err)
+    else {
+        panic!("expected a configuration error");
+    };
+    assert_eq!(
+        failed,
+        ConfigStep::AddIpv6 {
+            address: "ff02::26".parse().unwrap(),
+            prefix: 64
+        }
+    );
+    assert_eq!(applied.len(), 2);
+    assert!(matches!(applied[0], ConfigStep::Ipv4 { .. }));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "af_xdp")]
+#[test]
+fn test_af_xdp_veth() {
+    use std::process::Command;
+    use tun_rs::af_xdp::{XdpMode, XdpSocketBuilder};
+    const VETH: &str = "tun-rs-xdp0";
+    const PEER: &str = "tun-rs-xdp1";
+    let _ = Command::new("ip").args(["link", "delete", VETH]).status();
+    let status = Command::new("ip")
+        .args(["link", "add", VETH, "type", "veth", "peer", "name", PEER])
+        .status();
+    if !status.is_ok_and(|s| s.success()) {
+        // veth support unavailable
+        return;
+    }
+    for args in [
+        &["addr", "add", "10.26.21.1/24", "dev", VETH][..],
+        &["link", "set", VETH, "up"],
+        &["link", "set", PEER, "up"],
+    ] {
+        assert!(Command::new("ip").args(args).status().unwrap().success());
+    }
+    let mut sock = XdpSocketBuilder::new(PEER)
+        .mode(XdpMode::Generic)
+        .frame_count(256)
+        .ring_size(64)
+        .build()
+        .unwrap();
+    sock.set_nonblocking(true);
+
+    // Resolving the neighbor sends an ARP request out of the veth, which XDP redirects.
+    let udp = std::net::UdpSocket::bind("10.26.21.1:0").unwrap();
+    let mut bufs = vec![vec![0u8; 2048]; 8];
+    let mut sizes = vec![0; 8];
+    let mut arp = None;
+    for _ in 0..50 {
+        let _ = udp.send_to(b"xdp", "10.26.21.2:9");
+        match sock.recv_batch(&mut bufs, &mut sizes) {
+            Ok(n) => {
+                arp = (0..n).find(|&i| sizes[i] >= 14 && bufs[i][12..14] == [0x08, 0x06]);
+                if arp.is_some() {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("{e}"),
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let arp = arp.expect("no ARP request received");
+    assert_eq!(sock.send_batch(&[&bufs[arp][..sizes[arp]]]).unwrap(), 1);
+    drop(sock);
+    let _ = Command::new("ip").args(["link", "delete", VETH]).status();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_route_guard() {
+    let server = "198.51.100.7".parse().unwrap();
+    let other = "203.0.113.9".parse().unwrap();
+    let Ok(uplink) = tun_rs::find_route(server) else {
+        // no default route
+        return;
+    };
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.22.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let if_index = dev.if_index().unwrap();
+    let guard = dev.route_guard(server, true).unwrap();
+    assert_eq!(guard.routes().len(), 3);
+    assert_eq!(guard.routes()[0].prefix, 32);
+    assert_eq!(tun_rs::find_route(server).unwrap(), uplink);
+    assert_eq!(tun_rs::find_route(other).unwrap().if_index, if_index);
+    guard.remove().unwrap();
+    assert_eq!(tun_rs::find_route(other).unwrap().if_index, uplink.if_index);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_multi_queue_device() {
+    use tun_rs::multi_queue::MultiQueueDevice;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.23.100", 24, None)
+        .multi_queue(true)
+        .build_sync()
+        .unwrap();
+    let group = MultiQueueDevice::new(dev, 4).unwrap();
+    assert_eq!(group.queue_count(), 4);
+
+    let socket = std::net::UdpSocket::bind("10.26.23.100:0").unwrap();
+    socket.send_to(b"queues", "10.26.23.101:9").unwrap();
+    let mut buf = [0; 65535];
+    let (len, queue) = loop {
+        let (len, queue) = group.recv(&mut buf).unwrap();
+        if let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]) {
+            if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp {
+                break (len, queue);
+            }
+        }
+    };
+    assert!(queue < 4);
+    let forward = group.queue_for(&buf[..len]);
+    // Swapping the addresses and the ports keeps both checksums valid.
+    let (src, dst) = (buf[12..16].to_vec(), buf[16..20].to_vec());
+    buf[12..16].copy_from_slice(&dst);
+    buf[16..20].copy_from_slice(&src);
+    let (sport, dport) = (buf[20..22].to_vec(), buf[22..24].to_vec());
+    buf[20..22].copy_from_slice(&dport);
+    buf[22..24].copy_from_slice(&sport);
+    assert_eq!(group.queue_for(&buf[..len]), forward);
+    assert_eq!(group.send(&buf[..len]).unwrap(), len);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "broker"))]
+#[test]
+fn test_broker() {
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("tun-rs-broker-{}.sock", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let broker = std::thread::spawn(move || {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            _ = tun_rs::broker::handle(&stream, |_, request| {
+                request.name.as_deref() == Some("tun-broker0")
+            });
+        }
+    });
+    let denied = DeviceBuilder::new()
+        .name("tun-other0")
+        .via_broker(&path)
+        .build_sync();
+    assert_eq!(
+        denied.err().map(|e| e.kind()),
+        Some(std::io::ErrorKind::PermissionDenied)
+    );
+    let dev = DeviceBuilder::new()
+        .name("tun-broker0")
+        .mtu(1400)
+        .ipv4("10.26.24.100", 24, None)
+        .via_broker(&path)
+        .build_sync()
+        .unwrap();
+    broker.join().unwrap();
+    _ = std::fs::remove_file(&path);
+    assert_eq!(dev.name().unwrap(), "tun-broker0");
+    assert_eq!(dev.mtu().unwrap(), 1400);
+    assert!(dev
+        .addresses()
+        .unwrap()
+        .contains(&"10.26.24.100".parse().unwrap()));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_builder_validate() {
+    use tun_rs::{BuilderIssue, Layer};
+
+    assert!(DeviceBuilder::new()
+        .name("tun-valid0")
+        .ipv4("10.26.25.100", 24, None)
+        .validate()
+        .is_empty());
+    let issues = DeviceBuilder::new()
+        .name("a-name-that-is-far-too-long")
+        .mtu(20)
+        .ipv4("10.26.25.100", 33, None)
+        .mac_addr([0x02, 0, 0, 0, 0, 1])
+        .layer(Layer::L3)
+        .validate();
+    assert!(matches!(
+        issues[0],
+        BuilderIssue::NameTooLong { max: 15, .. }
+    ));
+    assert!(issues.contains(&BuilderIssue::MtuOutOfRange {
+        mtu: 20,
+        min: 68,
+        max: 65535
+    }));
+    assert!(issues.contains(&BuilderIssue::MacAddrOnL3));
+    assert!(issues.iter().any(|issue| matches!(
+        issue,
+        BuilderIssue::InvalidAddress {
+            setting: "ipv4",
+            ..
+        }
+    )));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_oper_state() {
+    use tun_rs::OperState;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.26.100", 24, None)
+        .build_sync()
+        .unwrap();
+    // Link state changes are applied by the kernel asynchronously.
+    let wait_for = |expected: OperState| {
+        for _ in 0..50 {
+            if dev.oper_state().unwrap() == expected {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("the interface did not reach {expected:?}");
+    };
+    assert!(dev.is_enabled().unwrap());
+    dev.set_carrier(false).unwrap();
+    wait_for(OperState::LowerLayerDown);
+    dev.set_carrier(true).unwrap();
+    wait_for(OperState::Up);
+    dev.enabled(false).unwrap();
+    assert!(!dev.is_enabled().unwrap());
+    wait_for(OperState::Down);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_ipv6_dad_privacy() {
+    let dev = DeviceBuilder::new()
+        .ipv6("fd12:3456:789a:4242::1", 64)
+        .ipv6_dad(false)
+        .ipv6_privacy(true)
+        .build_sync()
+        .unwrap();
+    let conf = |key: &str| {
+        let path = format!("/proc/sys/net/ipv6/conf/{}/{key}", dev.name().unwrap());
+        std::fs::read_to_string(path).unwrap().trim().to_string()
+    };
+    assert_eq!(conf("accept_dad"), "0");
+    assert_eq!(conf("use_tempaddr"), "2");
+    dev.set_ipv6_dad(true).unwrap();
+    dev.set_ipv6_privacy(false).unwrap();
+    assert_eq!(conf("accept_dad"), "1");
+    assert_eq!(conf("use_tempaddr"), "0");
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_send_buffer_size() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.40.100", 24, None)
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.send_buffer_size().unwrap(), i32::MAX as usize);
+    dev.set_send_buffer_size(1 << 20).unwrap();
+    assert_eq!(dev.send_buffer_size().unwrap(), 1 << 20);
+    dev.set_send_buffer_size(usize::MAX).unwrap();
+    assert_eq!(dev.send_buffer_size().unwrap(), i32::MAX as usize);
+}
+
+#[cfg(target_os = "freebsd")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_packet_information_vectored() {
+    use std::io::IoSliceMut;
+    use tun_rs::framing::{AfHeader, IpVersion};
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.27.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.27.100:0").unwrap();
+    let recv_udp = |bufs: &mut [IoSliceMut<'_>]| loop {
+        socket.send_to(b"vectored", "10.26.27.101:8080").unwrap();
+        let len = dev.recv_vectored(bufs).unwrap();
+        if len > 0 {
+            return len;
+        }
+    };
+
+    // Without packet information the first non-empty slice starts with the IP header.
+    let (mut first, mut rest) = ([0u8; 1], [0u8; 1500]);
+    let len = recv_udp(&mut [
+        IoSliceMut::new(&mut []),
+        IoSliceMut::new(&mut first),
+        IoSliceMut::new(&mut rest),
+    ]);
+    assert_eq!(first[0] >> 4, 4);
+    assert_eq!(u16::from_be_bytes([rest[1], rest[2]]) as usize, len);
+
+    // With packet information the header comes first and is counted in the length.
+    dev.set_packet_information(true).unwrap();
+    let (mut head, mut packet) = ([0u8; 4], [0u8; 1500]);
+    let len = recv_udp(&mut [IoSliceMut::new(&mut head), IoSliceMut::new(&mut packet)]);
+    let header = AfHeader::parse(&head).unwrap();
+    assert_eq!(header.ip_version(), Some(IpVersion::V4));
+    assert_eq!(u16::from_be_bytes([packet[2], packet[3]]) as usize, len - 4);
+}
+
+#[cfg(target_os = "freebsd")]
+#[cfg(feature = "async_tokio")]
+#[tokio::test]
+async fn test_async_vectored() {
+    use std::io::IoSliceMut;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.28.100", 24, None)
+        .build_async()
+        .unwrap();
+    let socket = tokio::net::UdpSocket::bind("10.26.28.100:0").await.unwrap();
+    let (mut first, mut rest) = ([0u8; 20], [0u8; 1500]);
+    let len = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            socket
+                .send_to(b"vectored", "10.26.28.101:8080")
+                .await
+                .unwrap();
+            let bufs = &mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut rest)];
+            let len = dev.recv_vectored(bufs).await.unwrap();
+            if len > 0 {
+                return len;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(first[0] >> 4, 4);
+    assert_eq!(u16::from_be_bytes([first[2], first[3]]) as usize, len);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "async_tokio")]
+#[tokio::test]
+async fn test_async_from_fd_with_options() {
+    use std::os::fd::AsRawFd;
+    use tun_rs::{AsyncDevice, FdOptions};
+
+    let dev = DeviceBuilder::new().build_sync().unwrap();
+    let fd = dev.as_raw_fd();
+    let options = FdOptions {
+        set_nonblocking: false,
+        owned: false,
+    };
+    let result = unsafe { AsyncDevice::from_fd_with_options(fd, options) };
+    assert_eq!(
+        result.err().map(|err| err.kind()),
+        Some(std::io::ErrorKind::InvalidInput)
+    );
+    assert!(!dev.is_nonblocking().unwrap());
+
+    dev.set_nonblocking(true).unwrap();
+    let borrowed = unsafe { AsyncDevice::from_fd_with_options(fd, options) }.unwrap();
+    assert_eq!(borrowed.name().unwrap(), dev.name().unwrap());
+    drop(borrowed);
+    // Not closed by the borrowed device.
+    assert!(dev.is_nonblocking().unwrap());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "async_tokio")]
+#[tokio::test]
+async fn test_async_peek() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.29.100", 24, None)
+        .build_async()
+        .unwrap();
+    let socket = tokio::net::UdpSocket::bind("10.26.29.100:0").await.unwrap();
+    let mut header = [0u8; 20];
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            socket.send_to(b"peek", "10.26.29.101:8080").await.unwrap();
+            dev.peek(&mut header).await.unwrap();
+            if header[0] >> 4 == 4 && header[9] == 17 {
+                return;
+            }
+            // Not our datagram, consume it.
+            dev.recv(&mut [0u8; 1500]).await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    // Peeking again sees the same packet, and readiness reflects the pushback.
+    let mut again = [0u8; 20];
+    assert_eq!(dev.try_peek(&mut again).unwrap(), 20);
+    assert_eq!(again, header);
+    dev.readable().await.unwrap();
+
+    let mut buf = [0u8; 1500];
+    let len = dev.recv(&mut buf).await.unwrap();
+    assert_eq!(buf[..20], header);
+    assert_eq!(u16::from_be_bytes([buf[2], buf[3]]) as usize, len);
+    assert_eq!(&buf[len - 4..len], b"peek");
+
+    let offload = DeviceBuilder::new().offload(true).build_async().unwrap();
+    let err = offload.try_peek(&mut again).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "ndp_proxy")]
+#[test]
+fn test_ndp_proxy() {
+    let dev = DeviceBuilder::new()
+        .ipv6("fd00:26:16::1", 64)
+        .build_sync()
+        .unwrap();
+    let uplink = DeviceBuilder::new()
+        .ipv6("fd00:26:17::1", 64)
+        .build_sync()
+        .unwrap();
+    let uplink_name = uplink.name().unwrap();
+    let proxy_ndp = || {
+        std::fs::read_to_string(format!("/proc/sys/net/ipv6/conf/{uplink_name}/proxy_ndp")).unwrap()
+    };
+    let err = dev.ndp_proxy(&dev.name().unwrap(), &[]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(proxy_ndp().trim(), "0");
+
+    let first = "fd00:26:17::100".parse().unwrap();
+    let second = "fd00:26:17::101".parse().unwrap();
+    let mut proxy = dev.ndp_proxy(&uplink_name, &[first]).unwrap();
+    assert_eq!(proxy_ndp().trim(), "1");
+    proxy.add(second).unwrap();
+    proxy.add(second).unwrap();
+    assert_eq!(proxy.addresses(), &[first, second]);
+    proxy.remove(first).unwrap();
+    assert!(proxy.remove(first).is_err());
+    proxy.close().unwrap();
+    assert_eq!(proxy_ndp().trim(), "0");
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "async_tokio", feature = "async_framed"))]
+#[tokio::test]
+async fn test_framed_burst() {
+    use futures::{SinkExt, StreamExt};
+    use tun_rs::async_framed::{BurstCodec, DeviceFramed};
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.31.100", 24, None)
+        .build_async()
+        .unwrap();
+    let mut framed = DeviceFramed::new(dev, BurstCodec::with_max_burst(4));
+    let socket = tokio::net::UdpSocket::bind("10.26.31.100:0").await.unwrap();
+    for _ in 0..8 {
+        socket.send_to(b"burst", "10.26.31.101:8080").await.unwrap();
+    }
+    let burst = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut received = 0;
+        loop {
+            let burst = framed.next().await.unwrap().unwrap();
+            assert!(!burst.is_empty() && burst.len() <= 4);
+            received += burst
+                .iter()
+                .filter(|packet| packet[0] >> 4 == 4 && packet.ends_with(b"burst"))
+                .count();
+            if received == 8 {
+                return burst;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    // The kernel drops them as martians, but the whole batch is written.
+    framed.send(burst).await.unwrap();
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "async_tokio", feature = "async_framed"))]
+#[tokio::test]
+async fn test_framed_pool() {
+    use futures::{SinkExt, StreamExt};
+    use tun_rs::async_framed::{DeviceFramed, PoolCodec};
+    use tun_rs::PacketPool;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.57.100", 24, None)
+        .build_async()
+        .unwrap();
+    let pool = PacketPool::new(2, 1500);
+    let mut framed = DeviceFramed::new(dev, PoolCodec::new(pool.clone()));
+    let socket = tokio::net::UdpSocket::bind("10.26.57.100:0").await.unwrap();
+    socket
+        .send_to(b"pooled", "10.26.57.101:8080")
+        .await
+        .unwrap();
+    let packet = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let packet = framed.next().await.unwrap().unwrap();
+            if packet[0] >> 4 == 4 && packet.ends_with(b"pooled") {
+                return packet;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(pool.available(), 1);
+    let shared = packet.clone();
+    // The kernel drops it as a martian, but it is written.
+    framed.send(packet).await.unwrap();
+    assert_eq!(pool.available(), 1);
+    drop(shared);
+    assert_eq!(pool.available(), 2);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[test]
+fn test_set_mac_address_live() {
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .build_sync()
+        .unwrap();
+    assert!(dev.is_enabled().unwrap());
+    // TAP devices accept a new address while up, so no bounce is needed.
+    let mac = [0x02, 0x00, 0x5e, 0x10, 0x33, 0x69];
+    dev.set_mac_address_live(mac).unwrap();
+    assert_eq!(dev.mac_address().unwrap(), mac);
+    assert!(dev.is_enabled().unwrap());
+
+    let mac = [0x02, 0x00, 0x5e, 0x10, 0x33, 0x70];
+    dev.set_mac_address(mac).unwrap();
+    assert_eq!(dev.mac_address().unwrap(), mac);
+
+    // A TUN device has no hardware address.
+    let tun = DeviceBuilder::new().build_sync().unwrap();
+    assert_eq!(tun.mac_address().unwrap(), [0; 6]);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_recv_uninit() {
+    use std::mem::MaybeUninit;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.32.100", 24, None)
+        .build_sync()
+        .unwrap();
+    dev.set_nonblocking(false).unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.32.100:0").unwrap();
+    // Skips router solicitations and other traffic of the new interface.
+    let is_ours = |packet: &[u8]| packet[0] >> 4 == 4 && packet.ends_with(b"uninit");
+
+    let mut buf = vec![MaybeUninit::<u8>::uninit(); 1500];
+    loop {
+        socket.send_to(b"uninit", "10.26.32.101:8080").unwrap();
+        let len = dev.recv_uninit(&mut buf).unwrap();
+        let packet = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len) };
+        if is_ours(packet) {
+            assert_eq!(u16::from_be_bytes([packet[2], packet[3]]) as usize, len);
+            break;
+        }
+    }
+
+    let (mut header, mut payload) = ([MaybeUninit::uninit(); 20], [MaybeUninit::uninit(); 1480]);
+    loop {
+        socket.send_to(b"uninit", "10.26.32.101:8080").unwrap();
+        let len = dev
+            .recv_vectored_uninit(&mut [&mut header[..], &mut payload[..]])
+            .unwrap();
+        let payload = unsafe {
+            std::slice::from_raw_parts(payload.as_ptr().cast::<u8>(), len.saturating_sub(20))
+        };
+        if unsafe { header[0].assume_init() } >> 4 == 4 && payload.ends_with(b"uninit") {
+            break;
+        }
+    }
+
+    let mut buf = bytes::BytesMut::with_capacity(1500);
+    loop {
+        socket.send_to(b"uninit", "10.26.32.101:8080").unwrap();
+        let len = dev.recv_buf(&mut buf).unwrap();
+        assert_eq!(buf.len(), len);
+        if is_ours(&buf) {
+            break;
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_unpersist() {
+    use std::process::Command;
+
+    const NAME: &str = "tununpersist";
+    let _ = Command::new("ip").args(["link", "delete", NAME]).status();
+    let exists = || {
+        Command::new("ip")
+            .args(["link", "show", NAME])
+            .output()
+            .unwrap()
+            .status
+            .success()
+    };
+
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .persist(true)
+        .build_sync()
+        .unwrap();
+    assert!(dev.is_persistent().unwrap());
+    dev.unpersist().unwrap();
+    assert!(!dev.is_persistent().unwrap());
+    dev.persist().unwrap();
+    drop(dev);
+    assert!(exists());
+
+    // A device left persistent is reclaimed with `persist(false)`.
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .persist(false)
+        .build_sync()
+        .unwrap();
+    assert!(!dev.is_persistent().unwrap());
+    drop(dev);
+    assert!(!exists());
+
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .persist(true)
+        .unpersist_on_drop(true)
+        .build_sync()
+        .unwrap();
+    assert!(dev.is_persistent().unwrap());
+    drop(dev);
+    assert!(!exists());
+
+    // `leak` keeps the device even so.
+    let dev = DeviceBuilder::new()
+        .name(NAME)
+        .persist(true)
+        .unpersist_on_drop(true)
+        .build_sync()
+        .unwrap();
+    dev.leak().unwrap();
+    drop(dev);
+    assert!(exists());
+    drop(
+        DeviceBuilder::new()
+            .name(NAME)
+            .persist(false)
+            .build_sync()
+            .unwrap(),
+    );
+    assert!(!exists());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_attach_tc_program() {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tun_rs::TcDirection;
+
+    // `r0 = TC_ACT_OK; exit`
+    let insns: [u64; 2] = [u64::from_le_bytes([0xb7, 0, 0, 0, 0, 0, 0, 0]), 0x95];
+    let license = c"GPL";
+    let mut attr = [0u32; 32];
+    attr[0] = 3; // BPF_PROG_TYPE_SCHED_CLS
+    attr[1] = insns.len() as u32;
+    attr[2..4].copy_from_slice(&split(insns.as_ptr() as u64));
+    attr[4..6].copy_from_slice(&split(license.as_ptr() as u64));
+    fn split(ptr: u64) -> [u32; 2] {
+        [ptr as u32, (ptr >> 32) as u32]
+    }
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            5, // BPF_PROG_LOAD
+            attr.as_ptr(),
+            std::mem::size_of_val(&attr),
+        )
+    };
+    assert!(fd >= 0, "{}", std::io::Error::last_os_error());
+    let prog = unsafe { OwnedFd::from_raw_fd(fd as _) };
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.33.100", 24, None)
+        .enable(false)
+        .build_sync()
+        .unwrap();
+    dev.attach_tc_program(&prog, TcDirection::Egress).unwrap();
+    // Replaces the program.
+    dev.attach_tc_program(&prog, TcDirection::Egress).unwrap();
+    dev.attach_tc_program(&prog, TcDirection::Ingress).unwrap();
+    dev.enabled(true).unwrap();
+    dev.detach_tc_program(TcDirection::Egress).unwrap();
+    dev.detach_tc_program(TcDirection::Ingress).unwrap();
+    assert!(dev.detach_tc_program(TcDirection::Ingress).is_err());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_address_broadcast_and_flags() {
+    use tun_rs::Ipv6AddressFlags;
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.34.100", 24, None)
+        .ipv4_broadcast("10.26.34.127")
+        .ipv6_with(
+            "fd00:26:34::100",
+            64,
+            Ipv6AddressFlags {
+                nodad: true,
+                no_prefix_route: true,
+                ..Default::default()
+            },
+        )
+        .build_sync()
+        .unwrap();
+    assert_eq!(
+        dev.broadcast().unwrap(),
+        "10.26.34.127".parse::<std::net::IpAddr>().unwrap()
+    );
+    let output = std::process::Command::new("ip")
+        .args(["-6", "addr", "show", "dev", &dev.name().unwrap()])
+        .output()
+        .unwrap();
+    let output = String::from_utf8_lossy(&output.stdout);
+    let line = output
+        .lines()
+        .find(|line| line.contains("fd00:26:34::100/64"))
+        .unwrap();
+    assert!(
+        line.contains("nodad") && line.contains("noprefixroute"),
+        "{line}"
+    );
+
+    let issues = DeviceBuilder::new()
+        .ipv4_broadcast("10.26.34.127")
+        .validate();
+    assert!(matches!(
+        issues[..],
+        [tun_rs::BuilderIssue::Conflict {
+            setting: "ipv4_broadcast",
+            ..
+        }]
+    ));
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_splice() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let a = DeviceBuilder::new()
+        .ipv4("10.26.35.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let b = DeviceBuilder::new()
+        .ipv4("10.26.36.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.35.100:0").unwrap();
+    let done = AtomicBool::new(false);
+    let stats = std::thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                socket.send_to(b"splice", "10.26.35.101:8080").unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+        let stats = tun_rs::splice(&a, &b, 5).unwrap();
+        done.store(true, Ordering::Relaxed);
+        stats
+    });
+    assert_eq!(stats.packets(), 5);
+    assert!(stats.a_to_b > 0, "{stats:?}");
+
+    let offload = DeviceBuilder::new().offload(true).build_sync().unwrap();
+    let err = tun_rs::splice(&a, &offload, 1).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "middleware")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_middleware() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+
+    /// Passes only UDP and swaps the addresses of sent packets.
+    struct UdpOnly(Arc<AtomicUsize>);
+    impl PacketMiddleware for UdpOnly {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            match pnet_packet::ipv4::Ipv4Packet::new(packet) {
+                Some(ipv4) if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp => {
+                    Verdict::Pass
+                }
+                _ => Verdict::Drop,
+            }
+        }
+        fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.len() < 20 {
+                return Verdict::Drop;
+            }
+            // Swapping the addresses keeps both checksums valid.
+            let src: [u8; 4] = packet[12..16].try_into().unwrap();
+            packet.copy_within(16..20, 12);
+            packet[16..20].copy_from_slice(&src);
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Verdict::Pass
+        }
+    }
+
+    let sent = Arc::new(AtomicUsize::new(0));
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.37.100", 24, None)
+        .build_sync()
+        .unwrap()
+        .with_middleware(vec![Box::new(UdpOnly(sent.clone()))]);
+    assert!(dev.has_middleware());
+
+    let socket = std::net::UdpSocket::bind("10.26.37.100:0").unwrap();
+    socket.send_to(b"middleware", "10.26.37.101:9").unwrap();
+    let mut buf = [0; 65535];
+    let len = dev.recv(&mut buf).unwrap();
+    let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]).unwrap();
+    assert_eq!(ipv4.get_next_level_protocol(), IpNextHeaderProtocols::Udp);
+    assert_eq!(&buf[len - 10..len], b"middleware");
+
+    assert_eq!(dev.send(&[0x45; 10]).unwrap(), 10);
+    assert_eq!(sent.load(Ordering::Relaxed), 0);
+    assert_eq!(dev.send(&buf[..len]).unwrap(), len);
+    assert_eq!(sent.load(Ordering::Relaxed), 1);
+
+    dev.set_middleware(Vec::new());
+    assert!(!dev.has_middleware());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "middleware")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_middleware_vectored_and_uninit() {
+    use std::io::{IoSlice, IoSliceMut};
+    use std::mem::MaybeUninit;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+
+    /// Passes only IPv4 UDP and counts the packets sent.
+    struct UdpOnly(Arc<AtomicUsize>);
+    impl PacketMiddleware for UdpOnly {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            match pnet_packet::ipv4::Ipv4Packet::new(packet) {
+                Some(ipv4) if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp => {
+                    Verdict::Pass
+                }
+                _ => Verdict::Drop,
+            }
+        }
+        fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.len() < 20 {
+                return Verdict::Drop;
+            }
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Verdict::Pass
+        }
+    }
+
+    let sent = Arc::new(AtomicUsize::new(0));
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.55.100", 24, None)
+        .build_sync()
+        .unwrap()
+        .with_middleware(vec![Box::new(UdpOnly(sent.clone()))]);
+    let socket = std::net::UdpSocket::bind("10.26.55.100:0").unwrap();
+
+    socket.send_to(b"vectored", "10.26.55.101:9").unwrap();
+    let mut header = [0u8; 20];
+    let mut payload = [0u8; 1500];
+    let len = dev
+        .recv_vectored(&mut [IoSliceMut::new(&mut header), IoSliceMut::new(&mut payload)])
+        .unwrap();
+    let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&header).unwrap();
+    assert_eq!(ipv4.get_next_level_protocol(), IpNextHeaderProtocols::Udp);
+    assert_eq!(&payload[len - 28..len - 20], b"vectored");
+
+    socket.send_to(b"uninit", "10.26.55.101:9").unwrap();
+    let mut buf = [MaybeUninit::<u8>::uninit(); 1500];
+    let len = dev.recv_uninit(&mut buf).unwrap();
+    let packet = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len) };
+    assert_eq!(&packet[len - 6..], b"uninit");
+    let (header, payload) = packet.split_at(20);
+
+    assert_eq!(
+        dev.send_vectored(&[IoSlice::new(&[0x45; 4]), IoSlice::new(&[0x45; 6])])
+            .unwrap(),
+        10
+    );
+    assert_eq!(sent.load(Ordering::Relaxed), 0);
+    assert_eq!(
+        dev.send_vectored(&[IoSlice::new(header), IoSlice::new(payload)])
+            .unwrap(),
+        len
+    );
+    assert_eq!(sent.load(Ordering::Relaxed), 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "middleware", feature = "tokio_uring"))]
+#[test]
+fn test_uring_middleware() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio_uring::buf::BoundedBuf;
+    use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+    use tun_rs::uring::UringDevice;
+
+    /// Passes only IPv4 UDP and counts the packets sent.
+    struct UdpOnly(Arc<AtomicUsize>);
+    impl PacketMiddleware for UdpOnly {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            match pnet_packet::ipv4::Ipv4Packet::new(packet) {
+                Some(ipv4) if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp => {
+                    Verdict::Pass
+                }
+                _ => Verdict::Drop,
+            }
+        }
+        fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.len() < 20 {
+                return Verdict::Drop;
+            }
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Verdict::Pass
+        }
+    }
+
+    tokio_uring::start(async {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let dev = DeviceBuilder::new()
+            .ipv4("10.26.56.100", 24, None)
+            .build_sync()
+            .unwrap()
+            .with_middleware(vec![Box::new(UdpOnly(sent.clone()))]);
+        let dev = UringDevice::new(dev).unwrap();
+        let socket = std::net::UdpSocket::bind("10.26.56.100:0").unwrap();
+
+        socket.send_to(b"uring", "10.26.56.101:9").unwrap();
+        let (res, buf) = dev.recv(vec![0u8; 1500]).await;
+        let len = res.unwrap();
+        let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]).unwrap();
+        assert_eq!(ipv4.get_next_level_protocol(), IpNextHeaderProtocols::Udp);
+        assert_eq!(&buf[len - 5..len], b"uring");
+
+        let (res, _) = dev.send(vec![0x45u8; 10]).await;
+        assert_eq!(res.unwrap(), 10);
+        assert_eq!(sent.load(Ordering::Relaxed), 0);
+        let (res, _) = dev.send(buf.slice(..len)).await;
+        assert_eq!(res.unwrap(), len);
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(all(feature = "middleware", feature = "async_tokio"))]
+#[tokio::test]
+async fn test_middleware_peek() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+
+    /// Counts the UDP packets it sees, dropping the rest.
+    struct CountUdp(Arc<AtomicUsize>);
+    impl PacketMiddleware for CountUdp {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            match pnet_packet::ipv4::Ipv4Packet::new(packet) {
+                Some(ipv4) if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp => {
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                    Verdict::Pass
+                }
+                _ => Verdict::Drop,
+            }
+        }
+    }
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.53.100", 24, None)
+        .build_async()
+        .unwrap()
+        .with_middleware(vec![Box::new(CountUdp(seen.clone()))]);
+    let socket = tokio::net::UdpSocket::bind("10.26.53.100:0").await.unwrap();
+    socket.send_to(b"peek", "10.26.53.101:9").await.unwrap();
+    let mut header = [0u8; 20];
+    tokio::time::timeout(Duration::from_secs(5), dev.peek(&mut header))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(seen.load(Ordering::Relaxed), 1);
+
+    // The peeked packet is not passed through the chain a second time.
+    let mut buf = [0u8; 1500];
+    let len = dev.recv(&mut buf).await.unwrap();
+    assert_eq!(&buf[len - 4..len], b"peek");
+    assert_eq!(seen.load(Ordering::Relaxed), 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "middleware")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_middleware_offload() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tun_rs::middleware::{Packet, PacketMiddleware, Verdict};
+    use tun_rs::{GROTable, IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+
+    /// Counts the packets that start with an IPv4 header, dropping the rest.
+    struct Ipv4Only {
+        received: Arc<AtomicUsize>,
+        sent: Arc<AtomicUsize>,
+    }
+    impl PacketMiddleware for Ipv4Only {
+        fn on_recv(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.first().map(|b| b >> 4) != Some(4) {
+                return Verdict::Drop;
+            }
+            self.received.fetch_add(1, Ordering::Relaxed);
+            Verdict::Pass
+        }
+        fn on_send(&self, packet: &mut Packet<'_>) -> Verdict {
+            if packet.first().map(|b| b >> 4) != Some(4) {
+                return Verdict::Drop;
+            }
+            self.sent.fetch_add(1, Ordering::Relaxed);
+            Verdict::Pass
+        }
+    }
+
+    let received = Arc::new(AtomicUsize::new(0));
+    let sent = Arc::new(AtomicUsize::new(0));
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.49.100", 24, None)
+        .offload(true)
+        .build_sync()
+        .unwrap()
+        .with_middleware(vec![Box::new(Ipv4Only {
+            received: received.clone(),
+            sent: sent.clone(),
+        })]);
+
+    let socket = std::net::UdpSocket::bind("10.26.49.100:0").unwrap();
+    socket.send_to(b"offload", "10.26.49.101:9").unwrap();
+    let mut original_buffer = vec![0; VIRTIO_NET_HDR_LEN + 65535];
+    let mut bufs = vec![vec![0u8; 1500]; IDEAL_BATCH_SIZE];
+    let mut sizes = vec![0; IDEAL_BATCH_SIZE];
+    let (index, len) = 'found: loop {
+        let num = dev
+            .recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0)
+            .unwrap();
+        for i in 0..num {
+            let ipv4 = pnet_packet::ipv4::Ipv4Packet::new(&bufs[i][..sizes[i]]).unwrap();
+            if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp {
+                break 'found (i, sizes[i]);
+            }
+        }
+    };
+    assert!(received.load(Ordering::Relaxed) >= 1);
+    assert_eq!(&bufs[index][len - 7..len], b"offload");
+
+    // Swapping the addresses keeps both checksums valid.
+    let mut packet = bufs[index][..len].to_vec();
+    let src: [u8; 4] = packet[12..16].try_into().unwrap();
+    packet.copy_within(16..20, 12);
+    packet[16..20].copy_from_slice(&src);
+    let mut out = vec![0u8; VIRTIO_NET_HDR_LEN];
+    out.extend_from_slice(&packet);
+    let mut dropped = vec![0u8; VIRTIO_NET_HDR_LEN];
+    dropped.extend_from_slice(&[0x60; 40]);
+    let mut gro_table = GROTable::default();
+    dev.send_multiple(&mut gro_table, &mut [out, dropped], VIRTIO_NET_HDR_LEN)
+        .unwrap();
+    assert_eq!(sent.load(Ordering::Relaxed), 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_recv_with_meta() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.38.100", 24, None)
+        .packet_information(true)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.38.100:0").unwrap();
+    socket.send_to(&[7; 100], "10.26.38.101:9").unwrap();
+    let mut buf = [0; 20];
+    let (len, info) = loop {
+        let (len, info) = dev.recv_with_meta(&mut buf).unwrap();
+        if info.protocol == 0x0800 && buf[9] == 17 {
+            break (len, info);
+        }
+    };
+    assert_eq!(len, 20);
+    assert!(info.truncated);
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.39.100", 24, None)
+        .build_sync()
+        .unwrap();
+    let err = dev.recv_with_meta(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "vhost-net")]
+#[test]
+fn test_vhost_net_rejects_offload() {
+    use tun_rs::vhost_net::VhostNetBuilder;
+    let offload = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    let err = VhostNetBuilder::new().build(&offload).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+}
+
+/// Marked `#[ignore]`: requires the vhost-net module, i.e. `/dev/vhost-net`.
+/// Run with:
+///   cargo test --features vhost-net --test test_dev -- --ignored vhost_net
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "vhost-net")]
+#[test]
+#[ignore = "needs /dev/vhost-net"]
+fn test_vhost_net() {
+    use tun_rs::ethernet::{FrameMeta, ETHERTYPE_ARP};
+    use tun_rs::vhost_net::VhostNetBuilder;
+    let dev = DeviceBuilder::new()
+        .layer(tun_rs::Layer::L2)
+        .ipv4("10.26.43.1", 24, None)
+        .build_sync()
+        .unwrap();
+    let mut vhost = VhostNetBuilder::new().queue_size(64).build(&dev).unwrap();
+    // Resolving the neighbor sends an ARP request out of the device, into the receive ring.
+    let udp = std::net::UdpSocket::bind("10.26.43.1:0").unwrap();
+    udp.send_to(b"vhost", "10.26.43.2:9").unwrap();
+    let mut bufs = vec![vec![0u8; 2048]; 8];
+    let mut sizes = vec![0; 8];
+    let mut found = false;
+    while !found {
+        let n = vhost.recv_batch(&mut bufs, &mut sizes).unwrap();
+        found = (0..n).any(|i| {
+            FrameMeta::parse(&bufs[i][..sizes[i]]).is_some_and(|m| m.ethertype == ETHERTYPE_ARP)
+        });
+    }
+    let mut frame = [0u8; 60];
+    frame[..6].fill(0xff);
+    frame[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+    frame[12..14].copy_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+    assert_eq!(vhost.send_batch(&[&frame[..]]).unwrap(), 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "tunnel")]
+#[test]
+fn test_tunnel() {
+    tunnel_round_trip(false, 44);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "tunnel")]
+#[test]
+fn test_tunnel_offload() {
+    tunnel_round_trip(true, 58);
+}
+
+/// Runs a tunnel on a device in `10.26.<net>.0/24` and passes a packet each way.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "tunnel")]
+fn tunnel_round_trip(offload: bool, net: u8) {
+    use std::net::UdpSocket;
+    use tun_rs::tunnel::Tunnel;
+    let dev = DeviceBuilder::new()
+        .ipv4(format!("10.26.{net}.1"), 24, None)
+        .offload(offload)
+        .build_sync()
+        .unwrap();
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let transport = UdpSocket::bind("127.0.0.1:0").unwrap();
+    transport.connect(peer.local_addr().unwrap()).unwrap();
+    transport
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    peer.connect(transport.local_addr().unwrap()).unwrap();
+    let started = Arc::new(AtomicBool::new(false));
+    let started2 = started.clone();
+    let tunnel =
+        Tunnel::new(dev, transport).on_start(move |_| started2.store(true, Ordering::SeqCst));
+    let app = UdpSocket::bind(format!("10.26.{net}.1:0")).unwrap();
+    std::thread::scope(|scope| {
+        let runner = scope.spawn(|| tunnel.run());
+        // Stops the tunnel even if an assertion fails, so the scope can end.
+        struct StopOnDrop<'a>(&'a Tunnel<UdpSocket>);
+        impl Drop for StopOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.stop();
+            }
+        }
+        let _stop = StopOnDrop(&tunnel);
+        // Outbound: what the host sends into the device comes out of the transport.
+        app.send_to(b"out", format!("10.26.{net}.2:9")).unwrap();
+        let mut buf = [0u8; 2048];
+        let len = loop {
+            let len = peer.recv(&mut buf).unwrap();
+            // The host also sends IPv6 control traffic into the device.
+            if buf[0] >> 4 == 4 {
+                break len;
+            }
+        };
+        assert_eq!(&buf[16..20], &[10, 26, net, 2]);
+        assert_eq!(&buf[len - 3..len], b"out");
+
+        // Inbound: a datagram of the peer is written to the device.
+        let port = app.local_addr().unwrap().port();
+        let mut packet = vec![
+            0x45, 0, 0, 31, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 26, net, 2, 10, 26, net, 1,
+        ];
+        let sum: u32 = packet
+            .chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]) as u32)
+            .sum();
+        let check = !((sum & 0xffff) + (sum >> 16)) as u16;
+        packet[10..12].copy_from_slice(&check.to_be_bytes());
+        packet.extend_from_slice(&9u16.to_be_bytes());
+        packet.extend_from_slice(&port.to_be_bytes());
+        packet.extend_from_slice(&[0, 11, 0, 0]);
+        packet.extend_from_slice(b"in!");
+        peer.send(&packet).unwrap();
+        app.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let len = app.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"in!");
+
+        tunnel.stop();
+        runner.join().unwrap().unwrap();
+    });
+    assert!(started.load(Ordering::SeqCst));
+    let stats = tunnel.stats();
+    assert!(stats.outbound >= 1 && stats.inbound >= 1);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "mss_clamp")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_mss_clamp() {
+    use pnet_packet::tcp::{TcpFlags, TcpOptionNumbers, TcpPacket};
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.45.1", 24, None)
+        .build_sync()
+        .unwrap();
+    assert!(dev.set_mss_clamp(Some(0)).is_err());
+    dev.set_mss_clamp(Some(1200)).unwrap();
+    assert_eq!(dev.mss_clamp(), Some(1200));
+    // The kernel advertises an MSS derived from the device MTU in its SYN.
+    std::thread::spawn(|| {
+        let _ = std::net::TcpStream::connect_timeout(
+            &"10.26.45.2:80".parse().unwrap(),
+            Duration::from_secs(5),
+        );
+    });
+    let mut buf = [0u8; 65535];
+    let syn = loop {
+        let len = dev.recv(&mut buf).unwrap();
+        let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&buf[..len]) else {
+            continue;
+        };
+        if ipv4.get_next_level_protocol() != IpNextHeaderProtocols::Tcp {
+            continue;
+        }
+        let tcp = TcpPacket::new(ipv4.payload()).unwrap();
+        if tcp.get_flags() & TcpFlags::SYN != 0 {
+            break ipv4.payload().to_vec();
+        }
+    };
+    let tcp = TcpPacket::new(&syn).unwrap();
+    let mss = tcp
+        .get_options_iter()
+        .find(|option| option.get_number() == TcpOptionNumbers::MSS)
+        .unwrap();
+    assert_eq!(mss.payload(), &1200u16.to_be_bytes());
+    let checksum = pnet_packet::tcp::ipv4_checksum(
+        &tcp,
+        &"10.26.45.1".parse().unwrap(),
+        &"10.26.45.2".parse().unwrap(),
+    );
+    assert_eq!(checksum, tcp.get_checksum());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "mss_clamp")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_mss_clamp_offload() {
+    use pnet_packet::tcp::{TcpFlags, TcpOptionNumbers, TcpPacket};
+    use tun_rs::{IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.48.1", 24, None)
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    dev.set_mss_clamp(Some(1200)).unwrap();
+    std::thread::spawn(|| {
+        let _ = std::net::TcpStream::connect_timeout(
+            &"10.26.48.2:80".parse().unwrap(),
+            Duration::from_secs(5),
+        );
+    });
+    let mut original_buffer = vec![0; VIRTIO_NET_HDR_LEN + 65535];
+    let mut bufs = vec![vec![0u8; 1500]; IDEAL_BATCH_SIZE];
+    let mut sizes = vec![0; IDEAL_BATCH_SIZE];
+    let syn = 'found: loop {
+        let num = dev
+            .recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0)
+            .unwrap();
+        for i in 0..num {
+            let Some(ipv4) = pnet_packet::ipv4::Ipv4Packet::new(&bufs[i][..sizes[i]]) else {
+                continue;
+            };
+            if ipv4.get_next_level_protocol() != IpNextHeaderProtocols::Tcp {
+                continue;
+            }
+            let tcp = TcpPacket::new(ipv4.payload()).unwrap();
+            if tcp.get_flags() & TcpFlags::SYN != 0 {
+                break 'found ipv4.payload().to_vec();
+            }
+        }
+    };
+    let tcp = TcpPacket::new(&syn).unwrap();
+    let mss = tcp
+        .get_options_iter()
+        .find(|option| option.get_number() == TcpOptionNumbers::MSS)
+        .unwrap();
+    assert_eq!(mss.payload(), &1200u16.to_be_bytes());
+    let checksum = pnet_packet::tcp::ipv4_checksum(
+        &tcp,
+        &"10.26.48.1".parse().unwrap(),
+        &"10.26.48.2".parse().unwrap(),
+    );
+    assert_eq!(checksum, tcp.get_checksum());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "counters")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_counters() {
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.46.1", 24, None)
+        .build_sync()
+        .unwrap();
+    assert_eq!(dev.counters(), Default::default());
+    let socket = std::net::UdpSocket::bind("10.26.46.1:0").unwrap();
+    socket.send_to(b"counted", "10.26.46.2:9").unwrap();
+    let mut buf = [0u8; 65535];
+    let len = loop {
+        let len = dev.recv(&mut buf).unwrap();
+        if buf[0] >> 4 == 4 {
+            break len;
+        }
+    };
+    let counters = dev.counters();
+    assert!(counters.rx_packets >= 1);
+    assert!(counters.rx_bytes >= len as u64);
+    // Send the packet back with the addresses swapped.
+    let mut packet = buf[..len].to_vec();
+    let (src, dst) = packet[12..20].split_at_mut(4);
+    src.swap_with_slice(dst);
+    dev.send(&packet).unwrap();
+    let counters = dev.counters();
+    assert_eq!(counters.tx_packets, 1);
+    assert_eq!(counters.tx_bytes, len as u64);
+    assert_eq!(counters.tx_errors, 0);
+    // Not a valid IP packet.
+    assert!(dev.send(&[0u8; 4]).is_err());
+    assert_eq!(dev.counters().tx_errors, 1);
+    dev.reset_counters();
+    assert_eq!(dev.counters(), Default::default());
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg(feature = "counters")]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_counters_offload() {
+    use tun_rs::{GROTable, IDEAL_BATCH_SIZE, VIRTIO_NET_HDR_LEN};
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.52.1", 24, None)
+        .offload(true)
+        .build_sync()
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.52.1:0").unwrap();
+    socket.send_to(b"counted", "10.26.52.2:9").unwrap();
+    let mut original_buffer = vec![0; VIRTIO_NET_HDR_LEN + 65535];
+    let mut bufs = vec![vec![0u8; 1500]; IDEAL_BATCH_SIZE];
+    let mut sizes = vec![0; IDEAL_BATCH_SIZE];
+    let mut received = 0;
+    let packet = 'found: loop {
+        let num = dev
+            .recv_multiple(&mut original_buffer, &mut bufs, &mut sizes, 0)
+            .unwrap();
+        for i in 0..num {
+            received += sizes[i] as u64;
+            if bufs[i][0] >> 4 == 4 && bufs[i][9] == 17 {
+                break 'found bufs[i][..sizes[i]].to_vec();
+            }
+        }
+    };
+    // The virtio network header is not counted.
+    let counters = dev.counters();
+    assert_eq!(counters.rx_bytes, received);
+
+    let mut out = vec![0u8; VIRTIO_NET_HDR_LEN];
+    out.extend_from_slice(&packet);
+    let (src, dst) = out[VIRTIO_NET_HDR_LEN + 12..VIRTIO_NET_HDR_LEN + 20].split_at_mut(4);
+    src.swap_with_slice(dst);
+    let mut gro_table = GROTable::default();
+    dev.send_multiple(&mut gro_table, &mut [out], VIRTIO_NET_HDR_LEN)
+        .unwrap();
+    let counters = dev.counters();
+    assert_eq!(counters.tx_packets, 1);
+    assert_eq!(counters.tx_bytes, packet.len() as u64);
+    assert_eq!(counters.tx_errors, 0);
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos"), feature = "ebpf"))]
+#[cfg(not(any(feature = "async_tokio", feature = "async_io")))]
+#[test]
+fn test_steering_ebpf() {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    // `r0 = 1; exit`: every packet goes to the second queue.
+    let insns: [u64; 2] = [u64::from_le_bytes([0xb7, 0, 0, 0, 1, 0, 0, 0]), 0x95];
+    let license = c"GPL";
+    let mut attr = [0u32; 32];
+    attr[0] = 1; // BPF_PROG_TYPE_SOCKET_FILTER
+    attr[1] = insns.len() as u32;
+    attr[2..4].copy_from_slice(&split(insns.as_ptr() as u64));
+    attr[4..6].copy_from_slice(&split(license.as_ptr() as u64));
+    fn split(ptr: u64) -> [u32; 2] {
+        [ptr as u32, (ptr >> 32) as u32]
+    }
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            5, // BPF_PROG_LOAD
+            attr.as_ptr(),
+            std::mem::size_of_val(&attr),
+        )
+    };
+    assert!(fd >= 0, "{}", std::io::Error::last_os_error());
+    let prog = unsafe { OwnedFd::from_raw_fd(fd as _) };
+
+    let dev = DeviceBuilder::new()
+        .ipv4("10.26.47.1", 24, None)
+        .multi_queue(true)
+        .steering_ebpf(prog.try_clone().unwrap())
+        .build_sync()
+        .unwrap();
+    let second = dev.try_clone().unwrap();
+    second
+        .set_nonblocking(true)
+        .and_then(|_| dev.set_nonblocking(true))
+        .unwrap();
+    let socket = std::net::UdpSocket::bind("10.26.47.1:0").unwrap();
+    socket.send_to(b"steered", "10.26.47.2:9").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    let mut buf = [0u8; 65535];
+    let mut steered = false;
+    while let Ok(len) = second.recv(&mut buf) {
+        steered |= buf[..len].ends_with(b"steered");
+    }
+    assert!(steered);
+    while let Ok(len) = dev.recv(&mut buf) {
+        assert!(!buf[..len].ends_with(b"steered"));
+    }
+
+    dev.set_steering_ebpf(&prog).unwrap();
+    dev.clear_steering_ebpf().unwrap();
+}